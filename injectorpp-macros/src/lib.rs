@@ -7,6 +7,8 @@ use syn::{Expr, Token, Type, parenthesized, punctuated::Punctuated};
 struct FuncInput {
     func_expr: Expr,
     arg_types: Vec<Type>,
+    /// Whether the argument types end with `...`, for variadic C functions.
+    variadic: bool,
     return_type: Option<Type>,
     is_unsafe: bool,
     extern_abi: Option<String>,
@@ -57,8 +59,20 @@ impl Parse for FuncInput {
         // Parse "( arg_types )"
         let types_content;
         parenthesized!(types_content in input);
-        let arg_types: Punctuated<Type, Token![,]> =
-            Punctuated::parse_terminated(&types_content)?;
+        let mut arg_types = Vec::new();
+        let mut variadic = false;
+        while !types_content.is_empty() {
+            if types_content.peek(Token![...]) {
+                let _: Token![...] = types_content.parse()?;
+                variadic = true;
+                break;
+            }
+            arg_types.push(types_content.parse::<Type>()?);
+            if types_content.is_empty() {
+                break;
+            }
+            let _: Token![,] = types_content.parse()?;
+        }
 
         // Parse optional "-> return_type"
         let return_type = if input.peek(Token![->]) {
@@ -70,7 +84,8 @@ impl Parse for FuncInput {
 
         Ok(FuncInput {
             func_expr,
-            arg_types: arg_types.into_iter().collect(),
+            arg_types,
+            variadic,
             return_type,
             is_unsafe,
             extern_abi,
@@ -105,48 +120,60 @@ pub fn func_checked(input: TokenStream) -> TokenStream {
     };
 
     let func_expr = &parsed.func_expr;
-    let arg_types = &parsed.arg_types;
+    let types = &parsed.arg_types;
+    let arg_types = if parsed.variadic {
+        quote! { #(#types,)* ... }
+    } else {
+        quote! { #(#types),* }
+    };
 
     // Build the function pointer type
     let fn_type = match (&parsed.return_type, parsed.is_unsafe, &parsed.extern_abi) {
-        (Some(ret), false, None) => quote! { fn(#(#arg_types),*) -> #ret },
-        (None, false, None) => quote! { fn(#(#arg_types),*) },
-        (Some(ret), true, None) => quote! { unsafe fn(#(#arg_types),*) -> #ret },
-        (None, true, None) => quote! { unsafe fn(#(#arg_types),*) -> () },
+        (Some(ret), false, None) => quote! { fn(#arg_types) -> #ret },
+        (None, false, None) => quote! { fn(#arg_types) },
+        (Some(ret), true, None) => quote! { unsafe fn(#arg_types) -> #ret },
+        (None, true, None) => quote! { unsafe fn(#arg_types) -> () },
         (Some(ret), true, Some(abi)) => {
             let abi_lit = syn::LitStr::new(abi, proc_macro2::Span::call_site());
-            quote! { unsafe extern #abi_lit fn(#(#arg_types),*) -> #ret }
+            quote! { unsafe extern #abi_lit fn(#arg_types) -> #ret }
         }
         (None, true, Some(abi)) => {
             let abi_lit = syn::LitStr::new(abi, proc_macro2::Span::call_site());
-            quote! { unsafe extern #abi_lit fn(#(#arg_types),*) -> () }
+            quote! { unsafe extern #abi_lit fn(#arg_types) -> () }
         }
         (Some(ret), false, Some(abi)) => {
             let abi_lit = syn::LitStr::new(abi, proc_macro2::Span::call_site());
-            quote! { extern #abi_lit fn(#(#arg_types),*) -> #ret }
+            quote! { extern #abi_lit fn(#arg_types) -> #ret }
         }
         (None, false, Some(abi)) => {
             let abi_lit = syn::LitStr::new(abi, proc_macro2::Span::call_site());
-            quote! { extern #abi_lit fn(#(#arg_types),*) }
+            quote! { extern #abi_lit fn(#arg_types) }
         }
     };
 
+    // The return type and calling convention, to check that fakes return the same way
+    let ret_type = match &parsed.return_type {
+        Some(ret) => quote! { #ret },
+        None => quote! { () },
+    };
+    let abi = parsed.extern_abi.as_deref().unwrap_or("Rust");
+
     // Generate the lifetime invariance check for bare reference returns.
     // This only applies to non-unsafe functions (unsafe/extern functions
     // typically don't have Rust lifetime semantics).
-    let lifetime_check = if !parsed.is_unsafe {
+    let lifetime_check = if !parsed.is_unsafe && !parsed.variadic {
         if let Some(ref ret) = parsed.return_type {
             if is_bare_reference(ret) {
                 quote! {
                     {
                         fn __injpp_check_ret<__R>(
-                            _f: fn(#(#arg_types),*) -> __R,
-                        ) -> fn(#(#arg_types),*) -> __R {
+                            _f: fn(#arg_types) -> __R,
+                        ) -> fn(#arg_types) -> __R {
                             _f
                         }
                         fn __injpp_eq<__T>(_: &mut __T, _: &mut __T) {}
                         let mut __a = __injpp_check_ret(#func_expr);
-                        let mut __b: fn(#(#arg_types),*) -> #ret = #func_expr;
+                        let mut __b: fn(#arg_types) -> #ret = #func_expr;
                         __injpp_eq(&mut __a, &mut __b);
                     }
                 }
@@ -164,14 +191,913 @@ pub fn func_checked(input: TokenStream) -> TokenStream {
         {
             #lifetime_check
             {
-                let fn_val: #fn_type = #func_expr;
+                let (fn_val, item): (#fn_type, &'static str) = {
+                    let item = #func_expr;
+                    (item, std::any::type_name_of_val(&item))
+                };
                 let ptr = fn_val as *const ();
                 let sig = std::any::type_name_of_val(&fn_val);
                 let type_id = std::any::TypeId::of::<#fn_type>();
                 unsafe { FuncPtr::new_with_type_id(ptr, sig, type_id) }
+                    .__returning::<#ret_type>(#abi)
+                    .__item(item)
+            }
+        }
+    };
+
+    output.into()
+}
+
+/// Parsed input for `when_report!`: `$crate; condition; arg, arg, ...`.
+struct WhenReportInput {
+    krate: proc_macro2::TokenStream,
+    cond: Expr,
+    args: Vec<syn::Ident>,
+}
+
+impl Parse for WhenReportInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        // The crate path is forwarded as raw tokens since `$crate` cannot be
+        // parsed as a regular path by syn.
+        let mut krate = proc_macro2::TokenStream::new();
+        while !input.peek(Token![;]) {
+            let tt: proc_macro2::TokenTree = input.parse()?;
+            krate.extend(std::iter::once(tt));
+        }
+        let _: Token![;] = input.parse()?;
+
+        let cond: Expr = input.parse()?;
+        let _: Token![;] = input.parse()?;
+
+        let args: Punctuated<syn::Ident, Token![,]> = Punctuated::parse_terminated(input)?;
+
+        Ok(WhenReportInput {
+            krate,
+            cond,
+            args: args.into_iter().collect(),
+        })
+    }
+}
+
+/// Find the parameter an expression is rooted at, e.g. `path`, `*path`,
+/// `path.len()` or `data[0]` are all rooted at the parameter of the same name.
+fn root_param(expr: &Expr, params: &[syn::Ident]) -> Option<syn::Ident> {
+    match expr {
+        Expr::Path(p) if p.qself.is_none() && p.path.segments.len() == 1 => {
+            let ident = &p.path.segments[0].ident;
+            params.iter().find(|param| *param == ident).cloned()
+        }
+        Expr::Unary(u) if matches!(u.op, syn::UnOp::Deref(_)) => root_param(&u.expr, params),
+        Expr::Reference(r) => root_param(&r.expr, params),
+        Expr::Paren(p) => root_param(&p.expr, params),
+        Expr::Group(g) => root_param(&g.expr, params),
+        Expr::Field(f) => root_param(&f.base, params),
+        Expr::MethodCall(m) => root_param(&m.receiver, params),
+        Expr::Index(i) => root_param(&i.expr, params),
+        Expr::Cast(c) => root_param(&c.expr, params),
+        _ => None,
+    }
+}
+
+/// Mirror a comparison operator so that `5 < x` can be reported as `x > 5`.
+fn flip_op(op: &syn::BinOp) -> syn::BinOp {
+    match op {
+        syn::BinOp::Lt(t) => syn::BinOp::Gt(Token![>](t.spans)),
+        syn::BinOp::Le(t) => syn::BinOp::Ge(Token![>=](t.spans)),
+        syn::BinOp::Gt(t) => syn::BinOp::Lt(Token![<](t.spans)),
+        syn::BinOp::Ge(t) => syn::BinOp::Le(Token![<=](t.spans)),
+        other => *other,
+    }
+}
+
+/// Collect the parameters that one side of a comparison in a `when:` condition
+/// is rooted at, which are reported by `check_comparison`.
+fn collect_compared_params(expr: &Expr, params: &[syn::Ident], out: &mut Vec<syn::Ident>) {
+    match expr {
+        Expr::Binary(b) => match b.op {
+            syn::BinOp::And(_) | syn::BinOp::Or(_) => {
+                collect_compared_params(&b.left, params, out);
+                collect_compared_params(&b.right, params, out);
+            }
+            syn::BinOp::Eq(_)
+            | syn::BinOp::Ne(_)
+            | syn::BinOp::Lt(_)
+            | syn::BinOp::Le(_)
+            | syn::BinOp::Gt(_)
+            | syn::BinOp::Ge(_) => {
+                if let Some(param) =
+                    root_param(&b.left, params).or_else(|| root_param(&b.right, params))
+                {
+                    out.push(param);
+                }
+            }
+            _ => {}
+        },
+        Expr::Paren(p) => collect_compared_params(&p.expr, params, out),
+        Expr::Group(g) => collect_compared_params(&g.expr, params, out),
+        Expr::Unary(u) if matches!(u.op, syn::UnOp::Not(_)) => {
+            collect_compared_params(&u.expr, params, out)
+        }
+        _ => {}
+    }
+}
+
+/// Render tokens roughly the way a user would have written them.
+fn pretty_tokens(tokens: &proc_macro2::TokenStream) -> String {
+    let mut s = tokens.to_string();
+    for (from, to) in [
+        (" . ", "."),
+        (" (", "("),
+        ("( ", "("),
+        (" )", ")"),
+        (" [", "["),
+        ("[ ", "["),
+        (" ]", "]"),
+        (" ,", ","),
+        (":: ", "::"),
+        (" ::", "::"),
+    ] {
+        s = s.replace(from, to);
+    }
+    // Prefix operators are only collapsed where they cannot be binary ones.
+    for op in ["&", "*", "!"] {
+        s = s.replace(&format!("({} ", op), &format!("({}", op));
+        if let Some(rest) = s.strip_prefix(&format!("{} ", op)) {
+            s = format!("{}{}", op, rest);
+        }
+    }
+    s
+}
+
+/// Proc macro that evaluates a `when:` condition of `fake!` and builds the
+/// diagnostic text appended to the panic message when it does not hold.
+///
+/// Expands to a `(bool, String)` of the condition and the report. Every
+/// comparison in the condition whose one side is rooted at a parameter is
+/// reported as an expected-vs-actual pair, formatted from its operands as the
+/// condition evaluates them, so each operand is evaluated once and may move its
+/// arguments. Operands skipped by `&&` or `||` are not evaluated, only their
+/// source text is reported. Parameters that are not part of any comparison are
+/// reported with their actual value only, described before the condition runs
+/// when the condition mentions them. Values are formatted with `Debug` when
+/// the type implements it.
+#[doc(hidden)]
+#[proc_macro]
+pub fn when_report(input: TokenStream) -> TokenStream {
+    let parsed = match syn::parse::<WhenReportInput>(input) {
+        Ok(parsed) => parsed,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    let krate = &parsed.krate;
+    let mut compared = Vec::new();
+    collect_compared_params(&parsed.cond, &parsed.args, &mut compared);
+
+    let cond = &parsed.cond;
+    let cond_text = pretty_tokens(&quote! { #cond }).replace(RECEIVER_NAME, "self");
+    let checked = check_condition(cond, &parsed.args);
+
+    let uncovered: Vec<(usize, &syn::Ident)> = parsed
+        .args
+        .iter()
+        .filter(|arg| !compared.contains(arg))
+        .enumerate()
+        .collect();
+
+    // The condition may move the parameters it mentions, so they are described first.
+    let mentioned = |arg: &syn::Ident| mentions(quote! { #cond }, arg);
+    let described_before =
+        uncovered
+            .iter()
+            .filter(|(_, arg)| mentioned(arg))
+            .map(|(index, arg)| {
+                let var = quote::format_ident!("__injpp_arg_{}", index);
+                quote! {
+                    let #var = (&__DebugProbe(&(#arg))).__injectorpp_describe();
+                }
+            });
+
+    let uncovered_lines = uncovered.iter().map(|(index, arg)| {
+        let name = arg.to_string().replace(RECEIVER_NAME, "self");
+        let description = if mentioned(arg) {
+            let var = quote::format_ident!("__injpp_arg_{}", index);
+            quote! { #var }
+        } else {
+            quote! { (&__DebugProbe(&(#arg))).__injectorpp_describe() }
+        };
+        quote! {
+            __injpp_report.push_str(&::std::format!("\n  {}: got {}", #name, #description));
+        }
+    });
+
+    let output = quote! {
+        {
+            #[allow(unused_imports)]
+            use #krate::interface::injector::{__DebugProbe, __ProbeDebug as _, __ProbeFallback as _};
+            #[allow(unused_mut)]
+            let mut __injpp_report = ::std::format!("\n  when: {}", #cond_text);
+            #(#described_before)*
+            let __injpp_matched: bool = #checked;
+            if !__injpp_matched {
+                #(#uncovered_lines)*
             }
+            (__injpp_matched, __injpp_report)
         }
     };
 
     output.into()
 }
+
+/// Returns whether `ident` occurs anywhere in `tokens`.
+fn mentions(tokens: proc_macro2::TokenStream, ident: &syn::Ident) -> bool {
+    tokens.into_iter().any(|tt| match tt {
+        proc_macro2::TokenTree::Ident(i) => i == *ident,
+        proc_macro2::TokenTree::Group(g) => mentions(g.stream(), ident),
+        _ => false,
+    })
+}
+
+/// Rewrites a `when:` condition so that the comparisons with a side rooted at a
+/// parameter record their operands in `__injpp_report` as they are evaluated.
+///
+/// `&&` and `||` still short-circuit, as the skipped side may only be valid
+/// when the other side holds, such as an index checked against a length. The
+/// skipped side is not evaluated, it is reported by its source text.
+fn check_condition(expr: &Expr, params: &[syn::Ident]) -> proc_macro2::TokenStream {
+    match expr {
+        Expr::Binary(b) if matches!(b.op, syn::BinOp::And(_)) => {
+            let left = check_condition(&b.left, params);
+            let right = check_condition(&b.right, params);
+            let skipped = report_skipped(&b.right);
+            quote! { (if #left { #right } else { #skipped false }) }
+        }
+        Expr::Binary(b) if matches!(b.op, syn::BinOp::Or(_)) => {
+            let left = check_condition(&b.left, params);
+            let right = check_condition(&b.right, params);
+            let skipped = report_skipped(&b.right);
+            quote! { (if #left { #skipped true } else { #right }) }
+        }
+        Expr::Binary(b) => check_comparison(b, params).unwrap_or_else(|| quote! { (#expr) }),
+        Expr::Paren(p) => check_condition(&p.expr, params),
+        Expr::Group(g) => check_condition(&g.expr, params),
+        Expr::Unary(u) if matches!(u.op, syn::UnOp::Not(_)) => {
+            let inner = check_condition(&u.expr, params);
+            quote! { (!#inner) }
+        }
+        _ => quote! { (#expr) },
+    }
+}
+
+/// Records in the report the source text of a part of a `when:` condition that
+/// `&&` or `||` skipped, without evaluating it.
+fn report_skipped(expr: &Expr) -> proc_macro2::TokenStream {
+    let text = pretty_tokens(&quote! { #expr }).replace(RECEIVER_NAME, "self");
+    quote! {
+        __injpp_report.push_str(&::std::format!("\n  {}: not evaluated", #text));
+    }
+}
+
+/// Evaluates `left op right`, when it is a comparison with one side rooted at a
+/// parameter, and records both operands in the report.
+///
+/// The operands are evaluated once, left to right, and compared and described
+/// by reference, so the way `assert_eq!` does, temporaries live until the
+/// report is written.
+fn check_comparison(
+    b: &syn::ExprBinary,
+    params: &[syn::Ident],
+) -> Option<proc_macro2::TokenStream> {
+    if !matches!(
+        b.op,
+        syn::BinOp::Eq(_)
+            | syn::BinOp::Ne(_)
+            | syn::BinOp::Lt(_)
+            | syn::BinOp::Le(_)
+            | syn::BinOp::Gt(_)
+            | syn::BinOp::Ge(_)
+    ) {
+        return None;
+    }
+
+    let left_var = quote! { __injpp_left };
+    let right_var = quote! { __injpp_right };
+    let (actual, actual_var, expected_var, shown_op) = if root_param(&b.left, params).is_some() {
+        (&b.left, &left_var, &right_var, b.op)
+    } else if root_param(&b.right, params).is_some() {
+        (&b.right, &right_var, &left_var, flip_op(&b.op))
+    } else {
+        return None;
+    };
+
+    let label = pretty_tokens(&quote! { #actual }).replace(RECEIVER_NAME, "self");
+    let prefix = match shown_op {
+        syn::BinOp::Eq(_) => String::new(),
+        other => format!("{} ", quote! { #other }),
+    };
+    let left = &b.left;
+    let right = &b.right;
+    let op = &b.op;
+    Some(quote! {
+        (match (&(#left), &(#right)) {
+            (#left_var, #right_var) => {
+                let __injpp_matched = *#left_var #op *#right_var;
+                __injpp_report.push_str(&::std::format!(
+                    "\n  {}: expected {}{}, got {}{}",
+                    #label,
+                    #prefix,
+                    (&__DebugProbe(#expected_var)).__injectorpp_describe(),
+                    (&__DebugProbe(#actual_var)).__injectorpp_describe(),
+                    if __injpp_matched { "" } else { " (mismatch)" },
+                ));
+                __injpp_matched
+            }
+        })
+    })
+}
+
+/// The name a `self` receiver of a `fake!` is renamed to, as fakes are free functions.
+const RECEIVER_NAME: &str = "__self";
+
+/// Renames every `self` in `tokens` to `RECEIVER_NAME`, except in paths such as `self::item`.
+fn rename_self(tokens: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+    let tokens: Vec<proc_macro2::TokenTree> = tokens.into_iter().collect();
+    let mut out = proc_macro2::TokenStream::new();
+    for (i, tt) in tokens.iter().enumerate() {
+        let renamed = match tt {
+            proc_macro2::TokenTree::Ident(ident) if ident == "self" => {
+                let is_path = matches!(
+                    tokens.get(i + 1),
+                    Some(proc_macro2::TokenTree::Punct(p)) if p.as_char() == ':' && p.spacing() == proc_macro2::Spacing::Joint
+                );
+                if is_path {
+                    tt.clone()
+                } else {
+                    // The `self` in the signature comes from `fake!` itself, so every renamed
+                    // receiver resolves at the call site to find the same binding.
+                    let span = proc_macro2::Span::call_site().located_at(ident.span());
+                    proc_macro2::Ident::new(RECEIVER_NAME, span).into()
+                }
+            }
+            proc_macro2::TokenTree::Group(group) => {
+                let mut renamed =
+                    proc_macro2::Group::new(group.delimiter(), rename_self(group.stream()));
+                renamed.set_span(group.span());
+                renamed.into()
+            }
+            other => other.clone(),
+        };
+        out.extend(std::iter::once(renamed));
+    }
+    out
+}
+
+/// Proc macro behind `fake!` for methods whose first parameter is written as a `self`
+/// receiver, e.g. `fn(self: Box<Client>, id: u32) -> String`.
+///
+/// The receiver is renamed in the signature and in `when`, `assign` and `returns`, and the
+/// renamed arguments are passed back to `fake!`.
+#[doc(hidden)]
+#[proc_macro]
+pub fn fake_receiver(input: TokenStream) -> TokenStream {
+    let input = proc_macro2::TokenStream::from(input);
+    let mut tokens = input.into_iter();
+    let mut krate = proc_macro2::TokenStream::new();
+    for tt in tokens.by_ref() {
+        if matches!(&tt, proc_macro2::TokenTree::Punct(p) if p.as_char() == ';') {
+            break;
+        }
+        krate.extend(std::iter::once(tt));
+    }
+
+    let args = rename_self(tokens.collect());
+
+    // A `Box<Self>` receiver becomes a boxed argument of the fake.
+    quote! {
+        {
+            #[allow(clippy::boxed_local)]
+            let fake = #krate::fake!(#args);
+            fake
+        }
+    }
+    .into()
+}
+
+/// A function `#[mockable]` generates a `mock_*` helper for.
+struct Mockable {
+    /// The helper name, e.g. `mock_fs_create_dir_all`.
+    helper: syn::Ident,
+    vis: syn::Visibility,
+    /// The path of the function as seen from the helper.
+    path: proc_macro2::TokenStream,
+    arg_types: Vec<proc_macro2::TokenStream>,
+    return_type: Option<proc_macro2::TokenStream>,
+}
+
+/// Generates a typed fake builder, `mock_<name>(&mut injector)`, for each function of the
+/// annotated function, inline module or impl block.
+///
+/// Inside a module, functions visible outside it get `mock_<module>_<name>`. In an impl
+/// block, methods get `mock_<type>_<name>`. Generic, `async`, `unsafe` and `extern`
+/// functions, and functions returning borrowed data, are skipped.
+#[proc_macro_attribute]
+pub fn mockable(attr: TokenStream, item: TokenStream) -> TokenStream {
+    if !attr.is_empty() {
+        return syn::Error::new(
+            proc_macro2::Span::call_site(),
+            "#[mockable] does not take arguments",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    let parsed = match syn::parse::<syn::Item>(item.clone()) {
+        Ok(parsed) => parsed,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    let mut mockables = Vec::new();
+    match &parsed {
+        syn::Item::Fn(item_fn) => {
+            let name = &item_fn.sig.ident;
+            collect_mockable(
+                &item_fn.sig,
+                &item_fn.vis,
+                "",
+                quote! { #name },
+                None,
+                &mut mockables,
+            );
+        }
+        syn::Item::Mod(item_mod) => {
+            let Some((_, items)) = &item_mod.content else {
+                return syn::Error::new_spanned(
+                    item_mod,
+                    "#[mockable] needs an inline module, `mod name { ... }`",
+                )
+                .to_compile_error()
+                .into();
+            };
+
+            let module = &item_mod.ident;
+            let prefix = format!("{}_", module);
+            for item in items {
+                match item {
+                    syn::Item::Fn(item_fn)
+                        if !matches!(item_fn.vis, syn::Visibility::Inherited) =>
+                    {
+                        let name = &item_fn.sig.ident;
+                        collect_mockable(
+                            &item_fn.sig,
+                            &item_mod.vis,
+                            &prefix,
+                            quote! { #module::#name },
+                            None,
+                            &mut mockables,
+                        );
+                    }
+                    syn::Item::Impl(item_impl)
+                        if item_impl.trait_.is_none() && is_public_type(items, item_impl) =>
+                    {
+                        collect_impl(
+                            item_impl,
+                            &item_mod.vis,
+                            &prefix,
+                            quote! { #module:: },
+                            true,
+                            &mut mockables,
+                        )
+                    }
+                    _ => {}
+                }
+            }
+        }
+        syn::Item::Impl(item_impl) => collect_impl(
+            item_impl,
+            &syn::Visibility::Inherited,
+            "",
+            quote! {},
+            false,
+            &mut mockables,
+        ),
+        other => {
+            return syn::Error::new_spanned(
+                other,
+                "#[mockable] applies to a function, an inline module or an impl block",
+            )
+            .to_compile_error()
+            .into();
+        }
+    }
+
+    let helpers = mockables.iter().map(mock_helper);
+    let item = proc_macro2::TokenStream::from(item);
+    quote! {
+        #item
+        #(#helpers)*
+    }
+    .into()
+}
+
+/// Marks functions as not to be faked: `when_called()` refuses to fake them unless the injector
+/// opts in with the unsafe `ignore_do_not_fake()`.
+///
+/// Applies to a function, an impl block or an inline module, marking every non-generic
+/// function of it. The addresses of the marked functions are stored in a link section read by
+/// injectorpp, so the crate must depend on injectorpp outside of tests too.
+#[proc_macro_attribute]
+pub fn do_not_fake(attr: TokenStream, item: TokenStream) -> TokenStream {
+    if !attr.is_empty() {
+        return syn::Error::new(
+            proc_macro2::Span::call_site(),
+            "#[do_not_fake] does not take arguments",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    let mut parsed = match syn::parse::<syn::Item>(item) {
+        Ok(parsed) => parsed,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    let mut paths = Vec::new();
+    match &mut parsed {
+        syn::Item::Fn(item_fn) => {
+            if !item_fn.sig.generics.params.is_empty() {
+                return syn::Error::new_spanned(
+                    &item_fn.sig,
+                    "#[do_not_fake] cannot mark generic functions",
+                )
+                .to_compile_error()
+                .into();
+            }
+            let name = &item_fn.sig.ident;
+            paths.push(quote! { #name });
+        }
+        syn::Item::Impl(item_impl) => collect_impl_paths(item_impl, &mut paths),
+        syn::Item::Mod(item_mod) => {
+            let Some((_, items)) = &mut item_mod.content else {
+                return syn::Error::new_spanned(
+                    &*item_mod,
+                    "#[do_not_fake] needs an inline module, `mod name { ... }`",
+                )
+                .to_compile_error()
+                .into();
+            };
+
+            let mut module_paths = Vec::new();
+            for item in items.iter() {
+                match item {
+                    syn::Item::Fn(item_fn) if item_fn.sig.generics.params.is_empty() => {
+                        let name = &item_fn.sig.ident;
+                        module_paths.push(quote! { #name });
+                    }
+                    syn::Item::Impl(item_impl) => collect_impl_paths(item_impl, &mut module_paths),
+                    _ => {}
+                }
+            }
+            items.push(syn::Item::Verbatim(do_not_fake_markers(&module_paths)));
+        }
+        other => {
+            return syn::Error::new_spanned(
+                other,
+                "#[do_not_fake] applies to a function, an impl block or an inline module",
+            )
+            .to_compile_error()
+            .into();
+        }
+    }
+
+    let markers = do_not_fake_markers(&paths);
+    quote! {
+        #parsed
+        #markers
+    }
+    .into()
+}
+
+/// Collects the paths of the non-generic methods of a non-generic impl block.
+fn collect_impl_paths(item_impl: &syn::ItemImpl, paths: &mut Vec<proc_macro2::TokenStream>) {
+    if !item_impl.generics.params.is_empty() {
+        return;
+    }
+
+    let self_ty = &item_impl.self_ty;
+    let qualified = match &item_impl.trait_ {
+        Some((_, trait_path, _)) => quote! { <#self_ty as #trait_path> },
+        None => quote! { <#self_ty> },
+    };
+    for item in &item_impl.items {
+        if let syn::ImplItem::Fn(method) = item {
+            if method.sig.generics.params.is_empty() {
+                let name = &method.sig.ident;
+                paths.push(quote! { #qualified::#name });
+            }
+        }
+    }
+}
+
+/// Generates the link section entries marking the functions at `paths`.
+fn do_not_fake_markers(paths: &[proc_macro2::TokenStream]) -> proc_macro2::TokenStream {
+    if paths.is_empty() {
+        return quote! {};
+    }
+
+    // The entries bring their own pointer wrapper, laid out like the entries injectorpp reads,
+    // so the expansion names no path into injectorpp and works however the crate is renamed.
+    let count = paths.len();
+    quote! {
+        const _: () = {
+            #[repr(transparent)]
+            struct Marker(*const ());
+
+            // Safety: the pointer is only compared, never dereferenced.
+            unsafe impl Sync for Marker {}
+
+            #[used]
+            #[cfg_attr(
+                any(
+                    target_os = "linux",
+                    target_os = "android",
+                    target_os = "freebsd",
+                    target_os = "netbsd"
+                ),
+                link_section = "injectorpp_do_not_fake"
+            )]
+            #[cfg_attr(
+                target_vendor = "apple",
+                link_section = "__DATA,__injpp_nofake,regular,no_dead_strip"
+            )]
+            #[cfg_attr(windows, link_section = ".injpp$m")]
+            static MARKERS: [Marker; #count] = [#(Marker(#paths as *const ())),*];
+        };
+    }
+}
+
+/// Returns whether the type of an inherent impl block is visible outside the module holding
+/// both. Types declared elsewhere are assumed to be.
+fn is_public_type(items: &[syn::Item], item_impl: &syn::ItemImpl) -> bool {
+    let syn::Type::Path(type_path) = &*item_impl.self_ty else {
+        return false;
+    };
+    let Some(name) = type_path.path.get_ident() else {
+        return false;
+    };
+
+    items.iter().all(|item| match item {
+        syn::Item::Struct(item) if item.ident == *name => {
+            !matches!(item.vis, syn::Visibility::Inherited)
+        }
+        syn::Item::Enum(item) if item.ident == *name => {
+            !matches!(item.vis, syn::Visibility::Inherited)
+        }
+        _ => true,
+    })
+}
+
+/// Collects the methods of an impl block. Methods of impl blocks inside a module are only
+/// collected when visible outside it, and then get the module's visibility.
+fn collect_impl(
+    item_impl: &syn::ItemImpl,
+    module_vis: &syn::Visibility,
+    prefix: &str,
+    module_path: proc_macro2::TokenStream,
+    in_module: bool,
+    mockables: &mut Vec<Mockable>,
+) {
+    if !item_impl.generics.params.is_empty() {
+        return;
+    }
+
+    let syn::Type::Path(type_path) = &*item_impl.self_ty else {
+        return;
+    };
+    let Some(type_name) = type_path.path.segments.last() else {
+        return;
+    };
+    if !matches!(type_name.arguments, syn::PathArguments::None) {
+        return;
+    }
+
+    let self_ty = &item_impl.self_ty;
+    let self_tokens = quote! { #module_path #self_ty };
+    let qualified = match &item_impl.trait_ {
+        Some((_, trait_path, _)) => quote! { <#self_tokens as #trait_path> },
+        None => quote! { <#self_tokens> },
+    };
+    let prefix = format!("{}{}_", prefix, snake_case(&type_name.ident.to_string()));
+
+    for item in &item_impl.items {
+        let syn::ImplItem::Fn(method) = item else {
+            continue;
+        };
+
+        let visible = item_impl.trait_.is_some()
+            || !in_module
+            || !matches!(method.vis, syn::Visibility::Inherited);
+        if !visible {
+            continue;
+        }
+
+        let vis = if in_module {
+            module_vis.clone()
+        } else {
+            method.vis.clone()
+        };
+        let name = &method.sig.ident;
+        collect_mockable(
+            &method.sig,
+            &vis,
+            &prefix,
+            quote! { #qualified::#name },
+            Some(&self_tokens),
+            mockables,
+        );
+    }
+}
+
+/// Collects a function unless its signature cannot be faked through a typed builder.
+fn collect_mockable(
+    sig: &syn::Signature,
+    vis: &syn::Visibility,
+    prefix: &str,
+    path: proc_macro2::TokenStream,
+    self_ty: Option<&proc_macro2::TokenStream>,
+    mockables: &mut Vec<Mockable>,
+) {
+    if !sig.generics.params.is_empty()
+        || sig.asyncness.is_some()
+        || sig.unsafety.is_some()
+        || sig.abi.is_some()
+        || sig.variadic.is_some()
+    {
+        return;
+    }
+
+    let replace_self = |ty: &Type| match self_ty {
+        Some(self_ty) => replace_self_type(quote! { #ty }, self_ty),
+        None => quote! { #ty },
+    };
+
+    let mut arg_types = Vec::new();
+    for input in &sig.inputs {
+        match input {
+            syn::FnArg::Receiver(receiver) => {
+                if receiver.colon_token.is_none() {
+                    let Some(self_ty) = self_ty else { return };
+                    let mutability = &receiver.mutability;
+                    arg_types.push(match &receiver.reference {
+                        Some((_, None)) => quote! { &#mutability #self_ty },
+                        Some((_, Some(_))) => return,
+                        None => quote! { #self_ty },
+                    });
+                } else {
+                    arg_types.push(replace_self(&receiver.ty));
+                }
+            }
+            syn::FnArg::Typed(pat_type) => {
+                if matches!(*pat_type.ty, Type::ImplTrait(_)) {
+                    return;
+                }
+                arg_types.push(replace_self(&pat_type.ty));
+            }
+        }
+    }
+
+    let return_type = match &sig.output {
+        syn::ReturnType::Default => None,
+        syn::ReturnType::Type(_, ty) => {
+            if borrows(&quote! { #ty }) || matches!(**ty, Type::ImplTrait(_)) {
+                return;
+            }
+            match &**ty {
+                Type::Tuple(tuple) if tuple.elems.is_empty() => None,
+                ty => Some(replace_self(ty)),
+            }
+        }
+    };
+
+    mockables.push(Mockable {
+        helper: syn::Ident::new(
+            &format!("mock_{}{}", prefix, sig.ident),
+            proc_macro2::Span::call_site(),
+        ),
+        vis: vis.clone(),
+        path,
+        arg_types,
+        return_type,
+    });
+}
+
+/// Returns whether a return type holds a reference or lifetime other than `'static`.
+fn borrows(tokens: &proc_macro2::TokenStream) -> bool {
+    use proc_macro2::TokenTree;
+
+    let tokens: Vec<TokenTree> = tokens.clone().into_iter().collect();
+    tokens.iter().enumerate().any(|(index, token)| match token {
+        // A reference without a lifetime.
+        TokenTree::Punct(punct) if punct.as_char() == '&' => !matches!(
+            tokens.get(index + 1),
+            Some(TokenTree::Punct(next)) if next.as_char() == '\''
+        ),
+        // A lifetime other than 'static.
+        TokenTree::Punct(punct) if punct.as_char() == '\'' => !matches!(
+            tokens.get(index + 1),
+            Some(TokenTree::Ident(name)) if name == "static"
+        ),
+        TokenTree::Group(group) => borrows(&group.stream()),
+        _ => false,
+    })
+}
+
+/// Replaces every `Self` in `tokens` with `self_ty`.
+fn replace_self_type(
+    tokens: proc_macro2::TokenStream,
+    self_ty: &proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    tokens
+        .into_iter()
+        .map(|token| match token {
+            proc_macro2::TokenTree::Ident(ident) if ident == "Self" => {
+                proc_macro2::TokenTree::Group(proc_macro2::Group::new(
+                    proc_macro2::Delimiter::None,
+                    self_ty.clone(),
+                ))
+            }
+            proc_macro2::TokenTree::Group(group) => {
+                let mut replaced = proc_macro2::Group::new(
+                    group.delimiter(),
+                    replace_self_type(group.stream(), self_ty),
+                );
+                replaced.set_span(group.span());
+                proc_macro2::TokenTree::Group(replaced)
+            }
+            other => other,
+        })
+        .collect()
+}
+
+/// Converts a type name such as `HttpClient` to `http_client`.
+fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    let mut previous_lower = false;
+    for c in name.chars() {
+        if c.is_uppercase() {
+            if previous_lower {
+                snake.push('_');
+            }
+            snake.extend(c.to_lowercase());
+            previous_lower = false;
+        } else {
+            snake.push(c);
+            previous_lower = c.is_lowercase() || c.is_ascii_digit();
+        }
+    }
+    snake
+}
+
+/// Generates the `mock_*` helper of a function.
+fn mock_helper(mockable: &Mockable) -> proc_macro2::TokenStream {
+    let Mockable {
+        helper,
+        vis,
+        path,
+        arg_types,
+        return_type,
+    } = mockable;
+
+    let helper_name = helper.to_string();
+    let doc = format!(
+        "Fakes `{}` in `injector`. Set the return value with `returns()` or `returns_with()`.",
+        pretty_tokens(path)
+    );
+    let unused = arg_types.iter().map(|_| quote! { _ });
+    let (ret, arrow_ret, default) = match return_type {
+        Some(ret) => (ret.clone(), quote! { -> #ret }, quote! { None }),
+        None => (quote! { () }, quote! {}, quote! { Some(Box::new(|| ())) }),
+    };
+
+    quote! {
+        #[doc = #doc]
+        #[allow(dead_code)]
+        #vis fn #helper(
+            injector: &mut ::injectorpp::interface::injector::InjectorPP,
+        ) -> ::injectorpp::interface::injector::Mock<'_, #ret> {
+            fn install(
+                injector: &mut ::injectorpp::interface::injector::InjectorPP,
+                mut produce: Box<dyn FnMut() -> #ret + Send>,
+            ) {
+                use ::injectorpp::interface::injector::*;
+
+                injector
+                    .when_called(::injectorpp::func!(fn (#path)(#(#arg_types),*) #arrow_ret))
+                    .will_execute_closure(::injectorpp::boxed_closure!(
+                        move |#(#unused),*| produce(),
+                        fn(#(#arg_types),*) #arrow_ret
+                    ));
+            }
+
+            ::injectorpp::interface::injector::Mock::__new(injector, #helper_name, install, #default)
+        }
+    }
+}
@@ -1,12 +1,34 @@
 pub(crate) mod arm64_codegenerator;
+pub(crate) mod call_site;
 pub(crate) mod common;
+pub(crate) mod debuginfo;
+pub(crate) mod delay;
+pub(crate) mod cxx_demangle;
+pub(crate) mod demangle;
+pub(crate) mod diagnostics;
+pub(crate) mod dual_mapping;
+pub(crate) mod elf;
+pub(crate) mod function_lock;
+pub(crate) mod function_size;
+pub(crate) mod got;
+pub(crate) mod hook_engine;
 pub(crate) mod internal;
+pub(crate) mod jit_arena;
+pub(crate) mod jit_dump;
 pub(crate) mod linuxapi;
 pub(crate) mod macosapi;
+pub(crate) mod objc;
 pub(crate) mod patch_amd64;
 pub(crate) mod patch_arm;
 pub(crate) mod patch_arm64;
+pub(crate) mod patch_riscv64;
 pub(crate) mod patch_trait;
+pub(crate) mod patch_x86;
+pub(crate) mod plt;
+pub(crate) mod redirect;
+pub(crate) mod symbols;
 pub(crate) mod thread_local_registry;
+pub(crate) mod thunk;
 pub(crate) mod utils;
 pub(crate) mod winapi;
+pub(crate) mod x86_64_insn;
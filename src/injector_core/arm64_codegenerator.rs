@@ -1,4 +1,4 @@
-#![cfg(target_arch = "aarch64")]
+#![cfg(any(target_arch = "aarch64", target_arch = "arm64ec"))]
 
 use crate::injector_core::utils::*;
 
@@ -347,6 +347,9 @@ pub(crate) fn emit_movz(
 /// ADRP x16, target
 /// ADD x16, x16, #:lo12:
 /// BR x16
+///
+/// The ADD is left out when the target is page-aligned, as JIT blocks are, so the jump takes
+/// 8 bytes instead of 12.
 pub(crate) fn maybe_emit_long_jump(pc: usize, target: usize) -> Vec<u32> {
     // We are storing the address in x16.
     const REGISTER: u32 = 16;
@@ -377,8 +380,10 @@ pub(crate) fn maybe_emit_long_jump(pc: usize, target: usize) -> Vec<u32> {
 
     // ADD instruction with the low 12 bits.
     let low12 = (target & 0xfff) as u32;
-    let add = 0x9100_0000 | (low12 << 10) | (REGISTER << 5) | REGISTER;
-    words.push(add);
+    if low12 != 0 {
+        let add = 0x9100_0000 | (low12 << 10) | (REGISTER << 5) | REGISTER;
+        words.push(add);
+    }
 
     // BR instruction to register 16.
     let br = 0xd61f_0000 | (REGISTER << 5);
@@ -386,3 +391,64 @@ pub(crate) fn maybe_emit_long_jump(pc: usize, target: usize) -> Vec<u32> {
 
     words
 }
+
+/// `bti c`, the landing pad of indirect calls.
+pub(crate) const BTI_C: u32 = 0xd503_245f;
+
+/// Returns the landing pad a patch must keep in front of its branch when a function starts
+/// with `insn`, or `None` if the function does not start with one.
+///
+/// Code built with branch protection (`-Z branch-protection=bti`) starts functions with a
+/// `bti` instruction, and indirect branches into such code must land on one. `paciasp` and
+/// `pacibsp` act as `bti c` too, but sign the link register, which the fake would not
+/// authenticate, so they are replaced with `bti c`.
+pub(crate) fn landing_pad(insn: u32) -> Option<u32> {
+    match insn {
+        // bti, bti c, bti j, bti jc
+        0xd503_241f | 0xd503_245f | 0xd503_249f | 0xd503_24df => Some(insn),
+        // paciasp, pacibsp
+        0xd503_233f | 0xd503_237f => Some(BTI_C),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_long_jump_within_branch_range_is_single_b() {
+        assert_eq!(maybe_emit_long_jump(0x1_0000, 0x1_0100), vec![0x1400_0040]);
+        assert_eq!(maybe_emit_long_jump(0x1_0100, 0x1_0000), vec![0x17ff_ffc0]);
+    }
+
+    #[test]
+    fn test_long_jump_to_page_aligned_target_is_adrp_br() {
+        assert_eq!(
+            maybe_emit_long_jump(0x4000_0000, 0x5000_0000),
+            vec![0x9008_0010, 0xd61f_0200]
+        );
+        assert_eq!(
+            maybe_emit_long_jump(0x5000_0000, 0x4000_0000),
+            vec![0x90f8_0010, 0xd61f_0200]
+        );
+    }
+
+    #[test]
+    fn test_long_jump_to_unaligned_target_adds_low_bits() {
+        assert_eq!(
+            maybe_emit_long_jump(0x4000_0000, 0x5000_0123),
+            vec![0x9008_0010, 0x9104_8e10, 0xd61f_0200]
+        );
+    }
+
+    #[test]
+    fn test_landing_pad_of_branch_protected_prologues() {
+        assert_eq!(landing_pad(0xd503245f), Some(BTI_C)); // bti c
+        assert_eq!(landing_pad(0xd50324df), Some(0xd50324df)); // bti jc
+        assert_eq!(landing_pad(0xd503233f), Some(BTI_C)); // paciasp
+        assert_eq!(landing_pad(0xd503237f), Some(BTI_C)); // pacibsp
+        assert_eq!(landing_pad(0xa9bf7bfd), None); // stp x29, x30, [sp, #-16]!
+        assert_eq!(landing_pad(0xd503201f), None); // nop
+    }
+}
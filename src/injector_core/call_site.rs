@@ -0,0 +1,358 @@
+#![cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec"
+))]
+
+//! Redirects the calls of a function by rewriting the call instructions calling it, instead of
+//! the code of the function.
+//!
+//! Only the rewritten calls reach the fake, so functions too small or too hot to patch can be
+//! faked for the code under test while the rest of the program keeps calling them. A call
+//! whose rewritten target is out of its range calls a veneer allocated near it, which jumps
+//! to the fake.
+
+use std::collections::HashMap;
+use std::ptr::NonNull;
+
+use crate::injector_core::common::{
+    allocate_jit_memory, free_jit_memory, inject_asm_code, read_bytes, FuncPtrInternal,
+};
+use crate::injector_core::function_size::{function_name, function_size};
+use crate::injector_core::redirect::{self, Redirect, Site};
+
+/// The calls a `PatchStrategy::CallSites` fake rewrites.
+pub(crate) enum CallSiteScope {
+    /// Every call of the main program, found through its symbol table.
+    #[cfg(target_os = "linux")]
+    Program,
+    /// The calls made by the functions at these addresses.
+    Callers(Vec<usize>),
+    /// The call instructions at these addresses.
+    Sites(Vec<usize>),
+}
+
+/// The size up to which the end of a caller without a recorded size is searched for, on
+/// Windows.
+const MAX_CALLER_SIZE: usize = 1 << 16;
+
+/// Rewrites the calls of the function at `func_addr` within `scope` to call `target`, until the
+/// returned redirection is dropped.
+///
+/// Panics if no call is found, or if a call site given explicitly does not call the function.
+pub(crate) fn redirect(
+    func_addr: usize,
+    target: usize,
+    scope: &CallSiteScope,
+    mut jit: Vec<(*mut u8, usize)>,
+) -> Redirect {
+    // Calls redirected by an earlier redirection of the function are redirected on top of it.
+    let mut finder = CallFinder {
+        func_addr,
+        redirected: redirect::redirected_sites(func_addr),
+        resolved: HashMap::new(),
+    };
+
+    let mut calls = match scope {
+        #[cfg(target_os = "linux")]
+        CallSiteScope::Program => program_functions()
+            .into_iter()
+            .flat_map(|(start, size)| unsafe { finder.calls_in(start, size) })
+            .collect(),
+        CallSiteScope::Callers(callers) => callers
+            .iter()
+            .flat_map(|&caller| {
+                let size = function_size(caller, MAX_CALLER_SIZE).unwrap_or_else(|| {
+                    panic!(
+                        "injectorpp: the size of {} is unknown, so its calls cannot be found. \
+                         Pass the addresses of its call instructions to at_call_sites() instead.",
+                        function_name(caller)
+                    )
+                });
+                unsafe { finder.calls_in(caller, size) }
+            })
+            .collect::<Vec<_>>(),
+        CallSiteScope::Sites(sites) => sites
+            .iter()
+            .map(|&site| {
+                let code = unsafe { read_bytes(site as *const u8, MAX_CALL_LEN) };
+                match unsafe { finder.call_at(site, &code) } {
+                    Some(len) => (site, len),
+                    None => panic!(
+                        "injectorpp: the instruction at {:#x} is not a call to {}",
+                        site,
+                        function_name(func_addr)
+                    ),
+                }
+            })
+            .collect(),
+    };
+    calls.sort_unstable();
+    calls.dedup();
+
+    if calls.is_empty() {
+        for (jit_memory, jit_size) in jit {
+            unsafe { free_jit_memory(jit_memory, jit_size) };
+        }
+        panic!(
+            "injectorpp: no call to {} was found, so no call can be redirected. Its callers \
+             may call it through a function pointer, or have inlined it.",
+            function_name(func_addr)
+        );
+    }
+
+    let mut veneers = Veneers {
+        target,
+        blocks: Vec::new(),
+    };
+    let sites = calls
+        .into_iter()
+        .map(|(addr, len)| {
+            let original = unsafe { read_bytes(addr as *const u8, len) };
+            let redirected = veneers.redirected_call(addr, &original);
+            Site::Call {
+                addr,
+                original,
+                redirected,
+            }
+        })
+        .collect();
+    jit.extend(veneers.blocks);
+
+    Redirect::install(func_addr, target, sites, jit, "calls")
+}
+
+/// Returns the `(address, size)` of the functions of the main program.
+#[cfg(target_os = "linux")]
+fn program_functions() -> Vec<(usize, usize)> {
+    let Some(program) = crate::injector_core::elf::loaded_objects()
+        .into_iter()
+        .next()
+    else {
+        return Vec::new();
+    };
+    let Some(functions) = crate::injector_core::function_size::elf_functions(&program.path) else {
+        return Vec::new();
+    };
+
+    functions
+        .iter()
+        .filter(|&&(_, size)| size != 0)
+        .map(|&(start, size)| (program.bias.wrapping_add(start as usize), size as usize))
+        .collect()
+}
+
+/// Finds the calls of a function.
+struct CallFinder {
+    func_addr: usize,
+    /// The call sites live redirections of the function rewrote.
+    redirected: Vec<usize>,
+    /// Whether the thunks called so far lead to the function, by address.
+    resolved: HashMap<usize, bool>,
+}
+
+impl CallFinder {
+    /// Returns whether a call to `dest` calls the function, directly or through a thunk.
+    fn reaches_function(&mut self, dest: usize) -> bool {
+        if dest == self.func_addr {
+            return true;
+        }
+
+        // GOT slots of undefined weak symbols hold 0.
+        if dest == 0 {
+            return false;
+        }
+
+        let func_addr = self.func_addr;
+        *self.resolved.entry(dest).or_insert_with(|| {
+            // Only code of a loaded object can be read to look for a thunk.
+            #[cfg(target_os = "linux")]
+            if crate::injector_core::elf::object_containing(dest).is_none() {
+                return false;
+            }
+            crate::injector_core::thunk::resolve_thunks(dest) == func_addr
+        })
+    }
+
+    /// Returns the `(address, length)` of the calls of the function in the `size` bytes of code
+    /// at `start`.
+    ///
+    /// # Safety
+    ///
+    /// `start` must point to `size` bytes of code.
+    #[cfg(target_arch = "x86_64")]
+    unsafe fn calls_in(&mut self, start: usize, size: usize) -> Vec<(usize, usize)> {
+        use crate::injector_core::x86_64_insn::insn_len;
+
+        let code = read_bytes(start as *const u8, size);
+        let mut calls = Vec::new();
+        let mut at = 0;
+        while at < size {
+            // Instructions the decoder does not know end the search in this function.
+            let len = insn_len(&code[at..]);
+            if len == 0 {
+                break;
+            }
+            if let Some(len) = self.call_at(start + at, &code[at..]) {
+                calls.push((start + at, len));
+            }
+            at += len;
+        }
+        calls
+    }
+
+    /// Returns the `(address, length)` of the calls of the function in the `size` bytes of code
+    /// at `start`.
+    ///
+    /// # Safety
+    ///
+    /// `start` must point to `size` bytes of code.
+    #[cfg(any(target_arch = "aarch64", target_arch = "arm64ec"))]
+    unsafe fn calls_in(&mut self, start: usize, size: usize) -> Vec<(usize, usize)> {
+        let code = read_bytes(start as *const u8, size & !3);
+        (0..code.len())
+            .step_by(4)
+            .filter_map(|at| Some((start + at, self.call_at(start + at, &code[at..])?)))
+            .collect()
+    }
+
+    /// Returns the length of the call instruction at `addr`, whose bytes start `code`, if it
+    /// calls the function.
+    ///
+    /// # Safety
+    ///
+    /// The GOT slot a `call [rip+disp32]` at `addr` reads must be readable.
+    #[cfg(target_arch = "x86_64")]
+    unsafe fn call_at(&mut self, addr: usize, code: &[u8]) -> Option<usize> {
+        let disp = |at: usize| {
+            let bytes = code.get(at..at + 4)?;
+            Some(i32::from_le_bytes(bytes.try_into().ok()?) as isize)
+        };
+
+        let (dest, len) = match code.get(..2)? {
+            // call rel32
+            [0xE8, _] => ((addr + 5).wrapping_add_signed(disp(1)?), 5),
+            // call [rip+disp32], through a GOT slot or an import address table entry.
+            [0xFF, 0x15] => {
+                let slot = (addr + 6).wrapping_add_signed(disp(2)?) as *const usize;
+                (slot.read_unaligned(), 6)
+            }
+            _ => return None,
+        };
+
+        (self.redirected.contains(&addr) || self.reaches_function(dest)).then_some(len)
+    }
+
+    /// Returns the length of the call instruction at `addr`, whose bytes start `code`, if it
+    /// calls the function.
+    #[cfg(any(target_arch = "aarch64", target_arch = "arm64ec"))]
+    unsafe fn call_at(&mut self, addr: usize, code: &[u8]) -> Option<usize> {
+        let insn = u32::from_le_bytes(code.get(..4)?.try_into().ok()?);
+        // bl imm26
+        if insn & 0xFC00_0000 != 0x9400_0000 {
+            return None;
+        }
+
+        let offset = ((insn << 6) as i32 >> 4) as isize;
+        let dest = addr.wrapping_add_signed(offset);
+        (self.redirected.contains(&addr) || self.reaches_function(dest)).then_some(4)
+    }
+}
+
+/// The longest call instruction rewritten.
+#[cfg(target_arch = "x86_64")]
+const MAX_CALL_LEN: usize = 6;
+#[cfg(any(target_arch = "aarch64", target_arch = "arm64ec"))]
+const MAX_CALL_LEN: usize = 4;
+
+/// The veneers jumping to the target of a redirection, allocated near the calls that cannot
+/// reach it.
+struct Veneers {
+    target: usize,
+    blocks: Vec<(*mut u8, usize)>,
+}
+
+impl Veneers {
+    /// `jmp [rip+0]` followed by the target.
+    #[cfg(target_arch = "x86_64")]
+    fn code(&self) -> Vec<u8> {
+        let mut code = vec![0xFF, 0x25, 0x00, 0x00, 0x00, 0x00];
+        code.extend_from_slice(&(self.target as u64).to_le_bytes());
+        code
+    }
+
+    /// `ldr x16, #8`, `br x16`, followed by the target.
+    #[cfg(any(target_arch = "aarch64", target_arch = "arm64ec"))]
+    fn code(&self) -> Vec<u8> {
+        let mut code = Vec::with_capacity(16);
+        code.extend_from_slice(&0x5800_0050u32.to_le_bytes());
+        code.extend_from_slice(&0xD61F_0200u32.to_le_bytes());
+        code.extend_from_slice(&(self.target as u64).to_le_bytes());
+        code
+    }
+
+    /// Returns the address of a veneer `reaches` returns true for, allocating one near `addr`
+    /// if none does.
+    fn near(&mut self, addr: usize, reaches: impl Fn(usize) -> bool) -> usize {
+        if let Some(&(block, _)) = self
+            .blocks
+            .iter()
+            .find(|&&(block, _)| reaches(block as usize))
+        {
+            return block as usize;
+        }
+
+        let code = self.code();
+        let site = unsafe { FuncPtrInternal::new(NonNull::new_unchecked(addr as *mut ())) };
+        let block = allocate_jit_memory(&site, code.len());
+        unsafe { inject_asm_code(&code, block) };
+        self.blocks.push((block, code.len()));
+        block as usize
+    }
+
+    /// Returns the bytes of the call instruction `original` at `addr`, rewritten to call the
+    /// target.
+    #[cfg(target_arch = "x86_64")]
+    fn redirected_call(&mut self, addr: usize, original: &[u8]) -> Vec<u8> {
+        let rel = |from: usize, to: usize| i32::try_from(to as isize - from as isize).ok();
+
+        let mut code = vec![0xE8];
+        match rel(addr + 5, self.target) {
+            Some(rel) => code.extend_from_slice(&rel.to_le_bytes()),
+            None if original.len() == 5 => {
+                let veneer = self.near(addr, |veneer| rel(addr + 5, veneer).is_some());
+                code.extend_from_slice(&rel(addr + 5, veneer).unwrap().to_le_bytes());
+            }
+            // call [rip+disp32] reads the target from the veneer.
+            None => {
+                let veneer = self.near(addr, |veneer| rel(addr + 6, veneer + 6).is_some());
+                code = vec![0xFF, 0x15];
+                code.extend_from_slice(&rel(addr + 6, veneer + 6).unwrap().to_le_bytes());
+            }
+        }
+        // A call [rip+disp32] turned into call rel32 is one byte shorter.
+        code.resize(original.len(), 0x90);
+        code
+    }
+
+    /// Returns the bytes of the call instruction `original` at `addr`, rewritten to call the
+    /// target.
+    #[cfg(any(target_arch = "aarch64", target_arch = "arm64ec"))]
+    fn redirected_call(&mut self, addr: usize, _original: &[u8]) -> Vec<u8> {
+        let bl = |to: usize| {
+            let disp = to as isize - addr as isize;
+            (-(1isize << 27)..(1isize << 27))
+                .contains(&disp)
+                .then_some(0x9400_0000 | ((disp >> 2) as u32 & 0x03FF_FFFF))
+        };
+
+        let insn = match bl(self.target) {
+            Some(insn) => insn,
+            None => {
+                let veneer = self.near(addr, |veneer| bl(veneer).is_some());
+                bl(veneer).unwrap()
+            }
+        };
+        insn.to_le_bytes().to_vec()
+    }
+}
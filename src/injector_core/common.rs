@@ -2,15 +2,44 @@ use libc::*;
 use std::ptr;
 use std::ptr::NonNull;
 
+use crate::injector_core::diagnostics;
+use crate::injector_core::diagnostics::PatchMode;
+use crate::injector_core::jit_arena;
+
 #[cfg(target_os = "windows")]
 use crate::injector_core::winapi::*;
 
-#[cfg(target_os = "linux")]
+#[cfg(any(target_os = "linux", target_os = "freebsd", target_os = "netbsd"))]
 use crate::injector_core::linuxapi::*;
 
 #[cfg(target_os = "macos")]
 use crate::injector_core::macosapi::*;
 
+/// Whether JIT code and patched functions are never writable and executable at once, with
+/// the `strict_wx` feature on Linux and the BSDs. They are written while mapped read-write,
+/// then switched to read-execute.
+pub(crate) const STRICT_WX: bool = cfg!(all(
+    feature = "strict_wx",
+    any(
+        target_os = "linux",
+        target_os = "freebsd",
+        target_os = "netbsd"
+    )
+));
+
+/// The protection JIT memory is mapped with on Unix.
+#[cfg(any(
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "netbsd"
+))]
+const JIT_PROT: c_int = if STRICT_WX {
+    PROT_READ | PROT_WRITE
+} else {
+    PROT_READ | PROT_WRITE | PROT_EXEC
+};
+
 /// A safe wrapper around a raw function pointer.
 ///
 /// `FuncPtrInternal` encapsulates a non-null function pointer and provides safe
@@ -39,12 +68,127 @@ impl FuncPtrInternal {
     }
 }
 
+/// A floating-point value for a fake to return, see `will_return_f32` and `will_return_f64`.
+#[derive(Clone, Copy)]
+pub(crate) enum FloatReturn {
+    F32(f32),
+    F64(f64),
+}
+
+impl FloatReturn {
+    /// Returns the bytes of the value, as stored after the code returning it.
+    pub(crate) fn to_le_bytes(self) -> Vec<u8> {
+        match self {
+            FloatReturn::F32(value) => value.to_le_bytes().to_vec(),
+            FloatReturn::F64(value) => value.to_le_bytes().to_vec(),
+        }
+    }
+}
+
+/// Returns executable memory holding `code`, allocating it on first use of the same code.
+/// Used as the target of global patches. It is never freed, so a thread may still be
+/// executing it after the patch was restored.
+#[cfg(any(target_arch = "x86_64", target_arch = "arm", target_arch = "x86"))]
+pub(crate) fn shared_jit_code(src: &FuncPtrInternal, code: &[u8]) -> FuncPtrInternal {
+    static SHARED: std::sync::Mutex<Vec<(Vec<u8>, usize)>> = std::sync::Mutex::new(Vec::new());
+
+    let mut shared = SHARED.lock().unwrap_or_else(|e| e.into_inner());
+    let addr = match shared.iter().find(|(shared_code, _)| shared_code == code) {
+        Some((_, addr)) => *addr,
+        None => {
+            #[cfg(target_arch = "x86_64")]
+            let jit_memory = allocate_jit_memory_preferably_near(src, code.len());
+            #[cfg(any(target_arch = "arm", target_arch = "x86"))]
+            let jit_memory = allocate_jit_memory(src, code.len());
+            unsafe {
+                inject_asm_code(code, jit_memory);
+            }
+            shared.push((code.to_vec(), jit_memory as usize));
+            jit_memory as usize
+        }
+    };
+
+    unsafe {
+        FuncPtrInternal::new(NonNull::new(addr as *mut ()).expect("JIT memory must not be null"))
+    }
+}
+
 /// Allocates a block of executable memory near the provided source address,
 /// ensuring that the allocated memory lies within ±128MB of the source.
 /// This mirrors the C++ approach.
-#[cfg(any(target_arch = "aarch64", target_arch = "x86_64", target_arch = "arm"))]
+///
+/// # Panics
+/// Panics if no memory is found within the valid address range.
+#[cfg(any(
+    target_arch = "aarch64",
+    target_arch = "arm64ec",
+    target_arch = "x86_64",
+    target_arch = "arm",
+    target_arch = "x86",
+    target_arch = "riscv64"
+))]
 pub(crate) fn allocate_jit_memory(src: &FuncPtrInternal, code_size: usize) -> *mut u8 {
-    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    try_allocate_jit_memory(src, code_size).unwrap_or_else(|| {
+        panic!(
+            "Failed to allocate JIT memory within branch range of source on {} arch",
+            std::env::consts::ARCH
+        )
+    })
+}
+
+/// Like `allocate_jit_memory`, but returns `None` when no memory is found within the valid
+/// address range.
+///
+/// Small blocks are carved out of the chunks of `jit_arena`, shared by the JIT code of
+/// functions near each other.
+#[cfg(any(
+    target_arch = "aarch64",
+    target_arch = "arm64ec",
+    target_arch = "x86_64",
+    target_arch = "arm",
+    target_arch = "x86",
+    target_arch = "riscv64"
+))]
+pub(crate) fn try_allocate_jit_memory(src: &FuncPtrInternal, code_size: usize) -> Option<*mut u8> {
+    if jit_arena::pooled(code_size) {
+        return jit_arena::allocate(src.as_ptr() as usize, code_size, jit_branch_range(), || {
+            allocate_jit_memory_near(src, jit_arena::CHUNK_SIZE)
+        });
+    }
+    allocate_jit_memory_near(src, code_size)
+}
+
+/// Returns how far from a function its JIT code may be allocated, for the branches between
+/// them to reach, or `u64::MAX` where they reach the whole address space, as on i686.
+fn jit_branch_range() -> u64 {
+    if cfg!(any(
+        all(target_os = "macos", target_arch = "x86_64"),
+        all(target_os = "macos", target_arch = "aarch64"),
+        all(target_os = "windows", target_arch = "x86_64")
+    )) {
+        0x8000_0000 // ±2GB
+    } else if cfg!(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec",
+        target_arch = "riscv64"
+    )) {
+        0x800_0000 // ±128MB
+    } else if cfg!(target_arch = "arm") {
+        0x100_0000 // ±16MB
+    } else {
+        u64::MAX
+    }
+}
+
+/// Maps `code_size` bytes of JIT memory of its own within `jit_branch_range()` of `src`.
+fn allocate_jit_memory_near(src: &FuncPtrInternal, code_size: usize) -> Option<*mut u8> {
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "macos",
+        target_os = "freebsd",
+        target_os = "netbsd"
+    ))]
     {
         allocate_jit_memory_unix(src, code_size)
     }
@@ -55,43 +199,114 @@ pub(crate) fn allocate_jit_memory(src: &FuncPtrInternal, code_size: usize) -> *m
     }
 }
 
+/// Allocates executable memory near `src` when possible, and anywhere otherwise. Only for
+/// code reached through absolute branches, such as the `jmp [rip+0]` used by x86_64 patches
+/// whose target is out of `jmp rel32` range.
+///
+/// # Panics
+/// Panics if memory allocation fails.
+#[cfg(target_arch = "x86_64")]
+pub(crate) fn allocate_jit_memory_preferably_near(
+    src: &FuncPtrInternal,
+    code_size: usize,
+) -> *mut u8 {
+    if let Some(jit_memory) = try_allocate_jit_memory(src, code_size) {
+        return jit_memory;
+    }
+
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "macos",
+        target_os = "freebsd",
+        target_os = "netbsd"
+    ))]
+    let ptr = unsafe {
+        let ptr = map_jit_memory(
+            std::ptr::null_mut(),
+            code_size,
+            libc::MAP_ANON | libc::MAP_PRIVATE,
+        );
+        if ptr == libc::MAP_FAILED {
+            std::ptr::null_mut()
+        } else {
+            ptr
+        }
+    };
+
+    #[cfg(target_os = "windows")]
+    let ptr = unsafe { allocate_code(std::ptr::null_mut(), code_size) };
+
+    if ptr.is_null() {
+        panic!(
+            "Failed to allocate executable memory on {} arch",
+            std::env::consts::ARCH
+        );
+    }
+
+    ptr as *mut u8
+}
+
 // See https://github.com/microsoft/injectorppforrust/issues/84
 // See https://github.com/microsoft/injectorppforrust/issues/88
 /// Allocate JIT memory on Unix platforms.
 ///
 /// On MacOS, both aarch64 and x86_64 architectures have a ±2GB memory range.
-/// On Linux, both aarch64 and x86_64 architectures have a ±128MB memory range.
+/// On Linux and the BSDs, aarch64, x86_64 and riscv64 architectures have a ±128MB memory
+/// range.
 /// Other architectures have no enforced address range constraint.
 ///
+/// Returns `None` if no memory is found within the valid address range on `aarch64`,
+/// `x86_64`, `arm` or `riscv64`.
+///
 /// # Panics
-/// Panics if memory allocation fails or if no memory is found within the valid address range on
-/// `aarch64` or `x86_64`.
-#[cfg(any(target_os = "linux", target_os = "macos"))]
-#[cfg(any(target_arch = "aarch64", target_arch = "x86_64", target_arch = "arm"))]
-fn allocate_jit_memory_unix(_src: &FuncPtrInternal, code_size: usize) -> *mut u8 {
+/// Panics if memory allocation fails on other architectures.
+#[cfg(any(
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "netbsd"
+))]
+#[cfg(any(
+    target_arch = "aarch64",
+    target_arch = "x86_64",
+    target_arch = "arm",
+    target_arch = "x86",
+    target_arch = "riscv64"
+))]
+fn allocate_jit_memory_unix(_src: &FuncPtrInternal, code_size: usize) -> Option<*mut u8> {
+    #[cfg(target_os = "macos")]
+    check_jit_allowed();
+
     #[cfg(target_os = "macos")]
     let flags = libc::MAP_ANON | libc::MAP_PRIVATE | libc::MAP_JIT;
 
-    #[cfg(target_os = "linux")]
+    #[cfg(not(target_os = "macos"))]
     let flags = libc::MAP_ANONYMOUS | libc::MAP_PRIVATE;
 
-    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64", target_arch = "arm"))]
+    #[cfg(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm",
+        target_arch = "riscv64"
+    ))]
     {
-        #[cfg(target_os = "macos")]
-        let max_range: u64 = 0x8000_0000; // ±2GB
-
-        #[cfg(all(
-            target_os = "linux",
-            any(target_arch = "x86_64", target_arch = "aarch64")
-        ))]
-        let max_range: u64 = 0x8000000; // ±128MB
-
-        #[cfg(all(target_os = "linux", target_arch = "arm"))]
-        let max_range: u64 = 0x1000000; // ±16MB
-
+        let max_range = jit_branch_range();
         let original_addr = _src.as_ptr() as u64;
         let page_size = unsafe { sysconf(_SC_PAGESIZE) as u64 };
 
+        // Map the nearest free gaps listed in /proc/self/maps directly. Other threads may map
+        // them first, so the probe below still runs when none is left.
+        #[cfg(target_os = "linux")]
+        for hint_addr in free_gaps_near(original_addr, code_size as u64, max_range, page_size) {
+            let ptr = unsafe { map_jit_memory(hint_addr as *mut c_void, code_size, flags) };
+            if ptr != libc::MAP_FAILED {
+                if (ptr as u64).abs_diff(original_addr) <= max_range {
+                    return Some(ptr as *mut u8);
+                }
+                unsafe { free_jit_memory(ptr as *mut u8, code_size) };
+            }
+        }
+
         // Search outward from the function address to find the CLOSEST free page.
         // This minimizes the trampoline-to-function distance, which is critical for
         // PC-relative instruction fixups (CBZ/CBNZ have only ±1MB range).
@@ -109,47 +324,31 @@ fn allocate_jit_memory_unix(_src: &FuncPtrInternal, code_size: usize) -> *mut u8
 
                 let Some(hint_addr) = hint else { continue };
 
-                let ptr = unsafe {
-                    libc::mmap(
-                        hint_addr as *mut c_void,
-                        code_size,
-                        PROT_READ | PROT_WRITE | PROT_EXEC,
-                        flags,
-                        -1,
-                        0,
-                    )
-                };
+                let ptr = unsafe { map_jit_memory(hint_addr as *mut c_void, code_size, flags) };
                 if ptr != libc::MAP_FAILED {
                     let allocated = ptr as u64;
                     let diff = allocated.abs_diff(original_addr);
                     if diff <= max_range {
-                        return ptr as *mut u8;
+                        return Some(ptr as *mut u8);
                     } else {
-                        unsafe { libc::munmap(ptr, code_size) };
+                        unsafe { free_jit_memory(ptr as *mut u8, code_size) };
                     }
                 }
             }
             offset += page_size;
         }
 
-        panic!(
-            "Failed to allocate JIT memory within ±{max_range} of source on {} arch",
-            std::env::consts::ARCH
-        );
+        None
     }
 
-    #[cfg(not(any(target_arch = "aarch64", target_arch = "x86_64", target_arch = "arm")))]
+    #[cfg(not(any(
+        target_arch = "aarch64",
+        target_arch = "x86_64",
+        target_arch = "arm",
+        target_arch = "riscv64"
+    )))]
     {
-        let ptr = unsafe {
-            libc::mmap(
-                std::ptr::null_mut(),
-                code_size,
-                PROT_READ | PROT_WRITE | PROT_EXEC,
-                flags,
-                -1,
-                0,
-            )
-        };
+        let ptr = unsafe { map_jit_memory(std::ptr::null_mut(), code_size, flags) };
 
         if ptr == libc::MAP_FAILED {
             panic!(
@@ -158,7 +357,107 @@ fn allocate_jit_memory_unix(_src: &FuncPtrInternal, code_size: usize) -> *mut u8
             );
         }
 
-        ptr as *mut u8
+        Some(ptr as *mut u8)
+    }
+}
+
+/// The lowest address the kernel maps by default, `vm.mmap_min_addr`.
+#[cfg(target_os = "linux")]
+#[cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm",
+    target_arch = "riscv64"
+))]
+const MMAP_MIN_ADDR: u64 = 0x10000;
+
+/// Returns the page-aligned addresses within `max_range` of `addr` where the free gaps of the
+/// address space listed in `/proc/self/maps` fit `size` bytes, nearest first. The gap below
+/// the main thread stack is left for it to grow, and the one above the last mapping is left
+/// out, as it reaches into kernel space.
+#[cfg(target_os = "linux")]
+#[cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm",
+    target_arch = "riscv64"
+))]
+fn free_gaps_near(addr: u64, size: u64, max_range: u64, page_size: u64) -> Vec<u64> {
+    let Ok(maps) = std::fs::read_to_string("/proc/self/maps") else {
+        return Vec::new();
+    };
+    let size = size.next_multiple_of(page_size);
+    let near = addr & !(page_size - 1);
+
+    let mut gaps = Vec::new();
+    let mut gap_start = MMAP_MIN_ADDR;
+    for line in maps.lines() {
+        // start-end perms offset dev inode path
+        let Some((start, end)) = line
+            .split_whitespace()
+            .next()
+            .and_then(|range| range.split_once('-'))
+        else {
+            continue;
+        };
+        let (Ok(start), Ok(end)) = (u64::from_str_radix(start, 16), u64::from_str_radix(end, 16))
+        else {
+            continue;
+        };
+
+        if start >= gap_start + size && !line.ends_with("[stack]") {
+            // The address of the gap nearest to `addr`.
+            let hint = near.clamp(gap_start, start - size);
+            if hint.abs_diff(addr) <= max_range {
+                gaps.push(hint);
+            }
+        }
+        gap_start = gap_start.max(end);
+    }
+
+    gaps.sort_unstable_by_key(|&hint| hint.abs_diff(addr));
+    gaps
+}
+
+/// Maps `size` bytes of JIT memory with the given `mmap` flags, at `hint` when free. Returns
+/// `MAP_FAILED` on failure.
+///
+/// Where anonymous memory cannot be made executable, it is mapped twice from a memfd instead,
+/// and written through its writable view.
+#[cfg(any(
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "netbsd"
+))]
+unsafe fn map_jit_memory(hint: *mut c_void, size: usize, flags: c_int) -> *mut c_void {
+    #[cfg(target_os = "linux")]
+    if jit_dual_mapped() {
+        return crate::injector_core::dual_mapping::map(hint, size);
+    }
+
+    libc::mmap(hint, size, JIT_PROT, flags, -1, 0)
+}
+
+/// Panics with an actionable message when the process may not map `MAP_JIT` memory at all,
+/// as without the JIT entitlement under the hardened runtime, rather than searching the whole
+/// branch range for it. Probed once.
+#[cfg(target_os = "macos")]
+fn check_jit_allowed() {
+    static DENIED: std::sync::OnceLock<Option<String>> = std::sync::OnceLock::new();
+
+    let denied = DENIED.get_or_init(|| unsafe {
+        let size = sysconf(_SC_PAGESIZE) as usize;
+        let flags = libc::MAP_ANON | libc::MAP_PRIVATE | libc::MAP_JIT;
+        let ptr = libc::mmap(std::ptr::null_mut(), size, JIT_PROT, flags, -1, 0);
+        if ptr == libc::MAP_FAILED {
+            return Some(jit_denied_message(std::io::Error::last_os_error()));
+        }
+        libc::munmap(ptr, size);
+        None
+    });
+    if let Some(message) = denied {
+        panic!("{}", message);
     }
 }
 
@@ -166,81 +465,55 @@ fn allocate_jit_memory_unix(_src: &FuncPtrInternal, code_size: usize) -> *mut u8
 /// Allocate executable JIT memory on Windows platforms.
 ///
 /// For AArch64, memory must be within ±128MB due to instruction encoding limits (e.g., B/BL).
-/// For x86_64, memory must be within ±2GB for `jmp rel32` instructions.
+/// For x86_64, memory must be within ±2GB for `jmp rel32` instructions. Returns `None` if no
+/// memory is found within that range.
 #[cfg(target_os = "windows")]
-fn allocate_jit_memory_windows(_src: &FuncPtrInternal, code_size: usize) -> *mut u8 {
-    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+fn allocate_jit_memory_windows(_src: &FuncPtrInternal, code_size: usize) -> Option<*mut u8> {
+    #[cfg(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec"
+    ))]
     {
-        #[cfg(target_arch = "aarch64")]
-        let max_range: u64 = 0x8000000; // ±128MB
-
-        #[cfg(target_arch = "x86_64")]
-        let max_range: u64 = 0x8000_0000; // ±2GB
-
+        let max_range = jit_branch_range();
         let original_addr = _src.as_ptr() as u64;
-        let page_size = unsafe { get_page_size() as u64 };
 
-        // Search outward from the function address to find the CLOSEST free page.
-        // This avoids allocating far from the function (e.g., in/near stack memory),
-        // which could disrupt the stack guard page and cause STATUS_STACK_OVERFLOW.
-        let mut offset: u64 = 0;
-        while offset <= max_range {
-            for &dir in &[1i64, -1i64] {
-                let hint = if dir > 0 {
-                    original_addr.checked_add(offset)
-                } else if offset > 0 {
-                    original_addr.checked_sub(offset)
+        // Allocate in the CLOSEST free region to the function. This avoids allocating far
+        // from the function (e.g., in/near stack memory), which could disrupt the stack guard
+        // page and cause STATUS_STACK_OVERFLOW. Another thread may take a region first, so
+        // the next ones are tried in turn.
+        for hint_addr in free_regions_near(original_addr as usize, code_size, max_range as usize) {
+            let ptr = unsafe { allocate_code(hint_addr as *mut c_void, code_size) };
+            if !ptr.is_null() {
+                let allocated = ptr as u64;
+                let diff = allocated.abs_diff(original_addr);
+                if diff <= max_range {
+                    return Some(ptr as *mut u8);
                 } else {
-                    continue; // Already tried offset=0 with dir=1
-                };
-
-                let Some(hint_addr) = hint else { continue };
-
-                let ptr = unsafe {
-                    VirtualAlloc(
-                        hint_addr as *mut c_void,
-                        code_size,
-                        MEM_COMMIT | MEM_RESERVE,
-                        PAGE_EXECUTE_READWRITE,
-                    )
-                };
-                if !ptr.is_null() {
-                    let allocated = ptr as u64;
-                    let diff = allocated.abs_diff(original_addr);
-                    if diff <= max_range {
-                        return ptr as *mut u8;
-                    } else {
-                        unsafe {
-                            VirtualFree(ptr, 0, MEM_RELEASE);
-                        }
+                    unsafe {
+                        VirtualFree(ptr, 0, MEM_RELEASE);
                     }
                 }
             }
-            offset += page_size;
         }
 
-        panic!(
-            "Failed to allocate JIT memory within ±{max_range} bytes of source on {} arch",
-            std::env::consts::ARCH
-        );
+        None
     }
 
-    #[cfg(all(not(target_arch = "x86_64"), not(target_arch = "aarch64")))]
+    #[cfg(all(
+        not(target_arch = "x86_64"),
+        not(target_arch = "aarch64"),
+        not(target_arch = "arm64ec")
+    ))]
     {
-        let ptr = unsafe {
-            VirtualAlloc(
-                std::ptr::null_mut(), // let OS choose suitable address
-                code_size,
-                MEM_COMMIT | MEM_RESERVE,
-                PAGE_EXECUTE_READWRITE,
-            )
-        };
+        // Let the OS choose a suitable address.
+        let ptr = unsafe { allocate_code(std::ptr::null_mut(), code_size) };
 
         if ptr.is_null() {
             panic!("Failed to allocate executable memory on Windows (unsupported architecture)");
         }
 
-        ptr as *mut u8
+        Some(ptr as *mut u8)
     }
 }
 
@@ -255,8 +528,116 @@ pub(crate) unsafe fn read_bytes(ptr: *const u8, len: usize) -> Vec<u8> {
     buf
 }
 
+/// A live global patch. Patches of the same function form a stack in installation order.
+struct LivePatch {
+    id: u64,
+    func_addr: usize,
+    patch_size: usize,
+    jit_addr: usize,
+    /// The bytes that undo this patch. When a patch below it in the stack is removed first,
+    /// its bytes are handed over, so the patch on top always restores the original code.
+    restore: Vec<u8>,
+    /// The bytes of this patch while it is paused, with the restore bytes written instead.
+    paused: Option<Vec<u8>>,
+}
+
+/// Every live `PatchGuard` in the process, in installation order.
+static LIVE_GUARDS: std::sync::Mutex<Vec<LivePatch>> = std::sync::Mutex::new(Vec::new());
+
+static NEXT_GUARD_ID: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+
+/// Returns `(func_addr, patch_size, jit_addr)` for every live `PatchGuard` in the process.
+pub(crate) fn live_global_patches() -> Vec<(usize, usize, usize)> {
+    LIVE_GUARDS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .map(|patch| (patch.func_addr, patch.patch_size, patch.jit_addr))
+        .collect()
+}
+
+/// Returns whether a live `PatchGuard` currently patches `func_addr`, that is whether the top
+/// patch of the function is not paused.
+pub(crate) fn is_globally_patched(func_addr: usize) -> bool {
+    LIVE_GUARDS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .rev()
+        .find(|patch| patch.func_addr == func_addr)
+        .is_some_and(|top| top.paused.is_none())
+}
+
+/// Pauses or resumes the global patch installed by the `PatchGuard` with `id`. Pausing writes
+/// back the code the patch replaced, resuming writes the patch again, and the JIT stub stays
+/// allocated in between.
+///
+/// Returns `false` when another patch of the same function was installed above it since, as
+/// only the top patch of a function can be paused or resumed. Does nothing once the guard is
+/// dropped.
+pub(crate) fn set_global_patch_paused(id: u64, paused: bool) -> bool {
+    apply_deferred_patches();
+
+    let mut live = LIVE_GUARDS.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(index) = live.iter().position(|patch| patch.id == id) else {
+        return true;
+    };
+
+    let func_addr = live[index].func_addr;
+    if live[index + 1..]
+        .iter()
+        .any(|above| above.func_addr == func_addr)
+    {
+        return false;
+    }
+
+    let patch = &mut live[index];
+    let func_ptr = func_addr as *mut u8;
+    match (paused, patch.paused.take()) {
+        (true, None) => unsafe {
+            patch.paused = Some(read_bytes(func_ptr, patch.restore.len()));
+            patch_function(func_ptr, &patch.restore);
+        },
+        (false, Some(bytes)) => unsafe { patch_function(func_ptr, &bytes) },
+        (_, bytes) => patch.paused = bytes,
+    }
+
+    true
+}
+
+/// Returns whether the global patch installed by the `PatchGuard` with `id` is in effect: no
+/// longer in effect once its code was taken out of the function, as by pausing it. A patch
+/// stacked above it on the same function keeps it in effect.
+pub(crate) fn is_global_patch_in_effect(id: u64) -> bool {
+    apply_deferred_patches();
+
+    let live = LIVE_GUARDS.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(func_addr) = live
+        .iter()
+        .find(|patch| patch.id == id)
+        .map(|patch| patch.func_addr)
+    else {
+        return false;
+    };
+
+    // The function holds the restore bytes of its top patch when that patch is paused or
+    // its code was written back.
+    live.iter()
+        .rev()
+        .find(|patch| patch.func_addr == func_addr)
+        .is_some_and(|top| {
+            top.paused.is_none()
+                && unsafe { read_bytes(func_addr as *const u8, top.restore.len()) } != top.restore
+        })
+}
+
 /// A guard that stores the original bytes of a patched function and the allocated JIT memory.
 /// When dropped, it restores the original function code and frees the JIT memory.
+///
+/// Guards patching the same function stack: the last one installed is the one in effect, and
+/// they may be dropped in any order. Dropping the top guard brings back the guard below it,
+/// dropping the bottom one leaves the top in effect, and the original code is back once all
+/// of them are dropped.
 #[allow(dead_code)]
 pub(crate) struct PatchGuard {
     func_ptr: *mut u8,
@@ -266,8 +647,28 @@ pub(crate) struct PatchGuard {
 
     #[cfg_attr(target_os = "windows", allow(dead_code))]
     jit_size: usize,
+
+    /// Address of the entry counter of a counting JIT stub, followed by its exit counter.
+    stub_counters: Option<usize>,
+
+    /// Identifies this guard in `LIVE_GUARDS`.
+    id: u64,
 }
 
+/// Retired counting stubs kept mapped, oldest first, as `(func_addr, jit_addr, jit_size,
+/// counters)`.
+#[cfg(target_arch = "x86_64")]
+#[allow(clippy::type_complexity)]
+static RETIRED_STUBS: std::sync::Mutex<std::collections::VecDeque<(usize, usize, usize, usize)>> =
+    std::sync::Mutex::new(std::collections::VecDeque::new());
+
+/// How many retired counting stubs stay mapped before the oldest one is freed.
+#[cfg(target_arch = "x86_64")]
+const RETIRED_STUBS_KEPT: usize = 64;
+
+/// How long dropping a `PatchGuard` waits for threads to leave its JIT stub.
+const QUIESCENCE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
 #[allow(dead_code)]
 impl PatchGuard {
     pub(crate) fn new(
@@ -277,63 +678,1011 @@ impl PatchGuard {
         jit_memory: *mut u8,
         jit_size: usize,
     ) -> Self {
+        diagnostics::patch_installed(
+            PatchMode::Global,
+            func_ptr as usize,
+            &original_bytes,
+            patch_size,
+            jit_memory as usize,
+        );
+
+        let id = NEXT_GUARD_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        LIVE_GUARDS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(LivePatch {
+                id,
+                func_addr: func_ptr as usize,
+                patch_size,
+                jit_addr: jit_memory as usize,
+                restore: original_bytes[..patch_size].to_vec(),
+                paused: None,
+            });
+
         Self {
             func_ptr,
             original_bytes,
             patch_size,
             jit_memory,
             jit_size,
+            stub_counters: None,
+            id,
+        }
+    }
+
+    /// Marks the JIT stub as counting the threads passing through it, with the entry counter
+    /// at `counters` and the exit counter right after. Dropping the guard then waits until no
+    /// thread is inside the stub before freeing it.
+    ///
+    /// Only the x86_64 stubs count threads. Guards of other architectures free their stub as
+    /// soon as the original bytes are restored.
+    pub(crate) fn with_stub_counters(mut self, counters: usize) -> Self {
+        self.stub_counters = Some(counters);
+        self
+    }
+
+    /// Returns how many times a thread entered the JIT stub, when it counts them.
+    pub(crate) fn stub_entries(&self) -> Option<u64> {
+        use std::sync::atomic::AtomicU64;
+        use std::sync::atomic::Ordering;
+
+        self.stub_counters
+            .map(|counters| unsafe { &*(counters as *const AtomicU64) }.load(Ordering::Acquire))
+    }
+
+    /// Removes this guard from the patch stack of its function. Restores the code below it
+    /// when this is the top guard, otherwise hands its restore bytes to the guard above.
+    ///
+    /// A paused guard has its restore bytes written in its place, which are this guard's
+    /// branch until the handover, so they are rewritten too: in the function when it is the
+    /// top guard, otherwise in the restore bytes of the guard above it, and so on.
+    unsafe fn unwind_from_stack(&self) {
+        let mut live = LIVE_GUARDS.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(index) = live.iter().position(|patch| patch.id == self.id) else {
+            return;
+        };
+
+        let patch = live.remove(index);
+        let above_from = |live: &[LivePatch], from: usize| {
+            live[from..]
+                .iter()
+                .position(|above| above.func_addr == patch.func_addr)
+                .map(|offset| from + offset)
+        };
+
+        let mut restore = patch.restore;
+        let mut next = above_from(&live, index);
+        while let Some(above_index) = next {
+            let above = &mut live[above_index];
+            if above.restore.len() > restore.len() {
+                restore.extend_from_slice(&above.restore[restore.len()..]);
+            }
+            above.restore = restore.clone();
+            if above.paused.is_none() {
+                return;
+            }
+            next = above_from(&live, above_index + 1);
         }
+        patch_function(self.func_ptr, &restore);
+    }
+
+    /// Returns whether this patch is in effect, see `is_global_patch_in_effect()`.
+    pub(crate) fn is_in_effect(&self) -> bool {
+        is_global_patch_in_effect(self.id)
+    }
+
+    /// Returns the id passed to `set_global_patch_paused()` to pause this patch.
+    pub(crate) fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Returns `(func_addr, patch_size, jit_addr)` of this patch.
+    pub(crate) fn info(&self) -> (usize, usize, usize) {
+        (
+            self.func_ptr as usize,
+            self.patch_size,
+            self.jit_memory as usize,
+        )
+    }
+    /// Formats the original bytes, the written patch and the JIT stub of this patch.
+    #[cfg(feature = "jit-dump")]
+    pub(crate) fn dump(&self) -> String {
+        use crate::injector_core::diagnostics::hex_bytes;
+        use crate::injector_core::jit_dump::dump_block;
+
+        let mut out = format!(
+            "patch at {:#x} ({} bytes, global)\n  original: {}\n",
+            self.func_ptr as usize,
+            self.patch_size,
+            hex_bytes(&self.original_bytes[..self.patch_size.min(self.original_bytes.len())])
+        );
+        dump_block(&mut out, "patch", self.func_ptr as usize, self.patch_size);
+        dump_block(
+            &mut out,
+            "jit stub",
+            self.jit_memory as usize,
+            self.jit_size,
+        );
+        out
     }
 }
 
 impl Drop for PatchGuard {
     fn drop(&mut self) {
+        let stub = RestoredStub {
+            func_ptr: self.func_ptr,
+            jit_memory: self.jit_memory,
+            jit_size: self.jit_size,
+            stub_counters: self.stub_counters,
+        };
+
         unsafe {
-            patch_function(self.func_ptr, &self.original_bytes[..self.patch_size]);
-            if !self.jit_memory.is_null() {
-                #[cfg(any(target_os = "linux", target_os = "macos"))]
-                {
-                    libc::munmap(self.jit_memory as *mut c_void, self.jit_size);
-                }
+            self.unwind_from_stack();
+
+            // The stub is released once the restore is written, at the end of the batch
+            // restoring the patches of a dropped injector, or right away.
+            let deferred = RESTORES_DEFERRED.with(std::cell::Cell::get)
+                && RESTORED_STUBS
+                    .try_with(|stubs| stubs.borrow_mut().push(stub))
+                    .is_ok();
+            if !deferred {
+                apply_deferred_patches();
+                stub.release();
+            }
+        }
 
-                #[cfg(target_os = "windows")]
-                {
-                    VirtualFree(self.jit_memory as *mut c_void, 0, MEM_RELEASE);
-                }
+        diagnostics::patch_restored(PatchMode::Global, self.func_ptr as usize);
+    }
+}
+
+/// The JIT stub of a restored patch, released once the original bytes are written back.
+#[derive(Clone, Copy)]
+struct RestoredStub {
+    func_ptr: *mut u8,
+    jit_memory: *mut u8,
+    jit_size: usize,
+    stub_counters: Option<usize>,
+}
+
+impl RestoredStub {
+    unsafe fn release(&self) {
+        if self.stub_counters.is_some() {
+            self.retire_counting_stub();
+        } else if !self.jit_memory.is_null() {
+            free_jit_memory(self.jit_memory, self.jit_size);
+        }
+    }
+
+    /// Releases a counting JIT stub once the original bytes are restored.
+    ///
+    /// Waits until no thread is inside the stub, and leaks it if some thread stays inside.
+    /// A thread that took the patched branch just before the restore but has not yet run the
+    /// first stub instruction is not counted, so the stub is redirected to the original
+    /// function and kept mapped until `RETIRED_STUBS_KEPT` newer stubs were retired. It is
+    /// freed then only if no thread entered it since, and leaked otherwise.
+    ///
+    /// The redirect never overwrites an instruction a thread may be executing: the jump to
+    /// the original function is written to the padding after the code, which no thread
+    /// reaches, and only then is the `lock inc` following the `endbr64` replaced by a short
+    /// jump to it, with a single 2-byte store on an instruction boundary.
+    #[cfg(target_arch = "x86_64")]
+    unsafe fn retire_counting_stub(&self) {
+        if let Err(inside) = self.wait_for_quiescence() {
+            diagnostics::stub_leaked(self.func_ptr as usize, self.jit_memory as usize, inside);
+            return;
+        }
+
+        // jmp [rip+0]; .quad func, in the padding of the stub, reached by a short jump
+        // following its endbr64. The jump is published last.
+        use crate::injector_core::patch_amd64::RETIRED_JUMP_OFFSET;
+
+        let mut jump = vec![0xFF, 0x25, 0x00, 0x00, 0x00, 0x00];
+        jump.extend_from_slice(&(self.func_ptr as u64).to_le_bytes());
+        inject_asm_code(&jump, self.jit_memory.add(RETIRED_JUMP_OFFSET));
+        let view = jit_writable_view(self.jit_memory);
+        let in_place = view == self.jit_memory;
+        if in_place {
+            set_code_writable(view.add(4), 2, true);
+        }
+        store_in_word(view.add(4), &[0xEB, (RETIRED_JUMP_OFFSET - 6) as u8]);
+        if in_place {
+            set_code_writable(view.add(4), 2, false);
+        }
+        clear_cache(self.jit_memory.add(4), self.jit_memory.add(6));
+
+        let mut retired = RETIRED_STUBS.lock().unwrap_or_else(|e| e.into_inner());
+        retired.push_back((
+            self.func_ptr as usize,
+            self.jit_memory as usize,
+            self.jit_size,
+            self.stub_counters.unwrap_or_default(),
+        ));
+        let evicted = if retired.len() > RETIRED_STUBS_KEPT {
+            retired.pop_front()
+        } else {
+            None
+        };
+        drop(retired);
+
+        if let Some((func_addr, jit_addr, jit_size, counters)) = evicted {
+            // A thread that entered between the wait and the redirect is still counted.
+            let oldest = RestoredStub {
+                func_ptr: func_addr as *mut u8,
+                jit_memory: jit_addr as *mut u8,
+                jit_size,
+                stub_counters: Some(counters),
+            };
+            match oldest.wait_for_quiescence() {
+                Ok(()) => free_jit_memory(oldest.jit_memory, oldest.jit_size),
+                Err(inside) => diagnostics::stub_leaked(func_addr, jit_addr, inside),
             }
+        }
+    }
 
-            // Explicitly flush cache and synchronize pipeline after restoring original bytes
-            clear_cache(self.func_ptr, self.func_ptr.add(self.patch_size));
+    #[cfg(not(target_arch = "x86_64"))]
+    unsafe fn retire_counting_stub(&self) {
+        if let Err(inside) = self.wait_for_quiescence() {
+            diagnostics::stub_leaked(self.func_ptr as usize, self.jit_memory as usize, inside);
+            return;
+        }
+
+        free_jit_memory(self.jit_memory, self.jit_size);
+    }
+
+    /// Waits until every thread that entered the JIT stub has left it. Returns the number of
+    /// threads still inside when `QUIESCENCE_TIMEOUT` elapses first.
+    fn wait_for_quiescence(&self) -> Result<(), u64> {
+        use std::sync::atomic::AtomicU64;
+        use std::sync::atomic::Ordering;
+
+        let Some(counters) = self.stub_counters else {
+            return Ok(());
+        };
+
+        let entered = unsafe { &*(counters as *const AtomicU64) };
+        let exited = unsafe { &*((counters + 8) as *const AtomicU64) };
+
+        let deadline = std::time::Instant::now() + QUIESCENCE_TIMEOUT;
+        loop {
+            // Load the exit counter first: a thread counted as exited was counted as entered.
+            let left = exited.load(Ordering::Acquire);
+            let inside = entered.load(Ordering::Acquire).wrapping_sub(left);
+            if inside == 0 {
+                return Ok(());
+            }
+
+            if std::time::Instant::now() >= deadline {
+                return Err(inside);
+            }
+
+            std::thread::yield_now();
         }
     }
 }
 
+/// Serializes code writes. Injectors patching different functions run in parallel, but two
+/// functions may share a page whose protection is changed while it is written.
+static PATCH_SECTION: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+/// Returns whether the process may map executable memory for JIT code, either anonymous or
+/// mapped twice from a memfd.
+pub(crate) fn executable_memory_available() -> bool {
+    anonymous_executable_memory_available() || jit_dual_mapped()
+}
+
+/// Returns whether JIT memory is mapped twice from a memfd, executable and writable, because
+/// anonymous memory cannot be made executable or `INJECTORPP_JIT=memfd` asks for it. Linux only.
+pub(crate) fn jit_dual_mapped() -> bool {
+    #[cfg(target_os = "linux")]
+    {
+        static DUAL_MAPPED: std::sync::OnceLock<bool> = std::sync::OnceLock::new();
+
+        *DUAL_MAPPED.get_or_init(|| {
+            let requested = std::env::var("INJECTORPP_JIT").is_ok_and(|jit| jit == "memfd");
+            (requested || !anonymous_executable_memory_available())
+                && crate::injector_core::dual_mapping::available()
+        })
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        false
+    }
+}
+
+/// Returns whether JIT code is never writable once executable, with `strict_wx` or when it is
+/// mapped twice. Data the code updates then needs pages of its own.
+pub(crate) fn jit_code_read_only() -> bool {
+    STRICT_WX || jit_dual_mapped()
+}
+
+/// Makes the pages holding the `len` bytes of JIT memory at `addr` writable, for data the JIT
+/// code updates, when `jit_code_read_only()`. They must hold no code.
+#[cfg(target_arch = "x86_64")]
+pub(crate) unsafe fn make_jit_data_writable(addr: *mut u8, len: usize) {
+    #[cfg(target_os = "linux")]
+    if jit_dual_mapped() {
+        crate::injector_core::dual_mapping::make_data_writable(addr, len);
+    }
+
+    // Anonymous JIT memory is mapped writable, and only its code is made executable.
+    let _ = (addr, len);
+}
+
+/// Returns the size of a page of JIT memory.
+#[cfg(target_arch = "x86_64")]
+pub(crate) fn jit_page_size() -> usize {
+    #[cfg(not(target_os = "windows"))]
+    {
+        unsafe { sysconf(_SC_PAGESIZE) as usize }
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        unsafe { get_page_size() }
+    }
+}
+
+/// Returns whether the process may map anonymous memory writable and executable, as JIT code
+/// needs. Hardened systems forbid it, such as SELinux with `deny_execmem`, PaX `MPROTECT` or
+/// macOS without the JIT entitlement. With `strict_wx`, whether memory mapped writable may be
+/// made executable instead. Probed once.
+fn anonymous_executable_memory_available() -> bool {
+    static AVAILABLE: std::sync::OnceLock<bool> = std::sync::OnceLock::new();
+
+    *AVAILABLE.get_or_init(|| unsafe {
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "macos",
+            target_os = "freebsd",
+            target_os = "netbsd"
+        ))]
+        {
+            #[cfg(target_os = "macos")]
+            let flags = libc::MAP_ANON | libc::MAP_PRIVATE | libc::MAP_JIT;
+            #[cfg(not(target_os = "macos"))]
+            let flags = libc::MAP_ANONYMOUS | libc::MAP_PRIVATE;
+
+            let size = sysconf(_SC_PAGESIZE) as usize;
+            let ptr = libc::mmap(std::ptr::null_mut(), size, JIT_PROT, flags, -1, 0);
+            if ptr == libc::MAP_FAILED {
+                return false;
+            }
+            let available = !STRICT_WX || libc::mprotect(ptr, size, PROT_READ | PROT_EXEC) == 0;
+            libc::munmap(ptr, size);
+            available
+        }
+
+        #[cfg(target_os = "windows")]
+        {
+            let ptr = VirtualAlloc(
+                std::ptr::null_mut(),
+                1,
+                MEM_COMMIT | MEM_RESERVE,
+                PAGE_EXECUTE_READWRITE,
+            );
+            if ptr.is_null() {
+                return false;
+            }
+            VirtualFree(ptr, 0, MEM_RELEASE);
+            true
+        }
+    })
+}
+
+/// Frees JIT memory allocated by `allocate_jit_memory`.
+pub(crate) unsafe fn free_jit_memory(jit_memory: *mut u8, jit_size: usize) {
+    match jit_arena::release(jit_memory) {
+        // The last block of its chunk, which is unmapped in turn.
+        Some(Some(chunk)) => free_jit_mapping(chunk, jit_arena::CHUNK_SIZE),
+        Some(None) => {}
+        None => free_jit_mapping(jit_memory, jit_size),
+    }
+}
+
+/// Unmaps the JIT memory of its own at `jit_memory`.
+unsafe fn free_jit_mapping(jit_memory: *mut u8, jit_size: usize) {
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "macos",
+        target_os = "freebsd",
+        target_os = "netbsd"
+    ))]
+    {
+        #[cfg(target_os = "linux")]
+        if jit_dual_mapped() && crate::injector_core::dual_mapping::unmap(jit_memory) {
+            return;
+        }
+
+        libc::munmap(jit_memory as *mut c_void, jit_size);
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        let _ = jit_size;
+        VirtualFree(jit_memory as *mut c_void, 0, MEM_RELEASE);
+    }
+}
+
+thread_local! {
+    /// Code writes deferred by `defer_patches()` on this thread, in order, or `None` when
+    /// writes are applied right away.
+    static DEFERRED_PATCHES: std::cell::RefCell<Option<Vec<CodeWrite>>> =
+        const { std::cell::RefCell::new(None) };
+}
+
+thread_local! {
+    /// Whether dropped guards leave their restores deferred, as `defer_restores()` runs.
+    static RESTORES_DEFERRED: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+
+    /// The stubs of the patches whose deferred restores are not applied yet.
+    static RESTORED_STUBS: std::cell::RefCell<Vec<RestoredStub>> =
+        const { std::cell::RefCell::new(Vec::new()) };
+}
+
+/// Patch bytes to write at a function address, as `(func_addr, patch)`.
+type CodeWrite = (usize, Vec<u8>);
+
+/// Runs `f`, which drops guards, and writes back the code of every patch they restore
+/// together, followed by a single cache maintenance pass, before their stubs are released.
+/// Outside of `defer_patches()` only, as restores within it write the patches prepared so far.
+pub(crate) fn defer_restores<R>(f: impl FnOnce() -> R) -> R {
+    struct Reset;
+
+    impl Drop for Reset {
+        fn drop(&mut self) {
+            let _ = RESTORES_DEFERRED.try_with(|deferred| deferred.set(false));
+        }
+    }
+
+    let deferring = DEFERRED_PATCHES
+        .try_with(|deferred| deferred.borrow().is_some())
+        .unwrap_or(true);
+    if deferring {
+        return f();
+    }
+
+    defer_patches(|| {
+        RESTORES_DEFERRED.with(|deferred| deferred.set(true));
+        let _reset = Reset;
+        f()
+    })
+}
+
+/// Runs `f`, deferring the code writes it makes on this thread, and applies them together
+/// once it returns or panics. Nested calls join the outermost one.
+pub(crate) fn defer_patches<R>(f: impl FnOnce() -> R) -> R {
+    struct Apply;
+
+    impl Drop for Apply {
+        fn drop(&mut self) {
+            apply_deferred_patches();
+            let _ = DEFERRED_PATCHES.try_with(|deferred| deferred.borrow_mut().take());
+        }
+    }
+
+    let outermost = DEFERRED_PATCHES.with(|deferred| {
+        let mut deferred = deferred.borrow_mut();
+        if deferred.is_some() {
+            return false;
+        }
+
+        *deferred = Some(Vec::new());
+        true
+    });
+
+    let _apply = outermost.then_some(Apply);
+    f()
+}
+
+/// Applies the code writes deferred on this thread so far.
+pub(crate) fn apply_deferred_patches() {
+    let patches = DEFERRED_PATCHES
+        .try_with(|deferred| deferred.borrow_mut().as_mut().map(std::mem::take))
+        .ok()
+        .flatten()
+        .unwrap_or_default();
+
+    if !patches.is_empty() {
+        unsafe { write_code(&merge_writes(patches)) };
+    }
+
+    let stubs = RESTORED_STUBS
+        .try_with(|stubs| std::mem::take(&mut *stubs.borrow_mut()))
+        .unwrap_or_default();
+    for stub in stubs {
+        unsafe { stub.release() };
+    }
+}
+
+/// Merges the writes to the same function, in order, so each function is written once with
+/// the bytes the last writes leave there.
+fn merge_writes(patches: Vec<CodeWrite>) -> Vec<CodeWrite> {
+    let mut merged: Vec<CodeWrite> = Vec::with_capacity(patches.len());
+    for (func_addr, patch) in patches {
+        match merged.iter_mut().find(|(addr, _)| *addr == func_addr) {
+            Some((_, bytes)) => {
+                if bytes.len() < patch.len() {
+                    bytes.resize(patch.len(), 0);
+                }
+                bytes[..patch.len()].copy_from_slice(&patch);
+            }
+            None => merged.push((func_addr, patch)),
+        }
+    }
+    merged
+}
+
 /// Unsafely patches the code at `func` with the given patch bytes.
 ///
+/// The write is deferred while `defer_patches()` runs on this thread.
+///
 /// # Safety
 ///
 /// The caller must ensure that `func` points to a valid, patchable code region.
-#[cfg(not(target_os = "macos"))]
 pub(crate) unsafe fn patch_function(func: *mut u8, patch: &[u8]) {
-    make_memory_writable_and_executable(func);
+    let deferred = DEFERRED_PATCHES
+        .try_with(|deferred| match deferred.borrow_mut().as_mut() {
+            Some(patches) => {
+                patches.push((func as usize, patch.to_vec()));
+                true
+            }
+            None => false,
+        })
+        .unwrap_or(false);
 
-    inject_asm_code(patch, func);
+    if !deferred {
+        write_code(&[(func as usize, patch.to_vec())]);
+    }
 }
 
+/// Writes each `(func_addr, patch)` pair in one critical section.
+#[cfg(not(target_os = "macos"))]
+unsafe fn write_code(patches: &[CodeWrite]) {
+    let _section = PATCH_SECTION.lock().unwrap_or_else(|e| e.into_inner());
+
+    if STRICT_WX {
+        write_code_strict_wx(patches);
+        return;
+    }
+
+    for (start, len) in patched_pages(patches, page_size()) {
+        make_memory_writable_and_executable(start as *mut u8, len);
+    }
+
+    write_live_code(patches);
+}
+
+/// Returns the pages holding the code of `patches`, as `(start, len)`, with pages shared by
+/// several patches and adjacent ones coalesced, so each is made writable once per write.
+#[cfg(not(target_os = "macos"))]
+fn patched_pages(patches: &[CodeWrite], page_size: usize) -> Vec<(usize, usize)> {
+    coalesce(patches.iter().map(|(func_addr, patch)| {
+        let start = func_addr & !(page_size - 1);
+        let end = (func_addr + patch.len().max(1)).next_multiple_of(page_size);
+        (start, end)
+    }))
+    .into_iter()
+    .map(|(start, end)| (start, end - start))
+    .collect()
+}
+
+/// Returns the size of the pages code is protected by.
+#[cfg(not(target_os = "macos"))]
+fn page_size() -> usize {
+    #[cfg(target_os = "windows")]
+    unsafe {
+        get_page_size()
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    unsafe {
+        sysconf(_SC_PAGESIZE) as usize
+    }
+}
+
+/// Writes each `(func_addr, patch)` pair without ever making a page writable and executable.
+///
+/// On Linux the pages are written as copies moved over the originals. On the BSDs they are
+/// made read-write in place, so code sharing them cannot run until they are made read-execute
+/// again, and other threads entering it fault.
+#[cfg(not(target_os = "macos"))]
+unsafe fn write_code_strict_wx(patches: &[CodeWrite]) {
+    #[cfg(all(feature = "strict_wx", target_os = "linux"))]
+    for (func_addr, patch) in patches {
+        write_code_remapped(*func_addr as *mut u8, patch);
+    }
+
+    #[cfg(not(all(feature = "strict_wx", target_os = "linux")))]
+    {
+        let pages = patched_pages(patches, page_size());
+        for &(start, len) in &pages {
+            set_code_writable(start as *mut u8, len, true);
+        }
+        for (func_addr, patch) in patches {
+            ptr::copy_nonoverlapping(patch.as_ptr(), *func_addr as *mut u8, patch.len());
+        }
+        clear_caches(
+            patches
+                .iter()
+                .map(|(func_addr, patch)| (*func_addr, patch.len())),
+        );
+        for &(start, len) in &pages {
+            set_code_writable(start as *mut u8, len, false);
+        }
+    }
+}
+
+/// Writes `patch` at `func` in a read-write copy of the pages holding it, then makes the copy
+/// read-execute and moves it over the original pages, as `write_code_macos` does. The pages are
+/// never writable and executable, and stay executable for the code sharing them, which may be
+/// the code doing the write.
+#[cfg(all(feature = "strict_wx", target_os = "linux"))]
+unsafe fn write_code_remapped(func: *mut u8, patch: &[u8]) {
+    let page_size = sysconf(_SC_PAGESIZE) as usize;
+    let start = func as usize & !(page_size - 1);
+    let len = (func as usize + patch.len()).next_multiple_of(page_size) - start;
+
+    let copy = libc::mmap(
+        std::ptr::null_mut(),
+        len,
+        PROT_READ | PROT_WRITE,
+        libc::MAP_ANONYMOUS | libc::MAP_PRIVATE,
+        -1,
+        0,
+    );
+    if copy == libc::MAP_FAILED {
+        panic!("mmap failed: {}", std::io::Error::last_os_error());
+    }
+
+    let copy = copy as *mut u8;
+    ptr::copy_nonoverlapping(start as *const u8, copy, len);
+    ptr::copy_nonoverlapping(patch.as_ptr(), copy.add(func as usize - start), patch.len());
+
+    if libc::mprotect(copy as *mut c_void, len, PROT_READ | PROT_EXEC) != 0
+        || libc::mremap(
+            copy as *mut c_void,
+            len,
+            len,
+            libc::MREMAP_MAYMOVE | libc::MREMAP_FIXED,
+            start as *mut c_void,
+        ) == libc::MAP_FAILED
+    {
+        panic!(
+            "remapping patched code failed: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+
+    clear_cache(func, func.add(patch.len()));
+    sync_cores();
+}
+
+/// An instruction branching to itself, used to hold threads entering a function being patched.
+#[cfg(all(not(target_os = "macos"), target_arch = "x86_64"))]
+const SELF_BRANCH: [u8; 2] = [0xEB, 0xFE]; // jmp $
+#[cfg(all(
+    not(target_os = "macos"),
+    any(target_arch = "aarch64", target_arch = "arm64ec")
+))]
+const SELF_BRANCH: [u8; 4] = 0x14000000u32.to_le_bytes(); // b .
+
+/// Writes each `(func_addr, patch)` pair over the start of a function other threads may be
+/// entering, so that none of them ever runs a half-written patch.
+///
+/// A patch fitting in one aligned 8-byte word is stored with a single atomic write. Otherwise
+/// the first instruction is atomically replaced with a branch to itself, the rest of the patch
+/// is written, and the first instruction is atomically replaced with the patched one. Threads
+/// entering the function in between spin on the first instruction until the patch is complete.
+/// Each step is applied to every function before cores are synchronized, and the patched first
+/// instructions are stored last, so the functions switch over together.
+///
+/// On x86_64 the two bytes of the branch to itself may cross an aligned word, for functions
+/// starting at the last byte of one. They are then exchanged with a locked instruction, atomic
+/// as long as they do not cross a cache line too. Functions whose first two bytes cross a
+/// cache line are written as is, which is reported through the diagnostics.
+///
+/// This only protects threads entering the function. A thread already past the first
+/// instruction of the overwritten bytes, such as one preempted inside a multi-instruction
+/// prologue, resumes in the middle of the patch and runs bytes that belong to neither version.
+/// Threads are not stopped to check for this. Threads past the overwritten bytes finish the
+/// original call unaffected.
+///
+/// On arm the instruction set of the function is not known here, so the patch is copied as is.
+#[cfg(not(target_os = "macos"))]
+unsafe fn write_live_code(patches: &[CodeWrite]) {
+    #[cfg(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec"
+    ))]
+    {
+        let head = SELF_BRANCH.len();
+        let (split, rest): (Vec<_>, Vec<_>) = patches.iter().partition(|(func_addr, patch)| {
+            let func = *func_addr as *mut u8;
+            !fits_in_word(func, patch.len()) && patch.len() >= head && fits_head(func, head)
+        });
+        let (atomic, plain): (Vec<_>, Vec<_>) = rest
+            .into_iter()
+            .partition(|(func_addr, patch)| fits_in_word(*func_addr as *mut u8, patch.len()));
+        for (func_addr, patch) in &plain {
+            crate::injector_core::diagnostics::patch_not_atomic(*func_addr, patch.len());
+        }
+
+        if !split.is_empty() {
+            for (func_addr, _) in &split {
+                store_head(*func_addr as *mut u8, &SELF_BRANCH);
+            }
+            clear_caches(split.iter().map(|(func_addr, _)| (*func_addr, head)));
+            sync_cores();
+
+            for (func_addr, patch) in &split {
+                let func = *func_addr as *mut u8;
+                ptr::copy_nonoverlapping(
+                    patch[head..].as_ptr(),
+                    func.add(head),
+                    patch.len() - head,
+                );
+            }
+            clear_caches(
+                split
+                    .iter()
+                    .map(|(func_addr, patch)| (func_addr + head, patch.len() - head)),
+            );
+            sync_cores();
+        }
+
+        for (func_addr, patch) in &plain {
+            ptr::copy_nonoverlapping(patch.as_ptr(), *func_addr as *mut u8, patch.len());
+        }
+        for (func_addr, patch) in &split {
+            store_head(*func_addr as *mut u8, &patch[..head]);
+        }
+        for (func_addr, patch) in &atomic {
+            store_in_word(*func_addr as *mut u8, patch);
+        }
+        clear_caches(
+            plain
+                .iter()
+                .chain(&atomic)
+                .map(|(func_addr, patch)| (*func_addr, patch.len()))
+                .chain(split.iter().map(|(func_addr, _)| (*func_addr, head))),
+        );
+        sync_cores();
+    }
+
+    #[cfg(not(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec"
+    )))]
+    {
+        for (func_addr, patch) in patches {
+            ptr::copy_nonoverlapping(patch.as_ptr(), *func_addr as *mut u8, patch.len());
+        }
+        clear_caches(
+            patches
+                .iter()
+                .map(|(func_addr, patch)| (*func_addr, patch.len())),
+        );
+        sync_cores();
+    }
+}
+
+/// Flushes the `(start, len)` ranges of written code from the instruction cache, once for
+/// each run of overlapping or adjacent ones.
+#[cfg(not(target_os = "macos"))]
+unsafe fn clear_caches(ranges: impl Iterator<Item = (usize, usize)>) {
+    for (start, end) in coalesce(ranges.map(|(start, len)| (start, start + len))) {
+        clear_cache(start as *mut u8, end as *mut u8);
+    }
+}
+
+/// Merges the overlapping and adjacent `(start, end)` ranges, in ascending order.
+#[cfg(not(target_os = "macos"))]
+fn coalesce(ranges: impl Iterator<Item = (usize, usize)>) -> Vec<(usize, usize)> {
+    let mut ranges: Vec<(usize, usize)> = ranges.collect();
+    ranges.sort_unstable();
+
+    let mut merged: Vec<(usize, usize)> = Vec::with_capacity(ranges.len());
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some((_, last_end)) if start <= *last_end => *last_end = (*last_end).max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+/// Makes every core running a thread of this process serialize its instruction stream, so
+/// none keeps executing instructions fetched before a patch.
+///
+/// `__clear_cache` broadcasts the cache maintenance, but its trailing `isb` only resynchronizes
+/// the calling core. `membarrier(MEMBARRIER_CMD_PRIVATE_EXPEDITED_SYNC_CORE)` (Linux 4.16+)
+/// synchronizes the others too. Older kernels fall back to `MEMBARRIER_CMD_PRIVATE_EXPEDITED`,
+/// whose interrupt returns are context synchronizing on arm64, and to nothing at all when
+/// membarrier is unavailable. The registration both commands need is done on first use.
+#[cfg(all(target_os = "linux", target_arch = "aarch64"))]
+fn sync_cores() {
+    const MEMBARRIER_CMD_QUERY: c_int = 0;
+    const MEMBARRIER_CMD_PRIVATE_EXPEDITED: c_int = 1 << 3;
+    const MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED: c_int = 1 << 4;
+    const MEMBARRIER_CMD_PRIVATE_EXPEDITED_SYNC_CORE: c_int = 1 << 5;
+    const MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED_SYNC_CORE: c_int = 1 << 6;
+
+    static COMMAND: std::sync::OnceLock<Option<c_int>> = std::sync::OnceLock::new();
+
+    let command = *COMMAND.get_or_init(|| unsafe {
+        let supported = libc::syscall(libc::SYS_membarrier, MEMBARRIER_CMD_QUERY, 0, 0);
+        if supported < 0 {
+            return None;
+        }
+
+        [
+            (
+                MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED_SYNC_CORE,
+                MEMBARRIER_CMD_PRIVATE_EXPEDITED_SYNC_CORE,
+            ),
+            (
+                MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED,
+                MEMBARRIER_CMD_PRIVATE_EXPEDITED,
+            ),
+        ]
+        .into_iter()
+        .find(|&(register, command)| {
+            supported as c_int & command != 0
+                && libc::syscall(libc::SYS_membarrier, register, 0, 0) == 0
+        })
+        .map(|(_, command)| command)
+    });
+
+    if let Some(command) = command {
+        unsafe {
+            libc::syscall(libc::SYS_membarrier, command, 0, 0);
+        }
+    }
+}
+
+#[cfg(not(all(target_os = "linux", target_arch = "aarch64")))]
+#[cfg_attr(target_os = "macos", allow(dead_code))]
+fn sync_cores() {}
+
+/// Returns whether `len` bytes at `dest` lie within one aligned 8-byte word.
+#[cfg(all(
+    not(target_os = "macos"),
+    any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec"
+    )
+))]
+fn fits_in_word(dest: *mut u8, len: usize) -> bool {
+    (dest as usize % 8) + len <= 8
+}
+
+/// Returns whether the first instruction of a patch at `dest`, `len` bytes long, can be
+/// replaced with `store_head()`: within one aligned 8-byte word, or on x86_64, for the two
+/// bytes of `SELF_BRANCH`, within one cache line.
+#[cfg(all(
+    not(target_os = "macos"),
+    any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec"
+    )
+))]
+fn fits_head(dest: *mut u8, len: usize) -> bool {
+    fits_in_word(dest, len)
+        || (cfg!(target_arch = "x86_64") && len == 2 && (dest as usize % 64) + len <= 64)
+}
+
+/// Atomically replaces the first instruction of a patch at `dest` with `bytes`, for which
+/// `fits_head()` holds. Two bytes crossing an aligned word are exchanged with `xchg`, whose
+/// implicit lock makes it atomic for any operand within one cache line.
+#[cfg(all(
+    not(target_os = "macos"),
+    any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec"
+    )
+))]
+unsafe fn store_head(dest: *mut u8, bytes: &[u8]) {
+    if fits_in_word(dest, bytes.len()) {
+        return store_in_word(dest, bytes);
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    {
+        let [low, high] = bytes else {
+            unreachable!("a head crossing a word is a two-byte branch");
+        };
+        let value = u16::from_le_bytes([*low, *high]);
+        std::arch::asm!(
+            "xchg word ptr [{dest}], {value:x}",
+            dest = in(reg) dest,
+            value = inout(reg) value => _,
+            options(nostack, preserves_flags),
+        );
+    }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    unreachable!("instructions are aligned to their size");
+}
+
+/// Atomically replaces `bytes` at `dest`, which must lie within one aligned 8-byte word,
+/// leaving the other bytes of the word untouched.
+#[cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec"
+))]
+#[cfg_attr(
+    all(target_os = "macos", not(target_arch = "x86_64")),
+    allow(dead_code)
+)]
+unsafe fn store_in_word(dest: *mut u8, bytes: &[u8]) {
+    use std::sync::atomic::AtomicU64;
+    use std::sync::atomic::Ordering;
+
+    let offset = dest as usize % 8;
+    let word = &*(dest.sub(offset) as *const AtomicU64);
+
+    let mut current = word.load(Ordering::Acquire);
+    loop {
+        let mut updated = current.to_le_bytes();
+        updated[offset..offset + bytes.len()].copy_from_slice(bytes);
+
+        match word.compare_exchange_weak(
+            current,
+            u64::from_le_bytes(updated),
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        ) {
+            Ok(_) => return,
+            Err(actual) => current = actual,
+        }
+    }
+}
+
+/// Writes each `(func_addr, patch)` pair in one critical section.
 #[cfg(target_os = "macos")]
-pub(crate) unsafe fn patch_function(func: *mut u8, patch: &[u8]) {
+unsafe fn write_code(patches: &[CodeWrite]) {
+    if let Some(message) = code_patching_denied_message() {
+        panic!("{}", message);
+    }
+
+    let _section = PATCH_SECTION.lock().unwrap_or_else(|e| e.into_inner());
+
+    for (func_addr, patch) in patches {
+        write_code_macos(*func_addr as *mut u8, patch);
+    }
+}
+
+#[cfg(target_os = "macos")]
+unsafe fn write_code_macos(func: *mut u8, patch: &[u8]) {
+    use mach2::kern_return::{kern_return_t, KERN_SUCCESS};
     use mach2::traps::mach_task_self;
     use mach2::vm::{mach_vm_protect, mach_vm_remap};
     use mach2::vm_inherit::VM_INHERIT_NONE;
     use mach2::vm_prot::VM_PROT_COPY;
     use mach2::vm_statistics::{VM_FLAGS_ANYWHERE, VM_FLAGS_OVERWRITE, VM_FLAGS_RETURN_DATA_ADDR};
 
+    // A failure would otherwise surface as a bus error on the next call of the function.
+    let check = |result: kern_return_t, call: &str| {
+        if result != KERN_SUCCESS {
+            panic!(
+                "Failed to patch the code at {:p}: {} returned {}",
+                func, call, result
+            );
+        }
+    };
+
     let mut addr = func as mach_vm_address_t;
     let mut remap: mach_vm_address_t = std::mem::zeroed();
     let mut cur: vm_prot_t = std::mem::zeroed();
     let mut max: vm_prot_t = std::mem::zeroed();
-    mach_vm_remap(
+    let result = mach_vm_remap(
         mach_task_self(),
         &mut remap,
         patch.len() as u64,
@@ -346,30 +1695,33 @@ pub(crate) unsafe fn patch_function(func: *mut u8, patch: &[u8]) {
         &mut max,
         VM_INHERIT_NONE,
     );
+    check(result, "mach_vm_remap");
 
-    mach_vm_protect(
+    let result = mach_vm_protect(
         mach_task_self(),
         remap,
         0x8,
         0,
         VM_PROT_READ | VM_PROT_WRITE | VM_PROT_COPY,
     );
+    check(result, "mach_vm_protect");
 
     inject_asm_code(patch, remap as *mut u8);
 
     sys_dcache_flush(func, patch.len());
 
-    mach_vm_protect(
+    let result = mach_vm_protect(
         mach_task_self(),
         remap,
         0x8,
         0,
         VM_PROT_READ | VM_PROT_EXECUTE,
     );
+    check(result, "mach_vm_protect");
 
     sys_icache_invalidate(func, patch.len());
 
-    mach_vm_remap(
+    let result = mach_vm_remap(
         mach_task_self(),
         &mut addr,
         patch.len() as u64,
@@ -382,31 +1734,29 @@ pub(crate) unsafe fn patch_function(func: *mut u8, patch: &[u8]) {
         &mut max,
         VM_INHERIT_NONE,
     );
+    check(result, "mach_vm_remap");
 }
 
 // MacOS forces memory to be writable or executable but not both. So we don't need an
 // implementation for it.
 #[cfg(not(target_os = "macos"))]
-unsafe fn make_memory_writable_and_executable(func: *mut u8) {
-    #[cfg(target_os = "linux")]
+unsafe fn make_memory_writable_and_executable(start: *mut u8, len: usize) {
+    #[cfg(any(target_os = "linux", target_os = "freebsd", target_os = "netbsd"))]
     {
-        make_memory_writable_and_executable_linux(func);
+        make_memory_writable_and_executable_unix(start, len);
     }
 
     #[cfg(target_os = "windows")]
     {
-        make_memory_writable_and_executable_windows(func);
+        make_memory_writable_and_executable_windows(start, len);
     }
 }
 
-#[cfg(target_os = "linux")]
-unsafe fn make_memory_writable_and_executable_linux(func: *mut u8) {
-    let page_size = sysconf(_SC_PAGESIZE) as usize;
-    let addr = func as usize;
-    let page_start = addr & !(page_size - 1);
+#[cfg(any(target_os = "linux", target_os = "freebsd", target_os = "netbsd"))]
+unsafe fn make_memory_writable_and_executable_unix(start: *mut u8, len: usize) {
     if libc::mprotect(
-        page_start as *mut c_void,
-        page_size,
+        start as *mut c_void,
+        len,
         PROT_READ | PROT_WRITE | PROT_EXEC,
     ) != 0
     {
@@ -415,39 +1765,91 @@ unsafe fn make_memory_writable_and_executable_linux(func: *mut u8) {
 }
 
 #[cfg(target_os = "windows")]
-unsafe fn make_memory_writable_and_executable_windows(func: *const u8) {
+unsafe fn make_memory_writable_and_executable_windows(start: *mut u8, len: usize) {
+    let protect = |start: usize, len: usize| {
+        let mut old_protect: u32 = 0;
+        VirtualProtect(
+            start as *mut c_void,
+            len,
+            code_write_protection(),
+            &mut old_protect,
+        ) != 0
+    };
+
+    // Adjacent pages may belong to different allocations, which are protected one at a time.
+    if protect(start as usize, len) {
+        return;
+    }
     let page_size = get_page_size();
-    let addr = func as usize;
-    let page_start = addr & !(page_size - 1);
-
-    let mut old_protect: u32 = 0;
-
-    let result = VirtualProtect(
-        page_start as *mut c_void,
-        page_size,
-        PAGE_EXECUTE_READWRITE,
-        &mut old_protect,
-    );
-
-    if result == 0 {
-        panic!("VirtualProtect failed");
+    for page in (start as usize..start as usize + len).step_by(page_size) {
+        if !protect(page, page_size) {
+            panic!("VirtualProtect failed");
+        }
     }
 }
 
 pub(crate) unsafe fn inject_asm_code(asm_code: &[u8], dest: *mut u8) {
-    #[cfg(target_os = "macos")]
-    pthread_jit_write_protect_np(0);
+    let view = jit_writable_view(dest);
+    if view != dest {
+        ptr::copy_nonoverlapping(asm_code.as_ptr(), view, asm_code.len());
+        clear_cache(dest, dest.add(asm_code.len()));
+        return;
+    }
 
+    set_code_writable(dest, asm_code.len(), true);
     ptr::copy_nonoverlapping(asm_code.as_ptr(), dest, asm_code.len());
+    set_code_writable(dest, asm_code.len(), false);
+
+    clear_cache(dest, dest.add(asm_code.len()));
+}
+
+/// Returns the address JIT code at `addr` is written through: its writable view when it is
+/// mapped twice, and `addr` itself otherwise.
+pub(crate) fn jit_writable_view(addr: *mut u8) -> *mut u8 {
+    #[cfg(target_os = "linux")]
+    if jit_dual_mapped() {
+        return crate::injector_core::dual_mapping::writable_view(addr);
+    }
 
+    addr
+}
+
+/// With `strict_wx`, makes the pages holding the `len` bytes at `start` read-write, or
+/// read-execute once they are written. On macOS, switches the `MAP_JIT` memory of the current
+/// thread to writable or executable instead. Does nothing otherwise.
+pub(crate) unsafe fn set_code_writable(start: *mut u8, len: usize, writable: bool) {
     #[cfg(target_os = "macos")]
-    pthread_jit_write_protect_np(1);
+    pthread_jit_write_protect_np(!writable as libc::c_int);
 
-    clear_cache(dest, dest.add(asm_code.len()));
+    #[cfg(all(
+        feature = "strict_wx",
+        any(target_os = "linux", target_os = "freebsd", target_os = "netbsd")
+    ))]
+    {
+        let page_size = sysconf(_SC_PAGESIZE) as usize;
+        let page_start = start as usize & !(page_size - 1);
+        let page_end = (start as usize + len).next_multiple_of(page_size);
+        let prot = if writable {
+            PROT_READ | PROT_WRITE
+        } else {
+            PROT_READ | PROT_EXEC
+        };
+        if libc::mprotect(page_start as *mut c_void, page_end - page_start, prot) != 0 {
+            panic!("mprotect failed: {}", std::io::Error::last_os_error());
+        }
+    }
+
+    #[cfg(not(all(
+        feature = "strict_wx",
+        any(target_os = "linux", target_os = "freebsd", target_os = "netbsd")
+    )))]
+    {
+        let _ = (start, len, writable);
+    }
 }
 
 unsafe fn clear_cache(start: *mut u8, end: *mut u8) {
-    #[cfg(target_os = "linux")]
+    #[cfg(any(target_os = "linux", target_os = "freebsd", target_os = "netbsd"))]
     {
         __clear_cache(start, end)
     }
@@ -465,20 +1867,29 @@ unsafe fn clear_cache(start: *mut u8, end: *mut u8) {
 
     #[cfg(target_os = "macos")]
     {
-        // The cache is invalidated in patch_function.
-        let _ = start;
-        let _ = end;
+        sys_icache_invalidate(start, end.offset_from(start) as usize);
     }
 
     // On ARM64, explicitly synchronize the CPU pipeline.
-    #[cfg(target_arch = "aarch64")]
+    #[cfg(any(target_arch = "aarch64", target_arch = "arm64ec"))]
     {
         core::arch::asm!("dsb sy", "isb", options(nostack, nomem));
     }
+
+    // On RISC-V, `__clear_cache` has the kernel flush the instruction caches of every hart,
+    // and fence.i orders this hart's instruction fetches after the write.
+    #[cfg(target_arch = "riscv64")]
+    {
+        core::arch::asm!("fence.i", options(nostack, nomem));
+    }
 }
 
 #[cfg(test)]
-#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+#[cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec"
+))]
 mod tests {
     use super::*;
 
@@ -520,16 +1931,97 @@ mod tests {
         );
 
         // Clean up
-        unsafe {
-            #[cfg(any(target_os = "linux", target_os = "macos"))]
-            {
-                libc::munmap(jit_ptr as *mut c_void, 256);
-            }
-            #[cfg(target_os = "windows")]
-            {
-                VirtualFree(jit_ptr as *mut c_void, 0, MEM_RELEASE);
-            }
-        }
+        unsafe { free_jit_memory(jit_ptr, 256) };
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_free_gaps_near_should_be_unmapped_and_nearest_first() {
+        let func_addr = dummy_target_function as fn() -> i32 as usize as u64;
+        let page_size = unsafe { sysconf(_SC_PAGESIZE) as u64 };
+        let max_range: u64 = 0x8000000;
+
+        let gaps = free_gaps_near(func_addr, 256, max_range, page_size);
+        assert!(!gaps.is_empty());
+        let distances: Vec<u64> = gaps.iter().map(|gap| gap.abs_diff(func_addr)).collect();
+        assert!(distances.is_sorted());
+
+        let hint = gaps[0];
+        assert_eq!(hint % page_size, 0);
+        assert!(hint.abs_diff(func_addr) <= max_range);
+        let ptr = unsafe {
+            libc::mmap(
+                hint as *mut c_void,
+                256,
+                PROT_READ | PROT_WRITE,
+                MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED_NOREPLACE,
+                -1,
+                0,
+            )
+        };
+        assert_eq!(ptr as u64, hint);
+        unsafe { libc::munmap(ptr, 256) };
+    }
+
+    #[test]
+    #[cfg(target_os = "windows")]
+    fn test_free_regions_near_should_be_free_and_nearest_first() {
+        let func_addr = dummy_target_function as fn() -> i32 as usize;
+        let max_range = 0x8000000;
+
+        let regions = free_regions_near(func_addr, 256, max_range);
+        assert!(!regions.is_empty());
+        let distances: Vec<usize> = regions
+            .iter()
+            .map(|hint| hint.abs_diff(func_addr))
+            .collect();
+        assert!(distances.is_sorted());
+
+        let hint = regions[0];
+        assert!(hint.abs_diff(func_addr) <= max_range);
+        let ptr = unsafe {
+            VirtualAlloc(
+                hint as *mut c_void,
+                256,
+                MEM_COMMIT | MEM_RESERVE,
+                PAGE_EXECUTE_READWRITE,
+            )
+        };
+        assert_eq!(ptr as usize, hint);
+        unsafe { VirtualFree(ptr, 0, MEM_RELEASE) };
+    }
+
+    #[test]
+    #[cfg(not(target_os = "macos"))]
+    fn test_patched_pages_should_coalesce_shared_and_adjacent_pages() {
+        let patches = vec![
+            (0x3010, vec![0; 8]),
+            (0x1ff8, vec![0; 16]),
+            (0x1000, vec![0; 8]),
+            (0x1800, vec![0; 8]),
+            (0x6000, vec![0; 8]),
+        ];
+
+        assert_eq!(
+            patched_pages(&patches, 0x1000),
+            vec![(0x1000, 0x3000), (0x6000, 0x1000)]
+        );
+    }
+
+    #[test]
+    fn test_merge_writes_should_leave_last_bytes_written() {
+        let patches = vec![
+            (0x1000, vec![1, 1, 1, 1]),
+            (0x2000, vec![2, 2]),
+            (0x1000, vec![3, 3]),
+            (0x1000, vec![4, 4, 4, 4, 4, 4]),
+            (0x1000, vec![5]),
+        ];
+
+        assert_eq!(
+            merge_writes(patches),
+            vec![(0x1000, vec![5, 4, 4, 4, 4, 4]), (0x2000, vec![2, 2])]
+        );
     }
 
     /// Verify that JIT allocation does NOT land in the current thread's stack region.
@@ -564,15 +2056,6 @@ mod tests {
         );
 
         // Clean up
-        unsafe {
-            #[cfg(any(target_os = "linux", target_os = "macos"))]
-            {
-                libc::munmap(jit_ptr as *mut c_void, 256);
-            }
-            #[cfg(target_os = "windows")]
-            {
-                VirtualFree(jit_ptr as *mut c_void, 0, MEM_RELEASE);
-            }
-        }
+        unsafe { free_jit_memory(jit_ptr, 256) };
     }
 }
@@ -0,0 +1,795 @@
+//! Demangles the names of C++ functions, mangled with the Itanium C++ ABI by GCC and Clang or
+//! with the scheme of MSVC, so C++ functions can be looked up by name and told apart.
+//!
+//! Only the parts of the schemes functions are commonly named with are understood: nested
+//! names, templates, constructors, destructors and operators, with parameters of builtin,
+//! class, pointer and reference types. Symbols with function pointer or array parameters,
+//! local names or template arguments of expressions are not demangled. Itanium symbols are
+//! demangled as `c++filt` does, without the return type of template functions and the suffix
+//! of clones, and MSVC symbols to the qualified name of the function only, as in
+//! `net::Socket::connect`.
+
+/// Returns the demangled name of the C++ function whose symbol is `symbol`, or `None` when
+/// `symbol` is not a mangled C++ function, or uses parts of the scheme not understood.
+pub(crate) fn demangle(symbol: &str) -> Option<String> {
+    if symbol.starts_with('?') {
+        msvc(symbol)
+    } else {
+        itanium(symbol)
+    }
+}
+
+/// Returns whether `symbol` is mangled with either scheme, rather than a name to demangle.
+pub(crate) fn is_mangled(symbol: &str) -> bool {
+    symbol.starts_with("_Z") || symbol.starts_with("__Z") || symbol.starts_with('?')
+}
+
+/// Returns the name demangled by `demangle()` without its parameters and the qualifiers of
+/// the object it is called on, as in `net::Socket::connect`.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+pub(crate) fn without_params(name: &str) -> &str {
+    // Parameters of the types understood have no parentheses, unlike `operator()`.
+    name.rfind(')')
+        .and_then(|end| name[..end].rfind('('))
+        .map_or(name, |start| &name[..start])
+}
+
+fn itanium(symbol: &str) -> Option<String> {
+    // Mach-O symbols start with an extra underscore, and clones of functions, such as
+    // `.cold` or `.constprop.0`, have a suffix.
+    let symbol = symbol
+        .strip_prefix("__Z")
+        .or_else(|| symbol.strip_prefix("_Z"))?;
+    let mangled = symbol.split('.').next()?;
+
+    let mut parser = Parser {
+        input: mangled.as_bytes(),
+        pos: 0,
+        subs: Vec::new(),
+        template_args: Vec::new(),
+        depth: 0,
+    };
+    let name = parser.encoding()?;
+    (parser.pos == parser.input.len()).then_some(name)
+}
+
+/// A name parsed by `Parser::name()`.
+struct Name {
+    text: String,
+    /// Whether the name ends with template arguments, so a function is followed by its return
+    /// type.
+    template: bool,
+    /// Whether the name is a constructor, a destructor or a conversion operator, which have no
+    /// return type even as templates.
+    special: bool,
+    /// The qualifiers of the object a member function is called on, such as ` const`.
+    qualifiers: String,
+}
+
+impl Name {
+    fn plain(text: String) -> Self {
+        Self {
+            text,
+            template: false,
+            special: false,
+            qualifiers: String::new(),
+        }
+    }
+}
+
+/// A parser of the `<encoding>` of a mangled Itanium symbol, following the grammar of the
+/// Itanium C++ ABI.
+struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
+    /// The components substitutions refer to, in order.
+    subs: Vec<String>,
+    /// The template arguments of the function, which template parameters refer to.
+    template_args: Vec<String>,
+    /// How deep in the types of the function the parser is.
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.pos).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<u8> {
+        self.input.get(self.pos + offset).copied()
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let c = self.peek()?;
+        self.pos += 1;
+        Some(c)
+    }
+
+    fn eat(&mut self, c: u8) -> bool {
+        let found = self.peek() == Some(c);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn eat_std(&mut self) -> bool {
+        let found = self.peek() == Some(b'S') && self.peek_at(1) == Some(b't');
+        if found {
+            self.pos += 2;
+        }
+        found
+    }
+
+    /// `<encoding> ::= <name> <bare-function-type>`
+    fn encoding(&mut self) -> Option<String> {
+        // Special names, such as vtables, typeinfo and thunks, are not functions.
+        if matches!(self.peek(), Some(b'T' | b'G')) {
+            return None;
+        }
+        let name = self.name()?;
+        // A name without parameters is a variable, as are Rust functions mangled with the
+        // legacy scheme, which shares the names of Itanium.
+        self.peek()?;
+
+        if name.template && !name.special {
+            self.ty()?;
+        }
+        let params = if self.input[self.pos..] == *b"v" {
+            self.pos += 1;
+            String::new()
+        } else {
+            let mut params = Vec::new();
+            while self.peek().is_some() {
+                params.push(self.ty()?);
+            }
+            params.join(", ")
+        };
+        Some(format!("{}({}){}", name.text, params, name.qualifiers))
+    }
+
+    /// `<name> ::= <nested-name> | <unscoped-name> [<template-args>]
+    ///          | <substitution> <template-args>`
+    fn name(&mut self) -> Option<Name> {
+        match self.peek()? {
+            b'N' => self.nested_name(),
+            // Local names, of functions declared in functions.
+            b'Z' => None,
+            b'S' if self.peek_at(1) != Some(b't') => {
+                let text = self.substitution()?;
+                if self.peek() != Some(b'I') {
+                    return None;
+                }
+                let args = self.template_args()?;
+                Some(Name {
+                    template: true,
+                    ..Name::plain(text + &args)
+                })
+            }
+            _ => {
+                let std = self.eat_std();
+                let (text, special) = self.unqualified_name("")?;
+                let text = if std { format!("std::{}", text) } else { text };
+                if self.peek() != Some(b'I') {
+                    return Some(Name {
+                        special,
+                        ..Name::plain(text)
+                    });
+                }
+                self.subs.push(text.clone());
+                let args = self.template_args()?;
+                Some(Name {
+                    template: true,
+                    special,
+                    ..Name::plain(text + &args)
+                })
+            }
+        }
+    }
+
+    /// `<nested-name> ::= N [<CV-qualifiers>] [<ref-qualifier>] <prefix> E`
+    fn nested_name(&mut self) -> Option<Name> {
+        self.pos += 1;
+        let mut qualifiers = self.cv_qualifiers();
+        if self.eat(b'R') {
+            qualifiers.push_str(" &");
+        } else if self.eat(b'O') {
+            qualifiers.push_str(" &&");
+        }
+
+        let mut prefix = String::new();
+        let mut template = false;
+        let mut special = false;
+        while !self.eat(b'E') {
+            template = false;
+            special = false;
+            match self.peek()? {
+                // Substitutions are substitutable already, and `std` is not.
+                b'S' if prefix.is_empty() => {
+                    if self.eat_std() {
+                        prefix.push_str("std");
+                    } else {
+                        prefix = self.substitution()?;
+                    }
+                    continue;
+                }
+                b'I' if !prefix.is_empty() => {
+                    prefix += &self.template_args()?;
+                    template = true;
+                }
+                b'T' if prefix.is_empty() => prefix = self.template_param()?,
+                _ => {
+                    let (text, is_special) = self.unqualified_name(&prefix)?;
+                    if !prefix.is_empty() {
+                        prefix.push_str("::");
+                    }
+                    prefix.push_str(&text);
+                    special = is_special;
+                }
+            }
+            // Every prefix of the name is substitutable, but not the name itself.
+            if self.peek() != Some(b'E') {
+                self.subs.push(prefix.clone());
+            }
+        }
+
+        Some(Name {
+            text: prefix,
+            template,
+            special,
+            qualifiers,
+        })
+    }
+
+    /// `<CV-qualifiers> ::= [r] [V] [K]`, printed after what they qualify.
+    fn cv_qualifiers(&mut self) -> String {
+        let restrict = self.eat(b'r');
+        let volatile = self.eat(b'V');
+        let constant = self.eat(b'K');
+
+        let mut qualifiers = String::new();
+        if constant {
+            qualifiers.push_str(" const");
+        }
+        if volatile {
+            qualifiers.push_str(" volatile");
+        }
+        if restrict {
+            qualifiers.push_str(" restrict");
+        }
+        qualifiers
+    }
+
+    /// `<unqualified-name> ::= <source-name> | <operator-name> | <ctor-dtor-name>`, followed by
+    /// its ABI tags. Returns the name and whether it is a constructor, a destructor or a
+    /// conversion operator. Constructors and destructors are named after the class `scope`.
+    fn unqualified_name(&mut self, scope: &str) -> Option<(String, bool)> {
+        let (mut name, special) = match self.peek()? {
+            b'0'..=b'9' => (self.source_name()?, false),
+            // Functions with internal linkage, as GCC mangles them.
+            b'L' => {
+                self.pos += 1;
+                (self.source_name()?, false)
+            }
+            b'C' if matches!(self.peek_at(1), Some(b'1'..=b'5')) => {
+                self.pos += 2;
+                (class_name(scope)?.to_string(), true)
+            }
+            b'D' if matches!(self.peek_at(1), Some(b'0'..=b'5')) => {
+                self.pos += 2;
+                (format!("~{}", class_name(scope)?), true)
+            }
+            b'a'..=b'z' => self.operator_name()?,
+            _ => return None,
+        };
+
+        while self.eat(b'B') {
+            name.push_str(&format!("[abi:{}]", self.source_name()?));
+        }
+        Some((name, special))
+    }
+
+    /// `<source-name> ::= <length> <identifier>`
+    fn source_name(&mut self) -> Option<String> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        let len: usize = std::str::from_utf8(&self.input[start..self.pos])
+            .ok()?
+            .parse()
+            .ok()?;
+        let identifier = self.input.get(self.pos..self.pos + len)?;
+        self.pos += len;
+
+        let identifier = std::str::from_utf8(identifier).ok()?;
+        if identifier.starts_with("_GLOBAL__N") {
+            return Some("(anonymous namespace)".to_string());
+        }
+        Some(identifier.to_string())
+    }
+
+    /// `<operator-name>`, returned with whether it is a conversion operator.
+    fn operator_name(&mut self) -> Option<(String, bool)> {
+        let code = self.input.get(self.pos..self.pos + 2)?;
+        self.pos += 2;
+        let op = match code {
+            b"cv" => return Some((format!("operator {}", self.ty()?), true)),
+            b"li" => return Some((format!("operator\"\" {}", self.source_name()?), false)),
+            b"nw" => "new",
+            b"na" => "new[]",
+            b"dl" => "delete",
+            b"da" => "delete[]",
+            b"ps" | b"pl" => "+",
+            b"ng" | b"mi" => "-",
+            b"ad" | b"an" => "&",
+            b"de" | b"ml" => "*",
+            b"co" => "~",
+            b"dv" => "/",
+            b"rm" => "%",
+            b"or" => "|",
+            b"eo" => "^",
+            b"aS" => "=",
+            b"pL" => "+=",
+            b"mI" => "-=",
+            b"mL" => "*=",
+            b"dV" => "/=",
+            b"rM" => "%=",
+            b"aN" => "&=",
+            b"oR" => "|=",
+            b"eO" => "^=",
+            b"ls" => "<<",
+            b"rs" => ">>",
+            b"lS" => "<<=",
+            b"rS" => ">>=",
+            b"eq" => "==",
+            b"ne" => "!=",
+            b"lt" => "<",
+            b"gt" => ">",
+            b"le" => "<=",
+            b"ge" => ">=",
+            b"ss" => "<=>",
+            b"nt" => "!",
+            b"aa" => "&&",
+            b"oo" => "||",
+            b"pp" => "++",
+            b"mm" => "--",
+            b"cm" => ",",
+            b"pm" => "->*",
+            b"pt" => "->",
+            b"cl" => "()",
+            b"ix" => "[]",
+            _ => return None,
+        };
+        let space = if op.starts_with(char::is_alphabetic) {
+            " "
+        } else {
+            ""
+        };
+        Some((format!("operator{}{}", space, op), false))
+    }
+
+    /// `<template-args> ::= I <template-arg>+ E`
+    fn template_args(&mut self) -> Option<String> {
+        self.pos += 1;
+        let mut args = Vec::new();
+        while !self.eat(b'E') {
+            args.push(self.template_arg()?);
+        }
+
+        let text = args.join(", ");
+        // Closing angle brackets are kept apart, as before C++11.
+        let text = if text.ends_with('>') {
+            format!("<{} >", text)
+        } else {
+            format!("<{}>", text)
+        };
+        if self.depth == 0 {
+            self.template_args = args;
+        }
+        Some(text)
+    }
+
+    /// `<template-arg> ::= <type> | L <type> <value> E | J <template-arg>* E`
+    fn template_arg(&mut self) -> Option<String> {
+        match self.peek()? {
+            b'L' => {
+                self.pos += 1;
+                let ty = self.ty()?;
+                let negative = self.eat(b'n');
+                let start = self.pos;
+                while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                    self.pos += 1;
+                }
+                let value = std::str::from_utf8(&self.input[start..self.pos]).ok()?;
+                if value.is_empty() || !self.eat(b'E') {
+                    return None;
+                }
+                let sign = if negative { "-" } else { "" };
+                Some(match ty.as_str() {
+                    "bool" => (if value == "0" { "false" } else { "true" }).to_string(),
+                    "int" => format!("{}{}", sign, value),
+                    "unsigned int" => format!("{}{}u", sign, value),
+                    "long" => format!("{}{}l", sign, value),
+                    "unsigned long" => format!("{}{}ul", sign, value),
+                    _ => format!("({}){}{}", ty, sign, value),
+                })
+            }
+            b'J' => {
+                self.pos += 1;
+                let mut args = Vec::new();
+                while !self.eat(b'E') {
+                    args.push(self.template_arg()?);
+                }
+                Some(args.join(", "))
+            }
+            // Expressions.
+            b'X' => None,
+            _ => self.ty(),
+        }
+    }
+
+    /// `<template-param> ::= T_ | T <number> _`
+    fn template_param(&mut self) -> Option<String> {
+        self.pos += 1;
+        let index = self.seq_id()?;
+        self.template_args.get(index).cloned()
+    }
+
+    /// `<substitution> ::= S_ | S <seq-id> _`, or one of the abbreviations of `std` names.
+    fn substitution(&mut self) -> Option<String> {
+        self.pos += 1;
+        let abbreviation = match self.peek()? {
+            b'a' => "std::allocator",
+            b'b' => "std::basic_string",
+            b's' => "std::basic_string<char, std::char_traits<char>, std::allocator<char> >",
+            b'i' => "std::basic_istream<char, std::char_traits<char> >",
+            b'o' => "std::basic_ostream<char, std::char_traits<char> >",
+            b'd' => "std::basic_iostream<char, std::char_traits<char> >",
+            _ => {
+                let index = self.seq_id()?;
+                return self.subs.get(index).cloned();
+            }
+        };
+        self.pos += 1;
+        Some(abbreviation.to_string())
+    }
+
+    /// Parses the base 36 number ending with `_` of substitutions and template parameters,
+    /// returning 0 for a lone `_` and the number plus 1 otherwise.
+    fn seq_id(&mut self) -> Option<usize> {
+        if self.eat(b'_') {
+            return Some(0);
+        }
+        let mut id = 0usize;
+        loop {
+            let digit = match self.next()? {
+                c @ b'0'..=b'9' => c - b'0',
+                c @ b'A'..=b'Z' => c - b'A' + 10,
+                b'_' => return id.checked_add(1),
+                _ => return None,
+            };
+            id = id.checked_mul(36)?.checked_add(digit as usize)?;
+        }
+    }
+
+    /// `<type>`, keeping track of how deep in types the parser is.
+    fn ty(&mut self) -> Option<String> {
+        self.depth += 1;
+        let ty = self.ty_inner();
+        self.depth -= 1;
+        ty
+    }
+
+    fn ty_inner(&mut self) -> Option<String> {
+        let c = self.peek()?;
+        if let Some(builtin) = builtin_type(c) {
+            self.pos += 1;
+            return Some(builtin.to_string());
+        }
+
+        let ty = match c {
+            b'r' | b'V' | b'K' => {
+                let qualifiers = self.cv_qualifiers();
+                self.ty()? + &qualifiers
+            }
+            b'P' => {
+                self.pos += 1;
+                self.ty()? + "*"
+            }
+            b'R' => {
+                self.pos += 1;
+                self.ty()? + "&"
+            }
+            b'O' => {
+                self.pos += 1;
+                self.ty()? + "&&"
+            }
+            b'D' => {
+                let builtin = match self.peek_at(1)? {
+                    b'n' => "decltype(nullptr)",
+                    b'i' => "char32_t",
+                    b's' => "char16_t",
+                    b'u' => "char8_t",
+                    b'a' => "auto",
+                    b'h' => "half",
+                    b'p' => {
+                        self.pos += 2;
+                        let ty = self.ty()?;
+                        self.subs.push(ty.clone());
+                        return Some(ty);
+                    }
+                    _ => return None,
+                };
+                self.pos += 2;
+                return Some(builtin.to_string());
+            }
+            b'T' => self.template_param()?,
+            b'S' if self.peek_at(1) != Some(b't') => {
+                // Substitutions are substitutable already, unless given template arguments.
+                let ty = self.substitution()?;
+                if self.peek() != Some(b'I') {
+                    return Some(ty);
+                }
+                ty + &self.template_args()?
+            }
+            b'N' | b'S' | b'0'..=b'9' => self.name()?.text,
+            // Function, array and pointer to member types.
+            _ => return None,
+        };
+        self.subs.push(ty.clone());
+        Some(ty)
+    }
+}
+
+/// Returns the builtin type of the code `c`.
+fn builtin_type(c: u8) -> Option<&'static str> {
+    Some(match c {
+        b'v' => "void",
+        b'w' => "wchar_t",
+        b'b' => "bool",
+        b'c' => "char",
+        b'a' => "signed char",
+        b'h' => "unsigned char",
+        b's' => "short",
+        b't' => "unsigned short",
+        b'i' => "int",
+        b'j' => "unsigned int",
+        b'l' => "long",
+        b'm' => "unsigned long",
+        b'x' => "long long",
+        b'y' => "unsigned long long",
+        b'n' => "__int128",
+        b'o' => "unsigned __int128",
+        b'f' => "float",
+        b'd' => "double",
+        b'e' => "long double",
+        b'g' => "__float128",
+        b'z' => "...",
+        _ => return None,
+    })
+}
+
+/// Returns the name of the class `scope` names, which its constructors are named after.
+fn class_name(scope: &str) -> Option<&str> {
+    let mut name = scope;
+    // Without the template arguments of a class template.
+    if name.ends_with('>') {
+        let mut depth = 0;
+        for (i, c) in name.char_indices().rev() {
+            match c {
+                '>' => depth += 1,
+                '<' => depth -= 1,
+                _ => {}
+            }
+            if depth == 0 {
+                name = &name[..i];
+                break;
+            }
+        }
+    }
+    let name = name.rsplit("::").next()?;
+    (!name.is_empty()).then_some(name)
+}
+
+/// The special names of MSVC, following `??`.
+enum MsvcSpecial {
+    Constructor,
+    Destructor,
+    Operator(&'static str),
+}
+
+fn msvc(symbol: &str) -> Option<String> {
+    let mut rest = symbol.strip_prefix('?')?;
+    let special = match rest.strip_prefix('?') {
+        Some(after) => {
+            let (special, after) = msvc_special(after)?;
+            rest = after;
+            Some(special)
+        }
+        None => None,
+    };
+
+    // The name, unless special, then its scopes from the innermost one, each ending with `@`,
+    // until an empty one. A digit refers back to one of the first ten names.
+    let mut names: Vec<&str> = Vec::new();
+    let mut parts = Vec::new();
+    while !rest.starts_with('@') {
+        let c = rest.chars().next()?;
+        if let Some(index) = c.to_digit(10) {
+            parts.push(names.get(index as usize)?.to_string());
+            rest = &rest[1..];
+        } else if let Some(after) = rest.strip_prefix("?A") {
+            let end = after.find('@')?;
+            parts.push("`anonymous namespace'".to_string());
+            rest = &after[end + 1..];
+        } else if c == '?' {
+            // Templates and nested names.
+            return None;
+        } else {
+            let end = rest.find('@')?;
+            let name = &rest[..end];
+            if names.len() < 10 {
+                names.push(name);
+            }
+            parts.push(name.to_string());
+            rest = &rest[end + 1..];
+        }
+    }
+
+    // Variables are followed by their storage class, a digit, and functions by a letter.
+    if !rest[1..].starts_with(|c: char| c.is_ascii_alphabetic() || c == '$') {
+        return None;
+    }
+
+    let name = match special {
+        None => parts.remove(0),
+        Some(MsvcSpecial::Constructor) => parts.first()?.clone(),
+        Some(MsvcSpecial::Destructor) => format!("~{}", parts.first()?),
+        Some(MsvcSpecial::Operator(op)) => {
+            let space = if op.starts_with(char::is_alphabetic) {
+                " "
+            } else {
+                ""
+            };
+            format!("operator{}{}", space, op)
+        }
+    };
+    parts.reverse();
+    parts.push(name);
+    Some(parts.join("::"))
+}
+
+/// Parses the special name MSVC writes after `??`, returning it with the rest of the symbol.
+fn msvc_special(rest: &str) -> Option<(MsvcSpecial, &str)> {
+    let (code, len) = match rest.strip_prefix('_') {
+        Some(after) => (after.get(..1).map(|c| format!("_{}", c))?, 2),
+        None => (rest.get(..1)?.to_string(), 1),
+    };
+    let special = match code.as_str() {
+        "0" => MsvcSpecial::Constructor,
+        "1" => MsvcSpecial::Destructor,
+        code => MsvcSpecial::Operator(match code {
+            "2" => "new",
+            "3" => "delete",
+            "4" => "=",
+            "5" => ">>",
+            "6" => "<<",
+            "7" => "!",
+            "8" => "==",
+            "9" => "!=",
+            "A" => "[]",
+            "C" => "->",
+            "D" => "*",
+            "E" => "++",
+            "F" => "--",
+            "G" => "-",
+            "H" => "+",
+            "I" => "&",
+            "J" => "->*",
+            "K" => "/",
+            "L" => "%",
+            "M" => "<",
+            "N" => "<=",
+            "O" => ">",
+            "P" => ">=",
+            "Q" => ",",
+            "R" => "()",
+            "S" => "~",
+            "T" => "^",
+            "U" => "|",
+            "V" => "&&",
+            "W" => "||",
+            "X" => "*=",
+            "Y" => "+=",
+            "Z" => "-=",
+            "_0" => "/=",
+            "_1" => "%=",
+            "_2" => ">>=",
+            "_3" => "<<=",
+            "_4" => "&=",
+            "_5" => "|=",
+            "_6" => "^=",
+            "_U" => "new[]",
+            "_V" => "delete[]",
+            // Conversion operators, whose type follows, and compiler-generated functions.
+            _ => return None,
+        }),
+    };
+    Some((special, &rest[len..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_demangle_should_demangle_itanium_functions() {
+        let cases = [
+            ("_ZN3net6Socket7connectEi", "net::Socket::connect(int)"),
+            ("_ZNK3net6Socket4portEv", "net::Socket::port() const"),
+            ("_ZN3net6SocketC2EPKcRKS0_", "net::Socket::Socket(char const*, net::Socket const&)"),
+            ("_ZN3net6SocketD0Ev", "net::Socket::~Socket()"),
+            ("_Z3maxIiET_S0_S0_", "max<int>(int, int)"),
+            (
+                "_ZNSt6vectorIiSaIiEE9push_backERKi",
+                "std::vector<int, std::allocator<int> >::push_back(int const&)",
+            ),
+            (
+                "_ZN4rust6detail4sendERKNSt7__cxx1112basic_stringIcSt11char_traitsIcESaIcEEE",
+                "rust::detail::send(std::__cxx11::basic_string<char, std::char_traits<char>, std::allocator<char> > const&)",
+            ),
+            ("_ZN12_GLOBAL__N_14hashEPvm.cold", "(anonymous namespace)::hash(void*, unsigned long)"),
+            ("_ZN3netlsERSoRKNS_6SocketE", "net::operator<<(std::basic_ostream<char, std::char_traits<char> >&, net::Socket const&)"),
+            ("__ZN3net5closeEi", "net::close(int)"),
+        ];
+        for (symbol, name) in cases {
+            assert_eq!(demangle(symbol).as_deref(), Some(name), "{}", symbol);
+        }
+
+        // Rust functions, variables and vtables.
+        assert_eq!(
+            demangle("_ZN7mycrate3net7connect17h0123456789abcdefE"),
+            None
+        );
+        assert_eq!(demangle("_ZN3net7counterE"), None);
+        assert_eq!(demangle("_ZTVN3net6SocketE"), None);
+        assert_eq!(demangle("connect"), None);
+    }
+
+    #[test]
+    fn test_demangle_should_demangle_msvc_functions_to_their_name() {
+        let cases = [
+            ("?connect@Socket@net@@QEAAHH@Z", "net::Socket::connect"),
+            ("??0Socket@net@@QEAA@PEBD@Z", "net::Socket::Socket"),
+            ("??1Socket@net@@QEAA@XZ", "net::Socket::~Socket"),
+            ("??8net@@YA_NAEBVSocket@0@0@Z", "net::operator=="),
+            (
+                "?hash@?A0x1b2c3d4e@@YA_KPEAX_K@Z",
+                "`anonymous namespace'::hash",
+            ),
+        ];
+        for (symbol, name) in cases {
+            assert_eq!(demangle(symbol).as_deref(), Some(name), "{}", symbol);
+        }
+
+        assert_eq!(demangle("?counter@net@@3HA"), None);
+        assert_eq!(demangle("??$max@H@@YAHHH@Z"), None);
+    }
+
+    #[test]
+    fn test_without_params_should_keep_call_operator() {
+        assert_eq!(
+            without_params("net::Socket::port() const"),
+            "net::Socket::port"
+        );
+        assert_eq!(
+            without_params("net::Hasher::operator()(int)"),
+            "net::Hasher::operator()"
+        );
+        assert_eq!(
+            without_params("net::Socket::connect"),
+            "net::Socket::connect"
+        );
+    }
+}
@@ -0,0 +1,688 @@
+#![cfg(feature = "debuginfo-check")]
+
+//! Reads the debug info of the running program to check fakes against the functions they
+//! replace: the DWARF sections of ELF objects on Linux, and the PDB of modules on Windows.
+//!
+//! The DWARF sections are read with `object` and decoded with `gimli`. Only what is needed to
+//! describe the parameters and return value of a function is read: the `.debug_info` entry of
+//! the function, found through `.debug_aranges`, and the types it refers to.
+//!
+//! The PDB is read with `pdb`, from the path the CodeView debug directory entry of the module
+//! names, or else next to the module. Its procedure symbols are described once per PDB, from
+//! the procedure types they refer to.
+//!
+//! Compressed debug sections, separate debug files and Mach-O are not read, and functions
+//! described by them are not checked.
+
+/// The size and class of a parameter or return value, as described by the debug info.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct ValueInfo {
+    /// The size in bytes, `None` when the debug info does not give it.
+    pub(crate) size: Option<usize>,
+    /// Whether the value is a floating-point number, passed in floating-point registers.
+    pub(crate) float: bool,
+}
+
+/// The signature of a function, as described by the debug info.
+#[derive(Clone, Debug)]
+pub(crate) struct FunctionInfo {
+    pub(crate) name: Option<String>,
+    /// The fixed parameters, including `self`.
+    pub(crate) params: Vec<ValueInfo>,
+    /// `None` when the function returns nothing, as for `()`.
+    pub(crate) ret: Option<ValueInfo>,
+}
+
+/// Returns the signature of the function starting at `addr`, or `None` when the object
+/// containing it has no usable debug info for it.
+#[cfg(target_os = "linux")]
+pub(crate) fn function_info(addr: usize) -> Option<FunctionInfo> {
+    let (path, bias) = crate::injector_core::elf::object_containing(addr)?;
+    let dwarf = dwarf::dwarf_of(&path)?;
+    dwarf.function_at(addr.checked_sub(bias)? as u64)
+}
+
+/// Returns the signature of the function starting at `addr`, or `None` when the module
+/// containing it has no PDB describing it.
+#[cfg(target_os = "windows")]
+pub(crate) fn function_info(addr: usize) -> Option<FunctionInfo> {
+    let (base, path) = crate::injector_core::winapi::module_pdb(addr)?;
+    let rva = u32::try_from(addr - base).ok()?;
+    let functions = codeview::functions_of(&path, addr)?;
+    functions.get(&rva).cloned()
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+pub(crate) fn function_info(_addr: usize) -> Option<FunctionInfo> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+mod dwarf {
+    use super::{FunctionInfo, ValueInfo};
+    use gimli::{AttributeValue, DwAt, UnitOffset};
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    type Reader = gimli::EndianSlice<'static, gimli::RunTimeEndian>;
+    type Unit = gimli::Unit<Reader>;
+    type Entry<'unit> = gimli::DebuggingInformationEntry<'unit, 'unit, Reader>;
+
+    /// How many `DW_AT_specification`, `DW_AT_abstract_origin` or type references are followed
+    /// before giving up, in case of a cycle.
+    const MAX_REFERENCES: usize = 32;
+
+    const UNKNOWN: ValueInfo = ValueInfo {
+        size: None,
+        float: false,
+    };
+
+    /// Returns the debug info of the object at `path`, read once per object and kept for the
+    /// lifetime of the process.
+    pub(super) fn dwarf_of(path: &str) -> Option<Arc<Dwarf>> {
+        static OBJECTS: Mutex<Option<HashMap<String, Option<Arc<Dwarf>>>>> = Mutex::new(None);
+
+        let mut objects = OBJECTS.lock().unwrap_or_else(|e| e.into_inner());
+        objects
+            .get_or_insert_with(HashMap::new)
+            .entry(path.to_string())
+            .or_insert_with(|| {
+                crate::injector_core::elf::map_file(path)
+                    .and_then(Dwarf::load)
+                    .map(Arc::new)
+            })
+            .clone()
+    }
+
+    /// The debug info of an object.
+    pub(crate) struct Dwarf {
+        dwarf: gimli::Dwarf<Reader>,
+        /// The unit headers of `.debug_info`, in section order.
+        headers: Vec<gimli::UnitHeader<Reader>>,
+        /// The units parsed so far, by offset in `.debug_info`.
+        units: Mutex<HashMap<usize, Arc<Unit>>>,
+    }
+
+    impl Dwarf {
+        /// Reads the debug sections of an ELF file.
+        fn load(file: &'static [u8]) -> Option<Self> {
+            use object::{Object, ObjectSection};
+
+            let object = object::File::parse(file).ok()?;
+            if object.format() != object::BinaryFormat::Elf {
+                return None;
+            }
+            let endian = if object.is_little_endian() {
+                gimli::RunTimeEndian::Little
+            } else {
+                gimli::RunTimeEndian::Big
+            };
+
+            let dwarf = gimli::Dwarf::load(|id| {
+                let data = match object.section_by_name(id.name()) {
+                    // Compressed sections are not supported.
+                    Some(section) => match section.compressed_data() {
+                        Ok(data) if data.format == object::CompressionFormat::None => data.data,
+                        _ => return Err(()),
+                    },
+                    None => &[],
+                };
+                Ok(Reader::new(data, endian))
+            })
+            .ok()?;
+
+            let mut headers = Vec::new();
+            let mut units = dwarf.units();
+            while let Ok(Some(header)) = units.next() {
+                headers.push(header);
+            }
+            if headers.is_empty() {
+                return None;
+            }
+
+            Some(Self {
+                dwarf,
+                headers,
+                units: Mutex::new(HashMap::new()),
+            })
+        }
+
+        /// Describes the function whose code starts at `addr`, an address of the debug info.
+        pub(crate) fn function_at(&self, addr: u64) -> Option<FunctionInfo> {
+            let offsets = match self.unit_offset_of_address(addr) {
+                Some(offset) => vec![offset],
+                None => self.headers.iter().filter_map(unit_start).collect(),
+            };
+
+            offsets
+                .into_iter()
+                .find_map(|offset| self.function_in_unit(&self.unit(offset)?, addr))
+        }
+
+        /// Searches `unit` for the function at `addr`. Units without types, such as those of
+        /// the standard library compiled with line tables only, do not describe parameters,
+        /// so they are not searched.
+        fn function_in_unit(&self, unit: &Arc<Unit>, addr: u64) -> Option<FunctionInfo> {
+            let mut entries = unit.entries();
+            let mut found = None;
+            let mut has_types = false;
+
+            while let Some((_, entry)) = entries.next_dfs().ok()? {
+                has_types |= matches!(
+                    entry.tag(),
+                    gimli::DW_TAG_base_type
+                        | gimli::DW_TAG_pointer_type
+                        | gimli::DW_TAG_structure_type
+                );
+
+                if found.is_none()
+                    && entry.tag() == gimli::DW_TAG_subprogram
+                    && attr(entry, gimli::DW_AT_low_pc)
+                        .and_then(|value| self.dwarf.attr_address(unit, value).ok()?)
+                        == Some(addr)
+                {
+                    found = Some(entry.offset());
+                }
+                if found.is_some() && has_types {
+                    break;
+                }
+            }
+
+            if !has_types {
+                return None;
+            }
+            self.describe_function(unit, found?)
+        }
+
+        /// Describes the subprogram entry at `offset`.
+        fn describe_function(&self, unit: &Arc<Unit>, offset: UnitOffset) -> Option<FunctionInfo> {
+            let mut params = self.params(unit, offset)?;
+
+            // Concrete instances may leave the parameters to their abstract origin.
+            if params.is_empty() {
+                if let Some((origin_unit, origin)) = self.origin(unit, &unit.entry(offset).ok()?) {
+                    params = self.params(&origin_unit, origin)?;
+                }
+            }
+
+            let name = self
+                .inherited_attr(unit, offset, gimli::DW_AT_name)
+                .and_then(|(unit, value)| self.dwarf.attr_string(&unit, value).ok())
+                .map(|name| name.to_string_lossy().into_owned());
+            let ret = self
+                .inherited_attr(unit, offset, gimli::DW_AT_type)
+                .map(|(unit, value)| self.type_info(&unit, value, 0));
+
+            Some(FunctionInfo { name, params, ret })
+        }
+
+        /// Describes the formal parameters among the children of the entry at `offset`.
+        fn params(&self, unit: &Arc<Unit>, offset: UnitOffset) -> Option<Vec<ValueInfo>> {
+            let mut tree = unit.entries_tree(Some(offset)).ok()?;
+            let mut children = tree.root().ok()?.children();
+            let mut params = Vec::new();
+
+            while let Some(child) = children.next().ok()? {
+                let entry = child.entry();
+                if entry.tag() == gimli::DW_TAG_formal_parameter {
+                    let ty = match self.inherited_attr(unit, entry.offset(), gimli::DW_AT_type) {
+                        Some((unit, value)) => self.type_info(&unit, value, 0),
+                        None => UNKNOWN,
+                    };
+                    params.push(ty);
+                }
+            }
+
+            Some(params)
+        }
+
+        /// Describes the type a `DW_AT_type` attribute of `unit` refers to.
+        fn type_info(
+            &self,
+            unit: &Arc<Unit>,
+            value: AttributeValue<Reader>,
+            references: usize,
+        ) -> ValueInfo {
+            match self.resolve(unit, value) {
+                Some((unit, offset)) if references < MAX_REFERENCES => {
+                    self.value_info(&unit, offset, references)
+                }
+                _ => UNKNOWN,
+            }
+        }
+
+        /// Describes the type entry at `offset`.
+        fn value_info(&self, unit: &Arc<Unit>, offset: UnitOffset, references: usize) -> ValueInfo {
+            let Ok(entry) = unit.entry(offset) else {
+                return UNKNOWN;
+            };
+            let byte_size = attr(&entry, gimli::DW_AT_byte_size)
+                .and_then(|size| size.udata_value())
+                .map(|size| size as usize);
+            let referenced = || match attr(&entry, gimli::DW_AT_type) {
+                Some(ty) => self.type_info(unit, ty, references + 1),
+                None => UNKNOWN,
+            };
+
+            match entry.tag() {
+                gimli::DW_TAG_base_type => ValueInfo {
+                    size: byte_size,
+                    float: attr(&entry, gimli::DW_AT_encoding)
+                        == Some(AttributeValue::Encoding(gimli::DW_ATE_float)),
+                },
+                gimli::DW_TAG_pointer_type
+                | gimli::DW_TAG_reference_type
+                | gimli::DW_TAG_rvalue_reference_type => ValueInfo {
+                    size: Some(byte_size.unwrap_or(unit.encoding().address_size as usize)),
+                    float: false,
+                },
+                gimli::DW_TAG_typedef
+                | gimli::DW_TAG_const_type
+                | gimli::DW_TAG_volatile_type
+                | gimli::DW_TAG_restrict_type
+                | gimli::DW_TAG_atomic_type => referenced(),
+                gimli::DW_TAG_array_type if byte_size.is_none() => ValueInfo {
+                    size: self.array_size(unit, &entry, references),
+                    float: false,
+                },
+                _ => ValueInfo {
+                    size: byte_size,
+                    float: false,
+                },
+            }
+        }
+
+        /// Computes the size of an array type from its element type and its dimensions.
+        fn array_size(&self, unit: &Arc<Unit>, entry: &Entry, references: usize) -> Option<usize> {
+            let element = attr(entry, gimli::DW_AT_type)?;
+            let mut size = self.type_info(unit, element, references + 1).size?;
+
+            let mut tree = unit.entries_tree(Some(entry.offset())).ok()?;
+            let mut children = tree.root().ok()?.children();
+            let mut dimensions = 0;
+            while let Some(child) = children.next().ok()? {
+                let child = child.entry();
+                if child.tag() != gimli::DW_TAG_subrange_type {
+                    continue;
+                }
+                let bound = |name| attr(child, name).and_then(|value| value.udata_value());
+                let count = match bound(gimli::DW_AT_count) {
+                    Some(count) => count,
+                    None => {
+                        let lower = bound(gimli::DW_AT_lower_bound).unwrap_or(0);
+                        (bound(gimli::DW_AT_upper_bound)? + 1).checked_sub(lower)?
+                    }
+                };
+                size = size.checked_mul(count as usize)?;
+                dimensions += 1;
+            }
+
+            (dimensions > 0).then_some(size)
+        }
+
+        /// Returns the entry `DW_AT_specification` or `DW_AT_abstract_origin` of `entry`
+        /// refers to.
+        fn origin(&self, unit: &Arc<Unit>, entry: &Entry) -> Option<(Arc<Unit>, UnitOffset)> {
+            let value = attr(entry, gimli::DW_AT_specification)
+                .or_else(|| attr(entry, gimli::DW_AT_abstract_origin))?;
+            self.resolve(unit, value)
+        }
+
+        /// Returns the attribute `name` of the entry at `offset`, or of the entries it is a
+        /// concrete instance or a definition of, with the unit it is read from.
+        fn inherited_attr(
+            &self,
+            unit: &Arc<Unit>,
+            offset: UnitOffset,
+            name: DwAt,
+        ) -> Option<(Arc<Unit>, AttributeValue<Reader>)> {
+            let (mut unit, mut offset) = (unit.clone(), offset);
+            for _ in 0..MAX_REFERENCES {
+                let origin = {
+                    let entry = unit.entry(offset).ok()?;
+                    if let Some(value) = attr(&entry, name) {
+                        return Some((unit, value));
+                    }
+                    self.origin(&unit, &entry)?
+                };
+                (unit, offset) = origin;
+            }
+            None
+        }
+
+        /// Returns the unit and offset of the entry a reference of `unit` points to.
+        fn resolve(
+            &self,
+            unit: &Arc<Unit>,
+            value: AttributeValue<Reader>,
+        ) -> Option<(Arc<Unit>, UnitOffset)> {
+            match value {
+                AttributeValue::UnitRef(offset) => Some((unit.clone(), offset)),
+                AttributeValue::DebugInfoRef(offset) => {
+                    let index = self.headers.partition_point(|header| {
+                        unit_start(header).unwrap_or(0) + header.length_including_self() <= offset.0
+                    });
+                    let header = self.headers.get(index)?;
+                    let unit = self.unit(unit_start(header)?)?;
+                    let offset = offset.to_unit_offset(&unit.header)?;
+                    Some((unit, offset))
+                }
+                _ => None,
+            }
+        }
+
+        /// Returns the offset of the unit `.debug_aranges` gives for `addr`.
+        fn unit_offset_of_address(&self, addr: u64) -> Option<usize> {
+            let mut headers = self.dwarf.debug_aranges.headers();
+            while let Some(header) = headers.next().ok()? {
+                let mut entries = header.entries();
+                while let Ok(Some(entry)) = entries.next() {
+                    let range = entry.range();
+                    if (range.begin..range.end).contains(&addr) {
+                        return Some(header.debug_info_offset().0);
+                    }
+                }
+            }
+            None
+        }
+
+        /// Returns the unit at `offset` in `.debug_info`, parsed once.
+        fn unit(&self, offset: usize) -> Option<Arc<Unit>> {
+            let mut units = self.units.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(unit) = units.get(&offset) {
+                return Some(unit.clone());
+            }
+
+            let header = self
+                .headers
+                .iter()
+                .find(|header| unit_start(header) == Some(offset))?;
+            let unit = Arc::new(self.dwarf.unit(*header).ok()?);
+            units.insert(offset, unit.clone());
+            Some(unit)
+        }
+    }
+
+    /// Returns the offset of the unit of `header` in `.debug_info`.
+    fn unit_start(header: &gimli::UnitHeader<Reader>) -> Option<usize> {
+        Some(header.offset().as_debug_info_offset()?.0)
+    }
+
+    fn attr(entry: &Entry, name: DwAt) -> Option<AttributeValue<Reader>> {
+        entry.attr_value(name).ok()?
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod codeview {
+    use super::{FunctionInfo, ValueInfo};
+    use pdb::{FallibleIterator, PrimitiveKind, TypeData, TypeIndex};
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    /// How many modifier, enumeration or forward references are followed before giving up, in
+    /// case of a cycle.
+    const MAX_REFERENCES: usize = 32;
+
+    /// `S_LPROC32_ID`, `S_GPROC32_ID` and `S_LPROC32_DPC_ID`, the procedure symbols referring
+    /// to a function id rather than to the type of the procedure.
+    const ID_PROCEDURES: [u16; 3] = [0x1146, 0x1147, 0x1156];
+
+    const UNKNOWN: ValueInfo = ValueInfo {
+        size: None,
+        float: false,
+    };
+
+    /// The functions of a PDB, by the RVA of their code.
+    pub(super) type Functions = HashMap<u32, FunctionInfo>;
+
+    /// Returns the functions described by the PDB at `path`, or by the one next to the module
+    /// holding `addr` when `path` does not exist, read once per PDB and kept for the lifetime
+    /// of the process.
+    pub(super) fn functions_of(path: &str, addr: usize) -> Option<Arc<Functions>> {
+        static PDBS: Mutex<Option<HashMap<String, Option<Arc<Functions>>>>> = Mutex::new(None);
+
+        let mut pdbs = PDBS.lock().unwrap_or_else(|e| e.into_inner());
+        pdbs.get_or_insert_with(HashMap::new)
+            .entry(path.to_string())
+            .or_insert_with(|| {
+                let file = std::fs::File::open(path).or_else(|_| {
+                    let module = crate::injector_core::winapi::module_path(addr)
+                        .ok_or(std::io::ErrorKind::NotFound)?;
+                    std::fs::File::open(std::path::Path::new(&module).with_extension("pdb"))
+                });
+                load(file.ok()?).map(Arc::new)
+            })
+            .clone()
+    }
+
+    /// Describes the procedure symbols of every module of a PDB.
+    fn load(file: std::fs::File) -> Option<Functions> {
+        let mut pdb = pdb::PDB::open(file).ok()?;
+        let address_map = pdb.address_map().ok()?;
+        let type_information = pdb.type_information().ok()?;
+        let id_information = pdb.id_information().ok()?;
+
+        // Index every type, and the complete definition of the types forward references name.
+        let mut types = type_information.finder();
+        let mut definitions = HashMap::new();
+        let mut iter = type_information.iter();
+        while let Some(item) = iter.next().ok()? {
+            types.update(&iter);
+            let (properties, name, unique_name) = match item.parse() {
+                Ok(TypeData::Class(ty)) => (ty.properties, ty.name, ty.unique_name),
+                Ok(TypeData::Union(ty)) => (ty.properties, ty.name, ty.unique_name),
+                Ok(TypeData::Enumeration(ty)) => (ty.properties, ty.name, ty.unique_name),
+                _ => continue,
+            };
+            if !properties.forward_reference() {
+                definitions.insert(
+                    unique_name.unwrap_or(name).as_bytes().to_vec(),
+                    item.index(),
+                );
+            }
+        }
+        let mut ids = id_information.finder();
+        let mut iter = id_information.iter();
+        while iter.next().ok()?.is_some() {
+            ids.update(&iter);
+        }
+
+        let describer = Describer {
+            types: &types,
+            definitions: &definitions,
+        };
+        let mut functions = HashMap::new();
+        let debug_information = pdb.debug_information().ok()?;
+        let mut modules = debug_information.modules().ok()?;
+        while let Some(module) = modules.next().ok()? {
+            let Some(info) = pdb.module_info(&module).ok().flatten() else {
+                continue;
+            };
+            let Ok(mut symbols) = info.symbols() else {
+                continue;
+            };
+            while let Ok(Some(symbol)) = symbols.next() {
+                let Ok(pdb::SymbolData::Procedure(procedure)) = symbol.parse() else {
+                    continue;
+                };
+                let Some(rva) = procedure.offset.to_rva(&address_map) else {
+                    continue;
+                };
+
+                let ty = if ID_PROCEDURES.contains(&symbol.raw_kind()) {
+                    let id = pdb::IdIndex(procedure.type_index.0);
+                    match ids.find(id).and_then(|id| id.parse()) {
+                        Ok(pdb::IdData::Function(id)) => id.function_type,
+                        Ok(pdb::IdData::MemberFunction(id)) => id.function_type,
+                        _ => continue,
+                    }
+                } else {
+                    procedure.type_index
+                };
+                if let Some(mut function) = describer.function(ty) {
+                    function.name = Some(procedure.name.to_string().into_owned());
+                    functions.entry(rva.0).or_insert(function);
+                }
+            }
+        }
+
+        Some(functions)
+    }
+
+    /// Describes procedure types and the types of their parameters.
+    struct Describer<'a, 't> {
+        types: &'a pdb::TypeFinder<'t>,
+        /// The complete definitions of classes, unions and enumerations, by unique name.
+        definitions: &'a HashMap<Vec<u8>, TypeIndex>,
+    }
+
+    impl<'t> Describer<'_, 't> {
+        /// Describes the procedure or member function type at `index`, `self` first.
+        fn function(&self, index: TypeIndex) -> Option<FunctionInfo> {
+            let (ret, this, arguments) = match self.parse(index)? {
+                TypeData::Procedure(ty) => (ty.return_type, None, ty.argument_list),
+                TypeData::MemberFunction(ty) => {
+                    (Some(ty.return_type), ty.this_pointer_type, ty.argument_list)
+                }
+                _ => return None,
+            };
+            let TypeData::ArgumentList(arguments) = self.parse(arguments)? else {
+                return None;
+            };
+
+            let params = this
+                .into_iter()
+                .chain(arguments.arguments)
+                .map(|ty| self.value_info(ty, 0))
+                .collect();
+            let ret = match ret.map(|ty| (ty, self.parse(ty))) {
+                None
+                | Some((
+                    _,
+                    Some(TypeData::Primitive(pdb::PrimitiveType {
+                        kind: PrimitiveKind::Void,
+                        indirection: None,
+                    })),
+                )) => None,
+                Some((ty, _)) => Some(self.value_info(ty, 0)),
+            };
+
+            Some(FunctionInfo {
+                name: None,
+                params,
+                ret,
+            })
+        }
+
+        /// Describes the type at `index`.
+        fn value_info(&self, index: TypeIndex, references: usize) -> ValueInfo {
+            let Some(ty) = self.parse(index).filter(|_| references < MAX_REFERENCES) else {
+                return UNKNOWN;
+            };
+            let sized = |size: u64| ValueInfo {
+                size: Some(size as usize),
+                float: false,
+            };
+
+            match ty {
+                TypeData::Primitive(ty) => primitive_info(ty),
+                TypeData::Pointer(ty) => sized(ty.attributes.size().into()),
+                TypeData::Modifier(ty) => self.value_info(ty.underlying_type, references + 1),
+                TypeData::Enumeration(ty) => self.value_info(ty.underlying_type, references + 1),
+                TypeData::Class(ty) if ty.properties.forward_reference() => {
+                    self.definition(ty.unique_name.unwrap_or(ty.name), references)
+                }
+                TypeData::Union(ty) if ty.properties.forward_reference() => {
+                    self.definition(ty.unique_name.unwrap_or(ty.name), references)
+                }
+                TypeData::Class(ty) => sized(ty.size),
+                TypeData::Union(ty) => sized(ty.size),
+                // The last dimension aggregates the others.
+                TypeData::Array(ty) => match ty.dimensions.last() {
+                    Some(&size) => sized(size.into()),
+                    None => UNKNOWN,
+                },
+                _ => UNKNOWN,
+            }
+        }
+
+        /// Describes the complete definition of the type a forward reference names.
+        fn definition(&self, name: pdb::RawString, references: usize) -> ValueInfo {
+            match self.definitions.get(name.as_bytes()) {
+                Some(&index) => self.value_info(index, references + 1),
+                None => UNKNOWN,
+            }
+        }
+
+        fn parse(&self, index: TypeIndex) -> Option<TypeData<'t>> {
+            self.types.find(index).ok()?.parse().ok()
+        }
+    }
+
+    /// Describes a primitive type, or a pointer to one.
+    fn primitive_info(ty: pdb::PrimitiveType) -> ValueInfo {
+        use pdb::Indirection;
+
+        let (size, float) = match (ty.indirection, ty.kind) {
+            (Some(Indirection::Near16 | Indirection::Far16 | Indirection::Huge16), _) => (2, false),
+            (Some(Indirection::Near32 | Indirection::Far32), _) => (4, false),
+            (Some(Indirection::Near64), _) => (8, false),
+            (Some(Indirection::Near128), _) => (16, false),
+            (
+                None,
+                PrimitiveKind::Char
+                | PrimitiveKind::UChar
+                | PrimitiveKind::RChar
+                | PrimitiveKind::I8
+                | PrimitiveKind::U8
+                | PrimitiveKind::Bool8,
+            ) => (1, false),
+            (
+                None,
+                PrimitiveKind::WChar
+                | PrimitiveKind::RChar16
+                | PrimitiveKind::Short
+                | PrimitiveKind::UShort
+                | PrimitiveKind::I16
+                | PrimitiveKind::U16
+                | PrimitiveKind::Bool16,
+            ) => (2, false),
+            (
+                None,
+                PrimitiveKind::RChar32
+                | PrimitiveKind::Long
+                | PrimitiveKind::ULong
+                | PrimitiveKind::I32
+                | PrimitiveKind::U32
+                | PrimitiveKind::Bool32
+                | PrimitiveKind::HRESULT,
+            ) => (4, false),
+            (
+                None,
+                PrimitiveKind::Quad
+                | PrimitiveKind::UQuad
+                | PrimitiveKind::I64
+                | PrimitiveKind::U64
+                | PrimitiveKind::Bool64,
+            ) => (8, false),
+            (
+                None,
+                PrimitiveKind::Octa
+                | PrimitiveKind::UOcta
+                | PrimitiveKind::I128
+                | PrimitiveKind::U128,
+            ) => (16, false),
+            (None, PrimitiveKind::F16) => (2, true),
+            (None, PrimitiveKind::F32 | PrimitiveKind::F32PP) => (4, true),
+            (None, PrimitiveKind::F64) => (8, true),
+            (None, PrimitiveKind::F80) => (10, true),
+            (None, PrimitiveKind::F128) => (16, true),
+            _ => return UNKNOWN,
+        };
+        ValueInfo {
+            size: Some(size),
+            float,
+        }
+    }
+}
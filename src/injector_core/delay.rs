@@ -0,0 +1,145 @@
+//! Delays of fakes declared with `delay:`, slept for real or on a virtual clock.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+#[cfg(feature = "tokio")]
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::task::{Context, Waker};
+use std::time::{Duration, Instant};
+
+/// A clock the delays of fakes advance instead of sleeping, such as that of
+/// `TokioTimeMocker`.
+pub(crate) trait VirtualTimer: Send + Sync {
+    /// Advances the clock by the delay of a sync fake.
+    fn sleep(&self, delay: Duration);
+
+    /// Polls the delay of the async fake future identified by `key`. Returns whether it is
+    /// still pending, in which case the timer wakes the task once it may be over.
+    fn poll_delay(&self, key: (usize, usize), delay: Duration, cx: &mut Context<'_>) -> bool;
+}
+
+type Timers = Vec<(u64, Arc<dyn VirtualTimer>)>;
+
+#[cfg(feature = "tokio")]
+static NEXT_TIMER_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Timers installed for every thread, latest last.
+static GLOBAL_TIMERS: LazyLock<Mutex<Timers>> = LazyLock::new(|| Mutex::new(Vec::new()));
+
+thread_local! {
+    /// Timers installed for the current thread, latest last. They take precedence over the
+    /// global ones.
+    static THREAD_TIMERS: RefCell<Timers> = const { RefCell::new(Vec::new()) };
+}
+
+/// `(deadline, waker of the task polling it)` of pending async delays, by poll function and
+/// future address.
+type Deadlines = HashMap<(usize, usize), (Instant, Waker)>;
+
+/// Real deadlines of the pending async delays.
+static DEADLINES: LazyLock<Mutex<Deadlines>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Makes the delays of fakes advance `timer` rather than sleep, on the current thread or, when
+/// `global` is set, on every thread, until the returned guard is dropped.
+#[cfg(feature = "tokio")]
+pub(crate) fn install_timer(global: bool, timer: Arc<dyn VirtualTimer>) -> TimerGuard {
+    let id = NEXT_TIMER_ID.fetch_add(1, Ordering::Relaxed);
+    if global {
+        GLOBAL_TIMERS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push((id, timer));
+    } else {
+        THREAD_TIMERS.with(|timers| timers.borrow_mut().push((id, timer)));
+    }
+    TimerGuard { id, global }
+}
+
+/// Removes a timer installed with `install_timer()` on drop.
+#[cfg(feature = "tokio")]
+pub(crate) struct TimerGuard {
+    id: u64,
+    global: bool,
+}
+
+#[cfg(feature = "tokio")]
+impl Drop for TimerGuard {
+    fn drop(&mut self) {
+        let removed = if self.global {
+            let mut timers = GLOBAL_TIMERS.lock().unwrap_or_else(|e| e.into_inner());
+            timers
+                .iter()
+                .position(|(id, _)| *id == self.id)
+                .map(|index| timers.remove(index))
+        } else {
+            THREAD_TIMERS
+                .try_with(|timers| {
+                    let mut timers = timers.borrow_mut();
+                    timers
+                        .iter()
+                        .position(|(id, _)| *id == self.id)
+                        .map(|index| timers.remove(index))
+                })
+                .ok()
+                .flatten()
+        };
+        drop(removed);
+    }
+}
+
+/// Returns the timer the delays of the current thread advance, if any.
+fn current_timer() -> Option<Arc<dyn VirtualTimer>> {
+    THREAD_TIMERS
+        .try_with(|timers| timers.borrow().last().map(|(_, timer)| timer.clone()))
+        .ok()
+        .flatten()
+        .or_else(|| {
+            GLOBAL_TIMERS
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .last()
+                .map(|(_, timer)| timer.clone())
+        })
+}
+
+/// Delays a sync fake by `delay`, on the virtual clock of the thread if any.
+pub(crate) fn sleep(delay: Duration) {
+    match current_timer() {
+        Some(timer) => timer.sleep(delay),
+        None => std::thread::sleep(delay),
+    }
+}
+
+/// Polls the delay of the future at `fut` of an async fake. Returns whether it is still
+/// pending. Without a virtual clock, a thread wakes the task once the delay is over.
+pub(crate) fn poll(poll_fn: usize, fut: usize, delay: Duration, cx: &mut Context<'_>) -> bool {
+    if delay.is_zero() {
+        return false;
+    }
+    let key = (poll_fn, fut);
+    if let Some(timer) = current_timer() {
+        return timer.poll_delay(key, delay, cx);
+    }
+
+    let mut deadlines = DEADLINES.lock().unwrap_or_else(|e| e.into_inner());
+    match deadlines.get(&key) {
+        // Another future at the same address, polled by another task, starts its own delay.
+        Some((deadline, waker)) if waker.will_wake(cx.waker()) => {
+            if Instant::now() < *deadline {
+                return true;
+            }
+            deadlines.remove(&key);
+            false
+        }
+        _ => {
+            let waker = cx.waker().clone();
+            deadlines.insert(key, (Instant::now() + delay, waker.clone()));
+            std::thread::spawn(move || {
+                std::thread::sleep(delay);
+                waker.wake();
+            });
+            true
+        }
+    }
+}
@@ -0,0 +1,157 @@
+#![cfg(target_os = "linux")]
+
+//! Demangles the names of Rust functions in symbol tables, so functions can be looked up by
+//! their path.
+//!
+//! Only the legacy mangling scheme, rustc's default, is understood. It mangles
+//! `mycrate::net::connect` into `_ZN7mycrate3net7connect17h0123456789abcdefE`, the last
+//! segment being a hash of the crate and of the generic arguments of the instance.
+
+/// Returns the path of the function whose symbol is `name`, without its hash, or `None` when
+/// `name` is not a Rust symbol mangled with the legacy scheme.
+pub(crate) fn demangle(name: &[u8]) -> Option<String> {
+    let name = std::str::from_utf8(name).ok()?;
+    let mut rest = name.strip_prefix("_ZN")?;
+
+    let mut segments = Vec::new();
+    // Anything after the end, such as the `.llvm.<n>` suffix of a promoted local function,
+    // is not part of the path.
+    while !rest.starts_with('E') {
+        let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+        let len: usize = rest[..digits].parse().ok()?;
+        let segment = rest.get(digits..digits + len)?;
+        segments.push(segment);
+        rest = &rest[digits + len..];
+    }
+
+    if let Some(hash) = segments.last() {
+        if hash.len() == 17
+            && hash.starts_with('h')
+            && hash[1..].bytes().all(|b| b.is_ascii_hexdigit())
+        {
+            segments.pop();
+        }
+    }
+    if segments.is_empty() {
+        return None;
+    }
+
+    let mut path = String::with_capacity(name.len());
+    for (i, segment) in segments.into_iter().enumerate() {
+        if i > 0 {
+            path.push_str("::");
+        }
+        unescape(segment, &mut path)?;
+    }
+    Some(path)
+}
+
+/// Appends the identifier `segment` to `path`, with its `$..$` escapes and `..` path
+/// separators replaced.
+fn unescape(segment: &str, path: &mut String) -> Option<()> {
+    // Identifiers starting with an escape are prefixed with an underscore.
+    let mut rest = segment
+        .strip_prefix("_$")
+        .map_or(segment, |_| &segment[1..]);
+    while let Some(c) = rest.chars().next() {
+        match c {
+            '$' => {
+                let end = rest[1..].find('$')? + 1;
+                let escaped = match &rest[1..end] {
+                    "SP" => '@',
+                    "BP" => '*',
+                    "RF" => '&',
+                    "LT" => '<',
+                    "GT" => '>',
+                    "LP" => '(',
+                    "RP" => ')',
+                    "C" => ',',
+                    code => {
+                        let hex = code.strip_prefix('u')?;
+                        char::from_u32(u32::from_str_radix(hex, 16).ok()?)?
+                    }
+                };
+                path.push(escaped);
+                rest = &rest[end + 1..];
+            }
+            '.' if rest.starts_with("..") => {
+                path.push_str("::");
+                rest = &rest[2..];
+            }
+            _ => {
+                path.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    Some(())
+}
+
+/// Returns whether the path `path` matches `pattern`, in which `*` stands for any part of a
+/// path segment and `**` for any part of the path.
+pub(crate) fn matches_pattern(pattern: &str, path: &str) -> bool {
+    fn matches(pattern: &[u8], path: &[u8]) -> bool {
+        if let Some(rest) = pattern.strip_prefix(b"**") {
+            return (0..=path.len()).any(|i| matches(rest, &path[i..]));
+        }
+        if let Some(rest) = pattern.strip_prefix(b"*") {
+            let segment = path
+                .windows(2)
+                .position(|w| w == b"::")
+                .unwrap_or(path.len());
+            return (0..=segment).any(|i| matches(rest, &path[i..]));
+        }
+        match (pattern.split_first(), path.split_first()) {
+            (None, None) => true,
+            (Some((p, pattern)), Some((c, path))) if p == c => matches(pattern, path),
+            _ => false,
+        }
+    }
+
+    matches(pattern.as_bytes(), path.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_demangle_should_strip_hash_and_unescape() {
+        assert_eq!(
+            demangle(b"_ZN7mycrate3net7connect17h0123456789abcdefE").as_deref(),
+            Some("mycrate::net::connect")
+        );
+        assert_eq!(
+            demangle(b"_ZN49_$LT$mycrate..Conn$u20$as$u20$core..ops..Drop$GT$4drop17h0123456789abcdefE.llvm.42")
+                .as_deref(),
+            Some("<mycrate::Conn as core::ops::Drop>::drop")
+        );
+        assert_eq!(demangle(b"getenv"), None);
+        assert_eq!(demangle(b"_ZN7mycrate3net"), None);
+    }
+
+    #[test]
+    fn test_matches_pattern_should_keep_single_wildcard_within_a_segment() {
+        assert!(matches_pattern(
+            "mycrate::net::*::connect",
+            "mycrate::net::tcp::connect"
+        ));
+        assert!(!matches_pattern(
+            "mycrate::net::*::connect",
+            "mycrate::net::tcp::v4::connect"
+        ));
+        assert!(matches_pattern(
+            "mycrate::**::connect",
+            "mycrate::net::tcp::v4::connect"
+        ));
+        assert!(matches_pattern(
+            "mycrate::net::conn*",
+            "mycrate::net::connect"
+        ));
+        assert!(!matches_pattern(
+            "mycrate::net::conn*",
+            "mycrate::net::connect::inner"
+        ));
+        assert!(!matches_pattern("mycrate::net", "mycrate::net::connect"));
+    }
+}
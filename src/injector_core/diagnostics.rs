@@ -0,0 +1,218 @@
+//! Diagnostic hooks for the patch lifecycle.
+//!
+//! Every place that installs or restores a patch, and every generated fake, reports
+//! through this module. Two sinks are supported:
+//!
+//! - With the `tracing` feature enabled the events are forwarded to the `tracing`
+//!   ecosystem under the `injectorpp` target.
+//! - When the `INJECTORPP_LOG` environment variable is set (to anything but `0`,
+//!   `off` or `false`), or logging was enabled through `set_diagnostic_logging`,
+//!   the patched address, the original bytes, the written branch bytes and the JIT
+//!   stub location are printed to stderr.
+
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use crate::injector_core::common::read_bytes;
+
+/// How a patch is applied to the target function.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum PatchMode {
+    /// The function bytes are overwritten and every thread observes the fake.
+    Global,
+    /// The function is routed through a dispatcher and only the registering thread
+    /// observes the fake.
+    #[cfg(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec",
+        target_arch = "arm"
+    ))]
+    ThreadLocal,
+}
+
+impl PatchMode {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            PatchMode::Global => "global",
+            #[cfg(any(
+                target_arch = "x86_64",
+                target_arch = "aarch64",
+                target_arch = "arm64ec",
+                target_arch = "arm"
+            ))]
+            PatchMode::ThreadLocal => "thread-local",
+        }
+    }
+}
+
+const LOG_UNINITIALIZED: u8 = 0;
+const LOG_DISABLED: u8 = 1;
+const LOG_ENABLED: u8 = 2;
+
+static LOG_STATE: AtomicU8 = AtomicU8::new(LOG_UNINITIALIZED);
+
+/// Returns whether stderr logging is enabled, reading `INJECTORPP_LOG` on first use.
+pub(crate) fn log_enabled() -> bool {
+    match LOG_STATE.load(Ordering::Relaxed) {
+        LOG_ENABLED => true,
+        LOG_DISABLED => false,
+        _ => {
+            let enabled = match std::env::var("INJECTORPP_LOG") {
+                Ok(value) => !matches!(
+                    value.trim().to_ascii_lowercase().as_str(),
+                    "" | "0" | "off" | "false"
+                ),
+                Err(_) => false,
+            };
+            let state = if enabled { LOG_ENABLED } else { LOG_DISABLED };
+            // Keep an explicit `set_log_enabled` call made in the meantime.
+            let _ = LOG_STATE.compare_exchange(
+                LOG_UNINITIALIZED,
+                state,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            );
+            LOG_STATE.load(Ordering::Relaxed) == LOG_ENABLED
+        }
+    }
+}
+
+/// Overrides the `INJECTORPP_LOG` environment variable.
+pub(crate) fn set_log_enabled(enabled: bool) {
+    let state = if enabled { LOG_ENABLED } else { LOG_DISABLED };
+    LOG_STATE.store(state, Ordering::Relaxed);
+}
+
+/// Format bytes as space separated hex pairs, e.g. `e9 10 20 30 40`.
+pub(crate) fn hex_bytes(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Called after a patch has been written for `func_addr`.
+///
+/// `original_bytes` are the bytes that were at `func_addr` before patching. The
+/// branch bytes are read back from `func_addr` so the log reflects what was actually
+/// written.
+#[allow(unused_variables)]
+pub(crate) fn patch_installed(
+    mode: PatchMode,
+    func_addr: usize,
+    original_bytes: &[u8],
+    patch_size: usize,
+    jit_addr: usize,
+) {
+    #[cfg(feature = "tracing")]
+    tracing::debug!(
+        target: "injectorpp",
+        mode = mode.as_str(),
+        func_addr = format_args!("{:#x}", func_addr),
+        patch_size,
+        jit_addr = format_args!("{:#x}", jit_addr),
+        "patch installed"
+    );
+
+    if log_enabled() {
+        let original = &original_bytes[..patch_size.min(original_bytes.len())];
+        let written = unsafe { read_bytes(func_addr as *const u8, patch_size) };
+        eprintln!(
+            "[injectorpp] patch installed ({}): func={:#x} size={} jit={:#x} original=[{}] branch=[{}]",
+            mode.as_str(),
+            func_addr,
+            patch_size,
+            jit_addr,
+            hex_bytes(original),
+            hex_bytes(&written),
+        );
+    }
+}
+
+/// Called after the patch for `func_addr` has been removed.
+#[allow(unused_variables)]
+pub(crate) fn patch_restored(mode: PatchMode, func_addr: usize) {
+    #[cfg(feature = "tracing")]
+    tracing::debug!(
+        target: "injectorpp",
+        mode = mode.as_str(),
+        func_addr = format_args!("{:#x}", func_addr),
+        "patch restored"
+    );
+
+    if log_enabled() {
+        eprintln!(
+            "[injectorpp] patch restored ({}): func={:#x}",
+            mode.as_str(),
+            func_addr
+        );
+    }
+}
+
+/// Called when the first instruction of the patch at `func_addr` crosses a cache line, so it
+/// cannot be replaced atomically and the `len` bytes of the patch are written as is. Threads
+/// entering the function meanwhile may run a half-written patch. Only x86_64 reports this.
+pub(crate) fn patch_not_atomic(func_addr: usize, len: usize) {
+    #[cfg(feature = "tracing")]
+    tracing::warn!(
+        target: "injectorpp",
+        func_addr = format_args!("{:#x}", func_addr),
+        len,
+        "patch crosses a cache line, writing it non-atomically"
+    );
+
+    if log_enabled() {
+        eprintln!(
+            "[injectorpp] patch of {} bytes at func={:#x} crosses a cache line, writing it non-atomically",
+            len, func_addr
+        );
+    }
+}
+
+/// Called when threads are still inside the JIT stub of `func_addr` after its patch was
+/// restored, so the stub is leaked instead of freed. Only counting stubs, generated on
+/// x86_64, report this.
+pub(crate) fn stub_leaked(func_addr: usize, jit_addr: usize, inside: u64) {
+    #[cfg(feature = "tracing")]
+    tracing::warn!(
+        target: "injectorpp",
+        func_addr = format_args!("{:#x}", func_addr),
+        jit_addr = format_args!("{:#x}", jit_addr),
+        inside,
+        "jit stub still in use, leaking it"
+    );
+
+    if log_enabled() {
+        eprintln!(
+            "[injectorpp] patch restored but {} thread(s) still inside the jit stub at {:#x} for func={:#x}, leaking it",
+            inside, jit_addr, func_addr
+        );
+    }
+}
+
+/// Called each time a fake generated by `fake!` is entered. `call_index` is zero based.
+#[allow(unused_variables)]
+pub(crate) fn fake_invoked(file: &'static str, line: u32, column: u32, call_index: usize) {
+    // Subscribers may allocate, which must not reach a fake of the allocator.
+    #[cfg(feature = "tracing")]
+    let _internal = crate::interface::injector::__enter_internal();
+    #[cfg(feature = "tracing")]
+    tracing::trace!(
+        target: "injectorpp",
+        location = format_args!("{}:{}:{}", file, line, column),
+        call_index,
+        "fake invoked"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hex_bytes_formats_space_separated_pairs() {
+        assert_eq!(hex_bytes(&[0xe9, 0x00, 0x1f]), "e9 00 1f");
+        assert_eq!(hex_bytes(&[]), "");
+    }
+}
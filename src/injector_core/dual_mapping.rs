@@ -0,0 +1,178 @@
+#![cfg(target_os = "linux")]
+
+//! JIT memory mapped twice from a memfd, once executable and once writable, for systems that
+//! refuse to make anonymous memory executable, even after it was writable.
+
+use libc::{c_int, c_void};
+use std::sync::Mutex;
+
+/// A block of JIT memory, as `(exec_addr, writable_addr, len)`.
+static MAPPINGS: Mutex<Vec<(usize, usize, usize)>> = Mutex::new(Vec::new());
+
+/// Returns whether JIT memory can be mapped twice from a memfd. Probed once.
+pub(crate) fn available() -> bool {
+    static AVAILABLE: std::sync::OnceLock<bool> = std::sync::OnceLock::new();
+
+    *AVAILABLE.get_or_init(|| unsafe {
+        let size = libc::sysconf(libc::_SC_PAGESIZE) as usize;
+        let ptr = map(std::ptr::null_mut(), size);
+        if ptr == libc::MAP_FAILED {
+            return false;
+        }
+        unmap(ptr as *mut u8);
+        true
+    })
+}
+
+/// Creates a file of `len` bytes to map JIT memory from: a memfd, or an unlinked temporary file
+/// where memfds are unavailable.
+fn create_backing(len: usize) -> Option<c_int> {
+    let mut fd = unsafe { libc::memfd_create(c"injectorpp-jit".as_ptr(), libc::MFD_CLOEXEC) };
+    if fd < 0 {
+        let template = std::env::temp_dir().join("injectorpp-jit-XXXXXX");
+        let mut template = std::ffi::CString::new(template.into_os_string().into_encoded_bytes())
+            .ok()?
+            .into_bytes_with_nul();
+        fd = unsafe { libc::mkostemp(template.as_mut_ptr() as *mut libc::c_char, libc::O_CLOEXEC) };
+        if fd < 0 {
+            return None;
+        }
+        unsafe { libc::unlink(template.as_ptr() as *const libc::c_char) };
+    }
+
+    if unsafe { libc::ftruncate(fd, len as libc::off_t) } != 0 {
+        unsafe { libc::close(fd) };
+        return None;
+    }
+    Some(fd)
+}
+
+/// Maps `len` bytes of JIT memory executable, at `hint` when free, and a second time writable.
+/// Returns the executable address, or `MAP_FAILED`.
+pub(crate) unsafe fn map(hint: *mut c_void, len: usize) -> *mut c_void {
+    let Some(fd) = create_backing(len) else {
+        return libc::MAP_FAILED;
+    };
+
+    let exec = libc::mmap(
+        hint,
+        len,
+        libc::PROT_READ | libc::PROT_EXEC,
+        libc::MAP_SHARED,
+        fd,
+        0,
+    );
+    let writable = if exec == libc::MAP_FAILED {
+        libc::MAP_FAILED
+    } else {
+        libc::mmap(
+            std::ptr::null_mut(),
+            len,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_SHARED,
+            fd,
+            0,
+        )
+    };
+    // The mappings keep the file alive.
+    libc::close(fd);
+
+    if writable == libc::MAP_FAILED {
+        if exec != libc::MAP_FAILED {
+            libc::munmap(exec, len);
+        }
+        return libc::MAP_FAILED;
+    }
+
+    let mut mappings = MAPPINGS.lock().unwrap_or_else(|e| e.into_inner());
+    mappings.push((exec as usize, writable as usize, len));
+    exec
+}
+
+/// Unmaps both views of the JIT memory at `exec`. Returns `false` if it is not mapped twice.
+pub(crate) unsafe fn unmap(exec: *mut u8) -> bool {
+    let mut mappings = MAPPINGS.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(index) = mappings
+        .iter()
+        .position(|&(addr, _, _)| addr == exec as usize)
+    else {
+        return false;
+    };
+
+    let (_, writable, len) = mappings.swap_remove(index);
+    libc::munmap(exec as *mut c_void, len);
+    libc::munmap(writable as *mut c_void, len);
+    true
+}
+
+/// Returns the address `addr` of the executable view is written through, or `addr` itself
+/// when it is not mapped twice.
+pub(crate) fn writable_view(addr: *mut u8) -> *mut u8 {
+    let mappings = MAPPINGS.lock().unwrap_or_else(|e| e.into_inner());
+    mappings
+        .iter()
+        .find(|&&(exec, _, len)| (exec..exec + len).contains(&(addr as usize)))
+        .map_or(addr, |&(exec, writable, _)| {
+            (writable + (addr as usize - exec)) as *mut u8
+        })
+}
+
+/// Makes the pages of the executable view holding the `len` bytes at `addr` writable instead,
+/// for data the JIT code updates. The pages are moved there from the writable view, as
+/// executable pages are never made writable.
+#[cfg(target_arch = "x86_64")]
+pub(crate) unsafe fn make_data_writable(addr: *mut u8, len: usize) {
+    let page_size = libc::sysconf(libc::_SC_PAGESIZE) as usize;
+    let start = addr as usize & !(page_size - 1);
+    let size = (addr as usize + len).next_multiple_of(page_size) - start;
+
+    let view = writable_view(start as *mut u8);
+    if view as usize == start {
+        return;
+    }
+
+    let moved = libc::mremap(
+        view as *mut c_void,
+        size,
+        size,
+        libc::MREMAP_MAYMOVE | libc::MREMAP_FIXED,
+        start as *mut c_void,
+    );
+    if moved == libc::MAP_FAILED {
+        panic!(
+            "Failed to make JIT data writable: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code_written_through_writable_view_runs() {
+        let size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize };
+        let exec = unsafe { map(std::ptr::null_mut(), size) } as *mut u8;
+        assert_ne!(exec as *mut c_void, libc::MAP_FAILED);
+
+        // mov eax, 42; ret
+        let code = [0xB8, 0x2A, 0x00, 0x00, 0x00, 0xC3];
+        let view = writable_view(exec);
+        assert_ne!(view, exec);
+        unsafe { std::ptr::copy_nonoverlapping(code.as_ptr(), view, code.len()) };
+
+        #[cfg(target_arch = "x86_64")]
+        {
+            let func: extern "C" fn() -> i32 = unsafe { std::mem::transmute(exec) };
+            assert_eq!(func(), 42);
+        }
+        assert_eq!(
+            unsafe { std::slice::from_raw_parts(exec, code.len()) },
+            code
+        );
+
+        assert!(unsafe { unmap(exec) });
+        assert_eq!(writable_view(exec), exec);
+    }
+}
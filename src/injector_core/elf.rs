@@ -0,0 +1,484 @@
+#![cfg(any(target_os = "linux", target_os = "freebsd", target_os = "netbsd"))]
+
+//! Reads the ELF objects the running program is loaded from.
+
+use std::ffi::CStr;
+
+/// A loadable segment, `PT_LOAD`, which the `libc` crate only defines on Linux.
+const PT_LOAD: u32 = 1;
+
+/// A section of an ELF file.
+pub(crate) struct Section {
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    pub(crate) name: &'static [u8],
+    /// The section type, `sh_type`.
+    pub(crate) kind: u32,
+    /// The address of the section in the object, `sh_addr`.
+    #[cfg(target_os = "linux")]
+    pub(crate) addr: u64,
+    /// The index of the section it refers to, `sh_link`, such as the string table of a
+    /// symbol table.
+    pub(crate) link: u32,
+    /// The contents, empty for `SHT_NOBITS` sections.
+    pub(crate) data: &'static [u8],
+}
+
+/// The sections of a little-endian ELF file.
+pub(crate) struct ElfFile {
+    pub(crate) is_64: bool,
+    pub(crate) sections: Vec<Section>,
+}
+
+/// Returns the path of the loaded object containing `addr` and the difference between the
+/// addresses it is loaded at and the addresses in its headers, symbols and debug info.
+///
+/// Statically linked programs whose C library lists nothing to `dl_iterate_phdr`, as with
+/// some musl builds, are found from the program headers in the auxiliary vector instead.
+pub(crate) fn object_containing(addr: usize) -> Option<(String, usize)> {
+    struct Search {
+        addr: usize,
+        found: Option<(String, usize)>,
+    }
+
+    unsafe extern "C" fn visit(
+        info: *mut libc::dl_phdr_info,
+        _size: libc::size_t,
+        data: *mut libc::c_void,
+    ) -> libc::c_int {
+        let search = &mut *(data as *mut Search);
+        let info = &*info;
+        let bias = info.dlpi_addr as usize;
+
+        for i in 0..info.dlpi_phnum as usize {
+            let phdr = &*info.dlpi_phdr.add(i);
+            let start = bias.wrapping_add(phdr.p_vaddr as usize);
+            if phdr.p_type == PT_LOAD
+                && (start..start + phdr.p_memsz as usize).contains(&search.addr)
+            {
+                let name = if info.dlpi_name.is_null() {
+                    ""
+                } else {
+                    CStr::from_ptr(info.dlpi_name).to_str().unwrap_or("")
+                };
+                // The main program is listed without a name.
+                let path = if name.is_empty() {
+                    main_program()
+                } else {
+                    name.to_string()
+                };
+                search.found = Some((path, bias));
+                return 1;
+            }
+        }
+
+        0
+    }
+
+    let mut search = Search { addr, found: None };
+    unsafe {
+        libc::dl_iterate_phdr(Some(visit), &mut search as *mut Search as *mut libc::c_void);
+    }
+
+    #[cfg(target_os = "linux")]
+    if search.found.is_none() {
+        return static_program_containing(addr);
+    }
+    search.found
+}
+
+/// Returns the address `offset` bytes into the loaded object `module`, named by its path or
+/// file name, or into the main program when `None`, as addressed in its headers, symbols and
+/// link maps. Returns `None` unless `len` bytes from there are in an executable segment.
+pub(crate) fn code_address(module: Option<&str>, offset: usize, len: usize) -> Option<usize> {
+    /// An executable segment, `PF_X`.
+    const PF_X: u32 = 1;
+
+    struct Search<'a> {
+        module: Option<&'a str>,
+        offset: usize,
+        len: usize,
+        listed: usize,
+        found: Option<usize>,
+    }
+
+    unsafe extern "C" fn visit(
+        info: *mut libc::dl_phdr_info,
+        _size: libc::size_t,
+        data: *mut libc::c_void,
+    ) -> libc::c_int {
+        let search = &mut *(data as *mut Search);
+        let info = &*info;
+        search.listed += 1;
+
+        let name = if info.dlpi_name.is_null() {
+            ""
+        } else {
+            CStr::from_ptr(info.dlpi_name).to_str().unwrap_or("")
+        };
+        let wanted = match search.module {
+            // The main program is listed first.
+            None => search.listed == 1,
+            Some(module) => {
+                !name.is_empty()
+                    && (name == module
+                        || std::path::Path::new(name)
+                            .file_name()
+                            .is_some_and(|file| file == module))
+            }
+        };
+        if !wanted {
+            return 0;
+        }
+
+        let Some(end) = search.offset.checked_add(search.len) else {
+            return 1;
+        };
+        for i in 0..info.dlpi_phnum as usize {
+            let phdr = &*info.dlpi_phdr.add(i);
+            let start = phdr.p_vaddr as usize;
+            if phdr.p_type == PT_LOAD
+                && phdr.p_flags & PF_X != 0
+                && start <= search.offset
+                && end <= start + phdr.p_memsz as usize
+            {
+                search.found = Some((info.dlpi_addr as usize).wrapping_add(search.offset));
+                break;
+            }
+        }
+        1
+    }
+
+    let mut search = Search {
+        module,
+        offset,
+        len: len.max(1),
+        listed: 0,
+        found: None,
+    };
+    unsafe {
+        libc::dl_iterate_phdr(Some(visit), &mut search as *mut Search as *mut libc::c_void);
+    }
+    search.found
+}
+
+/// Returns the path and bias of the main program if it contains `addr`, from the program
+/// headers the kernel passes in the auxiliary vector.
+#[cfg(target_os = "linux")]
+fn static_program_containing(addr: usize) -> Option<(String, usize)> {
+    #[cfg(target_pointer_width = "64")]
+    type Phdr = libc::Elf64_Phdr;
+    #[cfg(target_pointer_width = "32")]
+    type Phdr = libc::Elf32_Phdr;
+
+    let (phdrs, count) = unsafe {
+        (
+            libc::getauxval(libc::AT_PHDR) as usize,
+            libc::getauxval(libc::AT_PHNUM) as usize,
+        )
+    };
+    if phdrs == 0 {
+        return None;
+    }
+    let phdrs = unsafe { std::slice::from_raw_parts(phdrs as *const Phdr, count) };
+
+    // Position-independent programs are loaded wherever their own program headers land.
+    // Others have no `PT_PHDR` and are loaded at the addresses in their headers.
+    let bias = phdrs
+        .iter()
+        .find(|phdr| phdr.p_type == libc::PT_PHDR)
+        .map_or(0, |phdr| {
+            (phdrs.as_ptr() as usize).wrapping_sub(phdr.p_vaddr as usize)
+        });
+
+    phdrs
+        .iter()
+        .any(|phdr| {
+            let start = bias.wrapping_add(phdr.p_vaddr as usize);
+            phdr.p_type == PT_LOAD && (start..start + phdr.p_memsz as usize).contains(&addr)
+        })
+        .then(|| (main_program(), bias))
+}
+
+/// Returns the path of the main program, which `dl_iterate_phdr` lists without a name.
+fn main_program() -> String {
+    #[cfg(target_os = "linux")]
+    {
+        "/proc/self/exe".to_string()
+    }
+
+    // The BSDs do not mount procfs by default.
+    #[cfg(not(target_os = "linux"))]
+    {
+        std::env::current_exe()
+            .map(|path| path.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+}
+
+/// An object loaded into the program.
+#[cfg(target_os = "linux")]
+pub(crate) struct LoadedObject {
+    pub(crate) path: String,
+    /// The difference between the addresses it is loaded at and the addresses in its headers.
+    pub(crate) bias: usize,
+    /// The `(start, end)` of its `PT_GNU_RELRO` segment, which the dynamic loader makes
+    /// read-only once it is relocated.
+    pub(crate) relro: Option<(usize, usize)>,
+}
+
+/// Returns the objects loaded into the program, the main program first.
+#[cfg(target_os = "linux")]
+pub(crate) fn loaded_objects() -> Vec<LoadedObject> {
+    unsafe extern "C" fn visit(
+        info: *mut libc::dl_phdr_info,
+        _size: libc::size_t,
+        data: *mut libc::c_void,
+    ) -> libc::c_int {
+        let objects = &mut *(data as *mut Vec<LoadedObject>);
+        let info = &*info;
+        let bias = info.dlpi_addr as usize;
+
+        let relro = (0..info.dlpi_phnum as usize)
+            .map(|i| &*info.dlpi_phdr.add(i))
+            .find(|phdr| phdr.p_type == libc::PT_GNU_RELRO)
+            .map(|phdr| {
+                let start = bias.wrapping_add(phdr.p_vaddr as usize);
+                (start, start + phdr.p_memsz as usize)
+            });
+        let name = if info.dlpi_name.is_null() {
+            ""
+        } else {
+            CStr::from_ptr(info.dlpi_name).to_str().unwrap_or("")
+        };
+        // The main program is listed without a name.
+        let path = if name.is_empty() && objects.is_empty() {
+            "/proc/self/exe"
+        } else {
+            name
+        };
+        if !path.is_empty() {
+            objects.push(LoadedObject {
+                path: path.to_string(),
+                bias,
+                relro,
+            });
+        }
+
+        0
+    }
+
+    let mut objects = Vec::new();
+    unsafe {
+        libc::dl_iterate_phdr(
+            Some(visit),
+            &mut objects as *mut Vec<LoadedObject> as *mut libc::c_void,
+        );
+    }
+    objects
+}
+
+/// Maps the file at `path` into memory. The mapping is never released, as what is read from
+/// it is cached.
+pub(crate) fn map_file(path: &str) -> Option<&'static [u8]> {
+    let path = std::ffi::CString::new(path).ok()?;
+    unsafe {
+        let fd = libc::open(path.as_ptr(), libc::O_RDONLY | libc::O_CLOEXEC);
+        if fd < 0 {
+            return None;
+        }
+
+        let mut stat: libc::stat = std::mem::zeroed();
+        let len = if libc::fstat(fd, &mut stat) == 0 {
+            stat.st_size as usize
+        } else {
+            0
+        };
+        let data = if len == 0 {
+            libc::MAP_FAILED
+        } else {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                fd,
+                0,
+            )
+        };
+        libc::close(fd);
+
+        if data == libc::MAP_FAILED {
+            return None;
+        }
+        Some(std::slice::from_raw_parts(data as *const u8, len))
+    }
+}
+
+/// Reads the section headers of a little-endian ELF file.
+pub(crate) fn parse(file: &'static [u8]) -> Option<ElfFile> {
+    if file.get(..4)? != b"\x7fELF" || *file.get(5)? != 1 {
+        return None;
+    }
+    let is_64 = match file.get(4)? {
+        1 => false,
+        2 => true,
+        _ => return None,
+    };
+
+    let read = |offset: usize, size: usize| -> Option<u64> {
+        let bytes = file.get(offset..offset + size)?;
+        let mut value = [0u8; 8];
+        value[..size].copy_from_slice(bytes);
+        Some(u64::from_le_bytes(value))
+    };
+    let word = if is_64 { 8 } else { 4 };
+    let (shoff, shentsize, shnum, shstrndx) = if is_64 {
+        (
+            read(0x28, 8)?,
+            read(0x3a, 2)?,
+            read(0x3c, 2)?,
+            read(0x3e, 2)?,
+        )
+    } else {
+        (
+            read(0x20, 4)?,
+            read(0x2e, 2)?,
+            read(0x30, 2)?,
+            read(0x32, 2)?,
+        )
+    };
+
+    // Returns the offset of the name of a section and the section, still unnamed.
+    let section = |index: u64| -> Option<(u64, Section)> {
+        let header = (shoff + index * shentsize) as usize;
+        let name = read(header, 4)?;
+        let kind = read(header + 4, 4)? as u32;
+        let offset = read(header + 8 + 2 * word, word)? as usize;
+        let size = read(header + 8 + 3 * word, word)? as usize;
+        // SHT_NOBITS sections have no contents in the file.
+        let data = if kind == 8 {
+            &[][..]
+        } else {
+            file.get(offset..offset.checked_add(size)?)?
+        };
+        let section = Section {
+            name: &[],
+            kind,
+            #[cfg(target_os = "linux")]
+            addr: read(header + 8 + word, word)?,
+            link: read(header + 8 + 4 * word, 4)? as u32,
+            data,
+        };
+        Some((name, section))
+    };
+
+    let names = section(shstrndx)?.1.data;
+    let mut sections = Vec::with_capacity(shnum as usize);
+    for index in 0..shnum {
+        let (name, mut section) = section(index)?;
+        let name = names.get(name as usize..)?;
+        section.name = CStr::from_bytes_until_nul(name).ok()?.to_bytes();
+        sections.push(section);
+    }
+
+    Some(ElfFile { is_64, sections })
+}
+
+/// A function of the symbol table of an ELF file.
+pub(crate) struct FunctionSymbol {
+    /// The name, mangled, or empty when the string table has none.
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    pub(crate) name: &'static [u8],
+    /// The address of the function in the object, `st_value`.
+    pub(crate) addr: u64,
+    pub(crate) size: u64,
+}
+
+/// Returns the functions defined in `.symtab`, or in `.dynsym`, which only lists exported
+/// functions, when the file has no `.symtab`.
+pub(crate) fn function_symbols(elf: &ElfFile) -> Option<Vec<FunctionSymbol>> {
+    const SHT_SYMTAB: u32 = 2;
+    const SHT_DYNSYM: u32 = 11;
+    const STT_FUNC: u8 = 2;
+
+    let table = elf
+        .sections
+        .iter()
+        .find(|section| section.kind == SHT_SYMTAB)
+        .or_else(|| {
+            elf.sections
+                .iter()
+                .find(|section| section.kind == SHT_DYNSYM)
+        })?;
+    let strings = elf
+        .sections
+        .get(table.link as usize)
+        .map_or(&[][..], |section| section.data);
+
+    // Elf64_Sym: name, info, other, shndx, value, size.
+    // Elf32_Sym: name, value, size, info, other, shndx.
+    let read = |bytes: &[u8]| {
+        let mut value = [0u8; 8];
+        value[..bytes.len()].copy_from_slice(bytes);
+        u64::from_le_bytes(value)
+    };
+    let entry_size = if elf.is_64 { 24 } else { 16 };
+    let functions = table
+        .data
+        .chunks_exact(entry_size)
+        .filter_map(|symbol| {
+            let (info, shndx, value, size) = if elf.is_64 {
+                (
+                    symbol[4],
+                    read(&symbol[6..8]),
+                    read(&symbol[8..16]),
+                    read(&symbol[16..24]),
+                )
+            } else {
+                (
+                    symbol[12],
+                    read(&symbol[14..16]),
+                    read(&symbol[4..8]),
+                    read(&symbol[8..12]),
+                )
+            };
+            if info & 0xF != STT_FUNC || shndx == 0 {
+                return None;
+            }
+
+            let name = strings
+                .get(read(&symbol[..4]) as usize..)
+                .and_then(|name| CStr::from_bytes_until_nul(name).ok())
+                .map_or(&[][..], CStr::to_bytes);
+            // Thumb functions have the lowest bit of their address set.
+            let addr = if cfg!(target_arch = "arm") {
+                value & !1
+            } else {
+                value
+            };
+            Some(FunctionSymbol { name, addr, size })
+        })
+        .collect();
+    Some(functions)
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_static_program_containing_matches_dynamic_loader() {
+        let addr = test_static_program_containing_matches_dynamic_loader as fn() as usize;
+
+        let found = static_program_containing(addr);
+        assert!(found.is_some());
+        assert_eq!(found, object_containing(addr));
+    }
+
+    #[test]
+    fn test_static_program_containing_skips_other_objects() {
+        let addr = libc::getpid as unsafe extern "C" fn() -> libc::pid_t as usize;
+
+        assert_eq!(static_program_containing(addr), None);
+    }
+}
@@ -0,0 +1,250 @@
+use std::backtrace::Backtrace;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Condvar;
+use std::sync::Mutex;
+use std::thread::ThreadId;
+use std::time::Duration;
+use std::time::Instant;
+
+/// An injector currently holding a function lock.
+struct Holder {
+    id: u64,
+    exclusive: bool,
+    thread_id: ThreadId,
+    thread: String,
+    backtrace: Backtrace,
+}
+
+impl Holder {
+    fn describe(&self) -> String {
+        let mode = if self.exclusive {
+            "global"
+        } else {
+            "thread-local"
+        };
+        let mut description = format!("{} fake on thread '{}'", mode, self.thread);
+        if let std::backtrace::BacktraceStatus::Captured = self.backtrace.status() {
+            description.push_str(&format!(", locked at:\n{}", self.backtrace));
+        }
+        description
+    }
+}
+
+/// The holders of every locked function, and what each thread waiting without a timeout
+/// waits for.
+#[derive(Default)]
+struct Locks {
+    holders: HashMap<usize, Vec<Holder>>,
+    waiting: HashMap<ThreadId, (usize, bool)>,
+}
+
+impl Locks {
+    /// Returns the holders keeping `thread` from locking `func_addr` in the requested mode.
+    fn blockers(&self, func_addr: usize, exclusive: bool, thread: ThreadId) -> Vec<&Holder> {
+        self.holders
+            .get(&func_addr)
+            .into_iter()
+            .flatten()
+            .filter(|holder| holder.thread_id != thread && (exclusive || holder.exclusive))
+            .collect()
+    }
+
+    /// Returns the holders blocking `thread` if some of them wait, directly or through other
+    /// waiting threads, for a function `thread` holds, so that none of them would ever wake.
+    ///
+    /// Only waits without a timeout are tracked, as the others end on their own.
+    fn deadlock(&self, func_addr: usize, exclusive: bool, thread: ThreadId) -> Option<Vec<String>> {
+        let blockers = self.blockers(func_addr, exclusive, thread);
+        let mut visited = HashSet::new();
+        let mut pending: Vec<ThreadId> = blockers.iter().map(|holder| holder.thread_id).collect();
+        while let Some(waiter) = pending.pop() {
+            if waiter == thread {
+                return Some(blockers.iter().map(|holder| holder.describe()).collect());
+            }
+            if !visited.insert(waiter) {
+                continue;
+            }
+            if let Some(&(func_addr, exclusive)) = self.waiting.get(&waiter) {
+                pending.extend(
+                    self.blockers(func_addr, exclusive, waiter)
+                        .iter()
+                        .map(|holder| holder.thread_id),
+                );
+            }
+        }
+
+        None
+    }
+}
+
+static LOCKS: std::sync::LazyLock<Mutex<Locks>> = std::sync::LazyLock::new(Mutex::default);
+
+/// Notified whenever a function lock is released.
+static RELEASED: Condvar = Condvar::new();
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Why a function lock could not be acquired.
+pub(crate) enum LockError {
+    /// The timeout elapsed. Holds a description of every current holder.
+    Timeout(Vec<String>),
+    /// An injector on the current thread holds the function in the other mode, so waiting
+    /// would never end. `exclusive` is the mode that was requested.
+    HeldByCurrentThread { exclusive: bool },
+    /// Waiting would never end because a holder, directly or through other waiting threads,
+    /// waits for a function the current thread holds. Holds a description of every holder.
+    Deadlock(Vec<String>),
+}
+
+/// A lock on a single patched function, released on drop.
+///
+/// Thread-local fakes take a shared lock: any number of threads may fake the same function
+/// through the dispatcher. Global fakes take an exclusive lock, waiting until no injector on
+/// another thread fakes the function. Injectors faking different functions never wait for
+/// each other.
+///
+/// Functions locked together are locked in ascending address order, so two injectors locking
+/// the same functions never each hold one the other waits for. Injectors locking functions one
+/// by one in opposite orders may, and the one that would wait for the other last fails
+/// instead of waiting forever.
+pub(crate) struct FunctionLock {
+    func_addr: usize,
+    id: u64,
+}
+
+impl FunctionLock {
+    /// Blocks until `func_addr` can be locked, shared or exclusively.
+    ///
+    /// Fails instead when `timeout` elapses first, right away when a conflicting holder is on
+    /// the current thread, and as soon as a holder waits for a function the current thread
+    /// holds.
+    pub(crate) fn acquire(
+        func_addr: usize,
+        exclusive: bool,
+        timeout: Option<Duration>,
+    ) -> Result<Self, LockError> {
+        let current = std::thread::current();
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut locks = LOCKS.lock().unwrap_or_else(|e| e.into_inner());
+        let result = loop {
+            let holders = locks.holders.entry(func_addr).or_default();
+            // Global fakes of the same function stack on one thread, but a thread-local
+            // fake cannot be stacked with a global one.
+            if holders
+                .iter()
+                .any(|holder| holder.thread_id == current.id() && holder.exclusive != exclusive)
+            {
+                break Err(LockError::HeldByCurrentThread { exclusive });
+            }
+
+            let free = holders.iter().all(|holder| {
+                (!exclusive && !holder.exclusive) || holder.thread_id == current.id()
+            });
+
+            if free {
+                let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+                holders.push(Holder {
+                    id,
+                    exclusive,
+                    thread_id: current.id(),
+                    thread: current
+                        .name()
+                        .map(str::to_string)
+                        .unwrap_or_else(|| format!("{:?}", current.id())),
+                    // Capturing is slow, so only done when it may be reported.
+                    backtrace: if crate::injector_core::diagnostics::log_enabled() {
+                        Backtrace::force_capture()
+                    } else {
+                        Backtrace::disabled()
+                    },
+                });
+
+                break Ok(Self { func_addr, id });
+            }
+
+            locks = match deadline {
+                None => {
+                    if let Some(holders) = locks.deadlock(func_addr, exclusive, current.id()) {
+                        break Err(LockError::Deadlock(holders));
+                    }
+
+                    locks.waiting.insert(current.id(), (func_addr, exclusive));
+                    RELEASED.wait(locks).unwrap_or_else(|e| e.into_inner())
+                }
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        break Err(LockError::Timeout(
+                            holders.iter().map(Holder::describe).collect(),
+                        ));
+                    }
+
+                    RELEASED
+                        .wait_timeout(locks, deadline - now)
+                        .unwrap_or_else(|e| e.into_inner())
+                        .0
+                }
+            };
+        };
+
+        locks.waiting.remove(&current.id());
+        if locks
+            .holders
+            .get(&func_addr)
+            .is_some_and(|holders| holders.is_empty())
+        {
+            locks.holders.remove(&func_addr);
+        }
+
+        result
+    }
+
+    /// Locks every function of `func_addrs`, in ascending address order.
+    ///
+    /// Fails with the address that could not be locked, releasing the locks already taken.
+    pub(crate) fn acquire_all(
+        func_addrs: &[usize],
+        exclusive: bool,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<Self>, (usize, LockError)> {
+        let mut func_addrs = func_addrs.to_vec();
+        func_addrs.sort_unstable();
+        func_addrs.dedup();
+
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut locks = Vec::with_capacity(func_addrs.len());
+        for func_addr in func_addrs {
+            let remaining =
+                deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+            match Self::acquire(func_addr, exclusive, remaining) {
+                Ok(lock) => locks.push(lock),
+                Err(error) => return Err((func_addr, error)),
+            }
+        }
+
+        Ok(locks)
+    }
+
+    /// Returns the address of the locked function.
+    pub(crate) fn func_addr(&self) -> usize {
+        self.func_addr
+    }
+}
+
+impl Drop for FunctionLock {
+    fn drop(&mut self) {
+        let mut locks = LOCKS.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(holders) = locks.holders.get_mut(&self.func_addr) {
+            holders.retain(|holder| holder.id != self.id);
+            if holders.is_empty() {
+                locks.holders.remove(&self.func_addr);
+            }
+        }
+        drop(locks);
+
+        RELEASED.notify_all();
+    }
+}
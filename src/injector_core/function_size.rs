@@ -0,0 +1,286 @@
+//! Sizes of patched functions, so a patch never runs past the end of the function it is
+//! written to and into the code of the next one.
+
+use std::cell::Cell;
+
+thread_local! {
+    /// Whether `check_patch_fits()` is skipped on this thread.
+    static SKIP_CHECK: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Skips `check_patch_fits()` on the current thread until dropped. Entered while installing a
+/// fake whose builder called `allow_undersized_patch()`.
+pub(crate) struct SkipSizeCheck {
+    previous: bool,
+}
+
+impl SkipSizeCheck {
+    pub(crate) fn enter() -> Self {
+        Self {
+            previous: SKIP_CHECK.with(|skip| skip.replace(true)),
+        }
+    }
+}
+
+impl Drop for SkipSizeCheck {
+    fn drop(&mut self) {
+        let _ = SKIP_CHECK.try_with(|skip| skip.set(self.previous));
+    }
+}
+
+/// Panics if writing `patch_size` bytes at `func_addr` would run past the end of the function
+/// there. Functions whose size cannot be found are patched unchecked.
+pub(crate) fn check_patch_fits(func_addr: usize, patch_size: usize) {
+    if SKIP_CHECK.with(Cell::get) {
+        return;
+    }
+
+    let Some(size) = function_size(func_addr, patch_size) else {
+        return;
+    };
+
+    if size < patch_size {
+        let name = function_name(func_addr);
+        panic!(
+            "injectorpp: {} is only {} bytes, too small for the {}-byte patch, which would \
+             overwrite the code following it. Add code to the function body (e.g., \
+             `let _ = std::hint::black_box(0u32);`) to increase its compiled size, or call \
+             allow_undersized_patch() if the bytes following it are unused.",
+            name, size, patch_size
+        );
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    crate::injector_core::x86_64_insn::check_branches_into_patch(func_addr, size, patch_size);
+}
+
+/// Names the function at `func_addr` in panic messages.
+pub(crate) fn function_name(func_addr: usize) -> String {
+    match crate::injector_core::symbols::symbol_name(func_addr) {
+        Some(symbol) => format!("{} ({:#x})", symbol, func_addr),
+        None => format!("The function at {:#x}", func_addr),
+    }
+}
+
+/// Returns the size of the function starting at `func_addr`, including the padding after it,
+/// when it can be found, as below. Each function is looked up once.
+pub(crate) fn function_size(func_addr: usize, limit: usize) -> Option<usize> {
+    crate::injector_core::symbols::function_size(func_addr, limit, || {
+        find_function_size(func_addr, limit)
+    })
+}
+
+/// Returns the size of the function starting at `func_addr`, including the padding after it,
+/// when the unwind tables of its module record it. Functions without unwind data, such as
+/// leaf functions on x86_64, are bounded by the next function that has some, which is only
+/// searched for within `limit` bytes.
+#[cfg(all(windows, not(target_arch = "x86")))]
+fn find_function_size(func_addr: usize, limit: usize) -> Option<usize> {
+    use crate::injector_core::winapi::*;
+
+    let lookup = |addr: usize| {
+        let mut image_base = 0u64;
+        let entry =
+            unsafe { RtlLookupFunctionEntry(addr as u64, &mut image_base, std::ptr::null_mut()) };
+        if entry.is_null() {
+            return None;
+        }
+
+        let entry = unsafe { &*entry };
+        let begin = image_base as usize + entry.begin_address as usize;
+        let length = unsafe { runtime_function_length(entry, image_base as usize) };
+        Some((begin, length))
+    };
+
+    if let Some((begin, length)) = lookup(func_addr) {
+        return (begin == func_addr).then_some(length);
+    }
+
+    (1..limit)
+        .find(|&offset| lookup(func_addr + offset).is_some_and(|(begin, _)| begin > func_addr))
+}
+
+/// Returns the size of the function starting at `func_addr`, including the padding after it,
+/// from the `LC_FUNCTION_STARTS` table of its image, which lists the address of every
+/// function. The last function of an image ends with the `__TEXT` section holding it.
+#[cfg(target_os = "macos")]
+fn find_function_size(func_addr: usize, _limit: usize) -> Option<usize> {
+    const MH_MAGIC_64: u32 = 0xfeed_facf;
+    const LC_SEGMENT_64: u32 = 0x19;
+    const LC_FUNCTION_STARTS: u32 = 0x26;
+
+    let mut info: libc::Dl_info = unsafe { std::mem::zeroed() };
+    let found = unsafe { libc::dladdr(func_addr as *const libc::c_void, &mut info) };
+    if found == 0 || info.dli_fbase.is_null() {
+        return None;
+    }
+
+    let read_u32 = |addr: usize| unsafe { (addr as *const u32).read_unaligned() };
+    let read_u64 = |addr: usize| unsafe { (addr as *const u64).read_unaligned() };
+
+    let header = info.dli_fbase as usize;
+    if read_u32(header) != MH_MAGIC_64 {
+        return None;
+    }
+
+    let mut text_vmaddr = None;
+    let mut text_sections = Vec::new();
+    let mut linkedit = None;
+    let mut function_starts = None;
+    let mut command = header + 32;
+    for _ in 0..read_u32(header + 16) {
+        match read_u32(command) {
+            LC_SEGMENT_64 => {
+                let name = unsafe { std::slice::from_raw_parts((command + 8) as *const u8, 16) };
+                if name.starts_with(b"__TEXT\0") {
+                    text_vmaddr = Some(read_u64(command + 24) as usize);
+                    // The `section_64` headers follow the segment command, 80 bytes each.
+                    for i in 0..read_u32(command + 64) as usize {
+                        let section = command + 72 + i * 80;
+                        text_sections.push((
+                            read_u64(section + 32) as usize,
+                            read_u64(section + 40) as usize,
+                        ));
+                    }
+                } else if name.starts_with(b"__LINKEDIT\0") {
+                    linkedit = Some((
+                        read_u64(command + 24) as usize,
+                        read_u64(command + 40) as usize,
+                    ));
+                }
+            }
+            LC_FUNCTION_STARTS => {
+                function_starts = Some((
+                    read_u32(command + 8) as usize,
+                    read_u32(command + 12) as usize,
+                ));
+            }
+            _ => {}
+        }
+        command += read_u32(command + 4) as usize;
+    }
+
+    let slide = header.wrapping_sub(text_vmaddr?);
+    let (linkedit_vmaddr, linkedit_fileoff) = linkedit?;
+    let (data_offset, data_size) = function_starts?;
+    let data = slide
+        .wrapping_add(linkedit_vmaddr)
+        .wrapping_sub(linkedit_fileoff)
+        .wrapping_add(data_offset);
+    let mut bytes = unsafe { std::slice::from_raw_parts(data as *const u8, data_size) }.iter();
+
+    // The last function ends with the section holding it.
+    let section_end = || {
+        text_sections.iter().find_map(|&(addr, size)| {
+            let start = slide.wrapping_add(addr);
+            (start..start + size)
+                .contains(&func_addr)
+                .then_some(start + size - func_addr)
+        })
+    };
+
+    // ULEB128 deltas from the previous function start, the first one from the image start,
+    // ended by a zero delta.
+    let mut start = header;
+    let mut previous = None;
+    loop {
+        let mut delta = 0usize;
+        let mut shift = 0;
+        loop {
+            let Some(&byte) = bytes.next() else {
+                delta = 0;
+                break;
+            };
+            if shift >= usize::BITS {
+                return None;
+            }
+            delta |= ((byte & 0x7F) as usize) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+        if delta == 0 {
+            return if previous == Some(func_addr) {
+                section_end()
+            } else {
+                None
+            };
+        }
+
+        start += delta;
+        if start > func_addr {
+            return (previous == Some(func_addr)).then_some(start - func_addr);
+        }
+        previous = Some(start);
+    }
+}
+
+/// Returns the size of the function starting at `func_addr`, including the padding after it,
+/// from the symbol table of the ELF object it is loaded from. Functions of objects without
+/// `.symtab` are looked up in `.dynsym`, which only lists exported functions.
+#[cfg(any(target_os = "linux", target_os = "freebsd", target_os = "netbsd"))]
+fn find_function_size(func_addr: usize, _limit: usize) -> Option<usize> {
+    let (path, bias) = crate::injector_core::elf::object_containing(func_addr)?;
+    let functions = elf_functions(&path)?;
+    let addr = func_addr.checked_sub(bias)? as u64;
+
+    let index = functions.partition_point(|&(start, _)| start < addr);
+    let &(start, size) = functions.get(index)?;
+    if start != addr {
+        return None;
+    }
+
+    // Up to the next function, so the padding after the function counts.
+    match functions[index..].iter().find(|&&(next, _)| next > addr) {
+        Some(&(next, _)) => Some((next - addr) as usize),
+        None => (size != 0).then_some(size as usize),
+    }
+}
+
+/// Returns the `(address, size)` of the functions in the symbol table of the ELF object at
+/// `path`, sorted by address. Read once per object and kept for the lifetime of the process.
+#[cfg(any(target_os = "linux", target_os = "freebsd", target_os = "netbsd"))]
+pub(crate) fn elf_functions(path: &str) -> Option<std::sync::Arc<Vec<(u64, u64)>>> {
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    type Functions = Option<Arc<Vec<(u64, u64)>>>;
+    static OBJECTS: Mutex<Option<HashMap<String, Functions>>> = Mutex::new(None);
+
+    let mut objects = OBJECTS.lock().unwrap_or_else(|e| e.into_inner());
+    objects
+        .get_or_insert_with(HashMap::new)
+        .entry(path.to_string())
+        .or_insert_with(|| {
+            crate::injector_core::elf::map_file(path)
+                .and_then(read_elf_functions)
+                .map(Arc::new)
+        })
+        .clone()
+}
+
+#[cfg(any(target_os = "linux", target_os = "freebsd", target_os = "netbsd"))]
+fn read_elf_functions(file: &'static [u8]) -> Option<Vec<(u64, u64)>> {
+    let elf = crate::injector_core::elf::parse(file)?;
+    let mut functions: Vec<(u64, u64)> = crate::injector_core::elf::function_symbols(&elf)?
+        .into_iter()
+        .map(|symbol| (symbol.addr, symbol.size))
+        .collect();
+
+    functions.sort_unstable();
+    Some(functions)
+}
+
+/// 32-bit Windows modules have no function table, as their exceptions unwind through frames.
+/// Other platforms are not looked up.
+#[cfg(not(any(
+    all(windows, not(target_arch = "x86")),
+    target_os = "macos",
+    target_os = "linux",
+    target_os = "freebsd",
+    target_os = "netbsd"
+)))]
+fn find_function_size(_func_addr: usize, _limit: usize) -> Option<usize> {
+    None
+}
@@ -0,0 +1,114 @@
+#![cfg(target_os = "linux")]
+
+//! Redirects calls of a dynamically linked function by rewriting the GOT entries of the
+//! loaded ELF objects, instead of the code of the function.
+//!
+//! Every call of an imported function goes through a GOT entry of the calling object, so
+//! pointing the entries at the fake reaches every caller outside the object defining the
+//! function, without writing to the pages of shared libraries. Calls the defining object
+//! makes internally, such as those of glibc to its own `memset`, bypass the GOT and keep
+//! reaching the function.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::injector_core::elf;
+use crate::injector_core::plt::{self, Dynamic};
+use crate::injector_core::redirect::{self, Redirect, Site};
+
+/// Points every GOT entry of the loaded objects that refers to the function at `func_addr`
+/// to `target`, until the returned redirection is dropped.
+///
+/// Panics if no GOT entry refers to the function, as its callers then call it directly.
+pub(crate) fn redirect(func_addr: usize, target: usize, jit: Vec<(*mut u8, usize)>) -> Redirect {
+    // Entries redirected by an earlier redirection of the function, e.g. a leaked one, are
+    // redirected on top of it.
+    let mut values = vec![func_addr];
+    values.extend(redirect::targets_of(func_addr));
+    let sites = find_entries(func_addr, target, &values);
+    if sites.is_empty() {
+        for (jit_memory, jit_size) in jit {
+            unsafe { crate::injector_core::common::free_jit_memory(jit_memory, jit_size) };
+        }
+        panic!(
+            "injectorpp: no GOT entry of a loaded object refers to {}, so its callers do \
+             not call it through the GOT. Use the default PatchStrategy::InlineBranch to fake it.",
+            crate::injector_core::function_size::function_name(func_addr)
+        );
+    }
+
+    Redirect::install(func_addr, target, sites, jit, "GOT entries")
+}
+
+/// Returns the GOT entries of the loaded objects holding one of `values`, or, when not bound
+/// yet under lazy binding, naming the symbol of the function at `func_addr`.
+fn find_entries(func_addr: usize, target: usize, values: &[usize]) -> Vec<Site> {
+    let name = exported_name(func_addr);
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+
+    let mut entries = Vec::new();
+    for object in elf::loaded_objects() {
+        let Some(dynamic) = plt::dynamic(&object.path) else {
+            continue;
+        };
+        // The loader protects whole pages, up to the last page the segment fills.
+        let read_only = |addr: usize| {
+            object.relro.is_some_and(|(start, end)| {
+                (start & !(page_size - 1)..end & !(page_size - 1)).contains(&addr)
+            })
+        };
+
+        for &(offset, symbol) in &dynamic.slots {
+            if symbol.is_empty() || !Dynamic::contains(&dynamic.got, offset) {
+                continue;
+            }
+
+            let addr = object.bias.wrapping_add(offset as usize);
+            let value = unsafe { read_entry(addr) };
+            let unbound = name.as_deref() == Some(symbol)
+                && Dynamic::contains(&dynamic.plt, value.wrapping_sub(object.bias) as u64);
+            if values.contains(&value) || unbound {
+                entries.push(Site::GotEntry {
+                    addr,
+                    original: value,
+                    redirected: target,
+                    read_only: read_only(addr),
+                });
+            }
+        }
+    }
+    entries
+}
+
+/// Returns the name of the exported symbol starting at `func_addr`.
+fn exported_name(func_addr: usize) -> Option<Vec<u8>> {
+    let mut info: libc::Dl_info = unsafe { std::mem::zeroed() };
+    let found = unsafe { libc::dladdr(func_addr as *const libc::c_void, &mut info) };
+    if found == 0 || info.dli_sname.is_null() || info.dli_saddr as usize != func_addr {
+        return None;
+    }
+    Some(
+        unsafe { std::ffi::CStr::from_ptr(info.dli_sname) }
+            .to_bytes()
+            .to_vec(),
+    )
+}
+
+pub(crate) unsafe fn read_entry(addr: usize) -> usize {
+    (*(addr as *const AtomicUsize)).load(Ordering::Acquire)
+}
+
+/// Writes `value` to the GOT entry in one store, so concurrent callers read either value.
+/// Entries in read-only pages are made writable for the write only.
+pub(crate) unsafe fn write_entry(addr: usize, value: usize, read_only: bool) {
+    let page_size = libc::sysconf(libc::_SC_PAGESIZE) as usize;
+    let page = (addr & !(page_size - 1)) as *mut libc::c_void;
+    if read_only && libc::mprotect(page, page_size, libc::PROT_READ | libc::PROT_WRITE) != 0 {
+        panic!("mprotect failed");
+    }
+
+    (*(addr as *const AtomicUsize)).store(value, Ordering::Release);
+
+    if read_only {
+        libc::mprotect(page, page_size, libc::PROT_READ);
+    }
+}
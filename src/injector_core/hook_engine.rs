@@ -0,0 +1,136 @@
+#![cfg(feature = "external-engine")]
+
+//! Delegates faking to an external hooking engine registered with
+//! `InjectorPP::set_hook_engine()`, for `PatchStrategy::External`.
+
+use std::sync::{Arc, Mutex, RwLock};
+
+use crate::injector_core::redirect::{Redirect, Site};
+
+/// A hooking engine injectorpp delegates faking to with `PatchStrategy::External`, such as the
+/// `Interceptor` of frida-gum. Registered with `InjectorPP::set_hook_engine()`.
+///
+/// The builders, macros, call count verifiers and fake handles of injectorpp work the same
+/// with an engine: injectorpp generates the fake, and the engine only makes the calls of the
+/// function reach it.
+///
+/// # Safety
+///
+/// Until `revert()` is called with the id `replace()` returned, every call of the function,
+/// from any thread, must reach the replacement with the arguments and the return address of
+/// the call, as a branch would, so the replacement returns to the caller.
+pub unsafe trait HookEngine: Send + Sync + 'static {
+    /// Names the engine in panic messages and logs.
+    fn name(&self) -> &str;
+
+    /// Makes the calls of the function at `func` reach `replacement`. Returns an id passed to
+    /// `revert()`, or why the function cannot be hooked.
+    ///
+    /// # Safety
+    ///
+    /// `func` and `replacement` are functions of the same signature.
+    unsafe fn replace(&self, func: *const (), replacement: *const ()) -> Result<u64, String>;
+
+    /// Makes the calls of the function hooked by `replace()` reach it again.
+    ///
+    /// # Safety
+    ///
+    /// `hook` was returned by `replace()` and was not reverted yet.
+    unsafe fn revert(&self, hook: u64);
+}
+
+/// The engine of the fakes installed from now on.
+static ENGINE: RwLock<Option<Arc<dyn HookEngine>>> = RwLock::new(None);
+
+/// Registers the engine of the fakes installed from now on. Installed fakes keep the engine
+/// that installed them.
+pub(crate) fn set_engine(engine: Arc<dyn HookEngine>) {
+    *ENGINE.write().unwrap_or_else(|e| e.into_inner()) = Some(engine);
+}
+
+/// Returns the registered engine, if any.
+pub(crate) fn engine() -> Option<Arc<dyn HookEngine>> {
+    ENGINE.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// A function hooked by an engine.
+pub(crate) struct Hook {
+    pub(crate) func_addr: usize,
+    target: usize,
+    engine: Arc<dyn HookEngine>,
+    /// The id of the hook while it is installed.
+    id: Mutex<Option<u64>>,
+}
+
+impl Hook {
+    /// Installs the hook, if it is not installed.
+    pub(crate) unsafe fn install(&self) -> Result<(), String> {
+        let mut id = self.id.lock().unwrap_or_else(|e| e.into_inner());
+        if id.is_none() {
+            *id = Some(
+                self.engine
+                    .replace(self.func_addr as *const (), self.target as *const ())?,
+            );
+        }
+        Ok(())
+    }
+
+    /// Reverts the hook, if it is installed.
+    pub(crate) unsafe fn revert(&self) {
+        if let Some(id) = self.id.lock().unwrap_or_else(|e| e.into_inner()).take() {
+            self.engine.revert(id);
+        }
+    }
+
+    pub(crate) fn is_installed(&self) -> bool {
+        self.id.lock().unwrap_or_else(|e| e.into_inner()).is_some()
+    }
+
+    /// Installs the hook, panicking if the engine refuses it.
+    pub(crate) unsafe fn install_or_panic(&self) {
+        if let Err(reason) = self.install() {
+            panic!("{}", self.refusal(&reason));
+        }
+    }
+
+    fn refusal(&self, reason: &str) -> String {
+        format!(
+            "injectorpp: the {} hooking engine cannot hook {}: {}",
+            self.engine.name(),
+            crate::injector_core::function_size::function_name(self.func_addr),
+            reason
+        )
+    }
+}
+
+/// Hooks the function at `func_addr` with the registered engine, making its calls reach
+/// `target` until the returned redirection is dropped.
+///
+/// Panics if no engine is registered, or if it refuses to hook the function.
+pub(crate) fn redirect(func_addr: usize, target: usize, jit: Vec<(*mut u8, usize)>) -> Redirect {
+    let free_jit = |jit: &[(*mut u8, usize)]| {
+        for &(jit_memory, jit_size) in jit {
+            unsafe { crate::injector_core::common::free_jit_memory(jit_memory, jit_size) };
+        }
+    };
+
+    let Some(engine) = engine() else {
+        free_jit(&jit);
+        panic!(
+            "injectorpp: PatchStrategy::External needs an engine registered with \
+             InjectorPP::set_hook_engine()"
+        );
+    };
+    let hook = Hook {
+        func_addr,
+        target,
+        engine,
+        id: Mutex::new(None),
+    };
+    if let Err(reason) = unsafe { hook.install() } {
+        free_jit(&jit);
+        panic!("{}", hook.refusal(&reason));
+    }
+
+    Redirect::install(func_addr, target, vec![Site::Hook(hook)], jit, "hooked")
+}
@@ -1,40 +1,301 @@
 use crate::injector_core::common::*;
+use crate::injector_core::function_size::SkipSizeCheck;
 
 use super::patch_trait::PatchTrait;
 
+use super::redirect::{Redirect, RedirectMode};
+
 #[cfg(target_arch = "x86_64")]
 use super::patch_amd64::PatchAmd64;
-#[cfg(target_arch = "aarch64")]
-use super::patch_arm64::PatchArm64;
 #[cfg(target_arch = "arm")]
 use super::patch_arm::PatchArm;
+#[cfg(any(target_arch = "aarch64", target_arch = "arm64ec"))]
+use super::patch_arm64::PatchArm64;
+#[cfg(target_arch = "riscv64")]
+use super::patch_riscv64::PatchRiscv64;
+#[cfg(target_arch = "x86")]
+use super::patch_x86::PatchX86;
 
-#[cfg(any(target_arch = "x86_64", target_arch = "aarch64", target_arch = "arm"))]
+#[cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec",
+    target_arch = "arm"
+))]
 use super::thread_local_registry;
 
-#[cfg(any(target_arch = "x86_64", target_arch = "aarch64", target_arch = "arm"))]
+#[cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec",
+    target_arch = "arm"
+))]
 use super::thread_local_registry::ThreadRegistration;
 
 /// An internal builder for patching a function. Not exposed publicly.
 pub(crate) struct WhenCalled {
     func_ptr: FuncPtrInternal,
+    /// Whether the function is patched even when it looks too small for the patch.
+    allow_undersized: bool,
+    /// How the calls of the function are redirected instead of patching its code, if they are.
+    redirect: Option<RedirectMode>,
+    /// Whether the calls the fake makes of the function reach the original function.
+    guard_reentrancy: bool,
+    /// Where the calls failing at random go, with thread-local dispatch.
+    #[cfg(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec",
+        target_arch = "arm"
+    ))]
+    faults: Option<thread_local_registry::FaultRoute>,
+    /// Whether the return addresses of the calls are logged, with thread-local dispatch.
+    #[cfg(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec",
+        target_arch = "arm"
+    ))]
+    log_callers: bool,
+    /// Whether a backtrace of each call is logged, with thread-local dispatch.
+    #[cfg(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec",
+        target_arch = "arm"
+    ))]
+    log_backtraces: bool,
 }
 
 impl WhenCalled {
     pub(crate) fn new(func: FuncPtrInternal) -> Self {
-        Self { func_ptr: func }
+        Self {
+            func_ptr: func,
+            allow_undersized: false,
+            redirect: None,
+            guard_reentrancy: false,
+            #[cfg(any(
+                target_arch = "x86_64",
+                target_arch = "aarch64",
+                target_arch = "arm64ec",
+                target_arch = "arm"
+            ))]
+            faults: None,
+            #[cfg(any(
+                target_arch = "x86_64",
+                target_arch = "aarch64",
+                target_arch = "arm64ec",
+                target_arch = "arm"
+            ))]
+            log_callers: false,
+            #[cfg(any(
+                target_arch = "x86_64",
+                target_arch = "aarch64",
+                target_arch = "arm64ec",
+                target_arch = "arm"
+            ))]
+            log_backtraces: false,
+        }
+    }
+
+    /// Redirects the calls of the function instead of patching its code, or hooks it with an
+    /// external engine.
+    pub(crate) fn redirect_calls(&mut self, mode: RedirectMode) {
+        self.redirect = Some(mode);
+    }
+
+    /// Patches the code of the function, undoing `redirect_calls()`.
+    pub(crate) fn patch_code(&mut self) {
+        self.redirect = None;
+    }
+
+    /// Returns the mode passed to `redirect_calls()`, if it was called.
+    #[cfg(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec"
+    ))]
+    pub(crate) fn redirect_mode_mut(&mut self) -> Option<&mut RedirectMode> {
+        self.redirect.as_mut()
+    }
+
+    /// Returns whether `redirect_calls()` was called.
+    pub(crate) fn redirects_calls(&self) -> bool {
+        self.redirect.is_some()
+    }
+
+    /// Skips the check that the patch fits in the function.
+    pub(crate) fn allow_undersized_patch(&mut self) {
+        self.allow_undersized = true;
+    }
+
+    /// Sends the calls the fake makes of the function to the original function, with
+    /// thread-local dispatch.
+    pub(crate) fn guard_reentrancy(&mut self) {
+        self.guard_reentrancy = true;
+    }
+
+    /// Sends the calls drawn by `route` to its failure function, with thread-local dispatch.
+    #[cfg(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec",
+        target_arch = "arm"
+    ))]
+    pub(crate) fn inject_faults(&mut self, route: thread_local_registry::FaultRoute) {
+        self.faults = Some(route);
+    }
+
+    /// Logs the return address of each call of the fake, with thread-local dispatch.
+    #[cfg(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec",
+        target_arch = "arm"
+    ))]
+    pub(crate) fn log_callers(&mut self) {
+        self.log_callers = true;
+    }
+
+    /// Logs a backtrace of each call of the fake, which must announce its calls, with
+    /// thread-local dispatch.
+    #[cfg(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec",
+        target_arch = "arm"
+    ))]
+    pub(crate) fn log_backtraces(&mut self) {
+        self.log_backtraces = true;
+    }
+
+    /// Returns whether `log_backtraces()` was called.
+    pub(crate) fn logs_backtraces(&self) -> bool {
+        #[cfg(any(
+            target_arch = "x86_64",
+            target_arch = "aarch64",
+            target_arch = "arm64ec",
+            target_arch = "arm"
+        ))]
+        return self.log_backtraces;
+
+        #[cfg(not(any(
+            target_arch = "x86_64",
+            target_arch = "aarch64",
+            target_arch = "arm64ec",
+            target_arch = "arm"
+        )))]
+        false
+    }
+
+    /// Returns whether `guard_reentrancy()` was called.
+    pub(crate) fn guards_reentrancy(&self) -> bool {
+        self.guard_reentrancy
+    }
+
+    /// Skips the size check while the returned guard lives, if `allow_undersized_patch()` was
+    /// called.
+    fn size_check(&self) -> Option<SkipSizeCheck> {
+        self.allow_undersized.then(SkipSizeCheck::enter)
+    }
+
+    /// Returns the address of the function being faked.
+    pub(crate) fn func_addr(&self) -> usize {
+        self.func_ptr.as_ptr() as usize
+    }
+
+    /// Redirects the calls of the function to the replacement, as chosen with
+    /// `redirect_calls()`. All threads see the fake, as with `will_execute_guard()`.
+    pub(crate) fn will_execute_redirect(self, target: FuncPtrInternal) -> Redirect {
+        self.redirect_to(target.as_ptr() as usize, Vec::new())
+    }
+
+    /// Redirects the calls of the function to code returning a fixed boolean.
+    #[cfg(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec",
+        target_arch = "arm"
+    ))]
+    pub(crate) fn will_return_boolean_redirect(self, value: bool) -> Redirect {
+        self.redirect_to_code(&return_boolean_code(value))
+    }
+
+    /// Redirects the calls of the function to code returning a fixed float.
+    #[cfg(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec",
+        target_arch = "arm"
+    ))]
+    pub(crate) fn will_return_float_redirect(self, value: FloatReturn) -> Redirect {
+        #[cfg(target_arch = "x86_64")]
+        let code = super::patch_amd64::generate_return_float_code(value);
+
+        #[cfg(any(target_arch = "aarch64", target_arch = "arm64ec"))]
+        let code = super::patch_arm64::generate_return_float_code(value);
+
+        #[cfg(target_arch = "arm")]
+        let code = super::patch_arm::generate_return_float_code(value);
+
+        self.redirect_to_code(&code)
+    }
+
+    #[cfg(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec",
+        target_arch = "arm"
+    ))]
+    fn redirect_to_code(self, code: &[u8]) -> Redirect {
+        let jit_memory = allocate_jit_memory(&self.func_ptr, code.len());
+        unsafe {
+            inject_asm_code(code, jit_memory);
+        }
+        self.redirect_to(jit_memory as usize, vec![(jit_memory, code.len())])
+    }
+
+    /// Redirects the calls of the function to `target`. `jit` is freed with the redirection.
+    fn redirect_to(self, target: usize, jit: Vec<(*mut u8, usize)>) -> Redirect {
+        let func_addr = self.func_addr();
+        match self
+            .redirect
+            .expect("redirect_calls() must be called first")
+        {
+            #[cfg(target_os = "linux")]
+            RedirectMode::Got => super::got::redirect(func_addr, target, jit),
+            #[cfg(any(
+                target_arch = "x86_64",
+                target_arch = "aarch64",
+                target_arch = "arm64ec"
+            ))]
+            RedirectMode::CallSites(scope) => {
+                super::call_site::redirect(func_addr, target, &scope, jit)
+            }
+            #[cfg(target_os = "windows")]
+            RedirectMode::VtableSlot(slot) => {
+                super::winapi::redirect_vtable_slot(slot, func_addr, target, jit)
+            }
+            #[cfg(target_os = "macos")]
+            RedirectMode::ObjcMethod(method) => {
+                super::objc::redirect(method, func_addr, target, jit)
+            }
+            #[cfg(feature = "external-engine")]
+            RedirectMode::External => super::hook_engine::redirect(func_addr, target, jit),
+        }
     }
 
     /// Patches the target function with a direct JMP to the replacement (0.4.0-style global patching).
     /// All threads see the fake because the function's code bytes are overwritten.
     /// Used by `when_called_globally()`.
     pub(crate) fn will_execute_guard(self, target: FuncPtrInternal) -> PatchGuard {
+        let _skip = self.size_check();
         #[cfg(target_arch = "x86_64")]
         {
             PatchAmd64::replace_function_with_other_function(self.func_ptr, target)
         }
 
-        #[cfg(target_arch = "aarch64")]
+        #[cfg(any(target_arch = "aarch64", target_arch = "arm64ec"))]
         {
             PatchArm64::replace_function_with_other_function(self.func_ptr, target)
         }
@@ -43,84 +304,128 @@ impl WhenCalled {
         {
             PatchArm::replace_function_with_other_function(self.func_ptr, target)
         }
+
+        #[cfg(target_arch = "x86")]
+        {
+            PatchX86::replace_function_with_other_function(self.func_ptr, target)
+        }
+
+        #[cfg(target_arch = "riscv64")]
+        {
+            PatchRiscv64::replace_function_with_other_function(self.func_ptr, target)
+        }
     }
 
     /// Patches the target function using thread-local dispatch (x86_64 only).
     /// The original function is patched to a dispatcher that routes calls
     /// to per-thread replacement functions.
-    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64", target_arch = "arm"))]
+    #[cfg(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec",
+        target_arch = "arm"
+    ))]
     pub(crate) fn will_execute_thread_local(
         self,
         target: FuncPtrInternal,
     ) -> ThreadRegistration {
-        let replacement_addr = target.as_ptr() as usize;
-        thread_local_registry::register_replacement(&self.func_ptr, replacement_addr, None)
+        let _skip = self.size_check();
+        self.register_thread_local(target.as_ptr() as usize, None)
+    }
+
+    /// Registers `replacement_addr` for the current thread, with the reentrancy guard, the
+    /// faults and the call logs chosen for it.
+    #[cfg(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec",
+        target_arch = "arm"
+    ))]
+    fn register_thread_local(
+        self,
+        replacement_addr: usize,
+        extra_jit: Option<(*mut u8, usize)>,
+    ) -> ThreadRegistration {
+        let guard = self
+            .guard_reentrancy
+            .then(|| thread_local_registry::guard_reentrancy(replacement_addr));
+        let mut reg = thread_local_registry::register_replacement(
+            &self.func_ptr,
+            replacement_addr,
+            extra_jit,
+        );
+        if let Some(guard) = guard {
+            reg.keep_reentrancy_guard(guard);
+        }
+        if let Some(route) = self.faults {
+            reg.keep_fault_route(thread_local_registry::route_faults(replacement_addr, route));
+        }
+        if self.log_callers {
+            reg.keep_caller_log(thread_local_registry::log_callers(replacement_addr));
+        }
+        if self.log_backtraces {
+            reg.keep_backtrace_log(thread_local_registry::log_backtraces(replacement_addr));
+        }
+        reg
     }
 
     /// Patches the target function to return a boolean using thread-local dispatch.
-    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64", target_arch = "arm"))]
+    #[cfg(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec",
+        target_arch = "arm"
+    ))]
     pub(crate) fn will_return_boolean_thread_local(self, value: bool) -> ThreadRegistration {
-        // Generate a small JIT block that returns the boolean value
+        let _skip = self.size_check();
+        let code = return_boolean_code(value);
+        let jit_size = code.len();
+        let jit_memory = allocate_jit_memory(&self.func_ptr, jit_size);
+
+        unsafe {
+            inject_asm_code(&code, jit_memory);
+        }
+
+        self.register_thread_local(jit_memory as usize, Some((jit_memory, jit_size)))
+    }
+
+    /// Patches the target function to return a float using thread-local dispatch.
+    #[cfg(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec",
+        target_arch = "arm"
+    ))]
+    pub(crate) fn will_return_float_thread_local(self, value: FloatReturn) -> ThreadRegistration {
+        let _skip = self.size_check();
         #[cfg(target_arch = "x86_64")]
-        let (jit_size, asm_code_vec) = {
-            let code: [u8; 8] = [
-                0x48, 0xC7, 0xC0, // mov rax, imm32
-                value as u8, 0x00, 0x00, 0x00, // imm32
-                0xC3, // ret
-            ];
-            (8usize, code.to_vec())
-        };
-
-        #[cfg(target_arch = "aarch64")]
-        let (jit_size, asm_code_vec) = {
-            use super::arm64_codegenerator::*;
-            use super::utils::{bool_array_to_u32, u8_to_bits};
-
-            let mut value_bits = [false; 16];
-            value_bits[0] = value;
-            let movz = emit_movz(value_bits, true, u8_to_bits::<2>(0), u8_to_bits::<5>(0));
-            let ret = emit_ret_x30();
-
-            let mut code = Vec::with_capacity(8);
-            code.extend_from_slice(&bool_array_to_u32(movz).to_le_bytes());
-            code.extend_from_slice(&bool_array_to_u32(ret).to_le_bytes());
-            (8usize, code)
-        };
+        let code = super::patch_amd64::generate_return_float_code(value);
+
+        #[cfg(any(target_arch = "aarch64", target_arch = "arm64ec"))]
+        let code = super::patch_arm64::generate_return_float_code(value);
 
         #[cfg(target_arch = "arm")]
-        let (jit_size, asm_code_vec) = {
-            // ARM mode: MOV r0, #value; BX lr
-            let mov_r0: u32 = 0xE3A00000 | (value as u32); // MOV r0, #0 or #1
-            let bx_lr: u32 = 0xE12FFF1E; // BX lr
-            let mut code = Vec::with_capacity(8);
-            code.extend_from_slice(&mov_r0.to_le_bytes());
-            code.extend_from_slice(&bx_lr.to_le_bytes());
-            (8usize, code)
-        };
+        let code = super::patch_arm::generate_return_float_code(value);
 
-        let jit_memory = allocate_jit_memory(&self.func_ptr, jit_size);
+        let jit_memory = allocate_jit_memory(&self.func_ptr, code.len());
 
         unsafe {
-            inject_asm_code(&asm_code_vec, jit_memory);
+            inject_asm_code(&code, jit_memory);
         }
 
-        let replacement_addr = jit_memory as usize;
-        thread_local_registry::register_replacement(
-            &self.func_ptr,
-            replacement_addr,
-            Some((jit_memory, jit_size)),
-        )
+        self.register_thread_local(jit_memory as usize, Some((jit_memory, code.len())))
     }
 
     /// Patches the target function to return a fixed boolean via direct JMP (0.4.0-style).
     /// All threads see the fake. Used by `when_called_globally().will_return_boolean()`.
     pub(crate) fn will_return_boolean_guard(self, value: bool) -> PatchGuard {
+        let _skip = self.size_check();
         #[cfg(target_arch = "x86_64")]
         {
             PatchAmd64::replace_function_return_boolean(self.func_ptr, value)
         }
 
-        #[cfg(target_arch = "aarch64")]
+        #[cfg(any(target_arch = "aarch64", target_arch = "arm64ec"))]
         {
             PatchArm64::replace_function_return_boolean(self.func_ptr, value)
         }
@@ -129,5 +434,104 @@ impl WhenCalled {
         {
             PatchArm::replace_function_return_boolean(self.func_ptr, value)
         }
+
+        #[cfg(target_arch = "x86")]
+        {
+            PatchX86::replace_function_return_boolean(self.func_ptr, value)
+        }
+
+        #[cfg(target_arch = "riscv64")]
+        {
+            PatchRiscv64::replace_function_return_boolean(self.func_ptr, value)
+        }
     }
+
+    /// Patches the target function to return a fixed float via direct JMP (0.4.0-style).
+    /// All threads see the fake. Used by `will_return_f32()` and `will_return_f64()` of a
+    /// global injector.
+    pub(crate) fn will_return_float_guard(self, value: FloatReturn) -> PatchGuard {
+        let _skip = self.size_check();
+        #[cfg(target_arch = "x86_64")]
+        {
+            PatchAmd64::replace_function_return_float(self.func_ptr, value)
+        }
+
+        #[cfg(any(target_arch = "aarch64", target_arch = "arm64ec"))]
+        {
+            PatchArm64::replace_function_return_float(self.func_ptr, value)
+        }
+
+        #[cfg(target_arch = "arm")]
+        {
+            PatchArm::replace_function_return_float(self.func_ptr, value)
+        }
+
+        #[cfg(target_arch = "x86")]
+        {
+            PatchX86::replace_function_return_float(self.func_ptr, value)
+        }
+
+        #[cfg(target_arch = "riscv64")]
+        {
+            PatchRiscv64::replace_function_return_float(self.func_ptr, value)
+        }
+    }
+}
+
+/// Returns code returning `value` as a `bool`, starting with `endbr64` on x86_64 as it is
+/// entered by indirect jumps.
+#[cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec",
+    target_arch = "arm"
+))]
+fn return_boolean_code(value: bool) -> Vec<u8> {
+    #[cfg(target_arch = "x86_64")]
+    let code = {
+        let code: [u8; 12] = [
+            0xF3,
+            0x0F,
+            0x1E,
+            0xFA, // endbr64
+            0x48,
+            0xC7,
+            0xC0, // mov rax, imm32
+            value as u8,
+            0x00,
+            0x00,
+            0x00, // imm32
+            0xC3, // ret
+        ];
+        code.to_vec()
+    };
+
+    #[cfg(any(target_arch = "aarch64", target_arch = "arm64ec"))]
+    let code = {
+        use super::arm64_codegenerator::*;
+        use super::utils::{bool_array_to_u32, u8_to_bits};
+
+        let mut value_bits = [false; 16];
+        value_bits[0] = value;
+        let movz = emit_movz(value_bits, true, u8_to_bits::<2>(0), u8_to_bits::<5>(0));
+        let ret = emit_ret_x30();
+
+        let mut code = Vec::with_capacity(8);
+        code.extend_from_slice(&bool_array_to_u32(movz).to_le_bytes());
+        code.extend_from_slice(&bool_array_to_u32(ret).to_le_bytes());
+        code
+    };
+
+    #[cfg(target_arch = "arm")]
+    let code = {
+        // ARM mode: MOV r0, #value; BX lr
+        let mov_r0: u32 = 0xE3A00000 | (value as u32); // MOV r0, #0 or #1
+        let bx_lr: u32 = 0xE12FFF1E; // BX lr
+        let mut code = Vec::with_capacity(8);
+        code.extend_from_slice(&mov_r0.to_le_bytes());
+        code.extend_from_slice(&bx_lr.to_le_bytes());
+        code
+    };
+
+    code
 }
@@ -0,0 +1,221 @@
+//! Pools small blocks of JIT code, such as stubs and trampolines, in shared chunks of
+//! executable memory, rather than mapping pages of their own for each of them. The JIT code of
+//! functions near each other shares a chunk, which is unmapped once its last block is freed,
+//! typically when the injectors using it are dropped.
+
+use std::sync::Mutex;
+
+/// The size of a chunk, the allocation granularity of Windows.
+pub(crate) const CHUNK_SIZE: usize = 0x10000;
+
+/// The alignment of blocks, which Control Flow Guard call targets need.
+const BLOCK_ALIGN: usize = 16;
+
+/// The largest block carved out of a chunk. Larger ones are mapped on their own.
+const MAX_BLOCK_SIZE: usize = 0x400;
+
+/// How far from the function a chunk was allocated for another function may be for the chunk
+/// to be shared, the range of the shortest PC-relative instructions trampolines relocate.
+const NEIGHBORHOOD: usize = 0x10_0000;
+
+/// A chunk of JIT memory blocks are carved out of.
+struct Chunk {
+    base: usize,
+    /// The function the chunk was allocated near.
+    anchor: usize,
+    /// The end of the blocks carved out so far.
+    used: usize,
+    /// The freed blocks below `used`, as `(offset, len)`.
+    free: Vec<(usize, usize)>,
+    /// How many blocks are allocated.
+    live: usize,
+}
+
+impl Chunk {
+    /// Carves `len` bytes out of the chunk. Returns their offset, and whether they were used
+    /// before.
+    fn carve(&mut self, len: usize) -> Option<(usize, bool)> {
+        if let Some(index) = self.free.iter().position(|&(_, free)| free >= len) {
+            let (offset, free) = self.free[index];
+            if free == len {
+                self.free.swap_remove(index);
+            } else {
+                self.free[index] = (offset + len, free - len);
+            }
+            return Some((offset, true));
+        }
+
+        if self.used + len > CHUNK_SIZE {
+            return None;
+        }
+        let offset = self.used;
+        self.used += len;
+        Some((offset, false))
+    }
+}
+
+struct Arena {
+    chunks: Vec<Chunk>,
+    /// The allocated blocks, as `(addr, len)`.
+    blocks: Vec<(usize, usize)>,
+}
+
+static ARENA: Mutex<Arena> = Mutex::new(Arena {
+    chunks: Vec::new(),
+    blocks: Vec::new(),
+});
+
+/// Returns whether `size` bytes of JIT code are carved out of a chunk. Not when pages holding
+/// JIT code are never writable, as writing a block would then make its neighbors unexecutable.
+pub(crate) fn pooled(size: usize) -> bool {
+    size <= MAX_BLOCK_SIZE && !crate::injector_core::common::jit_code_read_only()
+}
+
+/// Returns `size` bytes of zeroed JIT memory within `max_range` of `func_addr`, carved out of
+/// a chunk allocated near a function in its neighborhood, or out of a new chunk mapped by
+/// `map_chunk`.
+pub(crate) fn allocate(
+    func_addr: usize,
+    size: usize,
+    max_range: u64,
+    map_chunk: impl FnOnce() -> Option<*mut u8>,
+) -> Option<*mut u8> {
+    let len = size.max(1).next_multiple_of(BLOCK_ALIGN);
+    let reaches = |addr: usize| {
+        (addr.abs_diff(func_addr) as u64) <= max_range
+            && ((addr + len).abs_diff(func_addr) as u64) <= max_range
+    };
+
+    let mut arena = ARENA.lock().unwrap_or_else(|e| e.into_inner());
+    let mut carved = None;
+    for chunk in arena
+        .chunks
+        .iter_mut()
+        .filter(|chunk| chunk.anchor.abs_diff(func_addr) <= NEIGHBORHOOD)
+    {
+        if let Some((offset, reused)) = chunk.carve(len) {
+            if reaches(chunk.base + offset) {
+                chunk.live += 1;
+                carved = Some((chunk.base, offset, reused));
+                break;
+            }
+            chunk.free.push((offset, len));
+        }
+    }
+
+    let (base, offset, reused) = match carved {
+        Some(carved) => carved,
+        None => {
+            // Mapping may free out-of-range memory, which locks the arena.
+            drop(arena);
+            let base = map_chunk()? as usize;
+            arena = ARENA.lock().unwrap_or_else(|e| e.into_inner());
+            arena.chunks.push(Chunk {
+                base,
+                anchor: func_addr,
+                used: len,
+                free: Vec::new(),
+                live: 1,
+            });
+            (base, 0, false)
+        }
+    };
+    let addr = base + offset;
+    arena.blocks.push((addr, len));
+    drop(arena);
+
+    #[cfg(target_os = "windows")]
+    unsafe {
+        crate::injector_core::winapi::register_call_target(base, CHUNK_SIZE, offset);
+    }
+
+    // Freshly mapped memory is zeroed, and data such as the counters of stubs starts at zero.
+    if reused {
+        unsafe { crate::injector_core::common::inject_asm_code(&vec![0; len], addr as *mut u8) };
+    }
+    Some(addr as *mut u8)
+}
+
+/// Frees the block at `addr`. Returns `None` when it was not carved out of a chunk, and the
+/// chunk to unmap when it was its last block.
+pub(crate) fn release(addr: *mut u8) -> Option<Option<*mut u8>> {
+    let addr = addr as usize;
+    let mut arena = ARENA.lock().unwrap_or_else(|e| e.into_inner());
+    let Arena { chunks, blocks } = &mut *arena;
+
+    let index = blocks.iter().position(|&(block, _)| block == addr)?;
+    let (_, len) = blocks.swap_remove(index);
+    let index = chunks
+        .iter()
+        .position(|chunk| (chunk.base..chunk.base + CHUNK_SIZE).contains(&addr))?;
+
+    let chunk = &mut chunks[index];
+    chunk.live -= 1;
+    if chunk.live == 0 {
+        let chunk = chunks.swap_remove(index);
+        return Some(Some(chunk.base as *mut u8));
+    }
+    chunk.free.push((addr - chunk.base, len));
+    Some(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk() -> Chunk {
+        Chunk {
+            base: 0x10000,
+            anchor: 0,
+            used: 0,
+            free: Vec::new(),
+            live: 0,
+        }
+    }
+
+    #[test]
+    fn test_carve_should_reuse_freed_blocks_first() {
+        let mut chunk = chunk();
+        assert_eq!(chunk.carve(32), Some((0, false)));
+        assert_eq!(chunk.carve(64), Some((32, false)));
+
+        chunk.free.push((0, 32));
+        assert_eq!(chunk.carve(16), Some((0, true)));
+        assert_eq!(chunk.carve(16), Some((16, true)));
+        assert_eq!(chunk.carve(16), Some((96, false)));
+    }
+
+    #[test]
+    fn test_carve_should_fail_when_chunk_is_full() {
+        let mut chunk = chunk();
+        assert_eq!(chunk.carve(CHUNK_SIZE - 16), Some((0, false)));
+        assert_eq!(chunk.carve(32), None);
+        assert_eq!(chunk.carve(16), Some((CHUNK_SIZE - 16, false)));
+    }
+
+    #[test]
+    fn test_blocks_near_a_function_should_share_a_chunk() {
+        use crate::injector_core::common::FuncPtrInternal;
+        use crate::injector_core::common::{free_jit_memory, try_allocate_jit_memory};
+
+        if !pooled(64) {
+            return;
+        }
+
+        let func = unsafe {
+            FuncPtrInternal::new(
+                std::ptr::NonNull::new(pooled as fn(usize) -> bool as *mut ()).unwrap(),
+            )
+        };
+        let first = try_allocate_jit_memory(&func, 64).unwrap();
+        let second = try_allocate_jit_memory(&func, 64).unwrap();
+        assert_eq!(second as usize % BLOCK_ALIGN, 0);
+        assert!((second as usize).abs_diff(first as usize) < CHUNK_SIZE);
+
+        unsafe {
+            free_jit_memory(second, 64);
+            free_jit_memory(first, 64);
+        }
+        assert_eq!(release(first), None);
+    }
+}
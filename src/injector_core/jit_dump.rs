@@ -0,0 +1,198 @@
+#![cfg(feature = "jit-dump")]
+
+//! Human-readable dumps of patch bytes and generated JIT code.
+//!
+//! The disassembler here is intentionally minimal: it knows the handful of
+//! instructions injectorpp itself emits (branches, moves of immediates, pushes and
+//! pops) and prints everything else as raw data. That is enough to check what was
+//! written where when a patch misbehaves on a new target.
+
+use std::fmt::Write;
+
+use crate::injector_core::diagnostics::hex_bytes;
+
+/// Formats `code` located at `base` as one line per decoded instruction.
+pub(crate) fn disassemble(code: &[u8], base: usize) -> String {
+    let mut out = String::new();
+    let mut offset = 0;
+
+    while offset < code.len() {
+        let addr = base + offset;
+        let (len, text) = decode(&code[offset..], addr);
+        let len = len.clamp(1, code.len() - offset);
+        let _ = writeln!(
+            out,
+            "    {:#x}: {:<32} {}",
+            addr,
+            hex_bytes(&code[offset..offset + len]),
+            text
+        );
+        offset += len;
+    }
+
+    out
+}
+
+/// Formats a labelled block of code: a header line followed by its disassembly.
+pub(crate) fn dump_block(out: &mut String, label: &str, addr: usize, size: usize) {
+    if addr == 0 || size == 0 {
+        return;
+    }
+
+    let code = unsafe { crate::injector_core::common::read_bytes(addr as *const u8, size) };
+    let _ = writeln!(out, "  {} at {:#x} ({} bytes):", label, addr, size);
+    out.push_str(&disassemble(&code, addr));
+}
+
+#[cfg(target_arch = "x86_64")]
+const X86_64_REGS: [&str; 16] = [
+    "rax", "rcx", "rdx", "rbx", "rsp", "rbp", "rsi", "rdi", "r8", "r9", "r10", "r11", "r12", "r13",
+    "r14", "r15",
+];
+
+/// Decodes a single instruction, returning its length and text.
+#[cfg(target_arch = "x86_64")]
+fn decode(code: &[u8], addr: usize) -> (usize, String) {
+    let rel32 = |at: usize| -> Option<i32> {
+        code.get(at..at + 4)
+            .map(|b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    };
+
+    let (rex_b, rex_w, op_pos) = match code[0] {
+        rex @ 0x40..=0x4F => ((rex & 1) as usize * 8, rex & 8 != 0, 1),
+        _ => (0, false, 0),
+    };
+
+    let Some(&op) = code.get(op_pos) else {
+        return (code.len(), "(truncated)".to_string());
+    };
+
+    match op {
+        0xE9 => {
+            if let Some(disp) = rel32(op_pos + 1) {
+                let target = (addr + op_pos + 5).wrapping_add(disp as isize as usize);
+                return (op_pos + 5, format!("jmp {:#x}", target));
+            }
+        }
+        0xE8 => {
+            if let Some(disp) = rel32(op_pos + 1) {
+                let target = (addr + op_pos + 5).wrapping_add(disp as isize as usize);
+                return (op_pos + 5, format!("call {:#x}", target));
+            }
+        }
+        0xC3 => return (op_pos + 1, "ret".to_string()),
+        0x90 => return (op_pos + 1, "nop".to_string()),
+        0xCC => return (op_pos + 1, "int3".to_string()),
+        0x50..=0x57 => {
+            let reg = X86_64_REGS[(op - 0x50) as usize + rex_b];
+            return (op_pos + 1, format!("push {}", reg));
+        }
+        0x58..=0x5F => {
+            let reg = X86_64_REGS[(op - 0x58) as usize + rex_b];
+            return (op_pos + 1, format!("pop {}", reg));
+        }
+        0xB8..=0xBF if rex_w && code.len() >= op_pos + 9 => {
+            let reg = X86_64_REGS[(op - 0xB8) as usize + rex_b];
+            let mut imm = [0u8; 8];
+            imm.copy_from_slice(&code[op_pos + 1..op_pos + 9]);
+            return (
+                op_pos + 9,
+                format!("mov {}, {:#x}", reg, u64::from_le_bytes(imm)),
+            );
+        }
+        0xC7 if code.get(op_pos + 1).is_some_and(|m| m & 0xC0 == 0xC0) => {
+            if let Some(imm) = rel32(op_pos + 2) {
+                let reg = X86_64_REGS[(code[op_pos + 1] & 7) as usize + rex_b];
+                return (op_pos + 6, format!("mov {}, {:#x}", reg, imm));
+            }
+        }
+        0xFF => {
+            if let Some(&modrm) = code.get(op_pos + 1) {
+                let reg_field = (modrm >> 3) & 7;
+                if modrm & 0xC0 == 0xC0 && (reg_field == 2 || reg_field == 4) {
+                    let reg = X86_64_REGS[(modrm & 7) as usize + rex_b];
+                    let mnemonic = if reg_field == 2 { "call" } else { "jmp" };
+                    return (op_pos + 2, format!("{} {}", mnemonic, reg));
+                }
+                if modrm == 0x25 {
+                    if let Some(disp) = rel32(op_pos + 2) {
+                        let slot = (addr + op_pos + 6).wrapping_add(disp as isize as usize);
+                        return (op_pos + 6, format!("jmp qword ptr [{:#x}]", slot));
+                    }
+                }
+            }
+        }
+        _ => {}
+    }
+
+    let len = crate::injector_core::x86_64_insn::insn_len(code);
+    if len == 0 {
+        (code.len().min(8), "(data)".to_string())
+    } else {
+        (len, "...".to_string())
+    }
+}
+
+/// Decodes a single instruction, returning its length and text.
+#[cfg(any(target_arch = "aarch64", target_arch = "arm64ec"))]
+fn decode(code: &[u8], addr: usize) -> (usize, String) {
+    if code.len() < 4 {
+        return (code.len(), "(truncated)".to_string());
+    }
+
+    let word = u32::from_le_bytes([code[0], code[1], code[2], code[3]]);
+    let sign_extend =
+        |value: u32, bits: u32| -> i64 { ((value << (32 - bits)) as i32 >> (32 - bits)) as i64 };
+
+    let text = if word & 0xFC00_0000 == 0x1400_0000 {
+        let target = addr as i64 + sign_extend(word & 0x03FF_FFFF, 26) * 4;
+        format!("b {:#x}", target)
+    } else if word & 0xFC00_0000 == 0x9400_0000 {
+        let target = addr as i64 + sign_extend(word & 0x03FF_FFFF, 26) * 4;
+        format!("bl {:#x}", target)
+    } else if word & 0xFFFF_FC1F == 0xD61F_0000 {
+        format!("br x{}", (word >> 5) & 0x1F)
+    } else if word & 0xFFFF_FC1F == 0xD63F_0000 {
+        format!("blr x{}", (word >> 5) & 0x1F)
+    } else if word == 0xD65F_03C0 {
+        "ret".to_string()
+    } else if word == 0xD503_201F {
+        "nop".to_string()
+    } else if word & 0xFF00_0000 == 0x5800_0000 {
+        let target = addr as i64 + sign_extend((word >> 5) & 0x7FFFF, 19) * 4;
+        format!("ldr x{}, {:#x}", word & 0x1F, target)
+    } else if word & 0xFF80_0000 == 0xD280_0000 || word & 0xFF80_0000 == 0xF280_0000 {
+        let mnemonic = if word & 0x2000_0000 != 0 && word & 0x4000_0000 != 0 {
+            "movk"
+        } else {
+            "movz"
+        };
+        let shift = ((word >> 21) & 3) * 16;
+        format!(
+            "{} x{}, #{:#x}, lsl #{}",
+            mnemonic,
+            word & 0x1F,
+            (word >> 5) & 0xFFFF,
+            shift
+        )
+    } else {
+        format!(".word {:#010x}", word)
+    };
+
+    (4, text)
+}
+
+/// Decodes a single instruction, returning its length and text.
+#[cfg(not(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec"
+)))]
+fn decode(code: &[u8], _addr: usize) -> (usize, String) {
+    if code.len() < 4 {
+        return (code.len(), "(truncated)".to_string());
+    }
+
+    let word = u32::from_le_bytes([code[0], code[1], code[2], code[3]]);
+    (4, format!(".word {:#010x}", word))
+}
@@ -1,6 +1,7 @@
-#![cfg(target_os = "linux")]
+#![cfg(any(target_os = "linux", target_os = "freebsd", target_os = "netbsd"))]
 
 extern "C" {
-    /// Flushes the CPU instruction cache (provided by glibc on Linux).
+    /// Flushes the CPU instruction cache (provided by glibc on Linux, and by compiler-rt or
+    /// libgcc on the BSDs).
     pub(crate) fn __clear_cache(start: *mut u8, end: *mut u8);
 }
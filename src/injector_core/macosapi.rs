@@ -1,6 +1,144 @@
 #![cfg(target_os = "macos")]
 
+use libc::{c_char, c_int, c_uint, c_void, intptr_t, pid_t};
+
 extern "C" {
     pub(crate) fn sys_dcache_flush(start: *mut u8, len: usize);
     pub(crate) fn sys_icache_invalidate(start: *mut u8, len: usize);
+    fn csops(pid: pid_t, ops: c_uint, useraddr: *mut c_void, usersize: usize) -> c_int;
+    pub(crate) fn _dyld_image_count() -> u32;
+    pub(crate) fn _dyld_get_image_name(image_index: u32) -> *const c_char;
+    pub(crate) fn _dyld_get_image_header(image_index: u32) -> *const c_void;
+    pub(crate) fn _dyld_get_image_vmaddr_slide(image_index: u32) -> intptr_t;
+}
+
+const CS_OPS_STATUS: c_uint = 0;
+const CS_OPS_ENTITLEMENTS_BLOB: c_uint = 7;
+
+/// The process runs with the hardened runtime.
+const CS_RUNTIME: u32 = 0x0001_0000;
+
+/// Lets a hardened process map `MAP_JIT` memory.
+const ALLOW_JIT: &str = "com.apple.security.cs.allow-jit";
+
+/// Lets a hardened process modify its signed code.
+const DISABLE_EXECUTABLE_PAGE_PROTECTION: &str =
+    "com.apple.security.cs.disable-executable-page-protection";
+
+/// Returns whether the process runs with the hardened runtime, which enforces its entitlements.
+pub(crate) fn hardened_runtime() -> bool {
+    let mut flags: u32 = 0;
+    let result = unsafe {
+        csops(
+            libc::getpid(),
+            CS_OPS_STATUS,
+            &mut flags as *mut u32 as *mut c_void,
+            std::mem::size_of::<u32>(),
+        )
+    };
+    result == 0 && flags & CS_RUNTIME != 0
+}
+
+/// Returns whether the process is signed with the boolean entitlement `name` set.
+fn has_entitlement(name: &str) -> bool {
+    // A blob header, its magic and big-endian length, followed by the entitlements plist.
+    let mut header = [0u8; 8];
+    unsafe {
+        csops(
+            libc::getpid(),
+            CS_OPS_ENTITLEMENTS_BLOB,
+            header.as_mut_ptr() as *mut c_void,
+            header.len(),
+        );
+    }
+    let len = u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as usize;
+    if len <= header.len() {
+        return false;
+    }
+
+    let mut blob = vec![0u8; len];
+    let result = unsafe {
+        csops(
+            libc::getpid(),
+            CS_OPS_ENTITLEMENTS_BLOB,
+            blob.as_mut_ptr() as *mut c_void,
+            blob.len(),
+        )
+    };
+    result == 0 && entitlement_enabled(&blob[header.len()..], name)
+}
+
+/// Returns whether the entitlements plist sets the boolean `name` to true.
+fn entitlement_enabled(plist: &[u8], name: &str) -> bool {
+    let plist = String::from_utf8_lossy(plist);
+    let key = format!("<key>{}</key>", name);
+    plist
+        .find(&key)
+        .is_some_and(|at| plist[at + key.len()..].trim_start().starts_with("<true/>"))
+}
+
+/// Returns an actionable message explaining why `MAP_JIT` memory could not be mapped, failing
+/// with `error`.
+pub(crate) fn jit_denied_message(error: std::io::Error) -> String {
+    let cause = if hardened_runtime() && !has_entitlement(ALLOW_JIT) {
+        format!(
+            "the test binary runs with the hardened runtime without the `{}` entitlement. \
+             Sign it with that entitlement, or without the hardened runtime, e.g. \
+             `codesign -s - -f <binary>`",
+            ALLOW_JIT
+        )
+    } else {
+        "the system refused executable memory to the process".to_string()
+    };
+    format!("Failed to map JIT memory with MAP_JIT ({}): {}", error, cause)
+}
+
+/// Returns an actionable message if the code signature of the process forbids modifying its
+/// code, which would otherwise kill it on the first call of a patched function.
+pub(crate) fn code_patching_denied_message() -> Option<String> {
+    static DENIED: std::sync::OnceLock<Option<String>> = std::sync::OnceLock::new();
+
+    DENIED
+        .get_or_init(|| {
+            (hardened_runtime() && !has_entitlement(DISABLE_EXECUTABLE_PAGE_PROTECTION)).then(
+                || {
+                    format!(
+                        "Cannot patch code signed with the hardened runtime: the test binary \
+                         lacks the `{}` entitlement, so executing modified code pages would \
+                         kill it. Sign it with that entitlement, or without the hardened \
+                         runtime, e.g. `codesign -s - -f <binary>`",
+                        DISABLE_EXECUTABLE_PAGE_PROTECTION
+                    )
+                },
+            )
+        })
+        .clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entitlement_enabled_reads_boolean_keys() {
+        let plist = br#"<?xml version="1.0" encoding="UTF-8"?>
+<plist version="1.0">
+<dict>
+    <key>com.apple.security.cs.allow-jit</key>
+    <true/>
+    <key>com.apple.security.cs.disable-executable-page-protection</key>
+    <false/>
+</dict>
+</plist>"#;
+
+        assert!(entitlement_enabled(plist, ALLOW_JIT));
+        assert!(!entitlement_enabled(
+            plist,
+            DISABLE_EXECUTABLE_PAGE_PROTECTION
+        ));
+        assert!(!entitlement_enabled(
+            plist,
+            "com.apple.security.get-task-allow"
+        ));
+    }
 }
@@ -0,0 +1,137 @@
+#![cfg(target_os = "macos")]
+
+//! Replaces the implementations of Objective-C methods, so the methods of AppKit, Foundation
+//! or other frameworks, which are reached through `objc_msgSend` rather than called directly,
+//! can be faked.
+//!
+//! A method is redirected by swapping its implementation, its `IMP`, for the fake with
+//! `method_setImplementation()`, which also flushes the method caches of the runtime. A method
+//! a class inherits is first added to the class with the implementation of its superclass, as
+//! `class_replaceMethod()` does, so the fake does not reach the superclass and its other
+//! subclasses.
+
+use std::ffi::{c_char, c_void, CString};
+
+use crate::injector_core::redirect::{Redirect, Site};
+
+#[link(name = "objc")]
+extern "C" {
+    fn objc_getClass(name: *const c_char) -> *mut c_void;
+    fn object_getClass(object: *mut c_void) -> *mut c_void;
+    fn class_getSuperclass(class: *mut c_void) -> *mut c_void;
+    fn class_getInstanceMethod(class: *mut c_void, selector: *const c_void) -> *mut c_void;
+    fn class_addMethod(
+        class: *mut c_void,
+        selector: *const c_void,
+        imp: *const c_void,
+        types: *const c_char,
+    ) -> u8;
+    fn sel_registerName(name: *const c_char) -> *const c_void;
+    fn method_getImplementation(method: *mut c_void) -> *const c_void;
+    fn method_setImplementation(method: *mut c_void, imp: *const c_void) -> *const c_void;
+    fn method_getTypeEncoding(method: *mut c_void) -> *const c_char;
+}
+
+/// Returns whether `name` names a class method, its class and its selector, from a method
+/// named as in Objective-C, such as `-[NSFileManager fileExistsAtPath:]` or
+/// `+[NSDate date]`. The category of `-[NSString(Paths) lastPathComponent]` is ignored.
+fn parse_name(name: &str) -> Option<(bool, &str, &str)> {
+    let class_method = match name.as_bytes().first()? {
+        b'+' => true,
+        b'-' => false,
+        _ => return None,
+    };
+    let (class, selector) = name[1..]
+        .strip_prefix('[')?
+        .strip_suffix(']')?
+        .split_once(' ')?;
+    let class = class.split_once('(').map_or(class, |(class, _)| class);
+    (!class.is_empty() && !selector.is_empty()).then_some((class_method, class, selector))
+}
+
+/// Returns the method, `Method`, named `name` as in Objective-C, of its class rather than of
+/// a superclass: an inherited method is added to the class first. Returns `None` when `name`
+/// is not a method name, or the class is not loaded or has no such method.
+pub(crate) fn own_method(name: &str) -> Option<usize> {
+    let (class_method, class, selector) = parse_name(name)?;
+    let class = CString::new(class).ok()?;
+    let selector = CString::new(selector).ok()?;
+
+    unsafe {
+        let mut class = objc_getClass(class.as_ptr());
+        if class.is_null() {
+            return None;
+        }
+        // Class methods are the instance methods of the metaclass.
+        if class_method {
+            class = object_getClass(class);
+        }
+        let selector = sel_registerName(selector.as_ptr());
+        let method = class_getInstanceMethod(class, selector);
+        if method.is_null() {
+            return None;
+        }
+
+        let superclass = class_getSuperclass(class);
+        if superclass.is_null() || class_getInstanceMethod(superclass, selector) != method {
+            return Some(method as usize);
+        }
+        // The added method keeps the implementation of the superclass once restored.
+        class_addMethod(
+            class,
+            selector,
+            method_getImplementation(method),
+            method_getTypeEncoding(method),
+        );
+        let method = class_getInstanceMethod(class, selector);
+        (!method.is_null()).then_some(method as usize)
+    }
+}
+
+/// Returns the implementation of the method `method`.
+pub(crate) fn implementation(method: usize) -> usize {
+    unsafe { method_getImplementation(method as *mut c_void) as usize }
+}
+
+/// Makes `imp` the implementation of the method `method`.
+pub(crate) unsafe fn set_implementation(method: usize, imp: usize) {
+    method_setImplementation(method as *mut c_void, imp as *const c_void);
+}
+
+/// Makes `target` the implementation of the method `method`, implemented by the function at
+/// `func_addr`, until the returned redirection is dropped.
+pub(crate) fn redirect(
+    method: usize,
+    func_addr: usize,
+    target: usize,
+    jit: Vec<(*mut u8, usize)>,
+) -> Redirect {
+    // A method redirected by an earlier redirection, e.g. a leaked one, is redirected on top
+    // of it.
+    let site = Site::ObjcMethod {
+        method,
+        original: implementation(method),
+        redirected: target,
+    };
+    Redirect::install(func_addr, target, vec![site], jit, "Objective-C method")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_name_should_split_class_and_selector() {
+        assert_eq!(
+            parse_name("-[NSFileManager fileExistsAtPath:]"),
+            Some((false, "NSFileManager", "fileExistsAtPath:"))
+        );
+        assert_eq!(parse_name("+[NSDate date]"), Some((true, "NSDate", "date")));
+        assert_eq!(
+            parse_name("-[NSString(Paths) lastPathComponent]"),
+            Some((false, "NSString", "lastPathComponent"))
+        );
+        assert_eq!(parse_name("NSDate date"), None);
+        assert_eq!(parse_name("-[NSDate]"), None);
+    }
+}
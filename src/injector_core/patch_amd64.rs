@@ -2,62 +2,176 @@
 #![allow(dead_code)]
 
 use crate::injector_core::common::*;
+use crate::injector_core::function_size::check_patch_fits;
 use crate::injector_core::patch_trait::*;
+use crate::injector_core::thunk::resolve_thunks;
+use crate::injector_core::x86_64_insn::ENDBR64;
 
 /// Patch implementation for AMD64 (x86_64) architecture.
 pub(crate) struct PatchAmd64;
 
 /// Opcode constants for AMD64 jump and move instructions.
 const JMP_REL_OPCODE: u8 = 0xE9;
-const MOV_RAX_OPCODE: [u8; 2] = [0x48, 0xB8];
-const JMP_RAX_OPCODE: [u8; 2] = [0xFF, 0xE0];
+const JMP_RIP_OPCODE: [u8; 6] = [0xFF, 0x25, 0x00, 0x00, 0x00, 0x00];
+
+/// Size of the code of the JIT stub, followed by its entry and exit counters. The counters
+/// live on their own cache line so updating them does not look like self-modifying code.
+const ENTERED_OFFSET: usize = 64;
+
+/// Offset of the jump to the original function written when the stub is retired.
+pub(crate) const RETIRED_JUMP_OFFSET: usize = 48;
 
 impl PatchTrait for PatchAmd64 {
     fn replace_function_with_other_function(
         src: FuncPtrInternal,
         target: FuncPtrInternal,
     ) -> PatchGuard {
-        const JIT_SIZE: usize = 12;
-        let jit_memory = allocate_jit_memory(&src, JIT_SIZE);
-
-        let target_addr = target.as_ptr() as usize;
-        let jit_addr = jit_memory as usize;
+        // The stub is reached through `jmp rel32` when it lies within ±2GB of the function,
+        // and through the longer `jmp [rip+0]` otherwise.
+        let entered = entered_offset();
+        let jit_size = entered + 16;
+        let jit_memory = allocate_jit_memory_preferably_near(&src, jit_size);
 
-        let jit_code = generate_branch_to_target_function(jit_addr, target_addr);
+        let jit_code = generate_counting_stub(
+            jit_memory as usize,
+            entered,
+            target.as_ptr() as usize,
+            exit_tail(&src),
+        );
 
         unsafe {
             inject_asm_code(&jit_code, jit_memory);
+            make_jit_data_writable(jit_memory.add(entered), 16);
         }
 
-        patch_and_guard(src, jit_memory, JIT_SIZE)
+        patch_and_guard(src, jit_memory, jit_size).with_stub_counters(jit_memory as usize + entered)
     }
 
     fn replace_function_return_boolean(src: FuncPtrInternal, value: bool) -> PatchGuard {
-        const JIT_SIZE: usize = 8;
-        let jit_memory = allocate_jit_memory(&src, JIT_SIZE);
+        Self::replace_function_with_other_function(src, unsafe {
+            FuncPtrInternal::new(
+                std::ptr::NonNull::new(if value { return_true } else { return_false } as *mut ())
+                    .expect("Failed to create FuncPtrInternal"), // Should never fail
+            )
+        })
+    }
 
-        generate_will_return_boolean_jit_code(jit_memory, value);
+    fn replace_function_return_float(src: FuncPtrInternal, value: FloatReturn) -> PatchGuard {
+        let code = generate_return_float_code(value);
+        let target = shared_jit_code(&src, &code);
+        Self::replace_function_with_other_function(src, target)
+    }
+}
 
-        patch_and_guard(src, jit_memory, JIT_SIZE)
+/// Generates code returning `value` in xmm0, loaded from the literal following the code.
+///
+/// ```text
+///  0: endbr64
+///  4: movsd xmm0, [rip+4]       ; movss for f32
+/// 12: ret
+/// 16: .quad value
+/// ```
+pub(crate) fn generate_return_float_code(value: FloatReturn) -> Vec<u8> {
+    let mut code = Vec::with_capacity(24);
+
+    code.extend_from_slice(&ENDBR64);
+    match value {
+        FloatReturn::F32(_) => code.extend_from_slice(&[0xF3, 0x0F, 0x10, 0x05]),
+        FloatReturn::F64(_) => code.extend_from_slice(&[0xF2, 0x0F, 0x10, 0x05]),
     }
+    code.extend_from_slice(&4i32.to_le_bytes());
+    code.push(0xC3);
+
+    // int3 padding up to the literal.
+    code.resize(16, 0xCC);
+    code.extend_from_slice(&value.to_le_bytes());
+    code
 }
 
-/// Injects a return-boolean JIT sequence at `jit_ptr`.
-fn generate_will_return_boolean_jit_code(jit_ptr: *mut u8, value: bool) {
-    let mut asm_code: [u8; 8] = [
-        0x48, 0xC7, 0xC0, // mov rax, imm32
-        0x00, 0x00, 0x00, 0x00, // imm32
-        0xC3, // ret
-    ];
+fn return_true() -> bool {
+    true
+}
 
-    asm_code[3] = value as u8;
+fn return_false() -> bool {
+    false
+}
 
-    unsafe {
-        inject_asm_code(&asm_code, jit_ptr);
+/// Returns the offset of the entry counter of the JIT stub, followed by its exit counter.
+/// When JIT code is read-only the counters get a page of their own, which stays writable.
+fn entered_offset() -> usize {
+    if jit_code_read_only() {
+        jit_page_size()
+    } else {
+        ENTERED_OFFSET
     }
 }
 
-/// Generates a jump from `ori_func` to `target_func`.
+/// Generates a JIT stub that counts the threads passing through it and branches to `target`.
+///
+/// The stub increments its entry counter, then jumps to the shared exit tail with the target
+/// in r11 and the address of its exit counter in r10. The tail increments the exit counter
+/// and branches to the target, so once both counters are equal no thread executes the stub
+/// any more and it can be freed. r10 and r11 are scratch registers that carry no arguments.
+///
+/// ```text
+///  0: endbr64
+///  4: lock inc qword [rip+52]   ; entered, at +64 unless `entered` says otherwise
+/// 12: mov r11, target
+/// 22: mov r10, jit + 72         ; exited
+/// 32: jmp [rip+0]
+/// 38: .quad exit_tail
+/// 46: int3 padding              ; jmp [rip+0]; .quad func at +48 once retired
+/// ```
+///
+/// Only the code is returned. The counters start at zero, as freshly mapped memory.
+fn generate_counting_stub(
+    jit_addr: usize,
+    entered: usize,
+    target_addr: usize,
+    exit_tail: usize,
+) -> Vec<u8> {
+    let mut code = Vec::with_capacity(ENTERED_OFFSET);
+
+    code.extend_from_slice(&ENDBR64);
+    code.extend_from_slice(&[0xF0, 0x48, 0xFF, 0x05]);
+    code.extend_from_slice(&((entered - 12) as i32).to_le_bytes());
+
+    code.extend_from_slice(&[0x49, 0xBB]);
+    code.extend_from_slice(&(target_addr as u64).to_le_bytes());
+
+    code.extend_from_slice(&[0x49, 0xBA]);
+    code.extend_from_slice(&((jit_addr + entered + 8) as u64).to_le_bytes());
+
+    code.extend_from_slice(&[0xFF, 0x25, 0x00, 0x00, 0x00, 0x00]);
+    code.extend_from_slice(&(exit_tail as u64).to_le_bytes());
+
+    // int3 padding up to the end of the code.
+    code.resize(ENTERED_OFFSET, 0xCC);
+    code
+}
+
+/// Returns the exit tail shared by all counting stubs, allocating it on first use. It is never
+/// freed, so a thread may still be executing it after its stub was released.
+fn exit_tail(src: &FuncPtrInternal) -> usize {
+    static EXIT_TAIL: std::sync::OnceLock<usize> = std::sync::OnceLock::new();
+
+    *EXIT_TAIL.get_or_init(|| {
+        let code: [u8; 11] = [
+            0xF3, 0x0F, 0x1E, 0xFA, // endbr64
+            0xF0, 0x49, 0xFF, 0x02, // lock inc qword [r10]
+            0x41, 0xFF, 0xE3, // jmp r11
+        ];
+
+        let tail = allocate_jit_memory_preferably_near(src, code.len());
+        unsafe {
+            inject_asm_code(&code, tail);
+        }
+        tail as usize
+    })
+}
+
+/// Generates a jump from `ori_func` to `target_func`: a 5-byte `jmp rel32` within ±2GB, and a
+/// 14-byte `jmp [rip+0]` followed by the target address otherwise.
 fn generate_branch_to_target_function(ori_func: usize, target_func: usize) -> Vec<u8> {
     let offset = target_func as isize - (ori_func as isize + 5);
 
@@ -67,23 +181,28 @@ fn generate_branch_to_target_function(ori_func: usize, target_func: usize) -> Ve
         branch_code.extend_from_slice(&(offset as i32).to_le_bytes());
         branch_code
     } else {
-        let mut branch_code = Vec::with_capacity(13);
-        branch_code.extend_from_slice(&MOV_RAX_OPCODE);
+        let mut branch_code = Vec::with_capacity(14);
+        branch_code.extend_from_slice(&JMP_RIP_OPCODE);
         branch_code.extend_from_slice(&(target_func as u64).to_le_bytes());
-        branch_code.extend_from_slice(&JMP_RAX_OPCODE);
         branch_code
     }
 }
 
 fn patch_and_guard(src: FuncPtrInternal, jit_memory: *mut u8, jit_size: usize) -> PatchGuard {
-    // Resolve IAT thunks (jmp [rip+disp]) to the actual function address.
-    // This matches what thread_local_registry does, ensuring we patch the real
-    // function address — not the thunk — so the patch is visible to all call paths.
-    let func_addr = unsafe { resolve_to_real_function(src.as_ptr() as *mut u8) } as usize;
+    // Patch the function body, not a thunk, so the patch is visible to all call paths.
+    let func_addr = resolve_thunks(src.as_ptr() as usize);
     let jit_addr = jit_memory as usize;
 
-    let branch_code = generate_branch_to_target_function(func_addr, jit_addr);
+    // With CET enforced, calls through function pointers must still land on an `endbr64`.
+    let branch_code = if unsafe { read_bytes(func_addr as *const u8, 4) } == ENDBR64 {
+        let mut code = ENDBR64.to_vec();
+        code.extend(generate_branch_to_target_function(func_addr + 4, jit_addr));
+        code
+    } else {
+        generate_branch_to_target_function(func_addr, jit_addr)
+    };
     let patch_size = branch_code.len();
+    check_patch_fits(func_addr, patch_size);
 
     let original_bytes = unsafe { read_bytes(func_addr as *mut u8, patch_size) };
 
@@ -100,17 +219,90 @@ fn patch_and_guard(src: FuncPtrInternal, jit_memory: *mut u8, jit_size: usize) -
     )
 }
 
-/// Resolve import thunks to the actual function address on Windows x86_64.
-/// Extern functions go through an IAT thunk: `jmp [rip+disp32]` (FF 25 xx xx xx xx).
-/// This follows the indirection to return the real function address.
-unsafe fn resolve_to_real_function(func_addr: *mut u8) -> *mut u8 {
-    let code = std::slice::from_raw_parts(func_addr, 6);
-    if code[0] == 0xFF && code[1] == 0x25 {
-        let disp = i32::from_le_bytes([code[2], code[3], code[4], code[5]]);
-        let rip_after_insn = func_addr.add(6);
-        let iat_entry = rip_after_insn.offset(disp as isize) as *const *mut u8;
-        let real_addr = std::ptr::read(iat_entry);
-        return resolve_to_real_function(real_addr);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_branch_to_far_target_is_absolute() {
+        let near = generate_branch_to_target_function(0x1000, 0x2000);
+        assert_eq!(near, [0xE9, 0xFB, 0x0F, 0x00, 0x00]);
+
+        let far = generate_branch_to_target_function(0x1000, 0x1_2345_6789_0000);
+        assert_eq!(far.len(), 14);
+        assert_eq!(far[..6], JMP_RIP_OPCODE);
+        assert_eq!(far[6..], 0x1_2345_6789_0000u64.to_le_bytes());
+    }
+
+    #[test]
+    fn test_branch_just_out_of_rel32_range_is_absolute() {
+        let last_near = 0x1000 + 5 + i32::MAX as usize;
+        assert_eq!(generate_branch_to_target_function(0x1000, last_near).len(), 5);
+
+        let far = generate_branch_to_target_function(0x1000, last_near + 1);
+        assert_eq!(far.len(), 14);
+        // jmp [rip+0], followed by the target address it reads.
+        assert_eq!(far[..6], [0xFF, 0x25, 0x00, 0x00, 0x00, 0x00]);
+        assert_eq!(far[6..], ((last_near + 1) as u64).to_le_bytes());
+    }
+
+    // An 8-byte function followed by another one, with no padding in between.
+    #[cfg(target_os = "linux")]
+    std::arch::global_asm!(
+        ".text",
+        ".p2align 4",
+        ".type injectorpp_unit_eight_bytes, @function",
+        "injectorpp_unit_eight_bytes:",
+        "mov eax, 42",
+        "nop",
+        "nop",
+        "ret",
+        ".size injectorpp_unit_eight_bytes, . - injectorpp_unit_eight_bytes",
+        ".type injectorpp_unit_after_eight_bytes, @function",
+        "injectorpp_unit_after_eight_bytes:",
+        "xor eax, eax",
+        "ret",
+        ".size injectorpp_unit_after_eight_bytes, . - injectorpp_unit_after_eight_bytes",
+    );
+
+    #[cfg(target_os = "linux")]
+    extern "C" {
+        fn injectorpp_unit_eight_bytes() -> u32;
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    #[should_panic(expected = "is only 8 bytes, too small for the 14-byte patch")]
+    fn test_function_too_short_for_far_branch_is_refused() {
+        let func_addr = injectorpp_unit_eight_bytes as *const () as usize;
+
+        check_patch_fits(func_addr, 5);
+        check_patch_fits(func_addr, 14);
+    }
+
+    #[test]
+    fn test_counting_stub_starts_with_endbr64() {
+        let code = generate_counting_stub(0x10000, ENTERED_OFFSET, 0x20000, 0x30000);
+
+        assert_eq!(code[..4], ENDBR64);
+        // lock inc qword [rip+disp32], relative to the end of the instruction at +12.
+        let disp = i32::from_le_bytes([code[8], code[9], code[10], code[11]]);
+        assert_eq!(12 + disp as usize, ENTERED_OFFSET);
+        assert!(code[RETIRED_JUMP_OFFSET..ENTERED_OFFSET]
+            .iter()
+            .all(|&b| b == 0xCC));
+    }
+
+    #[test]
+    fn test_counting_stub_counters_on_their_own_page() {
+        let code = generate_counting_stub(0x10000, 0x1000, 0x20000, 0x30000);
+
+        // The counters are not part of the code, so they stay writable under `strict_wx`.
+        assert_eq!(code.len(), ENTERED_OFFSET);
+        let disp = i32::from_le_bytes([code[8], code[9], code[10], code[11]]);
+        assert_eq!(12 + disp as usize, 0x1000);
+        // mov r10, exited
+        assert_eq!(code[22..24], [0x49, 0xBA]);
+        assert_eq!(code[24..32], 0x11008u64.to_le_bytes());
     }
-    func_addr
 }
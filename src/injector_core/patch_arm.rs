@@ -1,78 +1,49 @@
 #![cfg(target_arch = "arm")]
 
-use std::ptr::null_mut;
 use std::ptr::NonNull;
 
 use crate::injector_core::common::*;
+use crate::injector_core::function_size::check_patch_fits;
 use crate::injector_core::patch_trait::*;
+use crate::injector_core::thunk::resolve_thunks;
 
 pub(crate) struct PatchArm;
 
+/// `ldr pc, [pc, #-4]`: pc reads 8 bytes ahead, so it loads the word following it.
+const ARM_LDR_PC: u32 = 0xE51FF004;
+
+/// `ldr.w pc, [pc, #0]`, as its two halfwords, loading the word at `Align(pc + 4, 4)`.
+const THUMB_LDR_W_PC: [u16; 2] = [0xF8DF, 0xF000];
+
+/// Thumb `nop`.
+const THUMB_NOP: u16 = 0xBF00;
+
 impl PatchTrait for PatchArm {
     fn replace_function_with_other_function(
         src: FuncPtrInternal,
         target: FuncPtrInternal,
     ) -> PatchGuard {
-        // Thumb mode (T32) functions are aligned on odd addresses,
-        // while ARM mode (A32) functions are aligned on even addresses.
-        let is_src_thumb = src.as_ptr() as usize & 1 != 0;
-
-        // Even if the function jump is on an odd address, the previous byte
-        // is executed in Thumb mode, so we need to align the memory on 2 bytes.
-        let src_ptr = if is_src_thumb {
-            (src.as_ptr() as u32 - 1) as *const ()
-        } else {
-            src.as_ptr()
-        };
-
-        let patch_size = 12;
-        let original_bytes = unsafe { read_bytes(src_ptr as *mut u8, patch_size) };
-
-        let instructions: [u32; 3] = if is_src_thumb {
-            [
-                // ldr r7, [pc, #0] ; 0x4F00. It will load pc + 0 into r6, so the target word
-                // bx r7 ; 4738
-                // Reversed because of little endian
-                0x47384F00,
-                // .word target
-                target.as_ptr() as u32,
-                // .word anything (unused)
-                0x00000000,
-            ]
-        } else {
-            [
-                // ldr r9, [pc, #-0] ; Load pc + 8 into r9, so the target word
-                0xE51F9000,
-                // bx r9 ; Branch to the target function
-                0xE12FFF19,
-                // .word target
-                target.as_ptr() as u32,
-            ]
-        };
-
-        let mut patch = [0u8; 12];
-
-        patch[0..4].copy_from_slice(&instructions[0].to_le_bytes());
-        patch[4..8].copy_from_slice(&instructions[1].to_le_bytes());
-        patch[8..12].copy_from_slice(&instructions[2].to_le_bytes());
-
-        // In thumb mode, if the source is not aligned on 32 bit, add a NOP to align it, so the target adress is also aligned on 32 bit
-        // If we don't do that, the load adress will be misaligned and will load the bx instruction instead of the target function.
-        if is_src_thumb && (src_ptr as usize % 4 != 0) {
-            patch.rotate_right(2);
-            patch[0] = 0xC0;
-            patch[1] = 0x46; // NOP instruction in Thumb mode
-        }
+        // Patch the function body, not a veneer, so the patch is visible to all call paths.
+        // Thumb (T32) functions have the lowest bit of their address set, ARM (A32) ones do not.
+        let func_addr = resolve_thunks(src.as_ptr() as usize);
+        let is_thumb = func_addr & 1 != 0;
+        let code_addr = func_addr & !1;
+
+        let patch =
+            generate_branch_to_target_function(code_addr, target.as_ptr() as usize, is_thumb);
+        check_patch_fits(code_addr, patch.len());
+
+        let original_bytes = unsafe { read_bytes(code_addr as *mut u8, patch.len()) };
 
         unsafe {
-            patch_function(src_ptr as *mut u8, &patch);
+            patch_function(code_addr as *mut u8, &patch);
         }
 
         PatchGuard::new(
-            src_ptr as *mut u8,
+            code_addr as *mut u8,
             original_bytes,
-            patch_size,
-            null_mut(), // No JIT memory needed for ARM
+            patch.len(),
+            std::ptr::null_mut(), // The literal holds the target, so no JIT memory is needed
             0,
         )
     }
@@ -85,6 +56,75 @@ impl PatchTrait for PatchArm {
             )
         })
     }
+
+    fn replace_function_return_float(src: FuncPtrInternal, value: FloatReturn) -> PatchGuard {
+        let code = generate_return_float_code(value);
+        let target = shared_jit_code(&src, &code);
+        Self::replace_function_with_other_function(src, target)
+    }
+}
+
+/// Generates ARM mode code returning `value`, loaded from the literal following the code, in
+/// s0 or d0 with the hard-float ABI and in r0 or r0:r1 otherwise.
+///
+/// ```text
+///  0: vldr d0, [pc, #0]         ; pc reads 8 bytes ahead, at the literal
+///  4: bx lr
+///  8: .quad value
+/// ```
+pub(crate) fn generate_return_float_code(value: FloatReturn) -> Vec<u8> {
+    let load: u32 = match (value, cfg!(target_abi = "eabihf")) {
+        (FloatReturn::F32(_), true) => 0xED9F0A00, // vldr s0, [pc, #0]
+        (FloatReturn::F64(_), true) => 0xED9F0B00, // vldr d0, [pc, #0]
+        (FloatReturn::F32(_), false) => 0xE59F0000, // ldr r0, [pc, #0]
+        (FloatReturn::F64(_), false) => 0xE1CF00D0, // ldrd r0, r1, [pc, #0]
+    };
+    let bx_lr: u32 = 0xE12FFF1E;
+
+    let mut code = Vec::with_capacity(16);
+    code.extend_from_slice(&load.to_le_bytes());
+    code.extend_from_slice(&bx_lr.to_le_bytes());
+    code.extend_from_slice(&value.to_le_bytes());
+    code
+}
+
+/// Generates a patch loading `target` into pc from a literal, which switches to Thumb or ARM
+/// state according to the lowest bit of `target` and leaves every register but pc untouched.
+///
+/// ARM functions are 4-byte aligned and get 8 bytes. Thumb functions get the 32-bit
+/// `ldr.w pc`, 8 bytes when they are 4-byte aligned and 10 bytes otherwise, since the literal
+/// is read from a word-aligned address and a `nop` pads the load up to it.
+///
+/// ```text
+/// ARM:                          Thumb, at 4n + 2:
+///  0: ldr pc, [pc, #-4]          0: ldr.w pc, [pc, #4]   ; Align(pc, 4) is 2 bytes back
+///  4: .word target               4: nop
+///                                6: .word target
+/// ```
+fn generate_branch_to_target_function(code_addr: usize, target: usize, is_thumb: bool) -> Vec<u8> {
+    let mut patch = Vec::with_capacity(10);
+
+    if !is_thumb {
+        assert!(
+            code_addr.is_multiple_of(4),
+            "injectorpp: ARM function at {:#x} is not 4-byte aligned. Thumb functions must be \
+             passed with the lowest bit of their address set.",
+            code_addr
+        );
+        patch.extend_from_slice(&ARM_LDR_PC.to_le_bytes());
+        patch.extend_from_slice(&(target as u32).to_le_bytes());
+        return patch;
+    }
+
+    let padded = !code_addr.is_multiple_of(4);
+    let offset = if padded { 4 } else { 0 };
+    patch.extend_from_slice(&THUMB_LDR_W_PC[0].to_le_bytes());
+    patch.extend_from_slice(&(THUMB_LDR_W_PC[1] | offset).to_le_bytes());
+    if padded {
+        patch.extend_from_slice(&THUMB_NOP.to_le_bytes());
+    }
+    patch.extend_from_slice(&(target as u32).to_le_bytes());
+    patch
 }
 
 fn return_true() -> bool {
@@ -94,3 +134,27 @@ fn return_true() -> bool {
 fn return_false() -> bool {
     false
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arm_branch_loads_pc_from_literal() {
+        let patch = generate_branch_to_target_function(0x1000, 0x2000_0001, false);
+        assert_eq!(patch, [0x04, 0xF0, 0x1F, 0xE5, 0x01, 0x00, 0x00, 0x20]);
+    }
+
+    #[test]
+    fn test_thumb_branch_keeps_literal_word_aligned() {
+        let aligned = generate_branch_to_target_function(0x1000, 0x2000, true);
+        assert_eq!(aligned, [0xDF, 0xF8, 0x00, 0xF0, 0x00, 0x20, 0x00, 0x00]);
+
+        // Align(0x1002 + 4, 4) is 0x1004, and the literal is at 0x1008.
+        let unaligned = generate_branch_to_target_function(0x1002, 0x2000, true);
+        assert_eq!(
+            unaligned,
+            [0xDF, 0xF8, 0x04, 0xF0, 0x00, 0xBF, 0x00, 0x20, 0x00, 0x00]
+        );
+    }
+}
@@ -1,21 +1,30 @@
-#![cfg(target_arch = "aarch64")]
+#![cfg(any(target_arch = "aarch64", target_arch = "arm64ec"))]
 
 use crate::injector_core::arm64_codegenerator::*;
 use crate::injector_core::common::*;
+use crate::injector_core::function_size::{check_patch_fits, function_size};
 use crate::injector_core::patch_trait::*;
 use crate::injector_core::utils::*;
 
 pub(crate) struct PatchArm64;
 
+/// Bytes written over the start of a function: its branch to the JIT block, padded with NOPs.
+const PATCH_SIZE: usize = 12;
+
+/// Bytes written over the start of a function smaller than `PATCH_SIZE` bytes.
+const COMPACT_PATCH_SIZE: usize = 8;
+
+/// The most bytes a patch can take: a landing pad followed by ADRP, ADD and BR.
+const MAX_PATCH_SIZE: usize = 16;
+
 impl PatchTrait for PatchArm64 {
     fn replace_function_with_other_function(
         src: FuncPtrInternal,
         target: FuncPtrInternal,
     ) -> PatchGuard {
-        const PATCH_SIZE: usize = 12;
         const JIT_SIZE: usize = 20;
 
-        let original_bytes = unsafe { read_bytes(src.as_ptr() as *mut u8, PATCH_SIZE) };
+        let original_bytes = unsafe { read_bytes(src.as_ptr() as *mut u8, MAX_PATCH_SIZE) };
         let jit_memory = allocate_jit_memory(&src, JIT_SIZE);
         generate_will_execute_jit_code_abs(jit_memory, target.as_ptr());
 
@@ -23,45 +32,77 @@ impl PatchTrait for PatchArm64 {
     }
 
     fn replace_function_return_boolean(src: FuncPtrInternal, value: bool) -> PatchGuard {
-        const PATCH_SIZE: usize = 12;
         const JIT_SIZE: usize = 8;
 
-        let original_bytes = unsafe { read_bytes(src.as_ptr() as *mut u8, PATCH_SIZE) };
+        let original_bytes = unsafe { read_bytes(src.as_ptr() as *mut u8, MAX_PATCH_SIZE) };
         let jit_memory = allocate_jit_memory(&src, JIT_SIZE);
         generate_will_return_boolean_jit_code(jit_memory, value);
 
         apply_branch_patch(src, jit_memory, JIT_SIZE, &original_bytes)
     }
+
+    fn replace_function_return_float(src: FuncPtrInternal, value: FloatReturn) -> PatchGuard {
+
+        let code = generate_return_float_code(value);
+        let original_bytes = unsafe { read_bytes(src.as_ptr() as *mut u8, MAX_PATCH_SIZE) };
+        let jit_memory = allocate_jit_memory(&src, code.len());
+        unsafe {
+            inject_asm_code(&code, jit_memory);
+        }
+
+        apply_branch_patch(src, jit_memory, code.len(), &original_bytes)
+    }
+}
+
+/// Generates code returning `value` in d0 or s0, loaded from the literal following the code.
+///
+/// The generated instructions are:
+///   ldr d0, #8      (ldr s0, #8 for f32)
+///   ret
+///   .quad value
+pub(crate) fn generate_return_float_code(value: FloatReturn) -> Vec<u8> {
+    let ldr: u32 = match value {
+        FloatReturn::F32(_) => 0x1C000040,
+        FloatReturn::F64(_) => 0x5C000040,
+    };
+
+    let mut code = Vec::with_capacity(16);
+    append_instruction(&mut code, ldr);
+    append_instruction(&mut code, bool_array_to_u32(emit_ret_x30()));
+    code.extend_from_slice(&value.to_le_bytes());
+    code
 }
 
-/// Generates a 16-byte JIT code block that loads the absolute address of `target`
-/// into register X9 (using a MOVZ and two MOVK instructions) and then branches to X9.
-/// This avoids branch-range limitations.
+/// Generates a 20-byte JIT code block that loads the absolute address of `target`
+/// into register X16 (using a MOVZ and three MOVK instructions) and then branches to X16.
+/// This avoids branch-range limitations. With BTI enforced, `br x16` may land on the
+/// `bti c` starting the target, which `br` through other registers may not.
 ///
 /// The generated instructions are:
-///   movz x9, #imm0, lsl #0
-///   movk x9, #imm1, lsl #16
-///   movk x9, #imm2, lsl #32
-///   br x9
+///   movz x16, #imm0, lsl #0
+///   movk x16, #imm1, lsl #16
+///   movk x16, #imm2, lsl #32
+///   movk x16, #imm3, lsl #48
+///   br x16
 fn generate_will_execute_jit_code_abs(jit_ptr: *mut u8, target: *const ()) {
     let target_addr = target as usize as u64;
 
-    // x9
-    let register_name: [bool; 5] = u8_to_bits::<5>(9);
+    // x16
+    let register_name: [bool; 5] = u8_to_bits::<5>(16);
 
-    // MOVZ x9, #imm0 (clears the rest)
+    // MOVZ x16, #imm0 (clears the rest)
     let movz = emit_movz_from_address(target_addr, 0, true, u8_to_bits::<2>(0), register_name);
 
-    // MOVK x9, #imm1, LSL #16
+    // MOVK x16, #imm1, LSL #16
     let movk1 = emit_movk_from_address(target_addr, 16, true, u8_to_bits::<2>(1), register_name);
 
-    // MOVK x9, #imm2, LSL #32
+    // MOVK x16, #imm2, LSL #32
     let movk2 = emit_movk_from_address(target_addr, 32, true, u8_to_bits::<2>(2), register_name);
 
-    // MOVK x9, #imm3, LSL #48
+    // MOVK x16, #imm3, LSL #48
     let movk3 = emit_movk_from_address(target_addr, 48, true, u8_to_bits::<2>(3), register_name);
 
-    // BR x9
+    // BR x16
     let br = emit_br(register_name);
 
     // Write instructions in the correct order: bottom-up so no overwrite
@@ -112,57 +153,60 @@ fn append_instruction(asm_code: &mut Vec<u8>, instruction: u32) {
     asm_code.push(((instruction >> 24) & 0xFF) as u8);
 }
 
+/// Writes a branch to the JIT block over the start of the function.
+///
+/// The branch is padded with NOPs to `PATCH_SIZE` bytes, or to `COMPACT_PATCH_SIZE` bytes
+/// when the function is known to be smaller than `PATCH_SIZE`. A single B reaches a JIT block
+/// within ±128MB, and the ADRP and BR pair reaching it from further away fits in 8 bytes as
+/// the block is page-aligned.
 fn apply_branch_patch(
     src: FuncPtrInternal,
     jit_memory: *mut u8,
     jit_size: usize,
     original_bytes: &[u8],
 ) -> PatchGuard {
-    const PATCH_SIZE: usize = 12;
     const NOP: u32 = 0xd503201f;
 
     let func_addr = src.as_ptr() as usize;
     let jit_addr = jit_memory as usize;
 
-    let mut patch = [0u8; PATCH_SIZE];
-
-    #[cfg(target_os = "macos")]
-    {
-        let instrs = maybe_emit_long_jump(func_addr, jit_addr);
-        if instrs.len() == 1 {
-            patch[0..4].copy_from_slice(&instrs[0].to_le_bytes());
-            patch[4..8].copy_from_slice(&NOP.to_le_bytes());
-            patch[8..12].copy_from_slice(&NOP.to_le_bytes());
-        } else {
-            patch[0..4].copy_from_slice(&instrs[0].to_le_bytes());
-            patch[4..8].copy_from_slice(&instrs[1].to_le_bytes());
-            patch[8..12].copy_from_slice(&instrs[2].to_le_bytes());
-        }
+    // Calls through function pointers must still land on a landing pad under BTI.
+    let first = u32::from_le_bytes([
+        original_bytes[0],
+        original_bytes[1],
+        original_bytes[2],
+        original_bytes[3],
+    ]);
+    let landing_pad = landing_pad(first);
+    let pad_size = landing_pad.map_or(0, |_| 4);
+
+    let branch = maybe_emit_long_jump(func_addr + pad_size, jit_addr);
+
+    let patch_size = match function_size(func_addr, PATCH_SIZE) {
+        Some(size) if size < PATCH_SIZE => COMPACT_PATCH_SIZE,
+        _ => PATCH_SIZE,
     }
-
-    #[cfg(not(target_os = "macos"))]
+    .max(pad_size + branch.len() * 4);
+
+    let mut patch = Vec::with_capacity(patch_size);
+    for instr in landing_pad
+        .iter()
+        .chain(branch.iter())
+        .chain(std::iter::repeat(&NOP))
+        .take(patch_size / 4)
     {
-        const BRANCH_RANGE: std::ops::RangeInclusive<isize> = -0x2000000..=0x1FFF_FFFF; // ±32MB
-
-        let offset = (jit_addr as isize - func_addr as isize) / 4;
-        if !BRANCH_RANGE.contains(&offset) {
-            panic!("JIT memory is out of branch range: offset = {offset}, expected ±32MB");
-        }
-
-        let branch_instr: u32 = 0x14000000 | ((offset as u32) & 0x03FF_FFFF);
-        patch[0..4].copy_from_slice(&branch_instr.to_le_bytes());
-        patch[4..8].copy_from_slice(&NOP.to_le_bytes());
-        patch[8..12].copy_from_slice(&NOP.to_le_bytes());
+        patch.extend_from_slice(&instr.to_le_bytes());
     }
 
+    check_patch_fits(func_addr, patch_size);
     unsafe {
         patch_function(src.as_ptr() as *mut u8, &patch);
     }
 
     PatchGuard::new(
         src.as_ptr() as *mut u8,
-        original_bytes.to_vec(),
-        PATCH_SIZE,
+        original_bytes[..patch_size].to_vec(),
+        patch_size,
         jit_memory,
         jit_size,
     )
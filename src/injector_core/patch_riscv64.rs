@@ -0,0 +1,196 @@
+#![cfg(target_arch = "riscv64")]
+
+use crate::injector_core::common::*;
+use crate::injector_core::function_size::check_patch_fits;
+use crate::injector_core::patch_trait::*;
+use crate::injector_core::thunk::resolve_thunks;
+
+/// Patch implementation for RISC-V 64 (riscv64gc).
+pub(crate) struct PatchRiscv64;
+
+/// Bytes written over the start of a function: `auipc` and `jalr` to the JIT block.
+const PATCH_SIZE: usize = 8;
+
+/// `t1`, the scratch register of the `tail` pseudo-instruction, which carries no arguments.
+const T1: u32 = 6;
+const NOP: u32 = 0x0000_0013;
+const RET: u32 = 0x0000_8067;
+
+impl PatchTrait for PatchRiscv64 {
+    fn replace_function_with_other_function(
+        src: FuncPtrInternal,
+        target: FuncPtrInternal,
+    ) -> PatchGuard {
+        let code = generate_absolute_jump_code(target.as_ptr() as usize);
+        patch_to_jit_code(src, &code)
+    }
+
+    fn replace_function_return_boolean(src: FuncPtrInternal, value: bool) -> PatchGuard {
+        patch_to_jit_code(src, &generate_return_boolean_code(value))
+    }
+
+    fn replace_function_return_float(src: FuncPtrInternal, value: FloatReturn) -> PatchGuard {
+        patch_to_jit_code(src, &generate_return_float_code(value))
+    }
+}
+
+fn auipc(rd: u32, imm20: u32) -> u32 {
+    (imm20 << 12) | (rd << 7) | 0x17
+}
+
+fn jalr(rd: u32, rs1: u32, imm12: i32) -> u32 {
+    ((imm12 as u32 & 0xFFF) << 20) | (rs1 << 15) | (rd << 7) | 0x67
+}
+
+/// `ld`, `flw` or `fld` of `rd` from `imm12(rs1)`, by `funct3` and `opcode`.
+fn load(opcode: u32, funct3: u32, rd: u32, rs1: u32, imm12: i32) -> u32 {
+    ((imm12 as u32 & 0xFFF) << 20) | (rs1 << 15) | (funct3 << 12) | (rd << 7) | opcode
+}
+
+fn append_instruction(code: &mut Vec<u8>, instruction: u32) {
+    code.extend_from_slice(&instruction.to_le_bytes());
+}
+
+/// Generates a jump from `ori_func` to `target_func`, within ±2GB of it:
+///
+/// ```text
+/// auipc t1, %hi(target - ori_func)
+/// jalr  zero, %lo(target - ori_func)(t1)
+/// ```
+///
+/// Panics when the target is out of range, which JIT memory allocated near the function
+/// never is.
+fn generate_branch_to_target_function(ori_func: usize, target_func: usize) -> Vec<u8> {
+    let offset = target_func.wrapping_sub(ori_func) as isize;
+    if offset < i32::MIN as isize + 0x800 || offset > i32::MAX as isize - 0x800 {
+        panic!(
+            "JIT code at {:#x} is out of auipc range of {:#x}",
+            target_func, ori_func
+        );
+    }
+
+    // The low 12 bits are sign-extended by jalr, so round the high 20 bits to compensate.
+    let hi = ((offset + 0x800) >> 12) as u32 & 0xF_FFFF;
+    let lo = (offset - (((offset + 0x800) >> 12) << 12)) as i32;
+
+    let mut code = Vec::with_capacity(PATCH_SIZE);
+    append_instruction(&mut code, auipc(T1, hi));
+    append_instruction(&mut code, jalr(0, T1, lo));
+    code
+}
+
+/// Generates code jumping to `target`, loaded from the literal following the code, so it
+/// reaches the whole address space.
+///
+/// ```text
+///  0: auipc t1, 0
+///  4: ld t1, 16(t1)
+///  8: jalr zero, 0(t1)
+/// 12: nop
+/// 16: .quad target
+/// ```
+fn generate_absolute_jump_code(target: usize) -> Vec<u8> {
+    let mut code = Vec::with_capacity(24);
+    append_instruction(&mut code, auipc(T1, 0));
+    append_instruction(&mut code, load(0x03, 3, T1, T1, 16));
+    append_instruction(&mut code, jalr(0, T1, 0));
+    append_instruction(&mut code, NOP);
+    code.extend_from_slice(&(target as u64).to_le_bytes());
+    code
+}
+
+/// Generates code returning `value` as a `bool` in a0.
+///
+/// ```text
+/// addi a0, zero, value
+/// ret
+/// ```
+fn generate_return_boolean_code(value: bool) -> Vec<u8> {
+    let mut code = Vec::with_capacity(8);
+    append_instruction(&mut code, ((value as u32) << 20) | (10 << 7) | 0x13);
+    append_instruction(&mut code, RET);
+    code
+}
+
+/// Generates code returning `value` in fa0, loaded from the literal following the code.
+///
+/// ```text
+///  0: auipc t1, 0
+///  4: fld fa0, 16(t1)           ; flw for f32
+///  8: ret
+/// 12: nop
+/// 16: .quad value
+/// ```
+pub(crate) fn generate_return_float_code(value: FloatReturn) -> Vec<u8> {
+    let funct3 = match value {
+        FloatReturn::F32(_) => 2,
+        FloatReturn::F64(_) => 3,
+    };
+
+    let mut code = Vec::with_capacity(24);
+    append_instruction(&mut code, auipc(T1, 0));
+    append_instruction(&mut code, load(0x07, funct3, 10, T1, 16));
+    append_instruction(&mut code, RET);
+    append_instruction(&mut code, NOP);
+    code.extend_from_slice(&value.to_le_bytes());
+    code
+}
+
+/// Copies `code` to JIT memory allocated near the function, and writes a jump to it over the
+/// start of the function.
+fn patch_to_jit_code(src: FuncPtrInternal, code: &[u8]) -> PatchGuard {
+    // Patch the function body, not a thunk, so the patch is visible to all call paths.
+    let func_addr = resolve_thunks(src.as_ptr() as usize);
+    check_patch_fits(func_addr, PATCH_SIZE);
+
+    let jit_memory = allocate_jit_memory(&src, code.len());
+    unsafe {
+        inject_asm_code(code, jit_memory);
+    }
+
+    let branch_code = generate_branch_to_target_function(func_addr, jit_memory as usize);
+    let original_bytes = unsafe { read_bytes(func_addr as *mut u8, PATCH_SIZE) };
+
+    unsafe {
+        patch_function(func_addr as *mut u8, &branch_code);
+    }
+
+    PatchGuard::new(
+        func_addr as *mut u8,
+        original_bytes,
+        PATCH_SIZE,
+        jit_memory,
+        code.len(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(code: &[u8]) -> Vec<u32> {
+        code.chunks(4)
+            .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+            .collect()
+    }
+
+    #[test]
+    fn test_branch_rounds_high_bits_for_negative_low_bits() {
+        // auipc t1, 0x1; jalr zero, -0x800(t1)
+        let code = generate_branch_to_target_function(0x1_0000, 0x1_0800);
+        assert_eq!(words(&code), [0x0000_1317, 0x8003_0067]);
+
+        // auipc t1, 0xfffff; jalr zero, 0x10(t1)
+        let code = generate_branch_to_target_function(0x1_0000, 0x0_F010);
+        assert_eq!(words(&code), [0xFFFF_F317, 0x0103_0067]);
+    }
+
+    #[test]
+    fn test_absolute_jump_loads_literal() {
+        let code = generate_absolute_jump_code(0x1234_5678_9ABC);
+
+        // auipc t1, 0; ld t1, 16(t1); jalr zero, 0(t1)
+        assert_eq!(words(&code[..12]), [0x0000_0317, 0x0103_3303, 0x0003_0067]);
+        assert_eq!(code[16..], 0x1234_5678_9ABCu64.to_le_bytes());
+    }
+}
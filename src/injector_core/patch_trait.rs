@@ -8,4 +8,6 @@ pub(crate) trait PatchTrait {
     ) -> PatchGuard;
 
     fn replace_function_return_boolean(src: FuncPtrInternal, value: bool) -> PatchGuard;
+
+    fn replace_function_return_float(src: FuncPtrInternal, value: FloatReturn) -> PatchGuard;
 }
@@ -0,0 +1,126 @@
+#![cfg(target_arch = "x86")]
+
+use crate::injector_core::common::*;
+use crate::injector_core::function_size::check_patch_fits;
+use crate::injector_core::patch_trait::*;
+use crate::injector_core::thunk::resolve_thunks;
+
+/// Patch implementation for 32-bit x86 (i686).
+pub(crate) struct PatchX86;
+
+const JMP_REL_OPCODE: u8 = 0xE9;
+const JMP_REL_SIZE: usize = 5;
+
+impl PatchTrait for PatchX86 {
+    fn replace_function_with_other_function(
+        src: FuncPtrInternal,
+        target: FuncPtrInternal,
+    ) -> PatchGuard {
+        // Patch the function body, not a thunk, so the patch is visible to all call paths.
+        let func_addr = resolve_thunks(src.as_ptr() as usize);
+        check_patch_fits(func_addr, JMP_REL_SIZE);
+
+        let branch_code = generate_branch_to_target_function(func_addr, target.as_ptr() as usize);
+        let original_bytes = unsafe { read_bytes(func_addr as *mut u8, JMP_REL_SIZE) };
+
+        unsafe {
+            patch_function(func_addr as *mut u8, &branch_code);
+        }
+
+        PatchGuard::new(
+            func_addr as *mut u8,
+            original_bytes,
+            JMP_REL_SIZE,
+            std::ptr::null_mut(), // The branch reaches the target directly
+            0,
+        )
+    }
+
+    fn replace_function_return_boolean(src: FuncPtrInternal, value: bool) -> PatchGuard {
+        Self::replace_function_with_other_function(src, unsafe {
+            FuncPtrInternal::new(
+                std::ptr::NonNull::new(if value { return_true } else { return_false } as *mut ())
+                    .expect("Failed to create FuncPtrInternal"), // Should never fail
+            )
+        })
+    }
+
+    fn replace_function_return_float(src: FuncPtrInternal, value: FloatReturn) -> PatchGuard {
+        let code = generate_return_float_code(value);
+        let target = shared_jit_code(&src, &code);
+        Self::replace_function_with_other_function(src, target)
+    }
+}
+
+/// Generates a `jmp rel32` from `ori_func` to `target_func`. The displacement wraps around the
+/// 32-bit address space, so every target is in range.
+fn generate_branch_to_target_function(ori_func: usize, target_func: usize) -> Vec<u8> {
+    let offset = target_func.wrapping_sub(ori_func.wrapping_add(JMP_REL_SIZE)) as u32;
+
+    let mut branch_code = Vec::with_capacity(JMP_REL_SIZE);
+    branch_code.push(JMP_REL_OPCODE);
+    branch_code.extend_from_slice(&offset.to_le_bytes());
+    branch_code
+}
+
+/// Generates position independent code returning `value`, loaded from the literal following
+/// the code, in xmm0 as Rust functions do when SSE2 is enabled, and in st(0) otherwise.
+///
+/// ```text
+///  0: call 5                    ; pushes the address of the next instruction
+///  5: pop ecx
+///  6: movsd xmm0, [ecx+11]      ; movss for f32, fld qword/dword without SSE2
+/// 11: ret
+/// 16: .quad value
+/// ```
+pub(crate) fn generate_return_float_code(value: FloatReturn) -> Vec<u8> {
+    let mut code = Vec::with_capacity(24);
+
+    code.extend_from_slice(&[0xE8, 0x00, 0x00, 0x00, 0x00]);
+    code.push(0x59);
+    match (value, cfg!(target_feature = "sse2")) {
+        (FloatReturn::F32(_), true) => code.extend_from_slice(&[0xF3, 0x0F, 0x10, 0x41]),
+        (FloatReturn::F64(_), true) => code.extend_from_slice(&[0xF2, 0x0F, 0x10, 0x41]),
+        (FloatReturn::F32(_), false) => code.extend_from_slice(&[0xD9, 0x41]),
+        (FloatReturn::F64(_), false) => code.extend_from_slice(&[0xDD, 0x41]),
+    }
+    code.push(16 - 5);
+    code.push(0xC3);
+
+    // int3 padding up to the literal.
+    code.resize(16, 0xCC);
+    code.extend_from_slice(&value.to_le_bytes());
+    code
+}
+
+fn return_true() -> bool {
+    true
+}
+
+fn return_false() -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_branch_wraps_around_address_space() {
+        let forward = generate_branch_to_target_function(0x1000, 0x2000);
+        assert_eq!(forward, [0xE9, 0xFB, 0x0F, 0x00, 0x00]);
+
+        let wrapped = generate_branch_to_target_function(0xFFFF_F000, 0x1000);
+        assert_eq!(wrapped, [0xE9, 0xFB, 0x1F, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn test_return_float_code_loads_literal() {
+        let code = generate_return_float_code(FloatReturn::F64(1.5));
+
+        // The displacement precedes the ret and is relative to the popped return address, at +5.
+        let ret = code.iter().position(|&byte| byte == 0xC3).unwrap();
+        assert_eq!(5 + code[ret - 1] as usize, 16);
+        assert_eq!(code[16..], 1.5f64.to_le_bytes());
+    }
+}
@@ -0,0 +1,338 @@
+#![cfg(target_os = "linux")]
+
+//! Resolves PLT stubs and IFUNC resolvers to the function the dynamic loader selected.
+//!
+//! The address of an imported function can be its PLT stub, which jumps through a GOT slot.
+//! With lazy binding the slot points back into the PLT until the first call, so following the
+//! jump would patch the PLT itself. glibc also selects the implementation of functions such as
+//! `memcpy` and `getenv` at load time: the symbol is an `STT_GNU_IFUNC` whose address is the
+//! resolver making the choice, not the implementation that runs.
+
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::sync::{Arc, Mutex};
+
+use crate::injector_core::elf;
+
+/// What is read from an ELF object to resolve its stubs.
+pub(crate) struct Dynamic {
+    /// The `(start, end)` of the PLT sections.
+    pub(crate) plt: Vec<(u64, u64)>,
+    /// The `(start, end)` of the GOT sections.
+    pub(crate) got: Vec<(u64, u64)>,
+    /// The `(slot, symbol name)` of the relocations of GOT slots, sorted by slot. The name is
+    /// empty for `IRELATIVE` relocations, which are resolved at load time.
+    pub(crate) slots: Vec<(u64, &'static [u8])>,
+    /// The `(address, symbol name)` of the IFUNC symbols, sorted by address.
+    ifuncs: Vec<(u64, &'static [u8])>,
+}
+
+impl Dynamic {
+    /// Returns whether `addr`, relative to the object, lies in one of the `sections`.
+    pub(crate) fn contains(sections: &[(u64, u64)], addr: u64) -> bool {
+        sections
+            .iter()
+            .any(|&(start, end)| (start..end).contains(&addr))
+    }
+}
+
+/// Returns the function the PLT stub or the IFUNC resolver at `addr` leads to, or `None` if
+/// `addr` is neither.
+pub(crate) fn implementation(addr: usize) -> Option<usize> {
+    // Thumb functions have the lowest bit of their address set.
+    let code_addr = if cfg!(target_arch = "arm") {
+        addr & !1
+    } else {
+        addr
+    };
+    let (path, bias) = elf::object_containing(code_addr)?;
+    let dynamic = dynamic(&path)?;
+    let file_addr = code_addr.checked_sub(bias)? as u64;
+    let in_plt = |addr: usize| Dynamic::contains(&dynamic.plt, addr.wrapping_sub(bias) as u64);
+
+    if in_plt(code_addr) {
+        let slot = unsafe { plt_slot(code_addr)? };
+        let index = dynamic
+            .slots
+            .binary_search_by_key(&(slot.wrapping_sub(bias) as u64), |&(slot, _)| slot)
+            .ok()?;
+        let target = unsafe { (slot as *const usize).read_unaligned() };
+        if !in_plt(target) {
+            return Some(target);
+        }
+
+        // Not bound yet: ask the loader for the symbol, which binds it and runs its resolver
+        // if it is an IFUNC.
+        return lookup(dynamic.slots[index].1).filter(|&target| !in_plt(target));
+    }
+
+    let index = dynamic
+        .ifuncs
+        .binary_search_by_key(&file_addr, |&(addr, _)| addr)
+        .ok()?;
+    lookup(dynamic.ifuncs[index].1)
+        .filter(|&target| target != addr)
+        .or_else(|| unsafe { run_resolver(addr) })
+}
+
+/// Returns the address the dynamic loader gives the symbol `name`.
+fn lookup(name: &[u8]) -> Option<usize> {
+    if name.is_empty() {
+        return None;
+    }
+    let name = CString::new(name).ok()?;
+    let addr = unsafe { libc::dlsym(libc::RTLD_DEFAULT, name.as_ptr()) };
+    (!addr.is_null()).then_some(addr as usize)
+}
+
+/// Calls the IFUNC resolver at `addr` the way glibc does for symbols the loader cannot look
+/// up, such as those local to their object.
+unsafe fn run_resolver(addr: usize) -> Option<usize> {
+    let resolver: extern "C" fn(libc::c_ulong) -> usize = std::mem::transmute(addr);
+    let target = resolver(libc::getauxval(libc::AT_HWCAP));
+    (target != 0 && target != addr).then_some(target)
+}
+
+/// Returns the address of the GOT slot the PLT stub at `addr` jumps through.
+#[cfg(target_arch = "x86_64")]
+unsafe fn plt_slot(addr: usize) -> Option<usize> {
+    use crate::injector_core::x86_64_insn::ENDBR64;
+
+    let code = crate::injector_core::common::read_bytes(addr as *const u8, 11);
+    // endbr64 and bnd start the stubs of `.plt.sec`.
+    let mut at = if code[..4] == ENDBR64 { 4 } else { 0 };
+    if code[at] == 0xF2 {
+        at += 1;
+    }
+
+    // jmp [rip+disp32]
+    if code[at..at + 2] != [0xFF, 0x25] {
+        return None;
+    }
+    let disp = i32::from_le_bytes([code[at + 2], code[at + 3], code[at + 4], code[at + 5]]);
+    Some((addr + at + 6).wrapping_add_signed(disp as isize))
+}
+
+/// Returns the address of the GOT slot the PLT stub at `addr` jumps through.
+#[cfg(target_arch = "aarch64")]
+unsafe fn plt_slot(addr: usize) -> Option<usize> {
+    use crate::injector_core::arm64_codegenerator::BTI_C;
+
+    let code = crate::injector_core::common::read_bytes(addr as *const u8, 12);
+    let insn = |i: usize| {
+        u32::from_le_bytes([
+            code[i * 4],
+            code[i * 4 + 1],
+            code[i * 4 + 2],
+            code[i * 4 + 3],
+        ])
+    };
+    let (pc, first, second) = if insn(0) == BTI_C {
+        (addr + 4, insn(1), insn(2))
+    } else {
+        (addr, insn(0), insn(1))
+    };
+
+    // adrp x16, page; ldr x17, [x16, #off]
+    if first & 0x9F00_001F != 0x9000_0010 || second & 0xFFC0_03FF != 0xF940_0211 {
+        return None;
+    }
+    let imm = (((first >> 5) & 0x7FFFF) << 2 | (first >> 29) & 0x3) as i32;
+    let page = (pc & !0xFFF).wrapping_add_signed(((imm << 11) >> 11) as isize * 0x1000);
+    Some(page + ((second >> 10) & 0xFFF) as usize * 8)
+}
+
+/// Returns the address of the GOT slot the PLT stub at `addr` jumps through.
+#[cfg(target_arch = "arm")]
+unsafe fn plt_slot(addr: usize) -> Option<usize> {
+    let code = crate::injector_core::common::read_bytes(addr as *const u8, 16);
+    let insn = |i: usize| {
+        u32::from_le_bytes([
+            code[i * 4],
+            code[i * 4 + 1],
+            code[i * 4 + 2],
+            code[i * 4 + 3],
+        ])
+    };
+    // An immediate of a data-processing instruction: 8 bits rotated right by twice 4 bits.
+    let immediate = |insn: u32| (insn & 0xFF).rotate_right((insn >> 8 & 0xF) * 2) as usize;
+
+    // add ip, pc, #imm; add ip, ip, #imm (once or twice); ldr pc, [ip, #imm]!
+    if insn(0) & 0xFFFF_F000 != 0xE28F_C000 {
+        return None;
+    }
+    // pc reads as the address of the instruction plus 8.
+    let mut slot = (addr + 8).wrapping_add(immediate(insn(0)));
+    for i in 1..4 {
+        match insn(i) & 0xFFFF_F000 {
+            0xE28C_C000 => slot = slot.wrapping_add(immediate(insn(i))),
+            0xE5BC_F000 => return Some(slot.wrapping_add((insn(i) & 0xFFF) as usize)),
+            _ => return None,
+        }
+    }
+    None
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64", target_arch = "arm")))]
+unsafe fn plt_slot(_addr: usize) -> Option<usize> {
+    None
+}
+
+/// Returns the PLT and GOT sections, GOT slot relocations and IFUNC symbols of the ELF object
+/// at `path`. Read once per object and kept for the lifetime of the process.
+pub(crate) fn dynamic(path: &str) -> Option<Arc<Dynamic>> {
+    static OBJECTS: Mutex<Option<HashMap<String, Option<Arc<Dynamic>>>>> = Mutex::new(None);
+
+    let mut objects = OBJECTS.lock().unwrap_or_else(|e| e.into_inner());
+    objects
+        .get_or_insert_with(HashMap::new)
+        .entry(path.to_string())
+        .or_insert_with(|| elf::map_file(path).and_then(read_dynamic).map(Arc::new))
+        .clone()
+}
+
+fn read_dynamic(file: &'static [u8]) -> Option<Dynamic> {
+    const SHT_SYMTAB: u32 = 2;
+    const SHT_RELA: u32 = 4;
+    const SHT_REL: u32 = 9;
+    const SHT_DYNSYM: u32 = 11;
+    const STT_GNU_IFUNC: u8 = 10;
+
+    let elf = elf::parse(file)?;
+    let read = |bytes: &[u8]| {
+        let mut value = [0u8; 8];
+        value[..bytes.len()].copy_from_slice(bytes);
+        u64::from_le_bytes(value)
+    };
+
+    // Returns the symbols of a symbol table as `(name, info, shndx, value)`.
+    // Elf64_Sym: name, info, other, shndx, value, size.
+    // Elf32_Sym: name, value, size, info, other, shndx.
+    let symbols = |table: &elf::Section| {
+        let names = elf
+            .sections
+            .get(table.link as usize)
+            .map_or(&[][..], |names| names.data);
+        let name = move |offset: u64| {
+            names
+                .get(offset as usize..)
+                .and_then(|name| std::ffi::CStr::from_bytes_until_nul(name).ok())
+                .map_or(&[][..], |name| name.to_bytes())
+        };
+        let entry_size = if elf.is_64 { 24 } else { 16 };
+        table.data.chunks_exact(entry_size).map(move |symbol| {
+            if elf.is_64 {
+                (
+                    name(read(&symbol[..4])),
+                    symbol[4],
+                    read(&symbol[6..8]),
+                    read(&symbol[8..16]),
+                )
+            } else {
+                (
+                    name(read(&symbol[..4])),
+                    symbol[12],
+                    read(&symbol[14..16]),
+                    read(&symbol[4..8]),
+                )
+            }
+        })
+    };
+
+    let ranges = |is_wanted: fn(&[u8]) -> bool| {
+        elf.sections
+            .iter()
+            .filter(|section| is_wanted(section.name))
+            .map(|section| (section.addr, section.addr + section.data.len() as u64))
+            .collect::<Vec<_>>()
+    };
+    let plt = ranges(|name| name.starts_with(b".plt") || name == b".iplt");
+    let got = ranges(|name| name == b".got" || name == b".got.plt");
+
+    let mut slots = Vec::new();
+    for section in &elf.sections {
+        if section.kind != SHT_RELA && section.kind != SHT_REL {
+            continue;
+        }
+        let names: Vec<&'static [u8]> = match elf.sections.get(section.link as usize) {
+            Some(table) if table.kind == SHT_DYNSYM => {
+                symbols(table).map(|(name, ..)| name).collect()
+            }
+            _ => Vec::new(),
+        };
+
+        // Elf64_Rela: offset, info (symbol in the upper 32 bits), addend.
+        // Elf32_Rel: offset, info (symbol in the upper 24 bits).
+        let entry_size = match (elf.is_64, section.kind) {
+            (true, SHT_RELA) => 24,
+            (true, _) => 16,
+            (false, SHT_RELA) => 12,
+            (false, _) => 8,
+        };
+        for relocation in section.data.chunks_exact(entry_size) {
+            let (offset, symbol) = if elf.is_64 {
+                (read(&relocation[..8]), read(&relocation[8..16]) >> 32)
+            } else {
+                (read(&relocation[..4]), read(&relocation[4..8]) >> 8)
+            };
+            let name = names.get(symbol as usize).copied().unwrap_or(&[]);
+            slots.push((offset, name));
+        }
+    }
+    slots.sort_unstable();
+
+    let mut ifuncs: Vec<(u64, &'static [u8])> = elf
+        .sections
+        .iter()
+        .filter(|section| section.kind == SHT_SYMTAB || section.kind == SHT_DYNSYM)
+        .flat_map(symbols)
+        .filter(|&(_, info, shndx, _)| info & 0xF == STT_GNU_IFUNC && shndx != 0)
+        .map(|(name, _, _, value)| {
+            // Thumb functions have the lowest bit of their address set.
+            let value = if cfg!(target_arch = "arm") {
+                value & !1
+            } else {
+                value
+            };
+            (value, name)
+        })
+        .collect();
+    ifuncs.sort_unstable();
+    ifuncs.dedup_by_key(|&mut (value, _)| value);
+
+    Some(Dynamic {
+        plt,
+        got,
+        slots,
+        ifuncs,
+    })
+}
+
+#[cfg(all(test, target_arch = "x86_64"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plt_slot_of_x86_64_stubs() {
+        // jmp [rip+0x10]; push 0; jmp .plt
+        let lazy: [u8; 16] = [
+            0xFF, 0x25, 0x10, 0, 0, 0, 0x68, 0, 0, 0, 0, 0xE9, 0, 0, 0, 0,
+        ];
+        let addr = lazy.as_ptr() as usize;
+        assert_eq!(unsafe { plt_slot(addr) }, Some(addr + 6 + 0x10));
+
+        // endbr64; bnd jmp [rip-0x20]: `.plt.sec`
+        let sec: [u8; 16] = [
+            0xF3, 0x0F, 0x1E, 0xFA, 0xF2, 0xFF, 0x25, 0xE0, 0xFF, 0xFF, 0xFF, 0x0F, 0x1F, 0x44, 0,
+            0,
+        ];
+        let addr = sec.as_ptr() as usize;
+        assert_eq!(unsafe { plt_slot(addr) }, Some(addr + 11 - 0x20));
+
+        // endbr64; push 0; bnd jmp .plt: the lazy half of an IBT PLT entry
+        let ibt: [u8; 16] = [
+            0xF3, 0x0F, 0x1E, 0xFA, 0x68, 0, 0, 0, 0, 0xF2, 0xE9, 0, 0, 0, 0, 0x90,
+        ];
+        assert_eq!(unsafe { plt_slot(ibt.as_ptr() as usize) }, None);
+    }
+}
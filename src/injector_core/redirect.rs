@@ -0,0 +1,494 @@
+//! Redirections of the calls of a function that leave its code untouched, by rewriting the
+//! GOT entries referring to it (`PatchStrategy::Got`), the call instructions calling it
+//! (`PatchStrategy::CallSites`), the COM vtable slot or the Objective-C method holding it, and
+//! hooks of an external engine (`PatchStrategy::External`), which share their lifecycle.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use crate::injector_core::diagnostics;
+
+/// How the calls of a function are redirected.
+pub(crate) enum RedirectMode {
+    /// Rewrites the GOT entries referring to the function.
+    #[cfg(target_os = "linux")]
+    Got,
+    /// Rewrites the instructions calling the function.
+    #[cfg(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec"
+    ))]
+    CallSites(crate::injector_core::call_site::CallSiteScope),
+    /// Rewrites the vtable slot at the address, holding the function.
+    #[cfg(target_os = "windows")]
+    VtableSlot(usize),
+    /// Replaces the implementation of the Objective-C method at the address, the function.
+    #[cfg(target_os = "macos")]
+    ObjcMethod(usize),
+    /// Hooks the function with the registered external engine.
+    #[cfg(feature = "external-engine")]
+    External,
+}
+
+/// A location rewritten by a redirection.
+pub(crate) enum Site {
+    /// A GOT entry, holding the address of the function.
+    #[cfg(target_os = "linux")]
+    GotEntry {
+        addr: usize,
+        original: usize,
+        redirected: usize,
+        /// Whether the entry lies in a page the dynamic loader made read-only.
+        read_only: bool,
+    },
+    /// A call instruction.
+    #[cfg(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec"
+    ))]
+    Call {
+        addr: usize,
+        original: Vec<u8>,
+        redirected: Vec<u8>,
+    },
+    /// A slot of a COM vtable, holding the address of the function.
+    #[cfg(target_os = "windows")]
+    VtableSlot {
+        addr: usize,
+        original: usize,
+        redirected: usize,
+    },
+    /// An Objective-C method, `Method`, whose implementation is the function.
+    #[cfg(target_os = "macos")]
+    ObjcMethod {
+        method: usize,
+        original: usize,
+        redirected: usize,
+    },
+    /// A function hooked by an external engine.
+    #[cfg(feature = "external-engine")]
+    Hook(crate::injector_core::hook_engine::Hook),
+}
+
+impl Site {
+    fn addr(&self) -> usize {
+        match self {
+            #[cfg(target_os = "linux")]
+            Site::GotEntry { addr, .. } => *addr,
+            #[cfg(any(
+                target_arch = "x86_64",
+                target_arch = "aarch64",
+                target_arch = "arm64ec"
+            ))]
+            Site::Call { addr, .. } => *addr,
+            #[cfg(target_os = "windows")]
+            Site::VtableSlot { addr, .. } => *addr,
+            #[cfg(target_os = "macos")]
+            Site::ObjcMethod { method, .. } => *method,
+            #[cfg(feature = "external-engine")]
+            Site::Hook(hook) => hook.func_addr,
+            #[cfg(not(any(
+                target_os = "linux",
+                target_arch = "x86_64",
+                target_arch = "aarch64",
+                target_arch = "arm64ec"
+            )))]
+            #[allow(unreachable_patterns)]
+            _ => unreachable!(),
+        }
+    }
+
+    /// Takes over the original contents of `other`, a redirection of the same site installed
+    /// before this one and removed first, so that removing this one restores them.
+    fn take_original(&mut self, other: Site) {
+        match (self, other) {
+            #[cfg(target_os = "linux")]
+            (
+                Site::GotEntry { original, .. },
+                Site::GotEntry {
+                    original: other, ..
+                },
+            ) => {
+                *original = other;
+            }
+            #[cfg(any(
+                target_arch = "x86_64",
+                target_arch = "aarch64",
+                target_arch = "arm64ec"
+            ))]
+            (
+                Site::Call { original, .. },
+                Site::Call {
+                    original: other, ..
+                },
+            ) => {
+                *original = other;
+            }
+            #[cfg(target_os = "windows")]
+            (
+                Site::VtableSlot { original, .. },
+                Site::VtableSlot {
+                    original: other, ..
+                },
+            ) => {
+                *original = other;
+            }
+            #[cfg(target_os = "macos")]
+            (
+                Site::ObjcMethod { original, .. },
+                Site::ObjcMethod {
+                    original: other, ..
+                },
+            ) => {
+                *original = other;
+            }
+            // Engines hook a function once, so the later hook reverts the only one.
+            #[cfg(feature = "external-engine")]
+            (Site::Hook(_), Site::Hook(_)) => {}
+            #[allow(unreachable_patterns)]
+            _ => unreachable!("later redirections of a site are of the same kind"),
+        }
+    }
+
+    /// Returns whether the site holds the address of the function, rather than code calling it.
+    #[cfg(any(target_os = "linux", target_os = "windows", target_os = "macos"))]
+    fn holds_address(&self) -> bool {
+        match self {
+            #[cfg(target_os = "linux")]
+            Site::GotEntry { .. } => true,
+            #[cfg(target_os = "windows")]
+            Site::VtableSlot { .. } => true,
+            #[cfg(target_os = "macos")]
+            Site::ObjcMethod { .. } => true,
+            #[allow(unreachable_patterns)]
+            _ => false,
+        }
+    }
+
+    /// Writes the redirected or the original contents of the site.
+    unsafe fn write(&self, redirected: bool) {
+        match self {
+            #[cfg(target_os = "linux")]
+            Site::GotEntry {
+                addr,
+                original,
+                redirected: value,
+                read_only,
+            } => crate::injector_core::got::write_entry(
+                *addr,
+                if redirected { *value } else { *original },
+                *read_only,
+            ),
+            #[cfg(any(
+                target_arch = "x86_64",
+                target_arch = "aarch64",
+                target_arch = "arm64ec"
+            ))]
+            Site::Call {
+                addr,
+                original,
+                redirected: code,
+            } => crate::injector_core::common::patch_function(
+                *addr as *mut u8,
+                if redirected { code } else { original },
+            ),
+            #[cfg(target_os = "windows")]
+            Site::VtableSlot {
+                addr,
+                original,
+                redirected: value,
+            } => crate::injector_core::winapi::write_vtable_slot(
+                *addr,
+                if redirected { *value } else { *original },
+            ),
+            #[cfg(target_os = "macos")]
+            Site::ObjcMethod {
+                method,
+                original,
+                redirected: imp,
+            } => crate::injector_core::objc::set_implementation(
+                *method,
+                if redirected { *imp } else { *original },
+            ),
+            #[cfg(feature = "external-engine")]
+            Site::Hook(hook) => {
+                if redirected {
+                    hook.install_or_panic()
+                } else {
+                    hook.revert()
+                }
+            }
+            #[cfg(not(any(
+                target_os = "linux",
+                target_arch = "x86_64",
+                target_arch = "aarch64",
+                target_arch = "arm64ec"
+            )))]
+            #[allow(unreachable_patterns)]
+            _ => unreachable!(),
+        }
+    }
+
+    /// Returns whether the site holds its redirected contents.
+    unsafe fn is_redirected(&self) -> bool {
+        match self {
+            #[cfg(target_os = "linux")]
+            Site::GotEntry {
+                addr,
+                redirected: value,
+                ..
+            } => crate::injector_core::got::read_entry(*addr) == *value,
+            #[cfg(any(
+                target_arch = "x86_64",
+                target_arch = "aarch64",
+                target_arch = "arm64ec"
+            ))]
+            Site::Call {
+                addr,
+                redirected: code,
+                ..
+            } => crate::injector_core::common::read_bytes(*addr as *const u8, code.len()) == *code,
+            #[cfg(target_os = "windows")]
+            Site::VtableSlot {
+                addr,
+                redirected: value,
+                ..
+            } => crate::injector_core::winapi::read_vtable_slot(*addr) == *value,
+            #[cfg(target_os = "macos")]
+            Site::ObjcMethod {
+                method,
+                redirected: imp,
+                ..
+            } => crate::injector_core::objc::implementation(*method) == *imp,
+            #[cfg(feature = "external-engine")]
+            Site::Hook(hook) => hook.is_installed(),
+            #[cfg(not(any(
+                target_os = "linux",
+                target_arch = "x86_64",
+                target_arch = "aarch64",
+                target_arch = "arm64ec"
+            )))]
+            #[allow(unreachable_patterns)]
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// A live redirection, in installation order.
+struct LiveRedirect {
+    id: u64,
+    func_addr: usize,
+    target: usize,
+    sites: Vec<Site>,
+    paused: bool,
+}
+
+/// Every live `Redirect` in the process. Also serializes the writes to the sites.
+static LIVE_REDIRECTS: Mutex<Vec<LiveRedirect>> = Mutex::new(Vec::new());
+
+static NEXT_REDIRECT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Returns `(func_addr, target)` for every live `Redirect` in the process.
+pub(crate) fn live_redirects() -> Vec<(usize, usize)> {
+    LIVE_REDIRECTS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .map(|redirect| (redirect.func_addr, redirect.target))
+        .collect()
+}
+
+/// Returns whether a live `Redirect` that is not paused redirects the calls of `func_addr`.
+pub(crate) fn is_redirected(func_addr: usize) -> bool {
+    LIVE_REDIRECTS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .any(|redirect| redirect.func_addr == func_addr && !redirect.paused)
+}
+
+/// Returns the targets live redirections of the function at `func_addr` point to, oldest first.
+#[cfg(target_os = "linux")]
+pub(crate) fn targets_of(func_addr: usize) -> Vec<usize> {
+    LIVE_REDIRECTS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .filter(|redirect| redirect.func_addr == func_addr)
+        .map(|redirect| redirect.target)
+        .collect()
+}
+
+/// Returns the addresses of the sites live redirections of the function at `func_addr` rewrote.
+#[cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec"
+))]
+pub(crate) fn redirected_sites(func_addr: usize) -> Vec<usize> {
+    LIVE_REDIRECTS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .filter(|redirect| redirect.func_addr == func_addr)
+        .flat_map(|redirect| redirect.sites.iter().map(Site::addr))
+        .collect()
+}
+
+/// Returns the function whose GOT entries, vtable slot or Objective-C method a live redirection
+/// points at `target`, as reading the address of a redirected function from them gives the
+/// address of its fake.
+#[cfg(any(target_os = "linux", target_os = "windows", target_os = "macos"))]
+pub(crate) fn redirected_function(target: usize) -> Option<usize> {
+    LIVE_REDIRECTS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .rev()
+        .find(|redirect| {
+            redirect.target == target && redirect.sites.iter().any(Site::holds_address)
+        })
+        .map(|redirect| redirect.func_addr)
+}
+
+/// Returns whether an external engine hooked the function at `func_addr`, whose code then
+/// starts with the jump of the engine rather than a thunk.
+#[cfg(feature = "external-engine")]
+pub(crate) fn is_hooked(func_addr: usize) -> bool {
+    LIVE_REDIRECTS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .filter(|redirect| redirect.func_addr == func_addr)
+        .any(|redirect| {
+            redirect
+                .sites
+                .iter()
+                .any(|site| matches!(site, Site::Hook(_)))
+        })
+}
+
+/// Pauses or resumes the redirection with `id`: pausing writes back the original contents of
+/// its sites, resuming redirects them again. Does nothing once the redirection is dropped.
+pub(crate) fn set_redirect_paused(id: u64, paused: bool) {
+    let mut live = LIVE_REDIRECTS.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(redirect) = live.iter_mut().find(|redirect| redirect.id == id) else {
+        return;
+    };
+
+    if redirect.paused != paused {
+        for site in &redirect.sites {
+            unsafe { site.write(!paused) };
+        }
+        redirect.paused = paused;
+    }
+}
+
+/// Redirects the calls of a function to a fake until dropped, then writes back the original
+/// contents of the sites. Frees its JIT memory, holding the fake or veneers, once the sites are
+/// restored.
+pub(crate) struct Redirect {
+    id: u64,
+    func_addr: usize,
+    jit: Vec<(*mut u8, usize)>,
+}
+
+impl Redirect {
+    /// Writes the redirected contents of `sites`, redirecting the function at `func_addr` to
+    /// `target`.
+    pub(crate) fn install(
+        func_addr: usize,
+        target: usize,
+        sites: Vec<Site>,
+        jit: Vec<(*mut u8, usize)>,
+        kind: &str,
+    ) -> Self {
+        let mut live = LIVE_REDIRECTS.lock().unwrap_or_else(|e| e.into_inner());
+        for site in &sites {
+            unsafe { site.write(true) };
+        }
+
+        if diagnostics::log_enabled() {
+            let addrs: Vec<String> = sites
+                .iter()
+                .map(|site| format!("{:#x}", site.addr()))
+                .collect();
+            eprintln!(
+                "[injectorpp] {} redirected: func={:#x} target={:#x} sites=[{}]",
+                kind,
+                func_addr,
+                target,
+                addrs.join(" ")
+            );
+        }
+
+        let id = NEXT_REDIRECT_ID.fetch_add(1, Ordering::Relaxed);
+        live.push(LiveRedirect {
+            id,
+            func_addr,
+            target,
+            sites,
+            paused: false,
+        });
+
+        Self { id, func_addr, jit }
+    }
+
+    /// Returns the id passed to `set_redirect_paused()` to pause this redirection.
+    pub(crate) fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Returns the address of the redirected function.
+    pub(crate) fn func_addr(&self) -> usize {
+        self.func_addr
+    }
+
+    /// Returns whether the sites of this redirection still hold their redirected contents.
+    pub(crate) fn is_effective(&self) -> bool {
+        let live = LIVE_REDIRECTS.lock().unwrap_or_else(|e| e.into_inner());
+        live.iter()
+            .find(|redirect| redirect.id == self.id)
+            .is_some_and(|redirect| {
+                !redirect.paused
+                    && redirect
+                        .sites
+                        .iter()
+                        .all(|site| unsafe { site.is_redirected() })
+            })
+    }
+}
+
+impl Drop for Redirect {
+    fn drop(&mut self) {
+        {
+            let mut live = LIVE_REDIRECTS.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(index) = live.iter().position(|redirect| redirect.id == self.id) {
+                let redirect = live.remove(index);
+                for site in redirect.sites {
+                    // A later redirection of the same site restores what this one found.
+                    let later = live[index..]
+                        .iter_mut()
+                        .flat_map(|later| later.sites.iter_mut())
+                        .find(|later| later.addr() == site.addr());
+                    match later {
+                        Some(later) => later.take_original(site),
+                        None if !redirect.paused => unsafe { site.write(false) },
+                        None => {}
+                    }
+                }
+            }
+        }
+        crate::injector_core::common::apply_deferred_patches();
+
+        for &(jit_memory, jit_size) in &self.jit {
+            unsafe { crate::injector_core::common::free_jit_memory(jit_memory, jit_size) };
+        }
+
+        if diagnostics::log_enabled() {
+            eprintln!("[injectorpp] calls restored: func={:#x}", self.func_addr);
+        }
+    }
+}
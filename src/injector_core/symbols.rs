@@ -0,0 +1,453 @@
+//! Best-effort symbol lookup for diagnostics and introspection.
+//!
+//! What is known of the symbol at an address is looked up once, and kept for the lifetime of
+//! the process, as patches and diagnostics ask for the same functions over and over.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// The cached metadata of the symbol at an address, each part looked up on first use.
+#[derive(Default)]
+struct Symbol {
+    /// The name of the symbol and the path of the module holding it.
+    loader: OnceLock<(Option<String>, Option<String>)>,
+    /// The size of the function starting at the address, with the limit it was searched
+    /// within.
+    size: Mutex<Option<(usize, Option<usize>)>>,
+}
+
+/// Returns the cached metadata of the symbol at `addr`.
+fn symbol(addr: usize) -> Arc<Symbol> {
+    static SYMBOLS: Mutex<Option<HashMap<usize, Arc<Symbol>>>> = Mutex::new(None);
+
+    let mut symbols = SYMBOLS.lock().unwrap_or_else(|e| e.into_inner());
+    symbols
+        .get_or_insert_with(HashMap::new)
+        .entry(addr)
+        .or_default()
+        .clone()
+}
+
+/// Returns the name of the symbol containing `addr`, if the dynamic loader knows it.
+///
+/// Only symbols exported to the dynamic symbol table can be resolved this way, so
+/// functions internal to the test binary commonly return `None`.
+pub(crate) fn symbol_name(addr: usize) -> Option<String> {
+    symbol(addr)
+        .loader
+        .get_or_init(|| lookup_loader(addr))
+        .0
+        .clone()
+}
+
+/// Returns the path of the executable or shared library holding `addr`.
+pub(crate) fn module_name(addr: usize) -> Option<String> {
+    symbol(addr)
+        .loader
+        .get_or_init(|| lookup_loader(addr))
+        .1
+        .clone()
+}
+
+/// Returns the size of the function starting at `addr`, found by `find` when it was not, or
+/// only within a smaller limit than `limit`.
+pub(crate) fn function_size(
+    addr: usize,
+    limit: usize,
+    find: impl FnOnce() -> Option<usize>,
+) -> Option<usize> {
+    let symbol = symbol(addr);
+    let mut size = symbol.size.lock().unwrap_or_else(|e| e.into_inner());
+    match *size {
+        Some((searched, found)) if found.is_some() || searched >= limit => found,
+        _ => {
+            let found = find();
+            *size = Some((limit, found));
+            found
+        }
+    }
+}
+
+/// Records `size` as the size of the function starting at `addr`, as known from elsewhere than
+/// the tables it is otherwise found in.
+pub(crate) fn record_function_size(addr: usize, size: usize) {
+    let symbol = symbol(addr);
+    *symbol.size.lock().unwrap_or_else(|e| e.into_inner()) = Some((usize::MAX, Some(size)));
+}
+
+/// Returns the address `offset` bytes into the loaded module `module`, or into the main
+/// program when `None`, when `len` bytes from there are executable code of the module.
+#[cfg(any(target_os = "linux", target_os = "freebsd", target_os = "netbsd"))]
+pub(crate) fn code_address(module: Option<&str>, offset: usize, len: usize) -> Option<usize> {
+    crate::injector_core::elf::code_address(module, offset, len)
+}
+
+#[cfg(target_os = "windows")]
+pub(crate) fn code_address(module: Option<&str>, offset: usize, len: usize) -> Option<usize> {
+    crate::injector_core::winapi::code_address(module, offset, len)
+}
+
+/// Offsets are from the Mach-O header of the image, which is where its `__TEXT` segment
+/// starts.
+#[cfg(target_os = "macos")]
+pub(crate) fn code_address(module: Option<&str>, offset: usize, len: usize) -> Option<usize> {
+    use crate::injector_core::macosapi::*;
+
+    const MH_MAGIC_64: u32 = 0xfeed_facf;
+    const LC_SEGMENT_64: u32 = 0x19;
+    const VM_PROT_EXECUTE: u32 = 0x4;
+
+    // The main program is the first image.
+    let image = (0..unsafe { _dyld_image_count() }).find(|&image| {
+        let Some(module) = module else {
+            return image == 0;
+        };
+        let name = unsafe { _dyld_get_image_name(image) };
+        if name.is_null() {
+            return false;
+        }
+        let name = unsafe { std::ffi::CStr::from_ptr(name) }.to_string_lossy();
+        name == module
+            || std::path::Path::new(&*name)
+                .file_name()
+                .is_some_and(|file| file == module)
+    })?;
+
+    let header = unsafe { _dyld_get_image_header(image) } as usize;
+    let slide = unsafe { _dyld_get_image_vmaddr_slide(image) } as usize;
+    let read_u32 = |addr: usize| unsafe { (addr as *const u32).read_unaligned() };
+    let read_u64 = |addr: usize| unsafe { (addr as *const u64).read_unaligned() };
+    if header == 0 || read_u32(header) != MH_MAGIC_64 {
+        return None;
+    }
+
+    let start = header.checked_add(offset)?;
+    let end = start.checked_add(len.max(1))?;
+    let mut command = header + 32;
+    for _ in 0..read_u32(header + 16) {
+        // segment_command_64: cmd, cmdsize, segname, vmaddr, vmsize, ..., initprot at 60.
+        if read_u32(command) == LC_SEGMENT_64 {
+            let segment = slide.wrapping_add(read_u64(command + 24) as usize);
+            let size = read_u64(command + 32) as usize;
+            if read_u32(command + 60) & VM_PROT_EXECUTE != 0
+                && segment <= start
+                && end <= segment + size
+            {
+                return Some(start);
+            }
+        }
+        command += read_u32(command + 4) as usize;
+    }
+    None
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "windows"
+)))]
+pub(crate) fn code_address(_module: Option<&str>, _offset: usize, _len: usize) -> Option<usize> {
+    None
+}
+
+/// Returns the address of the function exported as `symbol` by `module`, loaded if it is not
+/// yet, or by any loaded module when `None`.
+#[cfg(any(
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "netbsd"
+))]
+pub(crate) fn exported_address(module: Option<&str>, symbol: &str) -> Option<usize> {
+    let symbol = std::ffi::CString::new(symbol).ok()?;
+    let handle = match module {
+        // The handle is never closed, so the module stays loaded while it is patched.
+        Some(module) => {
+            let module = std::ffi::CString::new(module).ok()?;
+            let handle = unsafe { libc::dlopen(module.as_ptr(), libc::RTLD_LAZY) };
+            if handle.is_null() {
+                return None;
+            }
+            handle
+        }
+        None => libc::RTLD_DEFAULT,
+    };
+
+    let addr = unsafe { libc::dlsym(handle, symbol.as_ptr()) };
+    (!addr.is_null()).then_some(addr as usize)
+}
+
+#[cfg(target_os = "windows")]
+pub(crate) fn exported_address(module: Option<&str>, symbol: &str) -> Option<usize> {
+    crate::injector_core::winapi::exported_address(module, symbol)
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "windows"
+)))]
+pub(crate) fn exported_address(_module: Option<&str>, _symbol: &str) -> Option<usize> {
+    None
+}
+
+/// Returns the demangled paths and addresses of the Rust functions whose path matches
+/// `pattern`, from the symbol tables of the loaded objects, sorted by address. Returns `None`
+/// where symbol tables are not read.
+#[cfg(target_os = "linux")]
+pub(crate) fn functions_matching(pattern: &str) -> Option<Vec<(String, usize)>> {
+    use crate::injector_core::demangle::matches_pattern;
+
+    Some(functions_where(Mangling::Rust, |path| {
+        matches_pattern(pattern, path)
+    }))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn functions_matching(_pattern: &str) -> Option<Vec<(String, usize)>> {
+    None
+}
+
+/// Returns the demangled names and addresses of the C++ functions whose name matches
+/// `pattern`, from the symbol tables of the loaded objects, sorted by address. Names are
+/// matched with their parameters when `pattern` has some, and without them otherwise. Returns
+/// `None` where symbol tables are not read.
+#[cfg(target_os = "linux")]
+pub(crate) fn cxx_functions_matching(pattern: &str) -> Option<Vec<(String, usize)>> {
+    use crate::injector_core::{cxx_demangle::without_params, demangle::matches_pattern};
+
+    let with_params = pattern.contains('(');
+    Some(functions_where(Mangling::Cxx, |name| {
+        let name = if with_params {
+            name
+        } else {
+            without_params(name)
+        };
+        matches_pattern(pattern, name)
+    }))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn cxx_functions_matching(_pattern: &str) -> Option<Vec<(String, usize)>> {
+    None
+}
+
+/// Returns the demangled names and addresses of the C++ functions named `name`, with their
+/// parameters, from the symbol tables of the loaded objects, sorted by address. Returns `None`
+/// where symbol tables are not read.
+#[cfg(target_os = "linux")]
+pub(crate) fn cxx_functions_named(name: &str) -> Option<Vec<(String, usize)>> {
+    Some(functions_where(Mangling::Cxx, |other| other == name))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn cxx_functions_named(_name: &str) -> Option<Vec<(String, usize)>> {
+    None
+}
+
+/// Returns the demangled path of the Rust function at `addr` and the addresses of the
+/// functions sharing it, `addr` among them, such as the instances of a generic function.
+/// Returns `None` when the function is not in a symbol table, or where symbol tables are not
+/// read.
+#[cfg(target_os = "linux")]
+pub(crate) fn functions_sharing_path(addr: usize) -> Option<(String, Vec<usize>)> {
+    let (object, bias) = crate::injector_core::elf::object_containing(addr)?;
+    let offset = addr.wrapping_sub(bias) as u64;
+    let path = demangled_functions(&object, Mangling::Rust)?
+        .iter()
+        .find(|&&(_, addr)| addr == offset)?
+        .0
+        .clone();
+
+    let found = functions_where(Mangling::Rust, |other| other == path);
+    let addrs = found.into_iter().map(|(_, addr)| addr).collect();
+    Some((path, addrs))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn functions_sharing_path(_addr: usize) -> Option<(String, Vec<usize>)> {
+    None
+}
+
+/// Returns the demangled name of the function containing `addr`, and the offset of `addr` from
+/// its start, from the symbol table of its object. Returns `None` when no function symbol
+/// covers it, or where symbol tables are not read.
+#[cfg(target_os = "linux")]
+pub(crate) fn function_containing(addr: usize) -> Option<(String, usize)> {
+    use crate::injector_core::{cxx_demangle, demangle, elf};
+
+    let (object, bias) = elf::object_containing(addr)?;
+    let offset = addr.wrapping_sub(bias) as u64;
+    let elf = elf::parse(elf::map_file(&object)?)?;
+    let symbol = elf::function_symbols(&elf)?
+        .into_iter()
+        .find(|symbol| symbol.addr <= offset && offset < symbol.addr + symbol.size.max(1))?;
+
+    let name = demangle::demangle(symbol.name)
+        .or_else(|| cxx_demangle::demangle(std::str::from_utf8(symbol.name).ok()?))
+        .unwrap_or_else(|| String::from_utf8_lossy(symbol.name).into_owned());
+    Some((name, (offset - symbol.addr) as usize))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn function_containing(_addr: usize) -> Option<(String, usize)> {
+    None
+}
+
+/// The mangling scheme of the functions looked up in symbol tables.
+#[cfg(target_os = "linux")]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Mangling {
+    Rust,
+    Cxx,
+}
+
+/// Returns the demangled paths and addresses of the functions of the loaded objects mangled
+/// with `mangling` whose path satisfies `filter`, sorted by address.
+#[cfg(target_os = "linux")]
+fn functions_where(mangling: Mangling, filter: impl Fn(&str) -> bool) -> Vec<(String, usize)> {
+    let mut found = Vec::new();
+    for object in crate::injector_core::elf::loaded_objects() {
+        let Some(functions) = demangled_functions(&object.path, mangling) else {
+            continue;
+        };
+        found.extend(
+            functions
+                .iter()
+                .filter(|(path, _)| filter(path))
+                .map(|(path, addr)| (path.clone(), object.bias.wrapping_add(*addr as usize))),
+        );
+    }
+
+    // Aliases of a function, such as identical functions folded together, are one function.
+    found.sort_by_key(|&(_, addr)| addr);
+    found.dedup_by_key(|&mut (_, addr)| addr);
+    found
+}
+
+/// Returns the demangled paths and addresses of the functions mangled with `mangling` in the
+/// symbol table of the ELF object at `path`. Read once per object and scheme, and kept for the
+/// lifetime of the process.
+#[cfg(target_os = "linux")]
+fn demangled_functions(path: &str, mangling: Mangling) -> Option<Arc<Vec<(String, u64)>>> {
+    use crate::injector_core::{cxx_demangle, demangle, elf};
+
+    type Functions = Option<Arc<Vec<(String, u64)>>>;
+    static OBJECTS: Mutex<Option<HashMap<(String, Mangling), Functions>>> = Mutex::new(None);
+
+    let mut objects = OBJECTS.lock().unwrap_or_else(|e| e.into_inner());
+    objects
+        .get_or_insert_with(HashMap::new)
+        .entry((path.to_string(), mangling))
+        .or_insert_with(|| {
+            let elf = elf::parse(elf::map_file(path)?)?;
+            let functions = elf::function_symbols(&elf)?
+                .into_iter()
+                .filter_map(|symbol| {
+                    let name = match mangling {
+                        Mangling::Rust => demangle::demangle(symbol.name)?,
+                        Mangling::Cxx => {
+                            cxx_demangle::demangle(std::str::from_utf8(symbol.name).ok()?)?
+                        }
+                    };
+                    Some((name, symbol.addr))
+                })
+                .collect();
+            Some(Arc::new(functions))
+        })
+        .clone()
+}
+
+/// Returns the name of the symbol containing `addr` and the path of its module, as the
+/// dynamic loader knows them.
+#[cfg(any(
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "netbsd"
+))]
+fn lookup_loader(addr: usize) -> (Option<String>, Option<String>) {
+    let mut info: libc::Dl_info = unsafe { std::mem::zeroed() };
+    let found = unsafe { libc::dladdr(addr as *const libc::c_void, &mut info) };
+    if found == 0 {
+        return (None, None);
+    }
+
+    let string = |name: *const libc::c_char| {
+        (!name.is_null()).then(|| {
+            unsafe { std::ffi::CStr::from_ptr(name) }
+                .to_string_lossy()
+                .into_owned()
+        })
+    };
+    (string(info.dli_sname), string(info.dli_fname))
+}
+
+/// Returns the path of the module holding `addr`. Symbol names are not looked up.
+#[cfg(target_os = "windows")]
+fn lookup_loader(addr: usize) -> (Option<String>, Option<String>) {
+    (None, crate::injector_core::winapi::module_path(addr))
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "windows"
+)))]
+fn lookup_loader(_addr: usize) -> (Option<String>, Option<String>) {
+    (None, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_function_size_should_be_found_once_per_limit() {
+        let addr = test_function_size_should_be_found_once_per_limit as fn() as usize;
+        let searches = std::cell::Cell::new(0);
+        let find = |found| {
+            searches.set(searches.get() + 1);
+            found
+        };
+
+        assert_eq!(function_size(addr, 8, || find(None)), None);
+        assert_eq!(function_size(addr, 4, || find(Some(1))), None);
+        assert_eq!(searches.get(), 1);
+
+        assert_eq!(function_size(addr, 16, || find(Some(12))), Some(12));
+        assert_eq!(function_size(addr, 32, || find(None)), Some(12));
+        assert_eq!(searches.get(), 2);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_functions_matching_should_find_private_functions_by_path() {
+        let addr = test_functions_matching_should_find_private_functions_by_path as fn() as usize;
+        let found =
+            functions_matching("injectorpp::*::symbols::tests::test_functions_matching_*").unwrap();
+        assert_eq!(
+            found,
+            [(
+                "injectorpp::injector_core::symbols::tests::test_functions_matching_should_find_private_functions_by_path"
+                    .to_string(),
+                addr
+            )]
+        );
+    }
+
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "windows"))]
+    fn test_module_name_should_be_the_test_binary() {
+        let addr = test_module_name_should_be_the_test_binary as fn() as usize;
+        let module = module_name(addr).unwrap();
+        let exe = std::env::current_exe().unwrap();
+        assert!(module.ends_with(exe.file_name().unwrap().to_str().unwrap()));
+    }
+}
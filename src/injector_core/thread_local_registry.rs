@@ -1,22 +1,42 @@
-#![cfg(any(target_arch = "x86_64", target_arch = "aarch64", target_arch = "arm"))]
+#![cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec",
+    target_arch = "arm"
+))]
 
 use std::cell::Cell;
+use std::cell::RefCell;
 use std::cell::UnsafeCell;
 use std::collections::HashMap;
 use std::panic::AssertUnwindSafe;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
 use std::sync::Mutex;
 
 use crate::injector_core::common::*;
-
-#[cfg(target_os = "linux")]
+use crate::injector_core::diagnostics;
+use crate::injector_core::diagnostics::PatchMode;
+#[cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec",
+    target_arch = "arm"
+))]
+use crate::injector_core::function_size::check_patch_fits;
+
+#[cfg(any(target_os = "linux", target_os = "freebsd", target_os = "netbsd"))]
 use crate::injector_core::linuxapi::__clear_cache;
 
 #[cfg(target_os = "windows")]
 use crate::injector_core::winapi::*;
+#[cfg(target_arch = "x86_64")]
+use crate::injector_core::x86_64_insn;
 
-#[cfg(target_arch = "aarch64")]
+#[cfg(any(target_arch = "aarch64", target_arch = "arm64ec"))]
 use crate::injector_core::arm64_codegenerator::*;
-#[cfg(target_arch = "aarch64")]
+#[cfg(any(target_arch = "aarch64", target_arch = "arm64ec"))]
 use crate::injector_core::utils::*;
 
 thread_local! {
@@ -24,6 +44,132 @@ thread_local! {
     // Reentrancy guard: prevents infinite recursion when a patched function
     // (like memset) is called internally during our HashMap operations.
     static IN_TLS_OP: Cell<bool> = const { Cell::new(false) };
+    // Replacements registered by this thread for each method, oldest first. The last one
+    // not paused is the one in THREAD_REPLACEMENTS.
+    static THREAD_STACKS: RefCell<HashMap<usize, ReplacementStack>> = RefCell::new(HashMap::new());
+    // Guarded fakes running on this thread, innermost last, and their number. Entering a fake
+    // neither allocates nor copies memory, as the fake may replace `malloc` or `memcpy`.
+    static RUNNING_GUARDED: [Cell<usize>; MAX_RUNNING_GUARDED] =
+        const { [const { Cell::new(0) }; MAX_RUNNING_GUARDED] };
+    static RUNNING_GUARDED_COUNT: Cell<usize> = const { Cell::new(0) };
+    // Depth of injectorpp's own code running on this thread, see `enter_internal()`.
+    static INTERNAL_DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// How many guarded fakes a thread tracks at once. A fake entered deeper than that, which
+/// takes a fake calling itself, is not guarded.
+const MAX_RUNNING_GUARDED: usize = 16;
+
+/// Fakes whose reentrant calls reach the original function, see `guard_reentrancy()`, by
+/// address, with the number of registrations guarding each.
+static GUARDED_FAKES: std::sync::LazyLock<Mutex<HashMap<usize, usize>>> =
+    std::sync::LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Number of registrations guarding a fake, so fakes skip the lock when there are none.
+static GUARDED_REGISTRATIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// Where the calls of a replacement go while faults are injected, see `route_faults()`.
+#[derive(Clone)]
+pub(crate) struct FaultRoute {
+    /// Draws whether a call fails.
+    pub(crate) fails: std::sync::Arc<dyn Fn() -> bool + Send + Sync>,
+    /// The function the failing calls run.
+    pub(crate) failure: usize,
+    /// Whether the other calls run the original function rather than the replacement.
+    pub(crate) others_to_original: bool,
+}
+
+/// `(registration id, route)` entries of a replacement, latest last.
+type FaultRouteStack = Vec<(u64, FaultRoute)>;
+
+/// Fault routes by replacement address.
+static FAULT_ROUTES: std::sync::LazyLock<Mutex<HashMap<usize, FaultRouteStack>>> =
+    std::sync::LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Number of fault routes, so dispatching skips the lock when there are none.
+static FAULT_ROUTE_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Most calls a call log keeps; older ones are dropped first.
+const MAX_LOGGED_CALLS: usize = 1024;
+
+/// What is known of the latest calls of a replacement, oldest first.
+pub(crate) type CallLog<T> = std::sync::Arc<Mutex<std::collections::VecDeque<T>>>;
+
+/// `(registration id, log)` entries of a replacement, latest last.
+type CallLogStack<T> = Vec<(u64, CallLog<T>)>;
+
+/// Call logs by replacement address, see `CallLogs::start()`.
+pub(crate) struct CallLogs<T> {
+    logs: Mutex<Option<HashMap<usize, CallLogStack<T>>>>,
+    /// Number of logs, so calls skip the lock when there are none.
+    count: AtomicUsize,
+}
+
+/// The return addresses of the calls dispatched to replacements, see `log_callers()`.
+static CALLER_LOGS: CallLogs<usize> = CallLogs::new();
+
+/// The backtrace of a call of a fake, shared by the handles reading it.
+pub(crate) type CallBacktrace = std::sync::Arc<std::backtrace::Backtrace>;
+
+/// The backtraces of the calls of fakes, see `log_backtraces()`.
+static BACKTRACE_LOGS: CallLogs<CallBacktrace> = CallLogs::new();
+
+/// `(registration id, replacement, paused)` entries, oldest first.
+type ReplacementStack = Vec<(u64, usize, bool)>;
+
+static NEXT_REGISTRATION_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Pushes a replacement on the current thread's stack for `method_key` and makes it active.
+fn stack_push(method_key: usize, replacement: usize) -> u64 {
+    let id = NEXT_REGISTRATION_ID.fetch_add(1, Ordering::Relaxed);
+    stack_update(method_key, |stack| stack.push((id, replacement, false)));
+    id
+}
+
+/// Removes registration `id` from the current thread's stack for `method_key`, wherever it
+/// is, and makes the replacement left on top active again.
+fn stack_remove(method_key: usize, id: u64) {
+    stack_update(method_key, |stack| {
+        stack.retain(|&(entry, _, _)| entry != id)
+    });
+}
+
+/// Pauses or resumes registration `id` on the current thread. A paused replacement stays in
+/// the stack but is skipped, so the one below it, or the original function, runs instead.
+pub(crate) fn stack_set_paused(method_key: usize, id: u64, paused: bool) {
+    stack_update(method_key, |stack| {
+        for entry in stack.iter_mut().filter(|entry| entry.0 == id) {
+            entry.2 = paused;
+        }
+    });
+}
+
+/// Applies `update` to the current thread's stack for `method_key`, then makes the latest
+/// replacement not paused active.
+fn stack_update(method_key: usize, update: impl FnOnce(&mut ReplacementStack)) {
+    let _internal = enter_internal();
+    let top = THREAD_STACKS
+        .try_with(|stacks| {
+            let mut stacks = stacks.borrow_mut();
+            let stack = stacks.entry(method_key).or_default();
+            update(stack);
+            let top = stack
+                .iter()
+                .rev()
+                .find(|&&(_, _, paused)| !paused)
+                .map(|&(_, replacement, _)| replacement);
+            if stack.is_empty() {
+                stacks.remove(&method_key);
+            }
+            top
+        })
+        .ok()
+        .flatten();
+
+    match top {
+        Some(replacement) => tls_insert(method_key, replacement),
+        None => tls_remove(&method_key),
+    }
 }
 
 /// Read from thread-local replacements map with reentrancy protection.
@@ -93,9 +239,27 @@ static REGISTRY: std::sync::LazyLock<Mutex<HashMap<usize, MethodEntry>>> =
 
 /// A registration handle for a thread-local function replacement.
 /// When dropped, it unregisters the replacement and potentially restores the original function.
+///
+/// Registrations of the same function on one thread stack: the last one registered is in
+/// effect, and dropping it, in any order, brings back the latest one still registered.
 pub(crate) struct ThreadRegistration {
     method_key: usize,
+    /// Identifies this registration in the thread's replacement stack.
+    id: u64,
+    replacement: usize,
     extra_jit: Option<(*mut u8, usize)>,
+    /// False once the replacement was taken off the registering thread by `detach()`.
+    on_thread: bool,
+    /// Keeps the replacement guarded, see `guard_reentrancy()`. Dropped after the
+    /// replacement is removed.
+    reentrancy_guard: Option<ReentrancyGuard>,
+    /// Keeps the faults of the replacement injected, see `route_faults()`. Dropped after the
+    /// replacement is removed.
+    fault_route: Option<FaultRouteGuard>,
+    /// Keeps the callers of the replacement logged, see `log_callers()`.
+    caller_log: Option<CallLogGuard<usize>>,
+    /// Keeps the backtraces of the calls of the replacement logged, see `log_backtraces()`.
+    backtrace_log: Option<CallLogGuard<CallBacktrace>>,
 }
 
 // Safety: ThreadRegistration is intentionally !Send because it's tied to the creating thread's
@@ -103,11 +267,15 @@ pub(crate) struct ThreadRegistration {
 
 impl Drop for ThreadRegistration {
     fn drop(&mut self) {
+        let _internal = enter_internal();
+
         // Remove this thread's replacement from thread-local storage
-        tls_remove(&self.method_key);
+        if self.on_thread {
+            stack_remove(self.method_key, self.id);
+        }
 
         // Free extra JIT block (e.g., return-boolean code) if any.
-        // This is safe because tls_remove above already ensures no dispatcher
+        // This is safe because stack_remove above already ensures no dispatcher
         // will route to this block from the current thread.
         if let Some((ptr, _size)) = self.extra_jit {
             unsafe {
@@ -136,9 +304,134 @@ impl Drop for ThreadRegistration {
         if let Some(entry) = registry.get_mut(&self.method_key) {
             entry.ref_count = entry.ref_count.saturating_sub(1);
         }
+        drop(registry);
+
+        diagnostics::patch_restored(PatchMode::ThreadLocal, self.method_key);
     }
 }
 
+impl ThreadRegistration {
+    /// Removes the replacement from the registering thread, keeping the dispatcher installed.
+    /// Returns `(method_key, replacement_addr)` so the replacement can be entered with
+    /// `enter_replacements()` on any thread.
+    pub(crate) fn detach(&mut self) -> (usize, usize) {
+        stack_remove(self.method_key, self.id);
+        self.on_thread = false;
+
+        (self.method_key, self.replacement)
+    }
+
+    /// Keeps `guard` until the replacement is removed.
+    pub(crate) fn keep_reentrancy_guard(&mut self, guard: ReentrancyGuard) {
+        self.reentrancy_guard = Some(guard);
+    }
+
+    /// Keeps `route` until the replacement is removed.
+    pub(crate) fn keep_fault_route(&mut self, route: FaultRouteGuard) {
+        self.fault_route = Some(route);
+    }
+
+    /// Keeps `log` until the replacement is removed.
+    pub(crate) fn keep_caller_log(&mut self, log: CallLogGuard<usize>) {
+        self.caller_log = Some(log);
+    }
+
+    /// Returns the log of the callers of the replacement, if they are logged.
+    pub(crate) fn caller_log(&self) -> Option<CallLog<usize>> {
+        self.caller_log.as_ref().map(|guard| guard.log.clone())
+    }
+
+    /// Keeps `log` until the replacement is removed.
+    pub(crate) fn keep_backtrace_log(&mut self, log: CallLogGuard<CallBacktrace>) {
+        self.backtrace_log = Some(log);
+    }
+
+    /// Returns the log of the backtraces of the calls of the replacement, if they are logged.
+    pub(crate) fn backtrace_log(&self) -> Option<CallLog<CallBacktrace>> {
+        self.backtrace_log.as_ref().map(|guard| guard.log.clone())
+    }
+
+    /// Returns `(method_key, registration id, replacement_addr)`, identifying this
+    /// registration in the registering thread's replacement stack.
+    pub(crate) fn key(&self) -> (usize, u64, usize) {
+        (self.method_key, self.id, self.replacement)
+    }
+
+    /// Returns `(func_addr, patch_size, dispatcher_addr)` of the patched function.
+    /// Returns whether the function still branches to its dispatcher, rather than holding
+    /// the code the branch replaced.
+    pub(crate) fn is_dispatched(&self) -> bool {
+        apply_deferred_patches();
+
+        let registry = REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+        registry.get(&self.method_key).is_some_and(|entry| {
+            let code = unsafe { read_bytes(entry.func_ptr, entry.patch_size) };
+            code != entry.original_bytes[..entry.patch_size]
+        })
+    }
+
+    pub(crate) fn info(&self) -> (usize, usize, usize) {
+        let registry = REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+        match registry.get(&self.method_key) {
+            Some(entry) => (
+                self.method_key,
+                entry.patch_size,
+                entry.dispatcher_jit as usize,
+            ),
+            None => (self.method_key, 0, 0),
+        }
+    }
+
+    /// Formats the original bytes, the written patch, the dispatcher and the trampoline
+    /// of the patched function, plus the replacement JIT block if there is one.
+    #[cfg(feature = "jit-dump")]
+    pub(crate) fn dump(&self) -> String {
+        use crate::injector_core::jit_dump::dump_block;
+
+        let registry = REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(entry) = registry.get(&self.method_key) else {
+            return format!("patch at {:#x} (not installed)\n", self.method_key);
+        };
+
+        let mut out = format!(
+            "patch at {:#x} ({} bytes, thread-local)\n  original: {}\n",
+            self.method_key,
+            entry.patch_size,
+            diagnostics::hex_bytes(
+                &entry.original_bytes[..entry.patch_size.min(entry.original_bytes.len())]
+            )
+        );
+        dump_block(&mut out, "patch", entry.func_ptr as usize, entry.patch_size);
+        dump_block(
+            &mut out,
+            "dispatcher",
+            entry.dispatcher_jit as usize,
+            entry.dispatcher_jit_size,
+        );
+        dump_block(
+            &mut out,
+            "trampoline",
+            entry.trampoline as usize,
+            entry.trampoline_size,
+        );
+        if let Some((ptr, size)) = self.extra_jit {
+            dump_block(&mut out, "replacement", ptr as usize, size);
+        }
+        out
+    }
+}
+
+/// Returns `(func_addr, patch_size, dispatcher_addr)` for every function that has at
+/// least one thread-local replacement registered on any thread.
+pub(crate) fn active_registrations() -> Vec<(usize, usize, usize)> {
+    let registry = REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+    registry
+        .iter()
+        .filter(|(_, entry)| entry.ref_count > 0)
+        .map(|(key, entry)| (*key, entry.patch_size, entry.dispatcher_jit as usize))
+        .collect()
+}
+
 /// Called by the JIT dispatcher to get the target function pointer for the current thread.
 ///
 /// Returns the thread-local replacement if registered, otherwise falls back to
@@ -146,11 +439,23 @@ impl Drop for ThreadRegistration {
 ///
 /// # Safety
 /// This function is called from JIT-generated code. It must not panic across the FFI boundary.
-pub(crate) extern "C" fn get_thread_target(method_key: usize, default_target: usize) -> usize {
+pub(crate) extern "C" fn get_thread_target(
+    method_key: usize,
+    default_target: usize,
+    return_address: usize,
+) -> usize {
     match std::panic::catch_unwind(AssertUnwindSafe(|| {
+        if INTERNAL_DEPTH.try_with(Cell::get).unwrap_or(0) > 0 {
+            return default_target;
+        }
+
         let tls_result = tls_get(&method_key, 0);
-        if tls_result != 0 {
-            return tls_result;
+        if tls_result != 0 && !is_running_guarded(tls_result) {
+            // The return address into Thumb code has its lowest bit set.
+            #[cfg(target_arch = "arm")]
+            let return_address = return_address & !1;
+            CALLER_LOGS.push(tls_result, || return_address);
+            return route_call(tls_result, default_target);
         }
 
         default_target
@@ -160,6 +465,282 @@ pub(crate) extern "C" fn get_thread_target(method_key: usize, default_target: us
     }
 }
 
+/// Sends the calls a function makes of itself while its fake at `fake` runs, on the same
+/// thread, to the original function, until the returned guard is dropped. The fake must
+/// announce it runs with `enter_fake()`.
+///
+/// Called before the fake is registered: the bookkeeping may call the faked function, e.g.
+/// `memset`, which must not reach the fake unguarded.
+pub(crate) fn guard_reentrancy(fake: usize) -> ReentrancyGuard {
+    let _internal = enter_internal();
+    *GUARDED_FAKES
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .entry(fake)
+        .or_insert(0) += 1;
+    GUARDED_REGISTRATIONS.fetch_add(1, Ordering::Relaxed);
+    ReentrancyGuard { fake }
+}
+
+/// Keeps a fake guarded, see `guard_reentrancy()`.
+pub(crate) struct ReentrancyGuard {
+    fake: usize,
+}
+
+impl Drop for ReentrancyGuard {
+    fn drop(&mut self) {
+        let mut guarded = GUARDED_FAKES.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(count) = guarded.get_mut(&self.fake) {
+            *count -= 1;
+            if *count == 0 {
+                guarded.remove(&self.fake);
+            }
+        }
+        GUARDED_REGISTRATIONS.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Marks the fake at `fake` running on the current thread until the returned guard is
+/// dropped, if a registration guards it. While it runs, the calls it makes of the function it
+/// fakes reach the original function.
+pub(crate) fn enter_fake(fake: usize) -> Option<RunningFake> {
+    if GUARDED_REGISTRATIONS.load(Ordering::Relaxed) == 0 {
+        return None;
+    }
+    if !GUARDED_FAKES
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .contains_key(&fake)
+    {
+        return None;
+    }
+
+    let count = RUNNING_GUARDED_COUNT.try_with(Cell::get).ok()?;
+    if count == MAX_RUNNING_GUARDED {
+        return None;
+    }
+    RUNNING_GUARDED
+        .try_with(|running| running[count].set(fake))
+        .ok()?;
+    RUNNING_GUARDED_COUNT.set(count + 1);
+    Some(RunningFake)
+}
+
+/// Marks the innermost guarded fake of the current thread as returned on drop, also during
+/// unwinding.
+pub(crate) struct RunningFake;
+
+impl Drop for RunningFake {
+    fn drop(&mut self) {
+        let _ = RUNNING_GUARDED_COUNT.try_with(|count| count.set(count.get().saturating_sub(1)));
+    }
+}
+
+/// Injects the faults drawn by `route.fails` in the calls of the replacement at
+/// `replacement`, on every thread it is registered on, until the returned guard is dropped.
+/// The failing calls run `route.failure`, and the others the replacement or, with
+/// `route.others_to_original`, the original function.
+pub(crate) fn route_faults(replacement: usize, route: FaultRoute) -> FaultRouteGuard {
+    let _internal = enter_internal();
+    let id = NEXT_REGISTRATION_ID.fetch_add(1, Ordering::Relaxed);
+    FAULT_ROUTES
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .entry(replacement)
+        .or_default()
+        .push((id, route));
+    FAULT_ROUTE_COUNT.fetch_add(1, Ordering::Relaxed);
+    FaultRouteGuard { replacement, id }
+}
+
+/// Removes a fault route on drop, see `route_faults()`.
+pub(crate) struct FaultRouteGuard {
+    replacement: usize,
+    id: u64,
+}
+
+impl Drop for FaultRouteGuard {
+    fn drop(&mut self) {
+        let _internal = enter_internal();
+        let removed = {
+            let mut routes = FAULT_ROUTES.lock().unwrap_or_else(|e| e.into_inner());
+            let removed = routes.get_mut(&self.replacement).and_then(|routes| {
+                let pos = routes.iter().position(|&(id, _)| id == self.id)?;
+                Some(routes.remove(pos))
+            });
+            if routes.get(&self.replacement).is_some_and(Vec::is_empty) {
+                routes.remove(&self.replacement);
+            }
+            removed
+        };
+        FAULT_ROUTE_COUNT.fetch_sub(1, Ordering::Relaxed);
+        // The fault injector may report its seed when dropped, outside the lock.
+        drop(removed);
+    }
+}
+
+impl<T: 'static> CallLogs<T> {
+    const fn new() -> Self {
+        Self {
+            logs: Mutex::new(None),
+            count: AtomicUsize::new(0),
+        }
+    }
+
+    /// Logs the calls of the replacement at `replacement`, on every thread it is registered
+    /// on, until the returned guard is dropped.
+    fn start(&'static self, replacement: usize) -> CallLogGuard<T> {
+        let _internal = enter_internal();
+        let id = NEXT_REGISTRATION_ID.fetch_add(1, Ordering::Relaxed);
+        let log = CallLog::default();
+        self.logs
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get_or_insert_with(HashMap::new)
+            .entry(replacement)
+            .or_default()
+            .push((id, log.clone()));
+        self.count.fetch_add(1, Ordering::Relaxed);
+        CallLogGuard {
+            logs: self,
+            replacement,
+            id,
+            log,
+        }
+    }
+
+    /// Appends what `entry` returns to every log of the replacement at `replacement`, if its
+    /// calls are logged.
+    fn push(&self, replacement: usize, entry: impl FnOnce() -> T)
+    where
+        T: Clone,
+    {
+        if self.count.load(Ordering::Relaxed) == 0 {
+            return;
+        }
+        let _internal = enter_internal();
+        let logs: Vec<CallLog<T>> = self
+            .logs
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_ref()
+            .and_then(|logs| logs.get(&replacement))
+            .map(|logs| logs.iter().map(|(_, log)| log.clone()).collect())
+            .unwrap_or_default();
+
+        if logs.is_empty() {
+            return;
+        }
+        let entry = entry();
+        for log in logs {
+            let mut log = log.lock().unwrap_or_else(|e| e.into_inner());
+            if log.len() == MAX_LOGGED_CALLS {
+                log.pop_front();
+            }
+            log.push_back(entry.clone());
+        }
+    }
+}
+
+/// Stops logging calls on drop, see `CallLogs::start()`. The log stays readable.
+pub(crate) struct CallLogGuard<T: 'static> {
+    logs: &'static CallLogs<T>,
+    replacement: usize,
+    id: u64,
+    log: CallLog<T>,
+}
+
+impl<T> CallLogGuard<T> {
+    /// Returns whether a call was logged since the guard was created.
+    pub(crate) fn has_calls(&self) -> bool {
+        !self.log.lock().unwrap_or_else(|e| e.into_inner()).is_empty()
+    }
+}
+
+impl<T> Drop for CallLogGuard<T> {
+    fn drop(&mut self) {
+        let _internal = enter_internal();
+        let mut logs = self.logs.logs.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(logs) = logs.as_mut() {
+            if let Some(entries) = logs.get_mut(&self.replacement) {
+                entries.retain(|&(id, _)| id != self.id);
+                if entries.is_empty() {
+                    logs.remove(&self.replacement);
+                }
+            }
+        }
+        self.logs.count.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Logs the return address of the calls dispatched to the replacement at `replacement`, on
+/// every thread it is registered on, until the returned guard is dropped.
+pub(crate) fn log_callers(replacement: usize) -> CallLogGuard<usize> {
+    CALLER_LOGS.start(replacement)
+}
+
+/// Logs a backtrace of each call of the fake at `fake`, which announces its calls with
+/// `log_backtrace()`, until the returned guard is dropped.
+pub(crate) fn log_backtraces(fake: usize) -> CallLogGuard<CallBacktrace> {
+    BACKTRACE_LOGS.start(fake)
+}
+
+/// Captures a backtrace of the current call of the fake at `fake`, if they are logged.
+pub(crate) fn log_backtrace(fake: usize) {
+    BACKTRACE_LOGS.push(fake, || {
+        std::sync::Arc::new(std::backtrace::Backtrace::force_capture())
+    });
+}
+
+/// Returns where a call dispatched to the replacement at `replacement` goes: the replacement
+/// itself, or as drawn by its latest fault route, see `route_faults()`.
+fn route_call(replacement: usize, default_target: usize) -> usize {
+    if FAULT_ROUTE_COUNT.load(Ordering::Relaxed) == 0 {
+        return replacement;
+    }
+    let _internal = enter_internal();
+    let route = FAULT_ROUTES
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&replacement)
+        .and_then(|routes| routes.last())
+        .map(|(_, route)| route.clone());
+
+    match route {
+        None => replacement,
+        Some(route) if (route.fails)() => route.failure,
+        Some(route) if route.others_to_original => default_target,
+        Some(_) => replacement,
+    }
+}
+
+/// Sends the calls of every function faked on the current thread to its original function
+/// until the returned guard is dropped. Wraps the code of injectorpp itself, whose
+/// allocations, locks and formatting must not reach a fake of `malloc`, `free` or a
+/// `GlobalAlloc`.
+pub(crate) fn enter_internal() -> InternalScope {
+    let _ = INTERNAL_DEPTH.try_with(|depth| depth.set(depth.get() + 1));
+    InternalScope
+}
+
+/// Leaves the scope entered with `enter_internal()` on drop, also during unwinding.
+pub(crate) struct InternalScope;
+
+impl Drop for InternalScope {
+    fn drop(&mut self) {
+        let _ = INTERNAL_DEPTH.try_with(|depth| depth.set(depth.get().saturating_sub(1)));
+    }
+}
+
+/// Returns whether the guarded fake at `fake` is running on the current thread.
+fn is_running_guarded(fake: usize) -> bool {
+    let count = RUNNING_GUARDED_COUNT.try_with(Cell::get).unwrap_or(0);
+    count > 0
+        && RUNNING_GUARDED
+            .try_with(|running| running[..count].iter().any(|entry| entry.get() == fake))
+            .unwrap_or(false)
+}
+
 /// Check if a new ARM32 patch would overlap with any actively-used patch.
 ///
 /// Only checks entries with ref_count > 0 (functions currently being faked).
@@ -194,6 +775,44 @@ fn check_arm32_patch_overlap(
     }
 }
 
+/// Returns the key a function is registered under, which is also the address that
+/// gets patched.
+pub(crate) fn method_key_of(func_ptr: &FuncPtrInternal) -> usize {
+    // Resolve thunks to the function body, which gets patched. Critical on Windows, where
+    // extern functions go through an IAT thunk and incremental linking adds ILT jumps.
+    crate::injector_core::thunk::resolve_thunks(func_ptr.as_ptr() as usize)
+}
+
+/// Returns whether the current thread has a replacement registered for `method_key`.
+pub(crate) fn has_thread_replacement(method_key: usize) -> bool {
+    tls_get(&method_key, 0) != 0
+}
+
+/// Runs `f` with the current thread's replacement for `method_key` suspended, so that
+/// calls to the patched function inside `f` reach the original code via the trampoline.
+pub(crate) fn call_original<R>(method_key: usize, f: impl FnOnce() -> R) -> R {
+    struct Resume {
+        method_key: usize,
+        replacement: usize,
+    }
+
+    impl Drop for Resume {
+        fn drop(&mut self) {
+            if self.replacement != 0 {
+                tls_insert(self.method_key, self.replacement);
+            }
+        }
+    }
+
+    let _resume = Resume {
+        method_key,
+        replacement: tls_get(&method_key, 0),
+    };
+    tls_remove(&method_key);
+
+    f()
+}
+
 /// Register a thread-local replacement for a function.
 ///
 /// If this is the first replacement for this function, installs the dispatcher infrastructure
@@ -205,21 +824,11 @@ pub(crate) fn register_replacement(
     replacement_addr: usize,
     extra_jit: Option<(*mut u8, usize)>,
 ) -> ThreadRegistration {
-    // Resolve import thunks (jmp [rip+disp]) to the actual function address.
-    // This is critical on Windows x86_64 where extern functions go through an IAT thunk.
-    let raw_addr = func_ptr.as_ptr() as *mut u8;
-
-    #[cfg(target_arch = "x86_64")]
-    let func_addr = unsafe { resolve_function_address(raw_addr) };
-
-    #[cfg(target_arch = "aarch64")]
-    let func_addr = raw_addr;
-
-    #[cfg(target_arch = "arm")]
-    let func_addr = raw_addr;
-    let method_key = func_addr as usize;
+    let _internal = enter_internal();
+    let method_key = method_key_of(func_ptr);
+    let func_addr = method_key as *mut u8;
 
-    {
+    let (original_bytes, patch_size, dispatcher_addr) = {
         let mut registry = REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
 
         #[cfg(target_arch = "arm")]
@@ -230,36 +839,72 @@ pub(crate) fn register_replacement(
             .or_insert_with(|| install_dispatcher(func_addr, method_key));
 
         entry.ref_count += 1;
-    }
+
+        // Only copied when someone is listening, this path is hot in parallel tests.
+        let original_bytes = if diagnostics::log_enabled() {
+            entry.original_bytes.clone()
+        } else {
+            Vec::new()
+        };
+        (original_bytes, entry.patch_size, entry.dispatcher_jit as usize)
+    };
 
     // Set thread-local replacement
-    tls_insert(method_key, replacement_addr);
+    let id = stack_push(method_key, replacement_addr);
+
+    diagnostics::patch_installed(
+        PatchMode::ThreadLocal,
+        method_key,
+        &original_bytes,
+        patch_size,
+        dispatcher_addr,
+    );
 
     ThreadRegistration {
         method_key,
+        id,
+        replacement: replacement_addr,
         extra_jit,
+        on_thread: true,
+        reentrancy_guard: None,
+        fault_route: None,
+        caller_log: None,
+        backtrace_log: None,
     }
 }
 
-/// Resolve import thunks to the actual function address.
-/// On Windows x86_64, extern "C" functions often go through an import address table (IAT) thunk:
-/// `jmp [rip+disp32]` (FF 25 xx xx xx xx). This reads the target from the IAT and returns
-/// the real function address. For non-thunk functions, returns the address unchanged.
-///
-/// This is important because the trampoline copies the original function bytes, and
-/// RIP-relative instructions would resolve to wrong addresses in the trampoline.
-#[cfg(target_arch = "x86_64")]
-unsafe fn resolve_function_address(func_addr: *mut u8) -> *mut u8 {
-    let code = std::slice::from_raw_parts(func_addr, 6);
-    if code[0] == 0xFF && code[1] == 0x25 {
-        // jmp [rip+disp32]: target address is at *(rip_after_insn + disp32)
-        let disp = i32::from_le_bytes([code[2], code[3], code[4], code[5]]);
-        let rip_after_insn = func_addr.add(6);
-        let iat_entry = rip_after_insn.offset(disp as isize) as *const *mut u8;
-        let real_addr = std::ptr::read(iat_entry);
-        return resolve_function_address(real_addr);
+/// Restores the replacements the current thread had before `enter_replacements()` on drop.
+pub(crate) struct EnteredReplacements {
+    previous: Vec<(usize, usize)>,
+}
+
+impl Drop for EnteredReplacements {
+    fn drop(&mut self) {
+        for &(method_key, replacement) in self.previous.iter().rev() {
+            if replacement != 0 {
+                tls_insert(method_key, replacement);
+            } else {
+                tls_remove(&method_key);
+            }
+        }
     }
-    func_addr
+}
+
+/// Registers `(method_key, replacement_addr)` pairs taken by `ThreadRegistration::detach()`
+/// on the current thread until the returned guard is dropped.
+pub(crate) fn enter_replacements(
+    replacements: impl IntoIterator<Item = (usize, usize)>,
+) -> EnteredReplacements {
+    let previous = replacements
+        .into_iter()
+        .map(|(method_key, replacement)| {
+            let previous = tls_get(&method_key, 0);
+            tls_insert(method_key, replacement);
+            (method_key, previous)
+        })
+        .collect();
+
+    EnteredReplacements { previous }
 }
 
 /// Install the dispatcher infrastructure for a function:
@@ -272,7 +917,7 @@ fn install_dispatcher(func_addr: *mut u8, method_key: usize) -> MethodEntry {
         install_dispatcher_x86_64(func_addr, method_key)
     }
 
-    #[cfg(target_arch = "aarch64")]
+    #[cfg(any(target_arch = "aarch64", target_arch = "arm64ec"))]
     {
         install_dispatcher_aarch64(func_addr, method_key)
     }
@@ -295,10 +940,21 @@ fn install_dispatcher_x86_64(func_addr: *mut u8, method_key: usize) -> MethodEnt
         generate_dispatcher_jit(method_key, trampoline_addr, func_addr);
 
     // Step 3: Generate branch from original function to dispatcher
+    // A function starting with endbr64 keeps it, so indirect calls still land on it with CET.
     let dispatcher_addr = dispatcher as usize;
     let func_addr_usize = func_addr as usize;
-    let branch_code = generate_branch_to_dispatcher(func_addr_usize, dispatcher_addr);
+    let branch_code = if starts_with_endbr64(func_addr) {
+        let mut code = x86_64_insn::ENDBR64.to_vec();
+        code.extend(generate_branch_to_dispatcher(
+            func_addr_usize + 4,
+            dispatcher_addr,
+        ));
+        code
+    } else {
+        generate_branch_to_dispatcher(func_addr_usize, dispatcher_addr)
+    };
     let patch_size = branch_code.len();
+    check_patch_fits(func_addr_usize, patch_size);
 
     // Read original bytes (at least patch_size, but we already read copy_size for trampoline)
     let save_size = patch_size.max(copy_size);
@@ -325,7 +981,7 @@ fn install_dispatcher_x86_64(func_addr: *mut u8, method_key: usize) -> MethodEnt
 // ARM64 Dispatcher JIT Code Generation
 // ============================================================================
 
-#[cfg(target_arch = "aarch64")]
+#[cfg(any(target_arch = "aarch64", target_arch = "arm64ec"))]
 fn install_dispatcher_aarch64(func_addr: *mut u8, method_key: usize) -> MethodEntry {
     // ARM64 uses a dynamic patch size based on the distance between the function
     // and its dispatcher:
@@ -334,12 +990,15 @@ fn install_dispatcher_aarch64(func_addr: *mut u8, method_key: usize) -> MethodEn
     //   A 4-byte aligned write is atomic on ARM64, ensuring cross-core visibility.
     //   Another core sees either the old instruction or the new B, never a partial mix.
     //
-    // - If beyond ±128MB: 12-byte patch (ADRP + ADD + BR x16).
+    // - If beyond ±128MB: 8-byte patch (ADRP + BR x16), the dispatcher being page-aligned.
     //   This is needed for system library functions (e.g. getenv, memset) on macOS
     //   where the shared cache addresses can be far from allocatable JIT memory.
-    //   The 12-byte write is NOT atomic across cores, but the initial patching happens
+    //   The 8-byte write is NOT atomic across cores, but the initial patching happens
     //   under the REGISTRY lock (only one thread patches each function). Once installed,
     //   the dispatcher is safe for concurrent execution.
+    //
+    // A function built with branch protection starts with a landing pad (`bti c`,
+    // `paciasp`), which stays in front of the branch so indirect calls still land on one.
 
     let near_src =
         unsafe { FuncPtrInternal::new(std::ptr::NonNull::new(func_addr as *mut ()).unwrap()) };
@@ -351,12 +1010,14 @@ fn install_dispatcher_aarch64(func_addr: *mut u8, method_key: usize) -> MethodEn
     let dispatcher_addr = dispatcher as usize;
 
     // Step 2: Determine patch size based on actual distance to dispatcher.
-    // If within ±128MB, B instruction (4 bytes) reaches; otherwise ADRP+ADD+BR (12 bytes).
+    // If within ±128MB, B instruction (4 bytes) reaches; otherwise ADRP+BR (8 bytes).
+    let landing_pad = landing_pad(unsafe { (func_addr as *const u32).read() });
+    let pad_size = landing_pad.map_or(0, |_| 4);
     let branch_instrs = crate::injector_core::arm64_codegenerator::maybe_emit_long_jump(
-        func_addr as usize,
+        func_addr as usize + pad_size,
         dispatcher_addr,
     );
-    let patch_size = branch_instrs.len() * 4;
+    let patch_size = pad_size + branch_instrs.len() * 4;
 
     // Step 3: Create trampoline with the correct copy_size (must match patch_size)
     let (trampoline, trampoline_size) = create_trampoline_aarch64(func_addr, patch_size);
@@ -377,13 +1038,15 @@ fn install_dispatcher_aarch64(func_addr: *mut u8, method_key: usize) -> MethodEn
     }
 
     // Step 5: Generate branch patch
-    let patch = generate_branch_patch_aarch64(func_addr as usize, dispatcher_addr);
+    let patch = generate_branch_patch_aarch64(func_addr as usize, dispatcher_addr, landing_pad);
     assert_eq!(
         patch.len(),
         patch_size,
         "Branch patch size changed unexpectedly"
     );
 
+    check_patch_fits(func_addr as usize, patch_size);
+
     // Read original bytes before patching
     let original_bytes = unsafe { read_bytes(func_addr, patch_size) };
 
@@ -405,11 +1068,15 @@ fn install_dispatcher_aarch64(func_addr: *mut u8, method_key: usize) -> MethodEn
 }
 
 /// Generate branch patch bytes for ARM64.
-/// Returns 4 bytes (single B) if within ±128MB, or 12 bytes (ADRP+ADD+BR) otherwise.
-#[cfg(target_arch = "aarch64")]
-fn generate_branch_patch_aarch64(from: usize, to: usize) -> Vec<u8> {
-    let instrs: Vec<u32> =
-        crate::injector_core::arm64_codegenerator::maybe_emit_long_jump(from, to);
+/// Returns 4 bytes (single B) if within ±128MB, or 8 or 12 bytes (ADRP+[ADD+]BR) otherwise,
+/// preceded by `landing_pad` if any.
+#[cfg(any(target_arch = "aarch64", target_arch = "arm64ec"))]
+fn generate_branch_patch_aarch64(from: usize, to: usize, landing_pad: Option<u32>) -> Vec<u8> {
+    let pad_size = landing_pad.map_or(0, |_| 4);
+    let instrs: Vec<u32> = landing_pad
+        .into_iter()
+        .chain(crate::injector_core::arm64_codegenerator::maybe_emit_long_jump(from + pad_size, to))
+        .collect();
     let mut bytes = Vec::with_capacity(instrs.len() * 4);
     for insn in &instrs {
         bytes.extend_from_slice(&insn.to_le_bytes());
@@ -418,7 +1085,7 @@ fn generate_branch_patch_aarch64(from: usize, to: usize) -> Vec<u8> {
 }
 
 /// Build the ARM64 dispatcher code bytes without allocating JIT memory.
-#[cfg(target_arch = "aarch64")]
+#[cfg(any(target_arch = "aarch64", target_arch = "arm64ec"))]
 fn build_dispatcher_code_aarch64(method_key_val: u64, trampoline_val: u64) -> Vec<u8> {
     let fn_addr = get_thread_target as *const () as u64;
 
@@ -454,19 +1121,22 @@ fn build_dispatcher_code_aarch64(method_key_val: u64, trampoline_val: u64) -> Ve
     emit_stp_q(&mut code, 4, 5, 144);   // stp q4, q5, [sp, #144]
     emit_stp_q(&mut code, 6, 7, 176);   // stp q6, q7, [sp, #176]
 
-    // Load arguments for get_thread_target(method_key, trampoline_addr)
-    // x0 = method_key, x1 = trampoline_addr
+    // Load arguments for get_thread_target(method_key, trampoline_addr, return_address)
+    // x0 = method_key, x1 = trampoline_addr, x2 = x30, the return address of the call, as the
+    // patch branches here without linking
     emit_mov_x_imm64(&mut code, 0, method_key_val);
     emit_mov_x_imm64(&mut code, 1, trampoline_val);
+    emit_mov_reg(&mut code, 2, 30);
 
     // Load function address and call
     emit_mov_x_imm64(&mut code, 9, fn_addr);
     // BLR x9
     emit_blr(&mut code, 9);
 
-    // Save return value (target address) in x9
-    // MOV x9, x0
-    emit_mov_reg(&mut code, 9, 0);
+    // Save return value (target address) in x16: with BTI enforced, only `br x16` and
+    // `br x17` may land on the `bti c` starting the replacement.
+    // MOV x16, x0
+    emit_mov_reg(&mut code, 16, 0);
 
     // Restore SIMD/FP registers: q0-q7
     emit_ldp_q(&mut code, 0, 1, 80);
@@ -484,23 +1154,23 @@ fn build_dispatcher_code_aarch64(method_key_val: u64, trampoline_val: u64) -> Ve
     // add sp, sp, #224
     emit_add_sp_imm(&mut code, 224);
 
-    // BR x9 (jump to target)
-    let br = emit_br(u8_to_bits::<5>(9));
+    // BR x16 (jump to target)
+    let br = emit_br(u8_to_bits::<5>(16));
     code.extend_from_slice(&bool_array_to_u32(br).to_le_bytes());
 
     code
 }
 
-/// Create a trampoline for ARM64: copy original instructions + absolute branch back.
+/// Create a trampoline for ARM64: copy original instructions + branch back.
 /// ARM64 instructions are fixed 4 bytes, so copy_size is always instruction-aligned.
 /// PC-relative instructions (ADRP, ADR, B/BL, LDR literal, etc.) are adjusted to
 /// account for the trampoline's different address.
-#[cfg(target_arch = "aarch64")]
+#[cfg(any(target_arch = "aarch64", target_arch = "arm64ec"))]
 fn create_trampoline_aarch64(
     func_addr: *mut u8,
     copy_size: usize,
 ) -> (*mut u8, usize) {
-    // The jump-back uses MOVZ + MOVK×3 + BR = 20 bytes (5 instructions)
+    // The jump-back uses a B, or MOVZ + MOVK×3 + BR = 20 bytes (5 instructions) out of range
     let jump_back_size = 20;
     let trampoline_total = copy_size + jump_back_size;
 
@@ -519,45 +1189,52 @@ fn create_trampoline_aarch64(
     // Fix up PC-relative instructions in the buffer (using trampoline's target address)
     fixup_aarch64_pc_relative_buf(&mut buf, trampoline, func_addr, copy_size);
 
-    // Append absolute jump back to original + copy_size.
-    // Use x17 (IP1) instead of x16 (IP0) because the copied instructions may use x16
-    // (e.g., Windows ARM64 import thunks start with ADRP x16). The jump-back must not
-    // clobber registers set by the copied instructions before they're consumed by the
-    // original code at func_addr + copy_size.
+    // Append jump back to original + copy_size. A B instruction when it reaches: with BTI
+    // enforced, an indirect branch into the middle of the function faults, as no landing
+    // pad is there.
     let jump_back_target = (func_addr as usize + copy_size) as u64;
-    let reg: [bool; 5] = u8_to_bits::<5>(17); // x17 (IP1) scratch register
-
-    let instrs: [u32; 5] = [
-        bool_array_to_u32(emit_movz_from_address(
-            jump_back_target,
-            0,
-            true,
-            u8_to_bits::<2>(0),
-            reg,
-        )),
-        bool_array_to_u32(emit_movk_from_address(
-            jump_back_target,
-            16,
-            true,
-            u8_to_bits::<2>(1),
-            reg,
-        )),
-        bool_array_to_u32(emit_movk_from_address(
-            jump_back_target,
-            32,
-            true,
-            u8_to_bits::<2>(2),
-            reg,
-        )),
-        bool_array_to_u32(emit_movk_from_address(
-            jump_back_target,
-            48,
-            true,
-            u8_to_bits::<2>(3),
-            reg,
-        )),
-        bool_array_to_u32(emit_br(reg)),
-    ];
+    let disp = jump_back_target as i64 - (trampoline as usize + copy_size) as i64;
+    let instrs: Vec<u32> = if (-(1i64 << 27)..(1i64 << 27)).contains(&disp) {
+        vec![0x1400_0000 | ((disp >> 2) as u32 & 0x03ff_ffff)]
+    } else {
+        // Use x17 (IP1) instead of x16 (IP0) because the copied instructions may use x16
+        // (e.g., Windows ARM64 import thunks start with ADRP x16). The jump-back must not
+        // clobber registers set by the copied instructions before they're consumed by the
+        // original code at func_addr + copy_size.
+        let reg: [bool; 5] = u8_to_bits::<5>(17); // x17 (IP1) scratch register
+
+        vec![
+            bool_array_to_u32(emit_movz_from_address(
+                jump_back_target,
+                0,
+                true,
+                u8_to_bits::<2>(0),
+                reg,
+            )),
+            bool_array_to_u32(emit_movk_from_address(
+                jump_back_target,
+                16,
+                true,
+                u8_to_bits::<2>(1),
+                reg,
+            )),
+            bool_array_to_u32(emit_movk_from_address(
+                jump_back_target,
+                32,
+                true,
+                u8_to_bits::<2>(2),
+                reg,
+            )),
+            bool_array_to_u32(emit_movk_from_address(
+                jump_back_target,
+                48,
+                true,
+                u8_to_bits::<2>(3),
+                reg,
+            )),
+            bool_array_to_u32(emit_br(reg)),
+        ]
+    };
     for (i, insn) in instrs.iter().enumerate() {
         buf[copy_size + i * 4..copy_size + (i + 1) * 4].copy_from_slice(&insn.to_le_bytes());
     }
@@ -583,7 +1260,7 @@ fn create_trampoline_aarch64(
 /// If the adjusted offset overflows the instruction's immediate field, the instruction
 /// is NOP-ed out (safe because the trampoline is followed by a jump back to the
 /// original function which will re-execute the correct code path).
-#[cfg(target_arch = "aarch64")]
+#[cfg(any(target_arch = "aarch64", target_arch = "arm64ec"))]
 fn fixup_aarch64_pc_relative_buf(
     buf: &mut [u8],
     trampoline_addr: *mut u8,
@@ -708,7 +1385,7 @@ fn fixup_aarch64_pc_relative_buf(
 // ============================================================================
 
 /// SUB SP, SP, #imm12
-#[cfg(target_arch = "aarch64")]
+#[cfg(any(target_arch = "aarch64", target_arch = "arm64ec"))]
 fn emit_sub_sp_imm(code: &mut Vec<u8>, imm: u16) {
     // 1 1 0 1 0 0 0 1 0 0 [imm12] [Rn=SP(31)] [Rd=SP(31)]
     let insn: u32 = 0xD1000000 | ((imm as u32 & 0xFFF) << 10) | (31 << 5) | 31;
@@ -716,7 +1393,7 @@ fn emit_sub_sp_imm(code: &mut Vec<u8>, imm: u16) {
 }
 
 /// ADD SP, SP, #imm12
-#[cfg(target_arch = "aarch64")]
+#[cfg(any(target_arch = "aarch64", target_arch = "arm64ec"))]
 fn emit_add_sp_imm(code: &mut Vec<u8>, imm: u16) {
     // 1 0 0 1 0 0 0 1 0 0 [imm12] [Rn=SP(31)] [Rd=SP(31)]
     let insn: u32 = 0x91000000 | ((imm as u32 & 0xFFF) << 10) | (31 << 5) | 31;
@@ -724,7 +1401,7 @@ fn emit_add_sp_imm(code: &mut Vec<u8>, imm: u16) {
 }
 
 /// STP Xt1, Xt2, [SP, #imm7*8]
-#[cfg(target_arch = "aarch64")]
+#[cfg(any(target_arch = "aarch64", target_arch = "arm64ec"))]
 fn emit_stp_x(code: &mut Vec<u8>, rt1: u8, rt2: u8, offset: i16) {
     let imm7 = ((offset / 8) as u32) & 0x7F;
     // 10 1 0 1 0 0 1 0 0 [imm7] [Rt2] [Rn=SP(31)] [Rt1]
@@ -733,7 +1410,7 @@ fn emit_stp_x(code: &mut Vec<u8>, rt1: u8, rt2: u8, offset: i16) {
 }
 
 /// LDP Xt1, Xt2, [SP, #imm7*8]
-#[cfg(target_arch = "aarch64")]
+#[cfg(any(target_arch = "aarch64", target_arch = "arm64ec"))]
 fn emit_ldp_x(code: &mut Vec<u8>, rt1: u8, rt2: u8, offset: i16) {
     let imm7 = ((offset / 8) as u32) & 0x7F;
     // 10 1 0 1 0 0 1 0 1 [imm7] [Rt2] [Rn=SP(31)] [Rt1]
@@ -742,7 +1419,7 @@ fn emit_ldp_x(code: &mut Vec<u8>, rt1: u8, rt2: u8, offset: i16) {
 }
 
 /// STP Qt1, Qt2, [SP, #imm7*16]
-#[cfg(target_arch = "aarch64")]
+#[cfg(any(target_arch = "aarch64", target_arch = "arm64ec"))]
 fn emit_stp_q(code: &mut Vec<u8>, rt1: u8, rt2: u8, offset: i16) {
     let imm7 = ((offset / 16) as u32) & 0x7F;
     // 10 1 0 1 1 0 0 1 0 0 [imm7] [Rt2] [Rn=SP(31)] [Rt1]
@@ -751,7 +1428,7 @@ fn emit_stp_q(code: &mut Vec<u8>, rt1: u8, rt2: u8, offset: i16) {
 }
 
 /// LDP Qt1, Qt2, [SP, #imm7*16]
-#[cfg(target_arch = "aarch64")]
+#[cfg(any(target_arch = "aarch64", target_arch = "arm64ec"))]
 fn emit_ldp_q(code: &mut Vec<u8>, rt1: u8, rt2: u8, offset: i16) {
     let imm7 = ((offset / 16) as u32) & 0x7F;
     // 10 1 0 1 1 0 1 0 1 [imm7] [Rt2] [Rn=SP(31)] [Rt1]
@@ -761,7 +1438,7 @@ fn emit_ldp_q(code: &mut Vec<u8>, rt1: u8, rt2: u8, offset: i16) {
 
 /// Load a 64-bit immediate into register Xd using MOVZ + MOVK sequence.
 /// Uses 2-4 instructions depending on the value.
-#[cfg(target_arch = "aarch64")]
+#[cfg(any(target_arch = "aarch64", target_arch = "arm64ec"))]
 fn emit_mov_x_imm64(code: &mut Vec<u8>, rd: u8, val: u64) {
     let reg: [bool; 5] = u8_to_bits::<5>(rd);
     let movz = emit_movz_from_address(val, 0, true, u8_to_bits::<2>(0), reg);
@@ -778,7 +1455,7 @@ fn emit_mov_x_imm64(code: &mut Vec<u8>, rd: u8, val: u64) {
 }
 
 /// BLR Xn (Branch with Link to Register)
-#[cfg(target_arch = "aarch64")]
+#[cfg(any(target_arch = "aarch64", target_arch = "arm64ec"))]
 fn emit_blr(code: &mut Vec<u8>, rn: u8) {
     // 1101011 0 0 01 11111 0000 0 0 [Rn] 00000
     let insn: u32 = 0xD63F0000 | ((rn as u32) << 5);
@@ -786,7 +1463,7 @@ fn emit_blr(code: &mut Vec<u8>, rn: u8) {
 }
 
 /// MOV Xd, Xn (alias for ORR Xd, XZR, Xn)
-#[cfg(target_arch = "aarch64")]
+#[cfg(any(target_arch = "aarch64", target_arch = "arm64ec"))]
 fn emit_mov_reg(code: &mut Vec<u8>, rd: u8, rn: u8) {
     // 1 01 01010 00 0 [Rm] 000000 11111 [Rd]
     let insn: u32 = 0xAA0003E0 | ((rn as u32) << 16) | (rd as u32);
@@ -849,8 +1526,8 @@ fn install_dispatcher_arm32(func_addr: *mut u8, method_key: usize) -> MethodEntr
         unsafe { FuncPtrInternal::new(std::ptr::NonNull::new(func_addr_clean as *mut ()).unwrap()) };
 
     // Step 1: Pre-allocate dispatcher buffer to determine its address.
-    // 64 bytes fits an optional 12-byte Thumb stub + 52-byte ARM dispatcher.
-    let dispatcher_max_size = 64;
+    // 72 bytes fits an optional 12-byte Thumb stub + 56-byte ARM dispatcher.
+    let dispatcher_max_size = 72;
     let dispatcher = allocate_jit_memory(&near_src, dispatcher_max_size);
     let dispatcher_addr = dispatcher as usize;
 
@@ -859,6 +1536,7 @@ fn install_dispatcher_arm32(func_addr: *mut u8, method_key: usize) -> MethodEntr
     let distance = dispatcher_addr.abs_diff(func_addr_clean as usize);
     let max_b_range = if is_thumb { 16 * 1024 * 1024 } else { 32 * 1024 * 1024 };
     let patch_size = if distance < max_b_range { 4 } else { 12 };
+    check_patch_fits(func_addr_clean as usize, patch_size);
 
     // Step 3: Calculate the trampoline copy size. For Thumb, we must copy
     // complete instructions (the patch boundary might fall in the middle of a
@@ -926,7 +1604,7 @@ fn install_dispatcher_arm32(func_addr: *mut u8, method_key: usize) -> MethodEntr
 /// The dispatcher runs in ARM mode. It:
 /// 1. Saves argument registers (r0-r3) and lr
 /// 2. Saves VFP argument registers (d0-d7) for hard-float ABI
-/// 3. Calls get_thread_target(method_key, trampoline_addr)
+/// 3. Calls get_thread_target(method_key, trampoline_addr, lr)
 /// 4. Restores all registers
 /// 5. Branches to the returned target
 #[cfg(target_arch = "arm")]
@@ -935,9 +1613,10 @@ fn build_dispatcher_code_arm32(method_key: u32, trampoline_addr: u32) -> Vec<u8>
 
     // Push 6 registers (24 bytes) to maintain 8-byte stack alignment per AAPCS.
     // r4 is included as padding (callee-saved, correctly saved/restored).
-    let instructions: [u32; 13] = [
+    let instructions: [u32; 14] = [
         0xE92D402F, // PUSH {r0-r3, r4, lr}
         0xED2D0B10, // VPUSH {d0-d7}
+        0xE1A0200E, // MOV r2, lr         → return address
         0xE59F0018, // LDR r0, [pc, #24]  → method_key
         0xE59F1018, // LDR r1, [pc, #24]  → trampoline_addr
         0xE59FC018, // LDR r12, [pc, #24] → fn_addr
@@ -951,7 +1630,7 @@ fn build_dispatcher_code_arm32(method_key: u32, trampoline_addr: u32) -> Vec<u8>
         fn_addr,
     ];
 
-    let mut code = Vec::with_capacity(52);
+    let mut code = Vec::with_capacity(56);
     for insn in &instructions {
         code.extend_from_slice(&insn.to_le_bytes());
     }
@@ -1239,7 +1918,8 @@ fn generate_branch_to_dispatcher(from: usize, to: usize) -> Vec<u8> {
 ///
 /// The dispatcher:
 /// 1. Saves all argument registers (integer + xmm)
-/// 2. Calls `get_thread_target(method_key, trampoline_addr)` to get the target
+/// 2. Calls `get_thread_target(method_key, trampoline_addr, return_address)` to get the
+///    target
 /// 3. Restores all argument registers
 /// 4. Jumps to the returned target
 fn generate_dispatcher_jit(
@@ -1277,6 +1957,9 @@ fn generate_dispatcher_windows(
 ) -> Vec<u8> {
     let mut code: Vec<u8> = Vec::with_capacity(128);
 
+    // Entered by an indirect jump when out of rel32 range
+    code.extend_from_slice(&x86_64_insn::ENDBR64);
+
     // Save integer argument registers
     code.extend_from_slice(&[0x41, 0x51]); // push r9
     code.extend_from_slice(&[0x41, 0x50]); // push r8
@@ -1300,6 +1983,9 @@ fn generate_dispatcher_windows(
     code.extend_from_slice(&[0x48, 0xBA]);
     code.extend_from_slice(&(trampoline_addr as u64).to_le_bytes());
 
+    // mov r8, [rsp+0x88] (third arg = return address, above the 4 pushes and 0x68 bytes)
+    code.extend_from_slice(&[0x4C, 0x8B, 0x84, 0x24, 0x88, 0x00, 0x00, 0x00]);
+
     // mov rax, fn_addr
     code.extend_from_slice(&[0x48, 0xB8]);
     code.extend_from_slice(&(fn_addr as u64).to_le_bytes());
@@ -1342,6 +2028,9 @@ fn generate_dispatcher_sysv(
 ) -> Vec<u8> {
     let mut code: Vec<u8> = Vec::with_capacity(200);
 
+    // Entered by an indirect jump when out of rel32 range
+    code.extend_from_slice(&x86_64_insn::ENDBR64);
+
     // Save integer argument registers (6 registers)
     code.extend_from_slice(&[0x41, 0x51]); // push r9
     code.extend_from_slice(&[0x41, 0x50]); // push r8
@@ -1372,6 +2061,9 @@ fn generate_dispatcher_sysv(
     code.extend_from_slice(&[0x48, 0xBE]);
     code.extend_from_slice(&(trampoline_addr as u64).to_le_bytes());
 
+    // mov rdx, [rsp+0xB8] (third arg = return address, above the 6 pushes and 0x88 bytes)
+    code.extend_from_slice(&[0x48, 0x8B, 0x94, 0x24, 0xB8, 0x00, 0x00, 0x00]);
+
     // mov rax, fn_addr
     code.extend_from_slice(&[0x48, 0xB8]);
     code.extend_from_slice(&(fn_addr as u64).to_le_bytes());
@@ -1422,17 +2114,22 @@ fn generate_dispatcher_sysv(
 /// Returns (trampoline_ptr, trampoline_alloc_size, bytes_copied_from_original).
 fn create_trampoline(func_addr: *mut u8, _method_key: usize) -> (*mut u8, usize, usize) {
     // We need to copy enough bytes to cover the patch that will be applied.
-    // The patch is a jmp to the dispatcher (5 bytes for rel32, 12 for movabs+jmp).
+    // The patch is a jmp to the dispatcher (5 bytes for rel32, 12 for movabs+jmp),
+    // after the endbr64 starting the function if any.
     // Since dispatcher JIT is allocated nearby, the patch is typically 5 bytes.
     // Use 12 as the minimum to be safe.
-    let min_copy = 12;
+    let min_copy = if starts_with_endbr64(func_addr) {
+        16
+    } else {
+        12
+    };
 
     // Read enough bytes from the original function to decode instructions
     let read_size = min_copy + 16; // extra space for the last instruction
     let original_code = unsafe { read_bytes(func_addr, read_size) };
 
     // Find instruction-aligned boundary >= min_copy
-    let copy_size = find_instruction_boundary(&original_code, min_copy);
+    let copy_size = x86_64_insn::overwritten_len(&original_code, min_copy).unwrap_or(min_copy);
 
     // The jump-back uses jmp [rip+0] + 8-byte address = 14 bytes
     let jump_back_size = 14;
@@ -1447,10 +2144,16 @@ fn create_trampoline(func_addr: *mut u8, _method_key: usize) -> (*mut u8, usize,
     let near_src =
         unsafe { FuncPtrInternal::new(std::ptr::NonNull::new(func_addr as *mut ()).unwrap()) };
     let trampoline = allocate_jit_memory(&near_src, trampoline_total);
+    // Written in place, or through its writable view when JIT memory is mapped twice.
+    let view = jit_writable_view(trampoline);
+    if view == trampoline {
+        // Writable for this thread on macOS until it is written.
+        unsafe { set_code_writable(trampoline, trampoline_total, true) };
+    }
 
     // Copy original instruction bytes
     unsafe {
-        std::ptr::copy_nonoverlapping(func_addr, trampoline, copy_size);
+        std::ptr::copy_nonoverlapping(func_addr, view, copy_size);
     }
 
     // Fix up RIP-relative displacements in the copied instructions.
@@ -1461,6 +2164,7 @@ fn create_trampoline(func_addr: *mut u8, _method_key: usize) -> (*mut u8, usize,
     let stub_start = copy_size + jump_back_size;
     fixup_rip_relative_instructions(
         trampoline,
+        view,
         &original_code,
         copy_size,
         delta,
@@ -1470,25 +2174,32 @@ fn create_trampoline(func_addr: *mut u8, _method_key: usize) -> (*mut u8, usize,
     );
 
     // Append jump back to original + copy_size
-    // Using: jmp [rip+0] (FF 25 00 00 00 00) + 8-byte target address
+    // Using: jmp rel32 (E9) when it reaches, as with CET enforced an indirect jump into the
+    // middle of the function faults, no endbr64 being there. Otherwise
+    // jmp [rip+0] (FF 25 00 00 00 00) + 8-byte target address
     let jump_back_addr = (func_addr as usize + copy_size) as u64;
     let jump_back_offset = copy_size;
+    let rel = jump_back_addr as i64 - (trampoline as usize + jump_back_offset + 5) as i64;
 
     unsafe {
-        let jmp_ptr = trampoline.add(jump_back_offset);
-        // FF 25 00 00 00 00 = jmp [rip+0]
-        *jmp_ptr = 0xFF;
-        *jmp_ptr.add(1) = 0x25;
-        *jmp_ptr.add(2) = 0x00;
-        *jmp_ptr.add(3) = 0x00;
-        *jmp_ptr.add(4) = 0x00;
-        *jmp_ptr.add(5) = 0x00;
-        // 8-byte absolute target address
-        std::ptr::copy_nonoverlapping(
-            jump_back_addr.to_le_bytes().as_ptr(),
-            jmp_ptr.add(6),
-            8,
-        );
+        let jmp_ptr = view.add(jump_back_offset);
+        if let Ok(rel) = i32::try_from(rel) {
+            *jmp_ptr = 0xE9;
+            std::ptr::copy_nonoverlapping(rel.to_le_bytes().as_ptr(), jmp_ptr.add(1), 4);
+        } else {
+            // FF 25 00 00 00 00 = jmp [rip+0]
+            *jmp_ptr = 0xFF;
+            *jmp_ptr.add(1) = 0x25;
+            *jmp_ptr.add(2) = 0x00;
+            *jmp_ptr.add(3) = 0x00;
+            *jmp_ptr.add(4) = 0x00;
+            *jmp_ptr.add(5) = 0x00;
+            // 8-byte absolute target address
+            std::ptr::copy_nonoverlapping(jump_back_addr.to_le_bytes().as_ptr(), jmp_ptr.add(6), 8);
+        }
+
+        // Written in place, so made executable only now with `strict_wx` or on macOS.
+        set_code_writable(trampoline, trampoline_total, false);
 
         // Flush instruction cache for the trampoline
         clear_cache_ptr(trampoline, trampoline_total);
@@ -1497,6 +2208,12 @@ fn create_trampoline(func_addr: *mut u8, _method_key: usize) -> (*mut u8, usize,
     (trampoline, trampoline_total, copy_size)
 }
 
+#[cfg(target_arch = "x86_64")]
+/// Whether the function at `func_addr` starts with `endbr64`.
+fn starts_with_endbr64(func_addr: *mut u8) -> bool {
+    unsafe { read_bytes(func_addr, 4) == x86_64_insn::ENDBR64 }
+}
+
 #[cfg(target_arch = "x86_64")]
 /// Adjust RIP-relative displacements in trampoline instructions so they
 /// point to the same absolute targets as the original instructions.
@@ -1517,8 +2234,12 @@ fn create_trampoline(func_addr: *mut u8, _method_key: usize) -> (*mut u8, usize,
 /// semantics: `CALL stub` pushes the return address, then `JMP target`
 /// transfers control; when the callee returns, execution resumes in the
 /// trampoline right after the CALL.
+///
+/// The instructions are written at `view`, which maps the trampoline writable.
+#[allow(clippy::too_many_arguments)]
 fn fixup_rip_relative_instructions(
     trampoline: *mut u8,
+    view: *mut u8,
     original_code: &[u8],
     copy_size: usize,
     delta: isize,
@@ -1530,15 +2251,15 @@ fn fixup_rip_relative_instructions(
     let mut stub_cursor = stub_start;
     while offset < copy_size {
         let insn = &original_code[offset..];
-        let insn_len = x86_64_insn_len(insn);
+        let insn_len = x86_64_insn::insn_len(insn);
         if insn_len == 0 {
             break;
         }
 
         // Check for ModR/M-based RIP-relative addressing (mod=00, rm=101)
-        if let Some(disp_offset) = find_rip_relative_disp_offset(insn, insn_len) {
+        if let Some(disp_offset) = x86_64_insn::find_rip_relative_disp_offset(insn, insn_len) {
             unsafe {
-                let disp_ptr = trampoline.add(offset + disp_offset) as *mut i32;
+                let disp_ptr = view.add(offset + disp_offset) as *mut i32;
                 let old_disp = disp_ptr.read_unaligned();
                 let new_disp = old_disp as i64 + delta as i64;
                 if new_disp >= i32::MIN as i64 && new_disp <= i32::MAX as i64 {
@@ -1548,20 +2269,20 @@ fn fixup_rip_relative_instructions(
                     // This is safe for coverage/profiling counter increments
                     // (lock inc [rip+disp32]) which don't affect program logic.
                     for i in 0..insn_len {
-                        *trampoline.add(offset + i) = 0x90; // NOP
+                        *view.add(offset + i) = 0x90; // NOP
                     }
                 }
             }
         }
 
         // Check for relative call/jmp (E8/E9 rel32)
-        let opcode_pos = skip_prefixes(insn);
+        let opcode_pos = x86_64_insn::skip_prefixes(insn);
         if opcode_pos < insn.len() {
             let opcode = insn[opcode_pos];
             if opcode == 0xE8 || opcode == 0xE9 {
                 let rel_offset = opcode_pos + 1;
                 unsafe {
-                    let rel_ptr = trampoline.add(offset + rel_offset) as *mut i32;
+                    let rel_ptr = view.add(offset + rel_offset) as *mut i32;
                     let old_rel = rel_ptr.read_unaligned();
                     let new_rel = old_rel as i64 + delta as i64;
                     if new_rel >= i32::MIN as i64 && new_rel <= i32::MAX as i64 {
@@ -1580,7 +2301,7 @@ fn fixup_rip_relative_instructions(
                         );
 
                         // Write stub: MOV RAX, imm64 (48 B8 + 8 bytes) + JMP RAX (FF E0)
-                        let stub_ptr = trampoline.add(stub_cursor);
+                        let stub_ptr = view.add(stub_cursor);
                         *stub_ptr = 0x48; // REX.W
                         *stub_ptr.add(1) = 0xB8; // MOV RAX, imm64
                         std::ptr::copy_nonoverlapping(
@@ -1608,7 +2329,7 @@ fn fixup_rip_relative_instructions(
                 if (0x80..=0x8F).contains(&op2) {
                     let rel_offset = opcode_pos + 2;
                     unsafe {
-                        let rel_ptr = trampoline.add(offset + rel_offset) as *mut i32;
+                        let rel_ptr = view.add(offset + rel_offset) as *mut i32;
                         let old_rel = rel_ptr.read_unaligned();
                         let new_rel = old_rel as i64 + delta as i64;
                         if new_rel >= i32::MIN as i64 && new_rel <= i32::MAX as i64 {
@@ -1631,371 +2352,6 @@ fn fixup_rip_relative_instructions(
     }
 }
 
-#[cfg(target_arch = "x86_64")]
-/// Find the byte offset of the disp32 field in a RIP-relative instruction.
-/// Returns None if the instruction doesn't use RIP-relative addressing.
-fn find_rip_relative_disp_offset(insn: &[u8], _insn_len: usize) -> Option<usize> {
-    let mut pos = skip_prefixes(insn);
-    if pos >= insn.len() {
-        return None;
-    }
-
-    let opcode = insn[pos];
-    pos += 1;
-
-    // Two-byte opcode
-    if opcode == 0x0F {
-        if pos >= insn.len() {
-            return None;
-        }
-        let op2 = insn[pos];
-        pos += 1;
-        // Jcc rel32 (0F 80-8F) don't use ModR/M RIP-relative, skip
-        if (0x80..=0x8F).contains(&op2) {
-            return None;
-        }
-        // Most other 0F xx opcodes have a ModR/M byte — fall through to check
-    } else {
-        // Single-byte opcodes: check if they have a ModR/M byte
-        match opcode {
-            // Opcodes that do NOT have ModR/M — skip
-            0x50..=0x5F | 0x90 | 0xC3 | 0xCC | 0xCB | 0xC9 | 0xF4 | 0xF5 | 0xF8 | 0xF9
-            | 0xFC | 0xFD | 0x99 | 0x9E | 0x9F => return None,
-            0x6A | 0x04 | 0x0C | 0x14 | 0x1C | 0x24 | 0x2C | 0x34 | 0x3C | 0xCD | 0xEB
-            | 0xA8 => return None,
-            0x70..=0x7F => return None, // Jcc rel8
-            0x05 | 0x0D | 0x15 | 0x1D | 0x25 | 0x2D | 0x35 | 0x3D | 0x68 | 0xA9 => {
-                return None
-            }
-            0xE8 | 0xE9 | 0xE3 => return None, // call/jmp rel32, JRCXZ
-            0xA0..=0xA3 => return None,         // MOV AL/AX moffs
-            0xB0..=0xBF => return None,         // MOV reg, imm
-            0xC2 => return None,                // RET imm16
-            _ => {
-                // Assume has ModR/M — fall through
-            }
-        }
-    }
-
-    // pos now points to the ModR/M byte
-    if pos >= insn.len() {
-        return None;
-    }
-
-    let modrm = insn[pos];
-    let mod_field = (modrm >> 6) & 3;
-    let rm_field = modrm & 7;
-
-    if mod_field == 0b00 && rm_field == 0b101 {
-        // RIP-relative: disp32 starts right after the ModR/M byte
-        Some(pos + 1)
-    } else {
-        None
-    }
-}
-
-#[cfg(target_arch = "x86_64")]
-/// Skip legacy prefixes and REX prefix, return the position of the opcode byte.
-fn skip_prefixes(code: &[u8]) -> usize {
-    let mut pos = 0;
-    // Skip legacy prefixes
-    while pos < code.len() {
-        match code[pos] {
-            0x66 | 0x67 | 0xF0 | 0xF2 | 0xF3 | 0x26 | 0x2E | 0x36 | 0x3E | 0x64 | 0x65 => {
-                pos += 1
-            }
-            _ => break,
-        }
-    }
-    // Skip REX prefix
-    if pos < code.len() && (code[pos] & 0xF0) == 0x40 {
-        pos += 1;
-    }
-    pos
-}
-
-// ============================================================================
-// Minimal x86_64 instruction length decoder
-// ============================================================================
-
-#[cfg(target_arch = "x86_64")]
-/// Find the first instruction boundary at or after `min_bytes`.
-fn find_instruction_boundary(code: &[u8], min_bytes: usize) -> usize {
-    let mut offset = 0;
-    while offset < min_bytes && offset < code.len() {
-        let len = x86_64_insn_len(&code[offset..]);
-        if len == 0 {
-            // Can't decode — fall back to min_bytes
-            return min_bytes;
-        }
-        offset += len;
-    }
-    offset
-}
-
-#[cfg(target_arch = "x86_64")]
-/// Returns the byte-length of the x86_64 instruction starting at `code[0]`.
-/// Returns 0 if the instruction cannot be decoded.
-fn x86_64_insn_len(code: &[u8]) -> usize {
-    if code.is_empty() {
-        return 0;
-    }
-
-    let mut pos = 0;
-
-    // Skip legacy prefixes
-    while pos < code.len() {
-        match code[pos] {
-            0x66 | 0x67 | 0xF0 | 0xF2 | 0xF3 | 0x26 | 0x2E | 0x36 | 0x3E | 0x64 | 0x65 => {
-                pos += 1
-            }
-            _ => break,
-        }
-    }
-
-    if pos >= code.len() {
-        return 0;
-    }
-
-    // Check for REX prefix (0x40-0x4F)
-    let has_rex_w = if (code[pos] & 0xF0) == 0x40 {
-        let rex = code[pos];
-        pos += 1;
-        (rex & 0x08) != 0
-    } else {
-        false
-    };
-
-    if pos >= code.len() {
-        return 0;
-    }
-
-    let opcode = code[pos];
-    pos += 1;
-
-    match opcode {
-        // Single byte, no operands
-        0x50..=0x5F | 0x90 | 0xC3 | 0xCC | 0x99 | 0x9E | 0x9F | 0xCB | 0xF4 | 0xF5 | 0xF8
-        | 0xF9 | 0xFC | 0xFD => pos,
-
-        // imm8 operand
-        0x6A | 0x04 | 0x0C | 0x14 | 0x1C | 0x24 | 0x2C | 0x34 | 0x3C | 0xCD | 0xEB | 0xA8 => {
-            pos + 1
-        }
-        0x70..=0x7F => pos + 1, // Jcc rel8
-
-        // imm32 operand
-        0x05 | 0x0D | 0x15 | 0x1D | 0x25 | 0x2D | 0x35 | 0x3D | 0x68 | 0xA9 => pos + 4,
-        0xE8 | 0xE9 => pos + 4, // call/jmp rel32
-
-        // Short jump
-        0xE3 => pos + 1, // JRCXZ rel8
-
-        // MOV AL/AX/EAX/RAX, moffs
-        0xA0 | 0xA1 => pos + if has_rex_w { 8 } else { 4 },
-        0xA2 | 0xA3 => pos + if has_rex_w { 8 } else { 4 },
-
-        // MOV r8, imm8
-        0xB0..=0xB7 => pos + 1,
-
-        // MOV r32/r64, imm32/imm64
-        0xB8..=0xBF => {
-            if has_rex_w {
-                pos + 8
-            } else {
-                pos + 4
-            }
-        }
-
-        // Two-byte opcodes (0x0F prefix)
-        0x0F => {
-            if pos >= code.len() {
-                return 0;
-            }
-            let op2 = code[pos];
-            pos += 1;
-            match op2 {
-                // NOP/ENDBR with ModR/M
-                0x1E | 0x1F => pos + modrm_len(&code[pos..]),
-                // Jcc rel32
-                0x80..=0x8F => pos + 4,
-                // SETcc — ModR/M
-                0x90..=0x9F => pos + modrm_len(&code[pos..]),
-                // MOVZX, MOVSX with ModR/M
-                0xB6 | 0xB7 | 0xBE | 0xBF => pos + modrm_len(&code[pos..]),
-                // CMOVcc with ModR/M
-                0x40..=0x4F => pos + modrm_len(&code[pos..]),
-                // MOVAPS/MOVUPS/MOVAPD/MOVUPD
-                0x10 | 0x11 | 0x28 | 0x29 => pos + modrm_len(&code[pos..]),
-                // XORPS/ANDPS/ORPS etc
-                0x54..=0x59 => pos + modrm_len(&code[pos..]),
-                // Other 0F opcodes with ModR/M (best effort)
-                _ => pos + modrm_len(&code[pos..]),
-            }
-        }
-
-        // Opcodes with ModR/M, no immediate
-        0x00..=0x03
-        | 0x08..=0x0B
-        | 0x10..=0x13
-        | 0x18..=0x1B
-        | 0x20..=0x23
-        | 0x28..=0x2B
-        | 0x30..=0x33
-        | 0x38..=0x3B
-        | 0x62
-        | 0x63
-        | 0x84..=0x8B
-        | 0x8D
-        | 0x8E
-        | 0x8F => pos + modrm_len(&code[pos..]),
-
-        // ALU r/m, imm8
-        0x80 | 0x82 | 0x83 => {
-            if pos < code.len() {
-                pos + modrm_len(&code[pos..]) + 1
-            } else {
-                0
-            }
-        }
-
-        // ALU r/m, imm32
-        0x81 => {
-            if pos < code.len() {
-                pos + modrm_len(&code[pos..]) + 4
-            } else {
-                0
-            }
-        }
-
-        // MOV r/m8, imm8
-        0xC6 => {
-            if pos < code.len() {
-                pos + modrm_len(&code[pos..]) + 1
-            } else {
-                0
-            }
-        }
-
-        // MOV r/m32, imm32
-        0xC7 => {
-            if pos < code.len() {
-                pos + modrm_len(&code[pos..]) + 4
-            } else {
-                0
-            }
-        }
-
-        // TEST r/m, imm (F6/F7 with reg field 0 or 1)
-        0xF6 => {
-            if pos < code.len() {
-                let reg_field = (code[pos] >> 3) & 7;
-                let ml = modrm_len(&code[pos..]);
-                if reg_field < 2 {
-                    pos + ml + 1
-                } else {
-                    pos + ml
-                }
-            } else {
-                0
-            }
-        }
-        0xF7 => {
-            if pos < code.len() {
-                let reg_field = (code[pos] >> 3) & 7;
-                let ml = modrm_len(&code[pos..]);
-                if reg_field < 2 {
-                    pos + ml + 4
-                } else {
-                    pos + ml
-                }
-            } else {
-                0
-            }
-        }
-
-        // SHIFT/ROT with implicit 1 or CL
-        0xD0..=0xD3 => {
-            if pos < code.len() {
-                pos + modrm_len(&code[pos..])
-            } else {
-                0
-            }
-        }
-
-        // SHIFT/ROT with imm8
-        0xC0 | 0xC1 => {
-            if pos < code.len() {
-                pos + modrm_len(&code[pos..]) + 1
-            } else {
-                0
-            }
-        }
-
-        // INC/DEC/CALL/JMP/PUSH with ModR/M
-        0xFE | 0xFF => {
-            if pos < code.len() {
-                pos + modrm_len(&code[pos..])
-            } else {
-                0
-            }
-        }
-
-        // LEAVE, RET imm16, INT3 already covered
-        0xC9 => pos,
-        0xC2 => pos + 2, // RET imm16
-
-        // Unknown opcode — can't decode
-        _ => 0,
-    }
-}
-
-#[cfg(target_arch = "x86_64")]
-/// Decode the byte-length contribution of a ModR/M byte (including SIB and displacement).
-fn modrm_len(code: &[u8]) -> usize {
-    if code.is_empty() {
-        return 1; // Just the ModR/M byte itself, assume register-direct
-    }
-
-    let modrm = code[0];
-    let mod_field = (modrm >> 6) & 3;
-    let rm_field = modrm & 7;
-
-    let mut len = 1; // ModR/M byte
-
-    match mod_field {
-        0b00 => {
-            if rm_field == 0b100 {
-                // SIB byte follows
-                len += 1;
-                if code.len() > 1 && (code[1] & 7) == 0b101 {
-                    len += 4; // SIB with base=101 in mod=00 → disp32
-                }
-            } else if rm_field == 0b101 {
-                len += 4; // RIP-relative: disp32
-            }
-        }
-        0b01 => {
-            if rm_field == 0b100 {
-                len += 1; // SIB byte
-            }
-            len += 1; // disp8
-        }
-        0b10 => {
-            if rm_field == 0b100 {
-                len += 1; // SIB byte
-            }
-            len += 4; // disp32
-        }
-        0b11 => {
-            // Register-direct: no SIB or displacement
-        }
-        _ => unreachable!(),
-    }
-
-    len
-}
-
 // ============================================================================
 // Helper functions
 // ============================================================================
@@ -2008,7 +2364,7 @@ unsafe fn clear_cache_ptr(ptr: *mut u8, size: usize) {
         FlushInstructionCache(process, ptr as *const libc::c_void, size);
     }
 
-    #[cfg(target_os = "linux")]
+    #[cfg(any(target_os = "linux", target_os = "freebsd", target_os = "netbsd"))]
     {
         __clear_cache(ptr, ptr.add(size));
     }
@@ -2019,24 +2375,16 @@ unsafe fn clear_cache_ptr(ptr: *mut u8, size: usize) {
     }
 
     // Synchronize the instruction pipeline on ARM64.
-    #[cfg(target_arch = "aarch64")]
+    #[cfg(any(target_arch = "aarch64", target_arch = "arm64ec"))]
     {
         core::arch::asm!("dsb sy", "isb", options(nostack, nomem));
     }
 }
 
-unsafe fn free_jit_block(ptr: *mut u8, _size: usize) {
+unsafe fn free_jit_block(ptr: *mut u8, size: usize) {
     if ptr.is_null() {
         return;
     }
 
-    #[cfg(any(target_os = "linux", target_os = "macos"))]
-    {
-        libc::munmap(ptr as *mut libc::c_void, _size);
-    }
-
-    #[cfg(target_os = "windows")]
-    {
-        VirtualFree(ptr as *mut libc::c_void, 0, MEM_RELEASE);
-    }
+    free_jit_memory(ptr, size);
 }
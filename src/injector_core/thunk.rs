@@ -0,0 +1,226 @@
+//! Thunks a function address may point to instead of the function body.
+//!
+//! Imported functions are reached through import thunks, linkers put veneers in front of
+//! functions out of branch range, and Windows incremental linking makes the address of every
+//! function a jump in its incremental linking table (ILT). Patching a thunk misses the calls
+//! that do not go through it, and the size check would measure the thunk, so the address is
+//! resolved to the function body first.
+//!
+//! On Linux, PLT stubs and IFUNC resolvers are resolved first, see `plt`. On Windows, the
+//! import slot of a function of a delay-loaded DLL leads to a stub loading the DLL until the
+//! function is first called, so the function is bound first, as that call would.
+//!
+//! Plain jumps (`jmp rel32`, `b`) are only followed on Windows, where the ILT produces them.
+//! Elsewhere a function made of one jump is a tail call of its own, and following it would
+//! fake the callee for all of its callers.
+//!
+//! In ARM64EC processes, x64 code reaches exported ARM64EC functions through a fast-forward
+//! sequence, x64 code ending with a jump to the native body, which is followed too. x64 callers
+//! of other ARM64EC functions go through an entry thunk calling the native body, so patching
+//! the body fakes the function for both x64 and ARM64EC callers.
+
+#[cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec",
+    target_arch = "arm"
+))]
+use crate::injector_core::common::read_bytes;
+
+/// How many thunks are followed before giving up, as a chain may loop.
+const MAX_THUNKS: usize = 8;
+
+/// Returns the address of the function body `func_addr` leads to through thunks.
+pub(crate) fn resolve_thunks(func_addr: usize) -> usize {
+    #[cfg(target_os = "linux")]
+    if let Some(func_addr) = crate::injector_core::redirect::redirected_function(func_addr) {
+        return func_addr;
+    }
+
+    let mut addr = func_addr;
+    for _ in 0..MAX_THUNKS {
+        #[cfg(feature = "external-engine")]
+        if crate::injector_core::redirect::is_hooked(addr) {
+            break;
+        }
+
+        #[cfg(target_os = "linux")]
+        let target = crate::injector_core::plt::implementation(addr)
+            .or_else(|| unsafe { thunk_target(addr) });
+        #[cfg(not(target_os = "linux"))]
+        let target = unsafe { thunk_target(addr) };
+
+        match target {
+            Some(target) => addr = target,
+            None => break,
+        }
+    }
+    addr
+}
+
+/// Returns the target of the thunk at `addr`, or `None` if no thunk is there.
+#[cfg(target_arch = "x86_64")]
+unsafe fn thunk_target(addr: usize) -> Option<usize> {
+    let code = read_bytes(addr as *const u8, 6);
+    let disp = |at: usize| {
+        i32::from_le_bytes([code[at], code[at + 1], code[at + 2], code[at + 3]]) as isize
+    };
+
+    match code[..2] {
+        // jmp [rip+disp32]: import thunks.
+        [0xFF, 0x25] => {
+            let slot = (addr + 6).wrapping_add_signed(disp(2)) as *const usize;
+            #[cfg(windows)]
+            if let Some(target) = crate::injector_core::winapi::bind_delay_import(slot as usize) {
+                return Some(target);
+            }
+            Some(slot.read_unaligned())
+        }
+        // jmp rel32: ILT entries.
+        #[cfg(windows)]
+        [0xE9, _] => Some((addr + 5).wrapping_add_signed(disp(1))),
+        _ => None,
+    }
+}
+
+/// Returns the target of the thunk at `addr`, or `None` if no thunk is there.
+#[cfg(any(target_arch = "aarch64", target_arch = "arm64ec"))]
+unsafe fn thunk_target(addr: usize) -> Option<usize> {
+    #[cfg(target_arch = "arm64ec")]
+    if !crate::injector_core::winapi::is_ec_code(addr) {
+        return Some(fast_forward_target(addr));
+    }
+
+    let code = read_bytes(addr as *const u8, 12);
+    let insn = |i: usize| {
+        u32::from_le_bytes([
+            code[i * 4],
+            code[i * 4 + 1],
+            code[i * 4 + 2],
+            code[i * 4 + 3],
+        ])
+    };
+    let sign_extend =
+        |value: u32, bits: u32| ((value << (32 - bits)) as i32 >> (32 - bits)) as isize;
+
+    // br x16 / br x17, returning the register.
+    let br = |insn: u32| (insn & 0xFFFF_FC1F == 0xD61F_0000).then_some((insn >> 5) & 0x1F);
+
+    // adrp xN, page, returning the register and the page.
+    let adrp = |insn: u32, pc: usize| {
+        (insn & 0x9F00_0000 == 0x9000_0000).then(|| {
+            let imm = ((insn >> 5) & 0x7FFFF) << 2 | (insn >> 29) & 0x3;
+            let page = (pc & !0xFFF).wrapping_add_signed(sign_extend(imm, 21) << 12);
+            (insn & 0x1F, page)
+        })
+    };
+
+    let (first, second, third) = (insn(0), insn(1), insn(2));
+
+    // ldr x16, #8; br x16; .quad target: veneers.
+    if first & 0xFF00_0000 == 0x5800_0000 && br(second) == Some(first & 0x1F) {
+        let literal = addr.wrapping_add_signed(sign_extend((first >> 5) & 0x7FFFF, 19) * 4);
+        return Some((literal as *const usize).read_unaligned());
+    }
+
+    if let Some((reg, page)) = adrp(first, addr) {
+        let rn = (second >> 5) & 0x1F;
+        let rt = second & 0x1F;
+        let imm12 = ((second >> 10) & 0xFFF) as usize;
+        if rn == reg && rt == reg && br(third) == Some(reg) {
+            // adrp x16, page; ldr x16, [x16, #off]; br x16: import thunks.
+            if second & 0xFFC0_0000 == 0xF940_0000 {
+                let slot = page + imm12 * 8;
+                #[cfg(windows)]
+                if let Some(target) = crate::injector_core::winapi::bind_delay_import(slot) {
+                    return Some(target);
+                }
+                return Some((slot as *const usize).read_unaligned());
+            }
+            // adrp x16, page; add x16, x16, #off; br x16: veneers.
+            if second & 0xFFC0_0000 == 0x9100_0000 {
+                return Some(page + imm12);
+            }
+        }
+    }
+
+    // b target: ILT entries.
+    #[cfg(windows)]
+    if first & 0xFC00_0000 == 0x1400_0000 {
+        return Some(addr.wrapping_add_signed(sign_extend(first & 0x03FF_FFFF, 26) * 4));
+    }
+
+    None
+}
+
+/// Returns the target of the veneer at `addr`, or `None` if no veneer is there. Thumb
+/// addresses have their lowest bit set, and so do the targets of veneers switching to Thumb.
+#[cfg(target_arch = "arm")]
+unsafe fn thunk_target(addr: usize) -> Option<usize> {
+    let code_addr = addr & !1;
+    let code = read_bytes(code_addr as *const u8, 16);
+    let word = |at: usize| {
+        u32::from_le_bytes([code[at], code[at + 1], code[at + 2], code[at + 3]]) as usize
+    };
+    let halfword = |at: usize| u16::from_le_bytes([code[at], code[at + 1]]);
+
+    if addr & 1 == 0 {
+        return match (word(0), word(4)) {
+            // ldr pc, [pc, #-4]; .word target: ARM long branch veneers.
+            (0xE51F_F004, target) => Some(target),
+            // ldr ip, [pc]; add pc, pc, ip; .word target - (addr + 12): position independent
+            // ones.
+            (0xE59F_C000, 0xE08F_F00C) => Some((code_addr + 12).wrapping_add(word(8))),
+            _ => None,
+        };
+    }
+
+    let aligned = code_addr.is_multiple_of(4);
+    match (halfword(0), halfword(2)) {
+        // ldr.w pc, [pc, #0] (or #-0); .word target: Thumb-2 long branch veneers.
+        (0xF8DF | 0xF85F, 0xF000) if aligned => Some(word(4)),
+        // bx pc; nop; ldr pc, [pc, #-4]; .word target: veneers from Thumb to ARM state.
+        (0x4778, 0x46C0 | 0xBF00) if aligned && word(4) == 0xE51F_F004 => Some(word(8)),
+        _ => None,
+    }
+}
+
+/// Returns the ARM64EC function the fast-forward sequence at `addr` jumps to.
+///
+/// ```text
+///  0: mov rax, rsp
+///  3: mov [rax+0x20], rbx
+///  7: push rbp
+///  8: pop rbp
+///  9: jmp rel32                 ; the native body
+/// ```
+///
+/// # Panics
+/// Panics if `addr` is other x64 code, which cannot be patched with ARM64 branches.
+#[cfg(target_arch = "arm64ec")]
+unsafe fn fast_forward_target(addr: usize) -> usize {
+    const FAST_FORWARD: [u8; 10] = [0x48, 0x8B, 0xC4, 0x48, 0x89, 0x58, 0x20, 0x55, 0x5D, 0xE9];
+
+    let code = read_bytes(addr as *const u8, 14);
+    if code[..10] != FAST_FORWARD {
+        panic!(
+            "injectorpp: {} is x64 code, which an ARM64EC process emulates, and cannot be \
+             patched. Only ARM64EC functions and the fast-forward sequences of their exports \
+             can be faked.",
+            crate::injector_core::function_size::function_name(addr)
+        );
+    }
+
+    let disp = i32::from_le_bytes([code[10], code[11], code[12], code[13]]);
+    (addr + 14).wrapping_add_signed(disp as isize)
+}
+
+#[cfg(not(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec",
+    target_arch = "arm"
+)))]
+unsafe fn thunk_target(_addr: usize) -> Option<usize> {
+    None
+}
@@ -1,4 +1,4 @@
-#![cfg(target_arch = "aarch64")]
+#![cfg(any(target_arch = "aarch64", target_arch = "arm64ec"))]
 
 /// Convert a u64 value into a [bool; 64] array of bits.
 /// Bit 0 is the least-significant bit.
@@ -1,11 +1,47 @@
 #![cfg(target_os = "windows")]
 
-use core::ffi::c_void;
+use core::ffi::{c_char, c_void};
+
+use crate::injector_core::redirect::{Redirect, Site};
 
 pub(crate) const MEM_COMMIT: u32 = 0x1000;
 pub(crate) const MEM_RESERVE: u32 = 0x2000;
 pub(crate) const PAGE_EXECUTE_READWRITE: u32 = 0x40;
+const PAGE_READWRITE: u32 = 0x04;
 pub(crate) const MEM_RELEASE: u32 = 0x8000;
+/// The state of address space neither reserved nor committed.
+#[cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec"
+))]
+const MEM_FREE: u32 = 0x10000;
+
+/// Marks every location of executable pages allocated with it as an invalid Control Flow Guard
+/// call target.
+const PAGE_TARGETS_INVALID: u32 = 0x4000_0000;
+/// Keeps the Control Flow Guard call targets of pages whose protection `VirtualProtect`
+/// changes, rather than making every location of executable pages a valid one.
+const PAGE_TARGETS_NO_UPDATE: u32 = 0x4000_0000;
+
+/// `ProcessControlFlowGuardPolicy`, the `PROCESS_MITIGATION_POLICY` of Control Flow Guard.
+const PROCESS_CONTROL_FLOW_GUARD_POLICY: i32 = 7;
+/// Marks an offset of `CFG_CALL_TARGET_INFO` as a valid call target.
+const CFG_CALL_TARGET_VALID: usize = 0x1;
+
+/// Looks the module up by an address inside it, rather than by name.
+const GET_MODULE_HANDLE_EX_FLAG_FROM_ADDRESS: u32 = 0x4;
+/// Leaves the reference count of the module unchanged, so it need not be freed.
+const GET_MODULE_HANDLE_EX_FLAG_UNCHANGED_REFCOUNT: u32 = 0x2;
+/// Keeps the module loaded until the process exits, whatever frees it.
+const GET_MODULE_HANDLE_EX_FLAG_PIN: u32 = 0x1;
+
+/// A call target registered by `SetProcessValidCallTargets`, `CFG_CALL_TARGET_INFO`.
+#[repr(C)]
+struct CfgCallTargetInfo {
+    offset: usize,
+    flags: usize,
+}
 
 #[repr(C)]
 struct SystemInfo {
@@ -22,6 +58,39 @@ struct SystemInfo {
     w_processor_revision: u16,
 }
 
+/// A region of pages with the same state, `MEMORY_BASIC_INFORMATION`.
+#[cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec"
+))]
+#[repr(C)]
+struct MemoryBasicInformation {
+    base_address: *mut c_void,
+    allocation_base: *mut c_void,
+    allocation_protect: u32,
+    #[cfg(target_pointer_width = "64")]
+    partition_id: u16,
+    region_size: usize,
+    state: u32,
+    protect: u32,
+    kind: u32,
+}
+
+/// An entry of the function table of a module, `RUNTIME_FUNCTION`.
+#[cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec"
+))]
+#[repr(C)]
+pub(crate) struct RuntimeFunction {
+    pub(crate) begin_address: u32,
+    #[cfg(target_arch = "x86_64")]
+    pub(crate) end_address: u32,
+    pub(crate) unwind_data: u32,
+}
+
 extern "system" {
     pub(crate) fn VirtualProtect(
         lpAddress: *mut c_void,
@@ -48,6 +117,601 @@ extern "system" {
     pub(crate) fn GetCurrentProcess() -> *mut c_void;
 
     fn GetSystemInfo(lpSystemInfo: *mut SystemInfo);
+
+    #[cfg(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec"
+    ))]
+    fn VirtualQuery(
+        lpAddress: *const c_void,
+        lpBuffer: *mut MemoryBasicInformation,
+        dwLength: usize,
+    ) -> usize;
+
+    pub(crate) fn SetLastError(dwErrCode: u32);
+
+    fn GetModuleHandleExW(
+        dwFlags: u32,
+        lpModuleName: *const u16,
+        phModule: *mut *mut c_void,
+    ) -> i32;
+
+    fn GetModuleFileNameW(hModule: *mut c_void, lpFilename: *mut u16, nSize: u32) -> u32;
+
+    fn LoadLibraryW(lpLibFileName: *const u16) -> *mut c_void;
+
+    fn GetProcAddress(hModule: *mut c_void, lpProcName: *const c_char) -> *mut c_void;
+
+    fn K32EnumProcessModules(
+        hProcess: *mut c_void,
+        lphModule: *mut *mut c_void,
+        cb: u32,
+        lpcbNeeded: *mut u32,
+    ) -> i32;
+
+    fn GetProcessMitigationPolicy(
+        hProcess: *mut c_void,
+        MitigationPolicy: i32,
+        lpBuffer: *mut c_void,
+        dwLength: usize,
+    ) -> i32;
+
+    #[cfg(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec"
+    ))]
+    pub(crate) fn RtlLookupFunctionEntry(
+        ControlPc: u64,
+        ImageBase: *mut u64,
+        HistoryTable: *mut c_void,
+    ) -> *const RuntimeFunction;
+}
+
+extern "C" {
+    pub(crate) fn _errno() -> *mut i32;
+}
+
+/// `MemExtendedParameterAttributeFlags`, the type of a `MEM_EXTENDED_PARAMETER` holding
+/// `MEM_EXTENDED_PARAMETER_*` flags.
+#[cfg(target_arch = "arm64ec")]
+const MEM_EXTENDED_PARAMETER_ATTRIBUTE_FLAGS: u64 = 5;
+
+/// Marks memory allocated by `VirtualAlloc2` as ARM64EC code.
+#[cfg(target_arch = "arm64ec")]
+const MEM_EXTENDED_PARAMETER_EC_CODE: u64 = 0x40;
+
+/// An extended parameter of `VirtualAlloc2`, `MEM_EXTENDED_PARAMETER`.
+#[cfg(target_arch = "arm64ec")]
+#[repr(C)]
+struct MemExtendedParameter {
+    kind: u64,
+    value: u64,
+}
+
+#[link(name = "onecore")]
+extern "system" {
+    fn SetProcessValidCallTargets(
+        hProcess: *mut c_void,
+        VirtualAddress: *mut c_void,
+        RegionSize: usize,
+        NumberOfOffsets: u32,
+        OffsetInformation: *mut CfgCallTargetInfo,
+    ) -> i32;
+}
+
+#[cfg(target_arch = "arm64ec")]
+#[link(name = "onecore")]
+extern "system" {
+    fn VirtualAlloc2(
+        Process: *mut c_void,
+        BaseAddress: *mut c_void,
+        Size: usize,
+        AllocationType: u32,
+        PageProtection: u32,
+        ExtendedParameters: *mut MemExtendedParameter,
+        ParameterCount: u32,
+    ) -> *mut c_void;
+}
+
+#[cfg(target_arch = "arm64ec")]
+#[link(name = "ntdll")]
+extern "system" {
+    fn RtlIsEcCode(CodePointer: u64) -> u8;
+}
+
+/// Returns whether the process runs with Control Flow Guard, which checks the targets of the
+/// indirect calls of the code built with it. Probed once.
+pub(crate) fn control_flow_guard_enabled() -> bool {
+    static ENABLED: std::sync::OnceLock<bool> = std::sync::OnceLock::new();
+
+    *ENABLED.get_or_init(|| {
+        // `PROCESS_MITIGATION_CONTROL_FLOW_GUARD_POLICY`, whose first bit enables it.
+        let mut flags: u32 = 0;
+        let found = unsafe {
+            GetProcessMitigationPolicy(
+                GetCurrentProcess(),
+                PROCESS_CONTROL_FLOW_GUARD_POLICY,
+                &mut flags as *mut u32 as *mut c_void,
+                std::mem::size_of::<u32>(),
+            )
+        };
+        found != 0 && flags & 1 != 0
+    })
+}
+
+/// The protection `VirtualProtect` makes functions writable with, keeping their Control Flow
+/// Guard call targets.
+pub(crate) fn code_write_protection() -> u32 {
+    if control_flow_guard_enabled() {
+        PAGE_EXECUTE_READWRITE | PAGE_TARGETS_NO_UPDATE
+    } else {
+        PAGE_EXECUTE_READWRITE
+    }
+}
+
+/// Allocates `size` bytes of read-write-execute memory for JIT code at `addr`, or anywhere
+/// when `addr` is null. Returns null on failure.
+///
+/// Under Control Flow Guard, only the start of the memory is a valid call target, as JIT code
+/// is entered there, for instance by the trampolines calling original functions.
+pub(crate) unsafe fn allocate_code(addr: *mut c_void, size: usize) -> *mut c_void {
+    let protection = if control_flow_guard_enabled() {
+        PAGE_EXECUTE_READWRITE | PAGE_TARGETS_INVALID
+    } else {
+        PAGE_EXECUTE_READWRITE
+    };
+
+    #[cfg(not(target_arch = "arm64ec"))]
+    let ptr = VirtualAlloc(addr, size, MEM_COMMIT | MEM_RESERVE, protection);
+    #[cfg(target_arch = "arm64ec")]
+    let ptr = allocate_ec_code(addr, size, protection);
+
+    if !ptr.is_null() {
+        register_call_target(ptr as usize, size, 0);
+    }
+    ptr
+}
+
+/// Registers the JIT code at `offset` in the `size` bytes of JIT memory allocated at `base` as
+/// a valid Control Flow Guard call target, when the process runs with it.
+pub(crate) unsafe fn register_call_target(base: usize, size: usize, offset: usize) {
+    if !control_flow_guard_enabled() {
+        return;
+    }
+
+    let mut target = CfgCallTargetInfo {
+        offset,
+        flags: CFG_CALL_TARGET_VALID,
+    };
+    let region = size.next_multiple_of(get_page_size());
+    if SetProcessValidCallTargets(
+        GetCurrentProcess(),
+        base as *mut c_void,
+        region,
+        1,
+        &mut target,
+    ) == 0
+    {
+        panic!(
+            "Failed to register the JIT code at {:#x} as a Control Flow Guard call target: {}",
+            base + offset,
+            std::io::Error::last_os_error()
+        );
+    }
+}
+
+/// Allocates `size` bytes of executable memory at `addr` like `VirtualAlloc`, marked as
+/// ARM64EC code. Memory allocated by `VirtualAlloc` is x64 code to an ARM64EC process, so x64
+/// callers and checked indirect calls would emulate the native code written to it.
+#[cfg(target_arch = "arm64ec")]
+unsafe fn allocate_ec_code(addr: *mut c_void, size: usize, protection: u32) -> *mut c_void {
+    let mut parameter = MemExtendedParameter {
+        kind: MEM_EXTENDED_PARAMETER_ATTRIBUTE_FLAGS,
+        value: MEM_EXTENDED_PARAMETER_EC_CODE,
+    };
+    VirtualAlloc2(
+        GetCurrentProcess(),
+        addr,
+        size,
+        MEM_COMMIT | MEM_RESERVE,
+        protection,
+        &mut parameter,
+        1,
+    )
+}
+
+/// Returns whether `addr` is ARM64EC code rather than x64 code emulated in the process.
+#[cfg(target_arch = "arm64ec")]
+pub(crate) fn is_ec_code(addr: usize) -> bool {
+    unsafe { RtlIsEcCode(addr as u64) != 0 }
+}
+
+/// Returns the length in bytes of the code described by a function table entry of the module
+/// loaded at `image_base`.
+#[cfg(target_arch = "x86_64")]
+pub(crate) unsafe fn runtime_function_length(entry: &RuntimeFunction, _image_base: usize) -> usize {
+    entry.end_address.saturating_sub(entry.begin_address) as usize
+}
+
+/// Returns the length in bytes of the code described by a function table entry of the module
+/// loaded at `image_base`. Packed entries hold it in bits 2 to 12 of the unwind data, others
+/// in bits 0 to 17 of the first word of their `.xdata` record, both in 4-byte units.
+#[cfg(any(target_arch = "aarch64", target_arch = "arm64ec"))]
+pub(crate) unsafe fn runtime_function_length(entry: &RuntimeFunction, image_base: usize) -> usize {
+    let words = if entry.unwind_data & 0b11 != 0 {
+        (entry.unwind_data >> 2) & 0x7FF
+    } else {
+        let xdata = (image_base + entry.unwind_data as usize) as *const u32;
+        xdata.read_unaligned() & 0x3FFFF
+    };
+    words as usize * 4
+}
+
+/// Returns the base address, which is its handle, of the module loaded at the range holding
+/// `addr`.
+fn module_base(addr: usize) -> Option<usize> {
+    let mut module = std::ptr::null_mut();
+    let found = unsafe {
+        GetModuleHandleExW(
+            GET_MODULE_HANDLE_EX_FLAG_FROM_ADDRESS | GET_MODULE_HANDLE_EX_FLAG_UNCHANGED_REFCOUNT,
+            addr as *const u16,
+            &mut module,
+        )
+    };
+    (found != 0).then_some(module as usize)
+}
+
+/// Returns the path of the module loaded at the range holding `addr`.
+pub(crate) fn module_path(addr: usize) -> Option<String> {
+    let module = module_base(addr)? as *mut c_void;
+    let mut path = vec![0u16; 1024];
+    let len = unsafe { GetModuleFileNameW(module, path.as_mut_ptr(), path.len() as u32) };
+    (len != 0).then(|| String::from_utf16_lossy(&path[..len as usize]))
+}
+
+/// Returns the base of the module loaded at the range holding `addr`, and the path of the PDB
+/// its CodeView debug directory entry names, as the linker wrote it.
+#[cfg(feature = "debuginfo-check")]
+pub(crate) fn module_pdb(addr: usize) -> Option<(usize, String)> {
+    /// `IMAGE_DIRECTORY_ENTRY_DEBUG`.
+    const DEBUG_DIRECTORY: usize = 6;
+    /// `IMAGE_DEBUG_TYPE_CODEVIEW`.
+    const DEBUG_TYPE_CODEVIEW: u32 = 2;
+
+    let base = module_base(addr)?;
+    let read_u16 = |addr: usize| unsafe { (addr as *const u16).read_unaligned() };
+    let read_u32 = |addr: usize| unsafe { (addr as *const u32).read_unaligned() };
+
+    // IMAGE_NT_HEADERS: signature, IMAGE_FILE_HEADER, then the optional header.
+    let nt_headers = base + read_u32(base + 0x3c) as usize;
+    if read_u32(nt_headers) != 0x4550 {
+        return None;
+    }
+    let optional_header = nt_headers + 24;
+    // NumberOfRvaAndSizes, then the data directories.
+    let directories = match read_u16(optional_header) {
+        0x10b => optional_header + 92,
+        0x20b => optional_header + 108,
+        _ => return None,
+    };
+    if (read_u32(directories) as usize) <= DEBUG_DIRECTORY {
+        return None;
+    }
+    let directory = directories + 4 + DEBUG_DIRECTORY * 8;
+    let rva = read_u32(directory) as usize;
+    let size = read_u32(directory + 4) as usize;
+    if rva == 0 {
+        return None;
+    }
+
+    // IMAGE_DEBUG_DIRECTORY: ..., Type, SizeOfData, AddressOfRawData, PointerToRawData.
+    (base + rva..base + rva + size)
+        .step_by(28)
+        .find_map(|entry| {
+            let data = read_u32(entry + 20) as usize;
+            let data_size = read_u32(entry + 16) as usize;
+            if read_u32(entry + 12) != DEBUG_TYPE_CODEVIEW || data == 0 || data_size <= 24 {
+                return None;
+            }
+            // CV_INFO_PDB70: "RSDS", the GUID and the age, then the path ended by a NUL.
+            let data = base + data;
+            if read_u32(data) != u32::from_le_bytes(*b"RSDS") {
+                return None;
+            }
+            let path =
+                unsafe { std::slice::from_raw_parts((data + 24) as *const u8, data_size - 24) };
+            let len = path.iter().position(|&byte| byte == 0)?;
+            Some(String::from_utf8_lossy(&path[..len]).into_owned())
+        })
+        .map(|path| (base, path))
+}
+
+/// A function a module imports from a delay-loaded DLL, `ImgDelayDescr` and the entries of its
+/// tables for the function.
+#[cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec"
+))]
+struct DelayImport {
+    /// The name of the DLL.
+    dll: *const c_char,
+    /// The name of the function, or its ordinal as `MAKEINTRESOURCE` makes it.
+    function: *const c_char,
+    /// Where the handle of the DLL is kept once it is loaded.
+    module_slot: usize,
+}
+
+/// Returns the delay-loaded import whose import address table slot is `slot`, in the tables of
+/// the module at `base`.
+#[cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec"
+))]
+unsafe fn delay_import(base: usize, slot: usize) -> Option<DelayImport> {
+    /// `IMAGE_DIRECTORY_ENTRY_DELAY_IMPORT`.
+    const DELAY_IMPORT_DIRECTORY: usize = 13;
+    /// Marks a descriptor addressing its tables by RVA, as every linker since VC 7 writes them.
+    const DLATTR_RVA: u32 = 0x1;
+    const ORDINAL_FLAG: usize = 1 << (usize::BITS - 1);
+
+    let read_u16 = |addr: usize| (addr as *const u16).read_unaligned();
+    let read_u32 = |addr: usize| (addr as *const u32).read_unaligned();
+    let read_usize = |addr: usize| (addr as *const usize).read_unaligned();
+    let entry_size = std::mem::size_of::<usize>();
+
+    // IMAGE_NT_HEADERS: signature, IMAGE_FILE_HEADER, then the optional header.
+    let nt_headers = base + read_u32(base + 0x3c) as usize;
+    if read_u32(nt_headers) != 0x4550 {
+        return None;
+    }
+    let optional_header = nt_headers + 24;
+    // NumberOfRvaAndSizes, then the data directories.
+    let directories = match read_u16(optional_header) {
+        0x10b => optional_header + 92,
+        0x20b => optional_header + 108,
+        _ => return None,
+    };
+    if (read_u32(directories) as usize) <= DELAY_IMPORT_DIRECTORY {
+        return None;
+    }
+    let directory = directories + 4 + DELAY_IMPORT_DIRECTORY * 8;
+    let rva = read_u32(directory) as usize;
+    let size = read_u32(directory + 4) as usize;
+    if rva == 0 {
+        return None;
+    }
+
+    // ImgDelayDescr: grAttrs, rvaDLLName, rvaHmod, rvaIAT, rvaINT, ..., ended by a zeroed one.
+    for descriptor in (base + rva..base + rva + size).step_by(32) {
+        let dll_name = read_u32(descriptor + 4) as usize;
+        if dll_name == 0 {
+            break;
+        }
+        if read_u32(descriptor) & DLATTR_RVA == 0 {
+            continue;
+        }
+
+        let iat = base + read_u32(descriptor + 12) as usize;
+        let int = base + read_u32(descriptor + 16) as usize;
+        if slot < iat || !(slot - iat).is_multiple_of(entry_size) {
+            continue;
+        }
+        let index = (slot - iat) / entry_size;
+        // The import name table lists the functions up to a zeroed entry.
+        if (0..=index).any(|i| read_usize(int + i * entry_size) == 0) {
+            continue;
+        }
+
+        let entry = read_usize(int + index * entry_size);
+        let function = if entry & ORDINAL_FLAG != 0 {
+            (entry & 0xFFFF) as *const c_char
+        } else {
+            // IMAGE_IMPORT_BY_NAME: a hint, then the name.
+            (base + entry + 2) as *const c_char
+        };
+        return Some(DelayImport {
+            dll: (base + dll_name) as *const c_char,
+            function,
+            module_slot: base + read_u32(descriptor + 8) as usize,
+        });
+    }
+    None
+}
+
+/// Binds the delay-loaded import whose import address table slot is `slot`, as its first call
+/// would, and returns the function it is bound to. Returns `None` unless `slot` is the slot of
+/// a delay-loaded import, or when the DLL or the function cannot be found.
+///
+/// Until its first call, the slot leads to a stub of the importing module loading the DLL and
+/// rewriting the slot, so a patch of the stub would be bypassed once the function is bound.
+#[cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec"
+))]
+pub(crate) unsafe fn bind_delay_import(slot: usize) -> Option<usize> {
+    let base = module_base(slot)?;
+    let import = delay_import(base, slot)?;
+    let bound = (slot as *const usize).read_unaligned();
+    // The stub is code of the importing module, and the function is not.
+    if module_base(bound) != Some(base) {
+        return Some(bound);
+    }
+
+    let dll: Vec<u16> = std::ffi::CStr::from_ptr(import.dll)
+        .to_string_lossy()
+        .encode_utf16()
+        .chain(Some(0))
+        .collect();
+    // The DLL is never freed, as it would not be once bound by its stub either.
+    let module = LoadLibraryW(dll.as_ptr());
+    if module.is_null() {
+        return None;
+    }
+    let function = GetProcAddress(module, import.function);
+    if function.is_null() {
+        return None;
+    }
+
+    let module_slot = &*(import.module_slot as *const std::sync::atomic::AtomicUsize);
+    let _ = module_slot.compare_exchange(
+        0,
+        module as usize,
+        std::sync::atomic::Ordering::SeqCst,
+        std::sync::atomic::Ordering::SeqCst,
+    );
+
+    // Protected delay loading keeps the import address table read-only.
+    if !store_pointer(slot, function as usize) {
+        return None;
+    }
+    Some(function as usize)
+}
+
+/// Writes `value` to the pointer at `addr` in one store, so concurrent readers read either
+/// value, making its page writable for the write only. Returns whether it was written.
+unsafe fn store_pointer(addr: usize, value: usize) -> bool {
+    let mut old = 0u32;
+    let size = std::mem::size_of::<usize>();
+    if VirtualProtect(addr as *mut c_void, size, PAGE_READWRITE, &mut old) == 0 {
+        return false;
+    }
+    (*(addr as *const std::sync::atomic::AtomicUsize))
+        .store(value, std::sync::atomic::Ordering::SeqCst);
+    VirtualProtect(addr as *mut c_void, size, old, &mut old);
+    true
+}
+
+/// Returns the function held by the COM vtable slot at `addr`.
+pub(crate) unsafe fn read_vtable_slot(addr: usize) -> usize {
+    (*(addr as *const std::sync::atomic::AtomicUsize)).load(std::sync::atomic::Ordering::SeqCst)
+}
+
+/// Writes `value` to the COM vtable slot at `addr`. Vtables are usually in the read-only data
+/// of their module.
+pub(crate) unsafe fn write_vtable_slot(addr: usize, value: usize) {
+    if !store_pointer(addr, value) {
+        panic!("VirtualProtect failed");
+    }
+}
+
+/// Points the COM vtable slot at `slot`, holding the function at `func_addr`, to `target`,
+/// until the returned redirection is dropped.
+///
+/// The module holding the vtable is pinned, so that `CoFreeUnusedLibraries()` cannot unload it
+/// once its objects are released, before the slot is written back.
+pub(crate) fn redirect_vtable_slot(
+    slot: usize,
+    func_addr: usize,
+    target: usize,
+    jit: Vec<(*mut u8, usize)>,
+) -> Redirect {
+    let mut module = std::ptr::null_mut();
+    unsafe {
+        GetModuleHandleExW(
+            GET_MODULE_HANDLE_EX_FLAG_FROM_ADDRESS | GET_MODULE_HANDLE_EX_FLAG_PIN,
+            slot as *const u16,
+            &mut module,
+        )
+    };
+
+    // A slot redirected by an earlier redirection, e.g. a leaked one, is redirected on top of
+    // it.
+    let original = unsafe { read_vtable_slot(slot) };
+    let site = Site::VtableSlot {
+        addr: slot,
+        original,
+        redirected: target,
+    };
+    Redirect::install(func_addr, target, vec![site], jit, "vtable slot")
+}
+
+/// Returns the address at the RVA `offset` of the loaded module `module`, or of the executable
+/// when `None`. Returns `None` unless `len` bytes from there are in an executable section.
+pub(crate) fn code_address(module: Option<&str>, offset: usize, len: usize) -> Option<usize> {
+    const IMAGE_SCN_MEM_EXECUTE: u32 = 0x2000_0000;
+
+    let name: Option<Vec<u16>> =
+        module.map(|module| module.encode_utf16().chain(Some(0)).collect());
+    let mut base = std::ptr::null_mut();
+    let found = unsafe {
+        GetModuleHandleExW(
+            GET_MODULE_HANDLE_EX_FLAG_UNCHANGED_REFCOUNT,
+            name.as_ref().map_or(std::ptr::null(), |name| name.as_ptr()),
+            &mut base,
+        )
+    };
+    if found == 0 {
+        return None;
+    }
+
+    let base = base as usize;
+    let read_u16 = |addr: usize| unsafe { (addr as *const u16).read_unaligned() };
+    let read_u32 = |addr: usize| unsafe { (addr as *const u32).read_unaligned() };
+
+    // IMAGE_NT_HEADERS: signature, IMAGE_FILE_HEADER, then the optional header.
+    let nt_headers = base + read_u32(base + 0x3c) as usize;
+    if read_u32(nt_headers) != 0x4550 {
+        return None;
+    }
+    let sections = read_u16(nt_headers + 6) as usize;
+    let optional_header_size = read_u16(nt_headers + 20) as usize;
+    let end = offset.checked_add(len.max(1))?;
+
+    // IMAGE_SECTION_HEADER: name, virtual size, virtual address, ..., characteristics.
+    let executable = (0..sections).any(|i| {
+        let section = nt_headers + 24 + optional_header_size + i * 40;
+        let start = read_u32(section + 12) as usize;
+        let size = read_u32(section + 8) as usize;
+        read_u32(section + 36) & IMAGE_SCN_MEM_EXECUTE != 0
+            && start <= offset
+            && end <= start + size
+    });
+    executable.then_some(base + offset)
+}
+
+/// Returns the address of the function exported as `symbol` by the DLL `module`, loaded if it
+/// is not yet, or by any loaded module when `None`, the executable first.
+pub(crate) fn exported_address(module: Option<&str>, symbol: &str) -> Option<usize> {
+    let symbol = std::ffi::CString::new(symbol).ok()?;
+    let export = |module: *mut c_void| {
+        let addr = unsafe { GetProcAddress(module, symbol.as_ptr()) };
+        (!addr.is_null()).then_some(addr as usize)
+    };
+
+    if let Some(module) = module {
+        let name: Vec<u16> = module.encode_utf16().chain(Some(0)).collect();
+        // The module is never freed, so it stays loaded while it is patched.
+        let module = unsafe { LoadLibraryW(name.as_ptr()) };
+        if module.is_null() {
+            return None;
+        }
+        return export(module);
+    }
+
+    let mut modules = vec![std::ptr::null_mut(); 256];
+    loop {
+        let mut needed = 0u32;
+        let size = (modules.len() * std::mem::size_of::<*mut c_void>()) as u32;
+        let listed = unsafe {
+            K32EnumProcessModules(GetCurrentProcess(), modules.as_mut_ptr(), size, &mut needed)
+        };
+        if listed == 0 {
+            return None;
+        }
+        if needed <= size {
+            modules.truncate(needed as usize / std::mem::size_of::<*mut c_void>());
+            return modules.into_iter().find_map(export);
+        }
+        modules.resize(
+            needed as usize / std::mem::size_of::<*mut c_void>(),
+            std::ptr::null_mut(),
+        );
+    }
 }
 
 pub(crate) unsafe fn get_page_size() -> usize {
@@ -55,3 +719,74 @@ pub(crate) unsafe fn get_page_size() -> usize {
     GetSystemInfo(&mut sysinfo);
     sysinfo.dw_page_size as usize
 }
+
+/// Returns the addresses within `max_range` of `addr` where the free regions of the address
+/// space fit `size` bytes, aligned to the allocation granularity `VirtualAlloc` reserves
+/// memory at, nearest first. The regions are walked with `VirtualQuery` from `addr` outwards.
+#[cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec"
+))]
+pub(crate) fn free_regions_near(addr: usize, size: usize, max_range: usize) -> Vec<usize> {
+    let (granularity, lowest, highest) = unsafe {
+        let mut sysinfo = core::mem::zeroed::<SystemInfo>();
+        GetSystemInfo(&mut sysinfo);
+        (
+            sysinfo.dw_allocation_granularity as usize,
+            sysinfo.lp_minimum_application_address as usize,
+            sysinfo.lp_maximum_application_address as usize,
+        )
+    };
+    let low = addr.saturating_sub(max_range).max(lowest);
+    let high = addr.saturating_add(max_range).min(highest);
+
+    let query = |at: usize| unsafe {
+        let mut info = core::mem::zeroed::<MemoryBasicInformation>();
+        let written = VirtualQuery(
+            at as *const c_void,
+            &mut info,
+            core::mem::size_of::<MemoryBasicInformation>(),
+        );
+        (written != 0).then_some(info)
+    };
+
+    let mut found = Vec::new();
+    let mut consider = |info: &MemoryBasicInformation| {
+        if info.state != MEM_FREE {
+            return;
+        }
+        let start = (info.base_address as usize).next_multiple_of(granularity);
+        let end = info.base_address as usize + info.region_size;
+        if start.saturating_add(size) > end {
+            return;
+        }
+        // The aligned address of the region nearest to `addr`.
+        let last = (end - size) & !(granularity - 1);
+        let nearest = (addr & !(granularity - 1)).clamp(start, last);
+        if nearest.abs_diff(addr) <= max_range {
+            found.push(nearest);
+        }
+    };
+
+    // Upwards from the region holding `addr`, then downwards from the one below it.
+    let mut at = addr;
+    while at <= high {
+        let Some(info) = query(at) else { break };
+        consider(&info);
+        at = info.base_address as usize + info.region_size;
+    }
+    let mut at = addr;
+    while let Some(below) = query(at).and_then(|info| (info.base_address as usize).checked_sub(1)) {
+        if below < low {
+            break;
+        }
+        let Some(info) = query(below) else { break };
+        consider(&info);
+        at = below;
+    }
+
+    found.sort_unstable_by_key(|&hint| hint.abs_diff(addr));
+    found.dedup();
+    found
+}
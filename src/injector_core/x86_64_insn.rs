@@ -0,0 +1,513 @@
+#![cfg(target_arch = "x86_64")]
+
+//! A minimal x86_64 instruction length decoder, covering the instructions compilers emit in
+//! function prologues. Used to find the whole instructions a patch overwrites.
+
+use crate::injector_core::common::read_bytes;
+
+/// `endbr64`. With CET indirect branch tracking enforced, indirect jumps and calls must land on
+/// it, so compilers start functions with it and JIT code entered indirectly starts with it too.
+pub(crate) const ENDBR64: [u8; 4] = [0xF3, 0x0F, 0x1E, 0xFA];
+
+/// Returns the length of the whole instructions at the start of `code` that a patch of
+/// `patch_size` bytes overwrites, or `None` if one of them cannot be decoded.
+pub(crate) fn overwritten_len(code: &[u8], patch_size: usize) -> Option<usize> {
+    let mut offset = 0;
+    while offset < patch_size {
+        let len = insn_len(code.get(offset..)?);
+        if len == 0 {
+            return None;
+        }
+        offset += len;
+    }
+    Some(offset)
+}
+
+/// Find the byte offset of the disp32 field in a RIP-relative instruction.
+/// Returns None if the instruction doesn't use RIP-relative addressing.
+pub(crate) fn find_rip_relative_disp_offset(insn: &[u8], _insn_len: usize) -> Option<usize> {
+    let mut pos = skip_prefixes(insn);
+    if pos >= insn.len() {
+        return None;
+    }
+
+    let opcode = insn[pos];
+    pos += 1;
+
+    // Two-byte opcode
+    if opcode == 0x0F {
+        if pos >= insn.len() {
+            return None;
+        }
+        let op2 = insn[pos];
+        pos += 1;
+        // Jcc rel32 (0F 80-8F) don't use ModR/M RIP-relative, skip
+        if (0x80..=0x8F).contains(&op2) {
+            return None;
+        }
+        // Most other 0F xx opcodes have a ModR/M byte — fall through to check
+    } else {
+        // Single-byte opcodes: check if they have a ModR/M byte
+        match opcode {
+            // Opcodes that do NOT have ModR/M — skip
+            0x50..=0x5F
+            | 0x90
+            | 0xC3
+            | 0xCC
+            | 0xCB
+            | 0xC9
+            | 0xF4
+            | 0xF5
+            | 0xF8
+            | 0xF9
+            | 0xFC
+            | 0xFD
+            | 0x99
+            | 0x9E
+            | 0x9F => return None,
+            0x6A | 0x04 | 0x0C | 0x14 | 0x1C | 0x24 | 0x2C | 0x34 | 0x3C | 0xCD | 0xEB | 0xA8 => {
+                return None
+            }
+            0x70..=0x7F => return None, // Jcc rel8
+            0x05 | 0x0D | 0x15 | 0x1D | 0x25 | 0x2D | 0x35 | 0x3D | 0x68 | 0xA9 => return None,
+            0xE8 | 0xE9 | 0xE3 => return None, // call/jmp rel32, JRCXZ
+            0xA0..=0xA3 => return None,        // MOV AL/AX moffs
+            0xB0..=0xBF => return None,        // MOV reg, imm
+            0xC2 => return None,               // RET imm16
+            _ => {
+                // Assume has ModR/M — fall through
+            }
+        }
+    }
+
+    // pos now points to the ModR/M byte
+    if pos >= insn.len() {
+        return None;
+    }
+
+    let modrm = insn[pos];
+    let mod_field = (modrm >> 6) & 3;
+    let rm_field = modrm & 7;
+
+    if mod_field == 0b00 && rm_field == 0b101 {
+        // RIP-relative: disp32 starts right after the ModR/M byte
+        Some(pos + 1)
+    } else {
+        None
+    }
+}
+
+/// Skip legacy prefixes and REX prefix, return the position of the opcode byte.
+pub(crate) fn skip_prefixes(code: &[u8]) -> usize {
+    let mut pos = 0;
+    // Skip legacy prefixes
+    while pos < code.len() {
+        match code[pos] {
+            0x66 | 0x67 | 0xF0 | 0xF2 | 0xF3 | 0x26 | 0x2E | 0x36 | 0x3E | 0x64 | 0x65 => pos += 1,
+            _ => break,
+        }
+    }
+    // Skip REX prefix
+    if pos < code.len() && (code[pos] & 0xF0) == 0x40 {
+        pos += 1;
+    }
+    pos
+}
+
+/// Returns the byte-length of the x86_64 instruction starting at `code[0]`.
+/// Returns 0 if the instruction cannot be decoded.
+#[allow(clippy::collapsible_match)]
+pub(crate) fn insn_len(code: &[u8]) -> usize {
+    if code.is_empty() {
+        return 0;
+    }
+
+    let mut pos = 0;
+
+    // Skip legacy prefixes
+    while pos < code.len() {
+        match code[pos] {
+            0x66 | 0x67 | 0xF0 | 0xF2 | 0xF3 | 0x26 | 0x2E | 0x36 | 0x3E | 0x64 | 0x65 => pos += 1,
+            _ => break,
+        }
+    }
+
+    if pos >= code.len() {
+        return 0;
+    }
+
+    // Check for REX prefix (0x40-0x4F)
+    let has_rex_w = if (code[pos] & 0xF0) == 0x40 {
+        let rex = code[pos];
+        pos += 1;
+        (rex & 0x08) != 0
+    } else {
+        false
+    };
+
+    if pos >= code.len() {
+        return 0;
+    }
+
+    let opcode = code[pos];
+    pos += 1;
+
+    match opcode {
+        // Single byte, no operands
+        0x50..=0x5F
+        | 0x90..=0x99
+        | 0x9C..=0x9F
+        | 0xC3
+        | 0xCC
+        | 0xCB
+        | 0xF4
+        | 0xF5
+        | 0xF8
+        | 0xF9
+        | 0xFC
+        | 0xFD => pos,
+
+        // String instructions
+        0xA4..=0xA7 | 0xAA..=0xAF => pos,
+
+        // imm8 operand
+        0x6A | 0x04 | 0x0C | 0x14 | 0x1C | 0x24 | 0x2C | 0x34 | 0x3C | 0xCD | 0xEB | 0xA8 => {
+            pos + 1
+        }
+        0x70..=0x7F => pos + 1, // Jcc rel8
+
+        // imm32 operand
+        0x05 | 0x0D | 0x15 | 0x1D | 0x25 | 0x2D | 0x35 | 0x3D | 0x68 | 0xA9 => pos + 4,
+        0xE8 | 0xE9 => pos + 4, // call/jmp rel32
+
+        // Short jump
+        0xE0..=0xE3 => pos + 1, // LOOPcc/JRCXZ rel8
+
+        // MOV AL/AX/EAX/RAX, moffs: the offset has the address size, 64 bits unless 0x67
+        0xA0..=0xA3 => pos + if code[..pos].contains(&0x67) { 4 } else { 8 },
+
+        // MOV r8, imm8
+        0xB0..=0xB7 => pos + 1,
+
+        // MOV r32/r64, imm32/imm64
+        0xB8..=0xBF => {
+            if has_rex_w {
+                pos + 8
+            } else {
+                pos + 4
+            }
+        }
+
+        // Two-byte opcodes (0x0F prefix)
+        0x0F => {
+            if pos >= code.len() {
+                return 0;
+            }
+            let op2 = code[pos];
+            pos += 1;
+            match op2 {
+                // SYSCALL, UD2, RDTSC, CPUID, PUSH/POP FS/GS, BSWAP — no ModR/M
+                0x05 | 0x0B | 0x31 | 0xA0 | 0xA1 | 0xA2 | 0xA8 | 0xA9 | 0xC8..=0xCF => pos,
+                // Three-byte opcodes, with ModR/M
+                0x38 => pos + 1 + modrm_len(code.get(pos + 1..).unwrap_or(&[])),
+                0x3A => pos + 1 + modrm_len(code.get(pos + 1..).unwrap_or(&[])) + 1,
+                // PSHUFD/shifts, SHLD/SHRD, BT imm8, CMPPS, PINSRW, PEXTRW, SHUFPS — ModR/M + imm8
+                0x70..=0x73 | 0xA4 | 0xAC | 0xBA | 0xC2 | 0xC4..=0xC6 => {
+                    pos + modrm_len(&code[pos..]) + 1
+                }
+                // NOP/ENDBR with ModR/M
+                0x1E | 0x1F => pos + modrm_len(&code[pos..]),
+                // Jcc rel32
+                0x80..=0x8F => pos + 4,
+                // SETcc — ModR/M
+                0x90..=0x9F => pos + modrm_len(&code[pos..]),
+                // MOVZX, MOVSX with ModR/M
+                0xB6 | 0xB7 | 0xBE | 0xBF => pos + modrm_len(&code[pos..]),
+                // CMOVcc with ModR/M
+                0x40..=0x4F => pos + modrm_len(&code[pos..]),
+                // MOVAPS/MOVUPS/MOVAPD/MOVUPD
+                0x10 | 0x11 | 0x28 | 0x29 => pos + modrm_len(&code[pos..]),
+                // XORPS/ANDPS/ORPS etc
+                0x54..=0x59 => pos + modrm_len(&code[pos..]),
+                // Other 0F opcodes with ModR/M (best effort)
+                _ => pos + modrm_len(&code[pos..]),
+            }
+        }
+
+        // Opcodes with ModR/M, no immediate
+        0x00..=0x03
+        | 0x08..=0x0B
+        | 0x10..=0x13
+        | 0x18..=0x1B
+        | 0x20..=0x23
+        | 0x28..=0x2B
+        | 0x30..=0x33
+        | 0x38..=0x3B
+        | 0x62
+        | 0x63
+        | 0x84..=0x8B
+        | 0x8D
+        | 0x8E
+        | 0x8F => pos + modrm_len(&code[pos..]),
+
+        // ALU r/m, imm8
+        0x80 | 0x82 | 0x83 => {
+            if pos < code.len() {
+                pos + modrm_len(&code[pos..]) + 1
+            } else {
+                0
+            }
+        }
+
+        // IMUL r, r/m, imm32 / imm8
+        0x69 => {
+            if pos < code.len() {
+                pos + modrm_len(&code[pos..]) + 4
+            } else {
+                0
+            }
+        }
+        0x6B => {
+            if pos < code.len() {
+                pos + modrm_len(&code[pos..]) + 1
+            } else {
+                0
+            }
+        }
+
+        // ALU r/m, imm32
+        0x81 => {
+            if pos < code.len() {
+                pos + modrm_len(&code[pos..]) + 4
+            } else {
+                0
+            }
+        }
+
+        // MOV r/m8, imm8
+        0xC6 => {
+            if pos < code.len() {
+                pos + modrm_len(&code[pos..]) + 1
+            } else {
+                0
+            }
+        }
+
+        // MOV r/m32, imm32
+        0xC7 => {
+            if pos < code.len() {
+                pos + modrm_len(&code[pos..]) + 4
+            } else {
+                0
+            }
+        }
+
+        // TEST r/m, imm (F6/F7 with reg field 0 or 1)
+        0xF6 => {
+            if pos < code.len() {
+                let reg_field = (code[pos] >> 3) & 7;
+                let ml = modrm_len(&code[pos..]);
+                if reg_field < 2 {
+                    pos + ml + 1
+                } else {
+                    pos + ml
+                }
+            } else {
+                0
+            }
+        }
+        0xF7 => {
+            if pos < code.len() {
+                let reg_field = (code[pos] >> 3) & 7;
+                let ml = modrm_len(&code[pos..]);
+                if reg_field < 2 {
+                    pos + ml + 4
+                } else {
+                    pos + ml
+                }
+            } else {
+                0
+            }
+        }
+
+        // SHIFT/ROT with implicit 1 or CL
+        0xD0..=0xD3 => {
+            if pos < code.len() {
+                pos + modrm_len(&code[pos..])
+            } else {
+                0
+            }
+        }
+
+        // SHIFT/ROT with imm8
+        0xC0 | 0xC1 => {
+            if pos < code.len() {
+                pos + modrm_len(&code[pos..]) + 1
+            } else {
+                0
+            }
+        }
+
+        // INC/DEC/CALL/JMP/PUSH with ModR/M
+        0xFE | 0xFF => {
+            if pos < code.len() {
+                pos + modrm_len(&code[pos..])
+            } else {
+                0
+            }
+        }
+
+        // LEAVE, RET imm16, INT3 already covered
+        0xC9 => pos,
+        0xC2 => pos + 2, // RET imm16
+        0xC8 => pos + 3, // ENTER imm16, imm8
+
+        // Unknown opcode — can't decode
+        _ => 0,
+    }
+}
+
+/// Decode the byte-length contribution of a ModR/M byte (including SIB and displacement).
+fn modrm_len(code: &[u8]) -> usize {
+    if code.is_empty() {
+        return 1; // Just the ModR/M byte itself, assume register-direct
+    }
+
+    let modrm = code[0];
+    let mod_field = (modrm >> 6) & 3;
+    let rm_field = modrm & 7;
+
+    let mut len = 1; // ModR/M byte
+
+    match mod_field {
+        0b00 => {
+            if rm_field == 0b100 {
+                // SIB byte follows
+                len += 1;
+                if code.len() > 1 && (code[1] & 7) == 0b101 {
+                    len += 4; // SIB with base=101 in mod=00 → disp32
+                }
+            } else if rm_field == 0b101 {
+                len += 4; // RIP-relative: disp32
+            }
+        }
+        0b01 => {
+            if rm_field == 0b100 {
+                len += 1; // SIB byte
+            }
+            len += 1; // disp8
+        }
+        0b10 => {
+            if rm_field == 0b100 {
+                len += 1; // SIB byte
+            }
+            len += 4; // disp32
+        }
+        0b11 => {
+            // Register-direct: no SIB or displacement
+        }
+        _ => unreachable!(),
+    }
+
+    len
+}
+
+/// Returns the displacement of a relative jump or call, added to the address of the next
+/// instruction, or `None` if `insn` is not one.
+pub(crate) fn branch_displacement(insn: &[u8]) -> Option<isize> {
+    let pos = skip_prefixes(insn);
+    let rel8 = |at: usize| insn.get(at).map(|&b| b as i8 as isize);
+    let rel32 = |at: usize| {
+        insn.get(at..at + 4)
+            .map(|b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as isize)
+    };
+
+    match *insn.get(pos)? {
+        0x70..=0x7F | 0xE0..=0xE3 | 0xEB => rel8(pos + 1),
+        0xE8 | 0xE9 => rel32(pos + 1),
+        0x0F if (0x80..=0x8F).contains(insn.get(pos + 1)?) => rel32(pos + 2),
+        _ => None,
+    }
+}
+
+/// Panics if the function at `func_addr`, of `size` bytes, branches into the middle of the
+/// instructions a patch of `patch_size` bytes overwrites. A thread taking the branch would run
+/// the patch from one of its middle bytes. The function is decoded up to the first instruction
+/// the decoder does not know.
+pub(crate) fn check_branches_into_patch(func_addr: usize, size: usize, patch_size: usize) {
+    let code = unsafe { read_bytes(func_addr as *const u8, size) };
+
+    let mut starts = Vec::new();
+    let mut offset = 0;
+    while offset < code.len() {
+        let len = insn_len(&code[offset..]);
+        if len == 0 || offset + len > code.len() {
+            return;
+        }
+
+        // Instructions starting in the patch are overwritten and never run. A patch keeps the
+        // `endbr64` starting a function, so its branch starts right after it.
+        if offset < patch_size && !(offset == ENDBR64.len() && code.starts_with(&ENDBR64)) {
+            starts.push(offset);
+        } else if let Some(displacement) = branch_displacement(&code[offset..offset + len]) {
+            let target = (offset + len) as isize + displacement;
+            if target > 0 && starts.contains(&(target as usize)) {
+                let name = crate::injector_core::function_size::function_name(func_addr);
+                panic!(
+                    "injectorpp: {} branches back to offset {}, inside the {}-byte patch, so \
+                     a thread taking the branch would run the middle of the patch. Call \
+                     allow_undersized_patch() if the branch is never taken while the fake is \
+                     installed.",
+                    name, target, patch_size
+                );
+            }
+        }
+
+        offset += len;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overwritten_len_covers_whole_instructions() {
+        let prologue = [
+            0xF3, 0x0F, 0x1E, 0xFA, // endbr64
+            0x55, // push rbp
+            0x48, 0x89, 0xE5, // mov rbp, rsp
+            0x48, 0x83, 0xEC, 0x20, // sub rsp, 0x20
+            0x48, 0x8B, 0x05, 0x10, 0x00, 0x00, 0x00, // mov rax, [rip+0x10]
+        ];
+
+        assert_eq!(overwritten_len(&prologue, 5), Some(5));
+        assert_eq!(overwritten_len(&prologue, 6), Some(8));
+        assert_eq!(overwritten_len(&prologue, 14), Some(19));
+        assert_eq!(overwritten_len(&[0x55, 0x06], 5), None);
+    }
+
+    #[test]
+    fn test_insn_len_of_moffs_and_three_byte_opcodes() {
+        assert_eq!(insn_len(&[0xA1, 0, 0, 0, 0, 0, 0, 0, 0]), 9);
+        assert_eq!(insn_len(&[0x67, 0xA1, 0, 0, 0, 0]), 6);
+        assert_eq!(insn_len(&[0x66, 0x0F, 0x38, 0x00, 0xC1]), 5); // pshufb xmm0, xmm1
+        assert_eq!(insn_len(&[0x66, 0x0F, 0x3A, 0x0F, 0xC1, 0x08]), 6); // palignr
+        assert_eq!(insn_len(&[0x0F, 0x0B]), 2); // ud2
+    }
+
+    #[test]
+    fn test_branch_displacement() {
+        assert_eq!(branch_displacement(&[0xEB, 0xFE]), Some(-2));
+        assert_eq!(branch_displacement(&[0x75, 0x10]), Some(0x10));
+        assert_eq!(
+            branch_displacement(&[0xE9, 0x00, 0x01, 0x00, 0x00]),
+            Some(0x100)
+        );
+        assert_eq!(
+            branch_displacement(&[0x0F, 0x84, 0xF0, 0xFF, 0xFF, 0xFF]),
+            Some(-16)
+        );
+        assert_eq!(branch_displacement(&[0xFF, 0xE0]), None);
+    }
+}
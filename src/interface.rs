@@ -1,4 +1,30 @@
+#[cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec",
+    target_arch = "arm"
+))]
+mod async_fn;
+mod boxed_closure;
+mod c_symbol;
+mod caller;
+mod cassette;
+mod cxx_symbol;
+mod deny_list;
+mod do_not_fake;
+mod expect;
+mod fake_handle;
+mod fake_set;
+mod fault_injector;
+mod fixture;
 mod func_ptr;
 pub mod injector;
+mod labels;
+mod lock_timeout;
 mod macros;
+mod mock;
+mod patch_info;
+mod patch_strategy;
+mod rust_symbol;
+mod scenario;
 mod verifier;
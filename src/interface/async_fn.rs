@@ -0,0 +1,293 @@
+//! Support for async fakes that receive the arguments of the faked async function.
+//!
+//! An `async fn` is made of two functions: the function itself, which only stores its
+//! arguments in a future, and the future's `poll`, which runs the body. To give a fake
+//! access to the arguments both are patched:
+//!
+//! - The function is replaced by a shim that passes a copy of the arguments to the fake
+//!   closure, then builds the real future by calling the original function. The future
+//!   returned by the fake closure is queued.
+//! - `poll` is replaced by a shim that drives the queued fake future instead of the body.
+//!
+//! A queued fake future is matched to the first real future polled whose bytes are equal to
+//! those of the future built for that call, or else to the oldest queued one. This relies on
+//! the patched `poll` never touching the real future, so its bytes stay the same until it is
+//! dropped.
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use crate::injector_core::thread_local_registry;
+
+type PollFn<P> = fn(
+    Pin<&mut <P as AsyncFnPointer>::Fut>,
+    &mut Context<'_>,
+) -> Poll<<<P as AsyncFnPointer>::Fut as Future>::Output>;
+type BoxedFuture<T> = Pin<Box<dyn Future<Output = T>>>;
+type BoxedFake<P> = Box<
+    dyn FnMut(
+        <P as AsyncFnPointer>::Args,
+    ) -> BoxedFuture<<<P as AsyncFnPointer>::Fut as Future>::Output>,
+>;
+
+/// A function pointer to an `async fn`, as returned by `async_fn!`.
+///
+/// Implemented for function pointers of up to eight arguments returning a future.
+pub trait AsyncFnPointer: Copy + 'static {
+    /// The arguments of the function, as a tuple.
+    type Args: Clone + 'static;
+
+    /// The future returned by the function.
+    type Fut: Future<Output: 'static> + 'static;
+
+    #[doc(hidden)]
+    fn __addr(self) -> *const ();
+
+    #[doc(hidden)]
+    fn __shim() -> *const ();
+
+    #[doc(hidden)]
+    fn __call(self, args: Self::Args) -> Self::Fut;
+}
+
+macro_rules! impl_async_fn_pointer {
+    ($shim:ident; $($arg:ident $name:ident),*) => {
+        impl<$($arg: Clone + 'static,)* Fut: Future<Output: 'static> + 'static> AsyncFnPointer
+            for fn($($arg),*) -> Fut
+        {
+            type Args = ($($arg,)*);
+            type Fut = Fut;
+
+            fn __addr(self) -> *const () {
+                self as *const ()
+            }
+
+            fn __shim() -> *const () {
+                let shim: fn($($arg),*) -> Fut = $shim::<$($arg,)* Fut>;
+                shim as *const ()
+            }
+
+            #[allow(clippy::unused_unit)]
+            fn __call(self, args: Self::Args) -> Fut {
+                let ($($name,)*) = args;
+                self($($name),*)
+            }
+        }
+
+        #[allow(clippy::too_many_arguments)]
+        fn $shim<$($arg: Clone + 'static,)* Fut: Future<Output: 'static> + 'static>(
+            $($name: $arg),*
+        ) -> Fut {
+            call_fake::<fn($($arg),*) -> Fut>(($($name,)*))
+        }
+    };
+}
+
+impl_async_fn_pointer!(shim0;);
+impl_async_fn_pointer!(shim1; A0 a0);
+impl_async_fn_pointer!(shim2; A0 a0, A1 a1);
+impl_async_fn_pointer!(shim3; A0 a0, A1 a1, A2 a2);
+impl_async_fn_pointer!(shim4; A0 a0, A1 a1, A2 a2, A3 a3);
+impl_async_fn_pointer!(shim5; A0 a0, A1 a1, A2 a2, A3 a3, A4 a4);
+impl_async_fn_pointer!(shim6; A0 a0, A1 a1, A2 a2, A3 a3, A4 a4, A5 a5);
+impl_async_fn_pointer!(shim7; A0 a0, A1 a1, A2 a2, A3 a3, A4 a4, A5 a5, A6 a6);
+impl_async_fn_pointer!(shim8; A0 a0, A1 a1, A2 a2, A3 a3, A4 a4, A5 a5, A6 a6, A7 a7);
+
+/// Checks the arguments and output of an async function pointer. Used internally by `async_fn!`.
+#[doc(hidden)]
+pub fn __async_fn_target<P, Args, Output>(target: P) -> P
+where
+    P: AsyncFnPointer<Args = Args>,
+    P::Fut: Future<Output = Output>,
+{
+    target
+}
+
+struct AsyncFakeEntry {
+    /// The async function pointer, a `P`.
+    target: Box<dyn Any>,
+    /// Patched address of the async function.
+    method_key: usize,
+    /// Patched address of the future's `poll`.
+    poll_key: usize,
+    /// The fake closure, a `BoxedFake<P>`. Taken out while it runs.
+    fake: Option<Box<dyn Any>>,
+    /// Fake futures of calls whose future has not been polled yet, with the bytes of that future.
+    pending: VecDeque<(Vec<u8>, Box<dyn Any>)>,
+    /// Fake futures being driven, with the address and bytes of the future they stand for.
+    active: Vec<(usize, Vec<u8>, Box<dyn Any>)>,
+}
+
+thread_local! {
+    /// Async fakes installed on this thread, keyed by the address of the function shim.
+    static ASYNC_FAKES: RefCell<HashMap<usize, AsyncFakeEntry>> = RefCell::new(HashMap::new());
+}
+
+/// Removes the async fake on drop, dropping its closure and pending futures.
+pub(crate) struct AsyncFakeRegistration {
+    key: usize,
+}
+
+impl Drop for AsyncFakeRegistration {
+    fn drop(&mut self) {
+        let removed = ASYNC_FAKES
+            .try_with(|fakes| fakes.borrow_mut().remove(&self.key))
+            .ok()
+            .flatten();
+
+        drop(removed);
+    }
+}
+
+/// Registers `fake` for `target` on the current thread.
+pub(crate) fn register<P: AsyncFnPointer>(
+    target: P,
+    method_key: usize,
+    poll_key: usize,
+    fake: BoxedFake<P>,
+) -> AsyncFakeRegistration {
+    let key = P::__shim() as usize;
+    ASYNC_FAKES.with(|fakes| {
+        let mut fakes = fakes.borrow_mut();
+        if fakes.contains_key(&key) {
+            panic!("The async function is already faked with will_execute_async on this thread");
+        }
+
+        fakes.insert(
+            key,
+            AsyncFakeEntry {
+                target: Box::new(target),
+                method_key,
+                poll_key,
+                fake: Some(Box::new(fake)),
+                pending: VecDeque::new(),
+                active: Vec::new(),
+            },
+        );
+    });
+
+    AsyncFakeRegistration { key }
+}
+
+/// Returns the future's `poll`.
+pub(crate) fn poll_fn<P: AsyncFnPointer>() -> *const () {
+    let poll: PollFn<P> = <P::Fut as Future>::poll;
+    poll as *const ()
+}
+
+/// Returns the shim that replaces the future's `poll`.
+pub(crate) fn poll_shim<P: AsyncFnPointer>() -> *const () {
+    let shim: PollFn<P> = poll_fake::<P>;
+    shim as *const ()
+}
+
+fn snapshot<T>(value: &T) -> Vec<u8> {
+    // The patched `poll` never writes to the future, so its bytes identify it.
+    unsafe { std::slice::from_raw_parts(value as *const T as *const u8, size_of::<T>()) }.to_vec()
+}
+
+fn call_fake<P: AsyncFnPointer>(args: P::Args) -> P::Fut {
+    let key = P::__shim() as usize;
+
+    let (target, method_key, mut fake) = ASYNC_FAKES.with(|fakes| {
+        let mut fakes = fakes.borrow_mut();
+        let entry = fakes
+            .get_mut(&key)
+            .expect("Async fake was called after its injector was dropped");
+        let fake = entry
+            .fake
+            .take()
+            .expect("Async fake was called re-entrantly. The fake closure must not call the function it fakes");
+        let target = *entry
+            .target
+            .downcast_ref::<P>()
+            .expect("Async fake target type does not match its shim");
+
+        (target, entry.method_key, fake)
+    });
+
+    let future = fake
+        .downcast_mut::<BoxedFake<P>>()
+        .expect("Async fake closure type does not match its shim")(args.clone());
+
+    let real = thread_local_registry::call_original(method_key, || target.__call(args));
+    let bytes = snapshot(&real);
+
+    ASYNC_FAKES.with(|fakes| {
+        if let Some(entry) = fakes.borrow_mut().get_mut(&key) {
+            entry.fake = Some(fake);
+            entry.pending.push_back((bytes, Box::new(future)));
+        }
+    });
+
+    real
+}
+
+fn poll_fake<P: AsyncFnPointer>(
+    fut: Pin<&mut P::Fut>,
+    cx: &mut Context<'_>,
+) -> Poll<<P::Fut as Future>::Output> {
+    let key = P::__shim() as usize;
+    let addr = &*fut as *const P::Fut as usize;
+    let bytes = snapshot(&*fut);
+
+    let mut stale = Vec::new();
+    let found = ASYNC_FAKES.with(|fakes| {
+        let mut fakes = fakes.borrow_mut();
+        let entry = fakes.get_mut(&key)?;
+
+        if let Some(pos) = entry
+            .active
+            .iter()
+            .position(|(a, b, _)| *a == addr && *b == bytes)
+        {
+            return Some(Ok(entry.active.swap_remove(pos).2));
+        }
+
+        // A different future was dropped at this address before completing.
+        while let Some(pos) = entry.active.iter().position(|(a, _, _)| *a == addr) {
+            stale.push(entry.active.swap_remove(pos));
+        }
+
+        let pos = entry
+            .pending
+            .iter()
+            .position(|(b, _)| *b == bytes)
+            .unwrap_or(0);
+
+        match entry.pending.remove(pos) {
+            Some((_, future)) => Some(Ok(future)),
+            None => Some(Err(entry.poll_key)),
+        }
+    });
+    drop(stale);
+
+    let mut future = match found {
+        Some(Ok(future)) => future,
+        // The future was created before the fake was installed, run the real body.
+        Some(Err(poll_key)) => {
+            return thread_local_registry::call_original(poll_key, || fut.poll(cx));
+        }
+        None => panic!("Async fake was polled after its injector was dropped"),
+    };
+
+    let result = future
+        .downcast_mut::<BoxedFuture<<P::Fut as Future>::Output>>()
+        .expect("Async fake future type does not match its shim")
+        .as_mut()
+        .poll(cx);
+
+    if result.is_pending() {
+        ASYNC_FAKES.with(|fakes| {
+            if let Some(entry) = fakes.borrow_mut().get_mut(&key) {
+                entry.active.push((addr, bytes, future));
+            }
+        });
+    }
+
+    result
+}
@@ -0,0 +1,188 @@
+//! Support for capturing closures as fakes.
+//!
+//! A capturing closure cannot be coerced to a function pointer, so `boxed_closure!`
+//! generates small shim functions with the target signature for every call site, one per
+//! slot. The closure itself is boxed and stored in a registry keyed by the address of the
+//! shim of the slot it is installed in. When the faked function is called, the shim looks
+//! up the closure and forwards its arguments.
+//!
+//! Every closure installed takes the first slot of its call site no other closure visible on
+//! the same threads uses, so a helper creating its fakes with one `boxed_closure!` can install
+//! up to eight of them at once. Thread-local fakes register the closure for the current
+//! thread only, so each thread has its own slots. Global fakes register it for every thread.
+
+use crate::interface::func_ptr::FuncPtr;
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock, Mutex, MutexGuard};
+use std::thread::ThreadId;
+
+/// A capturing closure together with the shims that can call it.
+///
+/// Created by the `boxed_closure!` macro and consumed by `will_execute_closure`.
+pub struct BoxedClosure {
+    pub(super) shims: Vec<FuncPtr>,
+    pub(super) closure: Box<dyn Any + Send>,
+}
+
+impl BoxedClosure {
+    /// Creates a `BoxedClosure`. Used internally by `boxed_closure!`.
+    ///
+    /// # Safety
+    ///
+    /// `shims` must be the shims of every slot generated by `boxed_closure!` for a closure of
+    /// the same type as `closure`.
+    #[doc(hidden)]
+    pub unsafe fn __new(shims: Vec<FuncPtr>, closure: Box<dyn Any + Send>) -> Self {
+        Self { shims, closure }
+    }
+}
+
+type SharedClosure = Arc<Mutex<Box<dyn Any + Send>>>;
+
+struct ClosureEntry {
+    id: u64,
+    /// The thread the closure is visible on, or `None` for global fakes.
+    owner: Option<ThreadId>,
+    closure: SharedClosure,
+}
+
+static CLOSURES: LazyLock<Mutex<HashMap<usize, Vec<ClosureEntry>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    /// Closures currently executing on this thread, used to detect re-entrant calls.
+    static ACTIVE: RefCell<Vec<u64>> = const { RefCell::new(Vec::new()) };
+}
+
+fn closures() -> MutexGuard<'static, HashMap<usize, Vec<ClosureEntry>>> {
+    match CLOSURES.lock() {
+        Ok(g) => g,
+        Err(e) => e.into_inner(),
+    }
+}
+
+/// Keeps a closure registered for its shim. Unregisters and drops the closure on drop.
+pub(crate) struct ClosureRegistration {
+    key: usize,
+    id: u64,
+}
+
+/// Registers `closure` to be called by one of the shims at `keys`, and returns the index of
+/// the shim it takes.
+///
+/// A shim cannot tell apart the closures registered for it on the same thread, so the closure
+/// takes the first shim no closure visible on the threads of `owner` uses. Panics if there is
+/// none.
+pub(crate) fn register(
+    keys: &[usize],
+    owner: Option<ThreadId>,
+    closure: Box<dyn Any + Send>,
+) -> (ClosureRegistration, usize) {
+    let mut closures = closures();
+    let conflicts = |e: &ClosureEntry| owner.is_none() || e.owner.is_none() || e.owner == owner;
+    let Some(slot) = keys.iter().position(|key| {
+        !closures
+            .get(key)
+            .is_some_and(|entries| entries.iter().any(conflicts))
+    }) else {
+        panic!(
+            "Cannot install the closure: {} closures created by the same boxed_closure! call site are already installed. Drop one of their injectors, or create this closure with a separate boxed_closure!.",
+            keys.len()
+        );
+    };
+
+    let key = keys[slot];
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    closures.entry(key).or_default().push(ClosureEntry {
+        id,
+        owner,
+        closure: Arc::new(Mutex::new(closure)),
+    });
+
+    (ClosureRegistration { key, id }, slot)
+}
+
+/// Returns whether `key` is the shim of a registered closure.
+pub(crate) fn is_registered(key: usize) -> bool {
+    closures().contains_key(&key)
+}
+
+impl Drop for ClosureRegistration {
+    fn drop(&mut self) {
+        // Release the closure outside the registry lock, its captures may run arbitrary code on drop.
+        let removed = {
+            let mut closures = closures();
+            let removed = closures.get_mut(&self.key).and_then(|entries| {
+                let pos = entries.iter().position(|e| e.id == self.id)?;
+                Some(entries.remove(pos))
+            });
+
+            if closures.get(&self.key).is_some_and(Vec::is_empty) {
+                closures.remove(&self.key);
+            }
+
+            removed
+        };
+
+        drop(removed);
+    }
+}
+
+/// Pops the running closure from `ACTIVE`, also during unwinding.
+struct ActiveGuard;
+
+impl Drop for ActiveGuard {
+    fn drop(&mut self) {
+        let _ = ACTIVE.try_with(|active| active.borrow_mut().pop());
+    }
+}
+
+/// Calls the closure registered for the shim at `key`. Used internally by `boxed_closure!`.
+#[doc(hidden)]
+pub fn __call_boxed_closure<C: 'static, R>(key: *const (), call: impl FnOnce(&mut C) -> R) -> R {
+    // Looking up the closure may allocate, which must not reach a fake of the allocator.
+    let (closure, _active, _running) = {
+        let _internal = crate::interface::macros::__enter_internal();
+        let current = std::thread::current().id();
+        let (id, closure) = {
+            let closures = closures();
+            let entries = closures.get(&(key as usize));
+            let entry = entries
+                .and_then(|entries| entries.iter().find(|e| e.owner == Some(current)))
+                .or_else(|| entries.and_then(|entries| entries.iter().find(|e| e.owner.is_none())));
+
+            match entry {
+                Some(entry) => (entry.id, entry.closure.clone()),
+                None => panic!("Boxed closure was called after its injector was dropped"),
+            }
+        };
+
+        if ACTIVE.with(|active| active.borrow().contains(&id)) {
+            panic!("Boxed closure was called re-entrantly. A closure fake must not call the function it fakes");
+        }
+
+        ACTIVE.with(|active| active.borrow_mut().push(id));
+        (
+            closure,
+            ActiveGuard,
+            crate::interface::macros::__fake_entered(key),
+        )
+    };
+
+    let mut closure = match closure.lock() {
+        Ok(g) => g,
+        Err(e) => e.into_inner(),
+    };
+
+    let closure = closure
+        .downcast_mut::<C>()
+        .expect("Boxed closure type does not match its shim");
+
+    call(closure)
+}
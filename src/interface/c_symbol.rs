@@ -0,0 +1,128 @@
+//! C functions looked up by the name of their symbol, so they can be faked without declaring
+//! them in an `extern` block first, or by their offset in their module, when they have no
+//! exported symbol.
+
+use crate::interface::func_ptr::FuncPtr;
+
+/// A function pointer type a C function can be declared with, such as
+/// `unsafe extern "C" fn(*const c_char) -> *mut c_char`.
+///
+/// Implemented for `extern "C"`, `extern "system"` and `extern "C-unwind"` function pointers,
+/// safe or unsafe, of up to twelve arguments, and for `extern "thiscall"` ones on 32-bit
+/// Windows, the calling convention of C++ member functions there. `extern "C-unwind"` declares
+/// C++ functions that may throw.
+pub trait CFnPointer: Copy + 'static {
+    #[doc(hidden)]
+    fn __returning(func: FuncPtr) -> FuncPtr;
+}
+
+macro_rules! impl_c_fn_pointer {
+    ($($arg:ident),*) => {
+        impl_c_fn_pointer!(@abi "C", $($arg),*);
+        impl_c_fn_pointer!(@abi "system", $($arg),*);
+        impl_c_fn_pointer!(@abi "C-unwind", $($arg),*);
+        #[cfg(all(target_os = "windows", target_arch = "x86"))]
+        impl_c_fn_pointer!(@abi "thiscall", $($arg),*);
+    };
+
+    (@abi $abi:literal, $($arg:ident),*) => {
+        impl<R: 'static, $($arg: 'static),*> CFnPointer for extern $abi fn($($arg),*) -> R {
+            fn __returning(func: FuncPtr) -> FuncPtr {
+                func.__returning::<R>($abi)
+            }
+        }
+
+        impl<R: 'static, $($arg: 'static),*> CFnPointer for unsafe extern $abi fn($($arg),*) -> R {
+            fn __returning(func: FuncPtr) -> FuncPtr {
+                func.__returning::<R>($abi)
+            }
+        }
+    };
+}
+
+impl_c_fn_pointer!();
+impl_c_fn_pointer!(A0);
+impl_c_fn_pointer!(A0, A1);
+impl_c_fn_pointer!(A0, A1, A2);
+impl_c_fn_pointer!(A0, A1, A2, A3);
+impl_c_fn_pointer!(A0, A1, A2, A3, A4);
+impl_c_fn_pointer!(A0, A1, A2, A3, A4, A5);
+impl_c_fn_pointer!(A0, A1, A2, A3, A4, A5, A6);
+impl_c_fn_pointer!(A0, A1, A2, A3, A4, A5, A6, A7);
+impl_c_fn_pointer!(A0, A1, A2, A3, A4, A5, A6, A7, A8);
+impl_c_fn_pointer!(A0, A1, A2, A3, A4, A5, A6, A7, A8, A9);
+impl_c_fn_pointer!(A0, A1, A2, A3, A4, A5, A6, A7, A8, A9, A10);
+impl_c_fn_pointer!(A0, A1, A2, A3, A4, A5, A6, A7, A8, A9, A10, A11);
+
+impl FuncPtr {
+    /// Looks up the function exported as `symbol`, by `module` when given, or by any loaded
+    /// module otherwise, and returns it with the signature `F` it is declared with in C.
+    /// Returns `None` when no such function is exported.
+    ///
+    /// On Unix the symbol is looked up with `dlsym`, and `module` is a shared library name or
+    /// path, as passed to `dlopen`, such as `"libc.so.6"`. On Windows it is looked up with
+    /// `GetProcAddress`, and `module` is a DLL name, such as `"ucrtbase.dll"`. The module is
+    /// loaded if it is not yet, and stays loaded.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use injectorpp::interface::injector::*;
+    /// use std::os::raw::c_char;
+    ///
+    /// let getenv = FuncPtr::from_c_symbol::<unsafe extern "C" fn(*const c_char) -> *mut c_char>(
+    ///     None, "getenv",
+    /// );
+    /// assert!(getenv.is_some());
+    /// ```
+    pub fn from_c_symbol<F: CFnPointer>(module: Option<&str>, symbol: &str) -> Option<Self> {
+        let addr = crate::injector_core::symbols::exported_address(module, symbol)?;
+        Some(unsafe { FuncPtr::with_c_signature::<F>(addr) })
+    }
+
+    /// Returns the function at `offset` in the loaded `module`, or in the main program when
+    /// `None`, with the signature `F` it is declared with in C, for functions without an
+    /// exported symbol, such as static functions known from a map file or functions found by
+    /// scanning code. Returns `None` when the module is not loaded, or when `offset` is not in
+    /// its executable code.
+    ///
+    /// `offset` is the address of the function relative to the base of its module: its RVA on
+    /// Windows, its address in the ELF headers and symbols on Linux and the BSDs, or its offset
+    /// from the Mach-O header on macOS. `module` is named by its path or file name, such as
+    /// `"libssl.so.3"`, and is not loaded if it is not yet.
+    ///
+    /// When `size` is given, all of `size` bytes must be executable code, and patches are
+    /// checked against it rather than against the size found in the unwind or symbol tables.
+    ///
+    /// # Safety
+    ///
+    /// A function declared as `F` must start at `offset`, and be `size` bytes long if given.
+    /// Patching an address in the middle of a function, or past its end, corrupts the code.
+    pub unsafe fn from_module_offset<F: CFnPointer>(
+        module: Option<&str>,
+        offset: usize,
+        size: Option<usize>,
+    ) -> Option<Self> {
+        use crate::injector_core::symbols;
+
+        let addr = symbols::code_address(module, offset, size.unwrap_or(1))?;
+        if let Some(size) = size {
+            symbols::record_function_size(addr, size);
+        }
+        Some(FuncPtr::with_c_signature::<F>(addr))
+    }
+
+    /// Returns the function at `addr` with the signature `F` it is declared with.
+    ///
+    /// # Safety
+    ///
+    /// A function declared as `F` must start at `addr`.
+    pub(crate) unsafe fn with_c_signature<F: CFnPointer>(addr: usize) -> Self {
+        let func = FuncPtr::new_with_type_id(
+            addr as *const (),
+            std::any::type_name::<F>(),
+            std::any::TypeId::of::<F>(),
+        );
+        F::__returning(func)
+    }
+}
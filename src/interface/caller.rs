@@ -0,0 +1,70 @@
+/// A call of a fake, by the address it returns to in the calling function.
+///
+/// Logged for the fakes installed after `WhenCalledBuilder::record_callers()`, and read with
+/// `FakeHandle::callers()`. On Linux, the return address resolves to the function containing
+/// it, from the symbol table of its object; elsewhere only the address is known.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Caller {
+    return_address: usize,
+}
+
+impl Caller {
+    #[cfg(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec",
+        target_arch = "arm"
+    ))]
+    pub(crate) fn new(return_address: usize) -> Self {
+        Self { return_address }
+    }
+
+    /// Returns the address the call returns to, just after the call instruction.
+    pub fn return_address(&self) -> usize {
+        self.return_address
+    }
+
+    /// Returns the demangled name of the calling function, if its symbol is found.
+    pub fn function(&self) -> Option<String> {
+        self.resolve().map(|(name, _)| name)
+    }
+
+    /// Returns the offset of the return address from the start of the calling function, if
+    /// its symbol is found.
+    pub fn offset(&self) -> Option<usize> {
+        self.resolve().map(|(_, offset)| offset)
+    }
+
+    fn resolve(&self) -> Option<(String, usize)> {
+        // A call ending a function returns past it, so the function holding the call
+        // instruction is the one holding the byte before the return address.
+        let (name, offset) = crate::injector_core::symbols::function_containing(
+            self.return_address.checked_sub(1)?,
+        )?;
+        Some((name, offset + 1))
+    }
+}
+
+impl std::fmt::Display for Caller {
+    /// Formats the caller as `function+0x1c`, or as its return address when the function is
+    /// not found.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.resolve() {
+            Some((name, offset)) => write!(f, "{}+{:#x}", name, offset),
+            None => write!(f, "{:#x}", self.return_address),
+        }
+    }
+}
+
+impl std::fmt::Debug for Caller {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Caller")
+            .field(
+                "return_address",
+                &format_args!("{:#x}", self.return_address),
+            )
+            .field("function", &self.function())
+            .field("offset", &self.offset())
+            .finish()
+    }
+}
@@ -0,0 +1,304 @@
+use crate::interface::fixture::{Fixture, Recordable};
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Environment variable which, when set, makes `Cassette::open()` record again over an
+/// existing cassette.
+const RECORD_VAR: &str = "INJECTORPP_RECORD";
+
+/// Whether a `Cassette` calls the recorded functions or replays their calls.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CassetteMode {
+    /// The functions run, and their arguments and return values are recorded.
+    Record,
+    /// The functions do not run, and return the values they were recorded with.
+    Replay,
+}
+
+/// Records the calls of functions to a file, then replays them without running the functions,
+/// like a VCR, for tests of code calling slow or non-deterministic dependencies.
+///
+/// Functions are recorded with `record!`. While recording, each call runs the function and
+/// keeps its arguments and return value, which are written to the file, one JSON line per call,
+/// when the last clone of the cassette is dropped. While replaying, the calls of each function
+/// return the recorded values in the order they were recorded, and panic if their arguments,
+/// compared as formatted with `Debug`, differ from the recorded ones.
+///
+/// Return values must implement `Recordable`. Recording calls the function from its fake,
+/// which requires thread-local dispatch: it is not available with `InjectorPP::new_global()`.
+///
+/// # Example
+///
+/// ```rust
+/// use injectorpp::interface::injector::*;
+///
+/// #[inline(never)]
+/// fn fetch_price(symbol: &str) -> Result<u32, String> {
+///     std::hint::black_box(Ok(symbol.len() as u32 * 100))
+/// }
+///
+/// let path = std::env::temp_dir().join(format!("prices-{}.cassette", std::process::id()));
+/// {
+///     let cassette = Cassette::open(&path);
+///     assert_eq!(cassette.mode(), CassetteMode::Record);
+///     let mut injector = InjectorPP::new();
+///     injectorpp::record!(injector, &cassette, fn (fetch_price)(symbol: &str) -> Result<u32, String>);
+///     assert_eq!(fetch_price("MSFT"), Ok(400));
+/// }
+///
+/// let cassette = Cassette::open(&path);
+/// assert_eq!(cassette.mode(), CassetteMode::Replay);
+/// let mut injector = InjectorPP::new();
+/// injectorpp::record!(injector, &cassette, fn (fetch_price)(symbol: &str) -> Result<u32, String>);
+/// assert_eq!(fetch_price("MSFT"), Ok(400));
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+#[derive(Clone)]
+pub struct Cassette {
+    state: Arc<Mutex<CassetteState>>,
+}
+
+struct CassetteState {
+    path: PathBuf,
+    mode: CassetteMode,
+    interactions: Vec<Interaction>,
+    /// Number of calls of each function replayed so far.
+    replayed: HashMap<String, usize>,
+}
+
+struct Interaction {
+    function: String,
+    args: String,
+    returns: Fixture,
+}
+
+impl Cassette {
+    /// Replays the cassette at `path` if it exists, and records it otherwise, or when
+    /// `INJECTORPP_RECORD` is set.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the cassette cannot be read or is malformed.
+    pub fn open(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        if path.exists() && std::env::var_os(RECORD_VAR).is_none() {
+            Self::replay(path)
+        } else {
+            Self::record(path)
+        }
+    }
+
+    /// Records a new cassette at `path`, replacing the existing one when dropped.
+    pub fn record(path: impl AsRef<Path>) -> Self {
+        Self::new(path.as_ref(), CassetteMode::Record, Vec::new())
+    }
+
+    /// Replays the cassette at `path`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the cassette cannot be read or is malformed.
+    pub fn replay(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .unwrap_or_else(|e| panic!("Cannot read cassette {}: {}", path.display(), e));
+
+        let interactions = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(index, line)| {
+                parse_interaction(line).unwrap_or_else(|e| {
+                    panic!(
+                        "Malformed cassette {} at line {}: {}",
+                        path.display(),
+                        index + 1,
+                        e
+                    )
+                })
+            })
+            .collect();
+        Self::new(path, CassetteMode::Replay, interactions)
+    }
+
+    fn new(path: &Path, mode: CassetteMode, interactions: Vec<Interaction>) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(CassetteState {
+                path: path.to_path_buf(),
+                mode,
+                interactions,
+                replayed: HashMap::new(),
+            })),
+        }
+    }
+
+    /// Returns whether the cassette records or replays the calls.
+    pub fn mode(&self) -> CassetteMode {
+        self.state().mode
+    }
+
+    /// Returns the file the cassette is read from or written to.
+    pub fn path(&self) -> PathBuf {
+        self.state().path.clone()
+    }
+
+    /// Returns the number of calls recorded, or read from the file when replaying.
+    pub fn len(&self) -> usize {
+        self.state().interactions.len()
+    }
+
+    /// Returns whether no call is recorded.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Writes the recorded calls to the file, creating its directory if needed. Done when the
+    /// last clone of a recording cassette is dropped, unless the thread is panicking.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the cassette is replaying.
+    pub fn save(&self) -> std::io::Result<()> {
+        let state = self.state();
+        if state.mode != CassetteMode::Record {
+            panic!(
+                "Cassette {} is replaying and cannot be saved",
+                state.path.display()
+            );
+        }
+        state.save()
+    }
+
+    fn state(&self) -> MutexGuard<'_, CassetteState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Runs `real` and records the call while recording, or returns the recorded value while
+    /// replaying. Used internally by `record!`.
+    #[doc(hidden)]
+    pub fn __play<R: Recordable>(
+        &self,
+        function: &str,
+        args: String,
+        real: impl FnOnce() -> R,
+    ) -> R {
+        if self.mode() == CassetteMode::Record {
+            // Called without the lock, as the function may call other recorded functions.
+            let returns = real();
+            self.state().interactions.push(Interaction {
+                function: function.to_string(),
+                args,
+                returns: returns.record(),
+            });
+            return returns;
+        }
+
+        let mut state = self.state();
+        let call = *state.replayed.get(function).unwrap_or(&0);
+        let Some(recorded) = state
+            .interactions
+            .iter()
+            .filter(|interaction| interaction.function == function)
+            .nth(call)
+        else {
+            panic!(
+                "Cassette {}: {} was called {} times but only {} calls were recorded; set {}=1 \
+                 to record it again",
+                state.path.display(),
+                function,
+                call + 1,
+                call,
+                RECORD_VAR
+            );
+        };
+        if recorded.args != args {
+            panic!(
+                "Cassette {}: call {} of {} was recorded with arguments {} but made with {}; set \
+                 {}=1 to record it again",
+                state.path.display(),
+                call + 1,
+                function,
+                recorded.args,
+                args,
+                RECORD_VAR
+            );
+        }
+        let Some(returns) = R::replay(&recorded.returns) else {
+            panic!(
+                "Cassette {}: call {} of {} returned {}, which is not a {}",
+                state.path.display(),
+                call + 1,
+                function,
+                recorded.returns,
+                std::any::type_name::<R>()
+            );
+        };
+        state.replayed.insert(function.to_string(), call + 1);
+        returns
+    }
+}
+
+impl CassetteState {
+    fn save(&self) -> std::io::Result<()> {
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut text = String::new();
+        for interaction in &self.interactions {
+            let line = Fixture::Object(vec![
+                (
+                    "function".to_string(),
+                    Fixture::String(interaction.function.clone()),
+                ),
+                (
+                    "args".to_string(),
+                    Fixture::String(interaction.args.clone()),
+                ),
+                ("returns".to_string(), interaction.returns.clone()),
+            ]);
+            text.push_str(&line.to_string());
+            text.push('\n');
+        }
+        std::fs::write(&self.path, text)
+    }
+}
+
+impl Drop for CassetteState {
+    fn drop(&mut self) {
+        // A failed test does not overwrite the cassette with a partial recording.
+        if self.mode == CassetteMode::Record && !std::thread::panicking() {
+            if let Err(e) = self.save() {
+                panic!("Cannot write cassette {}: {}", self.path.display(), e);
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for Cassette {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = self.state();
+        f.debug_struct("Cassette")
+            .field("path", &state.path)
+            .field("mode", &state.mode)
+            .field("calls", &state.interactions.len())
+            .finish()
+    }
+}
+
+fn parse_interaction(line: &str) -> Result<Interaction, String> {
+    let fixture = Fixture::parse(line)?;
+    let text = |key: &str| match fixture.get(key) {
+        Some(Fixture::String(text)) => Ok(text.clone()),
+        _ => Err(format!("missing \"{}\"", key)),
+    };
+    Ok(Interaction {
+        function: text("function")?,
+        args: text("args")?,
+        returns: fixture
+            .get("returns")
+            .cloned()
+            .ok_or_else(|| "missing \"returns\"".to_string())?,
+    })
+}
@@ -0,0 +1,86 @@
+//! C++ functions looked up by their mangled symbol or their demangled name, so functions of
+//! C++ libraries, such as those the `cxx` crate bridges, can be faked.
+
+use crate::injector_core::symbols;
+use crate::interface::c_symbol::CFnPointer;
+use crate::interface::func_ptr::FuncPtr;
+
+/// Returns the demangled name of the C++ function whose symbol is `symbol`, mangled with the
+/// Itanium C++ ABI of GCC and Clang or with the scheme of MSVC, or `None` when it is not the
+/// symbol of a C++ function or cannot be demangled. Helps finding the names to fake in the
+/// output of `nm` or `dumpbin /symbols`.
+///
+/// Itanium symbols are demangled with their parameters as `c++filt` prints them, such as
+/// `net::Socket::connect(char const*, int)`, without the return type of template functions.
+/// MSVC symbols are demangled to the qualified name of the function only, such as
+/// `net::Socket::connect`. Symbols with function pointer or array parameters, local names or
+/// template arguments of expressions are not demangled.
+///
+/// # Example
+///
+/// ```rust
+/// use injectorpp::interface::injector::*;
+///
+/// assert_eq!(
+///     demangle_cxx("_ZN3net6Socket7connectEPKci").as_deref(),
+///     Some("net::Socket::connect(char const*, int)")
+/// );
+/// assert_eq!(
+///     demangle_cxx("?connect@Socket@net@@QEAAHPEBDH@Z").as_deref(),
+///     Some("net::Socket::connect")
+/// );
+/// ```
+pub fn demangle_cxx(symbol: &str) -> Option<String> {
+    crate::injector_core::cxx_demangle::demangle(symbol)
+}
+
+impl FuncPtr {
+    /// Looks up the C++ function whose mangled symbol is `symbol`, exported by `module` when
+    /// given, or by any loaded module otherwise, and returns it with the signature `F` it is
+    /// declared with. Returns `None` when no such function is found.
+    ///
+    /// Symbols are looked up as by `from_c_symbol()`. Without `module`, functions of static
+    /// libraries linked into the program without being exported are found in the symbol tables
+    /// of the loaded modules too, on Linux.
+    ///
+    /// C++ functions are called with the C calling convention of the platform, so `F` is an
+    /// `extern "C"` function pointer, or `extern "C-unwind"` for functions that may throw.
+    /// Member functions take the object, `this`, as their first argument, and are declared
+    /// `extern "thiscall"` on 32-bit Windows. The signature is not checked against the
+    /// function, and must match its declaration.
+    pub fn from_cxx_symbol<F: CFnPointer>(module: Option<&str>, symbol: &str) -> Option<Self> {
+        if let Some(func) = Self::from_c_symbol::<F>(module, symbol) {
+            return Some(func);
+        }
+        if module.is_some() {
+            return None;
+        }
+
+        let name = demangle_cxx(symbol)?;
+        match symbols::cxx_functions_named(&name)?.as_slice() {
+            &[(_, addr)] => Some(unsafe { FuncPtr::with_c_signature::<F>(addr) }),
+            _ => None,
+        }
+    }
+
+    /// Looks up the C++ functions whose demangled name matches `pattern` and returns them,
+    /// with their name, as functions of the signature `F`.
+    ///
+    /// Names are demangled as by `demangle_cxx()`. A pattern with parameters, such as
+    /// `net::Socket::connect(char const*, int)`, is matched against the names with their
+    /// parameters, telling overloads apart, and a pattern without against the names without
+    /// them, such as `net::Socket::connect`. In `pattern`, `*` stands for any part of a name
+    /// segment and `**` for any part of the name, as in `FuncPtr::from_symbol_pattern()`.
+    ///
+    /// Functions are looked up in the symbol tables of the loaded modules, so only functions
+    /// that are not inlined, and modules that are not stripped, are found. Only Linux is
+    /// supported; elsewhere nothing is found, and functions are looked up by their mangled
+    /// symbol with `from_cxx_symbol()`. See `from_cxx_symbol()` for how `F` is declared.
+    pub fn from_cxx_name<F: CFnPointer>(pattern: &str) -> Vec<(String, Self)> {
+        symbols::cxx_functions_matching(pattern)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(name, addr)| (name, unsafe { FuncPtr::with_c_signature::<F>(addr) }))
+            .collect()
+    }
+}
@@ -0,0 +1,210 @@
+//! Functions `when_called()` refuses to fake unless the injector opts in with
+//! `allow_dangerous()`.
+//!
+//! Unsafe code relies on the behavior of some functions for soundness: a `Vec` trusts its
+//! capacity, an allocator trusts its own bookkeeping. Faking these can break the invariants of
+//! other crates and cause undefined behavior far away from the test, so they are denied.
+
+use std::sync::RwLock;
+
+/// The functions denied by default. Entries with `::` are item paths and also deny every item
+/// under them, such as the methods of a type. Generic arguments are ignored, and `std::` and
+/// `alloc::` paths match the same items as `core::` paths. Entries without `::` are symbol
+/// names, for functions such as `malloc` declared in `extern` blocks.
+pub const DEFAULT_DENY_LIST: &[&str] = &[
+    // Allocator entry points.
+    "std::alloc::alloc",
+    "std::alloc::alloc_zeroed",
+    "std::alloc::dealloc",
+    "std::alloc::realloc",
+    "std::alloc::Global",
+    "std::alloc::System",
+    "core::alloc::layout::Layout",
+    "__rust_alloc",
+    "__rust_alloc_zeroed",
+    "__rust_dealloc",
+    "__rust_realloc",
+    "malloc",
+    "calloc",
+    "realloc",
+    "free",
+    "aligned_alloc",
+    "posix_memalign",
+    // Pointer and slice primitives.
+    "core::intrinsics",
+    "core::ptr::read",
+    "core::ptr::read_unaligned",
+    "core::ptr::read_volatile",
+    "core::ptr::write",
+    "core::ptr::write_unaligned",
+    "core::ptr::write_volatile",
+    "core::ptr::write_bytes",
+    "core::ptr::copy",
+    "core::ptr::copy_nonoverlapping",
+    "core::ptr::drop_in_place",
+    "core::ptr::swap",
+    "core::ptr::replace",
+    "core::slice::from_raw_parts",
+    "core::slice::from_raw_parts_mut",
+    // Length and capacity accessors of collections.
+    "std::vec::Vec::len",
+    "std::vec::Vec::capacity",
+    "std::vec::Vec::as_ptr",
+    "std::vec::Vec::as_mut_ptr",
+    "std::vec::Vec::set_len",
+    "std::string::String::len",
+    "std::string::String::capacity",
+    "std::string::String::as_ptr",
+    "alloc::raw_vec",
+];
+
+/// The deny-list, `None` until changed from `DEFAULT_DENY_LIST`.
+static DENY_LIST: RwLock<Option<Vec<String>>> = RwLock::new(None);
+
+/// Adds `path` to the deny-list.
+pub(crate) fn add(path: &str) {
+    let mut list = DENY_LIST.write().unwrap_or_else(|e| e.into_inner());
+    list.get_or_insert_with(default_list).push(path.to_string());
+}
+
+/// Replaces the deny-list with `paths`.
+pub(crate) fn set(paths: Vec<String>) {
+    *DENY_LIST.write().unwrap_or_else(|e| e.into_inner()) = Some(paths);
+}
+
+fn default_list() -> Vec<String> {
+    DEFAULT_DENY_LIST
+        .iter()
+        .map(|path| path.to_string())
+        .collect()
+}
+
+/// Returns the deny-list entry matching a function, given by the type name of its item when
+/// known and by the name of its symbol when the dynamic loader knows it.
+pub(crate) fn denied_entry(item: Option<&str>, symbol: Option<&str>) -> Option<String> {
+    let path = item.map(item_path);
+    // Functions declared in `extern` blocks are named `crate::{{extern}}::symbol`.
+    let extern_symbol = item.and_then(|item| {
+        item.rsplit_once("{{extern}}::")
+            .map(|(_, symbol)| symbol.to_string())
+    });
+
+    let matches = |entry: &str| {
+        if entry.contains("::") {
+            let entry = item_path(entry);
+            path.as_deref().is_some_and(|path| {
+                path == entry
+                    || path
+                        .strip_prefix(entry.as_str())
+                        .is_some_and(|rest| rest.starts_with("::"))
+            })
+        } else {
+            symbol == Some(entry) || extern_symbol.as_deref() == Some(entry)
+        }
+    };
+
+    let list = DENY_LIST.read().unwrap_or_else(|e| e.into_inner());
+    match &*list {
+        Some(list) => list.iter().find(|entry| matches(entry)).cloned(),
+        None => DEFAULT_DENY_LIST
+            .iter()
+            .find(|entry| matches(entry))
+            .map(|entry| entry.to_string()),
+    }
+}
+
+/// Turns the type name of a function item into the path it is matched by: trait methods, as
+/// in `<Vec<u8> as Clone>::clone`, are matched under their self type, generic arguments are
+/// removed and `std` and `alloc` paths are written with `core`.
+fn item_path(type_name: &str) -> String {
+    let mut path = type_name.to_string();
+
+    if let Some(qualified) = type_name.strip_prefix('<') {
+        let mut depth = 0usize;
+        let close = qualified.char_indices().find_map(|(i, c)| {
+            match c {
+                '<' => depth += 1,
+                '>' if depth == 0 => return Some(i),
+                '>' => depth -= 1,
+                _ => {}
+            }
+            None
+        });
+
+        if let Some(close) = close {
+            let self_ty = top_level_split(&qualified[..close], " as ");
+            path = format!("{}{}", self_ty, &qualified[close + 1..]);
+        }
+    }
+
+    crate::interface::injector::canonical_crate_paths(&strip_generics(&path))
+}
+
+/// Returns the part of `s` before the first `separator` outside of angle brackets.
+fn top_level_split<'a>(s: &'a str, separator: &str) -> &'a str {
+    let mut depth = 0usize;
+    for (i, c) in s.char_indices() {
+        match c {
+            '<' => depth += 1,
+            '>' => depth = depth.saturating_sub(1),
+            _ if depth == 0 && s[i..].starts_with(separator) => return &s[..i],
+            _ => {}
+        }
+    }
+    s
+}
+
+fn strip_generics(path: &str) -> String {
+    let mut out = String::with_capacity(path.len());
+    let mut depth = 0usize;
+    for c in path.chars() {
+        match c {
+            '<' => depth += 1,
+            '>' => depth = depth.saturating_sub(1),
+            _ if depth == 0 => out.push(c),
+            _ => {}
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_item_path_of_methods() {
+        assert_eq!(
+            item_path("alloc::vec::Vec<u8>::capacity"),
+            "core::vec::Vec::capacity"
+        );
+        assert_eq!(
+            item_path("<std::alloc::System as core::alloc::global::GlobalAlloc>::alloc"),
+            "core::alloc::System::alloc"
+        );
+        assert_eq!(
+            item_path("<alloc::vec::Vec<u8> as core::clone::Clone>::clone"),
+            "core::vec::Vec::clone"
+        );
+        assert_eq!(item_path("core::ptr::read<u8>"), "core::ptr::read");
+    }
+
+    #[test]
+    fn test_default_deny_list_matches_paths_and_symbols() {
+        let denied = |item| denied_entry(Some(item), None);
+
+        assert_eq!(
+            denied("alloc::vec::Vec<u8>::capacity").as_deref(),
+            Some("std::vec::Vec::capacity")
+        );
+        assert_eq!(
+            denied("<std::alloc::System as core::alloc::global::GlobalAlloc>::dealloc").as_deref(),
+            Some("std::alloc::System")
+        );
+        assert_eq!(denied("app::{{extern}}::malloc").as_deref(), Some("malloc"));
+        assert_eq!(denied("alloc::vec::Vec<u8>::push"), None);
+        assert_eq!(denied("core::ptr::read_data"), None);
+        assert_eq!(denied("app::free_slots"), None);
+        assert_eq!(denied_entry(None, Some("free")).as_deref(), Some("free"));
+    }
+}
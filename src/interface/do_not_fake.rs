@@ -0,0 +1,93 @@
+//! Functions their crate marks with `#[injectorpp::do_not_fake]`.
+//!
+//! The attribute stores the address of each marked function in a dedicated link section,
+//! which the linker gathers from every crate of the program. The section is read when a
+//! function is about to be faked. Marked functions of dynamically loaded libraries are not
+//! seen, and targets without a known section layout ignore the markers.
+
+/// An entry of the marker section. `#[do_not_fake]` emits entries of its own type with the
+/// same layout.
+#[repr(transparent)]
+struct Marker(*const ());
+
+// Safety: the pointer is only compared, never dereferenced.
+unsafe impl Sync for Marker {}
+
+/// Returns whether the function at `addr` is marked with `#[do_not_fake]`.
+pub(crate) fn is_marked(addr: usize) -> bool {
+    markers().iter().any(|marker| marker.0 as usize == addr)
+}
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "freebsd",
+    target_os = "netbsd"
+))]
+fn markers() -> &'static [Marker] {
+    // The section always holds this entry, so the linker defines its bounds.
+    #[used]
+    #[link_section = "injectorpp_do_not_fake"]
+    static SENTINEL: Marker = Marker(std::ptr::null());
+
+    extern "C" {
+        static __start_injectorpp_do_not_fake: Marker;
+        static __stop_injectorpp_do_not_fake: Marker;
+    }
+
+    unsafe {
+        let start = std::ptr::addr_of!(__start_injectorpp_do_not_fake);
+        let stop = std::ptr::addr_of!(__stop_injectorpp_do_not_fake);
+        std::slice::from_raw_parts(start, stop.offset_from(start) as usize)
+    }
+}
+
+#[cfg(target_vendor = "apple")]
+fn markers() -> &'static [Marker] {
+    #[used]
+    #[link_section = "__DATA,__injpp_nofake,regular,no_dead_strip"]
+    static SENTINEL: Marker = Marker(std::ptr::null());
+
+    extern "C" {
+        #[link_name = "\x01section$start$__DATA$__injpp_nofake"]
+        static START: Marker;
+        #[link_name = "\x01section$end$__DATA$__injpp_nofake"]
+        static END: Marker;
+    }
+
+    unsafe {
+        let start = std::ptr::addr_of!(START);
+        let end = std::ptr::addr_of!(END);
+        std::slice::from_raw_parts(start, end.offset_from(start) as usize)
+    }
+}
+
+#[cfg(windows)]
+fn markers() -> &'static [Marker] {
+    // The linker sorts `.injpp$...` sections by the suffix, so the entries, in `.injpp$m`,
+    // lie between these. Padding between entries reads as null pointers.
+    #[used]
+    #[link_section = ".injpp$a"]
+    static START: [Marker; 0] = [];
+    #[used]
+    #[link_section = ".injpp$z"]
+    static END: [Marker; 0] = [];
+
+    unsafe {
+        let start = START.as_ptr();
+        let end = END.as_ptr();
+        std::slice::from_raw_parts(start, end.offset_from(start) as usize)
+    }
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_vendor = "apple",
+    windows
+)))]
+fn markers() -> &'static [Marker] {
+    &[]
+}
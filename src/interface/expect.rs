@@ -0,0 +1,289 @@
+use crate::interface::boxed_closure::{BoxedClosure, __call_boxed_closure};
+use crate::interface::func_ptr::FuncPtr;
+use crate::interface::injector::InjectorPP;
+use crate::interface::verifier::CallCountVerifier;
+
+use std::marker::PhantomData;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+
+/// Checks the arguments of a call, as given to `with()`.
+///
+/// Implemented for closures taking the arguments of the faked function by value and
+/// returning `bool`. Arguments are copied to the closure, so every argument must be `Copy`,
+/// which covers references and integers.
+pub trait ArgsMatcher<Args>: Send + 'static {
+    /// Returns whether a call with `args` is expected.
+    fn matches(&self, args: &Args) -> bool;
+}
+
+/// Accepts the arguments of every call. Used when `with()` is not given.
+pub struct AnyArgs;
+
+impl<Args> ArgsMatcher<Args> for AnyArgs {
+    fn matches(&self, _args: &Args) -> bool {
+        true
+    }
+}
+
+/// Produces the return value of a faked function, as given to `returning()`.
+///
+/// Implemented for closures taking the arguments of the faked function by value.
+pub trait Returning<Args, R>: Send + 'static {
+    /// Returns the shims calling this closure, one per slot. Used internally by `Expectation`.
+    #[doc(hidden)]
+    fn __shims<M: ArgsMatcher<Args>>() -> Vec<FuncPtr>;
+}
+
+/// The closures of an expectation, looked up by its shim.
+struct ExpectState<M, G> {
+    matcher: M,
+    returning: G,
+    target: String,
+    calls: Option<(&'static AtomicUsize, usize)>,
+}
+
+impl<M, G> ExpectState<M, G> {
+    fn check<Args>(&self, args: &Args)
+    where
+        M: ArgsMatcher<Args>,
+    {
+        if !self.matcher.matches(args) {
+            panic!(
+                "Expectation on {} called with unexpected arguments",
+                self.target
+            );
+        }
+
+        if let Some((counter, expected)) = self.calls {
+            if counter.fetch_add(1, Ordering::SeqCst) >= expected {
+                panic!(
+                    "Expectation on {} called more times than expected",
+                    self.target
+                );
+            }
+        }
+    }
+}
+
+macro_rules! expect_arity {
+    ($($arg:ident: $ty:ident),*) => {
+        impl<F, $($ty: Copy),*> ArgsMatcher<($($ty,)*)> for F
+        where
+            F: Fn($($ty),*) -> bool + Send + 'static,
+        {
+            fn matches(&self, ($($arg,)*): &($($ty,)*)) -> bool {
+                self($(*$arg),*)
+            }
+        }
+
+        impl<G, R, $($ty),*> Returning<($($ty,)*), R> for G
+        where
+            G: FnMut($($ty),*) -> R + Send + 'static,
+        {
+            fn __shims<M: ArgsMatcher<($($ty,)*)>>() -> Vec<FuncPtr> {
+                // Monomorphized for every closure type, so every `returning()` call site
+                // gets its own shims, like every `boxed_closure!` does.
+                #[allow(clippy::too_many_arguments)]
+                fn shim<const SLOT: usize, G, M, R, $($ty),*>($($arg: $ty),*) -> R
+                where
+                    G: FnMut($($ty),*) -> R + Send + 'static,
+                    M: ArgsMatcher<($($ty,)*)>,
+                {
+                    __call_boxed_closure(
+                        shim::<SLOT, G, M, R, $($ty),*> as *const (),
+                        |state: &mut ExpectState<M, G>| {
+                            let args = ($($arg,)*);
+                            state.check(&args);
+                            let ($($arg,)*) = args;
+                            (state.returning)($($arg),*)
+                        },
+                    )
+                }
+
+                let shims: [fn($($ty),*) -> R; 8] = [
+                    shim::<0, G, M, R, $($ty),*>,
+                    shim::<1, G, M, R, $($ty),*>,
+                    shim::<2, G, M, R, $($ty),*>,
+                    shim::<3, G, M, R, $($ty),*>,
+                    shim::<4, G, M, R, $($ty),*>,
+                    shim::<5, G, M, R, $($ty),*>,
+                    shim::<6, G, M, R, $($ty),*>,
+                    shim::<7, G, M, R, $($ty),*>,
+                ];
+
+                // The type id of a signature with references depends on the lifetimes
+                // inferred for the closure, so only the signature text is checked.
+                shims
+                    .iter()
+                    .map(|&f| {
+                        unsafe {
+                            FuncPtr::new(f as *const (), std::any::type_name::<fn($($ty),*) -> R>())
+                        }
+                        .__returning::<R>("Rust")
+                    })
+                    .collect()
+            }
+        }
+    };
+}
+
+expect_arity!();
+expect_arity!(a0: A0);
+expect_arity!(a0: A0, a1: A1);
+expect_arity!(a0: A0, a1: A1, a2: A2);
+expect_arity!(a0: A0, a1: A1, a2: A2, a3: A3);
+expect_arity!(a0: A0, a1: A1, a2: A2, a3: A3, a4: A4);
+expect_arity!(a0: A0, a1: A1, a2: A2, a3: A3, a4: A4, a5: A5);
+expect_arity!(a0: A0, a1: A1, a2: A2, a3: A3, a4: A4, a5: A5, a6: A6);
+expect_arity!(a0: A0, a1: A1, a2: A2, a3: A3, a4: A4, a5: A5, a6: A6, a7: A7);
+
+/// An expectation on a function, returned by `InjectorPP::expect()`.
+///
+/// Unlike `fake!`, the builder is made of plain generic methods, so closures given to it get
+/// type checking and completion from the IDE. The argument types of the closures must be
+/// written out, as must a return type the closure body leaves open, such as the error type
+/// of `Ok(())`. They are checked against the signature of the function when the fake is
+/// installed.
+///
+/// # Example
+///
+/// ```rust
+/// use injectorpp::interface::injector::*;
+///
+/// #[inline(never)]
+/// fn remove(path: &str) -> std::io::Result<()> {
+///     std::fs::remove_file(path)
+/// }
+///
+/// let mut injector = InjectorPP::new();
+/// injector
+///     .expect(injectorpp::func!(fn (remove)(&str) -> std::io::Result<()>))
+///     .with(|path: &str| path == "/tmp/cache")
+///     .returning(|_: &str| -> std::io::Result<()> { Ok(()) })
+///     .times(1);
+///
+/// assert!(remove("/tmp/cache").is_ok());
+/// ```
+pub struct Expect<'a> {
+    injector: &'a mut InjectorPP,
+    func: FuncPtr,
+    target: String,
+}
+
+impl<'a> Expect<'a> {
+    pub(crate) fn new(injector: &'a mut InjectorPP, func: FuncPtr, target: String) -> Self {
+        Self {
+            injector,
+            func,
+            target,
+        }
+    }
+
+    /// Expects only calls whose arguments `matcher` returns `true` for. Other calls panic.
+    pub fn with<M, Args>(self, matcher: M) -> ExpectWith<'a, M, Args>
+    where
+        M: ArgsMatcher<Args>,
+    {
+        ExpectWith {
+            expect: self,
+            matcher,
+            _args: PhantomData,
+        }
+    }
+
+    /// Makes the fake return what `returning` returns for the arguments of each call.
+    pub fn returning<G, Args, R>(self, returning: G) -> Expectation<'a, AnyArgs, G>
+    where
+        G: Returning<Args, R>,
+    {
+        Expectation::new(self, AnyArgs, returning, G::__shims::<AnyArgs>())
+    }
+}
+
+/// An expectation with an argument matcher, returned by `Expect::with()`.
+pub struct ExpectWith<'a, M, Args> {
+    expect: Expect<'a>,
+    matcher: M,
+    _args: PhantomData<fn(Args)>,
+}
+
+impl<'a, M, Args> ExpectWith<'a, M, Args>
+where
+    M: ArgsMatcher<Args>,
+{
+    /// Makes the fake return what `returning` returns for the arguments of each call.
+    pub fn returning<G, R>(self, returning: G) -> Expectation<'a, M, G>
+    where
+        G: Returning<Args, R>,
+    {
+        Expectation::new(self.expect, self.matcher, returning, G::__shims::<M>())
+    }
+}
+
+/// A complete expectation, returned by `returning()`.
+///
+/// The fake is installed when the expectation is dropped, normally at the end of the
+/// statement that created it.
+pub struct Expectation<'a, M: Send + 'static, G: Send + 'static> {
+    injector: &'a mut InjectorPP,
+    func: Option<FuncPtr>,
+    shims: Option<Vec<FuncPtr>>,
+    target: String,
+    closures: Option<(M, G)>,
+    times: Option<usize>,
+}
+
+impl<'a, M: Send + 'static, G: Send + 'static> Expectation<'a, M, G> {
+    fn new(expect: Expect<'a>, matcher: M, returning: G, shims: Vec<FuncPtr>) -> Self {
+        Self {
+            injector: expect.injector,
+            func: Some(expect.func),
+            shims: Some(shims),
+            target: expect.target,
+            closures: Some((matcher, returning)),
+            times: None,
+        }
+    }
+
+    /// Expects the fake to be called exactly `times` times before the injector is dropped.
+    pub fn times(mut self, times: usize) -> Self {
+        self.times = Some(times);
+        self
+    }
+}
+
+impl<M: Send + 'static, G: Send + 'static> Drop for Expectation<'_, M, G> {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            return;
+        }
+
+        let (Some(func), Some(shims), Some((matcher, returning))) =
+            (self.func.take(), self.shims.take(), self.closures.take())
+        else {
+            return;
+        };
+
+        let calls = self.times.map(|expected| {
+            // Leaked for the same reason as the counter of `Mock::times()`.
+            let counter: &'static AtomicUsize = Box::leak(Box::new(AtomicUsize::new(0)));
+            self.injector
+                .push_verifier(CallCountVerifier::WithCount { counter, expected });
+            (counter, expected)
+        });
+
+        let state = ExpectState {
+            matcher,
+            returning,
+            target: std::mem::take(&mut self.target),
+            calls,
+        };
+
+        // Safety: `shims` were created by `Returning::__shims` for these closure types.
+        let closure = unsafe { BoxedClosure::__new(shims, Box::new(state)) };
+        self.injector
+            .when_called(func)
+            .will_execute_closure(closure);
+    }
+}
@@ -0,0 +1,392 @@
+use crate::interface::caller::Caller;
+
+#[cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec",
+    target_arch = "arm"
+))]
+use crate::injector_core::thread_local_registry::CallLog;
+#[cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec",
+    target_arch = "arm"
+))]
+use crate::interface::injector::TaskFakes;
+use std::backtrace::Backtrace;
+use std::sync::Arc;
+
+#[cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec",
+    target_arch = "arm"
+))]
+use std::marker::PhantomData;
+
+/// Pauses and resumes a single fake, returned when the fake is installed.
+///
+/// While paused, calls run the real function (or the fake installed below it), and the fake
+/// keeps its configuration, JIT memory and call count verifier until it is resumed. Pausing
+/// and resuming have no effect once the fake is restored.
+///
+/// # Example
+///
+/// ```rust
+/// use injectorpp::interface::injector::*;
+///
+/// #[inline(never)]
+/// fn is_online() -> bool {
+///     std::hint::black_box(false)
+/// }
+///
+/// let mut injector = InjectorPP::new();
+/// let handle = injector
+///     .when_called(injectorpp::func!(fn (is_online)() -> bool))
+///     .will_return_boolean(true);
+///
+/// handle.pause();
+/// assert!(!is_online());
+///
+/// handle.resume();
+/// assert!(is_online());
+/// ```
+pub struct FakeHandle {
+    target: FakeTarget,
+    /// Thread-local fakes can only be paused on the thread that installed them.
+    #[cfg(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec",
+        target_arch = "arm"
+    ))]
+    _not_send: PhantomData<*const ()>,
+    /// The return addresses of the calls, logged after `record_callers()`.
+    #[cfg(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec",
+        target_arch = "arm"
+    ))]
+    callers: Option<CallLog<usize>>,
+    /// The backtraces of the calls, logged after `record_backtraces()`.
+    #[cfg(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec",
+        target_arch = "arm"
+    ))]
+    backtraces: Option<CallLog<Arc<Backtrace>>>,
+}
+
+enum FakeTarget {
+    /// A `PatchGuard`, by id.
+    Global(u64),
+    /// A `Redirect`, by id.
+    Redirect(u64),
+    /// A registration in the installing thread's replacement stack.
+    #[cfg(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec",
+        target_arch = "arm"
+    ))]
+    ThreadLocal { method_key: usize, id: u64 },
+    /// A replacement entered by the `scope()` futures of a `new_task_local()` injector.
+    #[cfg(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec",
+        target_arch = "arm"
+    ))]
+    TaskLocal {
+        fakes: TaskFakes,
+        method_key: usize,
+        replacement: usize,
+    },
+}
+
+impl FakeHandle {
+    pub(crate) fn global(id: u64) -> Self {
+        Self::with_target(FakeTarget::Global(id))
+    }
+
+    pub(crate) fn redirect(id: u64) -> Self {
+        Self::with_target(FakeTarget::Redirect(id))
+    }
+
+    #[cfg(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec",
+        target_arch = "arm"
+    ))]
+    pub(crate) fn thread_local(method_key: usize, id: u64) -> Self {
+        Self::with_target(FakeTarget::ThreadLocal { method_key, id })
+    }
+
+    #[cfg(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec",
+        target_arch = "arm"
+    ))]
+    pub(crate) fn task_local(fakes: TaskFakes, method_key: usize, replacement: usize) -> Self {
+        Self::with_target(FakeTarget::TaskLocal {
+            fakes,
+            method_key,
+            replacement,
+        })
+    }
+
+    fn with_target(target: FakeTarget) -> Self {
+        Self {
+            target,
+            #[cfg(any(
+                target_arch = "x86_64",
+                target_arch = "aarch64",
+                target_arch = "arm64ec",
+                target_arch = "arm"
+            ))]
+            _not_send: PhantomData,
+            #[cfg(any(
+                target_arch = "x86_64",
+                target_arch = "aarch64",
+                target_arch = "arm64ec",
+                target_arch = "arm"
+            ))]
+            callers: None,
+            #[cfg(any(
+                target_arch = "x86_64",
+                target_arch = "aarch64",
+                target_arch = "arm64ec",
+                target_arch = "arm"
+            ))]
+            backtraces: None,
+        }
+    }
+
+    /// Reads the callers and the backtraces of the calls of the fake from the logs, if they
+    /// are logged.
+    #[cfg(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec",
+        target_arch = "arm"
+    ))]
+    pub(crate) fn with_logs(
+        mut self,
+        callers: Option<CallLog<usize>>,
+        backtraces: Option<CallLog<Arc<Backtrace>>>,
+    ) -> Self {
+        self.callers = callers;
+        self.backtraces = backtraces;
+        self
+    }
+
+    /// Returns the callers of the latest calls of the fake, oldest first, up to the last 1024.
+    /// Calls made while the fake is paused are not included.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the fake was installed without `WhenCalledBuilder::record_callers()`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use injectorpp::interface::injector::*;
+    ///
+    /// #[inline(never)]
+    /// fn is_online() -> bool {
+    ///     std::hint::black_box(false)
+    /// }
+    ///
+    /// let mut injector = InjectorPP::new();
+    /// let handle = injector
+    ///     .when_called(injectorpp::func!(fn (is_online)() -> bool))
+    ///     .record_callers()
+    ///     .will_return_boolean(true);
+    ///
+    /// assert!(is_online());
+    /// let callers = handle.callers();
+    /// assert_eq!(callers.len(), 1);
+    /// println!("is_online() called from {}", callers[0]);
+    /// ```
+    pub fn callers(&self) -> Vec<Caller> {
+        #[cfg(any(
+            target_arch = "x86_64",
+            target_arch = "aarch64",
+            target_arch = "arm64ec",
+            target_arch = "arm"
+        ))]
+        if let Some(log) = &self.callers {
+            let log = log.lock().unwrap_or_else(|e| e.into_inner());
+            return log.iter().map(|&address| Caller::new(address)).collect();
+        }
+        panic!("callers() requires the fake to be installed after record_callers()");
+    }
+
+    /// Returns the backtraces of the latest calls of the fake, oldest first, up to the last
+    /// 1024. Calls made while the fake is paused are not included.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the fake was installed without `WhenCalledBuilder::record_backtraces()`.
+    pub fn backtraces(&self) -> Vec<Arc<Backtrace>> {
+        #[cfg(any(
+            target_arch = "x86_64",
+            target_arch = "aarch64",
+            target_arch = "arm64ec",
+            target_arch = "arm"
+        ))]
+        if let Some(log) = &self.backtraces {
+            let log = log.lock().unwrap_or_else(|e| e.into_inner());
+            return log.iter().cloned().collect();
+        }
+        panic!("backtraces() requires the fake to be installed after record_backtraces()");
+    }
+
+    /// Panics unless a call of the fake has a frame whose function name contains `function`
+    /// in its backtrace, such as `"cleanup_job"` or `"my_crate::jobs::"`. The panic shows the
+    /// backtrace of every call.
+    ///
+    /// Functions the compiler inlined have no frame of their own, so the code path is best
+    /// checked in debug builds.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the fake was installed without `WhenCalledBuilder::record_backtraces()`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use injectorpp::interface::injector::*;
+    ///
+    /// #[inline(never)]
+    /// fn charge(amount: u32) -> bool {
+    ///     std::hint::black_box(amount == 0)
+    /// }
+    ///
+    /// #[inline(never)]
+    /// fn checkout() -> bool {
+    ///     std::hint::black_box(charge(100))
+    /// }
+    ///
+    /// let mut injector = InjectorPP::new();
+    /// let handle = injector
+    ///     .when_called(injectorpp::func!(fn (charge)(u32) -> bool))
+    ///     .record_backtraces()
+    ///     .will_execute(injectorpp::fake!(
+    ///         func_type: fn(_amount: u32) -> bool,
+    ///         returns: true
+    ///     ));
+    ///
+    /// assert!(checkout());
+    /// handle.assert_reached_through("checkout");
+    /// ```
+    pub fn assert_reached_through(&self, function: &str) {
+        let backtraces = self.backtraces();
+        if backtraces.iter().any(|backtrace| {
+            frame_names(backtrace)
+                .iter()
+                .any(|name| name.contains(function))
+        }) {
+            return;
+        }
+
+        let mut message = format!(
+            "The fake was not reached through {}; it was called {} time(s)",
+            function,
+            backtraces.len()
+        );
+        for (index, backtrace) in backtraces.iter().enumerate() {
+            message.push_str(&format!("\n\ncall {}:\n{}", index + 1, backtrace));
+        }
+        panic!("{}", message);
+    }
+
+    /// Lets calls reach the real function until `resume()` is called.
+    ///
+    /// # Panics
+    ///
+    /// Panics for a global fake when another global fake of the same function was installed
+    /// after it and is still in effect.
+    pub fn pause(&self) {
+        self.set_paused(true);
+    }
+
+    /// Installs the fake again after `pause()`.
+    ///
+    /// # Panics
+    ///
+    /// Panics for a global fake when another global fake of the same function was installed
+    /// after it and is still in effect.
+    pub fn resume(&self) {
+        self.set_paused(false);
+    }
+
+    fn set_paused(&self, paused: bool) {
+        match &self.target {
+            FakeTarget::Global(id) => {
+                if !crate::injector_core::common::set_global_patch_paused(*id, paused) {
+                    panic!(
+                        "Cannot {} a global fake while a later global fake of the same function is installed",
+                        if paused { "pause" } else { "resume" }
+                    );
+                }
+            }
+            FakeTarget::Redirect(id) => {
+                crate::injector_core::redirect::set_redirect_paused(*id, paused);
+            }
+            #[cfg(any(
+                target_arch = "x86_64",
+                target_arch = "aarch64",
+                target_arch = "arm64ec",
+                target_arch = "arm"
+            ))]
+            FakeTarget::ThreadLocal { method_key, id } => {
+                crate::injector_core::thread_local_registry::stack_set_paused(
+                    *method_key,
+                    *id,
+                    paused,
+                );
+            }
+            #[cfg(any(
+                target_arch = "x86_64",
+                target_arch = "aarch64",
+                target_arch = "arm64ec",
+                target_arch = "arm"
+            ))]
+            FakeTarget::TaskLocal {
+                fakes,
+                method_key,
+                replacement,
+            } => {
+                let mut fakes = fakes.write().unwrap_or_else(|e| e.into_inner());
+                for fake in fakes
+                    .iter_mut()
+                    .filter(|fake| fake.0 == *method_key && fake.1 == *replacement)
+                {
+                    fake.2 = paused;
+                }
+            }
+        }
+    }
+}
+
+/// Returns the function names of the frames of `backtrace`, as it formats them.
+fn frame_names(backtrace: &Backtrace) -> Vec<String> {
+    // Frames are formatted `  12: path::to::function`, each followed by its location.
+    backtrace
+        .to_string()
+        .lines()
+        .filter_map(|line| {
+            let (index, name) = line.trim_start().split_once(": ")?;
+            index
+                .chars()
+                .all(|c| c.is_ascii_digit())
+                .then(|| name.to_string())
+        })
+        .collect()
+}
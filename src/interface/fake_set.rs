@@ -0,0 +1,98 @@
+use crate::interface::injector::InjectorPP;
+
+use std::sync::Arc;
+
+/// Configures fakes on an injector, as added to a `FakeSet`.
+type Installer = Arc<dyn Fn(&mut InjectorPP) + Send + Sync>;
+
+/// A named, reusable collection of fakes, such as "filesystem happy path" or "network offline".
+///
+/// A set is defined once and installed into any injector with `InjectorPP::install()`. Each
+/// fake is added as a closure configuring the injector, called on every installation. Sets
+/// compose with `include()`.
+///
+/// A `fake!` with `times` counts calls in a counter shared by every installation of the set,
+/// so sets installed by tests running in parallel should leave call counts to the tests.
+///
+/// # Example
+///
+/// ```rust
+/// use injectorpp::interface::injector::*;
+///
+/// #[inline(never)]
+/// fn is_connected() -> bool {
+///     std::hint::black_box(true)
+/// }
+///
+/// #[inline(never)]
+/// fn can_resolve() -> bool {
+///     std::hint::black_box(true)
+/// }
+///
+/// fn offline() -> FakeSet {
+///     FakeSet::new("network offline")
+///         .with(|injector| {
+///             injector
+///                 .when_called(injectorpp::func!(fn (is_connected)() -> bool))
+///                 .will_return_boolean(false);
+///         })
+///         .with(|injector| {
+///             injector
+///                 .when_called(injectorpp::func!(fn (can_resolve)() -> bool))
+///                 .will_return_boolean(false);
+///         })
+/// }
+///
+/// let mut injector = InjectorPP::new();
+/// injector.install(&offline());
+///
+/// assert!(!is_connected());
+/// assert!(!can_resolve());
+/// ```
+#[derive(Clone)]
+pub struct FakeSet {
+    name: String,
+    installers: Vec<Installer>,
+}
+
+impl FakeSet {
+    /// Creates an empty set. `name` identifies the set in panics raised while installing it.
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            installers: Vec::new(),
+        }
+    }
+
+    /// Adds fakes configured by `install`, which is called on every installation.
+    pub fn with(mut self, install: impl Fn(&mut InjectorPP) + Send + Sync + 'static) -> Self {
+        self.installers.push(Arc::new(install));
+        self
+    }
+
+    /// Adds every fake of `other`. A function faked by both sets gets the fake added last.
+    pub fn include(mut self, other: &FakeSet) -> Self {
+        self.installers.extend(other.installers.iter().cloned());
+        self
+    }
+
+    /// Returns the name of this set.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub(crate) fn install(&self, injector: &mut InjectorPP) {
+        for install in &self.installers {
+            if let Err(panic) =
+                std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| install(injector)))
+            {
+                let message = panic
+                    .downcast_ref::<String>()
+                    .map(String::as_str)
+                    .or_else(|| panic.downcast_ref::<&str>().copied())
+                    .unwrap_or("unknown panic");
+                panic!("Failed to install fake set '{}': {}", self.name, message);
+            }
+        }
+    }
+}
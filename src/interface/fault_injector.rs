@@ -0,0 +1,197 @@
+use std::hash::{BuildHasher, Hasher};
+use std::sync::{Arc, Mutex};
+
+/// Environment variable whose value `FaultInjector::from_env()` uses as the seed.
+const SEED_VAR: &str = "INJECTORPP_FAULT_SEED";
+
+/// Makes a share of the calls of faked functions fail, drawn from a seeded random number
+/// generator, so chaos-style tests fail the same calls every time they run with one seed.
+///
+/// Pass it to `WhenCalledBuilder::will_fail()` to make some calls of a function fail and let
+/// the others reach the original function, or to `WhenCalledBuilder::inject_faults()` to make
+/// some calls of a fake fail. The `fake!` given with it builds the error of each failing call.
+///
+/// Clones share the generator. A test that panics while an injector is alive prints the seed,
+/// which replays the same failures when set in `INJECTORPP_FAULT_SEED`.
+///
+/// # Example
+///
+/// ```rust
+/// use injectorpp::interface::injector::*;
+///
+/// #[inline(never)]
+/// fn send(payload: &[u8]) -> std::io::Result<usize> {
+///     Ok(std::hint::black_box(payload.len()))
+/// }
+///
+/// let faults = FaultInjector::new(7).failure_rate(0.5);
+/// let mut injector = InjectorPP::new();
+/// injector
+///     .when_called(injectorpp::func!(fn (send)(&[u8]) -> std::io::Result<usize>))
+///     .will_fail(&faults, injectorpp::fake!(
+///         func_type: fn(_payload: &[u8]) -> std::io::Result<usize>,
+///         returns: Err(std::io::ErrorKind::ConnectionReset.into())
+///     ));
+///
+/// let sent = (0..100).filter(|_| send(b"ping").is_ok()).count();
+/// assert_eq!(sent as u64, faults.calls() - faults.failures());
+/// ```
+#[derive(Clone)]
+pub struct FaultInjector {
+    state: Arc<FaultState>,
+}
+
+struct FaultState {
+    seed: u64,
+    rate: Mutex<f64>,
+    draws: Mutex<Draws>,
+}
+
+struct Draws {
+    rng: u64,
+    calls: u64,
+    failures: u64,
+}
+
+impl FaultInjector {
+    /// Creates an injector failing 10% of the calls, drawn from `seed`.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            state: Arc::new(FaultState {
+                seed,
+                rate: Mutex::new(0.1),
+                draws: Mutex::new(Draws {
+                    rng: seed,
+                    calls: 0,
+                    failures: 0,
+                }),
+            }),
+        }
+    }
+
+    /// Creates an injector failing 10% of the calls, seeded from `INJECTORPP_FAULT_SEED`, or
+    /// with a random seed when it is not set.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `INJECTORPP_FAULT_SEED` is not an unsigned integer.
+    pub fn from_env() -> Self {
+        let seed = match std::env::var(SEED_VAR) {
+            Ok(value) => value.trim().parse().unwrap_or_else(|_| {
+                panic!("{} must be an unsigned integer, got {:?}", SEED_VAR, value)
+            }),
+            Err(_) => std::collections::hash_map::RandomState::new()
+                .build_hasher()
+                .finish(),
+        };
+        Self::new(seed)
+    }
+
+    /// Sets the share of the calls that fail, from 0.0 (none) to 1.0 (all).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rate` is not within 0.0 and 1.0.
+    pub fn failure_rate(self, rate: f64) -> Self {
+        if !(0.0..=1.0).contains(&rate) {
+            panic!("The failure rate must be within 0.0 and 1.0, got {}", rate);
+        }
+        *self.state.rate.lock().unwrap_or_else(|e| e.into_inner()) = rate;
+        self
+    }
+
+    /// Returns the seed the failures are drawn from.
+    pub fn seed(&self) -> u64 {
+        self.state.seed
+    }
+
+    /// Returns how many calls were drawn.
+    pub fn calls(&self) -> u64 {
+        self.state.draws().calls
+    }
+
+    /// Returns how many of the drawn calls failed.
+    pub fn failures(&self) -> u64 {
+        self.state.draws().failures
+    }
+
+    /// Draws whether the next call fails. Calls of faked functions draw on their own; call it
+    /// from a `boxed_closure!` to fail part of its calls with the same generator.
+    pub fn should_fail(&self) -> bool {
+        self.state.draw()
+    }
+
+    /// Returns the function the dispatcher calls to draw whether a call fails.
+    #[cfg(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec",
+        target_arch = "arm"
+    ))]
+    pub(crate) fn roller(&self) -> Arc<dyn Fn() -> bool + Send + Sync> {
+        let state = self.state.clone();
+        Arc::new(move || state.draw())
+    }
+}
+
+impl FaultState {
+    fn draws(&self) -> std::sync::MutexGuard<'_, Draws> {
+        self.draws.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn draw(&self) -> bool {
+        let rate = *self.rate.lock().unwrap_or_else(|e| e.into_inner());
+        let mut draws = self.draws();
+
+        // splitmix64, which gives well-spread values even from consecutive seeds.
+        draws.rng = draws.rng.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = draws.rng;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+
+        let fails = ((z >> 11) as f64) / ((1u64 << 53) as f64) < rate;
+        draws.calls += 1;
+        if fails {
+            draws.failures += 1;
+        }
+        fails
+    }
+}
+
+impl Drop for FaultState {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            eprintln!(
+                "[injectorpp] faults injected with seed {}; set {}={} to replay them",
+                self.seed, SEED_VAR, self.seed
+            );
+        }
+    }
+}
+
+impl std::fmt::Display for FaultInjector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let draws = self.state.draws();
+        write!(
+            f,
+            "seed {}: {} of {} calls failed",
+            self.state.seed, draws.failures, draws.calls
+        )
+    }
+}
+
+impl std::fmt::Debug for FaultInjector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let draws = self.state.draws();
+        f.debug_struct("FaultInjector")
+            .field("seed", &self.state.seed)
+            .field(
+                "failure_rate",
+                &*self.state.rate.lock().unwrap_or_else(|e| e.into_inner()),
+            )
+            .field("calls", &draws.calls)
+            .field("failures", &draws.failures)
+            .finish()
+    }
+}
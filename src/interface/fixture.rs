@@ -0,0 +1,516 @@
+use std::fmt::Write as _;
+
+/// A value recorded in a `Cassette`, written as JSON.
+///
+/// Numbers keep their text, so that integers of any width and floats round-trip exactly.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Fixture {
+    Null,
+    Bool(bool),
+    Number(String),
+    String(String),
+    Array(Vec<Fixture>),
+    /// Fields in the order they were written.
+    Object(Vec<(String, Fixture)>),
+}
+
+impl Fixture {
+    /// Returns the field `key` of an object.
+    pub fn get(&self, key: &str) -> Option<&Fixture> {
+        match self {
+            Fixture::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// Returns the single field of an object, as written for enum variants like `{"Ok": 1}`.
+    pub fn variant(&self) -> Option<(&str, &Fixture)> {
+        match self {
+            Fixture::Object(fields) if fields.len() == 1 => {
+                Some((fields[0].0.as_str(), &fields[0].1))
+            }
+            _ => None,
+        }
+    }
+
+    /// Parses a value written by `to_string()`.
+    pub fn parse(text: &str) -> Result<Fixture, String> {
+        let mut parser = Parser {
+            text: text.as_bytes(),
+            pos: 0,
+        };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        if parser.pos != text.len() {
+            return Err(parser.error("unexpected trailing characters"));
+        }
+        Ok(value)
+    }
+}
+
+impl std::fmt::Display for Fixture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Fixture::Null => f.write_str("null"),
+            Fixture::Bool(value) => write!(f, "{}", value),
+            Fixture::Number(text) => f.write_str(text),
+            Fixture::String(text) => write_string(f, text),
+            Fixture::Array(items) => {
+                f.write_char('[')?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                f.write_char(']')
+            }
+            Fixture::Object(fields) => {
+                f.write_char('{')?;
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                f.write_char('}')
+            }
+        }
+    }
+}
+
+fn write_string(f: &mut std::fmt::Formatter<'_>, text: &str) -> std::fmt::Result {
+    f.write_char('"')?;
+    for c in text.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+struct Parser<'a> {
+    text: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> String {
+        format!("{} at offset {}", message, self.pos)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.pos < self.text.len() && self.text[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), String> {
+        self.skip_whitespace();
+        if self.text.get(self.pos) != Some(&byte) {
+            return Err(self.error(&format!("expected '{}'", byte as char)));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn keyword(&mut self, keyword: &str, value: Fixture) -> Result<Fixture, String> {
+        if !self.text[self.pos..].starts_with(keyword.as_bytes()) {
+            return Err(self.error("unexpected character"));
+        }
+        self.pos += keyword.len();
+        Ok(value)
+    }
+
+    fn value(&mut self) -> Result<Fixture, String> {
+        self.skip_whitespace();
+        match self.text.get(self.pos) {
+            None => Err(self.error("unexpected end")),
+            Some(b'n') => self.keyword("null", Fixture::Null),
+            Some(b't') => self.keyword("true", Fixture::Bool(true)),
+            Some(b'f') => self.keyword("false", Fixture::Bool(false)),
+            Some(b'"') => self.string().map(Fixture::String),
+            Some(b'[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                self.skip_whitespace();
+                if self.text.get(self.pos) == Some(&b']') {
+                    self.pos += 1;
+                    return Ok(Fixture::Array(items));
+                }
+                loop {
+                    items.push(self.value()?);
+                    self.skip_whitespace();
+                    match self.text.get(self.pos) {
+                        Some(b',') => self.pos += 1,
+                        Some(b']') => {
+                            self.pos += 1;
+                            return Ok(Fixture::Array(items));
+                        }
+                        _ => return Err(self.error("expected ',' or ']'")),
+                    }
+                }
+            }
+            Some(b'{') => {
+                self.pos += 1;
+                let mut fields = Vec::new();
+                self.skip_whitespace();
+                if self.text.get(self.pos) == Some(&b'}') {
+                    self.pos += 1;
+                    return Ok(Fixture::Object(fields));
+                }
+                loop {
+                    self.skip_whitespace();
+                    let key = self.string()?;
+                    self.expect(b':')?;
+                    fields.push((key, self.value()?));
+                    self.skip_whitespace();
+                    match self.text.get(self.pos) {
+                        Some(b',') => self.pos += 1,
+                        Some(b'}') => {
+                            self.pos += 1;
+                            return Ok(Fixture::Object(fields));
+                        }
+                        _ => return Err(self.error("expected ',' or '}'")),
+                    }
+                }
+            }
+            Some(_) => {
+                let start = self.pos;
+                while self.pos < self.text.len()
+                    && matches!(
+                        self.text[self.pos],
+                        b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9'
+                    )
+                {
+                    self.pos += 1;
+                }
+                if start == self.pos {
+                    return Err(self.error("unexpected character"));
+                }
+                // Only ASCII was consumed, so the slice is valid UTF-8.
+                let number = std::str::from_utf8(&self.text[start..self.pos]).unwrap_or_default();
+                Ok(Fixture::Number(number.to_string()))
+            }
+        }
+    }
+
+    /// Reads the 4 hex digits of a `\u` escape at `pos`.
+    fn hex4(&self, pos: usize) -> Option<u32> {
+        let hex = std::str::from_utf8(self.text.get(pos..pos + 4)?).ok()?;
+        u32::from_str_radix(hex, 16).ok()
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        if self.text.get(self.pos) != Some(&b'"') {
+            return Err(self.error("expected a string"));
+        }
+        self.pos += 1;
+        let mut bytes = Vec::new();
+        loop {
+            match self.text.get(self.pos) {
+                None => return Err(self.error("unterminated string")),
+                Some(b'"') => {
+                    self.pos += 1;
+                    return String::from_utf8(bytes).map_err(|_| self.error("invalid UTF-8"));
+                }
+                Some(b'\\') => {
+                    let escaped = match self.text.get(self.pos + 1) {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'u') => {
+                            let mut code = self
+                                .hex4(self.pos + 2)
+                                .ok_or_else(|| self.error("invalid \\u escape"))?;
+                            // Characters outside the BMP are escaped as a UTF-16 surrogate pair.
+                            if (0xD800..0xDC00).contains(&code) {
+                                let low = match self.text.get(self.pos + 6..self.pos + 8) {
+                                    Some(b"\\u") => self.hex4(self.pos + 8),
+                                    _ => None,
+                                }
+                                .filter(|low| (0xDC00..0xE000).contains(low))
+                                .ok_or_else(|| self.error("unpaired surrogate in \\u escape"))?;
+                                code = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00);
+                                self.pos += 6;
+                            }
+                            self.pos += 4;
+                            char::from_u32(code).ok_or_else(|| self.error("invalid \\u escape"))?
+                        }
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    self.pos += 2;
+                    let mut buf = [0; 4];
+                    bytes.extend_from_slice(escaped.encode_utf8(&mut buf).as_bytes());
+                }
+                Some(&byte) => {
+                    bytes.push(byte);
+                    self.pos += 1;
+                }
+            }
+        }
+    }
+}
+
+/// A type whose values a `Cassette` can record and replay.
+///
+/// Implemented for integers, floats, `bool`, `char`, `String`, `()`, `Vec`, `Option`, `Result`,
+/// tuples of up to 4 values and `std::io::Error`. Implement it for the return types of the
+/// functions to record.
+///
+/// # Example
+///
+/// ```rust
+/// use injectorpp::interface::injector::*;
+///
+/// struct Quote {
+///     symbol: String,
+///     price: u32,
+/// }
+///
+/// impl Recordable for Quote {
+///     fn record(&self) -> Fixture {
+///         (self.symbol.clone(), self.price).record()
+///     }
+///
+///     fn replay(fixture: &Fixture) -> Option<Self> {
+///         let (symbol, price) = <(String, u32)>::replay(fixture)?;
+///         Some(Quote { symbol, price })
+///     }
+/// }
+/// ```
+pub trait Recordable: Sized {
+    /// Returns the value as written in the cassette.
+    fn record(&self) -> Fixture;
+
+    /// Reads back a value written by `record()`, or `None` if `fixture` is not one.
+    fn replay(fixture: &Fixture) -> Option<Self>;
+}
+
+macro_rules! recordable_number {
+    ($($ty:ty),*) => {
+        $(
+            impl Recordable for $ty {
+                fn record(&self) -> Fixture {
+                    Fixture::Number(self.to_string())
+                }
+
+                fn replay(fixture: &Fixture) -> Option<Self> {
+                    match fixture {
+                        Fixture::Number(text) => text.parse().ok(),
+                        _ => None,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+recordable_number!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+
+macro_rules! recordable_float {
+    ($($ty:ty),*) => {
+        $(
+            impl Recordable for $ty {
+                // JSON has no NaN or infinities, which are written as strings.
+                fn record(&self) -> Fixture {
+                    if self.is_finite() {
+                        Fixture::Number(self.to_string())
+                    } else {
+                        Fixture::String(self.to_string())
+                    }
+                }
+
+                fn replay(fixture: &Fixture) -> Option<Self> {
+                    match fixture {
+                        Fixture::Number(text) | Fixture::String(text) => text.parse().ok(),
+                        _ => None,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+recordable_float!(f32, f64);
+
+impl Recordable for bool {
+    fn record(&self) -> Fixture {
+        Fixture::Bool(*self)
+    }
+
+    fn replay(fixture: &Fixture) -> Option<Self> {
+        match fixture {
+            Fixture::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+impl Recordable for char {
+    fn record(&self) -> Fixture {
+        Fixture::String(self.to_string())
+    }
+
+    fn replay(fixture: &Fixture) -> Option<Self> {
+        let text = String::replay(fixture)?;
+        let mut chars = text.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(c),
+            _ => None,
+        }
+    }
+}
+
+impl Recordable for String {
+    fn record(&self) -> Fixture {
+        Fixture::String(self.clone())
+    }
+
+    fn replay(fixture: &Fixture) -> Option<Self> {
+        match fixture {
+            Fixture::String(text) => Some(text.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl Recordable for () {
+    fn record(&self) -> Fixture {
+        Fixture::Null
+    }
+
+    fn replay(fixture: &Fixture) -> Option<Self> {
+        matches!(fixture, Fixture::Null).then_some(())
+    }
+}
+
+impl<T: Recordable> Recordable for Vec<T> {
+    fn record(&self) -> Fixture {
+        Fixture::Array(self.iter().map(Recordable::record).collect())
+    }
+
+    fn replay(fixture: &Fixture) -> Option<Self> {
+        match fixture {
+            Fixture::Array(items) => items.iter().map(T::replay).collect(),
+            _ => None,
+        }
+    }
+}
+
+impl<T: Recordable> Recordable for Option<T> {
+    fn record(&self) -> Fixture {
+        match self {
+            Some(value) => Fixture::Object(vec![("Some".to_string(), value.record())]),
+            None => Fixture::Null,
+        }
+    }
+
+    fn replay(fixture: &Fixture) -> Option<Self> {
+        match fixture.variant() {
+            Some(("Some", value)) => T::replay(value).map(Some),
+            _ => matches!(fixture, Fixture::Null).then_some(None),
+        }
+    }
+}
+
+impl<T: Recordable, E: Recordable> Recordable for Result<T, E> {
+    fn record(&self) -> Fixture {
+        match self {
+            Ok(value) => Fixture::Object(vec![("Ok".to_string(), value.record())]),
+            Err(error) => Fixture::Object(vec![("Err".to_string(), error.record())]),
+        }
+    }
+
+    fn replay(fixture: &Fixture) -> Option<Self> {
+        match fixture.variant()? {
+            ("Ok", value) => T::replay(value).map(Ok),
+            ("Err", error) => E::replay(error).map(Err),
+            _ => None,
+        }
+    }
+}
+
+macro_rules! recordable_tuple {
+    ($(($($name:ident),+)),*) => {
+        $(
+            impl<$($name: Recordable),+> Recordable for ($($name,)+) {
+                #[allow(non_snake_case)]
+                fn record(&self) -> Fixture {
+                    let ($($name,)+) = self;
+                    Fixture::Array(vec![$($name.record()),+])
+                }
+
+                fn replay(fixture: &Fixture) -> Option<Self> {
+                    let Fixture::Array(items) = fixture else {
+                        return None;
+                    };
+                    let mut items = items.iter();
+                    let value = ($($name::replay(items.next()?)?,)+);
+                    items.next().is_none().then_some(value)
+                }
+            }
+        )*
+    };
+}
+
+recordable_tuple!((A), (A, B), (A, B, C), (A, B, C, D));
+
+/// Error kinds an `io::Error` is replayed with, by the name it is recorded with.
+const ERROR_KINDS: &[std::io::ErrorKind] = &[
+    std::io::ErrorKind::NotFound,
+    std::io::ErrorKind::PermissionDenied,
+    std::io::ErrorKind::ConnectionRefused,
+    std::io::ErrorKind::ConnectionReset,
+    std::io::ErrorKind::ConnectionAborted,
+    std::io::ErrorKind::NotConnected,
+    std::io::ErrorKind::AddrInUse,
+    std::io::ErrorKind::AddrNotAvailable,
+    std::io::ErrorKind::BrokenPipe,
+    std::io::ErrorKind::AlreadyExists,
+    std::io::ErrorKind::WouldBlock,
+    std::io::ErrorKind::InvalidInput,
+    std::io::ErrorKind::InvalidData,
+    std::io::ErrorKind::TimedOut,
+    std::io::ErrorKind::WriteZero,
+    std::io::ErrorKind::Interrupted,
+    std::io::ErrorKind::Unsupported,
+    std::io::ErrorKind::UnexpectedEof,
+    std::io::ErrorKind::OutOfMemory,
+];
+
+/// Replayed with the kind and message it was recorded with, unknown kinds as `Other`.
+impl Recordable for std::io::Error {
+    fn record(&self) -> Fixture {
+        Fixture::Object(vec![
+            (
+                "kind".to_string(),
+                Fixture::String(format!("{:?}", self.kind())),
+            ),
+            ("message".to_string(), Fixture::String(self.to_string())),
+        ])
+    }
+
+    fn replay(fixture: &Fixture) -> Option<Self> {
+        let kind = String::replay(fixture.get("kind")?)?;
+        let message = String::replay(fixture.get("message")?)?;
+        let kind = ERROR_KINDS
+            .iter()
+            .copied()
+            .find(|known| format!("{:?}", known) == kind)
+            .unwrap_or(std::io::ErrorKind::Other);
+        Some(std::io::Error::new(kind, message))
+    }
+}
@@ -1,6 +1,9 @@
 use crate::injector_core::common::FuncPtrInternal;
 use std::any::TypeId;
+use std::future::Future;
+use std::pin::Pin;
 use std::ptr::NonNull;
+use std::task::{Context, Poll};
 
 /// A safe wrapper around a raw function pointer.
 ///
@@ -19,6 +22,9 @@ pub struct FuncPtr {
     pub(super) func_ptr_internal: FuncPtrInternal,
     pub(super) signature: &'static str,
     pub(super) type_id: Option<TypeId>,
+    pub(super) ret_layout: Option<ReturnLayout>,
+    pub(super) declared: Option<DeclaredSignature>,
+    pub(super) item: Option<&'static str>,
 }
 
 impl FuncPtr {
@@ -38,6 +44,9 @@ impl FuncPtr {
             func_ptr_internal: FuncPtrInternal::new(nn),
             signature,
             type_id: None,
+            ret_layout: None,
+            declared: None,
+            item: None,
         }
     }
 
@@ -58,6 +67,249 @@ impl FuncPtr {
             func_ptr_internal: FuncPtrInternal::new(nn),
             signature,
             type_id: Some(type_id),
+            ret_layout: None,
+            declared: None,
+            item: None,
         }
     }
+
+    /// Returns the function at `addr`, declared with the same signature as this one.
+    pub(crate) fn at_address(&self, addr: usize) -> Self {
+        let nn = NonNull::new(addr as *mut ()).expect("Pointer must not be null");
+        Self {
+            func_ptr_internal: unsafe { FuncPtrInternal::new(nn) },
+            signature: self.signature,
+            type_id: self.type_id,
+            ret_layout: self.ret_layout,
+            declared: self.declared.clone(),
+            item: self.item,
+        }
+    }
+
+    /// Records that the function returns `R` with the calling convention `abi`, so fakes
+    /// returning their value differently are rejected. Used internally by macros.
+    #[doc(hidden)]
+    pub fn __returning<R>(mut self, abi: &str) -> Self {
+        self.ret_layout = ReturnLayout::of::<R>(abi);
+        self
+    }
+
+    /// Records the type name of the function item, which names its path, so functions on the
+    /// deny-list are recognized. Used internally by macros.
+    #[doc(hidden)]
+    pub fn __item(mut self, type_name: &'static str) -> Self {
+        self.item = Some(type_name);
+        self
+    }
+
+    /// Records the sizes of the parameters and of the return value `R` the fake is declared
+    /// with, checked against the debug info of the function to fake with the
+    /// `debuginfo-check` feature. Used internally by `fake!`.
+    #[doc(hidden)]
+    pub fn __declared<R>(mut self, params: &[usize]) -> Self {
+        self.declared = Some(DeclaredSignature {
+            params: params.to_vec(),
+            ret_size: std::mem::size_of::<R>(),
+            ret_float: matches!(std::any::type_name::<R>(), "f32" | "f64"),
+        });
+        self
+    }
+}
+
+/// The sizes of the parameters and of the return value a fake is declared with.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(not(feature = "debuginfo-check"), allow(dead_code))]
+pub(crate) struct DeclaredSignature {
+    pub(crate) params: Vec<usize>,
+    pub(crate) ret_size: usize,
+    pub(crate) ret_float: bool,
+}
+
+/// How a function returns its value.
+///
+/// Values too large for the return registers, such as structs over 16 bytes on x86_64 and
+/// aarch64, are written through a hidden pointer passed by the caller (sret). A fake returning
+/// in registers to a caller expecting this, or the other way round, corrupts the stack.
+/// Likewise, `f32` and `f64` are returned in floating-point registers the caller of a
+/// function returning an integer does not read.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct ReturnLayout {
+    pub(crate) size: usize,
+    pub(crate) passing: ReturnPassing,
+}
+
+/// Where a value is returned, see `ReturnLayout`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ReturnPassing {
+    Registers,
+    FloatRegisters,
+    Memory,
+}
+
+impl ReturnLayout {
+    fn of<R>(abi: &str) -> Option<Self> {
+        let size = std::mem::size_of::<R>();
+        let float = matches!(std::any::type_name::<R>(), "f32" | "f64")
+            && cfg!(any(
+                target_arch = "x86_64",
+                target_arch = "aarch64",
+                target_arch = "arm64ec",
+                target_abi = "eabihf"
+            ));
+
+        returned_in_memory(size, abi).map(|in_memory| {
+            let passing = if in_memory {
+                ReturnPassing::Memory
+            } else if float {
+                ReturnPassing::FloatRegisters
+            } else {
+                ReturnPassing::Registers
+            };
+
+            Self { size, passing }
+        })
+    }
+
+    /// Describes where the value is returned, for panic messages.
+    pub(crate) fn describe(&self) -> String {
+        match self.passing {
+            ReturnPassing::Registers => format!("{} bytes in registers", self.size),
+            ReturnPassing::FloatRegisters => {
+                format!("{} bytes in a floating-point register", self.size)
+            }
+            ReturnPassing::Memory => format!("{} bytes through a hidden pointer", self.size),
+        }
+    }
+}
+
+/// Returns whether a value of `size` bytes is returned through a hidden pointer with the
+/// calling convention `abi`, or `None` when not known for the target. Only the size is
+/// considered, so float aggregates returned in vector registers on aarch64 are not told apart.
+fn returned_in_memory(size: usize, abi: &str) -> Option<bool> {
+    let win64 = |size| !matches!(size, 0 | 1 | 2 | 4 | 8);
+
+    match abi {
+        // rustc returns values of up to two pointers in registers.
+        "Rust" => Some(size > 2 * std::mem::size_of::<usize>()),
+        "win64" => Some(win64(size)),
+        _ if cfg!(all(windows, target_arch = "x86_64")) && abi != "sysv64" => Some(win64(size)),
+        _ if cfg!(any(
+            target_arch = "x86_64",
+            target_arch = "aarch64",
+            target_arch = "arm64ec"
+        )) =>
+        {
+            Some(size > 16)
+        }
+        _ => None,
+    }
+}
+
+/// The `poll` of a future type to fake, with the signature expected of its fakes.
+///
+/// Created by `async_func!`, either from a future or from a function returning one.
+pub struct AsyncTarget {
+    pub(super) poll: FuncPtr,
+    pub(super) expected_signature: &'static str,
+}
+
+impl AsyncTarget {
+    fn of<F: Future>(expected_signature: &'static str) -> Self {
+        let poll: fn(Pin<&mut F>, &mut Context<'_>) -> Poll<F::Output> = <F as Future>::poll;
+
+        Self {
+            poll: unsafe { FuncPtr::new(poll as *const (), std::any::type_name_of_val(&poll)) },
+            expected_signature,
+        }
+    }
+}
+
+impl<F: Future> From<(Pin<&mut F>, &'static str)> for AsyncTarget {
+    fn from(fake_pair: (Pin<&mut F>, &'static str)) -> Self {
+        Self::of::<F>(fake_pair.1)
+    }
+}
+
+/// Returns the target for the future returned by `make`, without calling it. Used internally
+/// by `async_func!`.
+#[doc(hidden)]
+pub fn __async_target<C, F, T>(_make: C, expected_signature: &'static str) -> AsyncTarget
+where
+    C: FnOnce() -> F,
+    F: Future<Output = T>,
+{
+    AsyncTarget::of::<F>(expected_signature)
+}
+
+/// Returns the target for a method generated by `async_trait`, checking that `make` returns a
+/// future of `T`. Used internally by `async_trait_func!`.
+///
+/// # Safety
+///
+/// `ptr` must point to the function called by `make`.
+#[doc(hidden)]
+pub unsafe fn __async_trait_target<C, R, T>(
+    ptr: *const (),
+    _make: C,
+    signature: &'static str,
+) -> FuncPtr
+where
+    C: FnOnce() -> R,
+    R: Future<Output = T>,
+{
+    FuncPtr::new(ptr, signature)
+}
+
+/// Gives the fake of a variadic function, which only takes the fixed arguments, the
+/// signature and type id of the function. Used internally by `fake!`.
+#[doc(hidden)]
+pub fn __variadic_fake(fake: FuncPtr, signature: &'static str, type_id: TypeId) -> FuncPtr {
+    FuncPtr {
+        signature,
+        type_id: Some(type_id),
+        ..fake
+    }
+}
+
+/// Stands for an argument of a function that is never called. Used internally by
+/// `async_func!`.
+#[doc(hidden)]
+pub fn __never<T>() -> T {
+    unreachable!("injectorpp never calls the function passed to async_func!")
+}
+
+/// Returns the address of the method at `index` in the vtable of the trait object `object`.
+/// Used internally by `dyn_func!`.
+///
+/// Rust does not specify the layout of trait objects and vtables. This relies on the layout
+/// used by rustc: a pointer to the data followed by a pointer to the vtable, which starts
+/// with the drop glue, size and alignment of the concrete type, followed by the methods.
+///
+/// # Safety
+///
+/// The trait of `object` must have a method callable on trait objects at `index`.
+#[doc(hidden)]
+pub unsafe fn __dyn_method<T: ?Sized>(object: &T, index: usize) -> *const () {
+    const VTABLE_HEADER: usize = 3;
+
+    let type_name = std::any::type_name::<T>();
+    if !type_name.starts_with("dyn ") || size_of::<&T>() != 2 * size_of::<usize>() {
+        panic!("dyn_func! needs a trait object, but got a reference to {type_name}");
+    }
+
+    // Safety: references to trait objects are a data pointer and a vtable pointer.
+    let [_, vtable] = unsafe { std::mem::transmute_copy::<&T, [*const usize; 2]>(&object) };
+
+    // Safety: the vtable starts with the drop glue, size and alignment of the concrete type.
+    let (size, align) = unsafe { (*vtable.add(1), *vtable.add(2)) };
+    if size != size_of_val(object) || align != align_of_val(object) {
+        panic!("dyn_func! cannot read the vtable of {type_name}: unexpected trait object layout");
+    }
+
+    let method = unsafe { *vtable.add(VTABLE_HEADER + index) } as *const ();
+    if method.is_null() {
+        panic!("dyn_func! found no method at index {index} of {type_name}");
+    }
+
+    method
 }
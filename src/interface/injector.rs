@@ -1,67 +1,309 @@
 #[allow(unused_imports)]
 use crate::injector_core::common::*;
+use crate::injector_core::function_lock::FunctionLock;
+use crate::injector_core::function_lock::LockError;
 use crate::injector_core::internal::*;
+#[cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec",
+    target_arch = "arm"
+))]
+use crate::interface::async_fn::AsyncFakeRegistration;
+use crate::interface::boxed_closure::ClosureRegistration;
+#[cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec",
+    target_arch = "arm"
+))]
+pub use crate::interface::async_fn::{AsyncFnPointer, __async_fn_target};
+pub use crate::interface::patch_strategy::PatchStrategy;
+#[cfg(feature = "external-engine")]
+pub use crate::injector_core::hook_engine::HookEngine;
+pub use crate::interface::boxed_closure::BoxedClosure;
+pub use crate::interface::boxed_closure::__call_boxed_closure;
+pub use crate::interface::caller::Caller;
+pub use crate::interface::cassette::{Cassette, CassetteMode};
+pub use crate::interface::c_symbol::CFnPointer;
+pub use crate::interface::cxx_symbol::demangle_cxx;
+pub use crate::interface::deny_list::DEFAULT_DENY_LIST;
+pub use crate::interface::expect::{AnyArgs, ArgsMatcher, Expect, ExpectWith, Expectation, Returning};
+pub use crate::interface::fake_handle::FakeHandle;
+pub use crate::interface::fake_set::FakeSet;
+pub use crate::interface::fault_injector::FaultInjector;
+pub use crate::interface::fixture::{Fixture, Recordable};
 pub use crate::interface::func_ptr::FuncPtr;
+use crate::interface::func_ptr::ReturnLayout;
+pub use crate::interface::func_ptr::{
+    AsyncTarget, __async_target, __async_trait_target, __dyn_method, __never, __variadic_fake,
+};
+pub use crate::interface::lock_timeout::LockTimeout;
+pub use crate::interface::mock::Mock;
+pub use crate::interface::patch_info::PatchInfo;
+pub use crate::interface::rust_symbol::RustFnPointer;
+pub use crate::interface::scenario::Scenario;
 pub use crate::interface::macros::__assert_future_output;
+pub use crate::interface::macros::__enter_internal;
+pub use crate::interface::macros::__fake_delay;
+pub use crate::interface::macros::__fake_entered;
+pub use crate::interface::macros::__fake_invoked;
+pub use crate::interface::macros::__fake_label;
+pub use crate::interface::macros::__set_errno;
+#[cfg(target_os = "windows")]
+pub use crate::interface::macros::__set_last_error;
+pub use crate::interface::macros::__poll_delay;
+pub use crate::interface::macros::__poll_pending;
+#[cfg(feature = "stream")]
+pub use crate::interface::macros::{__assert_stream_item, __stream_next_index};
 pub use crate::interface::macros::__type_id_of_val;
+pub use crate::interface::macros::__signature_of_val;
+pub use crate::interface::macros::__returning_of_val;
+pub use crate::interface::macros::{__DebugProbe, __ProbeDebug, __ProbeFallback};
 pub use crate::interface::verifier::CallCountVerifier;
 
 use std::future::Future;
-#[cfg(any(target_arch = "x86_64", target_arch = "aarch64", target_arch = "arm"))]
+#[cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec",
+    target_arch = "arm"
+))]
 use std::marker::PhantomData;
 use std::pin::Pin;
 use std::task::Context;
 use std::task::Poll;
 
-use std::sync::Mutex;
-use std::sync::MutexGuard;
 use std::sync::RwLock;
+#[cfg(not(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec",
+    target_arch = "arm"
+)))]
 use std::sync::RwLockReadGuard;
+#[cfg(not(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec",
+    target_arch = "arm"
+)))]
 use std::sync::RwLockWriteGuard;
 
-#[cfg(any(target_arch = "x86_64", target_arch = "aarch64", target_arch = "arm"))]
+#[cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec"
+))]
+use crate::injector_core::call_site::CallSiteScope;
+use crate::injector_core::redirect::{Redirect, RedirectMode};
+#[cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec",
+    target_arch = "arm"
+))]
 use crate::injector_core::thread_local_registry::ThreadRegistration;
 
-/// Normalize a type_name signature by removing anonymous lifetime annotations.
-/// Newer Rust versions (1.86+) render elided lifetimes as `&'_ T` instead of `&T`,
-/// which causes false signature mismatches.
+/// Normalize a type_name signature for comparison.
+///
+/// Lifetimes of arguments do not change how they are passed, yet type_name renders them
+/// inconsistently: newer Rust versions (1.86+) render elided lifetimes as `&'_ T` instead of
+/// `&T`, `'static` is not rendered at all, and `dyn Trait + '_` is parenthesized. So argument
+/// lifetimes are removed, along with the parentheses they leave around trait objects.
+/// Lifetimes of the return type and the `for<'a>` binder are kept: they tell a fake returning
+/// a borrow of its arguments from one returning a `'static` value. Paths into `std` are
+/// written with `core`, as `std` re-exports `core` and `alloc`.
 fn normalize_signature(sig: &str) -> String {
-    sig.replace("&'_ ", "&")
+    let (binder, rest) = split_binder(sig);
+    let (args, ret) = split_return_type(rest);
+
+    let args = strip_lifetimes(args)
+        .replace("for<> ", "")
+        .replace("<>", "");
+    canonical_crate_paths(&unparenthesize_dyn(&format!("{}{}{}", binder, args, ret)))
+}
+
+/// Splits the leading `for<'a, ...> ` binder, if any, from a signature.
+fn split_binder(sig: &str) -> (&str, &str) {
+    if !sig.starts_with("for<") {
+        return ("", sig);
+    }
+    match sig.find("> ") {
+        Some(end) => sig.split_at(end + 2),
+        None => ("", sig),
+    }
+}
+
+/// Splits a signature before the `->` of its return type, ignoring those of the function
+/// types among its arguments.
+fn split_return_type(sig: &str) -> (&str, &str) {
+    let mut depth = 0usize;
+    for (i, c) in sig.char_indices() {
+        match c {
+            '(' | '[' | '<' => depth += 1,
+            ')' | ']' => depth = depth.saturating_sub(1),
+            '>' if !sig[..i].ends_with('-') => depth = depth.saturating_sub(1),
+            '-' if depth == 0 && sig[i..].starts_with("->") => {
+                return sig.split_at(i);
+            }
+            _ => {}
+        }
+    }
+    (sig, "")
+}
+
+/// Removes the lifetimes from a type name, along with the separators they leave behind.
+fn strip_lifetimes(sig: &str) -> String {
+    let mut out = String::with_capacity(sig.len());
+    let mut chars = sig.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\'' {
+            out.push(c);
+            continue;
+        }
+
+        while chars
+            .next_if(|&next| next.is_alphanumeric() || next == '_')
+            .is_some()
+        {}
+
+        // Drop the separator following the lifetime, as in `&'a T` or `Ref<'a, T>`...
+        while chars.next_if_eq(&' ').is_some() {}
+        if chars.next_if_eq(&',').is_some() {
+            while chars.next_if_eq(&' ').is_some() {}
+        }
+
+        // ...or preceding it, as in `dyn Trait + 'a` or `Ref<T, 'a>`.
+        let trimmed = out.trim_end().len();
+        if out[..trimmed].ends_with('+')
+            || (out[..trimmed].ends_with(',') && chars.peek() == Some(&'>'))
+        {
+            out.truncate(trimmed - 1);
+            out.truncate(out.trim_end().len());
+        }
+    }
+
+    out
 }
 
-/// A `Mutex` that never stays poisoned: on panic it just recovers the guard.
-#[allow(dead_code)]
-struct NoPoisonMutex<T> {
-    inner: Mutex<T>,
+/// Removes the parentheses around trait objects, as in `Box<(dyn Fn() + Send)>`.
+fn unparenthesize_dyn(sig: &str) -> String {
+    let mut out = sig.to_string();
+
+    while let Some(open) = out.find("(dyn ") {
+        let mut depth = 0usize;
+        let close = out[open..].char_indices().find_map(|(i, c)| {
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                _ => {}
+            }
+            (depth == 0).then_some(open + i)
+        });
+
+        let Some(close) = close else {
+            break;
+        };
+        out.replace_range(close..close + 1, "");
+        out.replace_range(open..open + 1, "");
+    }
+
+    out
 }
 
-#[allow(dead_code)]
-impl<T> NoPoisonMutex<T> {
-    const fn new(value: T) -> Self {
-        Self {
-            inner: Mutex::new(value),
+/// Panics unless `target` has the signature expected of the fakes of a function, given by
+/// its type name and, when known, the `TypeId` of its function pointer type.
+///
+/// When both type ids are known they must be equal: they tell apart types whose names are the
+/// same, such as types of two versions of a crate, and signatures differing in lifetimes,
+/// such as a fake returning a borrow of its argument for a function returning `&'static str`.
+/// Otherwise the normalized type names are compared.
+fn check_signature(
+    lib: &InjectorPP,
+    expected_signature: &str,
+    expected_type_id: Option<std::any::TypeId>,
+    target: &FuncPtr,
+) {
+    match (expected_type_id, target.type_id) {
+        (Some(expected), Some(actual)) if expected == actual => {}
+        (Some(_), Some(_)) if expected_signature == target.signature => {
+            panic!(
+                "Signature mismatch: expected {:?} but got a different type with the same name{}",
+                expected_signature,
+                lib.label_suffix()
+            );
+        }
+        (Some(_), Some(_)) => {
+            panic!(
+                "Signature mismatch: expected {:?} but got {:?}{}",
+                expected_signature,
+                target.signature,
+                lib.label_suffix()
+            );
+        }
+        (None, _) | (_, None) => {
+            if normalize_signature(target.signature) != normalize_signature(expected_signature) {
+                panic!(
+                    "Signature mismatch: expected {:?} but got {:?}{}",
+                    expected_signature,
+                    target.signature,
+                    lib.label_suffix()
+                );
+            }
         }
     }
+}
+
+/// Rewrites paths starting with `std::` or `alloc::` to start with `core::`.
+pub(crate) fn canonical_crate_paths(sig: &str) -> String {
+    let mut out = String::with_capacity(sig.len());
+    let mut rest = sig;
 
-    fn lock(&self) -> MutexGuard<'_, T> {
-        match self.inner.lock() {
-            Ok(guard) => guard,
-            Err(poisoned) => poisoned.into_inner(),
+    while !rest.is_empty() {
+        let at_path_start = !out
+            .chars()
+            .next_back()
+            .is_some_and(|c| c.is_alphanumeric() || c == '_' || c == ':');
+
+        if at_path_start {
+            if let Some(path) = rest
+                .strip_prefix("std::")
+                .or_else(|| rest.strip_prefix("alloc::"))
+            {
+                out.push_str("core::");
+                rest = path;
+                continue;
+            }
         }
+
+        let c = rest.chars().next().unwrap();
+        out.push(c);
+        rest = &rest[c.len_utf8()..];
     }
+
+    out
 }
 
-/// Global mutex used on non-TLS architectures to serialize all patching.
-#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64", target_arch = "arm")))]
-static LOCK_FUNCTION: NoPoisonMutex<()> = NoPoisonMutex::new(());
+/// Formats a function address with its symbol name, when it can be resolved, for panics.
+fn func_display_name(func_addr: usize) -> String {
+    match crate::injector_core::symbols::symbol_name(func_addr) {
+        Some(symbol) => format!("{} ({:#x})", symbol, func_addr),
+        None => format!("the function at {:#x}", func_addr),
+    }
+}
 
-/// RwLock for coordinating thread-local vs global fakes.
-/// - `when_called()` (thread-local) acquires a **read** lock — multiple thread-local
-///   tests can run in parallel.
-/// - `when_called_globally()` acquires a **write** lock — blocks until all thread-local
-///   tests finish, and prevents new tests from starting. This is exactly 0.4.0 behavior
-///   for the duration of the global fake.
-static GLOBAL_FAKE_LOCK: RwLock<()> = RwLock::new(());
+/// RwLock used on non-TLS architectures, where every fake is global.
+/// - Each `InjectorPP` holds a **read** lock, so injectors faking different functions coexist.
+/// - `InjectorPP::prevent()` holds the **write** lock, so no injector is alive while it is.
+#[cfg(not(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec",
+    target_arch = "arm"
+)))]
+static PREVENT_LOCK: RwLock<()> = RwLock::new(());
 
 /// A high-level type that holds patch guards so that when it goes out of scope,
 /// the original function code is automatically restored.
@@ -74,33 +316,74 @@ static GLOBAL_FAKE_LOCK: RwLock<()> = RwLock::new(());
 ///
 /// Use `InjectorPP::new_global()` for 0.4.0-style global patching where fakes are visible
 /// to all threads (e.g., when faked functions are called from background timer threads).
-/// Global mode acquires an exclusive lock — other tests wait until the global injector drops.
+/// Each faked function is locked for the lifetime of the injector: thread-local fakes share
+/// the lock, global fakes hold it exclusively. A global fake only waits for injectors faking
+/// the same function, so unrelated tests keep running in parallel.
 ///
-/// On other architectures, InjectorPP always uses global patching with a global mutex.
+/// On other architectures, InjectorPP always uses global patching, locking each faked function
+/// exclusively.
 pub struct InjectorPP {
-    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64", target_arch = "arm"))]
+    #[cfg(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec",
+        target_arch = "arm"
+    ))]
     registrations: Vec<ThreadRegistration>,
     guards: Vec<PatchGuard>,
+    /// Call redirections installed with `PatchStrategy::Got` and `PatchStrategy::CallSites`.
+    redirects: Vec<Redirect>,
     verifiers: Vec<CallCountVerifier>,
-    /// Read guard: held by thread-local fakes. Allows parallel TLS tests.
-    /// Write guard: held by global fakes. Blocks all other tests.
-    _rw_guard: RwGuard,
+    /// Closures installed by `will_execute_closure()` with the address of the function they fake,
+    /// kept alive until the patches are restored.
+    closures: Vec<(usize, ClosureRegistration)>,
+    /// Fakes installed by `will_execute_async()`, holding their closures and pending futures.
+    #[cfg(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec",
+        target_arch = "arm"
+    ))]
+    async_fakes: Vec<AsyncFakeRegistration>,
+    /// Locks on the functions faked by this injector, released after the fakes are restored.
+    function_locks: Vec<FunctionLock>,
+    /// How long to wait for a function lock before panicking, set by `try_new()`.
+    lock_timeout: Option<std::time::Duration>,
     /// When true, `when_called()` uses direct code patching (0.4.0-style global).
     /// When false (default), uses thread-local dispatch.
     use_global: bool,
-    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64", target_arch = "arm"))]
+    /// Replacements entered by `scope()` futures, set by `new_task_local()`.
+    #[cfg(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec",
+        target_arch = "arm"
+    ))]
+    task_fakes: Option<TaskFakes>,
+    /// Optional label set by `new_named()`, included in panics raised on behalf of this injector.
+    name: Option<String>,
+    /// Replacement functions registered under `name`, unregistered on drop.
+    labeled_fakes: Vec<usize>,
+    /// Whether functions on the deny-list may be faked, set by `allow_dangerous()`.
+    allow_dangerous: bool,
+    /// The strategy of the fakes whose `when_called` builder does not choose one.
+    default_strategy: PatchStrategy,
+    /// Whether functions marked `#[do_not_fake]` may be faked, set by `ignore_do_not_fake()`.
+    ignore_do_not_fake: bool,
+    #[cfg(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec",
+        target_arch = "arm"
+    ))]
     _not_send: PhantomData<*const ()>,
-    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64", target_arch = "arm")))]
-    _lock: MutexGuard<'static, ()>,
-}
-
-/// Holds either a read or write guard on GLOBAL_FAKE_LOCK, or none (transient during upgrade).
-/// The guard values are never read directly — they exist solely to keep the lock held.
-#[allow(dead_code)]
-enum RwGuard {
-    None,
-    Read(RwLockReadGuard<'static, ()>),
-    Write(RwLockWriteGuard<'static, ()>),
+    #[cfg(not(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec",
+        target_arch = "arm"
+    )))]
+    _prevent_guard: RwLockReadGuard<'static, ()>,
 }
 
 impl InjectorPP {
@@ -119,49 +402,245 @@ impl InjectorPP {
     /// let injector = InjectorPP::new();
     /// ```
     pub fn new() -> Self {
-        #[cfg(any(target_arch = "x86_64", target_arch = "aarch64", target_arch = "arm"))]
+        #[cfg(any(
+            target_arch = "x86_64",
+            target_arch = "aarch64",
+            target_arch = "arm64ec",
+            target_arch = "arm"
+        ))]
         {
-            // Acquire a read lock — allows parallel TLS tests, blocks while a global fake is active.
-            let rw_guard = match GLOBAL_FAKE_LOCK.read() {
-                Ok(g) => g,
-                Err(e) => e.into_inner(),
-            };
             Self {
                 registrations: Vec::new(),
                 guards: Vec::new(),
+                redirects: Vec::new(),
                 verifiers: Vec::new(),
-                _rw_guard: RwGuard::Read(rw_guard),
+                closures: Vec::new(),
+                async_fakes: Vec::new(),
+                task_fakes: None,
+                function_locks: Vec::new(),
+                lock_timeout: None,
                 use_global: false,
+                name: None,
+                labeled_fakes: Vec::new(),
+                allow_dangerous: false,
+                default_strategy: PatchStrategy::InlineBranch,
+                ignore_do_not_fake: false,
                 _not_send: PhantomData,
             }
         }
 
-        #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64", target_arch = "arm")))]
+        #[cfg(not(any(
+            target_arch = "x86_64",
+            target_arch = "aarch64",
+            target_arch = "arm64ec",
+            target_arch = "arm"
+        )))]
         {
-            let lock = LOCK_FUNCTION.lock();
-            let rw_guard = match GLOBAL_FAKE_LOCK.read() {
+            let prevent_guard = match PREVENT_LOCK.read() {
                 Ok(g) => g,
                 Err(e) => e.into_inner(),
             };
             Self {
                 guards: Vec::new(),
+                redirects: Vec::new(),
                 verifiers: Vec::new(),
-                _rw_guard: RwGuard::Read(rw_guard),
+                closures: Vec::new(),
+                function_locks: Vec::new(),
+                lock_timeout: None,
                 use_global: false,
-                _lock: lock,
+                name: None,
+                labeled_fakes: Vec::new(),
+                allow_dangerous: false,
+                default_strategy: PatchStrategy::InlineBranch,
+                ignore_do_not_fake: false,
+                _prevent_guard: prevent_guard,
             }
         }
     }
 
+    /// Creates a new thread-local `InjectorPP` instance that gives up waiting for locks after `timeout`.
+    ///
+    /// A forgotten or deadlocked injector otherwise makes every other test faking the same
+    /// function block forever. With `try_new()`, the construction returns a `LockTimeout`
+    /// instead of blocking longer than `timeout`, and faking a function that another injector
+    /// holds for longer than `timeout` panics with the same `LockTimeout` message, or returns
+    /// it from `try_when_called()`. The message names the thread, which is the test name under
+    /// the default test harness, of every injector holding the lock, and where it was taken
+    /// when diagnostic logging is enabled with `INJECTORPP_LOG` or `set_diagnostic_logging`.
+    ///
+    /// On x86_64, aarch64 and arm, construction never waits, so this only returns `Err` on
+    /// other architectures while `InjectorPP::prevent()` is held. Use `try_when_called()` there
+    /// to get the timeout of a function lock as an `Err`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use injectorpp::interface::injector::*;
+    /// use std::time::Duration;
+    ///
+    /// fn is_ready() -> bool {
+    ///     false
+    /// }
+    ///
+    /// let mut injector = InjectorPP::try_new(Duration::from_secs(30)).unwrap();
+    /// injector
+    ///     .when_called(injectorpp::func!(fn (is_ready)() -> bool))
+    ///     .will_return_boolean(true);
+    ///
+    /// assert!(is_ready());
+    /// ```
+    pub fn try_new(timeout: std::time::Duration) -> Result<Self, LockTimeout> {
+        #[cfg(any(
+            target_arch = "x86_64",
+            target_arch = "aarch64",
+            target_arch = "arm64ec",
+            target_arch = "arm"
+        ))]
+        {
+            let mut injector = Self::new();
+            injector.lock_timeout = Some(timeout);
+            Ok(injector)
+        }
+
+        #[cfg(not(any(
+            target_arch = "x86_64",
+            target_arch = "aarch64",
+            target_arch = "arm64ec",
+            target_arch = "arm"
+        )))]
+        {
+            let deadline = std::time::Instant::now() + timeout;
+            let prevent_guard = loop {
+                match PREVENT_LOCK.try_read() {
+                    Ok(g) => break g,
+                    Err(std::sync::TryLockError::Poisoned(e)) => break e.into_inner(),
+                    Err(std::sync::TryLockError::WouldBlock) => {
+                        if std::time::Instant::now() >= deadline {
+                            return Err(LockTimeout::new(
+                                0,
+                                timeout,
+                                vec!["InjectorPP::prevent()".to_string()],
+                            ));
+                        }
+
+                        std::thread::sleep(std::time::Duration::from_millis(1));
+                    }
+                }
+            };
+
+            Ok(Self {
+                guards: Vec::new(),
+                redirects: Vec::new(),
+                verifiers: Vec::new(),
+                closures: Vec::new(),
+                function_locks: Vec::new(),
+                lock_timeout: Some(timeout),
+                use_global: false,
+                name: None,
+                labeled_fakes: Vec::new(),
+                allow_dangerous: false,
+                default_strategy: PatchStrategy::InlineBranch,
+                ignore_do_not_fake: false,
+                _prevent_guard: prevent_guard,
+            })
+        }
+    }
+
+    /// Creates a new `InjectorPP` instance whose fakes only take effect on the calling thread.
+    ///
+    /// This is what `new()` does, spelled out for tests that want to make the scope of their
+    /// fakes explicit. The patched function jumps to a dispatcher that looks up a replacement
+    /// registered by the current thread and falls back to the original code otherwise, so
+    /// tests faking different functions, or the same function, run in parallel. Each faked
+    /// function is locked shared: only a `new_global()` injector faking the same function
+    /// makes it wait.
+    ///
+    /// On architectures without thread-local dispatch, fakes are visible to all threads and
+    /// each faked function is locked exclusively as with `new_global()`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use injectorpp::interface::injector::*;
+    ///
+    /// fn is_ready() -> bool {
+    ///     false
+    /// }
+    ///
+    /// let mut injector = InjectorPP::new_thread_local();
+    /// injector
+    ///     .when_called(injectorpp::func!(fn (is_ready)() -> bool))
+    ///     .will_return_boolean(true);
+    ///
+    /// assert!(is_ready());
+    /// assert!(!std::thread::spawn(is_ready).join().unwrap());
+    /// ```
+    pub fn new_thread_local() -> Self {
+        Self::new()
+    }
+
+    /// Creates a new `InjectorPP` instance whose fakes only take effect inside `scope()` futures.
+    ///
+    /// Fakes are not visible on any thread by default, including the calling thread. A future
+    /// wrapped with `scope()` sees them whenever it is polled, on whichever thread polls it, so
+    /// a fake can be limited to one tokio task of a multi-thread runtime while other tasks
+    /// call the original. Tasks spawned from inside the scope do not inherit it.
+    ///
+    /// Async fakes installed with `will_execute_async` are not supported.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use injectorpp::interface::injector::*;
+    ///
+    /// fn region() -> String {
+    ///     "westus".to_string()
+    /// }
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut injector = InjectorPP::new_task_local();
+    ///     injector
+    ///         .when_called(injectorpp::func!(fn (region)() -> String))
+    ///         .will_execute(injectorpp::fake!(
+    ///             func_type: fn() -> String,
+    ///             returns: "eastus".to_string()
+    ///         ));
+    ///
+    ///     let faked = tokio::spawn(injector.scope(async { region() }));
+    ///     let original = tokio::spawn(async { region() });
+    ///
+    ///     assert_eq!(faked.await.unwrap(), "eastus");
+    ///     assert_eq!(original.await.unwrap(), "westus");
+    ///     assert_eq!(region(), "westus");
+    /// }
+    /// ```
+    #[cfg(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec",
+        target_arch = "arm"
+    ))]
+    pub fn new_task_local() -> Self {
+        let mut injector = Self::new();
+        injector.task_fakes = Some(std::sync::Arc::default());
+        injector
+    }
+
     /// Creates a new `InjectorPP` instance with **global** (0.4.0-style) patching.
     ///
     /// All `when_called()` fakes will use direct code patching, visible to **all threads**.
-    /// This acquires an exclusive write lock — other tests (both thread-local and global)
-    /// will wait until this instance is dropped.
+    /// Each faked function is locked exclusively until this instance is dropped: other tests
+    /// faking the same function wait, tests faking other functions keep running. A test that
+    /// calls a globally faked function without faking it sees the fake.
     ///
     /// Use this when the faked functions will be called from background threads,
     /// timers, or thread pools.
     ///
+    /// On x86_64, dropping the injector waits for threads still running the code generated
+    /// for its fakes to leave it before freeing it. Other architectures free that code right
+    /// away, so no thread may be inside a global fake when its injector is dropped there.
+    ///
     /// # Example
     ///
     /// ```rust
@@ -171,290 +650,2789 @@ impl InjectorPP {
     /// let injector = InjectorPP::new_global();
     /// ```
     pub fn new_global() -> Self {
-        #[cfg(any(target_arch = "x86_64", target_arch = "aarch64", target_arch = "arm"))]
+        #[cfg(any(
+            target_arch = "x86_64",
+            target_arch = "aarch64",
+            target_arch = "arm64ec",
+            target_arch = "arm"
+        ))]
         {
-            let rw_guard = match GLOBAL_FAKE_LOCK.write() {
-                Ok(g) => g,
-                Err(e) => e.into_inner(),
-            };
             Self {
                 registrations: Vec::new(),
                 guards: Vec::new(),
+                redirects: Vec::new(),
                 verifiers: Vec::new(),
-                _rw_guard: RwGuard::Write(rw_guard),
+                closures: Vec::new(),
+                async_fakes: Vec::new(),
+                task_fakes: None,
+                function_locks: Vec::new(),
+                lock_timeout: None,
                 use_global: true,
+                name: None,
+                labeled_fakes: Vec::new(),
+                allow_dangerous: false,
+                default_strategy: PatchStrategy::InlineBranch,
+                ignore_do_not_fake: false,
                 _not_send: PhantomData,
             }
         }
 
-        #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64", target_arch = "arm")))]
+        #[cfg(not(any(
+            target_arch = "x86_64",
+            target_arch = "aarch64",
+            target_arch = "arm64ec",
+            target_arch = "arm"
+        )))]
         {
-            let lock = LOCK_FUNCTION.lock();
-            let rw_guard = match GLOBAL_FAKE_LOCK.write() {
+            let prevent_guard = match PREVENT_LOCK.read() {
                 Ok(g) => g,
                 Err(e) => e.into_inner(),
             };
             Self {
                 guards: Vec::new(),
+                redirects: Vec::new(),
                 verifiers: Vec::new(),
-                _rw_guard: RwGuard::Write(rw_guard),
+                closures: Vec::new(),
+                function_locks: Vec::new(),
+                lock_timeout: None,
                 use_global: true,
-                _lock: lock,
+                name: None,
+                labeled_fakes: Vec::new(),
+                allow_dangerous: false,
+                default_strategy: PatchStrategy::InlineBranch,
+                ignore_do_not_fake: false,
+                _prevent_guard: prevent_guard,
             }
         }
     }
 
-    /// On x86_64 with thread-local dispatch, this is a no-op guard since
-    /// thread isolation is automatic. On other architectures, it holds
-    /// the global mutex to prevent other threads from patching.
-    pub fn prevent() -> Preventer {
-        #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64", target_arch = "arm")))]
-        {
-            let lock = LOCK_FUNCTION.lock();
-            Preventer { _lock: lock }
-        }
+    /// Creates a new thread-local `InjectorPP` instance with a name.
+    ///
+    /// The name is included in every panic raised on behalf of this injector: fakes called
+    /// with unexpected arguments or too many times, call count verification failures, and
+    /// signature mismatches. This helps to tell injectors apart in tests that use several.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use injectorpp::interface::injector::InjectorPP;
+    ///
+    /// let injector = InjectorPP::new_named("mount_happy");
+    /// assert_eq!(injector.name(), Some("mount_happy"));
+    /// ```
+    pub fn new_named(name: &str) -> Self {
+        let mut injector = Self::new();
+        injector.name = Some(name.to_string());
+        injector
+    }
 
-        #[cfg(any(target_arch = "x86_64", target_arch = "aarch64", target_arch = "arm"))]
-        {
-            Preventer {
-                _not_send: PhantomData,
-            }
-        }
+    /// Returns the name given to `new_named()`, if any.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
     }
 
-    /// Begins faking a function.
+    /// Wraps `future` so the fakes of this injector apply while it is polled.
     ///
-    /// Accepts a FuncPtr to the function you want to fake. Use the `func!` macro to obtain this pointer.
-    ///
-    /// # Parameters
-    ///
-    /// - `func`: A FuncPtr holds the pointer to the target function obtained via `func!` macro.
+    /// Fakes installed after the call apply too. Once the injector is dropped the future
+    /// calls the original functions. The injector must not be dropped from inside the future.
     ///
-    /// # Returns
+    /// # Panics
     ///
-    /// A builder (`WhenCalledBuilder`) to further specify the fake behavior.
+    /// Panics if the injector was not created by `new_task_local()`.
+    #[cfg(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec",
+        target_arch = "arm"
+    ))]
+    pub fn scope<F: Future>(&self, future: F) -> TaskScoped<F> {
+        let Some(fakes) = &self.task_fakes else {
+            panic!(
+                "scope() requires an injector created by InjectorPP::new_task_local(){}",
+                self.label_suffix()
+            );
+        };
+
+        TaskScoped {
+            fakes: fakes.clone(),
+            future,
+        }
+    }
+
+    /// Lets this injector fake functions on the deny-list, such as allocator entry points or
+    /// `Vec::len`, which unsafe code relies on. Faking them can cause undefined behavior in
+    /// code that never calls the fake directly.
     ///
     /// # Example
     ///
     /// ```rust
     /// use injectorpp::interface::injector::*;
-    /// use std::path::Path;
-    ///
-    /// fn fake_exists(_path: &Path) -> bool {
-    ///     true
-    /// }
     ///
     /// let mut injector = InjectorPP::new();
     /// injector
-    ///     .when_called(injectorpp::func!(fn (Path::exists)(&Path) -> bool))
-    ///     .will_execute_raw(injectorpp::func!(fn (fake_exists)(&Path) -> bool));
+    ///     .allow_dangerous()
+    ///     .when_called(injectorpp::func!(fn (Vec::<u8>::capacity)(&Vec<u8>) -> usize))
+    ///     .will_execute_raw(injectorpp::closure!(|_: &Vec<u8>| 0, fn(&Vec<u8>) -> usize));
     ///
-    /// assert!(Path::new("/non/existent/path").exists());
+    /// assert_eq!(Vec::<u8>::with_capacity(8).capacity(), 0);
     /// ```
-    pub fn when_called(&mut self, func: FuncPtr) -> WhenCalledBuilder<'_> {
-        let when = WhenCalled::new(func.func_ptr_internal);
-        WhenCalledBuilder {
-            lib: self,
-            when,
-            expected_signature: func.signature,
-            expected_type_id: func.type_id,
-        }
+    pub fn allow_dangerous(&mut self) -> &mut Self {
+        self.allow_dangerous = true;
+        self
     }
 
-    /// Begins faking a function.
-    ///
-    /// Accepts a FuncPtr to the function you want to fake. Use the `func!` macro to obtain this pointer.
-    ///
-    /// # Parameters
-    ///
-    /// - `func`: A FuncPtr holds the pointer to the target function obtained via `func!` macro.
-    ///
-    /// # Returns
-    ///
-    /// A builder (`WhenCalledBuilder`) to further specify the fake behavior.
+    /// Chooses the strategy of the fakes installed through `when_called()` and
+    /// `when_called_unchecked()` whose builder does not call `strategy()`,
+    /// `PatchStrategy::InlineBranch` by default.
     ///
-    /// # Safety
+    /// # Panics
     ///
-    /// This method is unsafe because it skips type check.
+    /// Panics if the strategy is not supported on the running platform, see
+    /// `PatchStrategy::is_supported()`, or if it redirects calls and the injector was not
+    /// created with `InjectorPP::new_global()`.
     ///
     /// # Example
     ///
-    /// ```rust
+    /// ```rust,no_run
     /// use injectorpp::interface::injector::*;
-    /// use std::path::Path;
     ///
-    /// fn fake_exists(_path: &Path) -> bool {
-    ///     true
+    /// extern "C" fn fake_id() -> u32 {
+    ///     42
     /// }
     ///
-    /// let mut injector = InjectorPP::new();
-    ///
+    /// let mut injector = InjectorPP::new_global();
+    /// injector.set_default_strategy(PatchStrategy::Got);
     /// unsafe {
     ///     injector
-    ///         .when_called_unchecked(injectorpp::func_unchecked!(Path::exists))
-    ///         .will_execute_raw_unchecked(injectorpp::func_unchecked!(fake_exists));
+    ///         .when_called_unchecked(injectorpp::func_unchecked!(libc::getuid))
+    ///         .will_execute_raw_unchecked(injectorpp::func_unchecked!(fake_id));
+    ///     injector
+    ///         .when_called_unchecked(injectorpp::func_unchecked!(libc::getgid))
+    ///         .will_execute_raw_unchecked(injectorpp::func_unchecked!(fake_id));
     /// }
     ///
-    /// assert!(Path::new("/non/existent/path").exists());
+    /// assert_eq!(unsafe { libc::getuid() }, 42);
     /// ```
-    pub unsafe fn when_called_unchecked(&mut self, func: FuncPtr) -> WhenCalledBuilder<'_> {
-        let when = WhenCalled::new(func.func_ptr_internal);
-        WhenCalledBuilder {
-            lib: self,
-            when,
-            expected_signature: "",
-            expected_type_id: None,
+    pub fn set_default_strategy(&mut self, strategy: PatchStrategy) -> &mut Self {
+        self.check_strategy(strategy);
+        self.default_strategy = strategy;
+        self
+    }
+
+    /// Panics if `strategy` cannot be used by this injector.
+    fn check_strategy(&self, strategy: PatchStrategy) {
+        if let Some(reason) = strategy.unsupported_reason() {
+            panic!(
+                "PatchStrategy::{:?} is not supported on this platform: {}{}",
+                strategy,
+                reason,
+                self.label_suffix()
+            );
+        }
+        if strategy != PatchStrategy::InlineBranch {
+            self.require_global(&format!("PatchStrategy::{:?}", strategy));
         }
     }
 
-    /// Begins faking an asynchronous function.
-    ///
-    /// Accepts a pinned mutable reference to the async function future. Use the `async_func!` macro to obtain this reference.
-    ///
-    /// # Parameters
+    /// Panics if this injector does not patch globally, as `what` rewrites code or data every
+    /// thread runs.
+    fn require_global(&self, what: &str) {
+        if !self.use_global {
+            panic!(
+                "{} redirects the calls of every thread, so it requires an injector created \
+                 with InjectorPP::new_global(){}",
+                what,
+                self.label_suffix()
+            );
+        }
+    }
+
+    /// Lets this injector fake functions their crate marks with `#[injectorpp::do_not_fake]`.
     ///
-    /// - `_`: A pinned mutable reference to the async function future.
+    /// # Safety
     ///
-    /// # Returns
+    /// The crate marking a function relies on its behavior for soundness. The fake must keep
+    /// every invariant the unsafe code of the crate expects from it.
+    pub unsafe fn ignore_do_not_fake(&mut self) -> &mut Self {
+        self.ignore_do_not_fake = true;
+        self
+    }
+
+    /// Adds a function to the deny-list of functions `when_called()` refuses to fake unless the
+    /// injector opts in with `allow_dangerous()`. The deny-list is shared by every injector and
+    /// starts as `DEFAULT_DENY_LIST`.
     ///
-    /// A builder (`WhenCalledBuilderAsync`) to further specify the async fake behavior.
+    /// `path` is the path of the function, such as `my_crate::pool::Pool::release`, or of a
+    /// module or type to deny every function under it. Paths without `::` are symbol names, for
+    /// functions declared in `extern` blocks.
     ///
     /// # Example
     ///
     /// ```rust
-    /// use injectorpp::interface::injector::*;
-    ///
-    /// async fn async_add_one(x: u32) -> u32 {
-    ///     x + 1
-    /// }
-    ///
-    /// #[tokio::main]
-    /// async fn main() {
-    ///     let mut injector = InjectorPP::new();
-    ///     injector
-    ///         .when_called_async(injectorpp::async_func!(async_add_one(u32::default()), u32))
-    ///         .will_return_async(injectorpp::async_return!(123, u32));
+    /// use injectorpp::interface::injector::InjectorPP;
     ///
-    ///     let result = async_add_one(5).await;
-    ///     assert_eq!(result, 123); // The patched value
-    /// }
+    /// InjectorPP::deny("my_crate::pool::Pool::release");
     /// ```
-    pub fn when_called_async<F, T>(
-        &mut self,
-        fake_pair: (Pin<&mut F>, &'static str),
-    ) -> WhenCalledBuilderAsync<'_>
+    pub fn deny(path: &str) {
+        crate::interface::deny_list::add(path);
+    }
+
+    /// Replaces the deny-list of functions `when_called()` refuses to fake, e.g. with part of
+    /// `DEFAULT_DENY_LIST`. See `deny()` for the format of the entries.
+    pub fn set_deny_list<I, S>(paths: I)
     where
-        F: Future<Output = T>,
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
     {
-        let poll_fn: fn(Pin<&mut F>, &mut Context<'_>) -> Poll<T> = <F as Future>::poll;
-        let when = WhenCalled::new(unsafe {
-            FuncPtr::new(poll_fn as *const (), std::any::type_name_of_val(&poll_fn))
-        }.func_ptr_internal);
+        crate::interface::deny_list::set(paths.into_iter().map(Into::into).collect());
+    }
 
-        let signature = fake_pair.1;
-        WhenCalledBuilderAsync {
-            lib: self,
-            when,
-            expected_signature: signature,
-            expected_type_id: None,
+    /// Panics if `func` is marked `#[do_not_fake]` or is on the deny-list, unless this injector
+    /// opted in with `ignore_do_not_fake()` or `allow_dangerous()` respectively.
+    fn check_fakeable(&self, func: &FuncPtr) {
+        let addr = func.func_ptr_internal.as_ptr() as usize;
+        let name = || match func.item {
+            Some(item) => format!("{} ({:#x})", item, addr),
+            None => func_display_name(addr),
+        };
+
+        if !self.ignore_do_not_fake && crate::interface::do_not_fake::is_marked(addr) {
+            panic!(
+                "Refusing to fake {}: its crate marks it #[do_not_fake], as its unsafe code relies \
+                 on its behavior. Call the unsafe ignore_do_not_fake() on the injector to fake it \
+                 anyway{}",
+                name(),
+                self.label_suffix()
+            );
+        }
+        if self.allow_dangerous {
+            return;
+        }
+
+        let symbol = crate::injector_core::symbols::symbol_name(addr);
+        if let Some(entry) = crate::interface::deny_list::denied_entry(func.item, symbol.as_deref())
+        {
+            panic!(
+                "Refusing to fake {}: it is on the deny-list as {:?}, as unsafe code may rely on \
+                 its behavior. Call allow_dangerous() on the injector to fake it anyway{}",
+                name(),
+                entry,
+                self.label_suffix()
+            );
+        }
+    }
+
+    /// Suffix appended to panic messages raised on behalf of this injector.
+    fn label_suffix(&self) -> String {
+        crate::interface::labels::suffix(self.name.as_deref())
+    }
+
+    /// Associates the replacement function with this injector's name, if it has one.
+    fn label_fake(&mut self, target: &FuncPtr) {
+        if let Some(name) = &self.name {
+            let addr = target.func_ptr_internal.as_ptr() as usize;
+            crate::interface::labels::register_fake(addr, name);
+            self.labeled_fakes.push(addr);
+        }
+    }
+
+    /// Keeps a thread-local registration. For `new_task_local()` injectors the replacement is
+    /// taken off the current thread and only entered by `scope()` futures.
+    #[cfg(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec",
+        target_arch = "arm"
+    ))]
+    fn push_registration(&mut self, mut reg: ThreadRegistration) -> FakeHandle {
+        let _internal = crate::injector_core::thread_local_registry::enter_internal();
+        let handle = match &self.task_fakes {
+            Some(task_fakes) => {
+                let (method_key, replacement) = reg.detach();
+                task_fakes.write().unwrap_or_else(|e| e.into_inner()).push((
+                    method_key,
+                    replacement,
+                    false,
+                ));
+                FakeHandle::task_local(task_fakes.clone(), method_key, replacement)
+            }
+            None => {
+                let (method_key, id, _) = reg.key();
+                FakeHandle::thread_local(method_key, id)
+            }
+        };
+
+        let handle = handle.with_logs(reg.caller_log(), reg.backtrace_log());
+        self.registrations.push(reg);
+        handle
+    }
+
+    /// Checks `verifier` when this injector is dropped, after its fakes are restored.
+    pub(crate) fn push_verifier(&mut self, verifier: CallCountVerifier) {
+        self.verifiers.push(verifier);
+    }
+
+    /// Keeps a global patch guard.
+    fn push_guard(&mut self, guard: PatchGuard) -> FakeHandle {
+        let handle = FakeHandle::global(guard.id());
+        self.guards.push(guard);
+        handle
+    }
+
+    /// Keeps a call redirection.
+    fn push_redirect(&mut self, redirect: Redirect) -> FakeHandle {
+        let handle = FakeHandle::redirect(redirect.id());
+        self.redirects.push(redirect);
+        handle
+    }
+
+    /// Locks `func` for the lifetime of this injector and starts faking it.
+    ///
+    /// Thread-local fakes lock the function shared, global fakes exclusively. If this injector
+    /// already fakes `func`, the old fake is restored first so the new one replaces it.
+    fn when(&mut self, func: FuncPtrInternal) -> WhenCalled {
+        match self.try_when(func) {
+            Ok(when) => when,
+            Err(timeout) => panic!("{}{}", timeout, self.label_suffix()),
+        }
+    }
+
+    /// Like `when`, but returns a `LockTimeout` instead of panicking when the lock on `func`
+    /// is not acquired in time.
+    fn try_when(&mut self, func: FuncPtrInternal) -> Result<WhenCalled, LockTimeout> {
+        // Fake the function body rather than a thunk leading to it.
+        let func_addr = crate::injector_core::thunk::resolve_thunks(func.as_ptr() as usize);
+        let func = unsafe {
+            FuncPtrInternal::new(
+                std::ptr::NonNull::new(func_addr as *mut ()).expect("thunk target is null"),
+            )
+        };
+        if self
+            .function_locks
+            .iter()
+            .any(|lock| lock.func_addr() == func_addr)
+        {
+            self.restore_fakes_of(&func);
+        } else {
+            self.try_lock_functions(&[func_addr])?;
+        }
+
+        Ok(WhenCalled::new(func))
+    }
+
+    /// Locks the functions at `func_addrs` this injector does not hold yet, in ascending
+    /// address order.
+    fn lock_functions(&mut self, func_addrs: &[usize]) {
+        if let Err(timeout) = self.try_lock_functions(func_addrs) {
+            panic!("{}{}", timeout, self.label_suffix());
+        }
+    }
+
+    /// Like `lock_functions`, but returns a `LockTimeout` instead of panicking when a lock is
+    /// not acquired in time. None of the functions are locked then.
+    fn try_lock_functions(&mut self, func_addrs: &[usize]) -> Result<(), LockTimeout> {
+        let missing: Vec<usize> = func_addrs
+            .iter()
+            .copied()
+            .filter(|func_addr| {
+                self.function_locks
+                    .iter()
+                    .all(|lock| lock.func_addr() != *func_addr)
+            })
+            .collect();
+        let thread_local = !self.use_global
+            && cfg!(any(
+                target_arch = "x86_64",
+                target_arch = "aarch64",
+                target_arch = "arm64ec",
+                target_arch = "arm"
+            ));
+        match FunctionLock::acquire_all(&missing, !thread_local, self.lock_timeout) {
+            Ok(locks) => {
+                self.function_locks.extend(locks);
+                Ok(())
+            }
+            Err((func_addr, LockError::Timeout(holders))) => Err(LockTimeout::new(
+                func_addr,
+                self.lock_timeout.unwrap_or_default(),
+                holders,
+            )),
+            Err((func_addr, LockError::HeldByCurrentThread { exclusive })) => panic!(
+                "Cannot fake {} {} while another injector on this thread fakes it {}. \
+                 Thread-local and global fakes of the same function cannot be stacked{}",
+                func_display_name(func_addr),
+                if exclusive {
+                    "globally"
+                } else {
+                    "thread-locally"
+                },
+                if exclusive {
+                    "thread-locally"
+                } else {
+                    "globally"
+                },
+                self.label_suffix()
+            ),
+            Err((func_addr, LockError::Deadlock(holders))) => panic!(
+                "Cannot fake {} {}: waiting for it would deadlock, as it is held by an \
+                 injector waiting for a function this injector fakes. Lock the functions \
+                 together with lock_all() in both tests{}, held by:{}",
+                func_display_name(func_addr),
+                if thread_local {
+                    "thread-locally"
+                } else {
+                    "globally"
+                },
+                self.label_suffix(),
+                holders
+                    .iter()
+                    .map(|holder| format!("\n  - {}", holder))
+                    .collect::<String>()
+            ),
+        }
+    }
+
+    /// Restores every fake this injector installed for `func`, keeping its call count
+    /// verifiers so calls made before the restore are still checked.
+    fn restore_fakes_of(&mut self, func: &FuncPtrInternal) {
+        #[cfg(any(
+            target_arch = "x86_64",
+            target_arch = "aarch64",
+            target_arch = "arm64ec",
+            target_arch = "arm"
+        ))]
+        let func_addr = crate::injector_core::thread_local_registry::method_key_of(func);
+        #[cfg(not(any(
+            target_arch = "x86_64",
+            target_arch = "aarch64",
+            target_arch = "arm64ec",
+            target_arch = "arm"
+        )))]
+        let func_addr = func.as_ptr() as usize;
+
+        #[cfg(any(
+            target_arch = "x86_64",
+            target_arch = "aarch64",
+            target_arch = "arm64ec",
+            target_arch = "arm"
+        ))]
+        {
+            // Waits for scoped futures being polled, as in drop.
+            let task_fakes = self
+                .task_fakes
+                .as_ref()
+                .map(|task_fakes| task_fakes.write().unwrap_or_else(|e| e.into_inner()));
+            if let Some(mut task_fakes) = task_fakes {
+                task_fakes.retain(|(method_key, _, _)| *method_key != func_addr);
+            }
+
+            let mut index = self.registrations.len();
+            while index > 0 {
+                index -= 1;
+                if self.registrations[index].info().0 == func_addr {
+                    drop(self.registrations.remove(index));
+                }
+            }
+        }
+
+        let mut index = self.guards.len();
+        while index > 0 {
+            index -= 1;
+            if self.guards[index].info().0 == func_addr {
+                drop(self.guards.remove(index));
+            }
+        }
+
+        self.redirects
+            .retain(|redirect| redirect.func_addr() != func_addr);
+
+        // Closures are released once no patch routes to them, so their shim slot can be reused.
+        let resolved_addr = crate::injector_core::thunk::resolve_thunks(func.as_ptr() as usize);
+        self.closures.retain(|(faked, _)| *faked != resolved_addr);
+    }
+
+    /// Returns whether this injector was created by `new_task_local()`.
+    fn is_task_local(&self) -> bool {
+        #[cfg(any(
+            target_arch = "x86_64",
+            target_arch = "aarch64",
+            target_arch = "arm64ec",
+            target_arch = "arm"
+        ))]
+        {
+            self.task_fakes.is_some()
+        }
+
+        #[cfg(not(any(
+            target_arch = "x86_64",
+            target_arch = "aarch64",
+            target_arch = "arm64ec",
+            target_arch = "arm"
+        )))]
+        {
+            false
+        }
+    }
+
+    /// On x86_64 with thread-local dispatch, this is a no-op guard since
+    /// thread isolation is automatic. On other architectures, it waits until no
+    /// injector is alive and keeps new injectors from being created.
+    pub fn prevent() -> Preventer {
+        #[cfg(not(any(
+            target_arch = "x86_64",
+            target_arch = "aarch64",
+            target_arch = "arm64ec",
+            target_arch = "arm"
+        )))]
+        {
+            let lock = match PREVENT_LOCK.write() {
+                Ok(g) => g,
+                Err(e) => e.into_inner(),
+            };
+            Preventer { _lock: lock }
+        }
+
+        #[cfg(any(
+            target_arch = "x86_64",
+            target_arch = "aarch64",
+            target_arch = "arm64ec",
+            target_arch = "arm"
+        ))]
+        {
+            Preventer {
+                _not_send: PhantomData,
+            }
+        }
+    }
+
+    /// Returns the patches currently applied by this injector.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use injectorpp::interface::injector::*;
+    ///
+    /// let injector = InjectorPP::new();
+    /// assert!(injector.active_patches().is_empty());
+    /// ```
+    pub fn active_patches(&self) -> Vec<PatchInfo> {
+        let mut patches: Vec<PatchInfo> = self
+            .guards
+            .iter()
+            .map(|guard| {
+                let (func_addr, patch_size, jit_addr) = guard.info();
+                PatchInfo::new(func_addr, patch_size, jit_addr, false)
+            })
+            .collect();
+
+        patches.extend(
+            self.redirects
+                .iter()
+                .map(|redirect| PatchInfo::new(redirect.func_addr(), 0, 0, false)),
+        );
+
+        #[cfg(any(
+            target_arch = "x86_64",
+            target_arch = "aarch64",
+            target_arch = "arm64ec",
+            target_arch = "arm"
+        ))]
+        patches.extend(self.registrations.iter().map(|registration| {
+            let (func_addr, patch_size, jit_addr) = registration.info();
+            PatchInfo::new(func_addr, patch_size, jit_addr, true)
+        }));
+
+        patches
+    }
+
+    /// Panics if a function faked by this injector does not lead to its fake.
+    ///
+    /// The code at the address of each faked function is read, without calling it, to check
+    /// that it still branches to the fake, or to the dispatcher choosing the fake of each
+    /// thread. Global fakes that are paused fail the check.
+    ///
+    /// A passing check only means the patch is in place. Calls the compiler inlined into their
+    /// callers never reach it: use `assert_effective()` to check that the code under test
+    /// reaches the fakes.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use injectorpp::interface::injector::*;
+    ///
+    /// #[inline(never)]
+    /// fn retries() -> u32 {
+    ///     std::hint::black_box(3)
+    /// }
+    ///
+    /// let mut injector = InjectorPP::new();
+    /// injector
+    ///     .when_called(injectorpp::func!(fn (retries)() -> u32))
+    ///     .will_execute(injectorpp::fake!(func_type: fn() -> u32, returns: 0));
+    /// injector.assert_patched();
+    ///
+    /// assert_eq!(retries(), 0);
+    /// ```
+    pub fn assert_patched(&self) {
+        let not_in_effect = |func_addr: usize| -> ! {
+            panic!(
+                "The patch of {} no longer leads to its fake: it was paused, or its code was \
+                 written back or overwritten{}",
+                func_display_name(func_addr),
+                self.label_suffix()
+            );
+        };
+
+        for redirect in &self.redirects {
+            if !redirect.is_effective() {
+                panic!(
+                    "The GOT entries or call sites redirected to the fake of {} no longer \
+                     point at it{}",
+                    func_display_name(redirect.func_addr()),
+                    self.label_suffix()
+                );
+            }
+        }
+
+        for guard in &self.guards {
+            if !guard.is_in_effect() {
+                not_in_effect(guard.info().0);
+            }
+        }
+
+        #[cfg(any(
+            target_arch = "x86_64",
+            target_arch = "aarch64",
+            target_arch = "arm64ec",
+            target_arch = "arm"
+        ))]
+        for registration in &self.registrations {
+            if !registration.is_dispatched() {
+                not_in_effect(registration.info().0);
+            }
+        }
+    }
+
+    /// Runs `exercise`, which calls the faked functions through the code under test, and
+    /// panics if a fake of this injector was not reached meanwhile. Returns what `exercise`
+    /// returns.
+    ///
+    /// A fake that is not reached although its patch is in place usually means the compiler
+    /// inlined the function into its callers, where no patch can reach it: mark it
+    /// `#[inline(never)]`, or build the tests with a lower `opt-level`. The patches are checked
+    /// first, as with `assert_patched()`.
+    ///
+    /// Thread-local fakes must be reached on the current thread. Global fakes may be reached
+    /// on any thread, and are only checked on x86_64: on other architectures, only their
+    /// patch is checked. Fakes installed with `PatchStrategy::Got` or
+    /// `PatchStrategy::CallSites` are not checked, as they redirect calls rather than the
+    /// function.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use injectorpp::interface::injector::*;
+    ///
+    /// #[inline(never)]
+    /// fn retries() -> u32 {
+    ///     std::hint::black_box(3)
+    /// }
+    ///
+    /// fn connect() -> Result<(), u32> {
+    ///     match retries() {
+    ///         0 => Err(0),
+    ///         _ => Ok(()),
+    ///     }
+    /// }
+    ///
+    /// let mut injector = InjectorPP::new();
+    /// injector
+    ///     .when_called(injectorpp::func!(fn (retries)() -> u32))
+    ///     .will_execute(injectorpp::fake!(func_type: fn() -> u32, returns: 0));
+    ///
+    /// let result = injector.assert_effective(connect);
+    /// assert_eq!(result, Err(0));
+    /// ```
+    pub fn assert_effective<R>(&self, exercise: impl FnOnce() -> R) -> R {
+        self.assert_patched();
+
+        #[cfg(any(
+            target_arch = "x86_64",
+            target_arch = "aarch64",
+            target_arch = "arm64ec",
+            target_arch = "arm"
+        ))]
+        let logs: Vec<_> = self
+            .registrations
+            .iter()
+            .map(|registration| {
+                let (_, _, replacement) = registration.key();
+                (
+                    registration.info().0,
+                    crate::injector_core::thread_local_registry::log_callers(replacement),
+                )
+            })
+            .collect();
+        let entries: Vec<Option<u64>> = self.guards.iter().map(PatchGuard::stub_entries).collect();
+
+        let result = exercise();
+
+        let mut not_reached: Vec<usize> = Vec::new();
+        #[cfg(any(
+            target_arch = "x86_64",
+            target_arch = "aarch64",
+            target_arch = "arm64ec",
+            target_arch = "arm"
+        ))]
+        not_reached.extend(
+            logs.iter()
+                .filter(|(_, log)| !log.has_calls())
+                .map(|(func_addr, _)| *func_addr),
+        );
+        not_reached.extend(
+            self.guards
+                .iter()
+                .zip(entries)
+                .filter(|(guard, before)| before.is_some() && guard.stub_entries() == *before)
+                .map(|(guard, _)| guard.info().0),
+        );
+
+        if let Some(func_addr) = not_reached.first() {
+            panic!(
+                "The fake of {} was not reached while running the code under test. If the code \
+                 calls the function, the compiler inlined it into its callers, where no patch \
+                 can reach it: mark it #[inline(never)], or build the tests with a lower \
+                 opt-level{}",
+                func_display_name(*func_addr),
+                self.label_suffix()
+            );
+        }
+
+        result
+    }
+
+    /// Returns the patches currently applied by every injector in the process.
+    ///
+    /// A thread-locally faked function is reported once, no matter how many threads
+    /// currently fake it. Test harnesses can use this to assert that no patch leaks
+    /// out of a test.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use injectorpp::interface::injector::*;
+    ///
+    /// fn my_func() -> i32 {
+    ///     1
+    /// }
+    ///
+    /// {
+    ///     let mut injector = InjectorPP::new();
+    ///     injector
+    ///         .when_called(injectorpp::func!(fn (my_func)() -> i32))
+    ///         .will_execute(injectorpp::fake!(func_type: fn() -> i32, returns: 2));
+    /// }
+    ///
+    /// let leaked = InjectorPP::all_active_patches()
+    ///     .into_iter()
+    ///     .any(|patch| patch.func_addr == my_func as *const () as usize);
+    /// assert!(!leaked);
+    /// ```
+    pub fn all_active_patches() -> Vec<PatchInfo> {
+        #[allow(unused_mut)]
+        let mut patches: Vec<PatchInfo> = live_global_patches()
+            .into_iter()
+            .map(|(func_addr, patch_size, jit_addr)| {
+                PatchInfo::new(func_addr, patch_size, jit_addr, false)
+            })
+            .collect();
+
+        patches.extend(
+            crate::injector_core::redirect::live_redirects()
+                .into_iter()
+                .map(|(func_addr, _)| PatchInfo::new(func_addr, 0, 0, false)),
+        );
+
+        #[cfg(any(
+            target_arch = "x86_64",
+            target_arch = "aarch64",
+            target_arch = "arm64ec",
+            target_arch = "arm"
+        ))]
+        patches.extend(
+            crate::injector_core::thread_local_registry::active_registrations()
+                .into_iter()
+                .map(|(func_addr, patch_size, jit_addr)| {
+                    PatchInfo::new(func_addr, patch_size, jit_addr, true)
+                }),
+        );
+
+        patches
+    }
+
+    /// Returns a human-readable dump of every patch applied by this injector.
+    ///
+    /// For each patch the dump contains the original bytes, the bytes written over the
+    /// function prologue and the generated JIT code (dispatcher and trampoline in
+    /// thread-local mode), as hex with a minimal disassembly. Attach this output when
+    /// reporting a patch that misbehaves on a new target.
+    ///
+    /// Requires the `jit-dump` feature.
+    #[cfg(feature = "jit-dump")]
+    pub fn dump_patches(&self) -> String {
+        let mut out = String::new();
+        for guard in &self.guards {
+            out.push_str(&guard.dump());
+        }
+
+        #[cfg(any(
+            target_arch = "x86_64",
+            target_arch = "aarch64",
+            target_arch = "arm64ec",
+            target_arch = "arm"
+        ))]
+        for registration in &self.registrations {
+            out.push_str(&registration.dump());
+        }
+
+        out
+    }
+
+    /// Enables or disables diagnostic logging of the patch lifecycle to stderr.
+    ///
+    /// When enabled, every installed patch logs the patched address, the original bytes,
+    /// the written branch bytes and the JIT stub location, and every restored patch logs
+    /// its address. This overrides the `INJECTORPP_LOG` environment variable, which
+    /// enables the same logging without code changes (e.g. `INJECTORPP_LOG=1 cargo test`).
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use injectorpp::interface::injector::InjectorPP;
+    ///
+    /// InjectorPP::set_diagnostic_logging(true);
+    /// ```
+    pub fn set_diagnostic_logging(enabled: bool) {
+        crate::injector_core::diagnostics::set_log_enabled(enabled);
+    }
+
+    /// Registers the external hooking engine of the `PatchStrategy::External` fakes installed
+    /// from now on, by every injector. Installed fakes keep the engine that installed them.
+    /// Requires the `external-engine` feature.
+    ///
+    /// # Example
+    ///
+    /// ```rust,ignore
+    /// use injectorpp::interface::injector::*;
+    ///
+    /// // An adapter for frida-gum, whose `Interceptor` keeps the original functions.
+    /// struct Frida(std::sync::Mutex<frida_gum::interceptor::Interceptor>);
+    ///
+    /// // The interceptor is only used under the lock.
+    /// unsafe impl Send for Frida {}
+    /// unsafe impl Sync for Frida {}
+    ///
+    /// unsafe impl HookEngine for Frida {
+    ///     fn name(&self) -> &str {
+    ///         "frida-gum"
+    ///     }
+    ///
+    ///     unsafe fn replace(&self, func: *const (), replacement: *const ()) -> Result<u64, String> {
+    ///         let mut interceptor = self.0.lock().unwrap();
+    ///         interceptor
+    ///             .replace_fast(
+    ///                 frida_gum::NativePointer(func as *mut _),
+    ///                 frida_gum::NativePointer(replacement as *mut _),
+    ///             )
+    ///             .map_err(|e| e.to_string())?;
+    ///         Ok(func as u64)
+    ///     }
+    ///
+    ///     unsafe fn revert(&self, hook: u64) {
+    ///         let mut interceptor = self.0.lock().unwrap();
+    ///         interceptor.revert(frida_gum::NativePointer(hook as *mut _));
+    ///     }
+    /// }
+    ///
+    /// let gum = Box::leak(Box::new(frida_gum::Gum::obtain()));
+    /// InjectorPP::set_hook_engine(Frida(std::sync::Mutex::new(
+    ///     frida_gum::interceptor::Interceptor::obtain(gum),
+    /// )));
+    ///
+    /// let mut injector = InjectorPP::new_global();
+    /// injector.set_default_strategy(PatchStrategy::External);
+    /// ```
+    #[cfg(feature = "external-engine")]
+    pub fn set_hook_engine(engine: impl HookEngine) {
+        crate::injector_core::hook_engine::set_engine(std::sync::Arc::new(engine));
+    }
+
+    /// Begins faking a function.
+    ///
+    /// Accepts a FuncPtr to the function you want to fake. Use the `func!` macro to obtain this pointer.
+    ///
+    /// # Parameters
+    ///
+    /// - `func`: A FuncPtr holds the pointer to the target function obtained via `func!` macro.
+    ///
+    /// # Returns
+    ///
+    /// A builder (`WhenCalledBuilder`) to further specify the fake behavior.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use injectorpp::interface::injector::*;
+    /// use std::path::Path;
+    ///
+    /// fn fake_exists(_path: &Path) -> bool {
+    ///     true
+    /// }
+    ///
+    /// let mut injector = InjectorPP::new();
+    /// injector
+    ///     .when_called(injectorpp::func!(fn (Path::exists)(&Path) -> bool))
+    ///     .will_execute_raw(injectorpp::func!(fn (fake_exists)(&Path) -> bool));
+    ///
+    /// assert!(Path::new("/non/existent/path").exists());
+    /// ```
+    pub fn when_called(&mut self, func: FuncPtr) -> WhenCalledBuilder<'_> {
+        self.check_fakeable(&func);
+        let when = self.when(func.func_ptr_internal);
+        let strategy = self.default_strategy;
+        WhenCalledBuilder {
+            lib: self,
+            when,
+            expected_signature: func.signature,
+            expected_type_id: func.type_id,
+            expected_return: func.ret_layout,
+        }
+        .strategy(strategy)
+    }
+
+    /// Begins faking a function like `when_called()`, but returns a `LockTimeout` instead of
+    /// panicking when the function cannot be locked within the timeout of an injector created
+    /// with `InjectorPP::try_new()`.
+    ///
+    /// Injectors without a timeout wait for the lock, so this only returns `Err` for injectors
+    /// created with `try_new()`. Faking a function that an injector on this thread fakes in
+    /// the other mode still panics, as waiting could never succeed.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use injectorpp::interface::injector::*;
+    /// use std::time::Duration;
+    ///
+    /// #[inline(never)]
+    /// fn is_ready() -> bool {
+    ///     std::hint::black_box(false)
+    /// }
+    ///
+    /// let mut injector = InjectorPP::try_new(Duration::from_secs(30)).unwrap();
+    /// match injector.try_when_called(injectorpp::func!(fn (is_ready)() -> bool)) {
+    ///     Ok(when) => when.will_return_boolean(true),
+    ///     Err(timeout) => panic!("{}", timeout),
+    /// };
+    ///
+    /// assert!(is_ready());
+    /// ```
+    pub fn try_when_called(&mut self, func: FuncPtr) -> Result<WhenCalledBuilder<'_>, LockTimeout> {
+        self.check_fakeable(&func);
+        let when = self.try_when(func.func_ptr_internal)?;
+        let strategy = self.default_strategy;
+        Ok(WhenCalledBuilder {
+            lib: self,
+            when,
+            expected_signature: func.signature,
+            expected_type_id: func.type_id,
+            expected_return: func.ret_layout,
+        }
+        .strategy(strategy))
+    }
+
+    /// Begins an expectation on a function, an alternative to `when_called` and `fake!`
+    /// configured with plain closures.
+    ///
+    /// Chain `with()` to check the arguments, `returning()` to produce the return value and
+    /// `times()` to verify the call count. The fake is installed at the end of the statement.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use injectorpp::interface::injector::*;
+    ///
+    /// #[inline(never)]
+    /// fn port_of(service: &str, fallback: u16) -> u16 {
+    ///     std::hint::black_box(service.len() as u16 + fallback)
+    /// }
+    ///
+    /// let mut injector = InjectorPP::new();
+    /// injector
+    ///     .expect(injectorpp::func!(fn (port_of)(&str, u16) -> u16))
+    ///     .with(|service: &str, _: u16| service == "billing")
+    ///     .returning(|_: &str, fallback: u16| fallback + 1)
+    ///     .times(2);
+    ///
+    /// assert_eq!(port_of("billing", 80), 81);
+    /// assert_eq!(port_of("billing", 442), 443);
+    /// ```
+    pub fn expect(&mut self, func: FuncPtr) -> Expect<'_> {
+        self.check_fakeable(&func);
+        let target = func_display_name(func.func_ptr_internal.as_ptr() as usize);
+        Expect::new(self, func, target)
+    }
+
+    /// Begins faking a function.
+    ///
+    /// Accepts a FuncPtr to the function you want to fake. Use the `func!` macro to obtain this pointer.
+    ///
+    /// # Parameters
+    ///
+    /// - `func`: A FuncPtr holds the pointer to the target function obtained via `func!` macro.
+    ///
+    /// # Returns
+    ///
+    /// A builder (`WhenCalledBuilder`) to further specify the fake behavior.
+    ///
+    /// # Safety
+    ///
+    /// This method is unsafe because it skips type check.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use injectorpp::interface::injector::*;
+    /// use std::path::Path;
+    ///
+    /// fn fake_exists(_path: &Path) -> bool {
+    ///     true
+    /// }
+    ///
+    /// let mut injector = InjectorPP::new();
+    ///
+    /// unsafe {
+    ///     injector
+    ///         .when_called_unchecked(injectorpp::func_unchecked!(Path::exists))
+    ///         .will_execute_raw_unchecked(injectorpp::func_unchecked!(fake_exists));
+    /// }
+    ///
+    /// assert!(Path::new("/non/existent/path").exists());
+    /// ```
+    pub unsafe fn when_called_unchecked(&mut self, func: FuncPtr) -> WhenCalledBuilder<'_> {
+        self.check_fakeable(&func);
+        let when = self.when(func.func_ptr_internal);
+        let strategy = self.default_strategy;
+        WhenCalledBuilder {
+            lib: self,
+            when,
+            expected_signature: "",
+            expected_type_id: None,
+            expected_return: func.ret_layout,
+        }
+        .strategy(strategy)
+    }
+
+    /// Locks every function of `funcs` for the lifetime of this injector, without faking them.
+    ///
+    /// `when_called()` locks each function as it is faked, so two tests faking the same
+    /// functions in different orders, one of them globally, can each hold the function the
+    /// other waits for. The one that would wait last then panics instead of waiting forever.
+    /// Locking them together first takes the locks in ascending address order, whatever the
+    /// order of `funcs`, so such tests wait for each other instead.
+    /// `when_called_symbols()` and `when_called_instances()` lock their functions this way.
+    ///
+    /// # Panics
+    ///
+    /// Panics like `when_called()` when a lock cannot be taken.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use injectorpp::interface::injector::*;
+    ///
+    /// #[inline(never)]
+    /// fn is_connected() -> bool {
+    ///     std::hint::black_box(false)
+    /// }
+    ///
+    /// #[inline(never)]
+    /// fn can_resolve() -> bool {
+    ///     std::hint::black_box(false)
+    /// }
+    ///
+    /// let mut injector = InjectorPP::new_global();
+    /// injector.lock_all(&[
+    ///     injectorpp::func!(fn (is_connected)() -> bool),
+    ///     injectorpp::func!(fn (can_resolve)() -> bool),
+    /// ]);
+    /// injector
+    ///     .when_called(injectorpp::func!(fn (can_resolve)() -> bool))
+    ///     .will_return_boolean(true);
+    /// injector
+    ///     .when_called(injectorpp::func!(fn (is_connected)() -> bool))
+    ///     .will_return_boolean(true);
+    ///
+    /// assert!(is_connected() && can_resolve());
+    /// ```
+    pub fn lock_all(&mut self, funcs: &[FuncPtr]) {
+        use crate::injector_core::thunk::resolve_thunks;
+
+        let func_addrs: Vec<usize> = funcs
+            .iter()
+            .map(|func| resolve_thunks(func.func_ptr_internal.as_ptr() as usize))
+            .collect();
+        self.lock_functions(&func_addrs);
+    }
+
+    /// Begins faking the C function exported as `symbol` by any loaded module, declared with
+    /// the signature `F`, without an `extern` block declaring it.
+    ///
+    /// The signature must match the C declaration of the function, as for a function of an
+    /// `extern` block. Fakes are checked against it as with `func!`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no loaded module exports `symbol`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use injectorpp::interface::injector::*;
+    /// use std::os::raw::c_char;
+    ///
+    /// let mut injector = InjectorPP::new();
+    /// injector
+    ///     .when_called_c::<unsafe extern "C" fn(*const c_char) -> *mut c_char>("getenv")
+    ///     .will_execute(injectorpp::fake!(
+    ///         func_type: unsafe extern "C" fn(_name: *const c_char) -> *mut c_char,
+    ///         returns: c"faked".as_ptr() as *mut c_char
+    ///     ));
+    ///
+    /// #[cfg(unix)]
+    /// assert_eq!(std::env::var("INJECTORPP_UNSET").unwrap(), "faked");
+    /// ```
+    pub fn when_called_c<F: CFnPointer>(&mut self, symbol: &str) -> WhenCalledBuilder<'_> {
+        self.when_called_c_in::<F>(None, symbol)
+    }
+
+    /// Begins faking the method at the index `slot` of the vtable of the COM interface
+    /// `interface`, declared with the signature `F` taking the interface pointer first, by
+    /// rewriting the slot rather than the code of the method.
+    ///
+    /// Slots count from the start of the vtable, `QueryInterface`, `AddRef` and `Release` of
+    /// `IUnknown` being 0, 1 and 2. The fake reaches every caller going through the vtable,
+    /// for every object sharing it, usually every instance of the class implementing the
+    /// interface. Other interfaces of the object, as returned by `QueryInterface`, have their
+    /// own vtable and keep calling the method, as do callers calling it directly.
+    ///
+    /// Nothing is called on the object, which may be released before the fake is restored:
+    /// the slot is written back in the vtable, and the module holding it is kept loaded.
+    ///
+    /// # Safety
+    ///
+    /// `interface` must point to a live COM interface whose vtable has at least `slot + 1`
+    /// methods, and the method at `slot` must be declared as `F`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the injector was not created with `InjectorPP::new_global()`, as the vtable
+    /// is shared by every thread.
+    ///
+    /// # Example
+    ///
+    /// ```rust,ignore
+    /// use injectorpp::interface::injector::*;
+    /// use std::ffi::c_void;
+    ///
+    /// // IStream::Read, after the 3 methods of IUnknown and ISequentialStream::Read.
+    /// let mut injector = InjectorPP::new_global();
+    /// unsafe {
+    ///     injector
+    ///         .when_called_com_method::<
+    ///             unsafe extern "system" fn(*mut c_void, *mut c_void, u32, *mut u32) -> i32,
+    ///         >(stream, 3)
+    ///         .will_execute(injectorpp::fake!(
+    ///             func_type: unsafe extern "system" fn(
+    ///                 _this: *mut c_void,
+    ///                 _buffer: *mut c_void,
+    ///                 _len: u32,
+    ///                 _read: *mut u32
+    ///             ) -> i32,
+    ///             returns: 0x8007_001Eu32 as i32 // E_FAIL reading
+    ///         ));
+    /// }
+    /// ```
+    #[cfg(target_os = "windows")]
+    pub unsafe fn when_called_com_method<F: CFnPointer>(
+        &mut self,
+        interface: *mut core::ffi::c_void,
+        slot: usize,
+    ) -> WhenCalledBuilder<'_> {
+        self.require_global("when_called_com_method()");
+
+        let vtable = *(interface as *const *const usize);
+        let slot_addr = vtable.add(slot) as usize;
+        let method = crate::injector_core::winapi::read_vtable_slot(slot_addr);
+        // The slot may hold the fake of a redirection of another injector.
+        let method = crate::injector_core::redirect::redirected_function(method).unwrap_or(method);
+
+        let mut builder = self.when_called(FuncPtr::with_c_signature::<F>(method));
+        builder.when.redirect_calls(RedirectMode::VtableSlot(slot_addr));
+        builder
+    }
+
+    /// Begins faking the Objective-C method `method`, named as in Objective-C, such as
+    /// `-[NSFileManager fileExistsAtPath:]` or `+[NSDate date]`, and declared with the
+    /// signature `F` of its implementation, taking the receiver and the selector first, by
+    /// replacing its implementation rather than patching its code.
+    ///
+    /// The fake reaches every message sent to the method, such as the messages the methods of
+    /// `objc2` send. A method the class inherits is added to the class, as
+    /// `class_replaceMethod()` does, so the superclass and its other subclasses keep calling
+    /// the original; the added method stays, calling the implementation of the superclass,
+    /// once the fake is restored.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the injector was not created with `InjectorPP::new_global()`, as the methods
+    /// of a class are shared by every thread, or if no loaded class has the method.
+    ///
+    /// # Example
+    ///
+    /// ```rust,ignore
+    /// use injectorpp::interface::injector::*;
+    /// use std::ffi::c_void;
+    ///
+    /// let mut injector = InjectorPP::new_global();
+    /// injector
+    ///     .when_called_objc::<unsafe extern "C" fn(*mut c_void, *const c_void, *mut c_void) -> bool>(
+    ///         "-[NSFileManager fileExistsAtPath:]",
+    ///     )
+    ///     .will_execute(injectorpp::fake!(
+    ///         func_type: unsafe extern "C" fn(_this: *mut c_void, _cmd: *const c_void, _path: *mut c_void) -> bool,
+    ///         returns: true
+    ///     ));
+    /// ```
+    #[cfg(target_os = "macos")]
+    pub fn when_called_objc<F: CFnPointer>(&mut self, method: &str) -> WhenCalledBuilder<'_> {
+        use crate::injector_core::objc;
+
+        self.require_global("when_called_objc()");
+        let Some(objc_method) = objc::own_method(method) else {
+            panic!(
+                "Cannot fake `{}`: no loaded class has the method, named as in \
+                 `-[NSFileManager fileExistsAtPath:]`{}",
+                method,
+                self.label_suffix()
+            );
+        };
+
+        let imp = objc::implementation(objc_method);
+        // The method may be implemented by the fake of a redirection of another injector.
+        let imp = crate::injector_core::redirect::redirected_function(imp).unwrap_or(imp);
+        let mut builder = self.when_called(unsafe { FuncPtr::with_c_signature::<F>(imp) });
+        builder.when.redirect_calls(RedirectMode::ObjcMethod(objc_method));
+        builder
+    }
+
+    /// Begins faking the C function exported as `symbol` by `module`, or by any loaded module
+    /// when `None`, declared with the signature `F`. The module is loaded if it is not yet.
+    ///
+    /// See `FuncPtr::from_c_symbol()` for how modules are named.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the module does not export `symbol`, or cannot be loaded.
+    pub fn when_called_c_in<F: CFnPointer>(
+        &mut self,
+        module: Option<&str>,
+        symbol: &str,
+    ) -> WhenCalledBuilder<'_> {
+        let Some(func) = FuncPtr::from_c_symbol::<F>(module, symbol) else {
+            match module {
+                Some(module) => panic!(
+                    "Cannot fake `{}`: {} does not export it, or cannot be loaded{}",
+                    symbol,
+                    module,
+                    self.label_suffix()
+                ),
+                None => panic!(
+                    "Cannot fake `{}`: no loaded module exports it{}",
+                    symbol,
+                    self.label_suffix()
+                ),
+            }
+        };
+        self.when_called(func)
+    }
+
+    /// Begins faking the C++ function named `name`, declared with the signature `F`, such as a
+    /// function of a C++ library bridged by the `cxx` crate.
+    ///
+    /// `name` is either the mangled symbol of the function, looked up with
+    /// `FuncPtr::from_cxx_symbol()`, or a pattern matching its demangled name, such as
+    /// `net::Socket::connect` or `net::Socket::connect(char const*, int)` to tell overloads
+    /// apart, looked up with `FuncPtr::from_cxx_name()` on Linux. Use `demangle_cxx()` to
+    /// find the name of a symbol. See `FuncPtr::from_cxx_symbol()` for how `F` is declared.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no function, or more than one, is found.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use injectorpp::interface::injector::*;
+    /// use std::os::raw::{c_char, c_int};
+    ///
+    /// // A function of a C++ library: `int net::connect(char const* host, int port)`.
+    /// #[export_name = "_ZN3net7connectEPKci"]
+    /// #[inline(never)]
+    /// extern "C" fn connect(_host: *const c_char, port: c_int) -> c_int {
+    ///     std::hint::black_box(port)
+    /// }
+    ///
+    /// # #[cfg(target_os = "linux")]
+    /// # {
+    /// let mut injector = InjectorPP::new();
+    /// injector
+    ///     .when_called_cxx::<unsafe extern "C" fn(*const c_char, c_int) -> c_int>("net::connect")
+    ///     .will_execute(injectorpp::fake!(
+    ///         func_type: unsafe extern "C" fn(_host: *const c_char, _port: c_int) -> c_int,
+    ///         returns: -1
+    ///     ));
+    ///
+    /// assert_eq!(connect(c"localhost".as_ptr(), 80), -1);
+    /// # }
+    /// ```
+    pub fn when_called_cxx<F: CFnPointer>(&mut self, name: &str) -> WhenCalledBuilder<'_> {
+        if crate::injector_core::cxx_demangle::is_mangled(name) {
+            let Some(func) = FuncPtr::from_cxx_symbol::<F>(None, name) else {
+                panic!(
+                    "Cannot fake `{}`: no loaded module exports it, or has it in its symbol \
+                     table{}",
+                    name,
+                    self.label_suffix()
+                );
+            };
+            return self.when_called(func);
+        }
+
+        let mut found = FuncPtr::from_cxx_name::<F>(name);
+        if found.len() > 1 {
+            let names: Vec<String> = found
+                .iter()
+                .map(|(name, func)| format!("{} at {:p}", name, func.func_ptr_internal.as_ptr()))
+                .collect();
+            panic!(
+                "Cannot fake `{}`: {} functions match it: {}. Overloads are told apart by \
+                 their parameters, as in `{}`{}",
+                name,
+                found.len(),
+                names.join(", "),
+                found[0].0,
+                self.label_suffix()
+            );
+        }
+        let Some((_, func)) = found.pop() else {
+            self.panic_no_symbol_match(name);
+        };
+        self.when_called(func)
+    }
+
+    /// Begins faking the Rust function whose path matches `pattern`, declared with the
+    /// signature `F`, so functions that are private or cannot be named from the test can be
+    /// faked.
+    ///
+    /// See `FuncPtr::from_symbol_pattern()` for how functions are looked up and patterns are
+    /// matched. Use `when_called_symbols()` to fake every function matching a pattern.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no function, or more than one, matches `pattern`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use injectorpp::interface::injector::*;
+    ///
+    /// mod net {
+    ///     #[inline(never)]
+    ///     fn resolve(host: &str) -> u32 {
+    ///         host.len() as u32
+    ///     }
+    ///
+    ///     pub fn connect(host: &str) -> u32 {
+    ///         resolve(host)
+    ///     }
+    /// }
+    ///
+    /// # #[cfg(target_os = "linux")]
+    /// # {
+    /// let mut injector = InjectorPP::new();
+    /// injector
+    ///     .when_called_symbol::<fn(&'static str) -> u32>("**::net::resolve")
+    ///     .will_execute(injectorpp::fake!(
+    ///         func_type: fn(_host: &str) -> u32,
+    ///         returns: 7
+    ///     ));
+    ///
+    /// assert_eq!(net::connect("localhost"), 7);
+    /// # }
+    /// ```
+    pub fn when_called_symbol<F: RustFnPointer>(&mut self, pattern: &str) -> WhenCalledBuilder<'_> {
+        let mut found = FuncPtr::from_symbol_pattern::<F>(pattern);
+        if found.len() > 1 {
+            let paths: Vec<String> = found
+                .iter()
+                .map(|(path, func)| format!("{} at {:p}", path, func.func_ptr_internal.as_ptr()))
+                .collect();
+            panic!(
+                "Cannot fake `{}`: {} functions match it: {}{}",
+                pattern,
+                found.len(),
+                paths.join(", "),
+                self.label_suffix()
+            );
+        }
+        let Some((_, func)) = found.pop() else {
+            self.panic_no_symbol_match(pattern);
+        };
+        self.when_called(func)
+    }
+
+    /// Fakes every Rust function whose path matches `pattern`, declared with the signature
+    /// `F`, by calling `each` with the builder of each of them and its path. Returns how many
+    /// functions are faked.
+    ///
+    /// See `FuncPtr::from_symbol_pattern()` for how functions are looked up and patterns are
+    /// matched.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no function matches `pattern`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use injectorpp::interface::injector::*;
+    ///
+    /// mod v4 {
+    ///     #[inline(never)]
+    ///     pub fn connect(port: u16) -> bool {
+    ///         port != 0
+    ///     }
+    /// }
+    ///
+    /// mod v6 {
+    ///     #[inline(never)]
+    ///     pub fn connect(port: u16) -> bool {
+    ///         port != 0
+    ///     }
+    /// }
+    ///
+    /// # #[cfg(target_os = "linux")]
+    /// # {
+    /// let mut injector = InjectorPP::new();
+    /// let faked = injector.when_called_symbols::<fn(u16) -> bool>("**::v*::connect", |when, _| {
+    ///     when.will_execute(injectorpp::fake!(
+    ///         func_type: fn(_port: u16) -> bool,
+    ///         returns: false
+    ///     ));
+    /// });
+    ///
+    /// assert_eq!(faked, 2);
+    /// assert!(!v4::connect(80));
+    /// assert!(!v6::connect(80));
+    /// # }
+    /// ```
+    pub fn when_called_symbols<F: RustFnPointer>(
+        &mut self,
+        pattern: &str,
+        mut each: impl FnMut(WhenCalledBuilder<'_>, &str),
+    ) -> usize {
+        use crate::injector_core::thunk::resolve_thunks;
+
+        let found = FuncPtr::from_symbol_pattern::<F>(pattern);
+        if found.is_empty() {
+            self.panic_no_symbol_match(pattern);
+        }
+        let count = found.len();
+        let func_addrs: Vec<usize> = found
+            .iter()
+            .map(|(_, func)| resolve_thunks(func.func_ptr_internal.as_ptr() as usize))
+            .collect();
+        self.lock_functions(&func_addrs);
+        for (path, func) in found {
+            each(self.when_called(func), &path);
+        }
+        count
+    }
+
+    /// Fakes every instance of the generic function `func` is an instance of, by calling
+    /// `each` with the builder of each of them. Returns how many instances are faked.
+    ///
+    /// `func!` obtains a single instance of a generic function, so calls with other type
+    /// arguments still run the original. The instances are found by the path of `func` in the
+    /// symbol tables of the loaded modules, and are all faked with the signature of `func`, so
+    /// fakes must be type-erased: only functions whose instances take their arguments and
+    /// return their value alike, such as when the type parameters only appear behind
+    /// references, can be faked this way. Instances are found on Linux only, and must not be
+    /// inlined.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `func` is not found in the symbol tables.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use injectorpp::interface::injector::*;
+    /// use std::fmt::Debug;
+    ///
+    /// #[inline(never)]
+    /// fn describe<T: Debug>(value: &T) -> String {
+    ///     format!("{:?}", value)
+    /// }
+    ///
+    /// # #[cfg(target_os = "linux")]
+    /// # {
+    /// assert_eq!(describe(&"text"), "\"text\"");
+    /// let mut injector = InjectorPP::new();
+    /// let faked = injector.when_called_instances(
+    ///     injectorpp::func!(fn (describe::<u8>)(&u8) -> String),
+    ///     |when| {
+    ///         when.will_execute(injectorpp::fake!(
+    ///             func_type: fn(_value: &u8) -> String,
+    ///             returns: "faked".to_string()
+    ///         ));
+    ///     },
+    /// );
+    ///
+    /// assert_eq!(faked, 2);
+    /// assert_eq!(describe(&1u8), "faked");
+    /// assert_eq!(describe(&"text"), "faked");
+    /// # }
+    /// ```
+    pub fn when_called_instances(
+        &mut self,
+        func: FuncPtr,
+        mut each: impl FnMut(WhenCalledBuilder<'_>),
+    ) -> usize {
+        let addr = func.func_ptr_internal.as_ptr() as usize;
+        let Some((_, addrs)) = crate::injector_core::symbols::functions_sharing_path(addr) else {
+            panic!(
+                "Cannot fake the instances of {}: it is not found in the symbol tables of the loaded modules{}",
+                func.signature,
+                self.label_suffix()
+            );
+        };
+
+        let instances: Vec<FuncPtr> = addrs
+            .iter()
+            .map(|&instance| func.at_address(instance))
+            .collect();
+        self.lock_all(&instances);
+        for instance in instances {
+            each(self.when_called(instance));
+        }
+        addrs.len()
+    }
+
+    fn panic_no_symbol_match(&self, pattern: &str) -> ! {
+        if cfg!(target_os = "linux") {
+            panic!(
+                "Cannot fake `{}`: no function in the symbol tables of the loaded modules matches it{}",
+                pattern,
+                self.label_suffix()
+            );
+        }
+        panic!(
+            "Cannot fake `{}`: functions are only looked up by path on Linux{}",
+            pattern,
+            self.label_suffix()
+        );
+    }
+
+    /// Begins faking an asynchronous function.
+    ///
+    /// Accepts the future type to fake. Use the `async_func!` macro to obtain it, either from a
+    /// future or from the async function, async closure or `impl Future` function returning it.
+    ///
+    /// # Parameters
+    ///
+    /// - `target`: The future type to fake, as returned by `async_func!`.
+    ///
+    /// # Returns
+    ///
+    /// A builder (`WhenCalledBuilderAsync`) to further specify the async fake behavior.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use injectorpp::interface::injector::*;
+    ///
+    /// async fn async_add_one(x: u32) -> u32 {
+    ///     x + 1
+    /// }
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut injector = InjectorPP::new();
+    ///     injector
+    ///         .when_called_async(injectorpp::async_func!(async_add_one(u32::default()), u32))
+    ///         .will_return_async(injectorpp::async_return!(123, u32));
+    ///
+    ///     let result = async_add_one(5).await;
+    ///     assert_eq!(result, 123); // The patched value
+    /// }
+    /// ```
+    pub fn when_called_async(
+        &mut self,
+        target: impl Into<AsyncTarget>,
+    ) -> WhenCalledBuilderAsync<'_> {
+        let target = target.into();
+        let when = self.when(target.poll.func_ptr_internal);
+
+        WhenCalledBuilderAsync {
+            lib: self,
+            when,
+            expected_signature: target.expected_signature,
+            expected_type_id: None,
+        }
+    }
+
+    /// Begins faking an asynchronous function.
+    ///
+    /// Accepts a pinned mutable reference to the async function future. Use the `async_func!` macro to obtain this reference.
+    ///
+    /// # Parameters
+    ///
+    /// - `_`: A pinned mutable reference to the async function future.
+    ///
+    /// # Returns
+    ///
+    /// A builder (`WhenCalledBuilderAsync`) to further specify the async fake behavior.
+    ///
+    /// # Safety
+    ///
+    /// This method is unsafe because it skips type check.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use injectorpp::interface::injector::*;
+    ///
+    /// async fn async_add_one(x: u32) -> u32 {
+    ///     x + 1
+    /// }
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut injector = InjectorPP::new();
+    ///
+    ///     unsafe {
+    ///         injector
+    ///             .when_called_async_unchecked(injectorpp::async_func_unchecked!(async_add_one(u32::default())))
+    ///             .will_return_async_unchecked(injectorpp::async_return_unchecked!(123, u32));
+    ///     }
+    ///
+    ///     let result = async_add_one(5).await;
+    ///     assert_eq!(result, 123); // The patched value
+    /// }
+    /// ```
+    pub unsafe fn when_called_async_unchecked<F, T>(
+        &mut self,
+        _: Pin<&mut F>,
+    ) -> WhenCalledBuilderAsync<'_>
+    where
+        F: Future<Output = T>,
+    {
+        let poll_fn: fn(Pin<&mut F>, &mut Context<'_>) -> Poll<T> = <F as Future>::poll;
+        let when = self.when(
+            unsafe { FuncPtr::new(poll_fn as *const (), std::any::type_name_of_val(&poll_fn)) }
+                .func_ptr_internal,
+        );
+
+        WhenCalledBuilderAsync {
+            lib: self,
+            when,
+            expected_signature: "",
+            expected_type_id: None,
+        }
+    }
+
+    /// Begins faking a stream.
+    ///
+    /// Accepts a pinned mutable reference to the stream and the signature of its items. Use the
+    /// `stream_func!` macro to obtain them. Every stream of the same type yields the faked
+    /// items. Requires the `stream` feature.
+    ///
+    /// # Returns
+    ///
+    /// A builder (`WhenCalledBuilderStream`) to specify the faked items.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use injectorpp::interface::injector::*;
+    /// use std::pin::Pin;
+    /// use std::task::{Context, Poll};
+    ///
+    /// struct Socket;
+    ///
+    /// impl futures_core::Stream for Socket {
+    ///     type Item = Vec<u8>;
+    ///
+    ///     fn poll_next(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Vec<u8>>> {
+    ///         Poll::Pending
+    ///     }
+    /// }
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     use futures::StreamExt;
+    ///
+    ///     let mut injector = InjectorPP::new();
+    ///     injector
+    ///         .when_called_stream(injectorpp::stream_func!(Socket, Vec<u8>))
+    ///         .will_return_stream(injectorpp::stream_return!([b"hello".to_vec()], Vec<u8>));
+    ///
+    ///     let mut socket = Socket;
+    ///     assert_eq!(socket.next().await, Some(b"hello".to_vec()));
+    ///     assert_eq!(socket.next().await, None);
+    /// }
+    /// ```
+    #[cfg(feature = "stream")]
+    pub fn when_called_stream<S, T>(
+        &mut self,
+        fake_pair: (Pin<&mut S>, &'static str),
+    ) -> WhenCalledBuilderStream<'_>
+    where
+        S: futures_core::Stream<Item = T>,
+    {
+        let poll_next: fn(Pin<&mut S>, &mut Context<'_>) -> Poll<Option<T>> =
+            <S as futures_core::Stream>::poll_next;
+        let when = self.when(
+            unsafe {
+                FuncPtr::new(
+                    poll_next as *const (),
+                    std::any::type_name_of_val(&poll_next),
+                )
+            }
+            .func_ptr_internal,
+        );
+
+        WhenCalledBuilderStream {
+            inner: WhenCalledBuilderAsync {
+                lib: self,
+                when,
+                expected_signature: fake_pair.1,
+                expected_type_id: None,
+            },
+        }
+    }
+
+    /// Begins faking an asynchronous function with a fake that receives its arguments.
+    ///
+    /// Accepts a pointer to the async function. Use the `async_fn!` macro to obtain it.
+    ///
+    /// # Returns
+    ///
+    /// A builder (`WhenCalledBuilderAsyncFn`) to specify the async fake.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use injectorpp::interface::injector::*;
+    ///
+    /// async fn lookup(host: &str, port: u16) -> String {
+    ///     format!("{host}:{port}")
+    /// }
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut injector = InjectorPP::new();
+    ///     injector
+    ///         .when_called_async_fn(injectorpp::async_fn!(fn (lookup)(&str, u16) -> String))
+    ///         .will_execute_async(|(host, port)| async move { format!("fake-{host}:{}", port + 1) });
+    ///
+    ///     assert_eq!(lookup("db", 80).await, "fake-db:81");
+    /// }
+    /// ```
+    #[cfg(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec",
+        target_arch = "arm"
+    ))]
+    pub fn when_called_async_fn<P: AsyncFnPointer>(
+        &mut self,
+        target: P,
+    ) -> WhenCalledBuilderAsyncFn<'_, P> {
+        WhenCalledBuilderAsyncFn { lib: self, target }
+    }
+
+    /// Restores a single function faked by this injector, before the injector is dropped.
+    ///
+    /// The real implementation runs for the rest of the test while the other fakes of this
+    /// injector stay in place. Call counts of the removed fake are still verified when the
+    /// injector is dropped, and the function can be faked again with `when_called()`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this injector does not fake `func`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use injectorpp::interface::injector::*;
+    ///
+    /// #[inline(never)]
+    /// fn is_ready() -> bool {
+    ///     std::hint::black_box(false)
+    /// }
+    ///
+    /// let mut injector = InjectorPP::new();
+    /// injector
+    ///     .when_called(injectorpp::func!(fn (is_ready)() -> bool))
+    ///     .will_return_boolean(true);
+    /// assert!(is_ready());
+    ///
+    /// injector.restore(injectorpp::func!(fn (is_ready)() -> bool));
+    /// assert!(!is_ready());
+    /// ```
+    pub fn restore(&mut self, func: FuncPtr) {
+        let func_addr =
+            crate::injector_core::thunk::resolve_thunks(func.func_ptr_internal.as_ptr() as usize);
+        let Some(index) = self
+            .function_locks
+            .iter()
+            .position(|lock| lock.func_addr() == func_addr)
+        else {
+            panic!(
+                "Cannot restore {}: it is not faked by this injector{}",
+                func_display_name(func_addr),
+                self.label_suffix()
+            );
+        };
+
+        self.restore_fakes_of(&func.func_ptr_internal);
+        self.function_locks.remove(index);
+    }
+
+    /// Keeps the fakes of this injector installed for the rest of the process.
+    ///
+    /// Meant for integration test binaries that fake a function for every test, e.g. to
+    /// always mock DNS. The locks on the faked functions are released, so other injectors can
+    /// still be created and fake the same functions on top of the leaked fakes. Thread-local
+    /// fakes stay on the thread that installed them. Call counts of leaked fakes are never
+    /// verified.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use injectorpp::interface::injector::*;
+    ///
+    /// #[inline(never)]
+    /// fn resolve(host: &str) -> String {
+    ///     std::hint::black_box(host).to_string()
+    /// }
+    ///
+    /// let mut injector = InjectorPP::new_global();
+    /// injector
+    ///     .when_called(injectorpp::func!(fn (resolve)(&str) -> String))
+    ///     .will_execute(injectorpp::fake!(
+    ///         func_type: fn(_host: &str) -> String,
+    ///         returns: "127.0.0.1".to_string()
+    ///     ));
+    /// injector.leak();
+    ///
+    /// assert_eq!(resolve("example.com"), "127.0.0.1");
+    /// ```
+    pub fn leak(mut self) {
+        #[cfg(any(
+            target_arch = "x86_64",
+            target_arch = "aarch64",
+            target_arch = "arm64ec",
+            target_arch = "arm"
+        ))]
+        {
+            std::mem::forget(std::mem::take(&mut self.registrations));
+            std::mem::forget(std::mem::take(&mut self.async_fakes));
+            // Scoped futures keep entering the leaked replacements.
+            self.task_fakes = None;
+        }
+        std::mem::forget(std::mem::take(&mut self.guards));
+        std::mem::forget(std::mem::take(&mut self.redirects));
+        std::mem::forget(std::mem::take(&mut self.closures));
+        std::mem::forget(std::mem::take(&mut self.verifiers));
+        self.labeled_fakes.clear();
+
+        // Dropping the rest releases the function locks.
+    }
+
+    /// Installs several fakes at once.
+    ///
+    /// The fakes configured by `f` are prepared first, and their patches are written together
+    /// once `f` returns, so other threads never see only part of them. Functions faked for the
+    /// first time keep running their original code inside `f`. Restoring or pausing a fake
+    /// inside `f` writes the patches prepared so far.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use injectorpp::interface::injector::*;
+    ///
+    /// #[inline(never)]
+    /// fn connect() -> bool {
+    ///     std::hint::black_box(false)
+    /// }
+    ///
+    /// #[inline(never)]
+    /// fn send() -> bool {
+    ///     std::hint::black_box(false)
+    /// }
+    ///
+    /// let mut injector = InjectorPP::new_global();
+    /// injector.batch(|b| {
+    ///     b.when_called(injectorpp::func!(fn (connect)() -> bool))
+    ///         .will_return_boolean(true);
+    ///     b.when_called(injectorpp::func!(fn (send)() -> bool))
+    ///         .will_return_boolean(true);
+    /// });
+    ///
+    /// assert!(connect() && send());
+    /// ```
+    pub fn batch<R>(&mut self, f: impl FnOnce(&mut InjectorPP) -> R) -> R {
+        crate::injector_core::common::defer_patches(|| f(self))
+    }
+
+    /// Installs every fake of `set`, written together as in `batch()`.
+    ///
+    /// Install several sets to compose them. A function faked by more than one set gets the
+    /// fake installed last.
+    ///
+    /// # Panics
+    ///
+    /// Panics, naming the set, if installing one of its fakes panics.
+    pub fn install(&mut self, set: &FakeSet) {
+        self.batch(|injector| set.install(injector));
+    }
+}
+
+impl Default for InjectorPP {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for InjectorPP {
+    fn drop(&mut self) {
+        // Restoring allocates, which must not reach a fake of the allocator being restored.
+        let _internal = __enter_internal();
+
+        // Waits for scoped futures being polled, so none runs a fake that is being freed.
+        #[cfg(any(
+            target_arch = "x86_64",
+            target_arch = "aarch64",
+            target_arch = "arm64ec",
+            target_arch = "arm"
+        ))]
+        if let Some(task_fakes) = &self.task_fakes {
+            task_fakes
+                .write()
+                .unwrap_or_else(|e| e.into_inner())
+                .clear();
+        }
+
+        // Restore the original functions before verifying call counts, so a failed
+        // verification never leaves a fake installed. Fakes are removed in reverse order, so
+        // a function faked twice unwinds to the first fake before its original code. The
+        // original code of every function is written back together, before the fakes are
+        // freed.
+        crate::injector_core::common::defer_restores(|| {
+            #[cfg(any(
+                target_arch = "x86_64",
+                target_arch = "aarch64",
+                target_arch = "arm64ec",
+                target_arch = "arm"
+            ))]
+            while self.registrations.pop().is_some() {}
+            while self.guards.pop().is_some() {}
+            while self.redirects.pop().is_some() {}
+        });
+        self.closures.clear();
+        #[cfg(any(
+            target_arch = "x86_64",
+            target_arch = "aarch64",
+            target_arch = "arm64ec",
+            target_arch = "arm"
+        ))]
+        self.async_fakes.clear();
+        self.function_locks.clear();
+
+        if let Some(name) = &self.name {
+            for addr in self.labeled_fakes.drain(..) {
+                crate::interface::labels::unregister_fake(addr, name);
+            }
+        }
+
+        let _scope = crate::interface::labels::VerifyScope::enter(self.name.as_deref());
+        self.verifiers.clear();
+    }
+}
+
+/// `(method key, replacement, paused)` entries of a `new_task_local()` injector.
+#[cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec",
+    target_arch = "arm"
+))]
+pub(crate) type TaskFakes = std::sync::Arc<RwLock<Vec<(usize, usize, bool)>>>;
+
+/// A future that sees the fakes of a `new_task_local()` injector while it is polled.
+///
+/// Created by `InjectorPP::scope()`.
+#[cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec",
+    target_arch = "arm"
+))]
+pub struct TaskScoped<F> {
+    fakes: TaskFakes,
+    future: F,
+}
+
+#[cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec",
+    target_arch = "arm"
+))]
+impl<F: Future> Future for TaskScoped<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        // Safety: `future` is never moved out of the pinned `TaskScoped`.
+        let this = unsafe { self.get_unchecked_mut() };
+        let future = unsafe { Pin::new_unchecked(&mut this.future) };
+
+        let fakes = this.fakes.read().unwrap_or_else(|e| e.into_inner());
+        let _entered = crate::injector_core::thread_local_registry::enter_replacements(
+            fakes
+                .iter()
+                .filter(|&&(_, _, paused)| !paused)
+                .map(|&(method_key, replacement, _)| (method_key, replacement)),
+        );
+
+        future.poll(cx)
+    }
+}
+
+/// Returns whether `func` is currently faked from the point of view of the calling thread.
+///
+/// A function counts as faked when it is patched globally (`InjectorPP::new_global()`),
+/// or when the calling thread registered a thread-local fake for it. A paused global fake
+/// does not count. The query does not patch anything, so it is cheap enough to call from
+/// production helpers that want to behave differently under a mock (e.g. skip real
+/// network setup).
+///
+/// # Example
+///
+/// ```rust
+/// use injectorpp::interface::injector::*;
+///
+/// fn connect() -> bool {
+///     true
+/// }
+///
+/// assert!(!injectorpp::is_patched(injectorpp::func!(fn (connect)() -> bool)));
+///
+/// let mut injector = InjectorPP::new();
+/// injector
+///     .when_called(injectorpp::func!(fn (connect)() -> bool))
+///     .will_return_boolean(false);
+///
+/// assert!(injectorpp::is_patched(injectorpp::func!(fn (connect)() -> bool)));
+/// ```
+pub fn is_patched(func: FuncPtr) -> bool {
+    #[cfg(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec",
+        target_arch = "arm"
+    ))]
+    {
+        let method_key =
+            crate::injector_core::thread_local_registry::method_key_of(&func.func_ptr_internal);
+
+        crate::injector_core::thread_local_registry::has_thread_replacement(method_key)
+            || is_globally_patched(method_key)
+            || is_redirected(method_key)
+    }
+
+    #[cfg(not(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec",
+        target_arch = "arm"
+    )))]
+    {
+        // Global patches are written over the function body thunks lead to.
+        let func_addr =
+            crate::injector_core::thunk::resolve_thunks(func.func_ptr_internal.as_ptr() as usize);
+        is_globally_patched(func_addr) || is_redirected(func_addr)
+    }
+}
+
+/// Returns whether the calls of `func_addr` are redirected by `PatchStrategy::Got` or
+/// `PatchStrategy::CallSites`.
+fn is_redirected(func_addr: usize) -> bool {
+    crate::injector_core::redirect::is_redirected(func_addr)
+}
+
+/// A guard that prevents injectorpp affecting the test while alive.
+///
+/// On x86_64, this is a no-op since thread-local dispatch naturally isolates threads.
+/// On other architectures, this holds the lock that prevents injectors from being created.
+pub struct Preventer {
+    #[cfg(not(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec",
+        target_arch = "arm"
+    )))]
+    _lock: RwLockWriteGuard<'static, ()>,
+    #[cfg(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec",
+        target_arch = "arm"
+    ))]
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Preventer {
+    /// Check if patching is currently prevented by this guard.
+    ///
+    /// This always returns `true` while the guard exists.
+    pub fn is_active(&self) -> bool {
+        true
+    }
+}
+
+/// A builder that lets you chain patching operations.
+pub struct WhenCalledBuilder<'a> {
+    lib: &'a mut InjectorPP,
+    when: WhenCalled,
+    expected_signature: &'static str,
+    expected_type_id: Option<std::any::TypeId>,
+    expected_return: Option<ReturnLayout>,
+}
+
+impl WhenCalledBuilder<'_> {
+    /// Panics if `target` returns its value differently from the function to fake, e.g. in
+    /// registers where the caller passes a hidden pointer for a large struct, or in an integer
+    /// register where the caller reads a floating-point one. Checked even
+    /// when the signature is not, as the mismatch corrupts the stack of the caller.
+    fn check_return_layout(&self, target: &FuncPtr) {
+        if let (Some(expected), Some(actual)) = (self.expected_return, target.ret_layout) {
+            if expected.passing != actual.passing {
+                panic!(
+                    "Return ABI mismatch: the function returns {} but the fake returns {}{}",
+                    expected.describe(),
+                    actual.describe(),
+                    self.lib.label_suffix()
+                );
+            }
+        }
+    }
+
+    /// Panics if the parameters or the return value a `fake!` is declared with differ in size
+    /// from those of the function to fake, as described by its debug info. Functions without
+    /// debug info, and fakes not generated by `fake!`, are not checked.
+    #[cfg(feature = "debuginfo-check")]
+    fn check_debug_info(&self, target: &FuncPtr) {
+        let Some(declared) = &target.declared else {
+            return;
+        };
+        let addr = self.when.func_addr();
+        let Some(info) = crate::injector_core::debuginfo::function_info(addr) else {
+            return;
+        };
+
+        let name = match &info.name {
+            Some(name) => format!("{} ({:#x})", name, addr),
+            None => func_display_name(addr),
+        };
+        let mismatch = |detail: String| -> ! {
+            panic!("Debug info mismatch: {}{}", detail, self.lib.label_suffix())
+        };
+
+        if info.params.len() != declared.params.len() {
+            mismatch(format!(
+                "{} takes {} parameters but the fake is declared with {}",
+                name,
+                info.params.len(),
+                declared.params.len()
+            ));
+        }
+        for (index, (param, &size)) in info.params.iter().zip(&declared.params).enumerate() {
+            if let Some(actual) = param.size.filter(|&actual| actual != size) {
+                mismatch(format!(
+                    "parameter {} of {} is {} bytes but the fake declares {} bytes",
+                    index + 1,
+                    name,
+                    actual,
+                    size
+                ));
+            }
+        }
+
+        let (ret_size, ret_float) = match info.ret {
+            Some(ret) => (ret.size, ret.float),
+            None => (Some(0), false),
+        };
+        if let Some(actual) = ret_size.filter(|&actual| actual != declared.ret_size) {
+            mismatch(format!(
+                "{} returns {} bytes but the fake returns {} bytes",
+                name, actual, declared.ret_size
+            ));
+        }
+        if ret_float != declared.ret_float {
+            mismatch(if ret_float {
+                format!(
+                    "{} returns a floating-point value but the fake does not",
+                    name
+                )
+            } else {
+                format!(
+                    "the fake returns a floating-point value but {} does not",
+                    name
+                )
+            });
+        }
+    }
+
+    /// Patches the function even when it looks too small for the patch, instead of panicking.
+    ///
+    /// Before a function is patched, its size is looked up, and functions smaller than the
+    /// branch written over their first bytes are refused, as the branch would overwrite the
+    /// code following them. On x86_64, functions branching back into the bytes the branch
+    /// overwrites are refused as well.
+    ///
+    /// # Safety
+    ///
+    /// The bytes following the function, up to the size of the patch, must be padding or
+    /// code that is never executed while the function is faked, as in hand-written assembly
+    /// stubs. Branches into the patched bytes must not be taken while the function is faked.
+    pub unsafe fn allow_undersized_patch(mut self) -> Self {
+        self.when.allow_undersized_patch();
+        self
+    }
+
+    /// Chooses how the fake reaches the callers of the function, instead of the default
+    /// strategy of the injector, see `InjectorPP::set_default_strategy()`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the strategy is not supported on the running platform, see
+    /// `PatchStrategy::is_supported()`, or if it redirects calls and the injector was not
+    /// created with `InjectorPP::new_global()`.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use injectorpp::interface::injector::*;
+    ///
+    /// extern "C" fn fake_getpid() -> i32 {
+    ///     42
+    /// }
+    ///
+    /// let mut injector = InjectorPP::new_global();
+    /// unsafe {
+    ///     injector
+    ///         .when_called_unchecked(injectorpp::func_unchecked!(libc::getpid))
+    ///         .strategy(PatchStrategy::Got)
+    ///         .will_execute_raw_unchecked(injectorpp::func_unchecked!(fake_getpid));
+    /// }
+    ///
+    /// assert_eq!(unsafe { libc::getpid() }, 42);
+    /// ```
+    pub fn strategy(mut self, strategy: PatchStrategy) -> Self {
+        // The default strategy of the injector was checked when it was set.
+        if strategy != self.lib.default_strategy {
+            self.lib.check_strategy(strategy);
+        }
+
+        match strategy {
+            PatchStrategy::InlineBranch => self.when.patch_code(),
+            #[cfg(target_os = "linux")]
+            PatchStrategy::Got => self.when.redirect_calls(RedirectMode::Got),
+            #[cfg(all(
+                target_os = "linux",
+                any(target_arch = "x86_64", target_arch = "aarch64")
+            ))]
+            PatchStrategy::CallSites => self
+                .when
+                .redirect_calls(RedirectMode::CallSites(CallSiteScope::Program)),
+            #[cfg(feature = "external-engine")]
+            PatchStrategy::External => self.when.redirect_calls(RedirectMode::External),
+            #[allow(unreachable_patterns)]
+            _ => unreachable!("unsupported strategies are refused by check_strategy()"),
+        }
+        self
+    }
+
+    /// Fakes the function only where `caller` calls it, by rewriting the call instructions of
+    /// `caller` (see `PatchStrategy::CallSites`). Called several times, the calls of every caller
+    /// are faked.
+    ///
+    /// Calls made by other functions, including those `caller` calls, keep reaching the real
+    /// function.
+    ///
+    /// # Panics
+    ///
+    /// Panics outside of x86_64 and aarch64, or when the injector was not created with
+    /// `InjectorPP::new_global()`. Installing the fake panics if the size of `caller` is
+    /// unknown, or if it never calls the function.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use injectorpp::interface::injector::*;
+    ///
+    /// #[inline(never)]
+    /// fn is_enabled() -> bool {
+    ///     std::hint::black_box(false)
+    /// }
+    ///
+    /// #[inline(never)]
+    /// fn feature_banner() -> &'static str {
+    ///     if is_enabled() { "on" } else { "off" }
+    /// }
+    ///
+    /// let mut injector = InjectorPP::new_global();
+    /// injector
+    ///     .when_called(injectorpp::func!(fn (is_enabled)() -> bool))
+    ///     .call_sites_in(injectorpp::func!(fn (feature_banner)() -> &'static str))
+    ///     .will_return_boolean(true);
+    ///
+    /// assert_eq!(feature_banner(), "on");
+    /// assert!(!is_enabled());
+    /// ```
+    #[cfg_attr(
+        not(any(
+            target_arch = "x86_64",
+            target_arch = "aarch64",
+            target_arch = "arm64ec"
+        )),
+        allow(unused_mut)
+    )]
+    pub fn call_sites_in(mut self, caller: FuncPtr) -> Self {
+        self.require_call_sites("call_sites_in()");
+        #[cfg(any(
+            target_arch = "x86_64",
+            target_arch = "aarch64",
+            target_arch = "arm64ec"
+        ))]
+        {
+            let caller = crate::injector_core::thunk::resolve_thunks(
+                caller.func_ptr_internal.as_ptr() as usize,
+            );
+            match self.when.redirect_mode_mut() {
+                Some(RedirectMode::CallSites(CallSiteScope::Callers(callers))) => {
+                    callers.push(caller)
+                }
+                _ => self
+                    .when
+                    .redirect_calls(RedirectMode::CallSites(CallSiteScope::Callers(vec![
+                        caller,
+                    ]))),
+            }
+        }
+        #[cfg(not(any(
+            target_arch = "x86_64",
+            target_arch = "aarch64",
+            target_arch = "arm64ec"
+        )))]
+        let _ = caller;
+        self
+    }
+
+    /// Fakes the function only at the call instructions at the addresses in `sites`, by
+    /// rewriting them (see `PatchStrategy::CallSites`). Called several times, every site is faked.
+    ///
+    /// For callers whose size is unknown, or to fake some of the calls of a caller. The
+    /// addresses can be read from a disassembly of the caller, as offsets from its address.
+    ///
+    /// # Panics
+    ///
+    /// Panics outside of x86_64 and aarch64, or when the injector was not created with
+    /// `InjectorPP::new_global()`. Installing the fake panics if an instruction in `sites` is
+    /// not a direct call of the function, or an indirect call through a GOT or import table
+    /// slot holding it (`call [rip+disp32]` on x86_64).
+    ///
+    /// # Safety
+    ///
+    /// Every address in `sites` must be the start of an instruction of executable code.
+    #[cfg_attr(
+        not(any(
+            target_arch = "x86_64",
+            target_arch = "aarch64",
+            target_arch = "arm64ec"
+        )),
+        allow(unused_mut)
+    )]
+    pub unsafe fn at_call_sites(mut self, sites: &[usize]) -> Self {
+        self.require_call_sites("at_call_sites()");
+        #[cfg(any(
+            target_arch = "x86_64",
+            target_arch = "aarch64",
+            target_arch = "arm64ec"
+        ))]
+        match self.when.redirect_mode_mut() {
+            Some(RedirectMode::CallSites(CallSiteScope::Sites(known))) => {
+                known.extend_from_slice(sites)
+            }
+            _ => self
+                .when
+                .redirect_calls(RedirectMode::CallSites(CallSiteScope::Sites(
+                    sites.to_vec(),
+                ))),
+        }
+        #[cfg(not(any(
+            target_arch = "x86_64",
+            target_arch = "aarch64",
+            target_arch = "arm64ec"
+        )))]
+        let _ = sites;
+        self
+    }
+
+    /// Panics if `PatchStrategy::CallSites` is unavailable, naming the `method` selecting it.
+    fn require_call_sites(&self, method: &str) {
+        if !cfg!(any(
+            target_arch = "x86_64",
+            target_arch = "aarch64",
+            target_arch = "arm64ec"
+        )) {
+            panic!(
+                "{} is only available on x86_64 and aarch64{}",
+                method,
+                self.lib.label_suffix()
+            );
+        }
+        self.lib.require_global(method);
+    }
+
+    /// Sends the calls the fake makes of the function it fakes, directly or through the code
+    /// it calls, to the original function instead of the fake, on the thread running the fake.
+    ///
+    /// A fake of `memcpy`, `write` or a logging function easily calls the function it fakes,
+    /// e.g. when it formats a message, which would otherwise call the fake again, looping or
+    /// deadlocking on a lock the fake holds.
+    ///
+    /// # Panics
+    ///
+    /// Panics when the injector was created with `InjectorPP::new_global()`, whose fakes
+    /// overwrite the original function, or on architectures without thread-local dispatch.
+    /// Installing the fake panics if it was not created with `fake!` or `boxed_closure!`, as
+    /// only those tell when they run.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use injectorpp::interface::injector::*;
+    ///
+    /// #[inline(never)]
+    /// fn log(message: &str) -> usize {
+    ///     std::hint::black_box(message.len())
+    /// }
+    ///
+    /// let mut injector = InjectorPP::new();
+    /// injector
+    ///     .when_called(injectorpp::func!(fn (log)(&str) -> usize))
+    ///     .reentrant_calls_original()
+    ///     .will_execute(injectorpp::fake!(
+    ///         func_type: fn(message: &str) -> usize,
+    ///         returns: log(&format!("[test] {}", message))
+    ///     ));
+    ///
+    /// assert_eq!(log("ready"), 12);
+    /// ```
+    pub fn reentrant_calls_original(mut self) -> Self {
+        self.require_thread_local("reentrant_calls_original()");
+        self.when.guard_reentrancy();
+        self
+    }
+
+    /// Logs the address each call of the fake returns to, which `FakeHandle::callers()`
+    /// resolves to the calling function. Finds the call site reaching a fake unexpectedly.
+    ///
+    /// # Panics
+    ///
+    /// Panics when the injector was created with `InjectorPP::new_global()`, whose fakes are
+    /// not dispatched, or on architectures without thread-local dispatch.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use injectorpp::interface::injector::*;
+    ///
+    /// #[inline(never)]
+    /// fn delete_all() -> bool {
+    ///     std::hint::black_box(false)
+    /// }
+    ///
+    /// let mut injector = InjectorPP::new();
+    /// let handle = injector
+    ///     .when_called(injectorpp::func!(fn (delete_all)() -> bool))
+    ///     .record_callers()
+    ///     .will_return_boolean(true);
+    ///
+    /// delete_all();
+    /// for caller in handle.callers() {
+    ///     println!("delete_all() called from {caller}");
+    /// }
+    /// ```
+    #[cfg_attr(
+        not(any(
+            target_arch = "x86_64",
+            target_arch = "aarch64",
+            target_arch = "arm64ec",
+            target_arch = "arm"
+        )),
+        allow(unused_mut)
+    )]
+    pub fn record_callers(mut self) -> Self {
+        self.require_thread_local("record_callers()");
+        #[cfg(any(
+            target_arch = "x86_64",
+            target_arch = "aarch64",
+            target_arch = "arm64ec",
+            target_arch = "arm"
+        ))]
+        self.when.log_callers();
+        self
+    }
+
+    /// Logs a backtrace of each call of the fake, which `FakeHandle::backtraces()` returns and
+    /// `FakeHandle::assert_reached_through()` checks, to assert the code path reaching a
+    /// dependency or find where an unexpected call came from. Capturing a backtrace is slow, so
+    /// it is best kept to the fakes under investigation.
+    ///
+    /// # Panics
+    ///
+    /// Panics when the injector was created with `InjectorPP::new_global()`, or on
+    /// architectures without thread-local dispatch. Installing the fake panics if it was not
+    /// created with `fake!` or `boxed_closure!`, as only those tell when they run.
+    #[cfg_attr(
+        not(any(
+            target_arch = "x86_64",
+            target_arch = "aarch64",
+            target_arch = "arm64ec",
+            target_arch = "arm"
+        )),
+        allow(unused_mut)
+    )]
+    pub fn record_backtraces(mut self) -> Self {
+        self.require_thread_local("record_backtraces()");
+        #[cfg(any(
+            target_arch = "x86_64",
+            target_arch = "aarch64",
+            target_arch = "arm64ec",
+            target_arch = "arm"
+        ))]
+        self.when.log_backtraces();
+        self
+    }
+
+    /// Panics unless the fake is dispatched per thread, which `method` requires.
+    fn require_thread_local(&self, method: &str) {
+        if !cfg!(any(
+            target_arch = "x86_64",
+            target_arch = "aarch64",
+            target_arch = "arm64ec",
+            target_arch = "arm"
+        )) {
+            panic!(
+                "{} requires thread-local dispatch, which is not available on this \
+                 architecture{}",
+                method,
+                self.lib.label_suffix()
+            );
+        }
+        if self.lib.use_global {
+            panic!(
+                "{} is only supported with thread-local dispatch, not with \
+                 InjectorPP::new_global(){}",
+                method,
+                self.lib.label_suffix()
+            );
         }
     }
 
-    /// Begins faking an asynchronous function.
+    /// Makes the share of the calls drawn by `faults` run `failure` instead of the fake
+    /// installed next, on the thread installing it.
     ///
-    /// Accepts a pinned mutable reference to the async function future. Use the `async_func!` macro to obtain this reference.
+    /// `failure` is a `fake!` with the signature of the function, building the error of a
+    /// failing call. Use `will_fail()` when the other calls should reach the original function.
     ///
-    /// # Parameters
+    /// # Panics
     ///
-    /// - `_`: A pinned mutable reference to the async function future.
+    /// Panics when the injector was created with `InjectorPP::new_global()`, on architectures
+    /// without thread-local dispatch, or if `failure` does not match the function.
     ///
-    /// # Returns
+    /// # Example
     ///
-    /// A builder (`WhenCalledBuilderAsync`) to further specify the async fake behavior.
+    /// ```rust
+    /// use injectorpp::interface::injector::*;
     ///
-    /// # Safety
+    /// #[inline(never)]
+    /// fn fetch(key: u32) -> Result<u32, String> {
+    ///     Err(std::hint::black_box(format!("{} not found", key)))
+    /// }
     ///
-    /// This method is unsafe because it skips type check.
+    /// let faults = FaultInjector::new(42).failure_rate(1.0);
+    /// let mut injector = InjectorPP::new();
+    /// injector
+    ///     .when_called(injectorpp::func!(fn (fetch)(u32) -> Result<u32, String>))
+    ///     .inject_faults(&faults, injectorpp::fake!(
+    ///         func_type: fn(_key: u32) -> Result<u32, String>,
+    ///         returns: Err("timed out".to_string())
+    ///     ))
+    ///     .will_execute(injectorpp::fake!(
+    ///         func_type: fn(key: u32) -> Result<u32, String>,
+    ///         returns: Ok(key * 2)
+    ///     ));
+    ///
+    /// assert_eq!(fetch(1), Err("timed out".to_string()));
+    /// ```
+    pub fn inject_faults(
+        mut self,
+        faults: &FaultInjector,
+        failure: (FuncPtr, CallCountVerifier),
+    ) -> Self {
+        self.require_thread_local("inject_faults()");
+        let failure = self.check_failure(failure);
+        self.route_faults(faults, failure.func_ptr_internal.as_ptr() as usize, false);
+        self
+    }
+
+    /// Makes the share of the calls drawn by `faults` run `failure`, and lets the others reach
+    /// the original function, on the current thread.
+    ///
+    /// `failure` is a `fake!` with the signature of the function, building the error of a
+    /// failing call, e.g. an `Err` for `send()` or `fs::write()`.
+    ///
+    /// # Panics
+    ///
+    /// Panics when the injector was created with `InjectorPP::new_global()`, on architectures
+    /// without thread-local dispatch, or if `failure` does not match the function.
     ///
     /// # Example
     ///
     /// ```rust
     /// use injectorpp::interface::injector::*;
     ///
-    /// async fn async_add_one(x: u32) -> u32 {
-    ///     x + 1
-    /// }
-    ///
-    /// #[tokio::main]
-    /// async fn main() {
-    ///     let mut injector = InjectorPP::new();
-    ///
-    ///     unsafe {
-    ///         injector
-    ///             .when_called_async_unchecked(injectorpp::async_func_unchecked!(async_add_one(u32::default())))
-    ///             .will_return_async_unchecked(injectorpp::async_return_unchecked!(123, u32));
-    ///     }
+    /// let faults = FaultInjector::new(1).failure_rate(1.0);
+    /// let mut injector = InjectorPP::new();
+    /// injector
+    ///     .when_called(injectorpp::func!(
+    ///         fn (std::fs::write::<String, String>)(String, String) -> std::io::Result<()>
+    ///     ))
+    ///     .will_fail(&faults, injectorpp::fake!(
+    ///         func_type: fn(_path: String, _contents: String) -> std::io::Result<()>,
+    ///         returns: Err(std::io::ErrorKind::StorageFull.into())
+    ///     ));
     ///
-    ///     let result = async_add_one(5).await;
-    ///     assert_eq!(result, 123); // The patched value
-    /// }
+    /// let path = "/nonexistent/config".to_string();
+    /// assert!(std::fs::write(path, "{}".to_string()).is_err());
+    /// assert_eq!(faults.failures(), 1);
     /// ```
-    pub unsafe fn when_called_async_unchecked<F, T>(
-        &mut self,
-        _: Pin<&mut F>,
-    ) -> WhenCalledBuilderAsync<'_>
-    where
-        F: Future<Output = T>,
-    {
-        let poll_fn: fn(Pin<&mut F>, &mut Context<'_>) -> Poll<T> = <F as Future>::poll;
-        let when = WhenCalled::new(unsafe {
-            FuncPtr::new(poll_fn as *const (), std::any::type_name_of_val(&poll_fn))
-        }.func_ptr_internal);
-
-        WhenCalledBuilderAsync {
-            lib: self,
-            when,
-            expected_signature: "",
-            expected_type_id: None,
-        }
+    pub fn will_fail(
+        mut self,
+        faults: &FaultInjector,
+        failure: (FuncPtr, CallCountVerifier),
+    ) -> FakeHandle {
+        self.require_thread_local("will_fail()");
+        let failure_addr = failure.0.func_ptr_internal.as_ptr() as usize;
+        self.route_faults(faults, failure_addr, true);
+        self.will_execute(failure)
     }
-}
 
-impl Default for InjectorPP {
-    fn default() -> Self {
-        Self::new()
+    /// Checks the failure function of a `FaultInjector` as a fake of the function, and keeps
+    /// the verifier of its call count.
+    fn check_failure(&mut self, failure: (FuncPtr, CallCountVerifier)) -> FuncPtr {
+        let (failure, verifier) = failure;
+        check_signature(
+            self.lib,
+            self.expected_signature,
+            self.expected_type_id,
+            &failure,
+        );
+        self.check_return_layout(&failure);
+        self.lib.label_fake(&failure);
+        self.lib.verifiers.push(verifier);
+        failure
     }
-}
 
-/// A guard that prevents injectorpp affecting the test while alive.
-///
-/// On x86_64, this is a no-op since thread-local dispatch naturally isolates threads.
-/// On other architectures, this holds the global mutex that prevents patching.
-pub struct Preventer {
-    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64", target_arch = "arm")))]
-    _lock: MutexGuard<'static, ()>,
-    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64", target_arch = "arm"))]
-    _not_send: PhantomData<*const ()>,
-}
+    /// Sends the calls drawn by `faults` to `failure`, and the others to the original function
+    /// when `others_to_original` is set.
+    #[allow(unused_variables, unused_mut)]
+    fn route_faults(&mut self, faults: &FaultInjector, failure: usize, others_to_original: bool) {
+        #[cfg(any(
+            target_arch = "x86_64",
+            target_arch = "aarch64",
+            target_arch = "arm64ec",
+            target_arch = "arm"
+        ))]
+        self.when
+            .inject_faults(crate::injector_core::thread_local_registry::FaultRoute {
+                fails: faults.roller(),
+                failure,
+                others_to_original,
+            });
+    }
 
-impl Preventer {
-    /// Check if patching is currently prevented by this guard.
-    ///
-    /// This always returns `true` while the guard exists.
-    pub fn is_active(&self) -> bool {
-        true
+    /// Panics if `record_backtraces()` was called, as the code `method` generates does not tell
+    /// when it runs.
+    fn check_generated_fake_allowed(&self, method: &str) {
+        if self.when.logs_backtraces() {
+            panic!(
+                "record_backtraces() requires a fake created with fake! or boxed_closure!, not {}{}",
+                method,
+                self.lib.label_suffix()
+            );
+        }
     }
-}
 
-/// A builder that lets you chain patching operations.
-pub struct WhenCalledBuilder<'a> {
-    lib: &'a mut InjectorPP,
-    when: WhenCalled,
-    expected_signature: &'static str,
-    expected_type_id: Option<std::any::TypeId>,
-}
+    /// Panics if `reentrant_calls_original()` or `record_backtraces()` was called and `target`
+    /// cannot tell when it runs, as it was not created with `fake!` or `boxed_closure!`.
+    fn check_fake_announces_calls(&self, target: &FuncPtr) {
+        let key = target.func_ptr_internal.as_ptr() as usize;
+        if target.declared.is_some() || crate::interface::boxed_closure::is_registered(key) {
+            return;
+        }
+        let method = if self.when.guards_reentrancy() {
+            "reentrant_calls_original()"
+        } else if self.when.logs_backtraces() {
+            "record_backtraces()"
+        } else {
+            return;
+        };
+        panic!(
+            "{} requires a fake created with fake! or boxed_closure!{}",
+            method,
+            self.lib.label_suffix()
+        );
+    }
 
-impl WhenCalledBuilder<'_> {
     /// Fake the target function to branch to the provided function.
     ///
     /// Allows full customization of the faked function behavior by providing your own function or closure.
@@ -498,41 +3476,49 @@ impl WhenCalledBuilder<'_> {
     ///
     /// assert!(Path::new("/nonexistent").exists());
     /// ```
-    pub fn will_execute_raw(self, target: FuncPtr) {
-        match (self.expected_type_id, target.type_id) {
-            (Some(expected), Some(actual)) if expected != actual => {
-                panic!(
-                    "Signature mismatch: expected {:?} but got {:?}",
-                    self.expected_signature, target.signature
-                );
-            }
-            (None, _) | (_, None) => {
-                if normalize_signature(target.signature)
-                    != normalize_signature(self.expected_signature)
-                {
-                    panic!(
-                        "Signature mismatch: expected {:?} but got {:?}",
-                        self.expected_signature, target.signature
-                    );
-                }
-            }
-            _ => {}
+    pub fn will_execute_raw(self, target: FuncPtr) -> FakeHandle {
+        check_signature(
+            self.lib,
+            self.expected_signature,
+            self.expected_type_id,
+            &target,
+        );
+
+        self.check_return_layout(&target);
+        #[cfg(feature = "debuginfo-check")]
+        self.check_debug_info(&target);
+        self.lib.label_fake(&target);
+        self.check_fake_announces_calls(&target);
+
+        if self.when.redirects_calls() {
+            let redirect = self.when.will_execute_redirect(target.func_ptr_internal);
+            return self.lib.push_redirect(redirect);
         }
 
         if self.lib.use_global {
             let guard = self.when.will_execute_guard(target.func_ptr_internal);
-            self.lib.guards.push(guard);
+            self.lib.push_guard(guard)
         } else {
-            #[cfg(any(target_arch = "x86_64", target_arch = "aarch64", target_arch = "arm"))]
+            #[cfg(any(
+                target_arch = "x86_64",
+                target_arch = "aarch64",
+                target_arch = "arm64ec",
+                target_arch = "arm"
+            ))]
             {
                 let reg = self.when.will_execute_thread_local(target.func_ptr_internal);
-                self.lib.registrations.push(reg);
+                self.lib.push_registration(reg)
             }
 
-            #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64", target_arch = "arm")))]
+            #[cfg(not(any(
+                target_arch = "x86_64",
+                target_arch = "aarch64",
+                target_arch = "arm64ec",
+                target_arch = "arm"
+            )))]
             {
                 let guard = self.when.will_execute_guard(target.func_ptr_internal);
-                self.lib.guards.push(guard);
+                self.lib.push_guard(guard)
             }
         }
     }
@@ -547,7 +3533,9 @@ impl WhenCalledBuilder<'_> {
     ///
     /// # Safety
     ///
-    /// This method is unsafe because it skips type check.
+    /// This method is unsafe because it skips type check. It still panics if the fake returns
+    /// its value in registers where the function returns it through a hidden pointer, or the
+    /// other way round, when the return types of both are known.
     ///
     /// # Example
     ///
@@ -590,21 +3578,42 @@ impl WhenCalledBuilder<'_> {
     ///
     /// assert!(Path::new("/nonexistent").exists());
     /// ```
-    pub unsafe fn will_execute_raw_unchecked(self, target: FuncPtr) {
+    pub unsafe fn will_execute_raw_unchecked(self, target: FuncPtr) -> FakeHandle {
+        self.check_return_layout(&target);
+        #[cfg(feature = "debuginfo-check")]
+        self.check_debug_info(&target);
+        self.lib.label_fake(&target);
+        self.check_fake_announces_calls(&target);
+
+        if self.when.redirects_calls() {
+            let redirect = self.when.will_execute_redirect(target.func_ptr_internal);
+            return self.lib.push_redirect(redirect);
+        }
+
         if self.lib.use_global {
             let guard = self.when.will_execute_guard(target.func_ptr_internal);
-            self.lib.guards.push(guard);
+            self.lib.push_guard(guard)
         } else {
-            #[cfg(any(target_arch = "x86_64", target_arch = "aarch64", target_arch = "arm"))]
+            #[cfg(any(
+                target_arch = "x86_64",
+                target_arch = "aarch64",
+                target_arch = "arm64ec",
+                target_arch = "arm"
+            ))]
             {
                 let reg = self.when.will_execute_thread_local(target.func_ptr_internal);
-                self.lib.registrations.push(reg);
+                self.lib.push_registration(reg)
             }
 
-            #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64", target_arch = "arm")))]
+            #[cfg(not(any(
+                target_arch = "x86_64",
+                target_arch = "aarch64",
+                target_arch = "arm64ec",
+                target_arch = "arm"
+            )))]
             {
                 let guard = self.when.will_execute_guard(target.func_ptr_internal);
-                self.lib.guards.push(guard);
+                self.lib.push_guard(guard)
             }
         }
     }
@@ -646,10 +3655,68 @@ impl WhenCalledBuilder<'_> {
     /// `assign``: // Optional. Use to set values to reference variables of the function to fake.
     /// `returns``: // Required for the function has return. Specify what the return value should be.
     /// `times``: // Optional. How many times the function should be called. If the value is not satisfied at the end of the test, the test will fail.
-    pub fn will_execute(self, fake_pair: (FuncPtr, CallCountVerifier)) {
+    pub fn will_execute(self, fake_pair: (FuncPtr, CallCountVerifier)) -> FakeHandle {
         let (fake_func, verifier) = fake_pair;
         self.lib.verifiers.push(verifier);
-        self.will_execute_raw(fake_func);
+        self.will_execute_raw(fake_func)
+    }
+
+    /// Fake the target function with a capturing closure.
+    ///
+    /// The closure may own and mutate captured state, which makes it convenient for fakes that
+    /// hand out canned responses or record the calls they receive. Use the `boxed_closure!` macro
+    /// to create it. The closure is dropped when the injector goes out of scope.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use injectorpp::interface::injector::*;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// fn send(message: &str) -> bool {
+    ///     false
+    /// }
+    ///
+    /// let sent = Arc::new(Mutex::new(Vec::new()));
+    /// let recorder = sent.clone();
+    ///
+    /// let mut injector = InjectorPP::new();
+    /// injector
+    ///     .when_called(injectorpp::func!(fn (send)(&str) -> bool))
+    ///     .will_execute_closure(injectorpp::boxed_closure!(
+    ///         move |message| {
+    ///             recorder.lock().unwrap().push(message.to_string());
+    ///             true
+    ///         },
+    ///         fn(&str) -> bool
+    ///     ));
+    ///
+    /// assert!(send("hello"));
+    /// assert_eq!(*sent.lock().unwrap(), vec!["hello".to_string()]);
+    /// ```
+    pub fn will_execute_closure(self, closure: BoxedClosure) -> FakeHandle {
+        let BoxedClosure { mut shims, closure } = closure;
+        let keys: Vec<usize> = shims
+            .iter()
+            .map(|shim| shim.func_ptr_internal.as_ptr() as usize)
+            .collect();
+
+        let thread_local = !self.lib.use_global
+            && !self.lib.is_task_local()
+            && cfg!(any(
+                target_arch = "x86_64",
+                target_arch = "aarch64",
+                target_arch = "arm64ec",
+                target_arch = "arm"
+            ));
+        let owner = thread_local.then(|| std::thread::current().id());
+
+        let (registration, slot) =
+            crate::interface::boxed_closure::register(&keys, owner, closure);
+        self.lib
+            .closures
+            .push((self.when.func_addr(), registration));
+        self.will_execute_raw(shims.swap_remove(slot))
     }
 
     /// Fake the target function to always return a fixed boolean value.
@@ -669,29 +3736,144 @@ impl WhenCalledBuilder<'_> {
     ///
     /// assert!(Path::new("/nonexistent").exists());
     /// ```
-    pub fn will_return_boolean(self, value: bool) {
+    pub fn will_return_boolean(self, value: bool) -> FakeHandle {
+        self.check_generated_fake_allowed("will_return_boolean()");
+
         // Ensure the target function returns a bool
         if !self.expected_signature.trim().ends_with("-> bool") {
             panic!(
-                "Signature mismatch: will_return_boolean requires a function returning bool but got {}",
-                self.expected_signature
+                "Signature mismatch: will_return_boolean requires a function returning bool but got {}{}",
+                self.expected_signature,
+                self.lib.label_suffix()
             );
         }
 
+        #[cfg(any(
+            target_arch = "x86_64",
+            target_arch = "aarch64",
+            target_arch = "arm64ec",
+            target_arch = "arm"
+        ))]
+        if self.when.redirects_calls() {
+            let redirect = self.when.will_return_boolean_redirect(value);
+            return self.lib.push_redirect(redirect);
+        }
+
         if self.lib.use_global {
             let guard = self.when.will_return_boolean_guard(value);
-            self.lib.guards.push(guard);
+            self.lib.push_guard(guard)
         } else {
-            #[cfg(any(target_arch = "x86_64", target_arch = "aarch64", target_arch = "arm"))]
+            #[cfg(any(
+                target_arch = "x86_64",
+                target_arch = "aarch64",
+                target_arch = "arm64ec",
+                target_arch = "arm"
+            ))]
             {
                 let reg = self.when.will_return_boolean_thread_local(value);
-                self.lib.registrations.push(reg);
+                self.lib.push_registration(reg)
             }
 
-            #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64", target_arch = "arm")))]
+            #[cfg(not(any(
+                target_arch = "x86_64",
+                target_arch = "aarch64",
+                target_arch = "arm64ec",
+                target_arch = "arm"
+            )))]
             {
                 let guard = self.when.will_return_boolean_guard(value);
-                self.lib.guards.push(guard);
+                self.lib.push_guard(guard)
+            }
+        }
+    }
+
+    /// Fake the target function to always return a fixed `f64` value.
+    ///
+    /// Convenient for functions of math libraries, including C functions returning `double`.
+    /// The value is returned in the floating-point register of the calling convention.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use injectorpp::interface::injector::*;
+    ///
+    /// #[inline(never)]
+    /// fn sensor_reading(channel: u8) -> f64 {
+    ///     std::hint::black_box(channel as f64 * 0.5)
+    /// }
+    ///
+    /// let mut injector = InjectorPP::new();
+    /// injector
+    ///     .when_called(injectorpp::func!(fn (sensor_reading)(u8) -> f64))
+    ///     .will_return_f64(21.5);
+    ///
+    /// assert_eq!(sensor_reading(3), 21.5);
+    /// ```
+    pub fn will_return_f64(self, value: f64) -> FakeHandle {
+        self.will_return_float("f64", FloatReturn::F64(value))
+    }
+
+    /// Fake the target function to always return a fixed `f32` value.
+    ///
+    /// Convenient for functions of math libraries, including C functions returning `float`.
+    /// The value is returned in the floating-point register of the calling convention.
+    pub fn will_return_f32(self, value: f32) -> FakeHandle {
+        self.will_return_float("f32", FloatReturn::F32(value))
+    }
+
+    fn will_return_float(self, ty: &str, value: FloatReturn) -> FakeHandle {
+        self.check_generated_fake_allowed(&format!("will_return_{}()", ty));
+
+        // Ensure the target function returns the float type
+        if !self
+            .expected_signature
+            .trim()
+            .ends_with(&format!("-> {}", ty))
+        {
+            panic!(
+                "Signature mismatch: will_return_{} requires a function returning {} but got {}{}",
+                ty,
+                ty,
+                self.expected_signature,
+                self.lib.label_suffix()
+            );
+        }
+
+        #[cfg(any(
+            target_arch = "x86_64",
+            target_arch = "aarch64",
+            target_arch = "arm64ec",
+            target_arch = "arm"
+        ))]
+        if self.when.redirects_calls() {
+            let redirect = self.when.will_return_float_redirect(value);
+            return self.lib.push_redirect(redirect);
+        }
+
+        if self.lib.use_global {
+            let guard = self.when.will_return_float_guard(value);
+            self.lib.push_guard(guard)
+        } else {
+            #[cfg(any(
+                target_arch = "x86_64",
+                target_arch = "aarch64",
+                target_arch = "arm64ec",
+                target_arch = "arm"
+            ))]
+            {
+                let reg = self.when.will_return_float_thread_local(value);
+                self.lib.push_registration(reg)
+            }
+
+            #[cfg(not(any(
+                target_arch = "x86_64",
+                target_arch = "aarch64",
+                target_arch = "arm64ec",
+                target_arch = "arm"
+            )))]
+            {
+                let guard = self.when.will_return_float_guard(value);
+                self.lib.push_guard(guard)
             }
         }
     }
@@ -730,37 +3912,36 @@ impl WhenCalledBuilderAsync<'_> {
     /// }
     /// ```
     pub fn will_return_async(self, target: FuncPtr) {
-        match (self.expected_type_id, target.type_id) {
-            (Some(expected), Some(actual)) if expected != actual => {
-                panic!(
-                    "Signature mismatch: expected {:?} but got {:?}",
-                    self.expected_signature, target.signature
-                );
-            }
-            (None, _) | (_, None) => {
-                if normalize_signature(target.signature)
-                    != normalize_signature(self.expected_signature)
-                {
-                    panic!(
-                        "Signature mismatch: expected {:?} but got {:?}",
-                        self.expected_signature, target.signature
-                    );
-                }
-            }
-            _ => {}
-        }
+        check_signature(
+            self.lib,
+            self.expected_signature,
+            self.expected_type_id,
+            &target,
+        );
+
+        self.lib.label_fake(&target);
 
         if self.lib.use_global {
             let guard = self.when.will_execute_guard(target.func_ptr_internal);
             self.lib.guards.push(guard);
         } else {
-            #[cfg(any(target_arch = "x86_64", target_arch = "aarch64", target_arch = "arm"))]
+            #[cfg(any(
+                target_arch = "x86_64",
+                target_arch = "aarch64",
+                target_arch = "arm64ec",
+                target_arch = "arm"
+            ))]
             {
                 let reg = self.when.will_execute_thread_local(target.func_ptr_internal);
-                self.lib.registrations.push(reg);
+                self.lib.push_registration(reg);
             }
 
-            #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64", target_arch = "arm")))]
+            #[cfg(not(any(
+                target_arch = "x86_64",
+                target_arch = "aarch64",
+                target_arch = "arm64ec",
+                target_arch = "arm"
+            )))]
             {
                 let guard = self.when.will_execute_guard(target.func_ptr_internal);
                 self.lib.guards.push(guard);
@@ -768,6 +3949,38 @@ impl WhenCalledBuilderAsync<'_> {
         }
     }
 
+    /// Fake the target async function to return a specified async value, and verify how many
+    /// times it is awaited.
+    ///
+    /// Use `async_return!` with `times:` to create the fake and its verifier. The test fails
+    /// when the injector is dropped if the async function was not awaited exactly that many
+    /// times.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use injectorpp::interface::injector::*;
+    ///
+    /// async fn fetch_page(page: u32) -> Vec<u32> {
+    ///     vec![page]
+    /// }
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut injector = InjectorPP::new();
+    ///     injector
+    ///         .when_called_async(injectorpp::async_func!(fetch_page(0), Vec<u32>))
+    ///         .will_return_async_times(injectorpp::async_return!(vec![7], Vec<u32>, times: 1));
+    ///
+    ///     assert_eq!(fetch_page(3).await, vec![7]);
+    /// }
+    /// ```
+    pub fn will_return_async_times(self, fake_pair: (FuncPtr, CallCountVerifier)) {
+        let (target, verifier) = fake_pair;
+        self.lib.verifiers.push(verifier);
+        self.will_return_async(target);
+    }
+
     /// Fake the target async function to return a specified async value.
     ///
     /// This method allows you to fake async functions by specifying the return value directly.
@@ -800,17 +4013,29 @@ impl WhenCalledBuilderAsync<'_> {
     /// }
     /// ```
     pub unsafe fn will_return_async_unchecked(self, target: FuncPtr) {
+        self.lib.label_fake(&target);
+
         if self.lib.use_global {
             let guard = self.when.will_execute_guard(target.func_ptr_internal);
             self.lib.guards.push(guard);
         } else {
-            #[cfg(any(target_arch = "x86_64", target_arch = "aarch64", target_arch = "arm"))]
+            #[cfg(any(
+                target_arch = "x86_64",
+                target_arch = "aarch64",
+                target_arch = "arm64ec",
+                target_arch = "arm"
+            ))]
             {
                 let reg = self.when.will_execute_thread_local(target.func_ptr_internal);
-                self.lib.registrations.push(reg);
+                self.lib.push_registration(reg);
             }
 
-            #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64", target_arch = "arm")))]
+            #[cfg(not(any(
+                target_arch = "x86_64",
+                target_arch = "aarch64",
+                target_arch = "arm64ec",
+                target_arch = "arm"
+            )))]
             {
                 let guard = self.when.will_execute_guard(target.func_ptr_internal);
                 self.lib.guards.push(guard);
@@ -819,3 +4044,190 @@ impl WhenCalledBuilderAsync<'_> {
     }
 }
 
+/// A builder for faking a stream.
+#[cfg(feature = "stream")]
+pub struct WhenCalledBuilderStream<'a> {
+    inner: WhenCalledBuilderAsync<'a>,
+}
+
+#[cfg(feature = "stream")]
+impl WhenCalledBuilderStream<'_> {
+    /// Fake the target stream to yield the items configured with `stream_return!`.
+    ///
+    /// Each stream of the target type yields the items in order, then `None`.
+    pub fn will_return_stream(self, target: FuncPtr) {
+        self.inner.will_return_async(target);
+    }
+}
+
+/// A builder for faking an async function with a fake that receives its arguments.
+#[cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec",
+    target_arch = "arm"
+))]
+pub struct WhenCalledBuilderAsyncFn<'a, P: AsyncFnPointer> {
+    lib: &'a mut InjectorPP,
+    target: P,
+}
+
+#[cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec",
+    target_arch = "arm"
+))]
+impl<P: AsyncFnPointer> WhenCalledBuilderAsyncFn<'_, P> {
+    /// Fake the target async function with a closure returning a future.
+    ///
+    /// The closure is called with a clone of the arguments, as a tuple, every time the async
+    /// function is called. The future it returns is awaited in place of the original body.
+    ///
+    /// Only supported with thread-local dispatch, it panics on injectors created with
+    /// `new_global()`. The future must be polled on the thread that installed the fake.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use injectorpp::interface::injector::*;
+    ///
+    /// async fn connect(addr: &str) -> Result<String, String> {
+    ///     Err(format!("cannot connect to {addr}"))
+    /// }
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut injector = InjectorPP::new();
+    ///     injector
+    ///         .when_called_async_fn(injectorpp::async_fn!(fn (connect)(&str) -> Result<String, String>))
+    ///         .will_execute_async(|(addr,)| async move {
+    ///             if addr.starts_with("10.") {
+    ///                 Ok(format!("connected to {addr}"))
+    ///             } else {
+    ///                 Err("unreachable".to_string())
+    ///             }
+    ///         });
+    ///
+    ///     assert_eq!(connect("10.0.0.1").await, Ok("connected to 10.0.0.1".to_string()));
+    ///     assert_eq!(connect("192.168.0.1").await, Err("unreachable".to_string()));
+    /// }
+    /// ```
+    pub fn will_execute_async<F, G>(self, mut fake: F)
+    where
+        F: FnMut(P::Args) -> G + 'static,
+        G: Future<Output = <P::Fut as Future>::Output> + 'static,
+    {
+        if self.lib.use_global {
+            panic!(
+                "will_execute_async is only supported with thread-local dispatch, not with InjectorPP::new_global(){}",
+                self.lib.label_suffix()
+            );
+        }
+
+        if self.lib.is_task_local() {
+            panic!(
+                "will_execute_async is not supported with InjectorPP::new_task_local(){}",
+                self.lib.label_suffix()
+            );
+        }
+
+        let func = unsafe { FuncPtr::new(self.target.__addr(), "") };
+        let poll = unsafe { FuncPtr::new(crate::interface::async_fn::poll_fn::<P>(), "") };
+        let func_shim = unsafe { FuncPtr::new(P::__shim(), "") };
+        let poll_shim = unsafe { FuncPtr::new(crate::interface::async_fn::poll_shim::<P>(), "") };
+
+        let method_key =
+            crate::injector_core::thread_local_registry::method_key_of(&func.func_ptr_internal);
+        let poll_key =
+            crate::injector_core::thread_local_registry::method_key_of(&poll.func_ptr_internal);
+
+        let registration = crate::interface::async_fn::register(
+            self.target,
+            method_key,
+            poll_key,
+            Box::new(move |args| Box::pin(fake(args))),
+        );
+        self.lib.async_fakes.push(registration);
+
+        let reg = self
+            .lib
+            .when(poll.func_ptr_internal)
+            .will_execute_thread_local(poll_shim.func_ptr_internal);
+        self.lib.push_registration(reg);
+
+        let reg = self
+            .lib
+            .when(func.func_ptr_internal)
+            .will_execute_thread_local(func_shim.func_ptr_internal);
+        self.lib.push_registration(reg);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::normalize_signature;
+
+    fn same(a: &str, b: &str) -> bool {
+        normalize_signature(a) == normalize_signature(b)
+    }
+
+    #[test]
+    fn test_normalize_signature_ignores_argument_lifetimes() {
+        assert!(same("fn(&str) -> bool", "fn(&'static str) -> bool"));
+        assert!(same("fn(&'_ str) -> bool", "fn(&str) -> bool"));
+        assert!(same(
+            "for<'a, 'b> fn(&'a mut Vec<u8>, &'b str) -> usize",
+            "for<'a, 'b> fn(&mut Vec<u8>, &'_ str) -> usize"
+        ));
+        assert!(same(
+            "fn(core::cell::Ref<'_, u8>, Box<dyn Fn() + 'static>)",
+            "fn(core::cell::Ref<u8>, Box<dyn Fn()>)"
+        ));
+        assert!(same("fn(Pair<u8, 'a>)", "fn(Pair<u8>)"));
+    }
+
+    #[test]
+    fn test_normalize_signature_keeps_return_and_binder_lifetimes() {
+        assert!(!same("fn(&'_ str) -> &'_ str", "fn(&str) -> &str"));
+        assert!(same("fn(&'_ str) -> &'_ str", "fn(&str) -> &'_ str"));
+        assert!(!same(
+            "for<'a> fn(&'a str) -> usize",
+            "fn(&'static str) -> usize"
+        ));
+        assert!(same(
+            "fn(fn(&'_ str) -> &'_ str) -> bool",
+            "fn(fn(&str) -> &str) -> bool"
+        ));
+    }
+
+    #[test]
+    fn test_normalize_signature_ignores_trait_object_lifetimes() {
+        assert!(same(
+            "fn(alloc::boxed::Box<(dyn core::ops::function::Fn() + '_)>)",
+            "fn(alloc::boxed::Box<dyn core::ops::function::Fn()>)"
+        ));
+        assert!(!same(
+            "fn(&'_ str) -> alloc::boxed::Box<(dyn core::ops::function::Fn() + '_)>",
+            "fn(&str) -> alloc::boxed::Box<dyn core::ops::function::Fn()>"
+        ));
+        assert!(same(
+            "fn(&(dyn core::any::Any + core::marker::Send + 'static))",
+            "fn(&(dyn core::any::Any + core::marker::Send))"
+        ));
+        assert!(!same(
+            "fn(&(dyn core::any::Any + core::marker::Send))",
+            "fn(&dyn core::any::Any)"
+        ));
+    }
+
+    #[test]
+    fn test_normalize_signature_canonicalizes_std_paths() {
+        assert!(same(
+            "fn(&std::path::Path) -> std::io::Result<alloc::string::String>",
+            "fn(&core::path::Path) -> core::io::Result<core::string::String>"
+        ));
+        assert!(!same("fn(mystd::Path)", "fn(core::Path)"));
+        assert!(!same("fn(u32) -> bool", "fn(u64) -> bool"));
+    }
+}
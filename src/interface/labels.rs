@@ -0,0 +1,90 @@
+//! Bookkeeping for named injectors.
+//!
+//! An injector created with `InjectorPP::new_named` attaches its name to every fake it
+//! installs, so that panics raised from inside a fake, from a call count verifier or
+//! from a signature check can say which injector they belong to.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::LazyLock;
+use std::sync::Mutex;
+
+/// Maps the address of a replacement function to the names of the injectors using it.
+/// The same fake may be installed by several injectors, the most recent one wins.
+static FAKE_LABELS: LazyLock<Mutex<HashMap<usize, Vec<String>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+thread_local! {
+    /// Name of the injector whose verifiers are currently being checked on this thread.
+    static VERIFY_LABEL: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+fn labels() -> std::sync::MutexGuard<'static, HashMap<usize, Vec<String>>> {
+    match FAKE_LABELS.lock() {
+        Ok(g) => g,
+        Err(e) => e.into_inner(),
+    }
+}
+
+/// Formats the suffix appended to panic messages for the given injector name.
+pub(crate) fn suffix(name: Option<&str>) -> String {
+    match name {
+        Some(name) => format!(" (injector \"{name}\")"),
+        None => String::new(),
+    }
+}
+
+/// Associates a replacement function with the name of the injector installing it.
+pub(crate) fn register_fake(addr: usize, name: &str) {
+    labels().entry(addr).or_default().push(name.to_string());
+}
+
+/// Removes one association made by `register_fake`.
+pub(crate) fn unregister_fake(addr: usize, name: &str) {
+    let mut labels = labels();
+    if let Some(names) = labels.get_mut(&addr) {
+        if let Some(pos) = names.iter().rposition(|n| n == name) {
+            names.remove(pos);
+        }
+
+        if names.is_empty() {
+            labels.remove(&addr);
+        }
+    }
+}
+
+/// Returns the panic message suffix for a replacement function.
+pub(crate) fn fake_suffix(addr: usize) -> String {
+    let labels = labels();
+    suffix(
+        labels
+            .get(&addr)
+            .and_then(|names| names.last())
+            .map(String::as_str),
+    )
+}
+
+/// Returns the panic message suffix for the verifiers being checked on this thread.
+pub(crate) fn verify_suffix() -> String {
+    VERIFY_LABEL.with(|label| suffix(label.borrow().as_deref()))
+}
+
+/// Marks the verifiers dropped while this guard is alive as belonging to a named injector.
+/// The previous label is restored on drop, including during unwinding.
+pub(crate) struct VerifyScope {
+    previous: Option<String>,
+}
+
+impl VerifyScope {
+    pub(crate) fn enter(name: Option<&str>) -> Self {
+        let previous = VERIFY_LABEL.with(|label| label.replace(name.map(str::to_string)));
+        VerifyScope { previous }
+    }
+}
+
+impl Drop for VerifyScope {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        let _ = VERIFY_LABEL.try_with(|label| *label.borrow_mut() = previous);
+    }
+}
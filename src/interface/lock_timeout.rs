@@ -0,0 +1,67 @@
+use std::time::Duration;
+
+/// Returned by `InjectorPP::try_new()` and `try_when_called()`, and raised as a panic by
+/// `when_called()`, when a lock could not be acquired within the timeout.
+///
+/// Lists every injector holding the lock. Enable diagnostic logging, with `INJECTORPP_LOG` or
+/// `InjectorPP::set_diagnostic_logging(true)`, to include where each of them locked it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct LockTimeout {
+    /// Address of the function that could not be locked, or 0 when waiting for
+    /// `InjectorPP::prevent()`.
+    pub func_addr: usize,
+    /// Name of that function, when it can be resolved from the dynamic symbol table.
+    pub symbol: Option<String>,
+    /// How long the lock was waited for.
+    pub timeout: Duration,
+    /// A description of each current holder: global or thread-local, thread name and,
+    /// when backtraces are enabled, where the lock was taken.
+    pub holders: Vec<String>,
+}
+
+impl LockTimeout {
+    pub(crate) fn new(func_addr: usize, timeout: Duration, holders: Vec<String>) -> Self {
+        Self {
+            func_addr,
+            symbol: crate::injector_core::symbols::symbol_name(func_addr),
+            timeout,
+            holders,
+        }
+    }
+}
+
+impl std::fmt::Display for LockTimeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.symbol, self.func_addr) {
+            (_, 0) => write!(
+                f,
+                "Timed out after {:?} waiting for the injector lock",
+                self.timeout
+            )?,
+            (Some(symbol), addr) => write!(
+                f,
+                "Timed out after {:?} waiting for the lock on {} ({:#x})",
+                self.timeout, symbol, addr
+            )?,
+            (None, addr) => write!(
+                f,
+                "Timed out after {:?} waiting for the lock on function at {:#x}",
+                self.timeout, addr
+            )?,
+        }
+
+        if self.holders.is_empty() {
+            return Ok(());
+        }
+
+        write!(f, ", held by:")?;
+        for holder in &self.holders {
+            write!(f, "\n  - {}", holder)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for LockTimeout {}
@@ -1,8 +1,16 @@
+use crate::interface::func_ptr::FuncPtr;
+
 /// Converts a function to a `FuncPtr`.
 ///
 /// This macro handles both generic and non-generic functions:
 /// - For generic functions, provide the function name and type parameters separately: `func!(function_name, fn(Type1, Type2))`
 /// - For non-generic functions, simply provide the function: `func!(function_name, fn())`
+/// - For functions and inherent methods whose signature should not be restated, provide the
+///   path alone: `func!(Counter::add)`. The receiver and the rest of the signature are taken
+///   from the function, as with `method!`. This form records no `TypeId`, as the lifetimes of
+///   the arguments are only inferred, so fakes are checked against its type name alone: types
+///   with the same name, such as from two versions of a crate, are not told apart, and the
+///   lifetimes of the arguments are not compared. Use the `fn` form where these matter.
 ///
 /// # Lifetime Safety
 ///
@@ -19,22 +27,46 @@
 macro_rules! func {
     // Case 1: Generic function — provide function name and types separately
     ($f:ident :: <$($gen:ty),*>, $fn_type:ty) => {{
-        let fn_val:$fn_type = $f::<$($gen),*>;
+        let item = $f::<$($gen),*>;
+        let fn_val:$fn_type = item;
         let ptr = fn_val as *const ();
         let sig = std::any::type_name_of_val(&fn_val);
         let type_id = std::any::TypeId::of::<$fn_type>();
 
-        unsafe { FuncPtr::new_with_type_id(ptr, sig, type_id) }
+        unsafe { FuncPtr::new_with_type_id(ptr, sig, type_id) }.__item(std::any::type_name_of_val(&item))
     }};
 
     // Case 2: Non-generic function with explicit type
     ($f:expr, $fn_type:ty) => {{
-        let fn_val:$fn_type = $f;
+        // The item is named before its type is coerced, inferring its generic arguments.
+        let (fn_val, item): ($fn_type, &'static str) = {
+            let item = $f;
+            (item, std::any::type_name_of_val(&item))
+        };
         let ptr = fn_val as *const ();
         let sig = std::any::type_name_of_val(&fn_val);
         let type_id = std::any::TypeId::of::<$fn_type>();
 
-        unsafe { FuncPtr::new_with_type_id(ptr, sig, type_id) }
+        unsafe { FuncPtr::new_with_type_id(ptr, sig, type_id) }.__item(item)
+    }};
+
+    // Simplified fn syntax starting with a keyword, which cannot be parsed as a path
+    (fn $($tt:tt)*) => {{
+        $crate::__func_checked!(fn $($tt)*)
+    }};
+
+    (unsafe $($tt:tt)*) => {{
+        $crate::__func_checked!(unsafe $($tt)*)
+    }};
+
+    (extern $($tt:tt)*) => {{
+        $crate::__func_checked!(extern $($tt)*)
+    }};
+
+    // Case 3: A function or inherent method alone — take the signature from it, checked by
+    // type name only
+    ($f:path) => {{
+        $crate::method!($f)
     }};
 
     // All simplified fn syntax patterns: delegate to proc macro for
@@ -65,7 +97,7 @@ macro_rules! func_unchecked {
         let fn_val = $f::<$($gen),*>;
         let ptr = fn_val as *const ();
 
-        FuncPtr::new(ptr, "")
+        FuncPtr::new(ptr, "").__item(std::any::type_name_of_val(&fn_val))
     }};
 
     // Case 2: Non-generic function
@@ -73,7 +105,7 @@ macro_rules! func_unchecked {
         let fn_val = $f;
         let ptr = fn_val as *const ();
 
-        FuncPtr::new(ptr, "")
+        FuncPtr::new(ptr, "").__item(std::any::type_name_of_val(&fn_val))
     }};
 
     // Case 3: Simplified fn syntax with return — skips lifetime check
@@ -87,6 +119,120 @@ macro_rules! func_unchecked {
     }};
 }
 
+/// Converts a method, such as `<Type as Trait>::method`, to a `FuncPtr`.
+///
+/// Unlike `func!`, the signature is not written out: it is taken from the method itself, so
+/// trait methods, including methods of generic traits, need no function pointer casts.
+/// Generic methods need their type parameters, as in `<Type as Trait>::method::<u32>`.
+///
+/// Operator and formatting impls, such as `<Money as Add>::add` or
+/// `<Money as std::fmt::Display>::fmt`, are resolved the same way, with their associated
+/// output types. Methods with `#[track_caller]`, such as `Index::index`, cannot be faked: a
+/// pointer to them leads to a shim passing the caller location, not to the method itself.
+///
+/// # Example
+///
+/// ```rust
+/// use injectorpp::interface::injector::*;
+///
+/// trait HttpSend {
+///     fn send(&self, body: &str) -> u16;
+/// }
+///
+/// struct Client;
+///
+/// impl HttpSend for Client {
+///     #[inline(never)]
+///     fn send(&self, body: &str) -> u16 {
+///         std::hint::black_box(body.len() as u16)
+///     }
+/// }
+///
+/// fn fake_send(_: &Client, _: &str) -> u16 {
+///     503
+/// }
+///
+/// let mut injector = InjectorPP::new();
+/// injector
+///     .when_called(injectorpp::method!(<Client as HttpSend>::send))
+///     .will_execute_raw(injectorpp::func!(fn (fake_send)(&Client, &str) -> u16));
+///
+/// assert_eq!(Client.send("ping"), 503);
+/// ```
+#[macro_export]
+macro_rules! method {
+    ($($path:tt)+) => {{
+        let fn_val = $($path)+;
+        let sig = $crate::interface::injector::__signature_of_val(&fn_val);
+        let func = unsafe { FuncPtr::new(fn_val as *const (), sig) }
+            .__item(std::any::type_name_of_val(&fn_val));
+
+        $crate::interface::injector::__returning_of_val(func, &fn_val)
+    }};
+}
+
+/// Converts a method of a trait object to a `FuncPtr` for the method of its concrete type,
+/// which does not need to be known, e.g. to fake the `Storage` behind a `Box<dyn Storage>`.
+///
+/// The method is given by its index among the methods of the trait callable on trait
+/// objects, in declaration order and after those of the supertrait. The signature excludes
+/// the receiver, which the fake takes as `*const ()`. Faking the method affects every
+/// object of the same concrete type.
+///
+/// # Safety
+///
+/// This macro must be used in an `unsafe` block. Trait object and vtable layouts are not
+/// specified by Rust, so it relies on the layout used by rustc, where the vtable lists
+/// methods in declaration order. Traits with several supertraits may have further vtable
+/// entries. The index must be a method of the trait and the signature must match it.
+///
+/// # Example
+///
+/// ```rust
+/// use injectorpp::interface::injector::*;
+///
+/// trait Storage {
+///     fn read(&self, key: &str) -> Option<String>;
+///     fn is_ready(&self) -> bool;
+/// }
+///
+/// struct Disk;
+///
+/// impl Storage for Disk {
+///     fn read(&self, _key: &str) -> Option<String> {
+///         None
+///     }
+///
+///     #[inline(never)]
+///     fn is_ready(&self) -> bool {
+///         std::hint::black_box(false)
+///     }
+/// }
+///
+/// let storage: Box<dyn Storage> = Box::new(Disk);
+///
+/// let mut injector = InjectorPP::new();
+/// injector
+///     .when_called(unsafe { injectorpp::dyn_func!(storage, 1, fn() -> bool) })
+///     .will_return_boolean(true);
+///
+/// assert!(storage.is_ready());
+/// ```
+#[macro_export]
+macro_rules! dyn_func {
+    ($object:expr, $index:expr, fn($($arg_ty:ty),*) -> $ret:ty) => {{
+        let ptr = $crate::interface::injector::__dyn_method(&*$object, $index);
+        let sig = std::any::type_name::<fn(*const (), $($arg_ty),*) -> $ret>();
+        let type_id = std::any::TypeId::of::<fn(*const (), $($arg_ty),*) -> $ret>();
+
+        FuncPtr::new_with_type_id(ptr, sig, type_id).__returning::<$ret>("Rust")
+    }};
+
+    ($object:expr, $index:expr, fn($($arg_ty:ty),*)) => {{
+        $crate::dyn_func!($object, $index, fn($($arg_ty),*) -> ())
+    }};
+}
+
 /// Converts a closure to a `FuncPtr`.
 ///
 /// This macro allows you to use Rust closures as mock implementations in injectorpp
@@ -132,6 +278,98 @@ macro_rules! closure_unchecked {
     }};
 }
 
+/// Converts a capturing closure to a `BoxedClosure` for `will_execute_closure`.
+///
+/// Unlike `closure!`, the closure may capture its environment by value and may mutate
+/// it. It must be `Send` and `'static`. Argument types are inferred from the signature.
+///
+/// Up to eight closures created by the same `boxed_closure!` call site, such as one in a
+/// helper function, can be installed on a thread at a time.
+///
+/// # Parameters
+///
+/// - `$closure`: The closure to convert
+/// - `$fn_type`: The function type signature that the closure conforms to
+///
+/// # Example
+///
+/// ```rust
+/// use injectorpp::interface::injector::*;
+/// use std::collections::HashMap;
+///
+/// fn lookup(key: &str) -> Option<String> {
+///     None
+/// }
+///
+/// let mut responses = HashMap::new();
+/// responses.insert("a".to_string(), "1".to_string());
+///
+/// let mut injector = InjectorPP::new();
+/// injector
+///     .when_called(injectorpp::func!(fn (lookup)(&str) -> Option<String>))
+///     .will_execute_closure(injectorpp::boxed_closure!(
+///         move |key| responses.remove(key),
+///         fn(&str) -> Option<String>
+///     ));
+///
+/// assert_eq!(lookup("a"), Some("1".to_string()));
+/// assert_eq!(lookup("a"), None);
+/// ```
+#[macro_export]
+macro_rules! boxed_closure {
+    ($closure:expr, fn($($arg_ty:ty),*)) => {
+        $crate::boxed_closure!($closure, fn($($arg_ty),*) -> ())
+    };
+
+    ($closure:expr, fn($($arg_ty:ty),*) -> $ret:ty) => {
+        $crate::__boxed_closure_shim!(
+            $closure; $ret; [] [$($arg_ty),*]
+            [a0 a1 a2 a3 a4 a5 a6 a7 a8 a9 a10 a11 a12 a13 a14 a15]
+        )
+    };
+}
+
+/// Names the arguments of the shim generated by `boxed_closure!`. Used internally by macros.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __boxed_closure_shim {
+    ($closure:expr; $ret:ty; [$($name:ident: $ty:ty),*] [$next_ty:ty $(, $rest_ty:ty)*] [$next_name:ident $($pool:ident)*]) => {
+        $crate::__boxed_closure_shim!(
+            $closure; $ret; [$($name: $ty,)* $next_name: $next_ty] [$($rest_ty),*] [$($pool)*]
+        )
+    };
+
+    ($closure:expr; $ret:ty; [$($name:ident: $ty:ty),*] [] [$($pool:ident)*]) => {{
+        type Boxed = Box<dyn FnMut($($ty),*) -> $ret + Send>;
+
+        fn shim<const SLOT: usize>($($name: $ty),*) -> $ret {
+            $crate::interface::injector::__call_boxed_closure(shim::<SLOT> as *const (), |f: &mut Boxed| {
+                f($($name),*)
+            })
+        }
+
+        let closure: Boxed = Box::new($closure);
+        let shims: [fn($($ty),*) -> $ret; 8] = [
+            shim::<0>, shim::<1>, shim::<2>, shim::<3>, shim::<4>, shim::<5>, shim::<6>, shim::<7>,
+        ];
+        let sig = std::any::type_name_of_val(&shims[0]);
+        let type_id = $crate::interface::injector::__type_id_of_val(&shims[0]);
+
+        unsafe {
+            BoxedClosure::__new(
+                shims
+                    .iter()
+                    .map(|&f| {
+                        FuncPtr::new_with_type_id(f as *const (), sig, type_id)
+                            .__returning::<$ret>("Rust")
+                    })
+                    .collect(),
+                Box::new(closure),
+            )
+        }
+    }};
+}
+
 #[doc(hidden)]
 pub fn __assert_future_output<Fut, T>(_: &mut Fut)
 where
@@ -139,15 +377,368 @@ where
 {
 }
 
+/// Returns whether a poll function generated by `async_return_after!` should still return
+/// `Poll::Pending` for the future at `fut`, waking the task if so. Used internally by macros.
+#[doc(hidden)]
+pub fn __poll_pending(
+    poll_fn: *const (),
+    fut: *const (),
+    pending: usize,
+    cx: &mut std::task::Context<'_>,
+) -> bool {
+    use std::collections::HashMap;
+    use std::sync::{LazyLock, Mutex};
+
+    // Number of times each future has returned `Poll::Pending` so far.
+    static POLLS: LazyLock<Mutex<HashMap<(usize, usize), usize>>> =
+        LazyLock::new(|| Mutex::new(HashMap::new()));
+
+    let key = (poll_fn as usize, fut as usize);
+    let mut polls = POLLS.lock().unwrap_or_else(|e| e.into_inner());
+    let polled = polls.entry(key).or_insert(0);
+    if *polled < pending {
+        *polled += 1;
+        drop(polls);
+        cx.waker().wake_by_ref();
+        return true;
+    }
+
+    polls.remove(&key);
+    false
+}
+
+#[doc(hidden)]
+#[cfg(feature = "stream")]
+pub fn __assert_stream_item<S, T>(_: &mut S)
+where
+    S: futures_core::Stream<Item = T>,
+{
+}
+
+/// Returns the index of the next item a `poll_next` generated by `stream_return!` should yield
+/// for the stream at `stream`, or `None` once all `len` items were yielded. Used internally by
+/// macros.
+#[doc(hidden)]
+#[cfg(feature = "stream")]
+pub fn __stream_next_index(poll_next: *const (), stream: *const (), len: usize) -> Option<usize> {
+    use std::collections::HashMap;
+    use std::sync::{LazyLock, Mutex};
+
+    // Number of items each stream has yielded so far.
+    static YIELDED: LazyLock<Mutex<HashMap<(usize, usize), usize>>> =
+        LazyLock::new(|| Mutex::new(HashMap::new()));
+
+    let key = (poll_next as usize, stream as usize);
+    let mut yielded = YIELDED.lock().unwrap_or_else(|e| e.into_inner());
+    let index = yielded.entry(key).or_insert(0);
+    if *index < len {
+        *index += 1;
+        return Some(*index - 1);
+    }
+
+    yielded.remove(&key);
+    None
+}
+
 /// Helper to extract TypeId from a value's type. Used internally by macros.
 #[doc(hidden)]
 pub fn __type_id_of_val<T: 'static>(_: &T) -> std::any::TypeId {
     std::any::TypeId::of::<T>()
 }
 
+/// The signature of a function, implemented for every function with up to 12 arguments.
+/// Used internally by `method!`.
+#[doc(hidden)]
+pub trait __FnSignature<Marker> {
+    fn signature() -> &'static str;
+
+    fn returning(func: FuncPtr) -> FuncPtr;
+}
+
+macro_rules! fn_signature {
+    ($($ty:ident),*) => {
+        impl<F, R, $($ty),*> __FnSignature<fn($($ty),*) -> R> for F
+        where
+            F: Fn($($ty),*) -> R,
+        {
+            fn signature() -> &'static str {
+                // The signature of a method taking references has no type id, as the
+                // lifetimes are inferred here, so only the signature text is checked.
+                std::any::type_name::<fn($($ty),*) -> R>()
+            }
+
+            fn returning(func: FuncPtr) -> FuncPtr {
+                func.__returning::<R>("Rust")
+            }
+        }
+    };
+}
+
+fn_signature!();
+fn_signature!(A0);
+fn_signature!(A0, A1);
+fn_signature!(A0, A1, A2);
+fn_signature!(A0, A1, A2, A3);
+fn_signature!(A0, A1, A2, A3, A4);
+fn_signature!(A0, A1, A2, A3, A4, A5);
+fn_signature!(A0, A1, A2, A3, A4, A5, A6);
+fn_signature!(A0, A1, A2, A3, A4, A5, A6, A7);
+fn_signature!(A0, A1, A2, A3, A4, A5, A6, A7, A8);
+fn_signature!(A0, A1, A2, A3, A4, A5, A6, A7, A8, A9);
+fn_signature!(A0, A1, A2, A3, A4, A5, A6, A7, A8, A9, A10);
+fn_signature!(A0, A1, A2, A3, A4, A5, A6, A7, A8, A9, A10, A11);
+
+/// Helper to get the signature of a function from its value. Used internally by macros.
+#[doc(hidden)]
+pub fn __signature_of_val<F: __FnSignature<Marker>, Marker>(_: &F) -> &'static str {
+    F::signature()
+}
+
+/// Helper to record the return type of a function from its value. Used internally by macros.
+#[doc(hidden)]
+pub fn __returning_of_val<F: __FnSignature<Marker>, Marker>(func: FuncPtr, _: &F) -> FuncPtr {
+    F::returning(func)
+}
+
+/// Records an invocation of the fake `$fake` generated by `fake!`, and marks it running until
+/// it returns. Used internally by macros.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __fake_called {
+    ($fake:ident) => {
+        let __injectorpp_running = $crate::interface::injector::__fake_entered($fake as *const ());
+        {
+            static __INJECTORPP_CALLS: std::sync::atomic::AtomicUsize =
+                std::sync::atomic::AtomicUsize::new(0);
+            let __call_index = __INJECTORPP_CALLS.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            $crate::interface::injector::__fake_invoked(file!(), line!(), column!(), __call_index);
+        }
+    };
+}
+
+/// Reports an invocation of a fake to the diagnostics sinks. Used internally by macros.
+#[doc(hidden)]
+pub fn __fake_invoked(file: &'static str, line: u32, column: u32, call_index: usize) {
+    crate::injector_core::diagnostics::fake_invoked(file, line, column, call_index);
+}
+
+/// Panics from a fake generated by `fake!`. The panic message is formatted, and the panic
+/// raised, with the fakes of the current thread out of the way, so a fake of `malloc` or of a
+/// `GlobalAlloc` can panic. Used internally by macros.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __fake_panic {
+    ($($arg:tt)*) => {{
+        let __injectorpp_internal = $crate::interface::injector::__enter_internal();
+        panic!($($arg)*)
+    }};
+}
+
+/// Keeps the fakes of the current thread out of the way, see `__enter_internal()`. Used
+/// internally by macros.
+#[doc(hidden)]
+pub struct __InternalScope {
+    #[cfg(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec",
+        target_arch = "arm"
+    ))]
+    _scope: crate::injector_core::thread_local_registry::InternalScope,
+}
+
+/// Sends the calls of the functions faked on the current thread to the original functions
+/// until the returned value is dropped, for code of injectorpp running while a fake of an
+/// allocation function is installed. Used internally by macros.
+#[doc(hidden)]
+pub fn __enter_internal() -> __InternalScope {
+    __InternalScope {
+        #[cfg(any(
+            target_arch = "x86_64",
+            target_arch = "aarch64",
+            target_arch = "arm64ec",
+            target_arch = "arm"
+        ))]
+        _scope: crate::injector_core::thread_local_registry::enter_internal(),
+    }
+}
+
+/// Keeps a fake marked running on the current thread, see
+/// `WhenCalledBuilder::reentrant_calls_original()`. Used internally by macros.
+#[doc(hidden)]
+pub struct __RunningFake {
+    #[cfg(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec",
+        target_arch = "arm"
+    ))]
+    _running: Option<crate::injector_core::thread_local_registry::RunningFake>,
+}
+
+/// Marks the fake at `fake` running on the current thread until the returned value is
+/// dropped. Used internally by macros.
+#[doc(hidden)]
+pub fn __fake_entered(fake: *const ()) -> __RunningFake {
+    #[cfg(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec",
+        target_arch = "arm"
+    ))]
+    {
+        crate::injector_core::thread_local_registry::log_backtrace(fake as usize);
+        __RunningFake {
+            _running: crate::injector_core::thread_local_registry::enter_fake(fake as usize),
+        }
+    }
+
+    #[cfg(not(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "arm64ec",
+        target_arch = "arm"
+    )))]
+    {
+        let _ = fake;
+        __RunningFake {}
+    }
+}
+
+/// Returns the name of the injector a fake belongs to, formatted for panic messages.
+/// Used internally by macros.
+#[doc(hidden)]
+pub fn __fake_label(fake: *const ()) -> String {
+    crate::interface::labels::fake_suffix(fake as usize)
+}
+
+/// Delays a fake declared with `delay:`, on the virtual clock of a `TokioTimeMocker` if one is
+/// installed. Used internally by `fake!`.
+#[doc(hidden)]
+pub fn __fake_delay(delay: std::time::Duration) {
+    crate::injector_core::delay::sleep(delay)
+}
+
+/// Returns whether a poll function generated by `async_return!` with `delay:` should still
+/// return `Poll::Pending` for the future at `fut`. Used internally by macros.
+#[doc(hidden)]
+pub fn __poll_delay(
+    poll_fn: *const (),
+    fut: *const (),
+    delay: std::time::Duration,
+    cx: &mut std::task::Context<'_>,
+) -> bool {
+    crate::injector_core::delay::poll(poll_fn as usize, fut as usize, delay, cx)
+}
+
+/// Sets `errno` of the calling thread. Used internally by `fake!`.
+#[doc(hidden)]
+pub fn __set_errno(code: i32) {
+    #[cfg(target_os = "linux")]
+    unsafe {
+        *libc::__errno_location() = code;
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    unsafe {
+        *libc::__error() = code;
+    }
+
+    #[cfg(target_os = "netbsd")]
+    unsafe {
+        *libc::__errno() = code;
+    }
+
+    #[cfg(target_os = "windows")]
+    unsafe {
+        *crate::injector_core::winapi::_errno() = code;
+    }
+}
+
+/// Sets the last error of the calling thread, as returned by `GetLastError`. Used internally
+/// by `fake!`.
+#[cfg(target_os = "windows")]
+#[doc(hidden)]
+pub fn __set_last_error(code: u32) {
+    unsafe { crate::injector_core::winapi::SetLastError(code) }
+}
+
+/// Wraps a value so that it can be described with `Debug` when available.
+/// Used internally by `fake!` to report `when:` mismatches.
+#[doc(hidden)]
+pub struct __DebugProbe<'a, T: ?Sized>(pub &'a T);
+
+#[doc(hidden)]
+pub trait __ProbeDebug {
+    fn __injectorpp_describe(&self) -> String;
+}
+
+impl<T: std::fmt::Debug + ?Sized> __ProbeDebug for __DebugProbe<'_, T> {
+    fn __injectorpp_describe(&self) -> String {
+        format!("{:?}", self.0)
+    }
+}
+
+#[doc(hidden)]
+pub trait __ProbeFallback {
+    fn __injectorpp_describe(&self) -> String;
+}
+
+impl<T: ?Sized> __ProbeFallback for &__DebugProbe<'_, T> {
+    fn __injectorpp_describe(&self) -> String {
+        format!("<{} does not implement Debug>", std::any::type_name::<T>())
+    }
+}
+
 /// Ensure the async function can be correctly used in injectorpp.
+///
+/// Takes either an expression creating the future and its output type, or a function
+/// returning the future with its argument and output types, in the form of `func!`. The
+/// second form never calls the function, so async methods can be targeted without an
+/// instance. It also accepts non-capturing closures returning an async block and functions
+/// returning `impl Future`.
+///
+/// # Example
+///
+/// ```rust
+/// use injectorpp::interface::injector::*;
+///
+/// struct Client {
+///     endpoint: String,
+/// }
+///
+/// impl Client {
+///     async fn get(&self, path: &str) -> String {
+///         format!("{}{}", self.endpoint, path)
+///     }
+/// }
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let mut injector = InjectorPP::new();
+///     injector
+///         .when_called_async(injectorpp::async_func!(fn (Client::get)(&Client, &str) -> String))
+///         .will_return_async(injectorpp::async_return!("fake".to_string(), String));
+///
+///     let client = Client { endpoint: "https://example.com".to_string() };
+///     assert_eq!(client.get("/").await, "fake");
+/// }
+/// ```
 #[macro_export]
 macro_rules! async_func {
+    (fn ( $f:expr ) ( $($arg_ty:ty),* ) -> $ret:ty) => {{
+        let target = $f as fn($($crate::__infer_type!($arg_ty)),*) -> _;
+        let sig = std::any::type_name::<fn() -> std::task::Poll<$ret>>();
+        $crate::interface::injector::__async_target::<_, _, $ret>(
+            move || target($($crate::interface::injector::__never::<$arg_ty>()),*),
+            sig,
+        )
+    }};
+
+    (fn ( $f:expr ) ( $($arg_ty:ty),* )) => {{
+        $crate::async_func!(fn ($f)($($arg_ty),*) -> ())
+    }};
+
     ($expr:expr, $ty:ty) => {{
         let mut __fut = $expr;
 
@@ -171,8 +762,127 @@ macro_rules! async_func_unchecked {
 }
 
 /// Config a return value for faking an async function.
+///
+/// The value expression is evaluated every time the faked future is polled, so values
+/// that are not `Clone` can be returned more than once. A non-capturing closure can be
+/// given instead of a value to run arbitrary code on every call:
+///
+/// ```rust
+/// use injectorpp::interface::injector::*;
+///
+/// async fn open_session() -> std::io::Result<String> {
+///     Err(std::io::Error::other("offline"))
+/// }
+///
+/// fn make_session() -> std::io::Result<String> {
+///     Ok("session".to_string())
+/// }
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let mut injector = InjectorPP::new();
+///     injector
+///         .when_called_async(injectorpp::async_func!(open_session(), std::io::Result<String>))
+///         .will_return_async(injectorpp::async_return!(|| make_session(), std::io::Result<String>));
+///
+///     assert_eq!(open_session().await.unwrap(), "session");
+///     assert_eq!(open_session().await.unwrap(), "session");
+/// }
+/// ```
+///
+/// Adding `times: n` verifies the faked async function is awaited exactly `n` times. The
+/// macro then returns the fake and its verifier, to pass to `will_return_async_times`:
+///
+/// ```rust
+/// use injectorpp::interface::injector::*;
+///
+/// async fn refresh_token() -> bool {
+///     false
+/// }
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let mut injector = InjectorPP::new();
+///     injector
+///         .when_called_async(injectorpp::async_func!(refresh_token(), bool))
+///         .will_return_async_times(injectorpp::async_return!(true, bool, times: 2));
+///
+///     assert!(refresh_token().await);
+///     assert!(refresh_token().await);
+/// }
+/// ```
+///
+/// Adding `delay: duration` resolves the future once the delay is over, to simulate a slow
+/// dependency when testing timeouts and cancellation. The delay is evaluated inside the
+/// generated poll function, so it cannot refer to local variables. While a `TokioTimeMocker`
+/// is installed, the delay elapses on its virtual clock: a `tokio::time::timeout` around the
+/// future elapses if it is shorter than the delay, without waiting:
+///
+/// ```rust
+/// use injectorpp::interface::injector::*;
+/// use std::time::Duration;
+///
+/// async fn fetch() -> u32 {
+///     1
+/// }
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let mut injector = InjectorPP::new();
+///     injector
+///         .when_called_async(injectorpp::async_func!(fetch(), u32))
+///         .will_return_async(injectorpp::async_return!(42, u32, delay: Duration::from_millis(20)));
+///
+///     let started = std::time::Instant::now();
+///     assert_eq!(fetch().await, 42);
+///     assert!(started.elapsed() >= Duration::from_millis(20));
+/// }
+/// ```
 #[macro_export]
 macro_rules! async_return {
+    (|| $body:expr, $ty:ty, times: $expected:expr) => {{
+        $crate::__async_return_times!({
+            let make: fn() -> $ty = || $body;
+            make()
+        }, $ty, $expected)
+    }};
+
+    (move || $body:expr, $ty:ty, times: $expected:expr) => {{
+        $crate::async_return!(|| $body, $ty, times: $expected)
+    }};
+
+    ($val:expr, $ty:ty, times: $expected:expr) => {{
+        $crate::__async_return_times!($val, $ty, $expected)
+    }};
+
+    (|| $body:expr, $ty:ty, delay: $delay:expr) => {{
+        $crate::__async_return_delayed!({
+            let make: fn() -> $ty = || $body;
+            make()
+        }, $ty, $delay)
+    }};
+
+    (move || $body:expr, $ty:ty, delay: $delay:expr) => {{
+        $crate::async_return!(|| $body, $ty, delay: $delay)
+    }};
+
+    ($val:expr, $ty:ty, delay: $delay:expr) => {{
+        $crate::__async_return_delayed!($val, $ty, $delay)
+    }};
+
+    (|| $body:expr, $ty:ty) => {{
+        fn generated_poll_fn() -> std::task::Poll<$ty> {
+            let make: fn() -> $ty = || $body;
+            std::task::Poll::Ready(make())
+        }
+
+        $crate::func!(generated_poll_fn, fn() -> std::task::Poll<$ty>)
+    }};
+
+    (move || $body:expr, $ty:ty) => {{
+        $crate::async_return!(|| $body, $ty)
+    }};
+
     ($val:expr, $ty:ty) => {{
         fn generated_poll_fn() -> std::task::Poll<$ty> {
             std::task::Poll::Ready($val)
@@ -182,13 +892,202 @@ macro_rules! async_return {
     }};
 }
 
+/// Generates a poll function that counts how many times it resolves. Used internally by
+/// `async_return!`.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __async_return_times {
+    ($val:expr, $ty:ty, $expected:expr) => {{
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        static FAKE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let verifier = $crate::interface::injector::CallCountVerifier::WithCount {
+            counter: &FAKE_COUNTER,
+            expected: $expected,
+        };
+
+        fn generated_poll_fn() -> std::task::Poll<$ty> {
+            let prev = FAKE_COUNTER.fetch_add(1, Ordering::SeqCst);
+            if prev >= $expected {
+                panic!(
+                    "Fake function defined at {}:{}:{}{} called more times than expected",
+                    file!(),
+                    line!(),
+                    column!(),
+                    $crate::interface::injector::__fake_label(generated_poll_fn as *const ())
+                );
+            }
+
+            std::task::Poll::Ready($val)
+        }
+
+        (
+            $crate::func!(generated_poll_fn, fn() -> std::task::Poll<$ty>),
+            verifier,
+        )
+    }};
+}
+
+/// Generates a poll function that resolves once its delay is over. Used internally by
+/// `async_return!`.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __async_return_delayed {
+    ($val:expr, $ty:ty, $delay:expr) => {{
+        fn generated_poll_fn(
+            fut: *const (),
+            cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<$ty> {
+            if $crate::interface::injector::__poll_delay(
+                generated_poll_fn as *const (),
+                fut,
+                $delay,
+                cx,
+            ) {
+                return std::task::Poll::Pending;
+            }
+
+            std::task::Poll::Ready($val)
+        }
+
+        // Only the output type is checked against `async_func!`, the arguments mirror `Future::poll`.
+        let f: fn(*const (), &mut std::task::Context<'_>) -> std::task::Poll<$ty> = generated_poll_fn;
+        unsafe { FuncPtr::new(f as *const (), std::any::type_name::<fn() -> std::task::Poll<$ty>>()) }
+    }};
+}
+
+/// Config a return value for faking an async function that is only ready after being polled
+/// a number of times.
+///
+/// The faked future returns `Poll::Pending` the first `n_pending` times it is polled, waking
+/// its task each time, and then resolves to the value. This exercises `select!` and timeout
+/// branches that only trigger when a future is not immediately ready. `n_pending` and the
+/// value are evaluated inside the generated poll function, so they cannot refer to local
+/// variables.
+///
+/// # Example
+///
+/// ```rust
+/// use injectorpp::interface::injector::*;
+///
+/// async fn fetch() -> u32 {
+///     1
+/// }
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let mut injector = InjectorPP::new();
+///     injector
+///         .when_called_async(injectorpp::async_func!(fetch(), u32))
+///         .will_return_async(injectorpp::async_return_after!(3, 42, u32));
+///
+///     assert_eq!(fetch().await, 42);
+/// }
+/// ```
+#[macro_export]
+macro_rules! async_return_after {
+    ($pending:expr, $val:expr, $ty:ty) => {{
+        fn generated_poll_fn(
+            fut: *const (),
+            cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<$ty> {
+            if $crate::interface::injector::__poll_pending(
+                generated_poll_fn as *const (),
+                fut,
+                $pending,
+                cx,
+            ) {
+                return std::task::Poll::Pending;
+            }
+
+            std::task::Poll::Ready($val)
+        }
+
+        // Only the output type is checked against `async_func!`, the arguments mirror `Future::poll`.
+        let f: fn(*const (), &mut std::task::Context<'_>) -> std::task::Poll<$ty> = generated_poll_fn;
+        unsafe { FuncPtr::new(f as *const (), std::any::type_name::<fn() -> std::task::Poll<$ty>>()) }
+    }};
+}
+
+/// Config a sequence of return values for faking an async function.
+///
+/// Each call of the faked async function returns the next value, so consecutive awaits can
+/// produce different outcomes, e.g. to test retry logic. Calling it more times than there are
+/// values panics. The values are evaluated inside the generated poll function, so they cannot
+/// refer to local variables, and the position in the sequence is shared by every fake created
+/// by the same macro call.
+///
+/// # Example
+///
+/// ```rust
+/// use injectorpp::interface::injector::*;
+///
+/// async fn connect() -> Result<u32, String> {
+///     Err("offline".to_string())
+/// }
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let mut injector = InjectorPP::new();
+///     injector
+///         .when_called_async(injectorpp::async_func!(connect(), Result<u32, String>))
+///         .will_return_async(injectorpp::async_return_seq!(
+///             [Err("timeout".to_string()), Ok(1)],
+///             Result<u32, String>
+///         ));
+///
+///     assert_eq!(connect().await, Err("timeout".to_string()));
+///     assert_eq!(connect().await, Ok(1));
+/// }
+/// ```
+#[macro_export]
+macro_rules! async_return_seq {
+    ([$($val:expr),+ $(,)?], $ty:ty) => {{
+        fn generated_poll_fn() -> std::task::Poll<$ty> {
+            static CALLS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+            let call = CALLS.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            let values: Vec<$ty> = vec![$($val),+];
+            let len = values.len();
+            match values.into_iter().nth(call) {
+                Some(value) => std::task::Poll::Ready(value),
+                None => panic!(
+                    "async_return_seq! defined at {}:{}:{} was called {} times but only has {} values",
+                    file!(),
+                    line!(),
+                    column!(),
+                    call + 1,
+                    len
+                ),
+            }
+        }
+
+        $crate::func!(generated_poll_fn, fn() -> std::task::Poll<$ty>)
+    }};
+}
+
 /// Config a return value for faking an async function.
 ///
+/// Like `async_return!`, accepts either a value or a non-capturing closure, both evaluated
+/// on every call.
+///
 /// # Safety
 ///
 /// This macro skips the signature check and assumes the caller knows what they are doing.
 #[macro_export]
 macro_rules! async_return_unchecked {
+    (|| $body:expr, $ty:ty) => {{
+        fn generated_poll_fn() -> std::task::Poll<$ty> {
+            let make: fn() -> $ty = || $body;
+            std::task::Poll::Ready(make())
+        }
+
+        $crate::func_unchecked!(generated_poll_fn)
+    }};
+
+    (move || $body:expr, $ty:ty) => {{
+        $crate::async_return_unchecked!(|| $body, $ty)
+    }};
+
     ($val:expr, $ty:ty) => {{
         fn generated_poll_fn() -> std::task::Poll<$ty> {
             std::task::Poll::Ready($val)
@@ -198,6 +1097,528 @@ macro_rules! async_return_unchecked {
     }};
 }
 
+/// Ensure the stream can be correctly used in injectorpp.
+///
+/// Takes an expression creating the stream and the type of its items. Requires the `stream`
+/// feature.
+#[cfg(feature = "stream")]
+#[macro_export]
+macro_rules! stream_func {
+    ($expr:expr, $ty:ty) => {{
+        let mut __stream = $expr;
+
+        let _ = __assert_stream_item::<_, $ty>(&mut __stream);
+
+        let sig = std::any::type_name::<fn() -> std::task::Poll<Option<$ty>>>();
+        (std::pin::pin!(__stream), sig)
+    }};
+}
+
+/// Config the items yielded by a faked stream.
+///
+/// Each stream yields the items in order and then `None`. A stream polled again after `None`
+/// starts over. The items are evaluated inside the
+/// generated `poll_next`, so they cannot refer to local variables. Requires the `stream`
+/// feature.
+///
+/// # Example
+///
+/// ```rust
+/// use injectorpp::interface::injector::*;
+/// use std::pin::Pin;
+/// use std::task::{Context, Poll};
+///
+/// struct Pages;
+///
+/// impl futures_core::Stream for Pages {
+///     type Item = u32;
+///
+///     fn poll_next(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<u32>> {
+///         Poll::Ready(None)
+///     }
+/// }
+///
+/// #[tokio::main]
+/// async fn main() {
+///     use futures::StreamExt;
+///
+///     let mut injector = InjectorPP::new();
+///     injector
+///         .when_called_stream(injectorpp::stream_func!(Pages, u32))
+///         .will_return_stream(injectorpp::stream_return!([1, 2, 3], u32));
+///
+///     assert_eq!(Pages.collect::<Vec<_>>().await, vec![1, 2, 3]);
+/// }
+/// ```
+#[cfg(feature = "stream")]
+#[macro_export]
+macro_rules! stream_return {
+    ([$($item:expr),* $(,)?], $ty:ty) => {{
+        fn generated_poll_next(
+            stream: *const (),
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<Option<$ty>> {
+            let items: Vec<$ty> = vec![$($item),*];
+            let index = $crate::interface::injector::__stream_next_index(
+                generated_poll_next as *const (),
+                stream,
+                items.len(),
+            );
+
+            std::task::Poll::Ready(index.and_then(|i| items.into_iter().nth(i)))
+        }
+
+        // Only the item type is checked against `stream_func!`, the arguments mirror `Stream::poll_next`.
+        let f: fn(*const (), &mut std::task::Context<'_>) -> std::task::Poll<Option<$ty>> =
+            generated_poll_next;
+        unsafe { FuncPtr::new(f as *const (), std::any::type_name::<fn() -> std::task::Poll<Option<$ty>>>()) }
+    }};
+}
+
+/// Creates a fake for a function returning a boxed future, such as
+/// `Pin<Box<dyn Future<Output = T> + Send + '_>>`.
+///
+/// Many async traits return boxed futures instead of `async fn` futures, so the function can
+/// be faked like any other function with `when_called` and `will_execute`. The `returns`
+/// expression is a future, it is evaluated and boxed on every call and may use the arguments.
+/// `when` and `times` behave as in `fake!`.
+///
+/// # Example
+///
+/// ```rust
+/// use injectorpp::interface::injector::*;
+/// use std::future::Future;
+/// use std::pin::Pin;
+///
+/// struct Client;
+///
+/// impl Client {
+///     fn get(&self, path: String) -> Pin<Box<dyn Future<Output = String> + Send + '_>> {
+///         Box::pin(async move { format!("GET {path}") })
+///     }
+/// }
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let mut injector = InjectorPP::new();
+///     injector
+///         .when_called(injectorpp::func!(
+///             fn (Client::get)(&Client, String) -> Pin<Box<dyn Future<Output = String> + Send + '_>>
+///         ))
+///         .will_execute(injectorpp::boxed_future!(
+///             func_type: fn(_client: &Client, path: String) -> Pin<Box<dyn Future<Output = String> + Send + '_>>,
+///             returns: async move { format!("fake {path}") },
+///             times: 1
+///         ));
+///
+///     assert_eq!(Client.get("/a".to_string()).await, "fake /a");
+/// }
+/// ```
+#[macro_export]
+macro_rules! boxed_future {
+    (
+        func_type: fn($($arg_name:ident: $arg_ty:ty),*) -> $ret:ty,
+        when: $cond:expr,
+        returns: $fut:expr,
+        times: $expected:expr
+    ) => {
+        $crate::fake!(
+            func_type: fn($($arg_name: $arg_ty),*) -> $ret,
+            when: $cond,
+            returns: Box::pin($fut),
+            times: $expected
+        )
+    };
+
+    (
+        func_type: fn($($arg_name:ident: $arg_ty:ty),*) -> $ret:ty,
+        when: $cond:expr,
+        returns: $fut:expr
+    ) => {
+        $crate::fake!(
+            func_type: fn($($arg_name: $arg_ty),*) -> $ret,
+            when: $cond,
+            returns: Box::pin($fut)
+        )
+    };
+
+    (
+        func_type: fn($($arg_name:ident: $arg_ty:ty),*) -> $ret:ty,
+        returns: $fut:expr,
+        times: $expected:expr
+    ) => {
+        $crate::fake!(
+            func_type: fn($($arg_name: $arg_ty),*) -> $ret,
+            returns: Box::pin($fut),
+            times: $expected
+        )
+    };
+
+    (
+        func_type: fn($($arg_name:ident: $arg_ty:ty),*) -> $ret:ty,
+        returns: $fut:expr
+    ) => {
+        $crate::fake!(
+            func_type: fn($($arg_name: $arg_ty),*) -> $ret,
+            returns: Box::pin($fut)
+        )
+    };
+}
+
+/// Converts a method generated by `#[async_trait]` to a `FuncPtr`.
+///
+/// `async_trait` turns `async fn read(&self, key: &str) -> Option<String>` into a function
+/// returning `Pin<Box<dyn Future<Output = Option<String>> + Send + 'async_trait>>`, with
+/// lifetimes that cannot be written in a function pointer type. This macro takes the method as
+/// it is written in the trait, with its argument types and output type, and checks it returns
+/// a future of that output. Prefix the function with `?Send` for `#[async_trait(?Send)]`.
+///
+/// Use it with `when_called` and fake the method with `async_trait_fake!`.
+///
+/// # Example
+///
+/// ```rust
+/// use injectorpp::interface::injector::*;
+///
+/// #[async_trait::async_trait]
+/// trait Storage {
+///     async fn read(&self, key: &str) -> Option<String>;
+/// }
+///
+/// struct Disk;
+///
+/// #[async_trait::async_trait]
+/// impl Storage for Disk {
+///     async fn read(&self, key: &str) -> Option<String> {
+///         None
+///     }
+/// }
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let mut injector = InjectorPP::new();
+///     injector
+///         .when_called(injectorpp::async_trait_func!(
+///             fn (<Disk as Storage>::read)(&Disk, &str) -> Option<String>
+///         ))
+///         .will_execute(injectorpp::async_trait_fake!(
+///             func_type: fn(_disk: &Disk, key: &str) -> Option<String>,
+///             returns: async move { Some(format!("cached {key}")) }
+///         ));
+///
+///     assert_eq!(Disk.read("a").await, Some("cached a".to_string()));
+/// }
+/// ```
+#[macro_export]
+macro_rules! async_trait_func {
+    (?Send fn ( $f:expr ) ( $($arg_ty:ty),* ) -> $ret:ty) => {{
+        $crate::__async_trait_func!(fn ($f)($($arg_ty),*) -> $ret; [])
+    }};
+
+    (?Send fn ( $f:expr ) ( $($arg_ty:ty),* )) => {{
+        $crate::async_trait_func!(?Send fn ($f)($($arg_ty),*) -> ())
+    }};
+
+    (fn ( $f:expr ) ( $($arg_ty:ty),* ) -> $ret:ty) => {{
+        $crate::__async_trait_func!(fn ($f)($($arg_ty),*) -> $ret; [+ Send])
+    }};
+
+    (fn ( $f:expr ) ( $($arg_ty:ty),* )) => {{
+        $crate::async_trait_func!(fn ($f)($($arg_ty),*) -> ())
+    }};
+}
+
+/// Implements `async_trait_func!`.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __async_trait_func {
+    (fn ( $f:expr ) ( $($arg_ty:ty),* ) -> $ret:ty; [$($send:tt)*]) => {{
+        let target = $f as fn($($crate::__infer_type!($arg_ty)),*) -> _;
+        // Lifetimes do not exist at runtime, so the signature is spelled with `'static` like
+        // the fakes generated by `async_trait_fake!`.
+        let sig = std::any::type_name::<fn($($arg_ty),*) -> std::pin::Pin<Box<dyn std::future::Future<Output = $ret> $($send)*>>>();
+        unsafe {
+            $crate::interface::injector::__async_trait_target::<_, _, $ret>(
+                target as *const (),
+                move || target($($crate::interface::injector::__never::<$arg_ty>()),*),
+                sig,
+            )
+        }
+    }};
+}
+
+/// Creates a fake for a method generated by `#[async_trait]`.
+///
+/// The options are those of `fake!`, except `assign`: `func_type` is the method as it is
+/// written in the trait, `returns` is a future of its output, evaluated and boxed on every
+/// call, and `when` and `times` are optional. Prefix with `?Send` for
+/// `#[async_trait(?Send)]`. See `async_trait_func!` for an example.
+///
+/// The returned future may borrow the arguments, as the real method does.
+#[macro_export]
+macro_rules! async_trait_fake {
+    (?Send func_type: fn($($arg_name:ident: $arg_ty:ty),*) -> $ret:ty, $($rest:tt)*) => {
+        $crate::__async_trait_fake!(
+            fn($($arg_name: $arg_ty),*) -> $ret;
+            [];
+            $($rest)*
+        )
+    };
+
+    (?Send func_type: fn($($arg_name:ident: $arg_ty:ty),*), $($rest:tt)*) => {
+        $crate::async_trait_fake!(?Send func_type: fn($($arg_name: $arg_ty),*) -> (), $($rest)*)
+    };
+
+    (func_type: fn($($arg_name:ident: $arg_ty:ty),*) -> $ret:ty, $($rest:tt)*) => {
+        $crate::__async_trait_fake!(
+            fn($($arg_name: $arg_ty),*) -> $ret;
+            [+ Send];
+            $($rest)*
+        )
+    };
+
+    (func_type: fn($($arg_name:ident: $arg_ty:ty),*), $($rest:tt)*) => {
+        $crate::async_trait_fake!(func_type: fn($($arg_name: $arg_ty),*) -> (), $($rest)*)
+    };
+}
+
+/// Implements `async_trait_fake!`.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __async_trait_fake {
+    (fn($($arg_name:ident: $arg_ty:ty),*) -> $ret:ty; [$($send:tt)*]; when: $cond:expr, returns: $val:expr, times: $expected:expr) => {
+        $crate::fake!(
+            func_type: fn($($arg_name: $arg_ty),*) -> std::pin::Pin<Box<dyn std::future::Future<Output = $ret> $($send)*>>,
+            when: $cond,
+            returns: $crate::__async_trait_box!($val; $ret; [$($send)*]),
+            times: $expected
+        )
+    };
+
+    (fn($($arg_name:ident: $arg_ty:ty),*) -> $ret:ty; [$($send:tt)*]; when: $cond:expr, returns: $val:expr) => {
+        $crate::fake!(
+            func_type: fn($($arg_name: $arg_ty),*) -> std::pin::Pin<Box<dyn std::future::Future<Output = $ret> $($send)*>>,
+            when: $cond,
+            returns: $crate::__async_trait_box!($val; $ret; [$($send)*])
+        )
+    };
+
+    (fn($($arg_name:ident: $arg_ty:ty),*) -> $ret:ty; [$($send:tt)*]; returns: $val:expr, times: $expected:expr) => {
+        $crate::fake!(
+            func_type: fn($($arg_name: $arg_ty),*) -> std::pin::Pin<Box<dyn std::future::Future<Output = $ret> $($send)*>>,
+            returns: $crate::__async_trait_box!($val; $ret; [$($send)*]),
+            times: $expected
+        )
+    };
+
+    (fn($($arg_name:ident: $arg_ty:ty),*) -> $ret:ty; [$($send:tt)*]; returns: $val:expr) => {
+        $crate::fake!(
+            func_type: fn($($arg_name: $arg_ty),*) -> std::pin::Pin<Box<dyn std::future::Future<Output = $ret> $($send)*>>,
+            returns: $crate::__async_trait_box!($val; $ret; [$($send)*])
+        )
+    };
+}
+
+/// Boxes a future borrowing the arguments of a fake generated by `async_trait_fake!`.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __async_trait_box {
+    ($val:expr; $ret:ty; [$($send:tt)*]) => {{
+        let fut: std::pin::Pin<Box<dyn std::future::Future<Output = $ret> $($send)* + '_>> =
+            Box::pin($val);
+        // The caller of an `async_trait` method keeps its arguments alive until the future
+        // completes, so the borrow can be extended like the real method does.
+        unsafe {
+            std::mem::transmute::<
+                std::pin::Pin<Box<dyn std::future::Future<Output = $ret> $($send)* + '_>>,
+                std::pin::Pin<Box<dyn std::future::Future<Output = $ret> $($send)*>>,
+            >(fut)
+        }
+    }};
+}
+
+/// Records the calls of a function to a `Cassette`, or replays them, depending on its mode.
+///
+/// Takes the injector, a reference to the cassette and the function in the form of `func!`,
+/// with a name for each parameter. The arguments are recorded as formatted with `Debug`, or
+/// by their type name when they do not implement it, and the return type must implement
+/// `Recordable`. Calls are recorded under the name the function is written with, so each
+/// function must be written the same way when recording and replaying.
+///
+/// # Example
+///
+/// ```rust
+/// use injectorpp::interface::injector::*;
+///
+/// #[inline(never)]
+/// fn read_setting(name: &str) -> Option<String> {
+///     std::env::var(name).ok()
+/// }
+///
+/// let path = std::env::temp_dir().join(format!("settings-{}.cassette", std::process::id()));
+/// {
+///     let cassette = Cassette::record(&path);
+///     let mut injector = InjectorPP::new();
+///     injectorpp::record!(injector, &cassette, fn (read_setting)(name: &str) -> Option<String>);
+///     read_setting("INJECTORPP_UNSET_SETTING");
+/// }
+///
+/// let cassette = Cassette::replay(&path);
+/// let mut injector = InjectorPP::new();
+/// injectorpp::record!(injector, &cassette, fn (read_setting)(name: &str) -> Option<String>);
+/// assert_eq!(read_setting("INJECTORPP_UNSET_SETTING"), None);
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+#[macro_export]
+macro_rules! record {
+    ($injector:expr, $cassette:expr, fn ( $f:expr ) ( $($arg_name:ident: $arg_ty:ty),* ) -> $ret:ty) => {{
+        let cassette: $crate::interface::injector::Cassette = ::std::clone::Clone::clone($cassette);
+        let builder = $injector.when_called($crate::func!(fn ($f)($($arg_ty),*) -> $ret));
+        let builder = match cassette.mode() {
+            $crate::interface::injector::CassetteMode::Record => builder.reentrant_calls_original(),
+            $crate::interface::injector::CassetteMode::Replay => builder,
+        };
+        builder.will_execute_closure($crate::boxed_closure!(
+            move |$($arg_name),*| {
+                #[allow(unused_imports)]
+                use $crate::interface::injector::{__ProbeDebug as _, __ProbeFallback as _};
+                let args: &[String] = &[$(
+                    (&$crate::interface::injector::__DebugProbe(&$arg_name)).__injectorpp_describe()
+                ),*];
+                let args = format!("({})", args.join(", "));
+                cassette.__play(stringify!($f), args, || $f($($arg_name),*))
+            },
+            fn($($arg_ty),*) -> $ret
+        ))
+    }};
+
+    ($injector:expr, $cassette:expr, fn ( $f:expr ) ( $($arg_name:ident: $arg_ty:ty),* )) => {{
+        $crate::record!($injector, $cassette, fn ($f)($($arg_name: $arg_ty),*) -> ())
+    }};
+}
+
+/// Fakes several functions with an ordered script of their calls, and returns the `Scenario`
+/// tracking it.
+///
+/// Takes the injector and a list of steps, each made of a function in the form of `func!`,
+/// with a name for each parameter, then `=>` and the value the call returns, which can use
+/// the parameters. A function is faked once, by its first step, and its calls run its steps in
+/// the order of the list. A call out of order panics, naming the step expected instead.
+///
+/// The values are computed by `move` closures, so a variable they capture is moved into the
+/// first step using it: clone it for the other steps.
+///
+/// # Example
+///
+/// ```rust
+/// use injectorpp::interface::injector::*;
+///
+/// #[inline(never)]
+/// fn open(path: &str) -> bool {
+///     std::hint::black_box(path.is_empty())
+/// }
+///
+/// #[inline(never)]
+/// fn read(len: usize) -> Vec<u8> {
+///     std::hint::black_box(vec![0; len])
+/// }
+///
+/// let mut injector = InjectorPP::new();
+/// let scenario = injectorpp::scenario!(injector, [
+///     fn (open)(path: &str) -> bool => path == "config.toml",
+///     fn (read)(len: usize) -> Vec<u8> => b"name".repeat(len / 4),
+///     fn (read)(len: usize) -> Vec<u8> => Vec::new(),
+/// ]);
+///
+/// assert!(open("config.toml"));
+/// assert_eq!(read(8), b"namename");
+/// assert!(read(8).is_empty());
+/// assert_eq!(scenario.completed(), 3);
+/// ```
+#[macro_export]
+macro_rules! scenario {
+    ($injector:expr, [ $( fn ( $f:expr ) ( $($arg_name:ident: $arg_ty:ty),* ) $(-> $ret:ty)? => $val:expr ),* $(,)? ]) => {{
+        let scenario = $crate::interface::injector::Scenario::__new();
+        $(
+            $crate::__scenario_step!(
+                $injector,
+                scenario,
+                fn ($f)($($arg_name: $arg_ty),*) -> ($($ret)?) => $val
+            );
+        )*
+        scenario
+    }};
+}
+
+/// Appends a step to the scenario of `scenario!`, faking the function if it is its first step.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __scenario_step {
+    ($injector:expr, $scenario:ident, fn ( $f:expr ) ( $($arg_name:ident: $arg_ty:ty),* ) -> () => $val:expr) => {
+        $crate::__scenario_step!($injector, $scenario, fn ($f)($($arg_name: $arg_ty),*) -> (()) => $val)
+    };
+
+    ($injector:expr, $scenario:ident, fn ( $f:expr ) ( $($arg_name:ident: $arg_ty:ty),* ) -> ($ret:ty) => $val:expr) => {{
+        let function = ($f as fn($($arg_ty),*) -> $ret) as usize;
+        #[allow(unused_variables)]
+        let step = move |$($arg_name: $arg_ty),*| -> $ret { $val };
+        let step: Box<dyn FnMut($($arg_ty),*) -> $ret + Send> = Box::new(step);
+        if $scenario.__step(function, stringify!($f), Box::new(step)) {
+            let scenario = ::std::clone::Clone::clone(&$scenario);
+            $injector
+                .when_called($crate::func!(fn ($f)($($arg_ty),*) -> $ret))
+                .will_execute_closure($crate::boxed_closure!(
+                    move |$($arg_name),*| {
+                        scenario.__call(
+                            function,
+                            stringify!($f),
+                            |step: &mut Box<dyn FnMut($($arg_ty),*) -> $ret + Send>| {
+                                step($($arg_name),*)
+                            },
+                        )
+                    },
+                    fn($($arg_ty),*) -> $ret
+                ));
+        }
+    }};
+}
+
+/// Converts an async function to a pointer for `when_called_async_fn`.
+///
+/// The argument types and the output type are checked against the async function.
+///
+/// # Example
+///
+/// ```rust
+/// use injectorpp::interface::injector::*;
+///
+/// async fn fetch(url: &str, retries: u32) -> Vec<u8> {
+///     Vec::new()
+/// }
+///
+/// let _target = injectorpp::async_fn!(fn (fetch)(&str, u32) -> Vec<u8>);
+/// ```
+#[macro_export]
+macro_rules! async_fn {
+    (fn ( $f:expr ) ( $($arg_ty:ty),* ) -> $ret:ty) => {{
+        let target = $f as fn($($crate::__infer_type!($arg_ty)),*) -> _;
+        $crate::interface::injector::__async_fn_target::<_, ($($arg_ty,)*), $ret>(target)
+    }};
+
+    (fn ( $f:expr ) ( $($arg_ty:ty),* )) => {{
+        $crate::async_fn!(fn ($f)($($arg_ty),*) -> ())
+    }};
+}
+
+/// Expands to the inferred type `_`. Used internally by macros.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __infer_type {
+    ($ty:ty) => {
+        _
+    };
+}
+
 /// Creates a mock function implementation with configurable behavior and verification.
 ///
 /// This macro generates a function that can be used to replace real functions during testing.
@@ -207,9 +1628,27 @@ macro_rules! async_return_unchecked {
 /// # Parameters
 ///
 /// - `func_type`: Required. The function signature to mock (e.g., `fn(x: i32) -> bool`).
+///   For a method, the first parameter may be written `self: T`, where `T` is the receiver
+///   type such as `Client`, `&mut Client`, `Box<Client>` or `Arc<Client>`, and `when`,
+///   `assign` and `returns` then refer to it as `self`.
+///   A variadic C function, such as `unsafe extern "C" fn(path: *const c_char, flags: c_int, ...)
+///   -> c_int`, is faked by a function taking only the fixed arguments, which must be integers
+///   or pointers. The variadic arguments cannot be read, as Rust has no stable `VaList`.
+///   Any ABI string can follow `unsafe extern`, such as `"system"`, `"win64"`, `"C-unwind"` or,
+///   on 32-bit Windows, `"stdcall"`, `"fastcall"` and `"thiscall"`. The fake is generated with
+///   that ABI, so it must be the one the faked function is declared with.
 /// - `when`: Optional. A condition on the function parameters that must be true for the mock to execute.
+///   If the condition does not hold, the panic message lists the expected and actual value of each
+///   parameter compared in the condition (values are shown with `Debug` when implemented).
 /// - `assign`: Optional. Code block to execute for modifying reference parameters.
 /// - `returns`: Required for non-unit functions. The value to return from the mock.
+/// - `errno`: Optional, after `returns`. The `errno` set before returning, for code reading it
+///   with `io::Error::last_os_error()`.
+/// - `last_error`: Optional, after `returns`, on Windows. The error code set with `SetLastError`
+///   before returning.
+/// - `delay`: Optional, after `returns`, or after `assign` for unit functions. A `Duration` the
+///   fake waits before returning, to simulate a slow dependency. While a `TokioTimeMocker` is
+///   installed, it advances the virtual clock of the mocker instead of sleeping.
 /// - `times`: Optional. Verifies the function is called exactly this many times.
 ///
 /// # Safety
@@ -222,6 +1661,113 @@ macro_rules! async_return_unchecked {
 /// - Mock functions created with this macro must only be used with the `will_execute` method
 #[macro_export]
 macro_rules! fake {
+    // A method with a `self` receiver: rename the receiver, which a free function cannot have.
+    (func_type: fn(self: $($args:tt)*) $($rest:tt)*) => {
+        $crate::__fake_receiver!($crate; func_type: fn(self: $($args)*) $($rest)*)
+    };
+
+    // A variadic C function: the fake is a regular C function taking the fixed arguments,
+    // which are passed the same way as to the variadic function.
+    (
+        func_type: unsafe extern "C" fn($($arg_name:ident: $arg_ty:ty,)+ ...) -> $ret:ty,
+        $($rest:tt)*
+    ) => {{
+        let (fake, verifier) = $crate::fake!(
+            func_type: unsafe extern "C" fn($($arg_name: $arg_ty),+) -> $ret,
+            $($rest)*
+        );
+        let sig = std::any::type_name::<unsafe extern "C" fn($($arg_ty,)+ ...) -> $ret>();
+        let type_id = std::any::TypeId::of::<unsafe extern "C" fn($($arg_ty,)+ ...) -> $ret>();
+        ($crate::interface::injector::__variadic_fake(fake, sig, type_id), verifier)
+    }};
+
+    // A function setting `errno` before returning: the error code is set once the return
+    // value is computed, so that computing it cannot overwrite the error code.
+    (
+        func_type: $(unsafe $(extern $abi:literal)?)? fn($($arg_name:ident: $arg_ty:ty),*) -> $ret:ty,
+        $(when: $cond:expr,)?
+        $(assign: { $($assign:tt)* },)?
+        returns: $ret_val:expr,
+        errno: $code:expr
+        $(, times: $expected:expr)?
+    ) => {
+        $crate::fake!(
+            func_type: $(unsafe $(extern $abi)?)? fn($($arg_name: $arg_ty),*) -> $ret,
+            $(when: $cond,)?
+            $(assign: { $($assign)* },)?
+            returns: {
+                let ret = $ret_val;
+                $crate::interface::injector::__set_errno($code);
+                ret
+            }
+            $(, times: $expected)?
+        )
+    };
+
+    // A function setting `last_error` before returning: the error code is set once the return
+    // value is computed, so that computing it cannot overwrite the error code.
+    (
+        func_type: $(unsafe $(extern $abi:literal)?)? fn($($arg_name:ident: $arg_ty:ty),*) -> $ret:ty,
+        $(when: $cond:expr,)?
+        $(assign: { $($assign:tt)* },)?
+        returns: $ret_val:expr,
+        last_error: $code:expr
+        $(, times: $expected:expr)?
+    ) => {
+        $crate::fake!(
+            func_type: $(unsafe $(extern $abi)?)? fn($($arg_name: $arg_ty),*) -> $ret,
+            $(when: $cond,)?
+            $(assign: { $($assign)* },)?
+            returns: {
+                let ret = $ret_val;
+                $crate::interface::injector::__set_last_error($code);
+                ret
+            }
+            $(, times: $expected)?
+        )
+    };
+
+    // A function delayed before returning: the delay is slept, or advances the clock of a
+    // `TokioTimeMocker`, before the return value is computed.
+    (
+        func_type: $(unsafe $(extern $abi:literal)?)? fn($($arg_name:ident: $arg_ty:ty),*) -> $ret:ty,
+        $(when: $cond:expr,)?
+        $(assign: { $($assign:tt)* },)?
+        returns: $ret_val:expr,
+        delay: $delay:expr
+        $(, times: $expected:expr)?
+    ) => {
+        $crate::fake!(
+            func_type: $(unsafe $(extern $abi)?)? fn($($arg_name: $arg_ty),*) -> $ret,
+            $(when: $cond,)?
+            $(assign: { $($assign)* },)?
+            returns: {
+                $crate::interface::injector::__fake_delay($delay);
+                $ret_val
+            }
+            $(, times: $expected)?
+        )
+    };
+
+    // A unit function delayed after its assignments.
+    (
+        func_type: $(unsafe $(extern $abi:literal)?)? fn($($arg_name:ident: $arg_ty:ty),*) -> (),
+        $(when: $cond:expr,)?
+        $(assign: { $($assign:tt)* },)?
+        delay: $delay:expr
+        $(, times: $expected:expr)?
+    ) => {
+        $crate::fake!(
+            func_type: $(unsafe $(extern $abi)?)? fn($($arg_name: $arg_ty),*) -> (),
+            $(when: $cond,)?
+            assign: {
+                { $($($assign)*)? }
+                $crate::interface::injector::__fake_delay($delay);
+            }
+            $(, times: $expected)?
+        )
+    };
+
     // === NON-UNIT RETURNING FUNCTIONS (return type not "()") ===
 
     // With when, assign, returns, and times.
@@ -236,20 +1782,22 @@ macro_rules! fake {
          static FAKE_COUNTER: AtomicUsize = AtomicUsize::new(0);
          let verifier = CallCountVerifier::WithCount { counter: &FAKE_COUNTER, expected: $expected };
          fn fake($($arg_name: $arg_ty),*) -> $ret {
-             if $cond {
+             $crate::__fake_called!(fake);
+             let (__injpp_matched, __injpp_report) = $crate::__when_report!($crate; $cond; $($arg_name),*);
+             if __injpp_matched {
                  let prev = FAKE_COUNTER.fetch_add(1, Ordering::SeqCst);
                  if prev >= $expected {
-                     panic!("Fake function defined at {}:{}:{} called more times than expected", file!(), line!(), column!());
+                     $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called more times than expected", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()));
                  }
                  { $($assign)* }
                  $ret_val
              } else {
-                 panic!("Fake function defined at {}:{}:{} called with unexpected arguments", file!(), line!(), column!());
+                 $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called with unexpected arguments{}", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()), __injpp_report);
              }
          }
          let f: fn($($arg_ty),*) -> $ret = fake;
          let raw_ptr = f as *const ();
-         (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+         (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<$ret>("Rust").__declared::<$ret>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
     // With when, assign, and returns (no times).
     (
@@ -260,16 +1808,18 @@ macro_rules! fake {
     ) => {{
          let verifier = CallCountVerifier::Dummy;
          fn fake($($arg_name: $arg_ty),*) -> $ret {
-             if $cond {
+             $crate::__fake_called!(fake);
+             let (__injpp_matched, __injpp_report) = $crate::__when_report!($crate; $cond; $($arg_name),*);
+             if __injpp_matched {
                  { $($assign)* }
                  $ret_val
              } else {
-                 panic!("Fake function defined at {}:{}:{} called with unexpected arguments", file!(), line!(), column!());
+                 $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called with unexpected arguments{}", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()), __injpp_report);
              }
          }
          let f: fn($($arg_ty),*) -> $ret = fake;
          let raw_ptr = f as *const ();
-         (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+         (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<$ret>("Rust").__declared::<$ret>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
     // With when and returns, times, but no assign.
     (
@@ -282,19 +1832,21 @@ macro_rules! fake {
          static FAKE_COUNTER: AtomicUsize = AtomicUsize::new(0);
          let verifier = CallCountVerifier::WithCount { counter: &FAKE_COUNTER, expected: $expected };
          fn fake($($arg_name: $arg_ty),*) -> $ret {
-             if $cond {
+             $crate::__fake_called!(fake);
+             let (__injpp_matched, __injpp_report) = $crate::__when_report!($crate; $cond; $($arg_name),*);
+             if __injpp_matched {
                  let prev = FAKE_COUNTER.fetch_add(1, Ordering::SeqCst);
                  if prev >= $expected {
-                     panic!("Fake function defined at {}:{}:{} called more times than expected", file!(), line!(), column!());
+                     $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called more times than expected", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()));
                  }
                  $ret_val
              } else {
-                 panic!("Fake function defined at {}:{}:{} called with unexpected arguments", file!(), line!(), column!());
+                 $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called with unexpected arguments{}", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()), __injpp_report);
              }
          }
          let f: fn($($arg_ty),*) -> $ret = fake;
          let raw_ptr = f as *const ();
-         (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+         (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<$ret>("Rust").__declared::<$ret>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
     // With when and returns (no times, no assign).
     (
@@ -304,15 +1856,17 @@ macro_rules! fake {
     ) => {{
          let verifier = CallCountVerifier::Dummy;
          fn fake($($arg_name: $arg_ty),*) -> $ret {
-             if $cond {
+             $crate::__fake_called!(fake);
+             let (__injpp_matched, __injpp_report) = $crate::__when_report!($crate; $cond; $($arg_name),*);
+             if __injpp_matched {
                  $ret_val
              } else {
-                 panic!("Fake function defined at {}:{}:{} called with unexpected arguments", file!(), line!(), column!());
+                 $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called with unexpected arguments{}", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()), __injpp_report);
              }
          }
          let f: fn($($arg_ty),*) -> $ret = fake;
          let raw_ptr = f as *const ();
-         (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+         (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<$ret>("Rust").__declared::<$ret>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
     (
         func_type: unsafe extern "C" fn($($arg_name:ident: $arg_ty:ty),*) -> $ret:ty,
@@ -321,15 +1875,17 @@ macro_rules! fake {
     ) => {{
          let verifier = CallCountVerifier::Dummy;
          unsafe extern "C" fn fake($($arg_name: $arg_ty),*) -> $ret {
-             if $cond {
+             $crate::__fake_called!(fake);
+             let (__injpp_matched, __injpp_report) = $crate::__when_report!($crate; $cond; $($arg_name),*);
+             if __injpp_matched {
                  $ret_val
              } else {
-                 panic!("Fake function defined at {}:{}:{} called with unexpected arguments", file!(), line!(), column!());
+                 $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called with unexpected arguments{}", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()), __injpp_report);
              }
          }
          let f: unsafe extern "C" fn($($arg_ty),*) -> $ret = fake;
          let raw_ptr = f as *const ();
-         (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+         (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<$ret>("C").__declared::<$ret>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
     // With assign, returns and times
     (
@@ -342,10 +1898,11 @@ macro_rules! fake {
          static FAKE_COUNTER: AtomicUsize = AtomicUsize::new(0);
          let verifier = CallCountVerifier::WithCount { counter: &FAKE_COUNTER, expected: $expected };
          fn fake($($arg_name: $arg_ty),*) -> $ret {
+             $crate::__fake_called!(fake);
              if true {
                  let prev = FAKE_COUNTER.fetch_add(1, Ordering::SeqCst);
                  if prev >= $expected {
-                     panic!("Fake function defined at {}:{}:{} called more times than expected", file!(), line!(), column!());
+                     $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called more times than expected", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()));
                  }
                  { $($assign)* }
                  $ret_val
@@ -355,7 +1912,7 @@ macro_rules! fake {
          }
          let f: fn($($arg_ty),*) -> $ret = fake;
          let raw_ptr = f as *const ();
-         (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+         (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<$ret>("Rust").__declared::<$ret>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
     // With assign and returns
     (
@@ -365,6 +1922,7 @@ macro_rules! fake {
     ) => {{
          let verifier = CallCountVerifier::Dummy;
          fn fake($($arg_name: $arg_ty),*) -> $ret {
+             $crate::__fake_called!(fake);
              if true {
                 { $($assign)* }
                  $ret_val
@@ -374,7 +1932,7 @@ macro_rules! fake {
          }
          let f: fn($($arg_ty),*) -> $ret = fake;
          let raw_ptr = f as *const ();
-         (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+         (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<$ret>("Rust").__declared::<$ret>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
     // With times and returns
     (
@@ -386,10 +1944,11 @@ macro_rules! fake {
          static FAKE_COUNTER: AtomicUsize = AtomicUsize::new(0);
          let verifier = CallCountVerifier::WithCount { counter: &FAKE_COUNTER, expected: $expected };
          fn fake($($arg_name: $arg_ty),*) -> $ret {
+             $crate::__fake_called!(fake);
              if true {
                  let prev = FAKE_COUNTER.fetch_add(1, Ordering::SeqCst);
                  if prev >= $expected {
-                     panic!("Fake function defined at {}:{}:{} called more times than expected", file!(), line!(), column!());
+                     $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called more times than expected", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()));
                  }
                  $ret_val
              } else {
@@ -398,7 +1957,7 @@ macro_rules! fake {
          }
          let f: fn($($arg_ty),*) -> $ret = fake;
          let raw_ptr = f as *const ();
-         (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+         (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<$ret>("Rust").__declared::<$ret>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
     // With returns only.
     (
@@ -407,6 +1966,7 @@ macro_rules! fake {
     ) => {{
          let verifier = CallCountVerifier::Dummy;
          fn fake($($arg_name: $arg_ty),*) -> $ret {
+             $crate::__fake_called!(fake);
              if true {
                  $ret_val
              } else {
@@ -415,7 +1975,7 @@ macro_rules! fake {
          }
          let f: fn($($arg_ty),*) -> $ret = fake;
          let raw_ptr = f as *const ();
-         (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+         (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<$ret>("Rust").__declared::<$ret>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
     (
         func_type: unsafe extern "C" fn($($arg_name:ident: $arg_ty:ty),*) -> $ret:ty,
@@ -423,6 +1983,7 @@ macro_rules! fake {
     ) => {{
          let verifier = CallCountVerifier::Dummy;
          unsafe extern "C" fn fake($($arg_name: $arg_ty),*) -> $ret {
+             $crate::__fake_called!(fake);
              if true {
                  $ret_val
              } else {
@@ -431,7 +1992,7 @@ macro_rules! fake {
          }
          let f: unsafe extern "C" fn($($arg_ty),*) -> $ret = fake;
          let raw_ptr = f as *const ();
-         (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+         (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<$ret>("C").__declared::<$ret>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
 
     // === UNIT RETURNING FUNCTIONS (-> ()) ===
@@ -447,19 +2008,21 @@ macro_rules! fake {
          static FAKE_COUNTER: AtomicUsize = AtomicUsize::new(0);
          let verifier = CallCountVerifier::WithCount { counter: &FAKE_COUNTER, expected: $expected };
          fn fake($($arg_name: $arg_ty),*) {
-             if $cond {
+             $crate::__fake_called!(fake);
+             let (__injpp_matched, __injpp_report) = $crate::__when_report!($crate; $cond; $($arg_name),*);
+             if __injpp_matched {
                  let prev = FAKE_COUNTER.fetch_add(1, Ordering::SeqCst);
                  if prev >= $expected {
-                     panic!("Fake function defined at {}:{}:{} called more times than expected", file!(), line!(), column!());
+                     $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called more times than expected", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()));
                  }
                  { $($assign)* }
              } else {
-                 panic!("Fake function defined at {}:{}:{} called with unexpected arguments", file!(), line!(), column!());
+                 $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called with unexpected arguments{}", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()), __injpp_report);
              }
          }
          let f: fn($($arg_ty),*) -> $ret = fake;
          let raw_ptr = f as *const ();
-         (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+         (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<$ret>("Rust").__declared::<$ret>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
     // With when and times (no assign).
     (
@@ -471,18 +2034,20 @@ macro_rules! fake {
          static FAKE_COUNTER: AtomicUsize = AtomicUsize::new(0);
          let verifier = CallCountVerifier::WithCount { counter: &FAKE_COUNTER, expected: $expected };
          fn fake($($arg_name: $arg_ty),*) {
-             if $cond {
+             $crate::__fake_called!(fake);
+             let (__injpp_matched, __injpp_report) = $crate::__when_report!($crate; $cond; $($arg_name),*);
+             if __injpp_matched {
                  let prev = FAKE_COUNTER.fetch_add(1, Ordering::SeqCst);
                  if prev >= $expected {
-                     panic!("Fake function defined at {}:{}:{} called more times than expected", file!(), line!(), column!());
+                     $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called more times than expected", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()));
                  }
              } else {
-                 panic!("Fake function defined at {}:{}:{} called with unexpected arguments", file!(), line!(), column!());
+                 $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called with unexpected arguments{}", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()), __injpp_report);
              }
          }
          let f: fn($($arg_ty),*) -> $ret = fake;
          let raw_ptr = f as *const ();
-         (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+         (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<$ret>("Rust").__declared::<$ret>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
     // With when and assign (no times).
     (
@@ -492,15 +2057,17 @@ macro_rules! fake {
     ) => {{
          let verifier = CallCountVerifier::Dummy;
          fn fake($($arg_name: $arg_ty),*) {
-             if $cond {
+             $crate::__fake_called!(fake);
+             let (__injpp_matched, __injpp_report) = $crate::__when_report!($crate; $cond; $($arg_name),*);
+             if __injpp_matched {
                  { $($assign)* }
              } else {
-                 panic!("Fake function defined at {}:{}:{} called with unexpected arguments", file!(), line!(), column!());
+                 $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called with unexpected arguments{}", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()), __injpp_report);
              }
          }
          let f: fn($($arg_ty),*) = fake;
          let raw_ptr = f as *const ();
-         (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+         (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<()>("Rust").__declared::<()>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
     // With assign only
     (
@@ -509,6 +2076,7 @@ macro_rules! fake {
     ) => {{
          let verifier = CallCountVerifier::Dummy;
          fn fake($($arg_name: $arg_ty),*) {
+             $crate::__fake_called!(fake);
              if true {
                  { $($assign)* }
              } else {
@@ -517,7 +2085,7 @@ macro_rules! fake {
          }
          let f: fn($($arg_ty),*) = fake;
          let raw_ptr = f as *const ();
-         (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+         (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<()>("Rust").__declared::<()>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
     // With assign and times
     (
@@ -530,19 +2098,20 @@ macro_rules! fake {
          static FAKE_COUNTER: AtomicUsize = AtomicUsize::new(0);
          let verifier = CallCountVerifier::WithCount { counter: &FAKE_COUNTER, expected: $expected };
          fn fake($($arg_name: $arg_ty),*) {
+             $crate::__fake_called!(fake);
              if true {
                  let prev = FAKE_COUNTER.fetch_add(1, Ordering::SeqCst);
                  if prev >= $expected {
-                     panic!("Fake function defined at {}:{}:{} called more times than expected", file!(), line!(), column!());
+                     $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called more times than expected", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()));
                  }
                  { $($assign)* }
              } else {
-                 panic!("Fake function defined at {}:{}:{} called with unexpected arguments", file!(), line!(), column!());
+                 $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called with unexpected arguments", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()));
              }
          }
          let f: fn($($arg_ty),*) = fake;
          let raw_ptr = f as *const ();
-         (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+         (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<()>("Rust").__declared::<()>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
     // With times only (when defaults to true, no assign).
     (
@@ -553,10 +2122,11 @@ macro_rules! fake {
          static FAKE_COUNTER: AtomicUsize = AtomicUsize::new(0);
          let verifier = CallCountVerifier::WithCount { counter: &FAKE_COUNTER, expected: $expected };
          fn fake($($arg_name: $arg_ty),*) {
+             $crate::__fake_called!(fake);
              if true {
                  let prev = FAKE_COUNTER.fetch_add(1, Ordering::SeqCst);
                  if prev >= $expected {
-                     panic!("Fake function defined at {}:{}:{} called more times than expected", file!(), line!(), column!());
+                     $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called more times than expected", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()));
                  }
              } else {
                  unreachable!()
@@ -564,7 +2134,7 @@ macro_rules! fake {
          }
          let f: fn($($arg_ty),*) = fake;
          let raw_ptr = f as *const ();
-         (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+         (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<()>("Rust").__declared::<()>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
     // With neither (no when, no times, no assign, no returns).
     (
@@ -572,11 +2142,12 @@ macro_rules! fake {
     ) => {{
          let verifier = CallCountVerifier::Dummy;
          fn fake($($arg_name: $arg_ty),*) {
+             $crate::__fake_called!(fake);
              if true { } else { unreachable!() }
          }
          let f: fn($($arg_ty),*) = fake;
          let raw_ptr = f as *const ();
-         (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+         (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<()>("Rust").__declared::<()>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
 
     // === NORMAL UNSAFE NON-UNIT RETURNING FUNCTIONS ===
@@ -587,6 +2158,7 @@ macro_rules! fake {
     ) => {{
         let verifier = CallCountVerifier::Dummy;
         unsafe fn fake($($arg_name: $arg_ty),*) -> $ret {
+            $crate::__fake_called!(fake);
             if true {
                 $ret_val
             } else {
@@ -595,7 +2167,7 @@ macro_rules! fake {
         }
         let f: unsafe fn($($arg_ty),*) -> $ret = fake;
         let raw_ptr = f as *const ();
-        (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+        (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<$ret>("Rust").__declared::<$ret>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
     // With returns and times for unsafe fn
     (
@@ -607,10 +2179,11 @@ macro_rules! fake {
         static FAKE_COUNTER: AtomicUsize = AtomicUsize::new(0);
         let verifier = CallCountVerifier::WithCount { counter: &FAKE_COUNTER, expected: $expected };
         unsafe fn fake($($arg_name: $arg_ty),*) -> $ret {
+            $crate::__fake_called!(fake);
             if true {
                 let prev = FAKE_COUNTER.fetch_add(1, Ordering::SeqCst);
                 if prev >= $expected {
-                    panic!("Fake function defined at {}:{}:{} called more times than expected", file!(), line!(), column!());
+                    $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called more times than expected", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()));
                 }
                 $ret_val
             } else {
@@ -619,7 +2192,7 @@ macro_rules! fake {
         }
         let f: unsafe fn($($arg_ty),*) -> $ret = fake;
         let raw_ptr = f as *const ();
-        (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+        (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<$ret>("Rust").__declared::<$ret>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
     // With assign and returns for unsafe fn
     (
@@ -629,6 +2202,7 @@ macro_rules! fake {
     ) => {{
         let verifier = CallCountVerifier::Dummy;
         unsafe fn fake($($arg_name: $arg_ty),*) -> $ret {
+            $crate::__fake_called!(fake);
             if true {
                 { $($assign)* }
                 $ret_val
@@ -638,7 +2212,7 @@ macro_rules! fake {
         }
         let f: unsafe fn($($arg_ty),*) -> $ret = fake;
         let raw_ptr = f as *const ();
-        (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+        (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<$ret>("Rust").__declared::<$ret>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
     // With assign, returns, and times for unsafe fn
     (
@@ -651,10 +2225,11 @@ macro_rules! fake {
         static FAKE_COUNTER: AtomicUsize = AtomicUsize::new(0);
         let verifier = CallCountVerifier::WithCount { counter: &FAKE_COUNTER, expected: $expected };
         unsafe fn fake($($arg_name: $arg_ty),*) -> $ret {
+            $crate::__fake_called!(fake);
             if true {
                 let prev = FAKE_COUNTER.fetch_add(1, Ordering::SeqCst);
                 if prev >= $expected {
-                    panic!("Fake function defined at {}:{}:{} called more times than expected", file!(), line!(), column!());
+                    $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called more times than expected", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()));
                 }
                 { $($assign)* }
                 $ret_val
@@ -664,7 +2239,7 @@ macro_rules! fake {
         }
         let f: unsafe fn($($arg_ty),*) -> $ret = fake;
         let raw_ptr = f as *const ();
-        (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+        (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<$ret>("Rust").__declared::<$ret>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
     // === NORMAL UNSAFE UNIT RETURNING FUNCTIONS ===
     // With times for unsafe fn
@@ -676,10 +2251,11 @@ macro_rules! fake {
         static FAKE_COUNTER: AtomicUsize = AtomicUsize::new(0);
         let verifier = CallCountVerifier::WithCount { counter: &FAKE_COUNTER, expected: $expected };
         unsafe fn fake($($arg_name: $arg_ty),*) {
+            $crate::__fake_called!(fake);
             if true {
                 let prev = FAKE_COUNTER.fetch_add(1, Ordering::SeqCst);
                 if prev >= $expected {
-                    panic!("Fake function defined at {}:{}:{} called more times than expected", file!(), line!(), column!());
+                    $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called more times than expected", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()));
                 }
             } else {
                 unreachable!()
@@ -687,7 +2263,7 @@ macro_rules! fake {
         }
         let f: unsafe fn($($arg_ty),*) = fake;
         let raw_ptr = f as *const ();
-        (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+        (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<()>("Rust").__declared::<()>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
     // With assign only
     (
@@ -696,6 +2272,7 @@ macro_rules! fake {
     ) => {{
         let verifier = CallCountVerifier::Dummy;
         unsafe fn fake($($arg_name: $arg_ty),*) {
+            $crate::__fake_called!(fake);
             if true {
                 { $($assign)* }
             } else {
@@ -704,7 +2281,7 @@ macro_rules! fake {
         }
         let f: unsafe fn($($arg_ty),*) = fake;
         let raw_ptr = f as *const ();
-        (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+        (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<()>("Rust").__declared::<()>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
     // With assign and times
     (
@@ -717,19 +2294,20 @@ macro_rules! fake {
          static FAKE_COUNTER: AtomicUsize = AtomicUsize::new(0);
          let verifier = CallCountVerifier::WithCount { counter: &FAKE_COUNTER, expected: $expected };
          unsafe fn fake($($arg_name: $arg_ty),*) {
+             $crate::__fake_called!(fake);
              if true {
                  let prev = FAKE_COUNTER.fetch_add(1, Ordering::SeqCst);
                  if prev >= $expected {
-                     panic!("Fake function defined at {}:{}:{} called more times than expected", file!(), line!(), column!());
+                     $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called more times than expected", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()));
                  }
                  { $($assign)* }
              } else {
-                 panic!("Fake function defined at {}:{}:{} called with unexpected arguments", file!(), line!(), column!());
+                 $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called with unexpected arguments", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()));
              }
          }
          let f: unsafe fn($($arg_ty),*) = fake;
          let raw_ptr = f as *const ();
-         (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+         (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<()>("Rust").__declared::<()>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
     // Without times for unsafe fn
     (
@@ -737,11 +2315,12 @@ macro_rules! fake {
     ) => {{
         let verifier = CallCountVerifier::Dummy;
         unsafe fn fake($($arg_name: $arg_ty),*) {
+            $crate::__fake_called!(fake);
             if true { } else { unreachable!() }
         }
         let f: unsafe fn($($arg_ty),*) = fake;
         let raw_ptr = f as *const ();
-        (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+        (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<()>("Rust").__declared::<()>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
 
     // === EXTERN "C" NON-UNIT RETURNING FUNCTIONS ===
@@ -757,20 +2336,22 @@ macro_rules! fake {
         static FAKE_COUNTER: AtomicUsize = AtomicUsize::new(0);
         let verifier = CallCountVerifier::WithCount { counter: &FAKE_COUNTER, expected: $expected };
         unsafe extern "C" fn fake($($arg_name: $arg_ty),*) -> $ret {
-            if $cond {
+            $crate::__fake_called!(fake);
+            let (__injpp_matched, __injpp_report) = $crate::__when_report!($crate; $cond; $($arg_name),*);
+            if __injpp_matched {
                 let prev = FAKE_COUNTER.fetch_add(1, Ordering::SeqCst);
                 if prev >= $expected {
-                    panic!("Fake function defined at {}:{}:{} called more times than expected", file!(), line!(), column!());
+                    $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called more times than expected", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()));
                 }
                 { $($assign)* }
                 $ret_val
             } else {
-                panic!("Fake function defined at {}:{}:{} called with unexpected arguments", file!(), line!(), column!());
+                $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called with unexpected arguments{}", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()), __injpp_report);
             }
         }
         let f: unsafe extern "C" fn($($arg_ty),*) -> $ret = fake;
         let raw_ptr = f as *const ();
-        (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+        (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<$ret>("C").__declared::<$ret>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
     // With when, assign, and returns
     (
@@ -781,16 +2362,18 @@ macro_rules! fake {
     ) => {{
         let verifier = CallCountVerifier::Dummy;
         unsafe extern "C" fn fake($($arg_name: $arg_ty),*) -> $ret {
-            if $cond {
+            $crate::__fake_called!(fake);
+            let (__injpp_matched, __injpp_report) = $crate::__when_report!($crate; $cond; $($arg_name),*);
+            if __injpp_matched {
                 { $($assign)* }
                 $ret_val
             } else {
-                panic!("Fake function defined at {}:{}:{} called with unexpected arguments", file!(), line!(), column!());
+                $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called with unexpected arguments{}", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()), __injpp_report);
             }
         }
         let f: unsafe extern "C" fn($($arg_ty),*) -> $ret = fake;
         let raw_ptr = f as *const ();
-        (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+        (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<$ret>("C").__declared::<$ret>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
     // With when and returns, times
     (
@@ -803,19 +2386,21 @@ macro_rules! fake {
         static FAKE_COUNTER: AtomicUsize = AtomicUsize::new(0);
         let verifier = CallCountVerifier::WithCount { counter: &FAKE_COUNTER, expected: $expected };
         unsafe extern "C" fn fake($($arg_name: $arg_ty),*) -> $ret {
-            if $cond {
+            $crate::__fake_called!(fake);
+            let (__injpp_matched, __injpp_report) = $crate::__when_report!($crate; $cond; $($arg_name),*);
+            if __injpp_matched {
                 let prev = FAKE_COUNTER.fetch_add(1, Ordering::SeqCst);
                 if prev >= $expected {
-                    panic!("Fake function defined at {}:{}:{} called more times than expected", file!(), line!(), column!());
+                    $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called more times than expected", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()));
                 }
                 $ret_val
             } else {
-                panic!("Fake function defined at {}:{}:{} called with unexpected arguments", file!(), line!(), column!());
+                $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called with unexpected arguments{}", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()), __injpp_report);
             }
         }
         let f: unsafe extern "C" fn($($arg_ty),*) -> $ret = fake;
         let raw_ptr = f as *const ();
-        (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+        (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<$ret>("C").__declared::<$ret>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
     // With assign, returns, and times
     (
@@ -828,10 +2413,11 @@ macro_rules! fake {
         static FAKE_COUNTER: AtomicUsize = AtomicUsize::new(0);
         let verifier = CallCountVerifier::WithCount { counter: &FAKE_COUNTER, expected: $expected };
         unsafe extern "C" fn fake($($arg_name: $arg_ty),*) -> $ret {
+            $crate::__fake_called!(fake);
             if true {
                 let prev = FAKE_COUNTER.fetch_add(1, Ordering::SeqCst);
                 if prev >= $expected {
-                    panic!("Fake function defined at {}:{}:{} called more times than expected", file!(), line!(), column!());
+                    $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called more times than expected", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()));
                 }
                 { $($assign)* }
                 $ret_val
@@ -841,7 +2427,7 @@ macro_rules! fake {
         }
         let f: unsafe extern "C" fn($($arg_ty),*) -> $ret = fake;
         let raw_ptr = f as *const ();
-        (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+        (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<$ret>("C").__declared::<$ret>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
     // With assign and returns
     (
@@ -851,6 +2437,7 @@ macro_rules! fake {
     ) => {{
         let verifier = CallCountVerifier::Dummy;
         unsafe extern "C" fn fake($($arg_name: $arg_ty),*) -> $ret {
+            $crate::__fake_called!(fake);
             if true {
                 { $($assign)* }
                 $ret_val
@@ -860,7 +2447,7 @@ macro_rules! fake {
         }
         let f: unsafe extern "C" fn($($arg_ty),*) -> $ret = fake;
         let raw_ptr = f as *const ();
-        (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+        (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<$ret>("C").__declared::<$ret>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
     // With returns and times
     (
@@ -872,10 +2459,11 @@ macro_rules! fake {
         static FAKE_COUNTER: AtomicUsize = AtomicUsize::new(0);
         let verifier = CallCountVerifier::WithCount { counter: &FAKE_COUNTER, expected: $expected };
         unsafe extern "C" fn fake($($arg_name: $arg_ty),*) -> $ret {
+            $crate::__fake_called!(fake);
             if true {
                 let prev = FAKE_COUNTER.fetch_add(1, Ordering::SeqCst);
                 if prev >= $expected {
-                    panic!("Fake function defined at {}:{}:{} called more times than expected", file!(), line!(), column!());
+                    $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called more times than expected", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()));
                 }
                 $ret_val
             } else {
@@ -884,7 +2472,7 @@ macro_rules! fake {
         }
         let f: unsafe extern "C" fn($($arg_ty),*) -> $ret = fake;
         let raw_ptr = f as *const ();
-        (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+        (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<$ret>("C").__declared::<$ret>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
     // === EXTERN "C" UNIT RETURNING FUNCTIONS ===
     // With when, assign, and times
@@ -898,19 +2486,21 @@ macro_rules! fake {
         static FAKE_COUNTER: AtomicUsize = AtomicUsize::new(0);
         let verifier = CallCountVerifier::WithCount { counter: &FAKE_COUNTER, expected: $expected };
         unsafe extern "C" fn fake($($arg_name: $arg_ty),*) {
-            if $cond {
+            $crate::__fake_called!(fake);
+            let (__injpp_matched, __injpp_report) = $crate::__when_report!($crate; $cond; $($arg_name),*);
+            if __injpp_matched {
                 let prev = FAKE_COUNTER.fetch_add(1, Ordering::SeqCst);
                 if prev >= $expected {
-                    panic!("Fake function defined at {}:{}:{} called more times than expected", file!(), line!(), column!());
+                    $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called more times than expected", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()));
                 }
                 { $($assign)* }
             } else {
-                panic!("Fake function defined at {}:{}:{} called with unexpected arguments", file!(), line!(), column!());
+                $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called with unexpected arguments{}", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()), __injpp_report);
             }
         }
         let f: unsafe extern "C" fn($($arg_ty),*) = fake;
         let raw_ptr = f as *const ();
-        (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+        (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<()>("C").__declared::<()>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
     // With when and times (no assign).
     (
@@ -922,18 +2512,20 @@ macro_rules! fake {
         static FAKE_COUNTER: AtomicUsize = AtomicUsize::new(0);
         let verifier = CallCountVerifier::WithCount { counter: &FAKE_COUNTER, expected: $expected };
         unsafe extern "C" fn fake($($arg_name: $arg_ty),*) {
-            if $cond {
+            $crate::__fake_called!(fake);
+            let (__injpp_matched, __injpp_report) = $crate::__when_report!($crate; $cond; $($arg_name),*);
+            if __injpp_matched {
                 let prev = FAKE_COUNTER.fetch_add(1, Ordering::SeqCst);
                 if prev >= $expected {
-                    panic!("Fake function defined at {}:{}:{} called more times than expected", file!(), line!(), column!());
+                    $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called more times than expected", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()));
                 }
             } else {
-                panic!("Fake function defined at {}:{}:{} called with unexpected arguments", file!(), line!(), column!());
+                $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called with unexpected arguments{}", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()), __injpp_report);
             }
         }
         let f: unsafe extern "C" fn($($arg_ty),*) = fake;
         let raw_ptr = f as *const ();
-        (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+        (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<()>("C").__declared::<()>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
     // With when and assign (no times).
     (
@@ -943,15 +2535,17 @@ macro_rules! fake {
     ) => {{
         let verifier = CallCountVerifier::Dummy;
         unsafe extern "C" fn fake($($arg_name: $arg_ty),*) {
-            if $cond {
+            $crate::__fake_called!(fake);
+            let (__injpp_matched, __injpp_report) = $crate::__when_report!($crate; $cond; $($arg_name),*);
+            if __injpp_matched {
                 { $($assign)* }
             } else {
-                panic!("Fake function defined at {}:{}:{} called with unexpected arguments", file!(), line!(), column!());
+                $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called with unexpected arguments{}", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()), __injpp_report);
             }
         }
         let f: unsafe extern "C" fn($($arg_ty),*) = fake;
         let raw_ptr = f as *const ();
-        (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+        (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<()>("C").__declared::<()>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
     // With assign only
     (
@@ -960,6 +2554,7 @@ macro_rules! fake {
     ) => {{
         let verifier = CallCountVerifier::Dummy;
         unsafe extern "C" fn fake($($arg_name: $arg_ty),*) {
+            $crate::__fake_called!(fake);
             if true {
                 { $($assign)* }
             } else {
@@ -968,7 +2563,7 @@ macro_rules! fake {
         }
         let f: unsafe extern "C" fn($($arg_ty),*) = fake;
         let raw_ptr = f as *const ();
-        (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+        (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<()>("C").__declared::<()>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
     // With assign and times
     (
@@ -981,19 +2576,20 @@ macro_rules! fake {
          static FAKE_COUNTER: AtomicUsize = AtomicUsize::new(0);
          let verifier = CallCountVerifier::WithCount { counter: &FAKE_COUNTER, expected: $expected };
          unsafe extern "C" fn fake($($arg_name: $arg_ty),*) {
+             $crate::__fake_called!(fake);
              if true {
                  let prev = FAKE_COUNTER.fetch_add(1, Ordering::SeqCst);
                  if prev >= $expected {
-                     panic!("Fake function defined at {}:{}:{} called more times than expected", file!(), line!(), column!());
+                     $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called more times than expected", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()));
                  }
                  { $($assign)* }
              } else {
-                 panic!("Fake function defined at {}:{}:{} called with unexpected arguments", file!(), line!(), column!());
+                 $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called with unexpected arguments", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()));
              }
          }
          let f: unsafe extern "C" fn($($arg_ty),*) = fake;
          let raw_ptr = f as *const ();
-         (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+         (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<()>("C").__declared::<()>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
     // With times only (when defaults to true, no assign).
     (
@@ -1004,10 +2600,11 @@ macro_rules! fake {
          static FAKE_COUNTER: AtomicUsize = AtomicUsize::new(0);
          let verifier = CallCountVerifier::WithCount { counter: &FAKE_COUNTER, expected: $expected };
          unsafe extern "C" fn fake($($arg_name: $arg_ty),*) {
+             $crate::__fake_called!(fake);
              if true {
                  let prev = FAKE_COUNTER.fetch_add(1, Ordering::SeqCst);
                  if prev >= $expected {
-                     panic!("Fake function defined at {}:{}:{} called more times than expected", file!(), line!(), column!());
+                     $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called more times than expected", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()));
                  }
              } else {
                  unreachable!()
@@ -1015,7 +2612,7 @@ macro_rules! fake {
          }
          let f: unsafe extern "C" fn($($arg_ty),*) = fake;
          let raw_ptr = f as *const ();
-         (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+         (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<()>("C").__declared::<()>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
     // With neither (no when, no times, no assign, no returns).
     (
@@ -1023,16 +2620,18 @@ macro_rules! fake {
     ) => {{
          let verifier = CallCountVerifier::Dummy;
          unsafe extern "C" fn fake($($arg_name: $arg_ty),*) {
+             $crate::__fake_called!(fake);
              if true { } else { unreachable!() }
          }
          let f: unsafe extern "C" fn($($arg_ty),*) = fake;
          let raw_ptr = f as *const ();
-         (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+         (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<()>("C").__declared::<()>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
-    // === EXTERN "system" NON-UNIT RETURNING FUNCTIONS ===
+    // === EXTERN "<abi>" NON-UNIT RETURNING FUNCTIONS ===
+    // Any other ABI string, such as "system", "win64", "stdcall" or "C-unwind".
     // With when, assign, returns, and times.
     (
-        func_type: unsafe extern "system" fn($($arg_name:ident: $arg_ty:ty),*) -> $ret:ty,
+        func_type: unsafe extern $abi:literal fn($($arg_name:ident: $arg_ty:ty),*) -> $ret:ty,
         when: $cond:expr,
         assign: { $($assign:tt)* },
         returns: $ret_val:expr,
@@ -1041,45 +2640,49 @@ macro_rules! fake {
         use std::sync::atomic::{AtomicUsize, Ordering};
         static FAKE_COUNTER: AtomicUsize = AtomicUsize::new(0);
         let verifier = CallCountVerifier::WithCount { counter: &FAKE_COUNTER, expected: $expected };
-        unsafe extern "system" fn fake($($arg_name: $arg_ty),*) -> $ret {
-            if $cond {
+        unsafe extern $abi fn fake($($arg_name: $arg_ty),*) -> $ret {
+            $crate::__fake_called!(fake);
+            let (__injpp_matched, __injpp_report) = $crate::__when_report!($crate; $cond; $($arg_name),*);
+            if __injpp_matched {
                 let prev = FAKE_COUNTER.fetch_add(1, Ordering::SeqCst);
                 if prev >= $expected {
-                    panic!("Fake function defined at {}:{}:{} called more times than expected", file!(), line!(), column!());
+                    $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called more times than expected", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()));
                 }
                 { $($assign)* }
                 $ret_val
             } else {
-                panic!("Fake function defined at {}:{}:{} called with unexpected arguments", file!(), line!(), column!());
+                $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called with unexpected arguments{}", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()), __injpp_report);
             }
         }
-        let f: unsafe extern "system" fn($($arg_ty),*) -> $ret = fake;
+        let f: unsafe extern $abi fn($($arg_ty),*) -> $ret = fake;
         let raw_ptr = f as *const ();
-        (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+        (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<$ret>($abi).__declared::<$ret>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
     // With when, assign, and returns
     (
-        func_type: unsafe extern "system" fn($($arg_name:ident: $arg_ty:ty),*) -> $ret:ty,
+        func_type: unsafe extern $abi:literal fn($($arg_name:ident: $arg_ty:ty),*) -> $ret:ty,
         when: $cond:expr,
         assign: { $($assign:tt)* },
         returns: $ret_val:expr
     ) => {{
         let verifier = CallCountVerifier::Dummy;
-        unsafe extern "system" fn fake($($arg_name: $arg_ty),*) -> $ret {
-            if $cond {
+        unsafe extern $abi fn fake($($arg_name: $arg_ty),*) -> $ret {
+            $crate::__fake_called!(fake);
+            let (__injpp_matched, __injpp_report) = $crate::__when_report!($crate; $cond; $($arg_name),*);
+            if __injpp_matched {
                 { $($assign)* }
                 $ret_val
             } else {
-                panic!("Fake function defined at {}:{}:{} called with unexpected arguments", file!(), line!(), column!());
+                $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called with unexpected arguments{}", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()), __injpp_report);
             }
         }
-        let f: unsafe extern "system" fn($($arg_ty),*) -> $ret = fake;
+        let f: unsafe extern $abi fn($($arg_ty),*) -> $ret = fake;
         let raw_ptr = f as *const ();
-        (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+        (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<$ret>($abi).__declared::<$ret>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
     // With when and returns, times
     (
-        func_type: unsafe extern "system" fn($($arg_name:ident: $arg_ty:ty),*) -> $ret:ty,
+        func_type: unsafe extern $abi:literal fn($($arg_name:ident: $arg_ty:ty),*) -> $ret:ty,
         when: $cond:expr,
         returns: $ret_val:expr,
         times: $expected:expr
@@ -1087,24 +2690,26 @@ macro_rules! fake {
         use std::sync::atomic::{AtomicUsize, Ordering};
         static FAKE_COUNTER: AtomicUsize = AtomicUsize::new(0);
         let verifier = CallCountVerifier::WithCount { counter: &FAKE_COUNTER, expected: $expected };
-        unsafe extern "system" fn fake($($arg_name: $arg_ty),*) -> $ret {
-            if $cond {
+        unsafe extern $abi fn fake($($arg_name: $arg_ty),*) -> $ret {
+            $crate::__fake_called!(fake);
+            let (__injpp_matched, __injpp_report) = $crate::__when_report!($crate; $cond; $($arg_name),*);
+            if __injpp_matched {
                 let prev = FAKE_COUNTER.fetch_add(1, Ordering::SeqCst);
                 if prev >= $expected {
-                    panic!("Fake function defined at {}:{}:{} called more times than expected", file!(), line!(), column!());
+                    $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called more times than expected", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()));
                 }
                 $ret_val
             } else {
-                panic!("Fake function defined at {}:{}:{} called with unexpected arguments", file!(), line!(), column!());
+                $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called with unexpected arguments{}", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()), __injpp_report);
             }
         }
-        let f: unsafe extern "system" fn($($arg_ty),*) -> $ret = fake;
+        let f: unsafe extern $abi fn($($arg_ty),*) -> $ret = fake;
         let raw_ptr = f as *const ();
-        (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+        (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<$ret>($abi).__declared::<$ret>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
     // With assign, returns, and times
     (
-        func_type: unsafe extern "system" fn($($arg_name:ident: $arg_ty:ty),*) -> $ret:ty,
+        func_type: unsafe extern $abi:literal fn($($arg_name:ident: $arg_ty:ty),*) -> $ret:ty,
         assign: { $($assign:tt)* },
         returns: $ret_val:expr,
         times: $expected:expr
@@ -1112,11 +2717,12 @@ macro_rules! fake {
         use std::sync::atomic::{AtomicUsize, Ordering};
         static FAKE_COUNTER: AtomicUsize = AtomicUsize::new(0);
         let verifier = CallCountVerifier::WithCount { counter: &FAKE_COUNTER, expected: $expected };
-        unsafe extern "system" fn fake($($arg_name: $arg_ty),*) -> $ret {
+        unsafe extern $abi fn fake($($arg_name: $arg_ty),*) -> $ret {
+            $crate::__fake_called!(fake);
             if true {
                 let prev = FAKE_COUNTER.fetch_add(1, Ordering::SeqCst);
                 if prev >= $expected {
-                    panic!("Fake function defined at {}:{}:{} called more times than expected", file!(), line!(), column!());
+                    $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called more times than expected", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()));
                 }
                 { $($assign)* }
                 $ret_val
@@ -1124,18 +2730,19 @@ macro_rules! fake {
                 unreachable!()
             }
         }
-        let f: unsafe extern "system" fn($($arg_ty),*) -> $ret = fake;
+        let f: unsafe extern $abi fn($($arg_ty),*) -> $ret = fake;
         let raw_ptr = f as *const ();
-        (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+        (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<$ret>($abi).__declared::<$ret>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
     // With assign and returns
     (
-        func_type: unsafe extern "system" fn($($arg_name:ident: $arg_ty:ty),*) -> $ret:ty,
+        func_type: unsafe extern $abi:literal fn($($arg_name:ident: $arg_ty:ty),*) -> $ret:ty,
         assign: { $($assign:tt)* },
         returns: $ret_val:expr
     ) => {{
         let verifier = CallCountVerifier::Dummy;
-        unsafe extern "system" fn fake($($arg_name: $arg_ty),*) -> $ret {
+        unsafe extern $abi fn fake($($arg_name: $arg_ty),*) -> $ret {
+            $crate::__fake_called!(fake);
             if true {
                 { $($assign)* }
                 $ret_val
@@ -1143,54 +2750,56 @@ macro_rules! fake {
                 unreachable!()
             }
         }
-        let f: unsafe extern "system" fn($($arg_ty),*) -> $ret = fake;
+        let f: unsafe extern $abi fn($($arg_ty),*) -> $ret = fake;
         let raw_ptr = f as *const ();
-        (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+        (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<$ret>($abi).__declared::<$ret>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
     // With returns and times
     (
-        func_type: unsafe extern "system" fn($($arg_name:ident: $arg_ty:ty),*) -> $ret:ty,
+        func_type: unsafe extern $abi:literal fn($($arg_name:ident: $arg_ty:ty),*) -> $ret:ty,
         returns: $ret_val:expr,
         times: $expected:expr
     ) => {{
         use std::sync::atomic::{AtomicUsize, Ordering};
         static FAKE_COUNTER: AtomicUsize = AtomicUsize::new(0);
         let verifier = CallCountVerifier::WithCount { counter: &FAKE_COUNTER, expected: $expected };
-        unsafe extern "system" fn fake($($arg_name: $arg_ty),*) -> $ret {
+        unsafe extern $abi fn fake($($arg_name: $arg_ty),*) -> $ret {
+            $crate::__fake_called!(fake);
             if true {
                 let prev = FAKE_COUNTER.fetch_add(1, Ordering::SeqCst);
                 if prev >= $expected {
-                    panic!("Fake function defined at {}:{}:{} called more times than expected", file!(), line!(), column!());
+                    $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called more times than expected", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()));
                 }
                 $ret_val
             } else {
                 unreachable!()
             }
         }
-        let f: unsafe extern "system" fn($($arg_ty),*) -> $ret = fake;
+        let f: unsafe extern $abi fn($($arg_ty),*) -> $ret = fake;
         let raw_ptr = f as *const ();
-        (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+        (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<$ret>($abi).__declared::<$ret>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
     (
-        func_type: unsafe extern "system" fn($($arg_name:ident: $arg_ty:ty),*) -> $ret:ty,
+        func_type: unsafe extern $abi:literal fn($($arg_name:ident: $arg_ty:ty),*) -> $ret:ty,
         returns: $ret_val:expr
     ) => {{
          let verifier = CallCountVerifier::Dummy;
-         unsafe extern "system" fn fake($($arg_name: $arg_ty),*) -> $ret {
+         unsafe extern $abi fn fake($($arg_name: $arg_ty),*) -> $ret {
+             $crate::__fake_called!(fake);
              if true {
                  $ret_val
              } else {
                  unreachable!()
              }
          }
-         let f: unsafe extern "system" fn($($arg_ty),*) -> $ret = fake;
+         let f: unsafe extern $abi fn($($arg_ty),*) -> $ret = fake;
          let raw_ptr = f as *const ();
-         (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+         (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<$ret>($abi).__declared::<$ret>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
-    // === EXTERN "system" UNIT RETURNING FUNCTIONS ===
+    // === EXTERN "<abi>" UNIT RETURNING FUNCTIONS ===
     // With when, assign, and times
     (
-        func_type: unsafe extern "system" fn($($arg_name:ident: $arg_ty:ty),*) -> (),
+        func_type: unsafe extern $abi:literal fn($($arg_name:ident: $arg_ty:ty),*) -> (),
         when: $cond:expr,
         assign: { $($assign:tt)* },
         times: $expected:expr
@@ -1198,82 +2807,89 @@ macro_rules! fake {
         use std::sync::atomic::{AtomicUsize, Ordering};
         static FAKE_COUNTER: AtomicUsize = AtomicUsize::new(0);
         let verifier = CallCountVerifier::WithCount { counter: &FAKE_COUNTER, expected: $expected };
-        unsafe extern "system" fn fake($($arg_name: $arg_ty),*) {
-            if $cond {
+        unsafe extern $abi fn fake($($arg_name: $arg_ty),*) {
+            $crate::__fake_called!(fake);
+            let (__injpp_matched, __injpp_report) = $crate::__when_report!($crate; $cond; $($arg_name),*);
+            if __injpp_matched {
                 let prev = FAKE_COUNTER.fetch_add(1, Ordering::SeqCst);
                 if prev >= $expected {
-                    panic!("Fake function defined at {}:{}:{} called more times than expected", file!(), line!(), column!());
+                    $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called more times than expected", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()));
                 }
                 { $($assign)* }
             } else {
-                panic!("Fake function defined at {}:{}:{} called with unexpected arguments", file!(), line!(), column!());
+                $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called with unexpected arguments{}", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()), __injpp_report);
             }
         }
-        let f: unsafe extern "system" fn($($arg_ty),*) = fake;
+        let f: unsafe extern $abi fn($($arg_ty),*) = fake;
         let raw_ptr = f as *const ();
-        (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+        (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<()>($abi).__declared::<()>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
     // With when and times (no assign).
     (
-        func_type: unsafe extern "system" fn($($arg_name:ident: $arg_ty:ty),*) -> (),
+        func_type: unsafe extern $abi:literal fn($($arg_name:ident: $arg_ty:ty),*) -> (),
         when: $cond:expr,
         times: $expected:expr
     ) => {{
         use std::sync::atomic::{AtomicUsize, Ordering};
         static FAKE_COUNTER: AtomicUsize = AtomicUsize::new(0);
         let verifier = CallCountVerifier::WithCount { counter: &FAKE_COUNTER, expected: $expected };
-        unsafe extern "system" fn fake($($arg_name: $arg_ty),*) {
-            if $cond {
+        unsafe extern $abi fn fake($($arg_name: $arg_ty),*) {
+            $crate::__fake_called!(fake);
+            let (__injpp_matched, __injpp_report) = $crate::__when_report!($crate; $cond; $($arg_name),*);
+            if __injpp_matched {
                 let prev = FAKE_COUNTER.fetch_add(1, Ordering::SeqCst);
                 if prev >= $expected {
-                    panic!("Fake function defined at {}:{}:{} called more times than expected", file!(), line!(), column!());
+                    $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called more times than expected", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()));
                 }
             } else {
-                panic!("Fake function defined at {}:{}:{} called with unexpected arguments", file!(), line!(), column!());
+                $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called with unexpected arguments{}", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()), __injpp_report);
             }
         }
-        let f: unsafe extern "system" fn($($arg_ty),*) = fake;
+        let f: unsafe extern $abi fn($($arg_ty),*) = fake;
         let raw_ptr = f as *const ();
-        (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+        (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<()>($abi).__declared::<()>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
     // With when and assign (no times).
     (
-        func_type: unsafe extern "system" fn($($arg_name:ident: $arg_ty:ty),*) -> (),
+        func_type: unsafe extern $abi:literal fn($($arg_name:ident: $arg_ty:ty),*) -> (),
         when: $cond:expr,
         assign: { $($assign:tt)* }
     ) => {{
         let verifier = CallCountVerifier::Dummy;
-        unsafe extern "system" fn fake($($arg_name: $arg_ty),*) {
-            if $cond {
+        unsafe extern $abi fn fake($($arg_name: $arg_ty),*) {
+            $crate::__fake_called!(fake);
+            let (__injpp_matched, __injpp_report) = $crate::__when_report!($crate; $cond; $($arg_name),*);
+            if __injpp_matched {
                 { $($assign)* }
             } else {
-                panic!("Fake function defined at {}:{}:{} called with unexpected arguments", file!(), line!(), column!());
+                $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called with unexpected arguments{}", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()), __injpp_report);
             }
         }
-        let f: unsafe extern "system" fn($($arg_ty),*) = fake;
+        let f: unsafe extern $abi fn($($arg_ty),*) = fake;
         let raw_ptr = f as *const ();
-        (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+        (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<()>($abi).__declared::<()>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
     // With assign only
     (
-        func_type: unsafe extern "system" fn($($arg_name:ident: $arg_ty:ty),*) -> (),
+        func_type: unsafe extern $abi:literal fn($($arg_name:ident: $arg_ty:ty),*) -> (),
         assign: { $($assign:tt)* }
     ) => {{
         let verifier = CallCountVerifier::Dummy;
-        unsafe extern "system" fn fake($($arg_name: $arg_ty),*) {
+        unsafe extern $abi fn fake($($arg_name: $arg_ty),*) {
+            $crate::__fake_called!(fake);
             if true {
                 { $($assign)* }
             } else {
                 unreachable!()
             }
         }
-        let f: unsafe extern "system" fn($($arg_ty),*) = fake;
+        let f: unsafe extern $abi fn($($arg_ty),*) = fake;
         let raw_ptr = f as *const ();
-        (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+        (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<()>($abi).__declared::<()>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
     // With assign and times
     (
-        func_type: unsafe extern "system" fn($($arg_name:ident: $arg_ty:ty),*) -> (),
+        func_type: unsafe extern $abi:literal fn($($arg_name:ident: $arg_ty:ty),*) -> (),
         assign: { $($assign:tt)* },
         times: $expected:expr
     ) => {{
@@ -1281,54 +2897,57 @@ macro_rules! fake {
         use std::sync::atomic::{AtomicUsize, Ordering};
          static FAKE_COUNTER: AtomicUsize = AtomicUsize::new(0);
          let verifier = CallCountVerifier::WithCount { counter: &FAKE_COUNTER, expected: $expected };
-         unsafe extern "system" fn fake($($arg_name: $arg_ty),*) {
+         unsafe extern $abi fn fake($($arg_name: $arg_ty),*) {
+             $crate::__fake_called!(fake);
              if true {
                  let prev = FAKE_COUNTER.fetch_add(1, Ordering::SeqCst);
                  if prev >= $expected {
-                     panic!("Fake function defined at {}:{}:{} called more times than expected", file!(), line!(), column!());
+                     $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called more times than expected", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()));
                  }
                  { $($assign)* }
              } else {
-                 panic!("Fake function defined at {}:{}:{} called with unexpected arguments", file!(), line!(), column!());
+                 $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called with unexpected arguments", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()));
              }
          }
-         let f: unsafe extern "system" fn($($arg_ty),*) = fake;
+         let f: unsafe extern $abi fn($($arg_ty),*) = fake;
          let raw_ptr = f as *const ();
-         (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+         (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<()>($abi).__declared::<()>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
     // With times only (when defaults to true, no assign).
     (
-        func_type: unsafe extern "system" fn($($arg_name:ident: $arg_ty:ty),*) -> (),
+        func_type: unsafe extern $abi:literal fn($($arg_name:ident: $arg_ty:ty),*) -> (),
         times: $expected:expr
     ) => {{
          use std::sync::atomic::{AtomicUsize, Ordering};
          static FAKE_COUNTER: AtomicUsize = AtomicUsize::new(0);
          let verifier = CallCountVerifier::WithCount { counter: &FAKE_COUNTER, expected: $expected };
-         unsafe extern "system" fn fake($($arg_name: $arg_ty),*) {
+         unsafe extern $abi fn fake($($arg_name: $arg_ty),*) {
+             $crate::__fake_called!(fake);
              if true {
                  let prev = FAKE_COUNTER.fetch_add(1, Ordering::SeqCst);
                  if prev >= $expected {
-                     panic!("Fake function defined at {}:{}:{} called more times than expected", file!(), line!(), column!());
+                     $crate::__fake_panic!("Fake function defined at {}:{}:{}{} called more times than expected", file!(), line!(), column!(), $crate::interface::injector::__fake_label(fake as *const ()));
                  }
              } else {
                  unreachable!()
              }
          }
-         let f: unsafe extern "system" fn($($arg_ty),*) = fake;
+         let f: unsafe extern $abi fn($($arg_ty),*) = fake;
          let raw_ptr = f as *const ();
-         (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+         (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<()>($abi).__declared::<()>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
     // With neither (no when, no times, no assign, no returns).
     (
-        func_type: unsafe extern "system" fn($($arg_name:ident: $arg_ty:ty),*) -> ()
+        func_type: unsafe extern $abi:literal fn($($arg_name:ident: $arg_ty:ty),*) -> ()
     ) => {{
          let verifier = CallCountVerifier::Dummy;
-         unsafe extern "system" fn fake($($arg_name: $arg_ty),*) {
+         unsafe extern $abi fn fake($($arg_name: $arg_ty),*) {
+             $crate::__fake_called!(fake);
              if true { } else { unreachable!() }
          }
-         let f: unsafe extern "system" fn($($arg_ty),*) = fake;
+         let f: unsafe extern $abi fn($($arg_ty),*) = fake;
          let raw_ptr = f as *const ();
-         (unsafe { FuncPtr::new(raw_ptr, std::any::type_name_of_val(&f)) }, verifier)
+         (unsafe { FuncPtr::new_with_type_id(raw_ptr, std::any::type_name_of_val(&f), $crate::interface::injector::__type_id_of_val(&f)).__returning::<()>($abi).__declared::<()>(&[$(::std::mem::size_of::<$arg_ty>()),*]) }, verifier)
     }};
 }
 
@@ -0,0 +1,122 @@
+use crate::interface::injector::InjectorPP;
+use crate::interface::verifier::CallCountVerifier;
+
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+
+/// Produces the return value of a mocked function.
+type Produce<R> = Box<dyn FnMut() -> R + Send>;
+
+/// A typed fake builder, returned by the `mock_*` helpers generated by `#[mockable]`.
+///
+/// The fake is installed when the builder is dropped, normally at the end of the statement
+/// that created it, so its settings can be chained in any order.
+///
+/// # Example
+///
+/// ```rust
+/// use injectorpp::interface::injector::*;
+///
+/// #[injectorpp::mockable]
+/// fn retry_limit(service: &str) -> u32 {
+///     std::hint::black_box(service.len() as u32)
+/// }
+///
+/// let mut injector = InjectorPP::new();
+/// mock_retry_limit(&mut injector).returns(0).times(1);
+///
+/// assert_eq!(retry_limit("billing"), 0);
+/// ```
+pub struct Mock<'a, R: 'static> {
+    injector: &'a mut InjectorPP,
+    helper: &'static str,
+    install: fn(&mut InjectorPP, Produce<R>),
+    produce: Option<Produce<R>>,
+    times: Option<usize>,
+}
+
+impl<'a, R: 'static> Mock<'a, R> {
+    /// Creates a builder. Used internally by `#[mockable]`.
+    #[doc(hidden)]
+    pub fn __new(
+        injector: &'a mut InjectorPP,
+        helper: &'static str,
+        install: fn(&mut InjectorPP, Box<dyn FnMut() -> R + Send>),
+        produce: Option<Box<dyn FnMut() -> R + Send>>,
+    ) -> Self {
+        Self {
+            injector,
+            helper,
+            install,
+            produce,
+            times: None,
+        }
+    }
+
+    /// Makes the fake return a clone of `value` on every call.
+    pub fn returns(mut self, value: R) -> Self
+    where
+        R: Clone + Send,
+    {
+        self.produce = Some(Box::new(move || value.clone()));
+        self
+    }
+
+    /// Makes the fake return what `produce` returns, called once per call.
+    pub fn returns_with(mut self, produce: impl FnMut() -> R + Send + 'static) -> Self {
+        self.produce = Some(Box::new(produce));
+        self
+    }
+
+    /// Expects the fake to be called exactly `times` times before the injector is dropped.
+    pub fn times(mut self, times: usize) -> Self {
+        self.times = Some(times);
+        self
+    }
+}
+
+impl<T: Clone + Send + 'static> Mock<'_, std::io::Result<T>> {
+    /// Makes the fake return `Ok` with a clone of `value` on every call.
+    ///
+    /// `std::io::Error` is not `Clone`, so `returns()` cannot be used for I/O results.
+    pub fn returns_ok(self, value: T) -> Self {
+        self.returns_with(move || Ok(value.clone()))
+    }
+
+    /// Makes the fake return an error of `kind` on every call.
+    pub fn returns_err(self, kind: std::io::ErrorKind) -> Self {
+        self.returns_with(move || Err(kind.into()))
+    }
+}
+
+impl<R: 'static> Drop for Mock<'_, R> {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            return;
+        }
+
+        let Some(mut produce) = self.produce.take() else {
+            panic!(
+                "{} needs returns() or returns_with() to install a fake",
+                self.helper
+            );
+        };
+
+        if let Some(expected) = self.times {
+            // The verifier needs a counter outliving every injector, like the one `fake!`
+            // keeps in a static. One is leaked per fake expecting a call count.
+            let counter: &'static AtomicUsize = Box::leak(Box::new(AtomicUsize::new(0)));
+            let helper = self.helper;
+            self.injector
+                .push_verifier(CallCountVerifier::WithCount { counter, expected });
+            produce = Box::new(move || {
+                if counter.fetch_add(1, Ordering::SeqCst) >= expected {
+                    panic!("Fake installed by {helper} called more times than expected");
+                }
+                produce()
+            });
+        }
+
+        (self.install)(self.injector, produce);
+    }
+}
@@ -0,0 +1,38 @@
+/// Describes a patch that is currently applied to a function.
+///
+/// Returned by `InjectorPP::active_patches()` and `InjectorPP::all_active_patches()`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PatchInfo {
+    /// Address of the patched function.
+    pub func_addr: usize,
+    /// Name of the patched function, when it can be resolved from the dynamic symbol table.
+    pub symbol: Option<String>,
+    /// Path of the executable or shared library holding the patched function, when the loader
+    /// knows it.
+    pub module: Option<String>,
+    /// Number of bytes overwritten at the start of the function.
+    pub patch_size: usize,
+    /// Address of the JIT stub the function branches to, or 0 when no stub is used.
+    pub jit_addr: usize,
+    /// `true` when the patch uses thread-local dispatch, `false` for global patching.
+    pub thread_local: bool,
+}
+
+impl PatchInfo {
+    pub(crate) fn new(
+        func_addr: usize,
+        patch_size: usize,
+        jit_addr: usize,
+        thread_local: bool,
+    ) -> Self {
+        Self {
+            func_addr,
+            symbol: crate::injector_core::symbols::symbol_name(func_addr),
+            module: crate::injector_core::symbols::module_name(func_addr),
+            patch_size,
+            jit_addr,
+            thread_local,
+        }
+    }
+}
@@ -0,0 +1,150 @@
+/// How a fake reaches the callers of the function it replaces, chosen for one fake with
+/// `WhenCalledBuilder::strategy()`, or for every fake of an injector with
+/// `InjectorPP::set_default_strategy()`.
+///
+/// Which strategies work depends on the platform, see `is_supported()`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PatchStrategy {
+    /// Overwrites the first instructions of the function with a branch to the fake, so every
+    /// call reaches the fake, including calls from the object defining the function.
+    #[default]
+    InlineBranch,
+    /// Rewrites the GOT entries referring to the function in every loaded ELF object, leaving
+    /// the code of the function untouched.
+    ///
+    /// For functions of shared libraries, such as `memset` or `getenv` of libc: no page of
+    /// the library is written to, the size of the function does not matter, and threads
+    /// running the function meanwhile are not disturbed. Only calls made through the GOT are
+    /// faked, so calls the library makes to its own functions are not. While the fake is
+    /// installed, the address of the function read from a GOT entry is the address of the
+    /// fake.
+    ///
+    /// Linux only. As GOT entries are shared by every thread, it requires an injector created
+    /// with `InjectorPP::new_global()`.
+    Got,
+    /// Rewrites the call instructions calling the function, leaving the code of the function
+    /// untouched. This strategy scans every function of the main program for its calls;
+    /// `WhenCalledBuilder::call_sites_in()` and `WhenCalledBuilder::at_call_sites()` limit
+    /// the rewritten calls to those of given callers or at given addresses.
+    ///
+    /// For functions too small or too hot to patch, and to fake a function for the module
+    /// under test only. Direct calls are rewritten, as are calls through a GOT or import
+    /// table slot holding the function (`call [rip+disp32]` on x86_64) and calls of a PLT
+    /// stub or import thunk leading to it. Calls through function pointers, calls the
+    /// compiler turned into jumps, and inlined copies of the function are not faked.
+    ///
+    /// Linux x86_64 and aarch64 only; `call_sites_in()` and `at_call_sites()` also work on
+    /// Windows and macOS. As the rewritten calls are run by every thread, it requires an
+    /// injector created with `InjectorPP::new_global()`.
+    CallSites,
+    /// Delegates patching to the external hooking engine registered with
+    /// `InjectorPP::set_hook_engine()`, such as the `Interceptor` of frida-gum.
+    ///
+    /// For platforms and prologues the built-in patcher does not support. The fake is still
+    /// generated by injectorpp. As engines hook functions for every thread, it requires an
+    /// injector created with `InjectorPP::new_global()`. Requires the `external-engine`
+    /// feature.
+    #[cfg(feature = "external-engine")]
+    External,
+}
+
+impl PatchStrategy {
+    /// Returns whether this strategy can fake functions on the running platform. Strategies
+    /// writing JIT code also need the process to be allowed to map executable memory, which
+    /// is probed on first use. `External` is supported once an engine is registered.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use injectorpp::interface::injector::*;
+    ///
+    /// let strategy = if PatchStrategy::Got.is_supported() {
+    ///     PatchStrategy::Got
+    /// } else {
+    ///     PatchStrategy::InlineBranch
+    /// };
+    /// assert!(PatchStrategy::supported().contains(&strategy));
+    /// ```
+    pub fn is_supported(self) -> bool {
+        self.unsupported_reason().is_none()
+    }
+
+    /// Returns the strategies supported on the running platform, `InlineBranch` first when it
+    /// is.
+    pub fn supported() -> Vec<PatchStrategy> {
+        let mut strategies = vec![
+            PatchStrategy::InlineBranch,
+            PatchStrategy::Got,
+            PatchStrategy::CallSites,
+        ];
+        #[cfg(feature = "external-engine")]
+        strategies.push(PatchStrategy::External);
+
+        strategies.retain(|strategy| strategy.is_supported());
+        strategies
+    }
+
+    /// Returns why this strategy cannot fake functions on the running platform, or `None` if
+    /// it can.
+    pub(crate) fn unsupported_reason(self) -> Option<&'static str> {
+        let platform = match self {
+            PatchStrategy::InlineBranch => cfg!(any(
+                target_arch = "x86_64",
+                target_arch = "aarch64",
+                target_arch = "arm64ec",
+                target_arch = "arm",
+                target_arch = "x86",
+                target_arch = "riscv64"
+            )),
+            PatchStrategy::Got => cfg!(all(
+                target_os = "linux",
+                any(
+                    target_arch = "x86_64",
+                    target_arch = "aarch64",
+                    target_arch = "arm"
+                )
+            )),
+            PatchStrategy::CallSites => cfg!(all(
+                target_os = "linux",
+                any(target_arch = "x86_64", target_arch = "aarch64")
+            )),
+            #[cfg(feature = "external-engine")]
+            PatchStrategy::External => {
+                if crate::injector_core::hook_engine::engine().is_none() {
+                    return Some("no engine is registered with InjectorPP::set_hook_engine()");
+                }
+                // The engine brings its own platform support and memory.
+                return None;
+            }
+        };
+
+        if !platform {
+            return Some(match self {
+                PatchStrategy::InlineBranch => "it is only available on x86_64, aarch64 and arm",
+                PatchStrategy::Got => "it is only available on Linux",
+                PatchStrategy::CallSites => {
+                    "it finds the calls in the symbol table of the program, which is only \
+                     read on Linux x86_64 and aarch64. Use call_sites_in() or at_call_sites() \
+                     instead"
+                }
+                #[cfg(feature = "external-engine")]
+                PatchStrategy::External => unreachable!(),
+            });
+        }
+        // Calls through the GOT need no JIT code unless the fake returns a fixed value.
+        if self != PatchStrategy::Got
+            && !crate::injector_core::common::executable_memory_available()
+        {
+            #[cfg(target_os = "macos")]
+            if crate::injector_core::macosapi::hardened_runtime() {
+                return Some(
+                    "the process runs with the hardened runtime without the \
+                     `com.apple.security.cs.allow-jit` entitlement",
+                );
+            }
+            return Some("the system does not let the process map executable memory");
+        }
+        None
+    }
+}
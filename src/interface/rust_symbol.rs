@@ -0,0 +1,88 @@
+//! Rust functions looked up by their path in the symbol tables of the loaded modules, so
+//! functions that are private, or otherwise cannot be named from the test, can be faked.
+
+use crate::interface::func_ptr::FuncPtr;
+
+/// A function pointer type a Rust function can be declared with, such as
+/// `fn(&str, u16) -> std::io::Result<()>`.
+///
+/// Implemented for `fn` and `unsafe fn` pointers of up to twelve arguments. Borrowed arguments
+/// are declared `'static`, as in `fn(&'static str) -> u16`, and fakes may borrow them for any
+/// lifetime.
+pub trait RustFnPointer: Copy + 'static {
+    #[doc(hidden)]
+    fn __returning(func: FuncPtr) -> FuncPtr;
+}
+
+macro_rules! impl_rust_fn_pointer {
+    ($($arg:ident),*) => {
+        impl<R: 'static, $($arg: 'static),*> RustFnPointer for fn($($arg),*) -> R {
+            fn __returning(func: FuncPtr) -> FuncPtr {
+                func.__returning::<R>("Rust")
+            }
+        }
+
+        impl<R: 'static, $($arg: 'static),*> RustFnPointer for unsafe fn($($arg),*) -> R {
+            fn __returning(func: FuncPtr) -> FuncPtr {
+                func.__returning::<R>("Rust")
+            }
+        }
+    };
+}
+
+impl_rust_fn_pointer!();
+impl_rust_fn_pointer!(A0);
+impl_rust_fn_pointer!(A0, A1);
+impl_rust_fn_pointer!(A0, A1, A2);
+impl_rust_fn_pointer!(A0, A1, A2, A3);
+impl_rust_fn_pointer!(A0, A1, A2, A3, A4);
+impl_rust_fn_pointer!(A0, A1, A2, A3, A4, A5);
+impl_rust_fn_pointer!(A0, A1, A2, A3, A4, A5, A6);
+impl_rust_fn_pointer!(A0, A1, A2, A3, A4, A5, A6, A7);
+impl_rust_fn_pointer!(A0, A1, A2, A3, A4, A5, A6, A7, A8);
+impl_rust_fn_pointer!(A0, A1, A2, A3, A4, A5, A6, A7, A8, A9);
+impl_rust_fn_pointer!(A0, A1, A2, A3, A4, A5, A6, A7, A8, A9, A10);
+impl_rust_fn_pointer!(A0, A1, A2, A3, A4, A5, A6, A7, A8, A9, A10, A11);
+
+impl FuncPtr {
+    /// Looks up the Rust functions whose path matches `pattern` and returns them, with their
+    /// path, as functions of the signature `F`.
+    ///
+    /// Paths are matched without the hash ending their symbol, as in
+    /// `mycrate::net::tcp::connect` or `<mycrate::Conn as core::ops::Drop>::drop`. In
+    /// `pattern`, `*` stands for any part of a path segment and `**` for any part of the path,
+    /// so `mycrate::net::*::connect` matches the `connect` functions of the modules of
+    /// `mycrate::net`. The instances of a generic function share its path.
+    ///
+    /// Functions are looked up in the symbol tables of the loaded modules, so only functions
+    /// that are not inlined, and modules that are not stripped, are found. Only Linux is
+    /// supported; elsewhere nothing is found. The signature is not checked against the
+    /// functions found, and must match their declaration.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use injectorpp::interface::injector::*;
+    ///
+    /// #[inline(never)]
+    /// fn parse_port(text: &str) -> u16 {
+    ///     text.parse().unwrap_or(0)
+    /// }
+    ///
+    /// assert_eq!(parse_port("80"), 80);
+    /// let found = FuncPtr::from_symbol_pattern::<fn(&'static str) -> u16>("**::parse_port");
+    /// #[cfg(target_os = "linux")]
+    /// assert_eq!(found.len(), 1);
+    /// ```
+    pub fn from_symbol_pattern<F: RustFnPointer>(pattern: &str) -> Vec<(String, Self)> {
+        crate::injector_core::symbols::functions_matching(pattern)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(path, addr)| {
+                // Without a type id, so fakes borrowing arguments for any lifetime match.
+                let func = unsafe { FuncPtr::new(addr as *const (), std::any::type_name::<F>()) };
+                (path, F::__returning(func))
+            })
+            .collect()
+    }
+}
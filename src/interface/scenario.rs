@@ -0,0 +1,219 @@
+use std::any::Any;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// An ordered script of calls spanning several faked functions, built with `scenario!`.
+///
+/// Each step names a function and the value its call returns. The calls must come in the order
+/// of the steps: a call of another function than the one of the next step, or a call after the
+/// last step, panics. A function listed several times returns the value of each of its steps in
+/// turn, so "the first send fails, the second one succeeds" is two steps of `send`.
+///
+/// The steps share one cursor, so the fakes of the functions coordinate without statics. When
+/// the last clone of the scenario is dropped, which is after the injector faking its functions
+/// is dropped, it panics if steps were never reached, unless the thread is panicking.
+///
+/// Steps run from fakes installed with `will_execute_closure()`, which requires thread-local
+/// dispatch: they are not available with `InjectorPP::new_global()`.
+///
+/// # Example
+///
+/// ```rust
+/// use injectorpp::interface::injector::*;
+/// use std::io;
+///
+/// #[inline(never)]
+/// fn connect(addr: &str) -> io::Result<()> {
+///     std::hint::black_box(Err(io::Error::other(addr.to_string())))
+/// }
+///
+/// #[inline(never)]
+/// fn send(buf: &[u8]) -> io::Result<usize> {
+///     std::hint::black_box(Err(io::Error::other(buf.len().to_string())))
+/// }
+///
+/// #[inline(never)]
+/// fn recv(buf: &mut [u8]) -> io::Result<usize> {
+///     std::hint::black_box(Err(io::Error::other(buf.len().to_string())))
+/// }
+///
+/// let mut injector = InjectorPP::new();
+/// let scenario = injectorpp::scenario!(injector, [
+///     fn (connect)(addr: &str) -> io::Result<()> => Ok(()),
+///     fn (send)(buf: &[u8]) -> io::Result<usize> => Err(io::ErrorKind::WouldBlock.into()),
+///     fn (send)(buf: &[u8]) -> io::Result<usize> => Ok(buf.len()),
+///     fn (recv)(buf: &mut [u8]) -> io::Result<usize> => {
+///         buf[..4].copy_from_slice(b"pong");
+///         Ok(4)
+///     },
+/// ]);
+///
+/// connect("localhost:80").unwrap();
+/// assert_eq!(send(b"ping").unwrap_err().kind(), io::ErrorKind::WouldBlock);
+/// assert_eq!(send(b"ping").unwrap(), 4);
+/// let mut buf = [0u8; 8];
+/// assert_eq!(recv(&mut buf).unwrap(), 4);
+/// assert_eq!(&buf[..4], b"pong");
+/// assert!(scenario.is_done());
+/// ```
+#[derive(Clone)]
+pub struct Scenario {
+    state: Arc<Mutex<ScenarioState>>,
+}
+
+struct ScenarioState {
+    steps: Vec<Step>,
+    /// Index of the next step.
+    next: usize,
+}
+
+struct Step {
+    /// Address of the function called at this step.
+    function: usize,
+    name: &'static str,
+    /// The boxed closure computing the return value, taken when the step runs.
+    run: Option<Box<dyn Any + Send>>,
+}
+
+impl Scenario {
+    /// Creates a scenario without steps. Used internally by `scenario!`.
+    #[doc(hidden)]
+    pub fn __new() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(ScenarioState {
+                steps: Vec::new(),
+                next: 0,
+            })),
+        }
+    }
+
+    /// Returns the number of steps.
+    pub fn len(&self) -> usize {
+        self.state().steps.len()
+    }
+
+    /// Returns whether the scenario has no step.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of steps reached so far.
+    pub fn completed(&self) -> usize {
+        self.state().next
+    }
+
+    /// Returns whether every step was reached.
+    pub fn is_done(&self) -> bool {
+        let state = self.state();
+        state.next == state.steps.len()
+    }
+
+    /// Checks that every step was reached, before the scenario is dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a step was not reached.
+    pub fn verify(&self) {
+        if let Some(message) = self.state().unfinished() {
+            panic!("{}", message);
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, ScenarioState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Appends a step calling the function at `function`, whose return value `run` computes.
+    /// Returns whether it is the first step of the function, whose fake must be installed.
+    /// Used internally by `scenario!`.
+    #[doc(hidden)]
+    pub fn __step(&self, function: usize, name: &'static str, run: Box<dyn Any + Send>) -> bool {
+        let mut state = self.state();
+        let first = state.steps.iter().all(|step| step.function != function);
+        state.steps.push(Step {
+            function,
+            name,
+            run: Some(run),
+        });
+        first
+    }
+
+    /// Runs the next step, which must call the function at `function`, by passing its closure,
+    /// of type `F`, to `call`. Used internally by `scenario!`.
+    #[doc(hidden)]
+    pub fn __call<F: 'static, R>(
+        &self,
+        function: usize,
+        name: &str,
+        call: impl FnOnce(&mut F) -> R,
+    ) -> R {
+        let mut state = self.state();
+        let index = state.next;
+        let count = state.steps.len();
+        let Some(step) = state.steps.get_mut(index) else {
+            panic!(
+                "Scenario: {} was called after the last of its {} steps",
+                name, count
+            );
+        };
+        if step.function != function {
+            panic!(
+                "Scenario: step {} of {} expects a call of {}, but {} was called",
+                index + 1,
+                count,
+                step.name,
+                name
+            );
+        }
+        let mut run = step.run.take().expect("a scenario step runs once");
+        state.next += 1;
+
+        // Called without the lock, as the step may call other functions of the scenario.
+        drop(state);
+        let Some(run) = run.downcast_mut::<F>() else {
+            panic!(
+                "Scenario: step {} of {} was declared with another signature of {}",
+                index + 1,
+                count,
+                name
+            );
+        };
+        call(run)
+    }
+}
+
+impl ScenarioState {
+    /// Describes the first step not reached, if any.
+    fn unfinished(&self) -> Option<String> {
+        self.steps.get(self.next).map(|step| {
+            format!(
+                "Scenario stopped at step {} of {}: {} was never called",
+                self.next + 1,
+                self.steps.len(),
+                step.name
+            )
+        })
+    }
+}
+
+impl Drop for ScenarioState {
+    fn drop(&mut self) {
+        // Avoid double panic
+        if std::thread::panicking() {
+            return;
+        }
+        if let Some(message) = self.unfinished() {
+            panic!("{}", message);
+        }
+    }
+}
+
+impl std::fmt::Debug for Scenario {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = self.state();
+        let steps: Vec<&str> = state.steps.iter().map(|step| step.name).collect();
+        f.debug_struct("Scenario")
+            .field("steps", &steps)
+            .field("completed", &state.next)
+            .finish()
+    }
+}
@@ -23,8 +23,18 @@ impl Drop for CallCountVerifier {
                     return;
                 }
 
+                // A fake never reached usually means the function was inlined.
+                let hint = if call_times == 0 {
+                    ". The fake was never reached: if the code under test calls the function, \
+                     the compiler may have inlined it. Mark it #[inline(never)], or check that \
+                     the code under test reaches it with InjectorPP::assert_effective()"
+                } else {
+                    ""
+                };
+
                 panic!(
-                    "Fake function was expected to be called {expected} time(s), but it is actually called {call_times} time(s)"
+                    "Fake function was expected to be called {expected} time(s), but it is actually called {call_times} time(s){}{hint}",
+                    crate::interface::labels::verify_suffix()
                 );
             }
         }
@@ -44,7 +44,7 @@
 //! injector
 //!     .when_called(injectorpp::func!(fn (fs::create_dir_all)(&'static str) -> std::io::Result<()>))
 //!     .will_execute(injectorpp::fake!(
-//!         func_type: fn(path: &str) -> std::io::Result<()>,
+//!         func_type: fn(path: &'static str) -> std::io::Result<()>,
 //!         when: path == "/tmp/target_files",
 //!         returns: Ok(()),
 //!         times: 1
@@ -153,7 +153,7 @@
 //!         fn (complex_generic_multiple_types_func)(&'static str, bool, i32) -> String
 //!     ))
 //!     .will_execute(injectorpp::fake!(
-//!         func_type: fn(a: &str, b: bool, c: i32) -> String,
+//!         func_type: fn(a: &'static str, b: bool, c: i32) -> String,
 //!         when: a == "abc" && b == true && c == 123,
 //!         returns: "Fake value".to_string(),
 //!         times: 1
@@ -245,7 +245,7 @@
 //!             fn (complex_generic_single_type_always_fail_func)(&'static str) -> std::io::Result<()>
 //!         ))
 //!         .will_execute(injectorpp::fake!(
-//!             func_type: fn(path: &str) -> std::io::Result<()>,
+//!             func_type: fn(path: &'static str) -> std::io::Result<()>,
 //!             when: path == "/not/exist/path",
 //!             returns: Ok(()),
 //!             times: 1
@@ -555,6 +555,16 @@
 //! ```
 mod injector_core;
 pub mod interface;
+pub mod utilities;
+
+pub use interface::injector::is_patched;
+
+pub use injectorpp_macros::do_not_fake;
+pub use injectorpp_macros::mockable;
 
+#[doc(hidden)]
+pub use injectorpp_macros::fake_receiver as __fake_receiver;
 #[doc(hidden)]
 pub use injectorpp_macros::func_checked as __func_checked;
+#[doc(hidden)]
+pub use injectorpp_macros::when_report as __when_report;
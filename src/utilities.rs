@@ -0,0 +1,11 @@
+//! Ready-made fakes for common dependencies of code under test.
+
+#[cfg(feature = "tokio")]
+pub mod time;
+#[cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec",
+    target_arch = "arm"
+))]
+pub mod task;
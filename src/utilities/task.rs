@@ -0,0 +1,244 @@
+//! Control over background tasks.
+//!
+//! `SpawnedTasks` holds back the tasks running one async function, by patching the `poll` of
+//! the future the function returns. It does not patch task spawning: `tokio::spawn` is
+//! `#[track_caller]` and generic over the future, so a pointer to it is a shim of a single
+//! instance and patching it would not affect direct calls. As a result, tasks cannot be run
+//! inline when spawned, and tasks running an async block, whose type cannot be named, cannot
+//! be held back.
+
+use crate::injector_core::common::FuncPtrInternal;
+use crate::injector_core::thread_local_registry;
+use crate::interface::injector::*;
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::future::Future;
+use std::pin::Pin;
+use std::ptr::NonNull;
+use std::task::{Context, Poll, Waker};
+
+type PollFn<F> = fn(Pin<&mut F>, &mut Context<'_>) -> Poll<<F as Future>::Output>;
+
+struct Gate {
+    /// Patched address of the future's `poll`.
+    poll_key: usize,
+    /// Futures held back, by address, in the order they were first polled.
+    parked: VecDeque<(usize, Waker)>,
+    /// Futures allowed to run.
+    released: HashSet<usize>,
+    /// Released futures that completed and were not yet reported to `run_next`.
+    completed: HashSet<usize>,
+    /// The task waiting in `run_next`.
+    driver: Option<Waker>,
+}
+
+thread_local! {
+    /// Gates installed on this thread, keyed by the address of the poll shim.
+    static GATES: RefCell<HashMap<usize, Gate>> = RefCell::new(HashMap::new());
+}
+
+/// Holds back the tasks running an async function until the test runs them.
+///
+/// Every future returned by the async function is parked the first time it is polled instead of
+/// running its body, so fire-and-forget tasks spawned by the code under test wait until the test
+/// calls `run_next` or `run_all`. Tasks then run one at a time in the order they were spawned,
+/// which makes their side effects deterministic. The remaining tasks are released when
+/// `SpawnedTasks` is dropped.
+///
+/// Only the futures of the captured async function are held back, whether a task was spawned
+/// to run them or not: a future of the function awaited directly by the test is parked too,
+/// and awaiting it never completes until `run_next` reaches it from another task. A task
+/// spawned with an async block or another function runs until it awaits the captured
+/// function, and is held back from there.
+///
+/// The fake uses thread-local dispatch: the tasks must be polled on the thread that created
+/// `SpawnedTasks`, as on the current-thread runtime used by `#[tokio::test]`.
+///
+/// # Example
+///
+/// ```rust
+/// use injectorpp::utilities::task::SpawnedTasks;
+/// use std::sync::atomic::{AtomicU32, Ordering};
+///
+/// static SENT: AtomicU32 = AtomicU32::new(0);
+///
+/// async fn send_metric(value: u32) {
+///     SENT.fetch_add(value, Ordering::SeqCst);
+/// }
+///
+/// fn record(value: u32) {
+///     tokio::spawn(send_metric(value));
+/// }
+///
+/// #[tokio::main(flavor = "current_thread")]
+/// async fn main() {
+///     let tasks = SpawnedTasks::capture(injectorpp::async_fn!(fn (send_metric)(u32)));
+///
+///     record(1);
+///     record(2);
+///     tokio::task::yield_now().await;
+///
+///     assert_eq!(tasks.pending(), 2);
+///     assert_eq!(SENT.load(Ordering::SeqCst), 0);
+///
+///     tasks.run_all().await;
+///     assert_eq!(SENT.load(Ordering::SeqCst), 3);
+/// }
+/// ```
+pub struct SpawnedTasks {
+    key: usize,
+    _injector: InjectorPP,
+}
+
+impl SpawnedTasks {
+    /// Holds back the futures of the async function. Use the `async_fn!` macro to obtain it.
+    pub fn capture<P: AsyncFnPointer>(_target: P) -> Self {
+        let poll: PollFn<P::Fut> = <P::Fut as Future>::poll;
+        let shim: PollFn<P::Fut> = gated_poll::<P::Fut>;
+        let key = shim as *const () as usize;
+
+        let poll_key = thread_local_registry::method_key_of(&unsafe {
+            FuncPtrInternal::new(NonNull::new(poll as *mut ()).expect("Pointer must not be null"))
+        });
+
+        GATES.with(|gates| {
+            let mut gates = gates.borrow_mut();
+            if gates.contains_key(&key) {
+                panic!("The async function is already captured by SpawnedTasks on this thread");
+            }
+
+            gates.insert(
+                key,
+                Gate {
+                    poll_key,
+                    parked: VecDeque::new(),
+                    released: HashSet::new(),
+                    completed: HashSet::new(),
+                    driver: None,
+                },
+            );
+        });
+
+        let mut injector = InjectorPP::new();
+        unsafe {
+            injector
+                .when_called_unchecked(FuncPtr::new(poll as *const (), ""))
+                .will_execute_raw_unchecked(FuncPtr::new(shim as *const (), ""));
+        }
+
+        Self {
+            key,
+            _injector: injector,
+        }
+    }
+
+    /// Returns the number of tasks held back.
+    pub fn pending(&self) -> usize {
+        GATES.with(|gates| {
+            gates
+                .borrow()
+                .get(&self.key)
+                .map_or(0, |gate| gate.parked.len())
+        })
+    }
+
+    /// Runs the oldest task held back to completion. Returns `false` if there was none.
+    pub async fn run_next(&self) -> bool {
+        let next = GATES.with(|gates| {
+            let mut gates = gates.borrow_mut();
+            let gate = gates.get_mut(&self.key)?;
+            let (addr, waker) = gate.parked.pop_front()?;
+            gate.released.insert(addr);
+
+            Some((addr, waker))
+        });
+
+        let Some((addr, waker)) = next else {
+            return false;
+        };
+        waker.wake();
+
+        std::future::poll_fn(|cx| {
+            GATES.with(|gates| {
+                let mut gates = gates.borrow_mut();
+                let gate = gates
+                    .get_mut(&self.key)
+                    .expect("SpawnedTasks gate was removed");
+                if gate.completed.remove(&addr) {
+                    return Poll::Ready(true);
+                }
+
+                gate.driver = Some(cx.waker().clone());
+                Poll::Pending
+            })
+        })
+        .await
+    }
+
+    /// Runs the tasks held back one at a time, including those they spawn, until none is left.
+    pub async fn run_all(&self) {
+        while self.run_next().await {}
+    }
+}
+
+impl Drop for SpawnedTasks {
+    fn drop(&mut self) {
+        // The patch is removed right after, so the parked tasks run their body when woken.
+        let gate = GATES
+            .try_with(|gates| gates.borrow_mut().remove(&self.key))
+            .ok()
+            .flatten();
+
+        if let Some(gate) = gate {
+            for (_, waker) in gate.parked {
+                waker.wake();
+            }
+        }
+    }
+}
+
+fn gated_poll<F: Future>(fut: Pin<&mut F>, cx: &mut Context<'_>) -> Poll<F::Output> {
+    let shim: PollFn<F> = gated_poll::<F>;
+    let key = shim as *const () as usize;
+    let addr = &*fut as *const F as usize;
+
+    let poll_key = GATES.with(|gates| {
+        let mut gates = gates.borrow_mut();
+        let gate = gates
+            .get_mut(&key)
+            .expect("Task was polled after its SpawnedTasks was dropped");
+
+        if gate.released.contains(&addr) {
+            return Some(gate.poll_key);
+        }
+
+        match gate.parked.iter_mut().find(|(parked, _)| *parked == addr) {
+            Some((_, waker)) => waker.clone_from(cx.waker()),
+            None => gate.parked.push_back((addr, cx.waker().clone())),
+        }
+
+        None
+    });
+
+    let Some(poll_key) = poll_key else {
+        return Poll::Pending;
+    };
+
+    let result = thread_local_registry::call_original(poll_key, || fut.poll(cx));
+    if result.is_ready() {
+        let driver = GATES.with(|gates| {
+            let mut gates = gates.borrow_mut();
+            let gate = gates.get_mut(&key)?;
+            gate.released.remove(&addr);
+            gate.completed.insert(addr);
+            gate.driver.take()
+        });
+
+        if let Some(driver) = driver {
+            driver.wake();
+        }
+    }
+
+    result
+}
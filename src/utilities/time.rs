@@ -0,0 +1,210 @@
+//! Fakes for `tokio::time`.
+
+use crate::injector_core::delay::{self, TimerGuard, VirtualTimer};
+use crate::interface::injector::*;
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+use tokio::time::{Instant, Sleep};
+
+#[derive(Default)]
+struct VirtualClock {
+    elapsed: Duration,
+    sleeps: Vec<Duration>,
+    /// The async fakes with `delay:` awaited, by poll function and future address.
+    delays: HashMap<(usize, usize), PendingDelay>,
+}
+
+struct PendingDelay {
+    /// The elapsed time at which the delay is over.
+    due: Duration,
+    /// The task awaiting the delay.
+    waker: Waker,
+}
+
+impl VirtualClock {
+    /// Returns how long until the first delay awaited by the task of `waker` is over, unless
+    /// it is already over.
+    fn next_delay(&self, waker: &Waker) -> Option<Duration> {
+        self.delays
+            .values()
+            .filter(|delay| delay.due > self.elapsed && delay.waker.will_wake(waker))
+            .map(|delay| delay.due - self.elapsed)
+            .min()
+    }
+}
+
+impl VirtualTimer for Mutex<VirtualClock> {
+    fn sleep(&self, delay: Duration) {
+        let mut clock = self.lock().unwrap_or_else(|e| e.into_inner());
+        clock.sleeps.push(delay);
+        clock.elapsed += delay;
+    }
+
+    fn poll_delay(&self, key: (usize, usize), delay: Duration, cx: &mut Context<'_>) -> bool {
+        let mut clock = self.lock().unwrap_or_else(|e| e.into_inner());
+        match clock.delays.get(&key) {
+            Some(pending) if pending.waker.will_wake(cx.waker()) => {
+                // Over, or the next event of its task, which no sleep of the task came before.
+                let due = pending.due;
+                clock.delays.remove(&key);
+                clock.elapsed = clock.elapsed.max(due);
+                clock.sleeps.push(delay);
+                false
+            }
+            _ => {
+                // Pending until polled again, so that a timeout around the future can elapse
+                // first.
+                let due = clock.elapsed + delay;
+                clock.delays.insert(
+                    key,
+                    PendingDelay {
+                        due,
+                        waker: cx.waker().clone(),
+                    },
+                );
+                cx.waker().wake_by_ref();
+                true
+            }
+        }
+    }
+}
+
+/// Makes `tokio::time` sleeps resolve immediately while keeping track of a virtual clock.
+///
+/// Every `Sleep`, including those created by `sleep`, `sleep_until` and `timeout`, resolves as
+/// soon as it is polled and advances the virtual clock by the time that was left until its
+/// deadline. Backoff and retry loops then run without real waits and without a paused runtime,
+/// and timeouts elapse as soon as the future they guard is pending. Durations are rounded up to
+/// the millisecond, the resolution of the tokio timer. Sleeps awaited concurrently each advance
+/// the clock, so it measures the total time slept.
+///
+/// The `delay:` of fakes also elapses on the virtual clock. A sync fake advances it instead of
+/// sleeping. An async fake, made with `async_return!`, is pending until polled again, unless
+/// a sleep awaited by the same task is over first: a timeout shorter than the delay elapses,
+/// and a longer one lets the fake return. A delayed future whose timeout elapsed is forgotten;
+/// awaited again, it waits for its whole delay.
+///
+/// Sleeps must still be created inside a tokio runtime with the time driver enabled, as with
+/// `#[tokio::test]`. The fake is removed when the mocker is dropped.
+///
+/// # Example
+///
+/// ```rust
+/// use injectorpp::utilities::time::TokioTimeMocker;
+/// use std::time::Duration;
+///
+/// async fn retry<T, E>(mut op: impl FnMut() -> Result<T, E>) -> Result<T, E> {
+///     let mut delay = Duration::from_secs(1);
+///     loop {
+///         match op() {
+///             Ok(value) => return Ok(value),
+///             Err(_) if delay <= Duration::from_secs(4) => {
+///                 tokio::time::sleep(delay).await;
+///                 delay *= 2;
+///             }
+///             Err(e) => return Err(e),
+///         }
+///     }
+/// }
+///
+/// #[tokio::main(flavor = "current_thread")]
+/// async fn main() {
+///     let time = TokioTimeMocker::new();
+///
+///     assert_eq!(retry(|| Err::<(), _>("offline")).await, Err("offline"));
+///     assert_eq!(time.elapsed(), Duration::from_secs(7));
+/// }
+/// ```
+pub struct TokioTimeMocker {
+    clock: Arc<Mutex<VirtualClock>>,
+    _timer: TimerGuard,
+    _injector: InjectorPP,
+}
+
+impl TokioTimeMocker {
+    /// Fast-forwards the sleeps polled on the current thread, like `InjectorPP::new()`.
+    ///
+    /// Use it with a current-thread runtime, which is the default of `#[tokio::test]`.
+    pub fn new() -> Self {
+        Self::install(InjectorPP::new(), false)
+    }
+
+    /// Fast-forwards the sleeps polled on any thread, like `InjectorPP::new_global()`.
+    ///
+    /// Use it with a multi-thread runtime.
+    pub fn new_global() -> Self {
+        Self::install(InjectorPP::new_global(), true)
+    }
+
+    fn install(mut injector: InjectorPP, global: bool) -> Self {
+        let clock = Arc::new(Mutex::new(VirtualClock::default()));
+        let state = clock.clone();
+
+        injector
+            .when_called(crate::func!(
+                <Sleep as Future>::poll,
+                fn(Pin<&mut Sleep>, &mut Context<'_>) -> Poll<()>
+            ))
+            .will_execute_closure(crate::boxed_closure!(
+                move |sleep: Pin<&mut Sleep>, cx: &mut Context<'_>| {
+                    let remaining = sleep.deadline().saturating_duration_since(Instant::now());
+                    let remaining =
+                        Duration::from_millis(remaining.as_nanos().div_ceil(1_000_000) as u64);
+
+                    let mut clock = state.lock().unwrap_or_else(|e| e.into_inner());
+                    if let Some(delay) = clock.next_delay(cx.waker()) {
+                        // A delay of a fake awaited by the same task is over first.
+                        if delay <= remaining {
+                            clock.elapsed += delay;
+                            cx.waker().wake_by_ref();
+                            return Poll::Pending;
+                        }
+                    }
+
+                    // The sleep is over first, e.g. a timeout dropping the delayed future.
+                    let waker = cx.waker();
+                    clock
+                        .delays
+                        .retain(|_, delay| !delay.waker.will_wake(waker));
+                    clock.sleeps.push(remaining);
+                    clock.elapsed += remaining;
+
+                    Poll::Ready(())
+                },
+                fn(Pin<&mut Sleep>, &mut Context<'_>) -> Poll<()>
+            ));
+
+        Self {
+            _timer: delay::install_timer(global, clock.clone()),
+            clock,
+            _injector: injector,
+        }
+    }
+
+    /// Returns the total time slept since the mocker was created.
+    pub fn elapsed(&self) -> Duration {
+        self.clock().elapsed
+    }
+
+    /// Returns the duration of every fast-forwarded sleep and elapsed delay of a fake, in the
+    /// order they were over.
+    pub fn sleeps(&self) -> Vec<Duration> {
+        self.clock().sleeps.clone()
+    }
+
+    fn clock(&self) -> MutexGuard<'_, VirtualClock> {
+        self.clock.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Default for TokioTimeMocker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,135 @@
+use injectorpp::interface::injector::*;
+
+#[inline(never)]
+extern "C-unwind" fn checksum(data: *const u8, len: usize) -> u32 {
+    std::hint::black_box(data as usize as u32 ^ len as u32)
+}
+
+#[inline(never)]
+extern "system" fn reset_counter(counter: *mut u32) {
+    unsafe { *counter = std::hint::black_box(0) };
+}
+
+#[cfg(target_arch = "x86_64")]
+#[inline(never)]
+extern "win64" fn scale(value: i64, factor: i64) -> i64 {
+    std::hint::black_box(value * factor)
+}
+
+#[cfg(target_arch = "x86_64")]
+#[inline(never)]
+extern "sysv64" fn scale_sysv(value: i64, factor: i64) -> i64 {
+    std::hint::black_box(value * factor)
+}
+
+#[cfg(all(windows, target_arch = "x86"))]
+#[inline(never)]
+extern "stdcall" fn add_stdcall(a: i32, b: i32) -> i32 {
+    std::hint::black_box(a + b)
+}
+
+#[cfg(all(windows, target_arch = "x86"))]
+#[inline(never)]
+extern "fastcall" fn add_fastcall(a: i32, b: i32) -> i32 {
+    std::hint::black_box(a + b)
+}
+
+#[test]
+fn test_fake_c_unwind_function() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(
+            unsafe{} extern "C-unwind" fn (checksum)(*const u8, usize) -> u32
+        ))
+        .will_execute(injectorpp::fake!(
+            func_type: unsafe extern "C-unwind" fn(_data: *const u8, len: usize) -> u32,
+            when: len == 4,
+            returns: 0xdead_beef,
+            times: 1
+        ));
+
+    let data = [1u8, 2, 3, 4];
+    assert_eq!(checksum(data.as_ptr(), data.len()), 0xdead_beef);
+}
+
+#[test]
+fn test_fake_system_unit_function() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(
+            unsafe{} extern "system" fn (reset_counter)(*mut u32) -> ()
+        ))
+        .will_execute(injectorpp::fake!(
+            func_type: unsafe extern "system" fn(counter: *mut u32) -> (),
+            assign: { unsafe { *counter = 42 } }
+        ));
+
+    let mut counter = 7;
+    reset_counter(&mut counter);
+    assert_eq!(counter, 42);
+}
+
+#[cfg(target_arch = "x86_64")]
+#[test]
+fn test_fake_win64_and_sysv64_functions() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(
+            unsafe{} extern "win64" fn (scale)(i64, i64) -> i64
+        ))
+        .will_execute(injectorpp::fake!(
+            func_type: unsafe extern "win64" fn(value: i64, factor: i64) -> i64,
+            returns: value + factor
+        ));
+    injector
+        .when_called(injectorpp::func!(
+            unsafe{} extern "sysv64" fn (scale_sysv)(i64, i64) -> i64
+        ))
+        .will_execute(injectorpp::fake!(
+            func_type: unsafe extern "sysv64" fn(value: i64, factor: i64) -> i64,
+            returns: value - factor
+        ));
+
+    assert_eq!(scale(6, 7), 13);
+    assert_eq!(scale_sysv(6, 7), -1);
+}
+
+#[cfg(target_arch = "x86_64")]
+#[test]
+#[should_panic(expected = "Signature mismatch")]
+fn test_fake_with_wrong_abi_should_panic() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(
+            unsafe{} extern "win64" fn (scale)(i64, i64) -> i64
+        ))
+        .will_execute(injectorpp::fake!(
+            func_type: unsafe extern "sysv64" fn(value: i64, _factor: i64) -> i64,
+            returns: value
+        ));
+}
+
+#[cfg(all(windows, target_arch = "x86"))]
+#[test]
+fn test_fake_stdcall_and_fastcall_functions() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(
+            unsafe{} extern "stdcall" fn (add_stdcall)(i32, i32) -> i32
+        ))
+        .will_execute(injectorpp::fake!(
+            func_type: unsafe extern "stdcall" fn(a: i32, b: i32) -> i32,
+            returns: a * b
+        ));
+    injector
+        .when_called(injectorpp::func!(
+            unsafe{} extern "fastcall" fn (add_fastcall)(i32, i32) -> i32
+        ))
+        .will_execute(injectorpp::fake!(
+            func_type: unsafe extern "fastcall" fn(a: i32, b: i32) -> i32,
+            returns: a - b
+        ));
+
+    assert_eq!(add_stdcall(3, 4), 12);
+    assert_eq!(add_fastcall(3, 4), -1);
+}
@@ -0,0 +1,180 @@
+use injectorpp::interface::injector::*;
+
+#[inline(never)]
+fn introspected_func() -> i32 {
+    std::hint::black_box(1)
+}
+
+#[inline(never)]
+fn introspected_global_func() -> i32 {
+    std::hint::black_box(2)
+}
+
+#[test]
+fn test_active_patches_should_list_thread_local_patch() {
+    let mut injector = InjectorPP::new();
+    assert!(injector.active_patches().is_empty());
+
+    injector
+        .when_called(injectorpp::func!(fn (introspected_func)() -> i32))
+        .will_execute(injectorpp::fake!(
+            func_type: fn() -> i32,
+            returns: 10
+        ));
+
+    let patches = injector.active_patches();
+    assert_eq!(patches.len(), 1);
+    assert_eq!(
+        patches[0].func_addr,
+        introspected_func as *const () as usize
+    );
+    assert!(patches[0].thread_local);
+    assert!(patches[0].patch_size > 0);
+    assert_ne!(patches[0].jit_addr, 0);
+
+    #[cfg(any(target_os = "linux", target_os = "windows"))]
+    {
+        let exe = std::env::current_exe().unwrap();
+        let exe = exe.file_name().unwrap().to_str().unwrap();
+        assert!(patches[0]
+            .module
+            .as_deref()
+            .is_some_and(|module| module.ends_with(exe)));
+    }
+
+    assert!(InjectorPP::all_active_patches()
+        .iter()
+        .any(|patch| patch.func_addr == introspected_func as *const () as usize));
+
+    drop(injector);
+
+    assert!(!InjectorPP::all_active_patches()
+        .iter()
+        .any(|patch| patch.func_addr == introspected_func as *const () as usize));
+}
+
+#[test]
+fn test_active_patches_should_list_global_patch() {
+    let mut injector = InjectorPP::new_global();
+    injector
+        .when_called(injectorpp::func!(fn (introspected_global_func)() -> i32))
+        .will_execute(injectorpp::fake!(
+            func_type: fn() -> i32,
+            returns: 20
+        ));
+
+    assert_eq!(introspected_global_func(), 20);
+
+    let patches = injector.active_patches();
+    assert_eq!(patches.len(), 1);
+    assert_eq!(
+        patches[0].func_addr,
+        introspected_global_func as *const () as usize
+    );
+    assert!(!patches[0].thread_local);
+
+    assert!(InjectorPP::all_active_patches()
+        .iter()
+        .any(
+            |patch| patch.func_addr == introspected_global_func as *const () as usize
+                && !patch.thread_local
+        ));
+
+    drop(injector);
+
+    assert!(!InjectorPP::all_active_patches()
+        .iter()
+        .any(|patch| patch.func_addr == introspected_global_func as *const () as usize));
+}
+
+#[test]
+fn test_active_patches_should_resolve_exported_symbol_name() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(
+            unsafe{} extern "C" fn (libc_getpid)() -> i32
+        ))
+        .will_execute(injectorpp::fake!(
+            func_type: unsafe extern "C" fn() -> i32,
+            returns: 7
+        ));
+
+    let patches = injector.active_patches();
+    assert_eq!(patches.len(), 1);
+
+    #[cfg(target_os = "linux")]
+    assert!(patches[0]
+        .symbol
+        .as_deref()
+        .is_some_and(|name| name.contains("getpid")));
+}
+
+extern "C" {
+    #[link_name = "getpid"]
+    fn libc_getpid() -> i32;
+}
+
+#[inline(never)]
+fn queried_func() -> bool {
+    std::hint::black_box(true)
+}
+
+#[test]
+fn test_is_patched_should_reflect_thread_local_fake() {
+    assert!(!injectorpp::is_patched(injectorpp::func!(
+        fn (queried_func)() -> bool
+    )));
+
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(fn (queried_func)() -> bool))
+        .will_return_boolean(false);
+
+    assert!(injectorpp::is_patched(injectorpp::func!(
+        fn (queried_func)() -> bool
+    )));
+
+    // Other threads are not affected by thread-local fakes.
+    let other_thread = std::thread::spawn(|| {
+        injectorpp::is_patched(injectorpp::func!(fn (queried_func)() -> bool))
+    })
+    .join()
+    .unwrap();
+    assert!(!other_thread);
+
+    drop(injector);
+
+    assert!(!injectorpp::is_patched(injectorpp::func!(
+        fn (queried_func)() -> bool
+    )));
+}
+
+#[inline(never)]
+fn queried_global_func() -> bool {
+    std::hint::black_box(true)
+}
+
+#[test]
+fn test_is_patched_should_ignore_paused_global_fake() {
+    let mut injector = InjectorPP::new_global();
+    let handle = injector
+        .when_called(injectorpp::func!(fn (queried_global_func)() -> bool))
+        .will_execute(injectorpp::fake!(
+            func_type: fn() -> bool,
+            returns: false
+        ));
+
+    assert!(injectorpp::is_patched(injectorpp::func!(
+        fn (queried_global_func)() -> bool
+    )));
+
+    handle.pause();
+    assert!(!injectorpp::is_patched(injectorpp::func!(
+        fn (queried_global_func)() -> bool
+    )));
+
+    handle.resume();
+    assert!(injectorpp::is_patched(injectorpp::func!(
+        fn (queried_global_func)() -> bool
+    )));
+}
@@ -0,0 +1,104 @@
+#![cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec",
+    target_arch = "arm"
+))]
+
+use std::alloc::{GlobalAlloc, Layout, System};
+
+use injectorpp::interface::injector::*;
+
+struct TestAllocator;
+
+unsafe impl GlobalAlloc for TestAllocator {
+    #[inline(never)]
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        System.alloc(std::hint::black_box(layout))
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: TestAllocator = TestAllocator;
+
+/// The size of the allocation the fakes fail.
+const FAILING_SIZE: usize = 4093;
+
+#[test]
+fn test_fake_global_alloc_should_fail_one_allocation_size() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(
+            unsafe{} fn (<TestAllocator as GlobalAlloc>::alloc)(&TestAllocator, Layout) -> *mut u8
+        ))
+        .reentrant_calls_original()
+        .will_execute(injectorpp::fake!(
+            func_type: unsafe fn(allocator: &TestAllocator, layout: Layout) -> *mut u8,
+            returns: if layout.size() == FAILING_SIZE {
+                std::ptr::null_mut()
+            } else {
+                allocator.alloc(layout)
+            }
+        ));
+
+    let layout = Layout::from_size_align(FAILING_SIZE, 1).unwrap();
+    assert!(unsafe { std::alloc::alloc(layout) }.is_null());
+
+    let message = format!("{} bytes", FAILING_SIZE - 1);
+    let mut buffer: Vec<u8> = Vec::with_capacity(FAILING_SIZE - 1);
+    buffer.extend_from_slice(message.as_bytes());
+    assert_eq!(buffer, b"4092 bytes");
+}
+
+#[test]
+#[should_panic(expected = "called more times than expected")]
+fn test_fake_global_alloc_called_too_often_should_panic() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(
+            unsafe{} fn (<TestAllocator as GlobalAlloc>::alloc)(&TestAllocator, Layout) -> *mut u8
+        ))
+        .will_execute(injectorpp::fake!(
+            func_type: unsafe fn(_allocator: &TestAllocator, _layout: Layout) -> *mut u8,
+            returns: std::ptr::null_mut(),
+            times: 1
+        ));
+
+    let layout = Layout::new::<u64>();
+    assert!(unsafe { ALLOCATOR.alloc(layout) }.is_null());
+    // The panic message is formatted, and the panic raised, without reaching the fake again.
+    unsafe { ALLOCATOR.alloc(layout) };
+}
+
+#[cfg(unix)]
+#[test]
+fn test_fake_malloc_should_fail_one_allocation_size() {
+    let mut injector = InjectorPP::new();
+    injector
+        .allow_dangerous()
+        .when_called(injectorpp::func!(
+            unsafe{} extern "C" fn (libc::malloc)(usize) -> *mut libc::c_void
+        ))
+        .reentrant_calls_original()
+        .will_execute(injectorpp::fake!(
+            func_type: unsafe extern "C" fn(size: usize) -> *mut libc::c_void,
+            returns: if size == FAILING_SIZE {
+                std::ptr::null_mut()
+            } else {
+                libc::malloc(size)
+            }
+        ));
+
+    assert!(unsafe { libc::malloc(FAILING_SIZE) }.is_null());
+
+    let ptr = unsafe { libc::malloc(FAILING_SIZE + 1) };
+    assert!(!ptr.is_null());
+    unsafe { libc::free(ptr) };
+
+    let words: Vec<String> = (0..64).map(|i| i.to_string()).collect();
+    assert_eq!(words.concat().len(), 118);
+}
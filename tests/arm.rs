@@ -139,3 +139,42 @@ fn test_arm_a32_32_bit_aligned_patch() {
         assert_eq!(result, 99, "A32 function should return 99");
     }
 }
+
+#[test]
+fn test_arm_t32_16_bit_aligned_global_patch_switches_state() {
+    unsafe {
+        let allocated_memory: [u8; T32_ALIGNED_32_FUNCTION_OPCODES.len() * 2 + 4] =
+            [0; T32_ALIGNED_32_FUNCTION_OPCODES.len() * 2 + 4];
+        let allocated_memory_ptr = allocated_memory.as_ptr() as *mut u8;
+        let mut aligned_memory = ((allocated_memory_ptr as usize)
+            + (2 - (allocated_memory_ptr as usize % 2)) % 2)
+            as *mut u8;
+        if aligned_memory as usize % 4 == 0 {
+            aligned_memory = aligned_memory.add(2); // Ensure to make it not 4-byte aligned
+        }
+
+        for (i, &opcode) in T32_ALIGNED_32_FUNCTION_OPCODES.iter().enumerate() {
+            for (j, b) in opcode.to_le_bytes().iter().enumerate() {
+                aligned_memory.add(i * 2 + j).write(*b);
+            }
+        }
+
+        fn fake() -> u32 {
+            99
+        }
+
+        // The patch of a global injector loads pc, so the Thumb function reaches the fake in
+        // whichever state the fake was compiled for.
+        let mut injector = InjectorPP::new_global();
+        injector
+            .when_called(FuncPtr::new(
+                aligned_memory.add(1) as *const (),
+                "fn() -> u32",
+            ))
+            .will_execute_raw(injectorpp::func!(fn(fake)() -> u32));
+
+        let result =
+            std::mem::transmute::<*const (), fn() -> u32>(aligned_memory.add(1) as *const ())();
+        assert_eq!(result, 99, "T32 function should return 99");
+    }
+}
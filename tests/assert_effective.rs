@@ -0,0 +1,170 @@
+use injectorpp::interface::injector::*;
+
+#[derive(Debug, PartialEq)]
+struct Quota {
+    limit: u64,
+    used: u64,
+    window: u64,
+}
+
+#[inline(never)]
+fn max_connections(pool: &str, default: u32) -> u32 {
+    std::hint::black_box(pool).len() as u32 + default
+}
+
+#[inline(never)]
+fn quota_of(tenant: &str) -> Quota {
+    Quota {
+        limit: std::hint::black_box(tenant).len() as u64,
+        used: 0,
+        window: 60,
+    }
+}
+
+#[inline(never)]
+fn reachable(host: &str) -> bool {
+    !std::hint::black_box(host).is_empty()
+}
+
+#[inline(always)]
+fn pool_size() -> u32 {
+    8
+}
+
+#[inline(never)]
+fn connect(pool: &str) -> u32 {
+    max_connections(pool, 8)
+}
+
+#[inline(never)]
+fn connect_with_default_pool() -> u32 {
+    pool_size() + 1
+}
+
+#[test]
+fn test_assert_effective_fake_reached_should_return_result() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(fn (max_connections)(&str, u32) -> u32))
+        .will_execute(injectorpp::fake!(
+            func_type: fn(_pool: &str, _default: u32) -> u32,
+            returns: 1
+        ));
+
+    assert_eq!(injector.assert_effective(|| connect("primary")), 1);
+}
+
+#[test]
+fn test_assert_effective_global_fake_reached_should_return_result() {
+    let mut injector = InjectorPP::new_global();
+    injector
+        .when_called(injectorpp::func!(fn (max_connections)(&str, u32) -> u32))
+        .will_execute(injectorpp::fake!(
+            func_type: fn(_pool: &str, _default: u32) -> u32,
+            returns: 2
+        ));
+
+    assert_eq!(injector.assert_effective(|| connect("primary")), 2);
+}
+
+#[test]
+#[should_panic(expected = "was not reached while running the code under test")]
+fn test_assert_effective_inlined_function_should_panic() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(fn (pool_size)() -> u32))
+        .will_execute(injectorpp::fake!(func_type: fn() -> u32, returns: 1));
+
+    // The patch is in place, but `pool_size` is inlined into its caller.
+    injector.assert_patched();
+    injector.assert_effective(connect_with_default_pool);
+}
+
+#[test]
+fn test_assert_patched_thread_local_fakes_should_pass() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(fn (max_connections)(&str, u32) -> u32))
+        .will_execute(injectorpp::fake!(
+            func_type: fn(_pool: &str, _default: u32) -> u32,
+            returns: 1,
+            times: 1
+        ));
+    injector
+        .when_called(injectorpp::func!(fn (quota_of)(&str) -> Quota))
+        .will_execute(injectorpp::fake!(
+            func_type: fn(_tenant: &str) -> Quota,
+            returns: Quota { limit: 1, used: 1, window: 1 }
+        ));
+    injector
+        .when_called(injectorpp::func!(fn (reachable)(&str) -> bool))
+        .will_return_boolean(false);
+
+    injector.assert_patched();
+
+    // The check does not call the fakes, which stay installed.
+    assert_eq!(max_connections("primary", 8), 1);
+    assert_eq!(
+        quota_of("contoso"),
+        Quota {
+            limit: 1,
+            used: 1,
+            window: 1
+        }
+    );
+    assert!(!reachable("example.com"));
+}
+
+#[test]
+fn test_assert_patched_global_fake_should_pass() {
+    let mut injector = InjectorPP::new_global();
+    injector
+        .when_called(injectorpp::func!(fn (max_connections)(&str, u32) -> u32))
+        .will_execute(injectorpp::fake!(
+            func_type: fn(_pool: &str, _default: u32) -> u32,
+            returns: 2,
+            times: 1
+        ));
+
+    injector.assert_patched();
+
+    assert_eq!(max_connections("primary", 8), 2);
+}
+
+#[test]
+fn test_assert_patched_should_skip_restored_functions() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(fn (reachable)(&str) -> bool))
+        .will_return_boolean(false);
+    injector.restore(injectorpp::func!(fn (reachable)(&str) -> bool));
+
+    injector.assert_patched();
+
+    assert!(reachable("example.com"));
+}
+
+#[test]
+#[should_panic(expected = "no longer leads to its fake")]
+fn test_assert_patched_paused_global_fake_should_panic() {
+    let mut injector = InjectorPP::new_global();
+    let handle = injector
+        .when_called(injectorpp::func!(fn (reachable)(&str) -> bool))
+        .will_return_boolean(false);
+    handle.pause();
+
+    injector.assert_patched();
+}
+
+#[test]
+#[should_panic(expected = "The fake was never reached")]
+fn test_fake_never_called_should_hint_at_inlining() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(fn (max_connections)(&str, u32) -> u32))
+        .will_execute(injectorpp::fake!(
+            func_type: fn(_pool: &str, _default: u32) -> u32,
+            returns: 1,
+            times: 1
+        ));
+}
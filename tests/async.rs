@@ -143,3 +143,269 @@ async fn test_complex_struct_async_func_with_param_should_success() {
     let result = real_client.post("test payload").await;
     assert_eq!(result, "POST test payload to https://test.com".to_string());
 }
+
+struct Session {
+    id: u32,
+}
+
+async fn open_session() -> std::io::Result<Session> {
+    Err(std::io::Error::other("offline"))
+}
+
+static SESSIONS_CREATED: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(0);
+
+fn make_session() -> std::io::Result<Session> {
+    let id = SESSIONS_CREATED.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+    Ok(Session { id })
+}
+
+#[tokio::test]
+async fn test_async_return_closure_should_run_on_every_call() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called_async(injectorpp::async_func!(
+            open_session(),
+            std::io::Result<Session>
+        ))
+        .will_return_async(injectorpp::async_return!(
+            || make_session(),
+            std::io::Result<Session>
+        ));
+
+    let first = open_session().await.unwrap();
+    let second = open_session().await.unwrap();
+
+    assert_eq!(first.id, 0);
+    assert_eq!(second.id, 1);
+    assert_eq!(
+        SESSIONS_CREATED.load(std::sync::atomic::Ordering::SeqCst),
+        2
+    );
+}
+
+#[tokio::test]
+async fn test_async_return_non_clone_value_should_be_returned_on_every_call() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called_async(injectorpp::async_func!(
+            open_session(),
+            std::io::Result<Session>
+        ))
+        .will_return_async(injectorpp::async_return!(
+            Ok(Session { id: 42 }),
+            std::io::Result<Session>
+        ));
+
+    assert_eq!(open_session().await.unwrap().id, 42);
+    assert_eq!(open_session().await.unwrap().id, 42);
+}
+
+async fn slow_response() -> u32 {
+    0
+}
+
+#[tokio::test]
+async fn test_async_return_after_should_be_pending_before_ready() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called_async(injectorpp::async_func!(slow_response(), u32))
+        .will_return_async(injectorpp::async_return_after!(2, 7, u32));
+
+    let mut fut = std::pin::pin!(slow_response());
+    let waker = std::task::Waker::noop();
+    let mut cx = std::task::Context::from_waker(waker);
+
+    assert!(std::future::Future::poll(fut.as_mut(), &mut cx).is_pending());
+    assert!(std::future::Future::poll(fut.as_mut(), &mut cx).is_pending());
+    assert_eq!(
+        std::future::Future::poll(fut.as_mut(), &mut cx),
+        std::task::Poll::Ready(7)
+    );
+
+    // A new future starts pending again.
+    assert_eq!(slow_response().await, 7);
+}
+
+#[tokio::test]
+async fn test_async_return_with_delay_should_be_ready_after_delay() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called_async(injectorpp::async_func!(slow_response(), u32))
+        .will_return_async(injectorpp::async_return!(
+            7,
+            u32,
+            delay: std::time::Duration::from_millis(30)
+        ));
+
+    let started = std::time::Instant::now();
+    let mut fut = std::pin::pin!(slow_response());
+    let waker = std::task::Waker::noop();
+    let mut cx = std::task::Context::from_waker(waker);
+    assert!(std::future::Future::poll(fut.as_mut(), &mut cx).is_pending());
+
+    assert_eq!(fut.await, 7);
+    assert!(started.elapsed() >= std::time::Duration::from_millis(30));
+}
+
+#[tokio::test]
+async fn test_async_return_after_should_let_select_pick_ready_branch() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called_async(injectorpp::async_func!(slow_response(), u32))
+        .will_return_async(injectorpp::async_return_after!(1, 7, u32));
+
+    let winner = tokio::select! {
+        biased;
+        value = slow_response() => value,
+        _ = std::future::ready(()) => 0,
+    };
+
+    assert_eq!(winner, 0);
+}
+
+async fn fetch_token() -> Result<String, String> {
+    Err("unreachable".to_string())
+}
+
+async fn fetch_token_with_retry(attempts: u32) -> Result<String, String> {
+    let mut last = Err("no attempts".to_string());
+    for _ in 0..attempts {
+        last = fetch_token().await;
+        if last.is_ok() {
+            break;
+        }
+    }
+
+    last
+}
+
+#[tokio::test]
+async fn test_async_return_seq_should_return_values_in_order() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called_async(injectorpp::async_func!(fetch_token(), Result<String, String>))
+        .will_return_async(injectorpp::async_return_seq!(
+            [
+                Err("timeout".to_string()),
+                Err("throttled".to_string()),
+                Ok("token".to_string())
+            ],
+            Result<String, String>
+        ));
+
+    assert_eq!(fetch_token_with_retry(3).await, Ok("token".to_string()));
+}
+
+#[tokio::test]
+#[should_panic(expected = "was called 2 times but only has 1 values")]
+async fn test_async_return_seq_when_values_exhausted_should_panic() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called_async(injectorpp::async_func!(fetch_token(), Result<String, String>))
+        .will_return_async(injectorpp::async_return_seq!(
+            [Err("timeout".to_string())],
+            Result<String, String>
+        ));
+
+    let _ = fetch_token_with_retry(2).await;
+}
+
+async fn ping() -> bool {
+    false
+}
+
+#[tokio::test]
+async fn test_async_return_times_should_verify_await_count() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called_async(injectorpp::async_func!(ping(), bool))
+        .will_return_async_times(injectorpp::async_return!(true, bool, times: 2));
+
+    assert!(ping().await);
+    assert!(ping().await);
+}
+
+#[tokio::test]
+#[should_panic(expected = "expected to be called 2 time(s), but it is actually called 1 time(s)")]
+async fn test_async_return_times_when_awaited_less_should_panic() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called_async(injectorpp::async_func!(ping(), bool))
+        .will_return_async_times(injectorpp::async_return!(|| true, bool, times: 2));
+
+    assert!(ping().await);
+}
+
+#[tokio::test]
+#[should_panic(expected = "called more times than expected")]
+async fn test_async_return_times_when_awaited_more_should_panic() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called_async(injectorpp::async_func!(ping(), bool))
+        .will_return_async_times(injectorpp::async_return!(true, bool, times: 1));
+
+    assert!(ping().await);
+    assert!(ping().await);
+}
+
+struct BlobClient {
+    pub endpoint: String,
+}
+
+impl BlobClient {
+    async fn download(&self, name: &str, offset: u64) -> Vec<u8> {
+        format!("{}/{}@{}", self.endpoint, name, offset).into_bytes()
+    }
+}
+
+fn upload(data: Vec<u8>) -> impl std::future::Future<Output = usize> {
+    let len = data.len();
+    async move { len }
+}
+
+#[tokio::test]
+async fn test_async_func_from_method_should_not_need_instance() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called_async(injectorpp::async_func!(
+            fn (BlobClient::download)(&BlobClient, &str, u64) -> Vec<u8>
+        ))
+        .will_return_async(injectorpp::async_return!(b"fake".to_vec(), Vec<u8>));
+
+    let client = BlobClient {
+        endpoint: "https://account".to_string(),
+    };
+
+    assert_eq!(client.download("a.txt", 0).await, b"fake".to_vec());
+}
+
+#[tokio::test]
+async fn test_async_func_from_impl_future_function_should_fake() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called_async(injectorpp::async_func!(fn (upload)(Vec<u8>) -> usize))
+        .will_return_async(injectorpp::async_return!(0, usize));
+
+    assert_eq!(upload(vec![1, 2, 3]).await, 0);
+}
+
+#[tokio::test]
+async fn test_async_func_from_closure_should_fake() {
+    let handler = |id: u32| async move { id * 2 };
+
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called_async(injectorpp::async_func!(fn (handler)(u32) -> u32))
+        .will_return_async(injectorpp::async_return!(7, u32));
+
+    assert_eq!(handler(5).await, 7);
+}
+
+#[test]
+#[should_panic(expected = "Signature mismatch")]
+fn test_async_func_from_function_when_output_mismatch_should_panic() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called_async(injectorpp::async_func!(fn (upload)(Vec<u8>) -> usize))
+        .will_return_async(injectorpp::async_return!(false, bool));
+}
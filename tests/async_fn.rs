@@ -0,0 +1,170 @@
+use injectorpp::interface::injector::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+async fn resolve(host: &str) -> Result<String, String> {
+    Err(format!("cannot resolve {host}"))
+}
+
+async fn add_async(a: u32, b: u32) -> u32 {
+    a + b
+}
+
+async fn read_payload(id: u64) -> Vec<u8> {
+    vec![id as u8]
+}
+
+async fn shutdown() {}
+
+async fn notify(channel: String) -> usize {
+    channel.len()
+}
+
+struct Connection {
+    pub host: String,
+}
+
+impl Connection {
+    pub async fn send(&self, payload: &str) -> String {
+        format!("sent {} to {}", payload, self.host)
+    }
+}
+
+#[tokio::test]
+async fn test_will_execute_async_should_receive_arguments() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called_async_fn(injectorpp::async_fn!(fn (resolve)(&str) -> Result<String, String>))
+        .will_execute_async(|(host,)| async move {
+            match host {
+                "db" => Ok("10.0.0.1".to_string()),
+                "cache" => Ok("10.0.0.2".to_string()),
+                _ => Err("unknown".to_string()),
+            }
+        });
+
+    assert_eq!(resolve("db").await, Ok("10.0.0.1".to_string()));
+    assert_eq!(resolve("cache").await, Ok("10.0.0.2".to_string()));
+    assert_eq!(resolve("web").await, Err("unknown".to_string()));
+}
+
+#[tokio::test]
+async fn test_will_execute_async_when_multiple_arguments_should_receive_all() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called_async_fn(injectorpp::async_fn!(fn (add_async)(u32, u32) -> u32))
+        .will_execute_async(|(a, b)| async move { a * b });
+
+    assert_eq!(add_async(3, 4).await, 12);
+    assert_eq!(add_async(5, 6).await, 30);
+}
+
+#[tokio::test]
+async fn test_will_execute_async_when_method_should_receive_self() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called_async_fn(injectorpp::async_fn!(
+            fn (Connection::send)(&Connection, &str) -> String
+        ))
+        .will_execute_async(|(conn, payload)| {
+            let reply = format!("fake {} to {}", payload, conn.host);
+            async move { reply }
+        });
+
+    let conn = Connection {
+        host: "example.com".to_string(),
+    };
+
+    assert_eq!(conn.send("ping").await, "fake ping to example.com");
+}
+
+#[tokio::test]
+async fn test_will_execute_async_when_unit_output_should_run_fake() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called_async_fn(injectorpp::async_fn!(fn (shutdown)()))
+        .will_execute_async(move |()| {
+            let counter = counter.clone();
+            async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+
+    shutdown().await;
+    shutdown().await;
+
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_will_execute_async_when_owned_argument_should_receive_clone() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called_async_fn(injectorpp::async_fn!(fn (notify)(String) -> usize))
+        .will_execute_async(|(channel,)| async move { channel.len() * 10 });
+
+    assert_eq!(notify("alerts".to_string()).await, 60);
+}
+
+#[tokio::test]
+async fn test_will_execute_async_when_polled_out_of_order_should_match_calls() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called_async_fn(injectorpp::async_fn!(fn (read_payload)(u64) -> Vec<u8>))
+        .will_execute_async(|(id,)| async move {
+            tokio::task::yield_now().await;
+            vec![id as u8; 2]
+        });
+
+    let first = read_payload(1);
+    let second = read_payload(2);
+
+    let second = second.await;
+    let first = first.await;
+
+    assert_eq!(first, vec![1, 1]);
+    assert_eq!(second, vec![2, 2]);
+
+    let (a, b) = tokio::join!(read_payload(3), read_payload(4));
+    assert_eq!(a, vec![3, 3]);
+    assert_eq!(b, vec![4, 4]);
+}
+
+#[tokio::test]
+async fn test_will_execute_async_when_injector_dropped_should_restore() {
+    {
+        let mut injector = InjectorPP::new();
+        injector
+            .when_called_async_fn(injectorpp::async_fn!(fn (add_async)(u32, u32) -> u32))
+            .will_execute_async(|(a, b)| async move { a.max(b) });
+
+        assert_eq!(add_async(1, 2).await, 2);
+    }
+
+    assert_eq!(add_async(1, 2).await, 3);
+}
+
+#[tokio::test]
+async fn test_will_execute_async_when_future_created_before_fake_should_run_original() {
+    let pending = read_payload(7);
+
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called_async_fn(injectorpp::async_fn!(fn (read_payload)(u64) -> Vec<u8>))
+        .will_execute_async(|(id,)| async move { vec![0u8; id as usize] });
+
+    assert_eq!(read_payload(3).await, vec![0, 0, 0]);
+    assert_eq!(pending.await, vec![7]);
+}
+
+#[test]
+#[should_panic(expected = "will_execute_async is only supported with thread-local dispatch")]
+fn test_will_execute_async_when_global_should_panic() {
+    let mut injector = InjectorPP::new_global();
+    injector
+        .when_called_async_fn(injectorpp::async_fn!(fn (shutdown)()))
+        .will_execute_async(|()| async {});
+}
@@ -0,0 +1,134 @@
+use injectorpp::interface::injector::*;
+
+#[async_trait::async_trait]
+trait Storage {
+    async fn read(&self, key: &str) -> Option<String>;
+
+    async fn write(&mut self, key: &str, value: Vec<u8>) -> Result<usize, String>;
+
+    async fn flush(&self);
+}
+
+struct Disk {
+    pub root: String,
+}
+
+#[async_trait::async_trait]
+impl Storage for Disk {
+    async fn read(&self, key: &str) -> Option<String> {
+        std::hint::black_box(format!("{}/{}", self.root, key));
+        None
+    }
+
+    async fn write(&mut self, key: &str, value: Vec<u8>) -> Result<usize, String> {
+        Err(format!(
+            "cannot write {} bytes to {}/{}",
+            value.len(),
+            self.root,
+            key
+        ))
+    }
+
+    async fn flush(&self) {
+        panic!("flush should be faked");
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+trait LocalCache {
+    async fn get(&self, key: u32) -> u32;
+}
+
+struct Memory;
+
+#[async_trait::async_trait(?Send)]
+impl LocalCache for Memory {
+    async fn get(&self, key: u32) -> u32 {
+        key
+    }
+}
+
+fn disk() -> Disk {
+    Disk {
+        root: "/data".to_string(),
+    }
+}
+
+#[tokio::test]
+async fn test_async_trait_fake_should_borrow_arguments() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::async_trait_func!(
+            fn (<Disk as Storage>::read)(&Disk, &str) -> Option<String>
+        ))
+        .will_execute(injectorpp::async_trait_fake!(
+            func_type: fn(disk: &Disk, key: &str) -> Option<String>,
+            returns: async move { Some(format!("{}/{} cached", disk.root, key)) }
+        ));
+
+    assert_eq!(
+        disk().read("a.txt").await,
+        Some("/data/a.txt cached".to_string())
+    );
+}
+
+#[tokio::test]
+async fn test_async_trait_fake_when_mut_self_should_check_condition_and_times() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::async_trait_func!(
+            fn (<Disk as Storage>::write)(&mut Disk, &str, Vec<u8>) -> Result<usize, String>
+        ))
+        .will_execute(injectorpp::async_trait_fake!(
+            func_type: fn(_disk: &mut Disk, key: &str, value: Vec<u8>) -> Result<usize, String>,
+            when: key == "log",
+            returns: async move { Ok(value.len()) },
+            times: 2
+        ));
+
+    let mut disk = disk();
+    assert_eq!(disk.write("log", vec![1, 2]).await, Ok(2));
+    assert_eq!(disk.write("log", vec![3]).await, Ok(1));
+}
+
+#[tokio::test]
+async fn test_async_trait_fake_when_unit_output_should_fake() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::async_trait_func!(fn (<Disk as Storage>::flush)(&Disk)))
+        .will_execute(injectorpp::async_trait_fake!(
+            func_type: fn(_disk: &Disk),
+            returns: async {}
+        ));
+
+    disk().flush().await;
+}
+
+#[tokio::test]
+async fn test_async_trait_fake_when_not_send_should_fake() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::async_trait_func!(
+            ?Send fn (<Memory as LocalCache>::get)(&Memory, u32) -> u32
+        ))
+        .will_execute(injectorpp::async_trait_fake!(
+            ?Send func_type: fn(_memory: &Memory, key: u32) -> u32,
+            returns: async move { key + 1 }
+        ));
+
+    assert_eq!(Memory.get(41).await, 42);
+}
+
+#[test]
+#[should_panic(expected = "Signature mismatch")]
+fn test_async_trait_fake_when_output_mismatch_should_panic() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::async_trait_func!(
+            fn (<Disk as Storage>::read)(&Disk, &str) -> Option<String>
+        ))
+        .will_execute(injectorpp::async_trait_fake!(
+            func_type: fn(_disk: &Disk, _key: &str) -> Option<u32>,
+            returns: async { None }
+        ));
+}
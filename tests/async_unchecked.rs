@@ -151,3 +151,24 @@ async fn test_complex_struct_async_func_unchecked_with_param_should_success() {
     let result = real_client.post("test payload").await;
     assert_eq!(result, "POST test payload to https://test.com".to_string());
 }
+
+async fn open_counter() -> Vec<u8> {
+    Vec::new()
+}
+
+#[tokio::test]
+async fn test_async_return_unchecked_closure_should_run_on_every_call() {
+    let mut injector = InjectorPP::new();
+
+    unsafe {
+        injector
+            .when_called_async_unchecked(injectorpp::async_func_unchecked!(open_counter()))
+            .will_return_async_unchecked(injectorpp::async_return_unchecked!(
+                move || vec![1, 2, 3],
+                Vec<u8>
+            ));
+    }
+
+    assert_eq!(open_counter().await, vec![1, 2, 3]);
+    assert_eq!(open_counter().await, vec![1, 2, 3]);
+}
@@ -0,0 +1,106 @@
+#![cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec",
+    target_arch = "arm"
+))]
+
+use injectorpp::interface::injector::*;
+
+#[inline(never)]
+fn charge(amount: u32) -> bool {
+    std::hint::black_box(amount == 0)
+}
+
+#[inline(never)]
+fn checkout() -> bool {
+    let charged = charge(100);
+    std::hint::black_box(charged)
+}
+
+#[inline(never)]
+fn refund() -> bool {
+    let charged = charge(0);
+    std::hint::black_box((charged, "refund")).0
+}
+
+#[test]
+fn test_record_backtraces_should_capture_each_call() {
+    let mut injector = InjectorPP::new();
+    let handle = injector
+        .when_called(injectorpp::func!(fn (charge)(u32) -> bool))
+        .record_backtraces()
+        .will_execute(injectorpp::fake!(
+            func_type: fn(_amount: u32) -> bool,
+            returns: false
+        ));
+
+    assert!(!checkout());
+    assert!(!refund());
+
+    let backtraces = handle.backtraces();
+    assert_eq!(backtraces.len(), 2);
+    assert!(backtraces[0].to_string().contains("checkout"));
+    assert!(backtraces[1].to_string().contains("refund"));
+    handle.assert_reached_through("backtraces::checkout");
+    handle.assert_reached_through("refund");
+}
+
+#[test]
+fn test_record_backtraces_should_capture_boxed_closure_calls() {
+    let mut injector = InjectorPP::new();
+    let handle = injector
+        .when_called(injectorpp::func!(fn (charge)(u32) -> bool))
+        .record_backtraces()
+        .will_execute_closure(injectorpp::boxed_closure!(
+            |amount: u32| amount > 50,
+            fn(u32) -> bool
+        ));
+
+    assert!(checkout());
+    handle.assert_reached_through("checkout");
+}
+
+#[test]
+#[should_panic(expected = "The fake was not reached through checkout; it was called 1 time(s)")]
+fn test_assert_reached_through_other_path_should_panic() {
+    let mut injector = InjectorPP::new();
+    let handle = injector
+        .when_called(injectorpp::func!(fn (charge)(u32) -> bool))
+        .record_backtraces()
+        .will_execute(injectorpp::fake!(
+            func_type: fn(_amount: u32) -> bool,
+            returns: true
+        ));
+
+    refund();
+    handle.assert_reached_through("checkout");
+}
+
+#[test]
+#[should_panic(
+    expected = "backtraces() requires the fake to be installed after record_backtraces()"
+)]
+fn test_backtraces_without_record_backtraces_should_panic() {
+    let mut injector = InjectorPP::new();
+    let handle = injector
+        .when_called(injectorpp::func!(fn (charge)(u32) -> bool))
+        .will_execute(injectorpp::fake!(
+            func_type: fn(_amount: u32) -> bool,
+            returns: true
+        ));
+
+    handle.backtraces();
+}
+
+#[test]
+#[should_panic(
+    expected = "record_backtraces() requires a fake created with fake! or boxed_closure!, not will_return_boolean()"
+)]
+fn test_record_backtraces_with_will_return_boolean_should_panic() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(fn (charge)(u32) -> bool))
+        .record_backtraces()
+        .will_return_boolean(true);
+}
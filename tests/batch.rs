@@ -0,0 +1,118 @@
+#![cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec",
+    target_arch = "arm"
+))]
+
+use injectorpp::interface::injector::*;
+
+#[inline(never)]
+fn open_socket() -> i32 {
+    core::hint::black_box(core::hint::black_box(-2) + core::hint::black_box(1))
+}
+
+#[inline(never)]
+fn bind_socket() -> i32 {
+    core::hint::black_box(core::hint::black_box(-3) + core::hint::black_box(1))
+}
+
+#[inline(never)]
+fn close_socket() -> i32 {
+    core::hint::black_box(core::hint::black_box(-4) + core::hint::black_box(1))
+}
+
+#[test]
+fn test_batch_should_apply_all_fakes_when_closure_returns() {
+    let mut injector = InjectorPP::new_global();
+    let installed = injector.batch(|b| {
+        b.when_called(injectorpp::func!(fn (open_socket)() -> i32))
+            .will_execute(injectorpp::fake!(
+                func_type: fn() -> i32,
+                returns: 3
+            ));
+        b.when_called(injectorpp::func!(fn (bind_socket)() -> i32))
+            .will_execute(injectorpp::fake!(
+                func_type: fn() -> i32,
+                returns: 0
+            ));
+
+        // Nothing is written until the batch ends.
+        assert_eq!(open_socket(), -1);
+        assert_eq!(bind_socket(), -2);
+        b.active_patches().len()
+    });
+
+    assert_eq!(installed, 2);
+    assert_eq!(open_socket(), 3);
+    assert_eq!(bind_socket(), 0);
+
+    drop(injector);
+    assert_eq!(open_socket(), -1);
+    assert_eq!(bind_socket(), -2);
+}
+
+#[test]
+fn test_batch_should_apply_reconfigured_fake() {
+    let mut injector = InjectorPP::new_global();
+    injector.batch(|b| {
+        b.when_called(injectorpp::func!(fn (close_socket)() -> i32))
+            .will_execute(injectorpp::fake!(
+                func_type: fn() -> i32,
+                returns: 1
+            ));
+        b.when_called(injectorpp::func!(fn (close_socket)() -> i32))
+            .will_execute(injectorpp::fake!(
+                func_type: fn() -> i32,
+                returns: 2
+            ));
+    });
+
+    assert_eq!(close_socket(), 2);
+    drop(injector);
+    assert_eq!(close_socket(), -3);
+}
+
+#[test]
+fn test_batch_should_apply_fakes_when_closure_panics() {
+    let mut injector = InjectorPP::new();
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        injector.batch(|b| {
+            b.when_called(injectorpp::func!(fn (open_socket)() -> i32))
+                .will_execute(injectorpp::fake!(
+                    func_type: fn() -> i32,
+                    returns: 7
+                ));
+            panic!("configuration failed");
+        })
+    }));
+
+    assert!(result.is_err());
+    assert_eq!(open_socket(), 7);
+    drop(injector);
+    assert_eq!(open_socket(), -1);
+}
+
+#[inline(never)]
+fn send_packet() -> i32 {
+    core::hint::black_box(core::hint::black_box(-5) + core::hint::black_box(1))
+}
+
+#[test]
+fn test_injector_dropped_inside_batch_should_restore_right_away() {
+    let mut global = InjectorPP::new_global();
+    global
+        .when_called(injectorpp::func!(fn (send_packet)() -> i32))
+        .will_execute(injectorpp::fake!(
+            func_type: fn() -> i32,
+            returns: 5
+        ));
+    assert_eq!(send_packet(), 5);
+
+    let mut injector = InjectorPP::new();
+    injector.batch(|_| {
+        drop(global);
+        assert_eq!(send_packet(), -4);
+    });
+    assert_eq!(send_packet(), -4);
+}
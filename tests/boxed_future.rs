@@ -0,0 +1,91 @@
+use injectorpp::interface::injector::*;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+struct StorageClient {
+    pub account: String,
+}
+
+impl StorageClient {
+    fn get_blob(
+        &self,
+        name: String,
+    ) -> Pin<Box<dyn Future<Output = Result<Vec<u8>, String>> + Send + '_>> {
+        Box::pin(async move { Err(format!("{} not found in {}", name, self.account)) })
+    }
+
+    fn delete_blob(&self, name: String) -> BoxFuture<'_, bool> {
+        Box::pin(async move { name.is_empty() })
+    }
+}
+
+fn fetch_count() -> Pin<Box<dyn Future<Output = u32>>> {
+    Box::pin(async { 0 })
+}
+
+#[tokio::test]
+async fn test_boxed_future_should_return_future_per_call() {
+    static CALLS: AtomicU32 = AtomicU32::new(0);
+
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(fn (fetch_count)() -> Pin<Box<dyn Future<Output = u32>>>))
+        .will_execute(injectorpp::boxed_future!(
+            func_type: fn() -> Pin<Box<dyn Future<Output = u32>>>,
+            returns: async { CALLS.fetch_add(1, Ordering::SeqCst) + 1 }
+        ));
+
+    assert_eq!(fetch_count().await, 1);
+    assert_eq!(fetch_count().await, 2);
+}
+
+#[tokio::test]
+async fn test_boxed_future_should_use_arguments() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(
+            fn (StorageClient::get_blob)(&StorageClient, String) -> Pin<Box<dyn Future<Output = Result<Vec<u8>, String>> + Send + '_>>
+        ))
+        .will_execute(injectorpp::boxed_future!(
+            func_type: fn(client: &StorageClient, name: String) -> Pin<Box<dyn Future<Output = Result<Vec<u8>, String>> + Send + '_>>,
+            returns: async move { Ok(format!("{}/{}", client.account, name).into_bytes()) },
+            times: 1
+        ));
+
+    let client = StorageClient {
+        account: "acct".to_string(),
+    };
+
+    assert_eq!(
+        client.get_blob("a.txt".to_string()).await,
+        Ok(b"acct/a.txt".to_vec())
+    );
+}
+
+#[tokio::test]
+async fn test_boxed_future_when_condition_should_check_arguments() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(
+            fn (StorageClient::delete_blob)(&StorageClient, String) -> BoxFuture<'_, bool>
+        ))
+        .will_execute(injectorpp::boxed_future!(
+            func_type: fn(_client: &StorageClient, name: String) -> BoxFuture<'_, bool>,
+            when: name == "keep.txt",
+            returns: async { true }
+        ));
+
+    let client = StorageClient {
+        account: "acct".to_string(),
+    };
+
+    assert!(client.delete_blob("keep.txt".to_string()).await);
+
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        drop(client.delete_blob("other.txt".to_string()));
+    }));
+    assert!(result.is_err());
+}
@@ -0,0 +1,110 @@
+#![cfg(all(target_os = "linux", target_arch = "aarch64"))]
+
+use injectorpp::interface::injector::*;
+
+// Functions built with `-Z branch-protection=bti,pac-ret`. The landing pads are written as
+// hints, which assemblers accept without the BTI and PAC extensions.
+std::arch::global_asm!(
+    ".text",
+    ".p2align 4",
+    ".globl injectorpp_test_bti_one",
+    ".type injectorpp_test_bti_one, %function",
+    "injectorpp_test_bti_one:",
+    "hint #34", // bti c
+    "mov w0, #1",
+    "add w0, w0, #0",
+    "ret",
+    ".size injectorpp_test_bti_one, . - injectorpp_test_bti_one",
+    ".p2align 4",
+    ".globl injectorpp_test_pac_two",
+    ".type injectorpp_test_pac_two, %function",
+    "injectorpp_test_pac_two:",
+    "hint #25", // paciasp
+    "mov w0, #2",
+    "add w0, w0, #0",
+    "hint #29", // autiasp
+    "ret",
+    ".size injectorpp_test_pac_two, . - injectorpp_test_pac_two",
+    ".p2align 4",
+    ".globl injectorpp_test_pac_three",
+    ".type injectorpp_test_pac_three, %function",
+    "injectorpp_test_pac_three:",
+    "hint #25", // paciasp
+    "mov w0, #3",
+    "add w0, w0, #0",
+    "hint #29", // autiasp
+    "ret",
+    ".size injectorpp_test_pac_three, . - injectorpp_test_pac_three",
+);
+
+extern "C" {
+    fn injectorpp_test_bti_one() -> u32;
+    fn injectorpp_test_pac_two() -> u32;
+    fn injectorpp_test_pac_three() -> u32;
+}
+
+extern "C" fn fake_seven() -> u32 {
+    7
+}
+
+const BTI_C: u32 = 0xd503245f;
+
+fn first_instruction(func: unsafe extern "C" fn() -> u32) -> u32 {
+    unsafe { (func as *const u32).read() }
+}
+
+#[test]
+fn test_global_fake_should_keep_bti_landing_pad() {
+    let mut injector = InjectorPP::new_global();
+    unsafe {
+        injector
+            .when_called_unchecked(injectorpp::func_unchecked!(injectorpp_test_bti_one))
+            .will_execute_raw_unchecked(injectorpp::func_unchecked!(fake_seven));
+    }
+
+    assert_eq!(first_instruction(injectorpp_test_bti_one), BTI_C);
+
+    let indirect: unsafe extern "C" fn() -> u32 = std::hint::black_box(injectorpp_test_bti_one);
+    unsafe {
+        assert_eq!(indirect(), 7);
+    }
+}
+
+#[test]
+fn test_global_fake_should_replace_paciasp_with_bti_c() {
+    {
+        let mut injector = InjectorPP::new_global();
+        unsafe {
+            injector
+                .when_called_unchecked(injectorpp::func_unchecked!(injectorpp_test_pac_two))
+                .will_execute_raw_unchecked(injectorpp::func_unchecked!(fake_seven));
+        }
+
+        assert_eq!(first_instruction(injectorpp_test_pac_two), BTI_C);
+        unsafe {
+            assert_eq!(injectorpp_test_pac_two(), 7);
+        }
+    }
+
+    assert_eq!(first_instruction(injectorpp_test_pac_two), 0xd503233f);
+    unsafe {
+        assert_eq!(injectorpp_test_pac_two(), 2);
+    }
+}
+
+#[test]
+fn test_thread_local_fake_should_run_original_landing_pad_on_other_threads() {
+    let mut injector = InjectorPP::new();
+    unsafe {
+        injector
+            .when_called_unchecked(injectorpp::func_unchecked!(injectorpp_test_pac_three))
+            .will_execute_raw_unchecked(injectorpp::func_unchecked!(fake_seven));
+
+        assert_eq!(injectorpp_test_pac_three(), 7);
+    }
+
+    let original = std::thread::spawn(|| unsafe { injectorpp_test_pac_three() })
+        .join()
+        .unwrap();
+    assert_eq!(original, 3);
+}
@@ -0,0 +1,126 @@
+#![cfg(unix)]
+
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_int};
+
+use injectorpp::interface::injector::*;
+
+type Getenv = unsafe extern "C" fn(*const c_char) -> *mut c_char;
+
+#[test]
+fn test_when_called_c_should_fake_function_by_symbol() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called_c::<Getenv>("getenv")
+        .will_execute(injectorpp::fake!(
+            func_type: unsafe extern "C" fn(name: *const c_char) -> *mut c_char,
+            when: unsafe { CStr::from_ptr(name) } == c"INJECTORPP_BY_SYMBOL",
+            returns: c"faked".as_ptr() as *mut c_char
+        ));
+
+    assert_eq!(std::env::var("INJECTORPP_BY_SYMBOL").unwrap(), "faked");
+}
+
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+#[test]
+fn test_when_called_c_in_should_fake_function_of_module() {
+    use std::ffi::CString;
+    use std::os::raw::c_uint;
+
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called_c_in::<unsafe extern "C" fn(*const c_char, c_int, c_uint) -> c_int>(
+            Some("libc.so.6"),
+            "shm_open",
+        )
+        .will_execute(injectorpp::fake!(
+            func_type: unsafe extern "C" fn(_name: *const c_char, _oflag: c_int, _mode: c_uint) -> c_int,
+            returns: 32
+        ));
+
+    let name = CString::new("/by_symbol").unwrap();
+    assert_eq!(unsafe { libc::shm_open(name.as_ptr(), 0, 0o600) }, 32);
+}
+
+#[test]
+fn test_from_c_symbol_should_return_none_for_unknown_symbol() {
+    assert!(FuncPtr::from_c_symbol::<Getenv>(None, "injectorpp_no_such_symbol").is_none());
+    assert!(FuncPtr::from_c_symbol::<Getenv>(Some("libinjectorpp_none.so"), "getenv").is_none());
+}
+
+#[test]
+#[should_panic(expected = "no loaded module exports it")]
+fn test_when_called_c_with_unknown_symbol_should_panic() {
+    let mut injector = InjectorPP::new();
+    injector.when_called_c::<Getenv>("injectorpp_no_such_symbol");
+}
+
+#[test]
+#[should_panic(expected = "Signature mismatch")]
+fn test_when_called_c_with_fake_of_other_signature_should_panic() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called_c::<Getenv>("getenv")
+        .will_execute(injectorpp::fake!(
+            func_type: unsafe extern "C" fn(_name: *const c_char) -> c_int,
+            returns: 0
+        ));
+}
+
+type Scale = unsafe extern "C" fn(c_int) -> c_int;
+
+#[inline(never)]
+extern "C" fn tripled(value: c_int) -> c_int {
+    std::hint::black_box(value * 3)
+}
+
+#[inline(never)]
+extern "C" fn quadrupled(value: c_int) -> c_int {
+    std::hint::black_box(value * 4)
+}
+
+/// Returns the offset of `addr` from the base of the module holding it.
+fn module_offset(addr: usize) -> usize {
+    let mut info: libc::Dl_info = unsafe { std::mem::zeroed() };
+    assert_ne!(
+        unsafe { libc::dladdr(addr as *const libc::c_void, &mut info) },
+        0
+    );
+    addr - info.dli_fbase as usize
+}
+
+#[test]
+fn test_from_module_offset_should_fake_function_of_main_program() {
+    let offset = module_offset(tripled as extern "C" fn(c_int) -> c_int as usize);
+    let func = unsafe { FuncPtr::from_module_offset::<Scale>(None, offset, None) }.unwrap();
+
+    let mut injector = InjectorPP::new();
+    injector.when_called(func).will_execute(injectorpp::fake!(
+        func_type: unsafe extern "C" fn(_value: c_int) -> c_int,
+        returns: 7
+    ));
+
+    assert_eq!(tripled(2), 7);
+}
+
+#[test]
+fn test_from_module_offset_should_return_none_outside_code() {
+    assert!(unsafe { FuncPtr::from_module_offset::<Scale>(None, usize::MAX / 2, None) }.is_none());
+    assert!(unsafe {
+        FuncPtr::from_module_offset::<Scale>(Some("libinjectorpp_none.so"), 0x1000, None)
+    }
+    .is_none());
+}
+
+#[test]
+#[should_panic(expected = "too small for the")]
+fn test_from_module_offset_with_size_should_check_patch_against_it() {
+    let offset = module_offset(quadrupled as extern "C" fn(c_int) -> c_int as usize);
+    let func = unsafe { FuncPtr::from_module_offset::<Scale>(None, offset, Some(2)) }.unwrap();
+
+    let mut injector = InjectorPP::new();
+    injector.when_called(func).will_execute(injectorpp::fake!(
+        func_type: unsafe extern "C" fn(_value: c_int) -> c_int,
+        returns: 0
+    ));
+}
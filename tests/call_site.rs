@@ -0,0 +1,184 @@
+#![cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+
+use injectorpp::interface::injector::*;
+
+#[inline(never)]
+fn tiny_limit() -> u32 {
+    std::hint::black_box(3)
+}
+
+#[inline(never)]
+fn uploader_limit() -> u32 {
+    tiny_limit() + 100
+}
+
+#[inline(never)]
+fn downloader_limit() -> u32 {
+    tiny_limit() + 200
+}
+
+#[test]
+fn test_call_sites_in_should_fake_only_calls_of_the_caller() {
+    {
+        let mut injector = InjectorPP::new_global();
+        injector
+            .when_called(injectorpp::func!(fn (tiny_limit)() -> u32))
+            .call_sites_in(injectorpp::func!(fn (uploader_limit)() -> u32))
+            .will_execute(injectorpp::fake!(func_type: fn() -> u32, returns: 7));
+        injector.assert_patched();
+
+        assert_eq!(uploader_limit(), 107);
+        let other_thread = std::thread::spawn(uploader_limit).join().unwrap();
+        assert_eq!(other_thread, 107);
+        assert_eq!(downloader_limit(), 203);
+        assert_eq!(tiny_limit(), 3);
+        assert!(injectorpp::is_patched(injectorpp::func!(
+            fn (tiny_limit)() -> u32
+        )));
+    }
+
+    assert_eq!(uploader_limit(), 103);
+    assert!(!injectorpp::is_patched(injectorpp::func!(
+        fn (tiny_limit)() -> u32
+    )));
+}
+
+#[inline(never)]
+fn tiny_flag() -> bool {
+    std::hint::black_box(false)
+}
+
+#[inline(never)]
+fn flag_label() -> &'static str {
+    if tiny_flag() {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+#[test]
+fn test_call_sites_backend_should_fake_calls_of_the_program() {
+    let mut injector = InjectorPP::new_global();
+    let handle = injector
+        .when_called(injectorpp::func!(fn (tiny_flag)() -> bool))
+        .strategy(PatchStrategy::CallSites)
+        .will_return_boolean(true);
+
+    assert_eq!(flag_label(), "on");
+    // Calls through a function pointer do not go through a rewritten call.
+    let through_pointer: fn() -> bool = std::hint::black_box(tiny_flag);
+    assert!(!through_pointer());
+
+    handle.pause();
+    assert_eq!(flag_label(), "off");
+    handle.resume();
+    assert_eq!(flag_label(), "on");
+
+    injector.restore(injectorpp::func!(fn (tiny_flag)() -> bool));
+    assert_eq!(flag_label(), "off");
+}
+
+extern "C" fn fake_id() -> u32 {
+    4242
+}
+
+#[inline(never)]
+fn effective_group() -> u32 {
+    unsafe { libc::getegid() }
+}
+
+#[test]
+fn test_call_sites_in_should_fake_calls_of_libc_function() {
+    let real = unsafe { libc::getegid() };
+    {
+        let mut injector = InjectorPP::new_global();
+        unsafe {
+            injector
+                .when_called_unchecked(injectorpp::func_unchecked!(libc::getegid))
+                .call_sites_in(injectorpp::func!(fn (effective_group)() -> u32))
+                .will_execute_raw_unchecked(injectorpp::func_unchecked!(fake_id));
+        }
+
+        assert_eq!(effective_group(), 4242);
+        assert_eq!(unsafe { libc::getegid() }, real);
+    }
+
+    assert_eq!(effective_group(), real);
+}
+
+#[inline(never)]
+fn tiny_retries() -> u32 {
+    std::hint::black_box(5)
+}
+
+#[inline(never)]
+fn nested_retries() -> u32 {
+    tiny_retries() * 2
+}
+
+#[test]
+fn test_call_sites_fakes_should_stack() {
+    let mut first = InjectorPP::new_global();
+    first
+        .when_called(injectorpp::func!(fn (tiny_retries)() -> u32))
+        .call_sites_in(injectorpp::func!(fn (nested_retries)() -> u32))
+        .will_execute(injectorpp::fake!(func_type: fn() -> u32, returns: 1));
+    assert_eq!(nested_retries(), 2);
+
+    {
+        let mut second = InjectorPP::new_global();
+        second
+            .when_called(injectorpp::func!(fn (tiny_retries)() -> u32))
+            .call_sites_in(injectorpp::func!(fn (nested_retries)() -> u32))
+            .will_execute(injectorpp::fake!(func_type: fn() -> u32, returns: 4));
+        assert_eq!(nested_retries(), 8);
+    }
+
+    assert_eq!(nested_retries(), 2);
+    drop(first);
+    assert_eq!(nested_retries(), 10);
+}
+
+#[test]
+fn test_call_sites_fake_dropped_first_should_leave_later_fake() {
+    let mut first = InjectorPP::new_global();
+    first
+        .when_called(injectorpp::func!(fn (tiny_retries)() -> u32))
+        .call_sites_in(injectorpp::func!(fn (nested_retries)() -> u32))
+        .will_execute(injectorpp::fake!(func_type: fn() -> u32, returns: 1));
+    let mut second = InjectorPP::new_global();
+    second
+        .when_called(injectorpp::func!(fn (tiny_retries)() -> u32))
+        .call_sites_in(injectorpp::func!(fn (nested_retries)() -> u32))
+        .will_execute(injectorpp::fake!(func_type: fn() -> u32, returns: 4));
+
+    drop(first);
+    assert_eq!(nested_retries(), 8);
+    drop(second);
+    assert_eq!(nested_retries(), 10);
+}
+
+#[test]
+#[should_panic(expected = "is not a call to")]
+fn test_at_call_sites_should_panic_for_other_instruction() {
+    let mut injector = InjectorPP::new_global();
+    unsafe {
+        injector
+            .when_called(injectorpp::func!(fn (tiny_limit)() -> u32))
+            .at_call_sites(&[tiny_limit as *const () as usize])
+            .will_execute(injectorpp::fake!(func_type: fn() -> u32, returns: 7));
+    }
+}
+
+#[test]
+#[should_panic(expected = "requires an injector created with InjectorPP::new_global()")]
+fn test_call_sites_in_should_panic_for_thread_local_injector() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(fn (tiny_limit)() -> u32))
+        .call_sites_in(injectorpp::func!(fn (downloader_limit)() -> u32));
+}
@@ -0,0 +1,99 @@
+#![cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec",
+    target_arch = "arm"
+))]
+
+use injectorpp::interface::injector::*;
+
+#[inline(never)]
+fn delete_all() -> bool {
+    std::hint::black_box(false)
+}
+
+#[inline(never)]
+fn cleanup_job() -> bool {
+    let deleted = delete_all();
+    std::hint::black_box(deleted)
+}
+
+#[inline(never)]
+fn reset_command() -> bool {
+    let deleted = delete_all();
+    std::hint::black_box((deleted, "reset")).0
+}
+
+#[test]
+fn test_record_callers_should_log_return_address_of_each_call() {
+    let mut injector = InjectorPP::new();
+    let handle = injector
+        .when_called(injectorpp::func!(fn (delete_all)() -> bool))
+        .record_callers()
+        .will_return_boolean(true);
+
+    assert!(cleanup_job());
+    assert!(reset_command());
+    assert!(cleanup_job());
+
+    let callers = handle.callers();
+    assert_eq!(callers.len(), 3);
+    assert_eq!(callers[0], callers[2]);
+    assert_ne!(callers[0], callers[1]);
+    assert!(callers.iter().all(|caller| caller.return_address() != 0));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_record_callers_should_resolve_calling_function() {
+    let mut injector = InjectorPP::new();
+    let handle = injector
+        .when_called(injectorpp::func!(fn (delete_all)() -> bool))
+        .record_callers()
+        .will_return_boolean(true);
+
+    cleanup_job();
+    reset_command();
+
+    let callers = handle.callers();
+    let function = callers[0].function().unwrap();
+    assert!(function.ends_with("cleanup_job"), "{}", function);
+    assert!(callers[0].offset().unwrap() > 0);
+    assert!(callers[1].to_string().contains("reset_command+0x"));
+}
+
+#[test]
+fn test_record_callers_should_skip_calls_while_paused() {
+    let mut injector = InjectorPP::new();
+    let handle = injector
+        .when_called(injectorpp::func!(fn (delete_all)() -> bool))
+        .record_callers()
+        .will_return_boolean(true);
+
+    handle.pause();
+    assert!(!cleanup_job());
+    handle.resume();
+    assert!(reset_command());
+
+    assert_eq!(handle.callers().len(), 1);
+}
+
+#[test]
+#[should_panic(expected = "callers() requires the fake to be installed after record_callers()")]
+fn test_callers_without_record_callers_should_panic() {
+    let mut injector = InjectorPP::new();
+    let handle = injector
+        .when_called(injectorpp::func!(fn (delete_all)() -> bool))
+        .will_return_boolean(true);
+
+    handle.callers();
+}
+
+#[test]
+#[should_panic(expected = "record_callers() is only supported with thread-local dispatch")]
+fn test_record_callers_with_global_injector_should_panic() {
+    let mut injector = InjectorPP::new_global();
+    let _ = injector
+        .when_called(injectorpp::func!(fn (delete_all)() -> bool))
+        .record_callers();
+}
@@ -0,0 +1,83 @@
+#![cfg(all(target_os = "linux", target_arch = "x86_64"))]
+
+use injectorpp::interface::injector::*;
+
+const ENDBR64: [u8; 4] = [0xF3, 0x0F, 0x1E, 0xFA];
+
+// Functions built with `-Z cf-protection=branch`, which start with endbr64.
+std::arch::global_asm!(
+    ".text",
+    ".p2align 4",
+    ".globl injectorpp_test_endbr_one",
+    ".type injectorpp_test_endbr_one, @function",
+    "injectorpp_test_endbr_one:",
+    "endbr64",
+    "mov eax, 1",
+    "add eax, 0",
+    "ret",
+    ".size injectorpp_test_endbr_one, . - injectorpp_test_endbr_one",
+    ".p2align 4",
+    ".globl injectorpp_test_endbr_two",
+    ".type injectorpp_test_endbr_two, @function",
+    "injectorpp_test_endbr_two:",
+    "endbr64",
+    "mov eax, 2",
+    "add eax, 0",
+    "ret",
+    ".size injectorpp_test_endbr_two, . - injectorpp_test_endbr_two",
+);
+
+extern "C" {
+    fn injectorpp_test_endbr_one() -> u32;
+    fn injectorpp_test_endbr_two() -> u32;
+}
+
+extern "C" fn fake_seven() -> u32 {
+    7
+}
+
+fn first_bytes(func: unsafe extern "C" fn() -> u32) -> [u8; 4] {
+    unsafe { (func as *const [u8; 4]).read() }
+}
+
+#[test]
+fn test_global_fake_should_keep_endbr64() {
+    {
+        let mut injector = InjectorPP::new_global();
+        unsafe {
+            injector
+                .when_called_unchecked(injectorpp::func_unchecked!(injectorpp_test_endbr_one))
+                .will_execute_raw_unchecked(injectorpp::func_unchecked!(fake_seven));
+        }
+
+        assert_eq!(first_bytes(injectorpp_test_endbr_one), ENDBR64);
+
+        let indirect: unsafe extern "C" fn() -> u32 =
+            std::hint::black_box(injectorpp_test_endbr_one);
+        unsafe {
+            assert_eq!(indirect(), 7);
+        }
+    }
+
+    unsafe {
+        assert_eq!(injectorpp_test_endbr_one(), 1);
+    }
+}
+
+#[test]
+fn test_thread_local_fake_should_keep_endbr64() {
+    let mut injector = InjectorPP::new();
+    unsafe {
+        injector
+            .when_called_unchecked(injectorpp::func_unchecked!(injectorpp_test_endbr_two))
+            .will_execute_raw_unchecked(injectorpp::func_unchecked!(fake_seven));
+
+        assert_eq!(injectorpp_test_endbr_two(), 7);
+    }
+    assert_eq!(first_bytes(injectorpp_test_endbr_two), ENDBR64);
+
+    let original = std::thread::spawn(|| unsafe { injectorpp_test_endbr_two() })
+        .join()
+        .unwrap();
+    assert_eq!(original, 2);
+}
@@ -0,0 +1,110 @@
+#![cfg(target_os = "windows")]
+
+use injectorpp::interface::injector::*;
+use std::ffi::c_void;
+
+type GetValue = unsafe extern "system" fn(*mut c_void, *mut i32) -> i32;
+
+/// The vtable of an `ICounter` interface, `IUnknown` followed by `GetValue`.
+#[repr(C)]
+struct CounterVtbl {
+    query_interface: unsafe extern "system" fn(*mut c_void, *const u8, *mut *mut c_void) -> i32,
+    add_ref: unsafe extern "system" fn(*mut c_void) -> u32,
+    release: unsafe extern "system" fn(*mut c_void) -> u32,
+    get_value: GetValue,
+}
+
+#[repr(C)]
+struct Counter {
+    vtbl: &'static CounterVtbl,
+    value: i32,
+}
+
+const E_NOINTERFACE: i32 = 0x8000_4002u32 as i32;
+
+unsafe extern "system" fn query_interface(
+    _this: *mut c_void,
+    _iid: *const u8,
+    object: *mut *mut c_void,
+) -> i32 {
+    *object = std::ptr::null_mut();
+    E_NOINTERFACE
+}
+
+unsafe extern "system" fn add_ref(_this: *mut c_void) -> u32 {
+    1
+}
+
+unsafe extern "system" fn release(_this: *mut c_void) -> u32 {
+    1
+}
+
+unsafe extern "system" fn get_value(this: *mut c_void, value: *mut i32) -> i32 {
+    *value = (*(this as *mut Counter)).value;
+    0
+}
+
+static COUNTER_VTBL: CounterVtbl = CounterVtbl {
+    query_interface,
+    add_ref,
+    release,
+    get_value,
+};
+
+/// Calls `GetValue` through the vtable, as a COM client would.
+fn value_of(counter: &mut Counter) -> i32 {
+    let counter = std::hint::black_box(counter as *mut Counter);
+    let mut value = 0;
+    unsafe { ((*counter).vtbl.get_value)(counter as *mut c_void, &mut value) };
+    value
+}
+
+#[test]
+fn test_when_called_com_method_should_fake_slot_of_shared_vtable_until_dropped() {
+    let mut first = Counter {
+        vtbl: &COUNTER_VTBL,
+        value: 1,
+    };
+    let mut second = Counter {
+        vtbl: &COUNTER_VTBL,
+        value: 2,
+    };
+
+    {
+        let mut injector = InjectorPP::new_global();
+        unsafe {
+            injector
+                .when_called_com_method::<GetValue>(&mut first as *mut Counter as *mut c_void, 3)
+                .will_execute(injectorpp::fake!(
+                    func_type: unsafe extern "system" fn(_this: *mut c_void, value: *mut i32) -> i32,
+                    assign: { unsafe { *value = 42 } },
+                    returns: 0
+                ));
+        }
+
+        assert_eq!(value_of(&mut first), 42);
+        assert_eq!(value_of(&mut second), 42);
+        // The method itself is untouched, and callers calling it directly keep reaching it.
+        let mut value = 0;
+        unsafe { get_value(&mut first as *mut Counter as *mut c_void, &mut value) };
+        assert_eq!(value, 1);
+    }
+
+    assert_eq!(value_of(&mut first), 1);
+    assert_eq!(value_of(&mut second), 2);
+    assert_eq!(COUNTER_VTBL.get_value as usize, get_value as GetValue as usize);
+}
+
+#[test]
+#[should_panic(expected = "requires an injector created with InjectorPP::new_global()")]
+fn test_when_called_com_method_without_global_injector_should_panic() {
+    let mut counter = Counter {
+        vtbl: &COUNTER_VTBL,
+        value: 1,
+    };
+
+    let mut injector = InjectorPP::new();
+    unsafe {
+        injector.when_called_com_method::<GetValue>(&mut counter as *mut Counter as *mut c_void, 3);
+    }
+}
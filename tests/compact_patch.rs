@@ -0,0 +1,65 @@
+#![cfg(all(target_os = "linux", target_arch = "aarch64"))]
+
+use injectorpp::interface::injector::*;
+
+// An 8-byte function followed directly by another one, too small for the 12-byte patch.
+std::arch::global_asm!(
+    ".text",
+    ".p2align 4",
+    ".globl injectorpp_test_small_zero",
+    ".type injectorpp_test_small_zero, %function",
+    "injectorpp_test_small_zero:",
+    "mov w0, #0",
+    "ret",
+    ".size injectorpp_test_small_zero, . - injectorpp_test_small_zero",
+    ".globl injectorpp_test_small_two",
+    ".type injectorpp_test_small_two, %function",
+    "injectorpp_test_small_two:",
+    "mov w0, #2",
+    "ret",
+    ".size injectorpp_test_small_two, . - injectorpp_test_small_two",
+);
+
+extern "C" {
+    fn injectorpp_test_small_zero() -> u32;
+    fn injectorpp_test_small_two() -> u32;
+}
+
+extern "C" fn fake_seven() -> u32 {
+    7
+}
+
+#[test]
+fn test_global_fake_of_8_byte_function_should_use_compact_patch() {
+    let mut injector = InjectorPP::new_global();
+    unsafe {
+        injector
+            .when_called_unchecked(injectorpp::func_unchecked!(injectorpp_test_small_zero))
+            .will_execute_raw_unchecked(injectorpp::func_unchecked!(fake_seven));
+    }
+
+    let patch = injector
+        .active_patches()
+        .into_iter()
+        .find(|patch| patch.func_addr == injectorpp_test_small_zero as usize)
+        .unwrap();
+    assert_eq!(patch.patch_size, 8);
+
+    unsafe {
+        assert_eq!(injectorpp_test_small_zero(), 7);
+        assert_eq!(injectorpp_test_small_two(), 2);
+    }
+}
+
+#[test]
+fn test_thread_local_fake_of_8_byte_function_should_be_installed() {
+    let mut injector = InjectorPP::new();
+    unsafe {
+        injector
+            .when_called_unchecked(injectorpp::func_unchecked!(injectorpp_test_small_zero))
+            .will_execute_raw_unchecked(injectorpp::func_unchecked!(fake_seven));
+
+        assert_eq!(injectorpp_test_small_zero(), 7);
+        assert_eq!(injectorpp_test_small_two(), 2);
+    }
+}
@@ -8,6 +8,12 @@ extern "C" {
     fn memset(s: *mut c_void, c: c_int, n: usize) -> *mut c_void;
 }
 
+#[cfg(unix)]
+extern "C" {
+    fn open(path: *const c_char, flags: c_int, ...) -> c_int;
+    fn snprintf(s: *mut c_char, n: usize, format: *const c_char, ...) -> c_int;
+}
+
 #[test]
 fn test_fake_getenv_returns_custom_pointer() {
     let mut injector = InjectorPP::new();
@@ -72,3 +78,152 @@ fn test_fake_memset_assign() {
     assert_eq!(buf[0], 0x5A);
     assert_eq!(ret, ptr);
 }
+
+#[cfg(unix)]
+#[test]
+fn test_fake_variadic_open_should_see_fixed_arguments() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(
+            unsafe{} extern "C" fn (open)(*const c_char, c_int, ...) -> c_int
+        ))
+        .will_execute(injectorpp::fake!(
+            func_type: unsafe extern "C" fn(path: *const c_char, flags: c_int, ...) -> c_int,
+            when: CStr::from_ptr(path).to_str() == Ok("/injectorpp/missing") && flags == 0o101,
+            returns: 42,
+            times: 1
+        ));
+
+    let path = CString::new("/injectorpp/missing").unwrap();
+    let fd = unsafe { open(path.as_ptr(), 0o101, 0o644 as c_int) };
+    assert_eq!(fd, 42);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_fake_variadic_snprintf_should_not_write() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(
+            unsafe{} extern "C" fn (snprintf)(*mut c_char, usize, *const c_char, ...) -> c_int
+        ))
+        .will_execute(injectorpp::fake!(
+            func_type: unsafe extern "C" fn(_s: *mut c_char, n: usize, _format: *const c_char, ...) -> c_int,
+            returns: n as c_int
+        ));
+
+    let mut buf = [0 as c_char; 16];
+    let format = CString::new("%d-%s").unwrap();
+    let text = CString::new("text").unwrap();
+    let written = unsafe {
+        snprintf(
+            buf.as_mut_ptr(),
+            buf.len(),
+            format.as_ptr(),
+            7 as c_int,
+            text.as_ptr(),
+        )
+    };
+
+    assert_eq!(written, 16);
+    assert_eq!(buf[0], 0);
+}
+
+#[cfg(unix)]
+#[test]
+#[should_panic(expected = "Signature mismatch")]
+fn test_fake_variadic_with_non_variadic_fake_should_panic() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(
+            unsafe{} extern "C" fn (open)(*const c_char, c_int, ...) -> c_int
+        ))
+        .will_execute(injectorpp::fake!(
+            func_type: unsafe extern "C" fn(_path: *const c_char, _flags: c_int) -> c_int,
+            returns: 0
+        ));
+}
+
+#[cfg(unix)]
+#[inline(never)]
+fn read_header(fd: c_int) -> std::io::Result<usize> {
+    let mut buf = [0u8; 16];
+    let read = unsafe { libc::read(fd, buf.as_mut_ptr() as *mut c_void, buf.len()) };
+    if read < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    Ok(read as usize)
+}
+
+#[cfg(unix)]
+#[test]
+fn test_fake_with_errno_should_set_last_os_error() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(
+            unsafe{} extern "C" fn (libc::read)(c_int, *mut c_void, usize) -> isize
+        ))
+        .will_execute(injectorpp::fake!(
+            func_type: unsafe extern "C" fn(fd: c_int, _buf: *mut c_void, _count: usize) -> isize,
+            when: fd == 7,
+            returns: -1,
+            errno: libc::EIO,
+            times: 1
+        ));
+
+    let err = read_header(7).unwrap_err();
+    assert_eq!(err.raw_os_error(), Some(libc::EIO));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_variadic_fake_with_errno_should_set_last_os_error() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(
+            unsafe{} extern "C" fn (open)(*const c_char, c_int, ...) -> c_int
+        ))
+        .will_execute(injectorpp::fake!(
+            func_type: unsafe extern "C" fn(_path: *const c_char, _flags: c_int, ...) -> c_int,
+            returns: -1,
+            errno: libc::EACCES
+        ));
+
+    let path = CString::new("/injectorpp/secret").unwrap();
+    let fd = unsafe { open(path.as_ptr(), 0) };
+    assert_eq!(fd, -1);
+    assert_eq!(
+        std::io::Error::last_os_error().kind(),
+        std::io::ErrorKind::PermissionDenied
+    );
+}
+
+#[cfg(target_os = "windows")]
+extern "system" {
+    fn CloseHandle(hObject: *mut c_void) -> i32;
+}
+
+#[cfg(target_os = "windows")]
+#[test]
+fn test_fake_with_last_error_should_set_last_os_error() {
+    const ERROR_INVALID_HANDLE: u32 = 6;
+
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(
+            unsafe{} extern "system" fn (CloseHandle)(*mut c_void) -> i32
+        ))
+        .will_execute(injectorpp::fake!(
+            func_type: unsafe extern "system" fn(_handle: *mut c_void) -> i32,
+            returns: 0,
+            last_error: ERROR_INVALID_HANDLE
+        ));
+
+    let closed = unsafe { CloseHandle(std::ptr::null_mut()) };
+    assert_eq!(closed, 0);
+    assert_eq!(
+        std::io::Error::last_os_error().raw_os_error(),
+        Some(ERROR_INVALID_HANDLE as i32)
+    );
+}
@@ -0,0 +1,95 @@
+#![cfg(target_os = "linux")]
+
+use std::os::raw::{c_char, c_int};
+
+use injectorpp::interface::injector::*;
+
+// The member functions of a C++ class, as a C++ static library would define them:
+//
+//     namespace net {
+//     struct Socket {
+//         int connect(char const* host, int port);
+//         int connect(int port);
+//         int port() const;
+//     };
+//     }
+#[repr(C)]
+struct Socket {
+    port: c_int,
+}
+
+#[export_name = "_ZN3net6Socket7connectEPKci"]
+#[inline(never)]
+extern "C" fn connect_to_host(socket: *mut Socket, _host: *const c_char, port: c_int) -> c_int {
+    unsafe { (*socket).port = port };
+    0
+}
+
+#[export_name = "_ZN3net6Socket7connectEi"]
+#[inline(never)]
+extern "C" fn connect(socket: *mut Socket, port: c_int) -> c_int {
+    unsafe { (*socket).port = port };
+    0
+}
+
+#[export_name = "_ZNK3net6Socket4portEv"]
+#[inline(never)]
+extern "C" fn port(socket: *const Socket) -> c_int {
+    unsafe { (*socket).port }
+}
+
+type ConnectToHost = unsafe extern "C" fn(*mut Socket, *const c_char, c_int) -> c_int;
+
+#[test]
+fn test_when_called_cxx_should_fake_overload_named_with_its_parameters() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called_cxx::<ConnectToHost>("net::Socket::connect(char const*, int)")
+        .will_execute(injectorpp::fake!(
+            func_type: unsafe extern "C" fn(_socket: *mut Socket, _host: *const c_char, _port: c_int) -> c_int,
+            returns: -1
+        ));
+
+    let mut socket = Socket { port: 0 };
+    assert_eq!(connect_to_host(&mut socket, c"localhost".as_ptr(), 80), -1);
+    assert_eq!(connect(&mut socket, 80), 0);
+    assert_eq!(port(&socket), 80);
+}
+
+#[test]
+fn test_when_called_cxx_should_fake_function_by_mangled_symbol() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called_cxx::<unsafe extern "C" fn(*const Socket) -> c_int>("_ZNK3net6Socket4portEv")
+        .will_execute(injectorpp::fake!(
+            func_type: unsafe extern "C" fn(_socket: *const Socket) -> c_int,
+            returns: 443
+        ));
+
+    assert_eq!(port(&Socket { port: 80 }), 443);
+}
+
+#[test]
+#[should_panic(expected = "2 functions match it")]
+fn test_when_called_cxx_with_overloaded_name_should_panic() {
+    let mut injector = InjectorPP::new();
+    injector.when_called_cxx::<ConnectToHost>("net::Socket::connect");
+}
+
+#[test]
+fn test_from_cxx_name_should_match_names_without_parameters() {
+    let mut names: Vec<String> = FuncPtr::from_cxx_name::<ConnectToHost>("net::Socket::*")
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect();
+    names.sort();
+
+    assert_eq!(
+        names,
+        [
+            "net::Socket::connect(char const*, int)",
+            "net::Socket::connect(int)",
+            "net::Socket::port() const"
+        ]
+    );
+}
@@ -0,0 +1,110 @@
+#![cfg(all(
+    feature = "debuginfo-check",
+    any(target_os = "linux", target_os = "windows")
+))]
+
+use injectorpp::interface::injector::*;
+
+struct Meter {
+    offset: u64,
+}
+
+impl Meter {
+    #[inline(never)]
+    fn read(&self, channel: u8) -> u64 {
+        std::hint::black_box(self.offset + channel as u64)
+    }
+}
+
+#[inline(never)]
+fn scale(value: u64, factor: u32) -> u64 {
+    std::hint::black_box(value * factor as u64)
+}
+
+#[test]
+fn test_fake_matching_debug_info_should_be_installed() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(fn (scale)(u64, u32) -> u64))
+        .will_execute(injectorpp::fake!(
+            func_type: fn(value: u64, _factor: u32) -> u64,
+            returns: value
+        ));
+    injector
+        .when_called(injectorpp::func!(fn (Meter::read)(&Meter, u8) -> u64))
+        .will_execute(injectorpp::fake!(
+            func_type: fn(self: &Meter, channel: u8) -> u64,
+            returns: self.offset * channel as u64
+        ));
+
+    assert_eq!(scale(6, 7), 6);
+    assert_eq!(Meter { offset: 2 }.read(3), 6);
+}
+
+#[test]
+#[should_panic(expected = "takes 2 parameters but the fake is declared with 1")]
+fn test_fake_with_missing_parameter_should_panic() {
+    let mut injector = InjectorPP::new();
+    unsafe {
+        injector
+            .when_called_unchecked(injectorpp::func_unchecked!(scale))
+            .will_execute_raw_unchecked(
+                injectorpp::fake!(
+                    func_type: fn(value: u64) -> u64,
+                    returns: value
+                )
+                .0,
+            );
+    }
+}
+
+#[test]
+#[should_panic(expected = "parameter 1 of scale")]
+fn test_fake_with_smaller_parameter_should_panic() {
+    let mut injector = InjectorPP::new();
+    unsafe {
+        injector
+            .when_called_unchecked(injectorpp::func_unchecked!(scale))
+            .will_execute_raw_unchecked(
+                injectorpp::fake!(
+                    func_type: fn(value: u32, _factor: u32) -> u64,
+                    returns: value as u64
+                )
+                .0,
+            );
+    }
+}
+
+#[test]
+#[should_panic(expected = "returns 8 bytes but the fake returns 4 bytes")]
+fn test_fake_with_smaller_return_value_should_panic() {
+    let mut injector = InjectorPP::new();
+    unsafe {
+        injector
+            .when_called_unchecked(injectorpp::func_unchecked!(scale))
+            .will_execute_raw_unchecked(
+                injectorpp::fake!(
+                    func_type: fn(value: u64, _factor: u32) -> u32,
+                    returns: value as u32
+                )
+                .0,
+            );
+    }
+}
+
+#[test]
+#[should_panic(expected = "parameter 2 of read")]
+fn test_fake_of_method_with_wrong_parameter_should_panic() {
+    let mut injector = InjectorPP::new();
+    unsafe {
+        injector
+            .when_called_unchecked(injectorpp::func_unchecked!(Meter::read))
+            .will_execute_raw_unchecked(
+                injectorpp::fake!(
+                    func_type: fn(_meter: &Meter, channel: u64) -> u64,
+                    returns: channel
+                )
+                .0,
+            );
+    }
+}
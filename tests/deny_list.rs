@@ -0,0 +1,61 @@
+use injectorpp::interface::injector::*;
+
+mod pool {
+    #[inline(never)]
+    pub fn release(slot: usize) -> bool {
+        std::hint::black_box(slot) > 0
+    }
+}
+
+#[test]
+#[should_panic(expected = "Refusing to fake alloc::vec::Vec<u8>::capacity")]
+fn test_fake_vec_capacity_should_panic() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(fn (Vec::<u8>::capacity)(&Vec<u8>) -> usize))
+        .will_execute_raw(injectorpp::closure!(|_: &Vec<u8>| 0, fn(&Vec<u8>) -> usize));
+}
+
+#[test]
+#[should_panic(expected = "on the deny-list as \"std::string::String::len\"")]
+fn test_fake_string_len_with_method_should_panic() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::method!(String::len))
+        .will_execute_raw(injectorpp::closure!(|_: &String| 0, fn(&String) -> usize));
+}
+
+#[test]
+#[should_panic(expected = "on the deny-list as \"malloc\"")]
+fn test_fake_malloc_unchecked_should_panic() {
+    let mut injector = InjectorPP::new();
+    unsafe {
+        injector
+            .when_called_unchecked(injectorpp::func_unchecked!(libc::malloc))
+            .will_execute_raw_unchecked(injectorpp::func_unchecked!(libc::calloc));
+    }
+}
+
+#[test]
+#[should_panic(expected = "Call allow_dangerous() on the injector")]
+fn test_fake_function_added_to_deny_list_should_panic() {
+    InjectorPP::deny("deny_list::pool::release");
+
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(fn (pool::release)(usize) -> bool))
+        .will_return_boolean(false);
+}
+
+#[test]
+fn test_allow_dangerous_should_fake_denied_function() {
+    InjectorPP::deny("deny_list::pool");
+
+    let mut injector = InjectorPP::new();
+    injector
+        .allow_dangerous()
+        .when_called(injectorpp::func!(fn (pool::release)(usize) -> bool))
+        .will_return_boolean(false);
+
+    assert!(!pool::release(3));
+}
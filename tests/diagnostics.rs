@@ -0,0 +1,91 @@
+use injectorpp::interface::injector::*;
+use std::process::Command;
+
+/// Set for the copy of this test binary that installs a fake and reports through stderr.
+const CHILD: &str = "INJECTORPP_TEST_DIAGNOSTICS_CHILD";
+
+#[inline(never)]
+fn logged_func() -> i32 {
+    std::hint::black_box(1)
+}
+
+/// Runs `test_diagnostics_child` in a new process and returns its stderr.
+///
+/// `log` is the value of `INJECTORPP_LOG`, and `logging` the value the child passes to
+/// `set_diagnostic_logging`, if any.
+fn child_stderr(log: Option<&str>, logging: Option<bool>) -> String {
+    let mut command = Command::new(std::env::current_exe().unwrap());
+    command
+        .args(["--exact", "test_diagnostics_child", "--nocapture"])
+        .env(
+            CHILD,
+            logging.map_or("", |enabled| if enabled { "on" } else { "off" }),
+        )
+        .env_remove("INJECTORPP_LOG");
+    if let Some(log) = log {
+        command.env("INJECTORPP_LOG", log);
+    }
+
+    let output = command.output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    String::from_utf8(output.stderr).unwrap()
+}
+
+fn assert_patch_logged(stderr: &str) {
+    for message in ["patch installed (", "patch restored ("] {
+        assert!(
+            stderr.contains(&format!("[injectorpp] {}", message)),
+            "{}",
+            stderr
+        );
+    }
+}
+
+#[test]
+fn test_diagnostics_child() {
+    let Ok(logging) = std::env::var(CHILD) else {
+        return;
+    };
+    match logging.as_str() {
+        "on" => InjectorPP::set_diagnostic_logging(true),
+        "off" => InjectorPP::set_diagnostic_logging(false),
+        _ => {}
+    }
+
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(fn (logged_func)() -> i32))
+        .will_execute(injectorpp::fake!(
+            func_type: fn() -> i32,
+            returns: 2
+        ));
+    assert_eq!(logged_func(), 2);
+    drop(injector);
+    assert_eq!(logged_func(), 1);
+}
+
+#[test]
+fn test_injectorpp_log_should_print_patch_and_restore() {
+    let stderr = child_stderr(Some("1"), None);
+    assert_patch_logged(&stderr);
+}
+
+#[test]
+fn test_injectorpp_log_off_should_stay_silent() {
+    for log in [None, Some("0"), Some("off"), Some("false")] {
+        let stderr = child_stderr(log, None);
+        assert!(!stderr.contains("[injectorpp]"), "{:?}: {}", log, stderr);
+    }
+}
+
+#[test]
+fn test_set_diagnostic_logging_true_should_print_without_env() {
+    let stderr = child_stderr(None, Some(true));
+    assert_patch_logged(&stderr);
+}
+
+#[test]
+fn test_set_diagnostic_logging_false_should_override_env() {
+    let stderr = child_stderr(Some("1"), Some(false));
+    assert!(!stderr.contains("[injectorpp]"), "{}", stderr);
+}
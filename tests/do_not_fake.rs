@@ -0,0 +1,101 @@
+#![cfg(any(target_os = "linux", target_vendor = "apple", windows))]
+
+use injectorpp::interface::injector::*;
+
+#[injectorpp::do_not_fake]
+#[inline(never)]
+fn checked_len(buffer: &[u8]) -> usize {
+    std::hint::black_box(buffer.len())
+}
+
+struct Arena {
+    used: usize,
+}
+
+#[injectorpp::do_not_fake]
+impl Arena {
+    #[inline(never)]
+    fn remaining(&self) -> usize {
+        std::hint::black_box(4096 - self.used)
+    }
+}
+
+#[injectorpp::do_not_fake]
+mod layout {
+    #[inline(never)]
+    pub fn align_up(value: usize) -> usize {
+        std::hint::black_box((value + 7) & !7)
+    }
+}
+
+#[inline(never)]
+fn unmarked_len(buffer: &[u8]) -> usize {
+    std::hint::black_box(buffer.iter().filter(|byte| **byte != 0).count())
+}
+
+#[test]
+#[should_panic(expected = "marks it #[do_not_fake]")]
+fn test_fake_of_marked_function_should_panic() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(fn (checked_len)(&[u8]) -> usize))
+        .will_execute_raw(injectorpp::closure!(|_: &[u8]| 0, fn(&[u8]) -> usize));
+}
+
+#[test]
+#[should_panic(expected = "marks it #[do_not_fake]")]
+fn test_fake_of_marked_method_should_panic() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(fn (Arena::remaining)(&Arena) -> usize))
+        .will_execute_raw(injectorpp::closure!(|_: &Arena| 0, fn(&Arena) -> usize));
+}
+
+#[test]
+#[should_panic(expected = "marks it #[do_not_fake]")]
+fn test_fake_of_function_in_marked_module_should_panic() {
+    let mut injector = InjectorPP::new();
+    unsafe {
+        injector
+            .when_called_unchecked(injectorpp::func_unchecked!(layout::align_up))
+            .will_execute_raw_unchecked(injectorpp::func_unchecked!(layout::align_up));
+    }
+}
+
+#[test]
+fn test_fake_of_unmarked_function_should_be_installed() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(fn (unmarked_len)(&[u8]) -> usize))
+        .will_execute(injectorpp::fake!(
+            func_type: fn(_buffer: &[u8]) -> usize,
+            returns: 0
+        ));
+
+    assert_eq!(unmarked_len(&[1, 2, 3]), 0);
+    assert_eq!(checked_len(&[1, 2, 3]), 3);
+}
+
+#[test]
+fn test_fake_of_marked_function_with_override_should_be_installed() {
+    let mut injector = InjectorPP::new();
+    unsafe {
+        injector.ignore_do_not_fake();
+    }
+    injector
+        .when_called(injectorpp::func!(fn (checked_len)(&[u8]) -> usize))
+        .will_execute(injectorpp::fake!(
+            func_type: fn(buffer: &[u8]) -> usize,
+            returns: buffer.len() * 2
+        ));
+    injector
+        .when_called(injectorpp::func!(fn (Arena::remaining)(&Arena) -> usize))
+        .will_execute(injectorpp::fake!(
+            func_type: fn(_arena: &Arena) -> usize,
+            returns: 0
+        ));
+
+    assert_eq!(checked_len(&[1, 2, 3]), 6);
+    assert_eq!(Arena { used: 96 }.remaining(), 0);
+    assert_eq!(layout::align_up(9), 16);
+}
@@ -0,0 +1,108 @@
+use injectorpp::interface::injector::*;
+
+trait Named {
+    fn name(&self) -> String;
+}
+
+trait Storage: Named {
+    fn read(&self, key: &str) -> Option<String>;
+
+    fn write(&mut self, key: &str, value: &str) -> bool;
+
+    fn boxed(self: Box<Self>) -> usize;
+}
+
+mod plugin {
+    use super::*;
+
+    struct Disk {
+        root: String,
+    }
+
+    impl Named for Disk {
+        #[inline(never)]
+        fn name(&self) -> String {
+            std::hint::black_box(format!("disk at {}", self.root))
+        }
+    }
+
+    impl Storage for Disk {
+        #[inline(never)]
+        fn read(&self, key: &str) -> Option<String> {
+            std::hint::black_box(format!("{}/{}", self.root, key));
+            None
+        }
+
+        #[inline(never)]
+        fn write(&mut self, key: &str, value: &str) -> bool {
+            std::hint::black_box(key.len() + value.len() + self.root.len()) == 0
+        }
+
+        #[inline(never)]
+        fn boxed(self: Box<Self>) -> usize {
+            std::hint::black_box(self.root.len())
+        }
+    }
+
+    pub fn open(root: &str) -> Box<dyn Storage> {
+        Box::new(Disk {
+            root: root.to_string(),
+        })
+    }
+}
+
+#[test]
+fn test_dyn_func_should_fake_method_of_unknown_concrete_type() {
+    let mut storage = plugin::open("/data");
+    let other = plugin::open("/backup");
+
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(unsafe { injectorpp::dyn_func!(storage, 1, fn(&str) -> Option<String>) })
+        .will_execute(injectorpp::fake!(
+            func_type: fn(_this: *const (), key: &str) -> Option<String>,
+            when: key == "config",
+            returns: Some("faked".to_string())
+        ));
+    injector
+        .when_called(unsafe { injectorpp::dyn_func!(storage, 2, fn(&str, &str) -> bool) })
+        .will_execute(injectorpp::fake!(
+            func_type: fn(_this: *const (), _key: &str, _value: &str) -> bool,
+            returns: true,
+            times: 1
+        ));
+
+    assert_eq!(storage.read("config"), Some("faked".to_string()));
+    assert_eq!(other.read("config"), Some("faked".to_string()));
+    assert!(storage.write("config", "value"));
+    assert_eq!(storage.name(), "disk at /data");
+}
+
+#[test]
+fn test_dyn_func_should_fake_supertrait_and_boxed_receiver_methods() {
+    let storage = plugin::open("/data");
+
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(unsafe { injectorpp::dyn_func!(storage, 0, fn() -> String) })
+        .will_execute(injectorpp::fake!(
+            func_type: fn(_this: *const ()) -> String,
+            returns: "fake disk".to_string()
+        ));
+    injector
+        .when_called(unsafe { injectorpp::dyn_func!(storage, 3, fn() -> usize) })
+        .will_execute(injectorpp::fake!(
+            func_type: fn(_this: *const ()) -> usize,
+            returns: 7
+        ));
+
+    assert_eq!(storage.name(), "fake disk");
+    assert_eq!(storage.boxed(), 7);
+}
+
+#[test]
+#[should_panic(expected = "dyn_func! needs a trait object")]
+fn test_dyn_func_with_slice_should_panic() {
+    let values: Box<[u32]> = vec![1, 2, 3].into_boxed_slice();
+    let _ = unsafe { injectorpp::dyn_func!(values, 0, fn() -> u32) };
+}
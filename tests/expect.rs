@@ -0,0 +1,92 @@
+#![cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec",
+    target_arch = "arm"
+))]
+
+use injectorpp::interface::injector::*;
+use std::io;
+
+#[inline(never)]
+fn remove_file(path: &str) -> io::Result<()> {
+    std::hint::black_box(std::fs::remove_file(path))
+}
+
+#[inline(never)]
+fn retry_delay(service: &str, attempt: u32) -> u64 {
+    std::hint::black_box(service.len() as u64 * attempt as u64)
+}
+
+#[inline(never)]
+fn is_enabled() -> bool {
+    std::hint::black_box(false)
+}
+
+#[test]
+fn test_expect_with_matching_arguments_should_return_fake_value() {
+    let mut injector = InjectorPP::new();
+    injector
+        .expect(injectorpp::func!(fn (remove_file)(&str) -> io::Result<()>))
+        .with(|path: &str| path == "/tmp/injectorpp/missing")
+        .returning(|_: &str| -> io::Result<()> { Ok(()) })
+        .times(2);
+
+    assert!(remove_file("/tmp/injectorpp/missing").is_ok());
+    assert!(remove_file("/tmp/injectorpp/missing").is_ok());
+}
+
+#[test]
+fn test_expect_returning_should_receive_arguments() {
+    let mut injector = InjectorPP::new();
+    let mut calls = 0;
+    injector
+        .expect(injectorpp::func!(fn (retry_delay)(&str, u32) -> u64))
+        .returning(move |service: &str, attempt: u32| {
+            calls += 1;
+            service.len() as u64 * 1000 + attempt as u64 + calls
+        });
+
+    assert_eq!(retry_delay("auth", 3), 4004);
+    assert_eq!(retry_delay("auth", 3), 4005);
+
+    injector
+        .expect(injectorpp::func!(fn (is_enabled)() -> bool))
+        .returning(|| true);
+
+    assert!(is_enabled());
+}
+
+#[test]
+#[should_panic(expected = "called with unexpected arguments")]
+fn test_expect_with_unexpected_arguments_should_panic() {
+    let mut injector = InjectorPP::new();
+    injector
+        .expect(injectorpp::func!(fn (retry_delay)(&str, u32) -> u64))
+        .with(|_: &str, attempt: u32| attempt < 3)
+        .returning(|_: &str, _: u32| 0u64);
+
+    assert_eq!(retry_delay("auth", 1), 0);
+    retry_delay("auth", 5);
+}
+
+#[test]
+#[should_panic(expected = "Signature mismatch")]
+fn test_expect_returning_with_wrong_signature_should_panic() {
+    let mut injector = InjectorPP::new();
+    injector
+        .expect(injectorpp::func!(fn (retry_delay)(&str, u32) -> u64))
+        .returning(|_: &str, _: u64| 0u64);
+}
+
+#[test]
+#[should_panic(expected = "expected to be called 2 time(s), but it is actually called 1 time(s)")]
+fn test_expect_times_not_reached_should_panic() {
+    let mut injector = InjectorPP::new();
+    injector
+        .expect(injectorpp::func!(fn (is_enabled)() -> bool))
+        .returning(|| true)
+        .times(2);
+
+    assert!(is_enabled());
+}
@@ -0,0 +1,130 @@
+#![cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec",
+    target_arch = "arm"
+))]
+
+use injectorpp::interface::injector::*;
+
+#[inline(never)]
+fn quota() -> i32 {
+    core::hint::black_box(core::hint::black_box(90) + core::hint::black_box(10))
+}
+
+#[inline(never)]
+fn global_quota() -> i32 {
+    core::hint::black_box(core::hint::black_box(190) + core::hint::black_box(10))
+}
+
+#[inline(never)]
+fn stacked_global_quota() -> i32 {
+    core::hint::black_box(core::hint::black_box(290) + core::hint::black_box(10))
+}
+
+#[inline(never)]
+fn is_throttled() -> bool {
+    std::hint::black_box(false)
+}
+
+#[test]
+fn test_paused_fake_should_call_original_until_resumed() {
+    let mut injector = InjectorPP::new();
+    let handle = injector
+        .when_called(injectorpp::func!(fn (quota)() -> i32))
+        .will_execute(injectorpp::fake!(
+            func_type: fn() -> i32,
+            returns: 5,
+            times: 2
+        ));
+
+    assert_eq!(quota(), 5);
+    handle.pause();
+    assert_eq!(quota(), 100);
+    assert_eq!(quota(), 100);
+    handle.resume();
+    assert_eq!(quota(), 5);
+
+    drop(injector);
+    handle.resume();
+    assert_eq!(quota(), 100);
+}
+
+#[test]
+fn test_paused_fake_should_uncover_fake_below() {
+    let mut outer = InjectorPP::new();
+    outer
+        .when_called(injectorpp::func!(fn (is_throttled)() -> bool))
+        .will_return_boolean(true);
+
+    let mut inner = InjectorPP::new();
+    let handle = inner
+        .when_called(injectorpp::func!(fn (is_throttled)() -> bool))
+        .will_return_boolean(false);
+
+    assert!(!is_throttled());
+    handle.pause();
+    assert!(is_throttled());
+    handle.resume();
+    assert!(!is_throttled());
+}
+
+#[test]
+fn test_paused_global_fake_should_call_original_until_resumed() {
+    let mut injector = InjectorPP::new_global();
+    let handle = injector
+        .when_called(injectorpp::func!(fn (global_quota)() -> i32))
+        .will_execute(injectorpp::fake!(
+            func_type: fn() -> i32,
+            returns: 5
+        ));
+
+    assert_eq!(global_quota(), 5);
+    handle.pause();
+    handle.pause();
+    assert_eq!(global_quota(), 200);
+    handle.resume();
+    assert_eq!(global_quota(), 5);
+
+    handle.pause();
+    drop(injector);
+    assert_eq!(global_quota(), 200);
+}
+
+#[test]
+#[should_panic(expected = "Cannot pause a global fake while a later global fake")]
+fn test_pause_global_fake_below_another_should_panic() {
+    let mut first = InjectorPP::new_global();
+    let handle = first
+        .when_called(injectorpp::func!(fn (stacked_global_quota)() -> i32))
+        .will_execute(injectorpp::fake!(
+            func_type: fn() -> i32,
+            returns: 1
+        ));
+
+    let mut second = InjectorPP::new_global();
+    second
+        .when_called(injectorpp::func!(fn (stacked_global_quota)() -> i32))
+        .will_execute(injectorpp::fake!(
+            func_type: fn() -> i32,
+            returns: 2
+        ));
+
+    handle.pause();
+}
+
+#[tokio::test]
+async fn test_paused_task_local_fake_should_call_original() {
+    let mut injector = InjectorPP::new_task_local();
+    let handle = injector
+        .when_called(injectorpp::func!(fn (quota)() -> i32))
+        .will_execute(injectorpp::fake!(
+            func_type: fn() -> i32,
+            returns: 5
+        ));
+
+    handle.pause();
+    assert_eq!(injector.scope(async { quota() }).await, 100);
+    handle.resume();
+    assert_eq!(injector.scope(async { quota() }).await, 5);
+}
@@ -0,0 +1,91 @@
+#![cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec",
+    target_arch = "arm"
+))]
+
+use injectorpp::interface::injector::*;
+use std::sync::LazyLock;
+
+#[inline(never)]
+fn file_exists() -> bool {
+    std::hint::black_box(false)
+}
+
+#[inline(never)]
+fn read_config() -> String {
+    std::hint::black_box("real").to_string()
+}
+
+#[inline(never)]
+fn is_connected() -> bool {
+    std::hint::black_box(true)
+}
+
+static FILESYSTEM_HAPPY_PATH: LazyLock<FakeSet> = LazyLock::new(|| {
+    FakeSet::new("filesystem happy path")
+        .with(|injector| {
+            injector
+                .when_called(injectorpp::func!(fn (file_exists)() -> bool))
+                .will_return_boolean(true);
+        })
+        .with(|injector| {
+            injector
+                .when_called(injectorpp::func!(fn (read_config)() -> String))
+                .will_execute(injectorpp::fake!(
+                    func_type: fn() -> String,
+                    returns: "fake".to_string()
+                ));
+        })
+});
+
+fn network_offline() -> FakeSet {
+    FakeSet::new("network offline").with(|injector| {
+        injector
+            .when_called(injectorpp::func!(fn (is_connected)() -> bool))
+            .will_return_boolean(false);
+    })
+}
+
+#[test]
+fn test_fake_set_should_install_into_any_injector() {
+    for _ in 0..2 {
+        let mut injector = InjectorPP::new();
+        injector.install(&FILESYSTEM_HAPPY_PATH);
+
+        assert!(file_exists());
+        assert_eq!(read_config(), "fake");
+    }
+
+    assert!(!file_exists());
+    assert_eq!(read_config(), "real");
+}
+
+#[test]
+fn test_composed_fake_sets_should_install_all_fakes() {
+    let offline_start = FakeSet::new("offline start")
+        .include(&FILESYSTEM_HAPPY_PATH)
+        .include(&network_offline());
+    assert_eq!(offline_start.name(), "offline start");
+
+    let mut injector = InjectorPP::new();
+    injector.install(&offline_start);
+
+    assert!(file_exists());
+    assert_eq!(read_config(), "fake");
+    assert!(!is_connected());
+}
+
+#[test]
+#[should_panic(expected = "Failed to install fake set 'broken': Signature mismatch")]
+fn test_fake_set_install_failure_should_name_set() {
+    let broken = FakeSet::new("broken").with(|injector| {
+        injector
+            .when_called(injectorpp::func!(fn (read_config)() -> String))
+            .will_return_boolean(true);
+    });
+
+    let mut injector = InjectorPP::new();
+    injector.install(&broken);
+}
@@ -0,0 +1,157 @@
+#![cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec",
+    target_arch = "arm"
+))]
+
+use std::io::{self, ErrorKind};
+
+use injectorpp::interface::injector::*;
+
+#[inline(never)]
+fn send(payload: &[u8]) -> io::Result<usize> {
+    Ok(std::hint::black_box(payload.len()))
+}
+
+/// Sends 1000 messages with `send()` failing at the rate of `faults`, and returns which failed.
+fn failed_sends(faults: &FaultInjector) -> Vec<bool> {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(fn (send)(&[u8]) -> io::Result<usize>))
+        .will_fail(
+            faults,
+            injectorpp::fake!(
+                func_type: fn(_payload: &[u8]) -> io::Result<usize>,
+                returns: Err(ErrorKind::ConnectionReset.into())
+            ),
+        );
+
+    (0..1000).map(|_| send(b"ping").is_err()).collect()
+}
+
+#[test]
+fn test_will_fail_should_fail_same_calls_for_same_seed() {
+    let first = failed_sends(&FaultInjector::new(2024));
+    let second = failed_sends(&FaultInjector::new(2024));
+    let other = failed_sends(&FaultInjector::new(2025));
+
+    assert_eq!(first, second);
+    assert_ne!(first, other);
+}
+
+#[test]
+fn test_will_fail_should_fail_share_of_calls_and_send_others_to_original() {
+    let faults = FaultInjector::new(11);
+    let failed = failed_sends(&faults)
+        .iter()
+        .filter(|&&failed| failed)
+        .count();
+
+    assert!(
+        (50..150).contains(&failed),
+        "{} of 1000 sends failed",
+        failed
+    );
+    assert_eq!(faults.calls(), 1000);
+    assert_eq!(faults.failures(), failed as u64);
+    assert_eq!(send(b"ping").unwrap(), 4);
+}
+
+#[test]
+fn test_will_fail_should_fail_fs_write() {
+    let path = std::env::temp_dir()
+        .join(format!("injectorpp-faults-{}", std::process::id()))
+        .display()
+        .to_string();
+    let faults = FaultInjector::new(3).failure_rate(0.5);
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(
+            fn (std::fs::write::<String, String>)(String, String) -> io::Result<()>
+        ))
+        .will_fail(
+            &faults,
+            injectorpp::fake!(
+                func_type: fn(_path: String, _contents: String) -> io::Result<()>,
+                returns: Err(ErrorKind::StorageFull.into())
+            ),
+        );
+
+    let contents = "data".to_string();
+    let written = (0..20)
+        .filter(|_| std::fs::write(path.clone(), contents.clone()).is_ok())
+        .count();
+    let _ = std::fs::remove_file(&path);
+
+    assert_eq!(written as u64, faults.calls() - faults.failures());
+    assert!(faults.failures() > 0 && written > 0, "{}", faults);
+}
+
+#[test]
+fn test_inject_faults_should_fail_share_of_fake_calls() {
+    let faults = FaultInjector::new(5).failure_rate(0.25);
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(fn (send)(&[u8]) -> io::Result<usize>))
+        .inject_faults(
+            &faults,
+            injectorpp::fake!(
+                func_type: fn(_payload: &[u8]) -> io::Result<usize>,
+                returns: Err(ErrorKind::TimedOut.into())
+            ),
+        )
+        .will_execute(injectorpp::fake!(
+            func_type: fn(_payload: &[u8]) -> io::Result<usize>,
+            returns: Ok(0)
+        ));
+
+    let results: Vec<_> = (0..100).map(|_| send(b"ping")).collect();
+    let failed = results.iter().filter(|result| result.is_err()).count();
+
+    assert_eq!(failed as u64, faults.failures());
+    assert!(results.iter().flatten().all(|&sent| sent == 0));
+}
+
+#[test]
+fn test_should_fail_should_draw_failures_for_closure() {
+    let faults = FaultInjector::new(9).failure_rate(0.5);
+    let draws = faults.clone();
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(fn (send)(&[u8]) -> io::Result<usize>))
+        .will_execute_closure(injectorpp::boxed_closure!(
+            move |payload: &[u8]| if draws.should_fail() {
+                Err(io::Error::other("dropped"))
+            } else {
+                Ok(payload.len() * 2)
+            },
+            fn(&[u8]) -> io::Result<usize>
+        ));
+
+    let sent = (0..10).filter(|_| send(b"ping").is_ok()).count();
+
+    assert_eq!(faults.calls(), 10);
+    assert_eq!(sent as u64, 10 - faults.failures());
+}
+
+#[test]
+#[should_panic(expected = "not with InjectorPP::new_global()")]
+fn test_will_fail_with_global_injector_should_panic() {
+    let mut injector = InjectorPP::new_global();
+    injector
+        .when_called(injectorpp::func!(fn (send)(&[u8]) -> io::Result<usize>))
+        .will_fail(
+            &FaultInjector::new(0),
+            injectorpp::fake!(
+                func_type: fn(_payload: &[u8]) -> io::Result<usize>,
+                returns: Err(ErrorKind::Other.into())
+            ),
+        );
+}
+
+#[test]
+#[should_panic(expected = "The failure rate must be within 0.0 and 1.0")]
+fn test_failure_rate_out_of_range_should_panic() {
+    let _ = FaultInjector::new(0).failure_rate(1.5);
+}
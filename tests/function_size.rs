@@ -0,0 +1,142 @@
+#![cfg(all(target_os = "linux", target_arch = "x86_64"))]
+
+use injectorpp::interface::injector::*;
+
+// Two 3-byte functions laid out back to back, with no padding in between.
+std::arch::global_asm!(
+    ".text",
+    ".p2align 4",
+    ".globl injectorpp_test_tiny_zero",
+    ".type injectorpp_test_tiny_zero, @function",
+    "injectorpp_test_tiny_zero:",
+    "xor eax, eax",
+    "ret",
+    ".size injectorpp_test_tiny_zero, . - injectorpp_test_tiny_zero",
+    ".globl injectorpp_test_tiny_one",
+    ".type injectorpp_test_tiny_one, @function",
+    "injectorpp_test_tiny_one:",
+    "mov al, 1",
+    "ret",
+    ".size injectorpp_test_tiny_one, . - injectorpp_test_tiny_one",
+);
+
+// A 3-byte stub followed by a stub that is never called, as in hand-written assembly.
+std::arch::global_asm!(
+    ".text",
+    ".p2align 4",
+    ".globl injectorpp_test_tiny_stub",
+    ".type injectorpp_test_tiny_stub, @function",
+    "injectorpp_test_tiny_stub:",
+    "xor eax, eax",
+    "ret",
+    ".size injectorpp_test_tiny_stub, . - injectorpp_test_tiny_stub",
+    ".type injectorpp_test_tiny_unused, @function",
+    "injectorpp_test_tiny_unused:",
+    ".fill 29, 1, 0xcc",
+    ".size injectorpp_test_tiny_unused, . - injectorpp_test_tiny_unused",
+);
+
+// A loop starting at offset 2, inside the 5-byte patch.
+std::arch::global_asm!(
+    ".text",
+    ".p2align 4",
+    ".globl injectorpp_test_loop_back",
+    ".type injectorpp_test_loop_back, @function",
+    "injectorpp_test_loop_back:",
+    "xor eax, eax",
+    "2:",
+    "inc eax",
+    "cmp eax, 3",
+    "jne 2b",
+    "ret",
+    ".size injectorpp_test_loop_back, . - injectorpp_test_loop_back",
+);
+
+extern "C" {
+    fn injectorpp_test_loop_back() -> u32;
+    fn injectorpp_test_tiny_zero() -> u8;
+    fn injectorpp_test_tiny_one() -> u8;
+    fn injectorpp_test_tiny_stub() -> u8;
+}
+
+#[inline(never)]
+fn regular(value: u32) -> u32 {
+    std::hint::black_box(value) + 1
+}
+
+#[test]
+#[should_panic(expected = "is only 3 bytes, too small for the 5-byte patch")]
+fn test_fake_function_smaller_than_patch_should_panic() {
+    let mut injector = InjectorPP::new();
+    unsafe {
+        injector
+            .when_called_unchecked(injectorpp::func_unchecked!(injectorpp_test_tiny_zero))
+            .will_execute_raw_unchecked(injectorpp::func_unchecked!(injectorpp_test_tiny_one));
+    }
+}
+
+#[test]
+#[should_panic(expected = "too small")]
+fn test_global_fake_function_smaller_than_patch_should_panic() {
+    let mut injector = InjectorPP::new_global();
+    unsafe {
+        injector
+            .when_called_unchecked(injectorpp::func_unchecked!(injectorpp_test_tiny_zero))
+            .will_execute_raw_unchecked(injectorpp::func_unchecked!(injectorpp_test_tiny_one));
+    }
+}
+
+#[test]
+fn test_refused_patch_should_leave_next_function_intact() {
+    let result = std::panic::catch_unwind(|| {
+        let mut injector = InjectorPP::new_global();
+        unsafe {
+            injector
+                .when_called_unchecked(injectorpp::func_unchecked!(injectorpp_test_tiny_zero))
+                .will_execute_raw_unchecked(injectorpp::func_unchecked!(injectorpp_test_tiny_one));
+        }
+    });
+
+    assert!(result.is_err());
+    unsafe {
+        assert_eq!(injectorpp_test_tiny_zero(), 0);
+        assert_eq!(injectorpp_test_tiny_one(), 1);
+    }
+}
+
+#[test]
+fn test_fake_function_large_enough_should_be_installed() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(fn (regular)(u32) -> u32))
+        .will_execute(injectorpp::fake!(
+            func_type: fn(_value: u32) -> u32,
+            returns: 0
+        ));
+
+    assert_eq!(regular(1), 0);
+}
+
+#[test]
+fn test_allow_undersized_patch_should_patch_small_function() {
+    let mut injector = InjectorPP::new();
+    unsafe {
+        injector
+            .when_called_unchecked(injectorpp::func_unchecked!(injectorpp_test_tiny_stub))
+            .allow_undersized_patch()
+            .will_execute_raw_unchecked(injectorpp::func_unchecked!(injectorpp_test_tiny_one));
+
+        assert_eq!(injectorpp_test_tiny_stub(), 1);
+    }
+}
+
+#[test]
+#[should_panic(expected = "branches back to offset 2, inside the 5-byte patch")]
+fn test_fake_function_branching_into_patch_should_panic() {
+    let mut injector = InjectorPP::new();
+    unsafe {
+        injector
+            .when_called_unchecked(injectorpp::func_unchecked!(injectorpp_test_loop_back))
+            .will_execute_raw_unchecked(injectorpp::func_unchecked!(injectorpp_test_tiny_one));
+    }
+}
@@ -1,5 +1,5 @@
 use injectorpp::interface::injector::*;
-use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use std::sync::Arc;
 use std::thread;
 
@@ -28,6 +28,147 @@ fn global_multiply(a: i32, b: i32) -> i32 {
     core::hint::black_box(core::hint::black_box(a) * core::hint::black_box(b))
 }
 
+#[inline(never)]
+fn global_subtract(a: i32, b: i32) -> i32 {
+    core::hint::black_box(core::hint::black_box(a) - core::hint::black_box(b))
+}
+
+#[inline(never)]
+fn global_negate(a: i32) -> i32 {
+    core::hint::black_box(-core::hint::black_box(a))
+}
+
+#[inline(never)]
+fn global_square(a: i32) -> i32 {
+    core::hint::black_box(core::hint::black_box(a) * core::hint::black_box(a))
+}
+
+#[inline(never)]
+fn global_cube(a: i32) -> i32 {
+    core::hint::black_box(core::hint::black_box(a) * core::hint::black_box(a) * a)
+}
+
+#[inline(never)]
+fn global_ordered_first() -> i32 {
+    core::hint::black_box(1)
+}
+
+#[inline(never)]
+fn global_ordered_second() -> i32 {
+    core::hint::black_box(2)
+}
+
+#[inline(never)]
+fn global_crossed_first() -> i32 {
+    core::hint::black_box(core::hint::black_box(3) + core::hint::black_box(1))
+}
+
+#[inline(never)]
+fn global_crossed_second() -> i32 {
+    core::hint::black_box(core::hint::black_box(3) + core::hint::black_box(2))
+}
+
+// The hammered functions are a single `mov eax, imm32; ret`, so a thread preempted inside
+// them is never in the middle of the bytes a patch overwrites.
+#[inline(never)]
+fn global_hammered() -> i32 {
+    2
+}
+
+#[inline(never)]
+fn global_hammered_through_stub() -> i32 {
+    3
+}
+
+// Saves registers over a 17-byte prologue, flags its entry in the first word at `rdi`, then
+// spins until the second word is set and returns 7. A thread spinning in it is past the bytes
+// any patch overwrites.
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+std::arch::global_asm!(
+    ".text",
+    ".p2align 4",
+    ".globl injectorpp_test_spin_past_prologue",
+    ".type injectorpp_test_spin_past_prologue, @function",
+    "injectorpp_test_spin_past_prologue:",
+    "push rbp",
+    "mov rbp, rsp",
+    "push rbx",
+    "push r12",
+    "push r13",
+    "push r14",
+    "push r15",
+    "sub rsp, 8",
+    "mov dword ptr [rdi], 1",
+    "2:",
+    "pause",
+    "mov eax, dword ptr [rdi + 4]",
+    "test eax, eax",
+    "jz 2b",
+    "add rsp, 8",
+    "pop r15",
+    "pop r14",
+    "pop r13",
+    "pop r12",
+    "pop rbx",
+    "pop rbp",
+    "mov eax, 7",
+    "ret",
+    ".size injectorpp_test_spin_past_prologue, . - injectorpp_test_spin_past_prologue",
+    ".globl injectorpp_test_spin_fake",
+    ".type injectorpp_test_spin_fake, @function",
+    "injectorpp_test_spin_fake:",
+    "mov eax, 100",
+    "ret",
+    ".size injectorpp_test_spin_fake, . - injectorpp_test_spin_fake",
+);
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+extern "C" {
+    fn injectorpp_test_spin_past_prologue(state: *const AtomicU32) -> u32;
+    fn injectorpp_test_spin_fake(state: *const AtomicU32) -> u32;
+}
+
+// Returns 5, starting at the last byte of an aligned 8-byte word, so the first two bytes of
+// any patch cross the word.
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+std::arch::global_asm!(
+    ".text",
+    ".p2align 6",
+    ".skip 7, 0xcc",
+    ".globl injectorpp_test_straddling_word",
+    ".type injectorpp_test_straddling_word, @function",
+    "injectorpp_test_straddling_word:",
+    "mov eax, 5",
+    "nop",
+    "nop",
+    "nop",
+    "nop",
+    "nop",
+    "nop",
+    "nop",
+    "nop",
+    "nop",
+    "nop",
+    "nop",
+    "nop",
+    "nop",
+    "nop",
+    "nop",
+    "nop",
+    "ret",
+    ".size injectorpp_test_straddling_word, . - injectorpp_test_straddling_word",
+);
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+extern "C" {
+    fn injectorpp_test_straddling_word() -> u32;
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+extern "C" fn straddling_word_fake() -> u32 {
+    100
+}
+
 // ---- Tests ----
 
 /// Verifies that a global fake using `will_execute` (fake! macro) is visible from a spawned thread.
@@ -188,7 +329,12 @@ fn test_global_fake_unchecked_cross_thread() {
 /// Verifies that `new()` (thread-local mode) still works correctly — fakes are NOT visible
 /// from spawned threads (default thread-local behavior).
 #[test]
-#[cfg(any(target_arch = "x86_64", target_arch = "aarch64", target_arch = "arm"))]
+#[cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec",
+    target_arch = "arm"
+))]
 fn test_thread_local_mode_not_visible_from_spawned_thread() {
     let mut injector = InjectorPP::new();
     injector
@@ -232,3 +378,392 @@ fn test_global_fake_call_count_across_threads() {
 
     // Verifier checks times:4 on drop — this test passes only if exactly 4 calls were made.
 }
+
+/// Verifies that global injectors faking different functions do not wait for each other.
+#[test]
+fn test_global_fakes_of_different_functions_should_coexist() {
+    let mut injector = InjectorPP::new_global();
+    injector
+        .when_called(injectorpp::func!(fn (global_subtract)(i32, i32) -> i32))
+        .will_execute(injectorpp::fake!(
+            func_type: fn(a: i32, b: i32) -> i32,
+            returns: 0
+        ));
+
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let handle = thread::spawn(move || {
+        let mut injector = InjectorPP::new_global();
+        injector
+            .when_called(injectorpp::func!(fn (global_negate)(i32) -> i32))
+            .will_execute(injectorpp::fake!(
+                func_type: fn(a: i32) -> i32,
+                returns: 7
+            ));
+
+        sender.send(global_negate(1)).unwrap();
+    });
+
+    let negated = receiver
+        .recv_timeout(std::time::Duration::from_secs(30))
+        .expect("a global fake of another function should not wait for this injector");
+
+    assert_eq!(negated, 7);
+    assert_eq!(global_subtract(5, 3), 0);
+    handle.join().unwrap();
+}
+
+/// Verifies that injectors locking the same functions in opposite orders take turns instead of
+/// deadlocking.
+#[test]
+fn test_lock_all_in_opposite_orders_should_not_deadlock() {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let barrier = Arc::new(std::sync::Barrier::new(2));
+    let handles: Vec<_> = [false, true]
+        .into_iter()
+        .map(|reversed| {
+            let sender = sender.clone();
+            let barrier = barrier.clone();
+            thread::spawn(move || {
+                let mut funcs = vec![
+                    injectorpp::func!(fn (global_ordered_first)() -> i32),
+                    injectorpp::func!(fn (global_ordered_second)() -> i32),
+                ];
+                if reversed {
+                    funcs.reverse();
+                }
+
+                barrier.wait();
+                let mut injector = InjectorPP::new_global();
+                injector.lock_all(&funcs);
+                for func in funcs {
+                    injector.when_called(func).will_execute(injectorpp::fake!(
+                        func_type: fn() -> i32,
+                        returns: 9
+                    ));
+                    thread::sleep(std::time::Duration::from_millis(20));
+                }
+
+                sender
+                    .send(global_ordered_first() + global_ordered_second())
+                    .unwrap();
+            })
+        })
+        .collect();
+
+    for _ in 0..2 {
+        let sum = receiver
+            .recv_timeout(std::time::Duration::from_secs(30))
+            .expect("injectors locking the same functions should not deadlock");
+        assert_eq!(sum, 18);
+    }
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    assert_eq!(global_ordered_first() + global_ordered_second(), 3);
+}
+
+/// Verifies that a thread-local and a global injector faking the same functions one by one in
+/// opposite orders do not deadlock: the one that would wait last panics and the other finishes.
+#[test]
+fn test_when_called_in_opposite_orders_should_not_deadlock() {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let barrier = Arc::new(std::sync::Barrier::new(2));
+    let handles: Vec<_> = [false, true]
+        .into_iter()
+        .map(|global| {
+            let sender = sender.clone();
+            let barrier = barrier.clone();
+            thread::spawn(move || {
+                let mut funcs = vec![
+                    injectorpp::func!(fn (global_crossed_first)() -> i32),
+                    injectorpp::func!(fn (global_crossed_second)() -> i32),
+                ];
+                let mut injector = if global {
+                    funcs.reverse();
+                    InjectorPP::new_global()
+                } else {
+                    InjectorPP::new()
+                };
+
+                let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                    for (index, func) in funcs.into_iter().enumerate() {
+                        injector.when_called(func).will_execute(injectorpp::fake!(
+                            func_type: fn() -> i32,
+                            returns: 0
+                        ));
+                        if index == 0 {
+                            barrier.wait();
+                        }
+                    }
+                }));
+                // Releases the functions locked before a panic.
+                drop(injector);
+                sender
+                    .send(result.map_err(|e| *e.downcast::<String>().unwrap()))
+                    .unwrap();
+            })
+        })
+        .collect();
+
+    let results: Vec<_> = (0..2)
+        .map(|_| {
+            receiver
+                .recv_timeout(std::time::Duration::from_secs(30))
+                .expect("injectors faking the same functions should not deadlock")
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    let errors: Vec<_> = results.into_iter().filter_map(Result::err).collect();
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].contains("waiting for it would deadlock"));
+    assert_eq!(global_crossed_first() + global_crossed_second(), 9);
+}
+
+/// Verifies that a global fake waits until a thread-local fake of the same function is dropped.
+#[test]
+fn test_global_fake_should_wait_for_thread_local_fake_of_same_function() {
+    static ACQUIRED: AtomicBool = AtomicBool::new(false);
+
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(fn (global_square)(i32) -> i32))
+        .will_execute(injectorpp::fake!(
+            func_type: fn(a: i32) -> i32,
+            returns: 1
+        ));
+
+    let handle = thread::spawn(|| {
+        let mut injector = InjectorPP::new_global();
+        injector
+            .when_called(injectorpp::func!(fn (global_square)(i32) -> i32))
+            .will_execute(injectorpp::fake!(
+                func_type: fn(a: i32) -> i32,
+                returns: 2
+            ));
+
+        ACQUIRED.store(true, Ordering::SeqCst);
+        global_square(3)
+    });
+
+    thread::sleep(std::time::Duration::from_millis(100));
+    assert!(!ACQUIRED.load(Ordering::SeqCst));
+    assert_eq!(global_square(3), 1);
+
+    drop(injector);
+
+    assert_eq!(handle.join().unwrap(), 2);
+    assert!(ACQUIRED.load(Ordering::SeqCst));
+    assert_eq!(global_square(3), 9);
+}
+
+/// Verifies that an injector from `try_new()` reports who holds a function instead of blocking forever.
+#[test]
+fn test_try_new_when_function_held_should_report_holder() {
+    let mut injector = InjectorPP::new_global();
+    injector
+        .when_called(injectorpp::func!(fn (global_cube)(i32) -> i32))
+        .will_execute(injectorpp::fake!(
+            func_type: fn(a: i32) -> i32,
+            returns: 0
+        ));
+
+    let result = thread::spawn(|| {
+        let mut injector = InjectorPP::try_new(std::time::Duration::from_millis(100)).unwrap();
+        injector
+            .when_called(injectorpp::func!(fn (global_cube)(i32) -> i32))
+            .will_execute(injectorpp::fake!(
+                func_type: fn(a: i32) -> i32,
+                returns: 1
+            ));
+    })
+    .join();
+
+    let message = *result.unwrap_err().downcast::<String>().unwrap();
+    assert!(message.contains("Timed out after 100ms waiting for the lock on"));
+    assert!(message
+        .contains("global fake on thread 'test_try_new_when_function_held_should_report_holder'"));
+    assert_eq!(global_cube(2), 0);
+}
+
+/// Verifies that `try_when_called()` returns the timeout instead of panicking.
+#[test]
+fn test_try_when_called_when_function_held_should_return_timeout() {
+    let mut injector = InjectorPP::new_global();
+    injector
+        .when_called(injectorpp::func!(fn (global_cube)(i32) -> i32))
+        .will_execute(injectorpp::fake!(
+            func_type: fn(a: i32) -> i32,
+            returns: 0
+        ));
+
+    let timeout = thread::spawn(|| {
+        let mut injector = InjectorPP::try_new(std::time::Duration::from_millis(100)).unwrap();
+        injector
+            .try_when_called(injectorpp::func!(fn (global_cube)(i32) -> i32))
+            .err()
+    })
+    .join()
+    .unwrap()
+    .expect("the lock should time out");
+
+    assert_eq!(timeout.timeout, std::time::Duration::from_millis(100));
+    assert_eq!(timeout.holders.len(), 1);
+    assert!(timeout.holders[0].contains(
+        "global fake on thread 'test_try_when_called_when_function_held_should_return_timeout'"
+    ));
+    assert_eq!(global_cube(2), 0);
+}
+
+/// Verifies that an injector from `try_new()` fakes functions nobody else holds.
+#[test]
+fn test_try_new_when_function_free_should_fake() {
+    let mut injector = InjectorPP::try_new(std::time::Duration::from_secs(30)).unwrap();
+    injector
+        .when_called(injectorpp::func!(fn (global_cube)(i32) -> i32))
+        .will_execute(injectorpp::fake!(
+            func_type: fn(a: i32) -> i32,
+            returns: 5
+        ));
+
+    assert_eq!(global_cube(2), 5);
+}
+
+/// Verifies that threads calling a function while it is repeatedly patched and restored
+/// only ever observe the original or the fake.
+#[test]
+fn test_global_fake_applied_while_other_threads_call_should_never_be_half_written() {
+    let stop = Arc::new(AtomicBool::new(false));
+    let mut handles = Vec::new();
+    for _ in 0..4 {
+        let stop = stop.clone();
+        handles.push(thread::spawn(move || {
+            while !stop.load(Ordering::Relaxed) {
+                let value = core::hint::black_box(global_hammered as fn() -> i32)();
+                assert!(value == 2 || value == 100, "unexpected value {value}");
+            }
+        }));
+    }
+
+    for _ in 0..200 {
+        let mut injector = InjectorPP::new_global();
+        injector
+            .when_called(injectorpp::func!(fn (global_hammered)() -> i32))
+            .will_execute(injectorpp::fake!(
+                func_type: fn() -> i32,
+                returns: 100
+            ));
+        thread::yield_now();
+    }
+
+    stop.store(true, Ordering::Relaxed);
+    for handle in handles {
+        handle.join().unwrap();
+    }
+}
+
+/// Verifies that dropping a global fake while other threads keep calling the function
+/// never frees the JIT stub under a thread still running it.
+#[test]
+fn test_global_fake_dropped_while_other_threads_call_should_not_crash() {
+    let stop = Arc::new(AtomicBool::new(false));
+    let mut handles = Vec::new();
+    for _ in 0..4 {
+        let stop = stop.clone();
+        handles.push(thread::spawn(move || {
+            while !stop.load(Ordering::Relaxed) {
+                let value = core::hint::black_box(global_hammered_through_stub as fn() -> i32)();
+                assert!(value == 3 || value == 4, "unexpected value {value}");
+            }
+        }));
+    }
+
+    for _ in 0..200 {
+        let mut injector = InjectorPP::new_global();
+        injector
+            .when_called(injectorpp::func!(fn (global_hammered_through_stub)() -> i32))
+            .will_execute(injectorpp::fake!(
+                func_type: fn() -> i32,
+                returns: 4
+            ));
+        thread::yield_now();
+    }
+
+    stop.store(true, Ordering::Relaxed);
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    assert_eq!(global_hammered_through_stub(), 3);
+}
+
+/// Verifies that a thread already running a function, spinning past its multi-instruction
+/// prologue when the prologue is overwritten, finishes the original call, while later calls
+/// reach the fake.
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+#[test]
+fn test_global_fake_applied_while_thread_spins_past_prologue_should_finish_original() {
+    static STATE: [AtomicU32; 2] = [AtomicU32::new(0), AtomicU32::new(0)];
+
+    let spinning = thread::spawn(|| unsafe { injectorpp_test_spin_past_prologue(STATE.as_ptr()) });
+    while STATE[0].load(Ordering::Acquire) == 0 {
+        thread::yield_now();
+    }
+
+    let mut injector = InjectorPP::new_global();
+    unsafe {
+        injector
+            .when_called_unchecked(injectorpp::func_unchecked!(
+                injectorpp_test_spin_past_prologue
+            ))
+            .will_execute_raw_unchecked(injectorpp::func_unchecked!(injectorpp_test_spin_fake));
+    }
+
+    let released = [AtomicU32::new(0), AtomicU32::new(1)];
+    assert_eq!(
+        unsafe { injectorpp_test_spin_past_prologue(released.as_ptr()) },
+        100
+    );
+
+    STATE[1].store(1, Ordering::Release);
+    assert_eq!(spinning.join().unwrap(), 7);
+}
+
+/// Verifies that threads calling a function whose patch crosses an aligned 8-byte word, while
+/// it is repeatedly patched and restored, only ever observe the original or the fake.
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+#[test]
+fn test_global_fake_of_function_crossing_word_should_never_be_half_written() {
+    let func = injectorpp_test_straddling_word as unsafe extern "C" fn() -> u32;
+    assert_eq!(func as usize % 8, 7);
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let mut handles = Vec::new();
+    for _ in 0..4 {
+        let stop = stop.clone();
+        handles.push(thread::spawn(move || {
+            while !stop.load(Ordering::Relaxed) {
+                let value = unsafe { core::hint::black_box(func)() };
+                assert!(value == 5 || value == 100, "unexpected value {value}");
+            }
+        }));
+    }
+
+    for _ in 0..200 {
+        let mut injector = InjectorPP::new_global();
+        unsafe {
+            injector
+                .when_called_unchecked(injectorpp::func_unchecked!(injectorpp_test_straddling_word))
+                .will_execute_raw_unchecked(injectorpp::func_unchecked!(straddling_word_fake));
+        }
+        assert_eq!(unsafe { func() }, 100);
+        thread::yield_now();
+    }
+
+    stop.store(true, Ordering::Relaxed);
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    assert_eq!(unsafe { func() }, 5);
+}
@@ -0,0 +1,87 @@
+#![cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64", target_arch = "arm")
+))]
+
+use injectorpp::interface::injector::*;
+
+extern "C" fn fake_id() -> u32 {
+    4242
+}
+
+#[test]
+fn test_got_backend_should_redirect_calls_of_libc_function() {
+    let real = unsafe { libc::getppid() };
+    {
+        let mut injector = InjectorPP::new_global();
+        unsafe {
+            injector
+                .when_called_unchecked(injectorpp::func_unchecked!(libc::getppid))
+                .strategy(PatchStrategy::Got)
+                .will_execute_raw_unchecked(injectorpp::func_unchecked!(fake_id));
+        }
+        injector.assert_patched();
+
+        assert_eq!(unsafe { libc::getppid() }, 4242);
+        let other_thread = std::thread::spawn(|| unsafe { libc::getppid() })
+            .join()
+            .unwrap();
+        assert_eq!(other_thread, 4242);
+        assert!(injectorpp::is_patched(unsafe {
+            injectorpp::func_unchecked!(libc::getppid)
+        }));
+    }
+
+    assert_eq!(unsafe { libc::getppid() }, real);
+    assert!(!injectorpp::is_patched(unsafe {
+        injectorpp::func_unchecked!(libc::getppid)
+    }));
+}
+
+#[test]
+fn test_got_backend_fake_should_pause_and_restore() {
+    let real = unsafe { libc::getuid() };
+    let mut injector = InjectorPP::new_global();
+    let handle = unsafe {
+        injector
+            .when_called_unchecked(injectorpp::func_unchecked!(libc::getuid))
+            .strategy(PatchStrategy::Got)
+            .will_execute_raw_unchecked(injectorpp::func_unchecked!(fake_id))
+    };
+    assert_eq!(unsafe { libc::getuid() }, 4242);
+
+    handle.pause();
+    assert_eq!(unsafe { libc::getuid() }, real);
+    handle.resume();
+    assert_eq!(unsafe { libc::getuid() }, 4242);
+
+    injector.restore(unsafe { injectorpp::func_unchecked!(libc::getuid) });
+    assert_eq!(unsafe { libc::getuid() }, real);
+}
+
+#[test]
+#[should_panic(expected = "requires an injector created with InjectorPP::new_global()")]
+fn test_got_backend_with_thread_local_injector_should_panic() {
+    let mut injector = InjectorPP::new();
+    unsafe {
+        injector
+            .when_called_unchecked(injectorpp::func_unchecked!(libc::getgid))
+            .strategy(PatchStrategy::Got)
+            .will_execute_raw_unchecked(injectorpp::func_unchecked!(fake_id));
+    }
+}
+
+#[inline(never)]
+fn local_function() -> u32 {
+    std::hint::black_box(1)
+}
+
+#[test]
+#[should_panic(expected = "so its callers do not call it through the GOT")]
+fn test_got_backend_for_function_not_in_got_should_panic() {
+    let mut injector = InjectorPP::new_global();
+    injector
+        .when_called(injectorpp::func!(fn (local_function)() -> u32))
+        .strategy(PatchStrategy::Got)
+        .will_execute(injectorpp::fake!(func_type: fn() -> u32, returns: 2));
+}
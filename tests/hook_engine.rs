@@ -0,0 +1,135 @@
+#![cfg(all(
+    feature = "external-engine",
+    target_os = "linux",
+    target_arch = "x86_64"
+))]
+
+use injectorpp::interface::injector::*;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// A minimal engine overwriting the start of the function with `jmp [rip+0]`.
+struct JumpEngine {
+    saved: Mutex<HashMap<u64, [u8; 14]>>,
+}
+
+static ENGINE_HOOKS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe fn write_code(addr: usize, code: &[u8]) {
+    let page_size = libc::sysconf(libc::_SC_PAGESIZE) as usize;
+    let page = addr & !(page_size - 1);
+    let len = addr + code.len() - page;
+    libc::mprotect(
+        page as *mut libc::c_void,
+        len,
+        libc::PROT_READ | libc::PROT_WRITE | libc::PROT_EXEC,
+    );
+    std::ptr::copy_nonoverlapping(code.as_ptr(), addr as *mut u8, code.len());
+    libc::mprotect(
+        page as *mut libc::c_void,
+        len,
+        libc::PROT_READ | libc::PROT_EXEC,
+    );
+}
+
+unsafe impl HookEngine for JumpEngine {
+    fn name(&self) -> &str {
+        "jump"
+    }
+
+    unsafe fn replace(&self, func: *const (), replacement: *const ()) -> Result<u64, String> {
+        let mut saved = self.saved.lock().unwrap();
+        if saved.contains_key(&(func as u64)) {
+            return Err("already hooked".to_string());
+        }
+
+        let mut original = [0u8; 14];
+        std::ptr::copy_nonoverlapping(func as *const u8, original.as_mut_ptr(), 14);
+        let mut jump = vec![0xFF, 0x25, 0, 0, 0, 0];
+        jump.extend_from_slice(&(replacement as u64).to_le_bytes());
+        write_code(func as usize, &jump);
+
+        saved.insert(func as u64, original);
+        ENGINE_HOOKS.fetch_add(1, Ordering::SeqCst);
+        Ok(func as u64)
+    }
+
+    unsafe fn revert(&self, hook: u64) {
+        let original = self.saved.lock().unwrap().remove(&hook).unwrap();
+        write_code(hook as usize, &original);
+    }
+}
+
+fn register_engine() {
+    static REGISTER: std::sync::Once = std::sync::Once::new();
+    REGISTER.call_once(|| {
+        InjectorPP::set_hook_engine(JumpEngine {
+            saved: Mutex::new(HashMap::new()),
+        });
+    });
+}
+
+#[inline(never)]
+fn checksum(data: &[u8]) -> u32 {
+    let mut sum = 0u32;
+    for &byte in std::hint::black_box(data) {
+        sum = sum.rotate_left(5) ^ byte as u32;
+    }
+    sum
+}
+
+#[test]
+fn test_external_strategy_should_fake_through_engine() {
+    register_engine();
+    assert!(PatchStrategy::External.is_supported());
+    let hooks = ENGINE_HOOKS.load(Ordering::SeqCst);
+
+    {
+        let mut injector = InjectorPP::new_global();
+        let handle = injector
+            .when_called(injectorpp::func!(fn (checksum)(&[u8]) -> u32))
+            .strategy(PatchStrategy::External)
+            .will_execute(injectorpp::fake!(
+                func_type: fn(_data: &[u8]) -> u32,
+                returns: 7,
+                times: 2
+            ));
+
+        assert_eq!(ENGINE_HOOKS.load(Ordering::SeqCst), hooks + 1);
+        assert_eq!(checksum(b"abc"), 7);
+        assert!(injectorpp::is_patched(injectorpp::func!(
+            fn (checksum)(&[u8]) -> u32
+        )));
+
+        handle.pause();
+        assert_ne!(checksum(b"abc"), 7);
+        handle.resume();
+        assert_eq!(checksum(b"abc"), 7);
+    }
+
+    assert_ne!(checksum(b"abc"), 7);
+}
+
+#[inline(never)]
+fn parity(data: &[u8]) -> bool {
+    std::hint::black_box(data)
+        .iter()
+        .fold(false, |parity, &byte| parity ^ (byte.count_ones() % 2 == 1))
+}
+
+#[test]
+#[should_panic(expected = "the jump hooking engine cannot hook")]
+fn test_external_strategy_should_panic_when_engine_refuses() {
+    register_engine();
+    let mut first = InjectorPP::new_global();
+    first
+        .when_called(injectorpp::func!(fn (parity)(&[u8]) -> bool))
+        .strategy(PatchStrategy::External)
+        .will_return_boolean(true);
+    let mut second = InjectorPP::new_global();
+    second
+        .when_called(injectorpp::func!(fn (parity)(&[u8]) -> bool))
+        .strategy(PatchStrategy::External)
+        .will_return_boolean(false);
+}
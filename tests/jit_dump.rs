@@ -0,0 +1,54 @@
+#![cfg(feature = "jit-dump")]
+
+use injectorpp::interface::injector::*;
+
+#[inline(never)]
+fn dumped_func() -> i32 {
+    std::hint::black_box(1)
+}
+
+#[inline(never)]
+fn dumped_global_func() -> i32 {
+    std::hint::black_box(2)
+}
+
+#[test]
+fn test_dump_patches_should_describe_thread_local_patch() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(fn (dumped_func)() -> i32))
+        .will_execute(injectorpp::fake!(
+            func_type: fn() -> i32,
+            returns: 3
+        ));
+
+    let dump = injector.dump_patches();
+    assert!(dump.contains(&format!(
+        "patch at {:#x}",
+        dumped_func as *const () as usize
+    )));
+    assert!(dump.contains("thread-local"));
+    assert!(dump.contains("original: "));
+    assert!(dump.contains("dispatcher at "));
+    assert!(dump.contains("trampoline at "));
+
+    #[cfg(target_arch = "x86_64")]
+    assert!(dump.contains("jmp 0x"));
+    #[cfg(any(target_arch = "aarch64", target_arch = "arm64ec"))]
+    assert!(dump.contains("br x"));
+}
+
+#[test]
+fn test_dump_patches_should_describe_global_patch() {
+    let mut injector = InjectorPP::new_global();
+    injector
+        .when_called(injectorpp::func!(fn (dumped_global_func)() -> i32))
+        .will_execute(injectorpp::fake!(
+            func_type: fn() -> i32,
+            returns: 4
+        ));
+
+    let dump = injector.dump_patches();
+    assert!(dump.contains("global"));
+    assert!(dump.contains("jit stub at "));
+}
@@ -0,0 +1,69 @@
+#![cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec",
+    target_arch = "arm"
+))]
+
+use injectorpp::interface::injector::*;
+
+#[inline(never)]
+fn resolve_host() -> i32 {
+    core::hint::black_box(core::hint::black_box(10) + core::hint::black_box(1))
+}
+
+#[inline(never)]
+fn is_offline() -> bool {
+    std::hint::black_box(false)
+}
+
+#[test]
+fn test_leaked_global_fake_should_stay_installed_and_release_lock() {
+    let mut injector = InjectorPP::new_global();
+    injector
+        .when_called(injectorpp::func!(fn (resolve_host)() -> i32))
+        .will_execute(injectorpp::fake!(
+            func_type: fn() -> i32,
+            returns: 127,
+            times: 100
+        ));
+    injector.leak();
+
+    assert_eq!(resolve_host(), 127);
+    assert!(InjectorPP::all_active_patches()
+        .iter()
+        .any(|patch| !patch.thread_local && patch.func_addr == resolve_host as *const () as usize));
+
+    // Another thread can fake the function globally on top of the leaked fake.
+    std::thread::spawn(|| {
+        let mut other = InjectorPP::new_global();
+        other
+            .when_called(injectorpp::func!(fn (resolve_host)() -> i32))
+            .will_execute(injectorpp::fake!(
+                func_type: fn() -> i32,
+                returns: 1
+            ));
+        assert_eq!(resolve_host(), 1);
+    })
+    .join()
+    .unwrap();
+
+    assert_eq!(resolve_host(), 127);
+}
+
+#[test]
+fn test_leaked_thread_local_fake_should_stay_on_installing_thread() {
+    std::thread::spawn(|| {
+        let mut injector = InjectorPP::new();
+        injector
+            .when_called(injectorpp::func!(fn (is_offline)() -> bool))
+            .will_return_boolean(true);
+        injector.leak();
+
+        assert!(is_offline());
+    })
+    .join()
+    .unwrap();
+
+    assert!(!is_offline());
+}
@@ -0,0 +1,114 @@
+#![cfg(target_os = "macos")]
+
+use injectorpp::interface::injector::*;
+
+// Two functions smaller than the patch laid out back to back, with no padding in between.
+#[cfg(target_arch = "x86_64")]
+std::arch::global_asm!(
+    ".text",
+    ".p2align 4",
+    ".globl _injectorpp_test_tiny_zero",
+    "_injectorpp_test_tiny_zero:",
+    "xor eax, eax",
+    "ret",
+    ".globl _injectorpp_test_tiny_one",
+    "_injectorpp_test_tiny_one:",
+    "mov al, 1",
+    "ret",
+);
+
+#[cfg(target_arch = "aarch64")]
+std::arch::global_asm!(
+    ".text",
+    ".p2align 4",
+    ".globl _injectorpp_test_tiny_zero",
+    "_injectorpp_test_tiny_zero:",
+    "ret",
+    ".globl _injectorpp_test_tiny_one",
+    "_injectorpp_test_tiny_one:",
+    "mov w0, #1",
+    "ret",
+);
+
+extern "C" {
+    fn injectorpp_test_tiny_zero() -> u8;
+    fn injectorpp_test_tiny_one() -> u8;
+}
+
+#[inline(never)]
+fn regular(value: u32) -> u32 {
+    std::hint::black_box(value) + 1
+}
+
+#[inline(never)]
+fn other(value: u32) -> u32 {
+    std::hint::black_box(value) + 2
+}
+
+#[test]
+#[should_panic(expected = "too small")]
+fn test_fake_function_smaller_than_patch_should_panic() {
+    let mut injector = InjectorPP::new();
+    unsafe {
+        injector
+            .when_called_unchecked(injectorpp::func_unchecked!(injectorpp_test_tiny_zero))
+            .will_execute_raw_unchecked(injectorpp::func_unchecked!(injectorpp_test_tiny_one));
+    }
+}
+
+#[test]
+fn test_refused_patch_should_leave_next_function_intact() {
+    let result = std::panic::catch_unwind(|| {
+        let mut injector = InjectorPP::new_global();
+        unsafe {
+            injector
+                .when_called_unchecked(injectorpp::func_unchecked!(injectorpp_test_tiny_zero))
+                .will_execute_raw_unchecked(injectorpp::func_unchecked!(injectorpp_test_tiny_one));
+        }
+    });
+
+    assert!(result.is_err());
+    unsafe {
+        assert_eq!(injectorpp_test_tiny_one(), 1);
+    }
+}
+
+#[test]
+fn test_fake_should_be_written_while_other_threads_run_jit_code() {
+    let mut injector = InjectorPP::new_global();
+    injector
+        .when_called(injectorpp::func!(fn (regular)(u32) -> u32))
+        .will_execute(injectorpp::fake!(
+            func_type: fn(_value: u32) -> u32,
+            returns: 0
+        ));
+
+    // Other threads keep executing the JIT code while this one writes more of it.
+    let running = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(true));
+    let callers: Vec<_> = (0..4)
+        .map(|_| {
+            let running = running.clone();
+            std::thread::spawn(move || {
+                while running.load(std::sync::atomic::Ordering::Relaxed) {
+                    assert_eq!(regular(1), 0);
+                }
+            })
+        })
+        .collect();
+
+    for _ in 0..16 {
+        let mut local = InjectorPP::new();
+        local
+            .when_called(injectorpp::func!(fn (other)(u32) -> u32))
+            .will_execute(injectorpp::fake!(
+                func_type: fn(_value: u32) -> u32,
+                returns: 7
+            ));
+        assert_eq!(other(1), 7);
+    }
+
+    running.store(false, std::sync::atomic::Ordering::Relaxed);
+    for caller in callers {
+        caller.join().unwrap();
+    }
+}
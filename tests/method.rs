@@ -0,0 +1,136 @@
+use injectorpp::interface::injector::*;
+
+trait HttpSend {
+    fn send(&self, body: &str) -> Result<u16, String>;
+}
+
+trait Codec<T> {
+    fn encode(&mut self, value: T) -> Vec<u8>;
+}
+
+struct Client {
+    host: String,
+}
+
+impl HttpSend for Client {
+    #[inline(never)]
+    fn send(&self, body: &str) -> Result<u16, String> {
+        Err(format!("cannot reach {} to send {}", self.host, body))
+    }
+}
+
+struct Json;
+
+impl Codec<u32> for Json {
+    #[inline(never)]
+    fn encode(&mut self, value: u32) -> Vec<u8> {
+        std::hint::black_box(value.to_string().into_bytes())
+    }
+}
+
+impl Codec<bool> for Json {
+    #[inline(never)]
+    fn encode(&mut self, value: bool) -> Vec<u8> {
+        std::hint::black_box(vec![value as u8])
+    }
+}
+
+fn client() -> Client {
+    Client {
+        host: "localhost".to_string(),
+    }
+}
+
+#[test]
+fn test_method_should_fake_trait_method() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::method!(<Client as HttpSend>::send))
+        .will_execute(injectorpp::fake!(
+            func_type: fn(_client: &Client, body: &str) -> Result<u16, String>,
+            when: body == "ping",
+            returns: Ok(200),
+            times: 1
+        ));
+
+    assert_eq!(client().send("ping"), Ok(200));
+}
+
+#[test]
+fn test_method_should_fake_generic_trait_method() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::method!(<Json as Codec<u32>>::encode))
+        .will_execute(injectorpp::fake!(
+            func_type: fn(_json: &mut Json, _value: u32) -> Vec<u8>,
+            returns: vec![0xff]
+        ));
+
+    assert_eq!(Codec::<u32>::encode(&mut Json, 42), vec![0xff]);
+    assert_eq!(Codec::<bool>::encode(&mut Json, true), vec![1]);
+}
+
+#[test]
+#[should_panic(expected = "Signature mismatch")]
+fn test_method_with_wrong_fake_signature_should_panic() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::method!(<Json as Codec<bool>>::encode))
+        .will_execute(injectorpp::fake!(
+            func_type: fn(_json: &mut Json, _value: u32) -> Vec<u8>,
+            returns: vec![]
+        ));
+}
+
+struct Counter {
+    value: i32,
+}
+
+impl Counter {
+    #[inline(never)]
+    fn add(&mut self, amount: i32) -> i32 {
+        self.value += amount;
+        std::hint::black_box(self.value)
+    }
+
+    #[inline(never)]
+    fn is_empty(&self) -> bool {
+        std::hint::black_box(self.value == 0)
+    }
+}
+
+#[inline(never)]
+fn parse_port(text: &str) -> Option<u16> {
+    std::hint::black_box(text.parse().ok())
+}
+
+#[test]
+fn test_func_with_inherent_method_path_should_derive_signature() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(Counter::add))
+        .will_execute(injectorpp::fake!(
+            func_type: fn(_counter: &mut Counter, amount: i32) -> i32,
+            returns: amount * 10
+        ));
+    injector
+        .when_called(injectorpp::func!(Counter::is_empty))
+        .will_return_boolean(true);
+
+    let mut counter = Counter { value: 1 };
+    assert_eq!(counter.add(2), 20);
+    assert!(counter.is_empty());
+}
+
+#[test]
+fn test_func_with_function_path_should_derive_signature() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(self::parse_port))
+        .will_execute(injectorpp::fake!(
+            func_type: fn(_text: &str) -> Option<u16>,
+            returns: Some(8080)
+        ));
+
+    assert_eq!(parse_port("not a port"), Some(8080));
+}
@@ -0,0 +1,120 @@
+#![cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec",
+    target_arch = "arm"
+))]
+
+use injectorpp::interface::injector::*;
+use std::path::Path;
+
+#[injectorpp::mockable]
+mod fs {
+    use std::path::Path;
+
+    #[inline(never)]
+    pub fn create_dir_all(path: &Path) -> std::io::Result<()> {
+        std::hint::black_box(std::fs::create_dir_all(path))
+    }
+
+    #[inline(never)]
+    pub fn touch(path: &Path) {
+        std::hint::black_box(path);
+    }
+
+    pub struct Store {
+        pub items: u32,
+    }
+
+    impl Store {
+        #[inline(never)]
+        pub fn count(&self) -> u32 {
+            std::hint::black_box(self.items)
+        }
+
+        #[inline(never)]
+        pub fn open(name: &str) -> Self {
+            Store {
+                items: std::hint::black_box(name.len() as u32),
+            }
+        }
+    }
+}
+
+#[injectorpp::mockable]
+#[inline(never)]
+fn retry_limit(service: &str, attempt: u32) -> u32 {
+    std::hint::black_box(service.len() as u32 + attempt)
+}
+
+struct Cache;
+
+#[injectorpp::mockable]
+impl Cache {
+    #[inline(never)]
+    fn lookup(&mut self, key: &str) -> Option<String> {
+        std::hint::black_box(Some(key.to_uppercase()))
+    }
+}
+
+#[test]
+fn test_mockable_helpers_should_fake_module_functions() {
+    let mut injector = InjectorPP::new();
+    mock_fs_create_dir_all(&mut injector)
+        .returns_ok(())
+        .times(1);
+    mock_fs_store_count(&mut injector).returns(42);
+    mock_fs_store_open(&mut injector).returns_with(|| fs::Store { items: 7 });
+
+    assert!(fs::create_dir_all(Path::new("/proc/forbidden/dir")).is_ok());
+
+    mock_fs_create_dir_all(&mut injector).returns_err(std::io::ErrorKind::PermissionDenied);
+    assert_eq!(
+        fs::create_dir_all(Path::new("/tmp")).unwrap_err().kind(),
+        std::io::ErrorKind::PermissionDenied
+    );
+    assert_eq!(fs::Store { items: 1 }.count(), 42);
+    assert_eq!(fs::Store::open("any").items, 7);
+}
+
+#[test]
+fn test_mockable_helper_for_unit_function_should_need_no_return_value() {
+    let mut injector = InjectorPP::new();
+    mock_fs_touch(&mut injector).times(2);
+
+    fs::touch(Path::new("a"));
+    fs::touch(Path::new("b"));
+}
+
+#[test]
+fn test_mockable_helpers_should_fake_free_functions_and_methods() {
+    let mut injector = InjectorPP::new();
+    mock_retry_limit(&mut injector).returns(0);
+    mock_cache_lookup(&mut injector).returns(None);
+
+    assert_eq!(retry_limit("billing", 3), 0);
+    assert_eq!(Cache.lookup("key"), None);
+
+    mock_retry_limit(&mut injector).returns(9);
+    assert_eq!(retry_limit("billing", 3), 9);
+
+    drop(injector);
+    assert_eq!(retry_limit("billing", 3), 10);
+    assert_eq!(Cache.lookup("key"), Some("KEY".to_string()));
+}
+
+#[test]
+#[should_panic(expected = "expected to be called 2 time(s), but it is actually called 1 time(s)")]
+fn test_mockable_times_should_verify_call_count() {
+    let mut injector = InjectorPP::new();
+    mock_retry_limit(&mut injector).returns(1).times(2);
+
+    retry_limit("billing", 0);
+}
+
+#[test]
+#[should_panic(expected = "mock_fs_store_count needs returns() or returns_with()")]
+fn test_mockable_without_return_value_should_panic() {
+    let mut injector = InjectorPP::new();
+    mock_fs_store_count(&mut injector);
+}
@@ -0,0 +1,103 @@
+use injectorpp::interface::injector::*;
+
+#[inline(never)]
+fn mount(path: &str) -> bool {
+    std::hint::black_box(path).is_empty()
+}
+
+#[inline(never)]
+fn unmount(path: &str) -> bool {
+    std::hint::black_box(path).is_empty()
+}
+
+#[inline(never)]
+fn mount_count() -> i32 {
+    std::hint::black_box(0)
+}
+
+fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap()
+}
+
+#[test]
+fn test_new_named_should_expose_name() {
+    let injector = InjectorPP::new_named("mount_happy");
+    assert_eq!(injector.name(), Some("mount_happy"));
+
+    let injector = InjectorPP::new();
+    assert_eq!(injector.name(), None);
+}
+
+#[test]
+fn test_named_injector_fake_unexpected_arguments_should_include_name() {
+    let mut injector = InjectorPP::new_named("mount_happy");
+    injector
+        .when_called(injectorpp::func!(fn (mount)(&str) -> bool))
+        .will_execute(injectorpp::fake!(
+            func_type: fn(path: &str) -> bool,
+            when: path == "/mnt",
+            returns: true
+        ));
+
+    assert!(mount("/mnt"));
+
+    let result = std::panic::catch_unwind(|| mount("/tmp"));
+    let message = panic_message(result.unwrap_err());
+
+    assert!(message.contains("called with unexpected arguments"));
+    assert!(message.contains("(injector \"mount_happy\")"));
+}
+
+#[test]
+fn test_named_injector_fake_over_called_should_include_name() {
+    let mut injector = InjectorPP::new_named("unmount_twice");
+    injector
+        .when_called(injectorpp::func!(fn (unmount)(&str) -> bool))
+        .will_execute(injectorpp::fake!(
+            func_type: fn(_path: &str) -> bool,
+            returns: true,
+            times: 1
+        ));
+
+    assert!(unmount("/mnt"));
+
+    let result = std::panic::catch_unwind(|| unmount("/mnt"));
+    let message = panic_message(result.unwrap_err());
+
+    assert!(message.ends_with("called more times than expected"));
+    assert!(message.contains("(injector \"unmount_twice\")"));
+
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| drop(injector)));
+    let message = panic_message(result.unwrap_err());
+
+    assert!(message.starts_with("Fake function was expected to be called 1 time(s)"));
+    assert!(message.ends_with("(injector \"unmount_twice\")"));
+}
+
+#[test]
+#[should_panic(
+    expected = "Fake function was expected to be called 1 time(s), but it is actually called 0 time(s) (injector \"mount_happy\")"
+)]
+fn test_named_injector_verifier_should_include_name() {
+    let mut injector = InjectorPP::new_named("mount_happy");
+    injector
+        .when_called(injectorpp::func!(fn (mount_count)() -> i32))
+        .will_execute(injectorpp::fake!(
+            func_type: fn() -> i32,
+            returns: 1,
+            times: 1
+        ));
+}
+
+#[test]
+#[should_panic(expected = "(injector \"mount_happy\")")]
+fn test_named_injector_signature_mismatch_should_include_name() {
+    let mut injector = InjectorPP::new_named("mount_happy");
+    injector
+        .when_called(injectorpp::func!(fn (mount_count)() -> i32))
+        .will_return_boolean(true);
+}
@@ -0,0 +1,109 @@
+#![cfg(target_os = "macos")]
+
+use std::ffi::{c_char, c_void};
+use std::sync::OnceLock;
+
+use injectorpp::interface::injector::*;
+
+#[link(name = "objc")]
+extern "C" {
+    fn objc_getClass(name: *const c_char) -> *mut c_void;
+    fn objc_allocateClassPair(
+        superclass: *mut c_void,
+        name: *const c_char,
+        extra_bytes: usize,
+    ) -> *mut c_void;
+    fn objc_registerClassPair(class: *mut c_void);
+    fn object_getClass(object: *mut c_void) -> *mut c_void;
+    fn class_addMethod(
+        class: *mut c_void,
+        selector: *const c_void,
+        imp: *const c_void,
+        types: *const c_char,
+    ) -> u8;
+    fn sel_registerName(name: *const c_char) -> *const c_void;
+    fn objc_msgSend();
+}
+
+type Value = unsafe extern "C" fn(*mut c_void, *const c_void) -> i32;
+
+unsafe extern "C" fn value(_this: *mut c_void, _cmd: *const c_void) -> i32 {
+    7
+}
+
+/// Registers `InjectorppCounter`, with the class method `+value`, and its subclass
+/// `InjectorppSubCounter`, inheriting it. Returns both classes.
+fn counter_classes() -> (usize, usize) {
+    static CLASSES: OnceLock<(usize, usize)> = OnceLock::new();
+    *CLASSES.get_or_init(|| unsafe {
+        let counter = objc_allocateClassPair(
+            objc_getClass(c"NSObject".as_ptr()),
+            c"InjectorppCounter".as_ptr(),
+            0,
+        );
+        class_addMethod(
+            object_getClass(counter),
+            sel_registerName(c"value".as_ptr()),
+            value as *const c_void,
+            c"i@:".as_ptr(),
+        );
+        objc_registerClassPair(counter);
+
+        let sub_counter = objc_allocateClassPair(counter, c"InjectorppSubCounter".as_ptr(), 0);
+        objc_registerClassPair(sub_counter);
+        (counter as usize, sub_counter as usize)
+    })
+}
+
+/// Sends `value` to `class`, as Objective-C code would.
+fn send_value(class: usize) -> i32 {
+    let send: Value = unsafe { std::mem::transmute(objc_msgSend as unsafe extern "C" fn()) };
+    unsafe { send(class as *mut c_void, sel_registerName(c"value".as_ptr())) }
+}
+
+#[test]
+fn test_when_called_objc_should_fake_class_method_until_dropped() {
+    let (counter, _) = counter_classes();
+    assert_eq!(send_value(counter), 7);
+
+    {
+        let mut injector = InjectorPP::new_global();
+        injector
+            .when_called_objc::<Value>("+[InjectorppCounter value]")
+            .will_execute(injectorpp::fake!(
+                func_type: unsafe extern "C" fn(_this: *mut c_void, _cmd: *const c_void) -> i32,
+                returns: 42
+            ));
+
+        assert_eq!(send_value(counter), 42);
+    }
+
+    assert_eq!(send_value(counter), 7);
+}
+
+#[test]
+fn test_when_called_objc_should_fake_inherited_method_of_subclass_only() {
+    let (counter, sub_counter) = counter_classes();
+
+    {
+        let mut injector = InjectorPP::new_global();
+        injector
+            .when_called_objc::<Value>("+[InjectorppSubCounter value]")
+            .will_execute(injectorpp::fake!(
+                func_type: unsafe extern "C" fn(_this: *mut c_void, _cmd: *const c_void) -> i32,
+                returns: 0
+            ));
+
+        assert_eq!(send_value(sub_counter), 0);
+        assert_eq!(send_value(counter), 7);
+    }
+
+    assert_eq!(send_value(sub_counter), 7);
+}
+
+#[test]
+#[should_panic(expected = "no loaded class has the method")]
+fn test_when_called_objc_with_unknown_method_should_panic() {
+    let mut injector = InjectorPP::new_global();
+    injector.when_called_objc::<Value>("+[InjectorppCounter count]");
+}
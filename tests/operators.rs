@@ -0,0 +1,85 @@
+use injectorpp::interface::injector::*;
+use std::fmt;
+use std::ops::{Add, AddAssign, Neg};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Money(i64);
+
+impl fmt::Display for Money {
+    #[inline(never)]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "${}", self.0)
+    }
+}
+
+impl Add for Money {
+    type Output = Money;
+
+    #[inline(never)]
+    fn add(self, other: Money) -> Money {
+        Money(std::hint::black_box(self.0 + other.0))
+    }
+}
+
+impl AddAssign<i64> for Money {
+    #[inline(never)]
+    fn add_assign(&mut self, amount: i64) {
+        self.0 = std::hint::black_box(self.0 + amount);
+    }
+}
+
+impl Neg for Money {
+    type Output = Money;
+
+    #[inline(never)]
+    fn neg(self) -> Money {
+        Money(std::hint::black_box(-self.0))
+    }
+}
+
+#[test]
+fn test_fake_display_impl_should_write_to_formatter() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::method!(<Money as fmt::Display>::fmt))
+        .will_execute(injectorpp::fake!(
+            func_type: fn(self: &Money, f: &mut fmt::Formatter) -> fmt::Result,
+            when: self.0 < 0,
+            returns: f.write_str("overdrawn")
+        ));
+
+    assert_eq!(Money(-5).to_string(), "overdrawn");
+    assert_eq!(format!("{:?}", Money(-5)), "Money(-5)");
+}
+
+#[test]
+fn test_fake_operator_impls_should_use_associated_output_type() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(
+            fn (<Money as Add>::add)(Money, Money) -> <Money as Add>::Output
+        ))
+        .will_execute(injectorpp::fake!(
+            func_type: fn(a: Money, b: Money) -> <Money as Add>::Output,
+            returns: Money(a.0 * b.0)
+        ));
+    injector
+        .when_called(injectorpp::method!(<Money as Neg>::neg))
+        .will_execute(injectorpp::fake!(
+            func_type: fn(self: Money) -> Money,
+            returns: self
+        ));
+    injector
+        .when_called(injectorpp::method!(<Money as AddAssign<i64>>::add_assign))
+        .will_execute(injectorpp::fake!(
+            func_type: fn(self: &mut Money, amount: i64) -> (),
+            assign: { self.0 -= amount }
+        ));
+
+    assert_eq!(Money(3) + Money(4), Money(12));
+    assert_eq!(-Money(3), Money(3));
+
+    let mut balance = Money(10);
+    balance += 4;
+    assert_eq!(balance, Money(6));
+}
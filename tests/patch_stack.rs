@@ -0,0 +1,230 @@
+#![cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec",
+    target_arch = "arm"
+))]
+
+use injectorpp::interface::injector::*;
+
+#[inline(never)]
+fn stacked_value() -> i32 {
+    core::hint::black_box(core::hint::black_box(40) + core::hint::black_box(2))
+}
+
+#[inline(never)]
+fn stacked_global_value() -> i32 {
+    core::hint::black_box(core::hint::black_box(50) + core::hint::black_box(2))
+}
+
+#[inline(never)]
+fn stacked_paused_value() -> i32 {
+    core::hint::black_box(core::hint::black_box(70) + core::hint::black_box(2))
+}
+
+#[inline(never)]
+fn stacked_mixed_value() -> i32 {
+    core::hint::black_box(core::hint::black_box(60) + core::hint::black_box(2))
+}
+
+#[test]
+fn test_thread_local_fakes_of_same_function_should_unwind_in_lifo_order() {
+    let mut outer = InjectorPP::new();
+    outer
+        .when_called(injectorpp::func!(fn (stacked_value)() -> i32))
+        .will_execute(injectorpp::fake!(
+            func_type: fn() -> i32,
+            returns: 1
+        ));
+
+    {
+        let mut inner = InjectorPP::new();
+        inner
+            .when_called(injectorpp::func!(fn (stacked_value)() -> i32))
+            .will_execute(injectorpp::fake!(
+                func_type: fn() -> i32,
+                returns: 2
+            ));
+        assert_eq!(stacked_value(), 2);
+    }
+
+    assert_eq!(stacked_value(), 1);
+    drop(outer);
+    assert_eq!(stacked_value(), 42);
+}
+
+#[test]
+fn test_thread_local_fakes_dropped_out_of_order_should_keep_latest() {
+    let mut first = InjectorPP::new();
+    first
+        .when_called(injectorpp::func!(fn (stacked_value)() -> i32))
+        .will_execute(injectorpp::fake!(
+            func_type: fn() -> i32,
+            returns: 1
+        ));
+    let mut second = InjectorPP::new();
+    second
+        .when_called(injectorpp::func!(fn (stacked_value)() -> i32))
+        .will_execute(injectorpp::fake!(
+            func_type: fn() -> i32,
+            returns: 2
+        ));
+
+    drop(first);
+    assert_eq!(stacked_value(), 2);
+    drop(second);
+    assert_eq!(stacked_value(), 42);
+}
+
+#[test]
+fn test_global_fakes_of_same_function_should_restore_original_in_any_order() {
+    let mut first = InjectorPP::new_global();
+    first
+        .when_called(injectorpp::func!(fn (stacked_global_value)() -> i32))
+        .will_execute(injectorpp::fake!(
+            func_type: fn() -> i32,
+            returns: 1
+        ));
+    let mut second = InjectorPP::new_global();
+    second
+        .when_called(injectorpp::func!(fn (stacked_global_value)() -> i32))
+        .will_execute(injectorpp::fake!(
+            func_type: fn() -> i32,
+            returns: 2
+        ));
+    assert_eq!(stacked_global_value(), 2);
+
+    drop(first);
+    assert_eq!(stacked_global_value(), 2);
+    drop(second);
+    assert_eq!(stacked_global_value(), 52);
+
+    let mut injector = InjectorPP::new_global();
+    injector
+        .when_called(injectorpp::func!(fn (stacked_global_value)() -> i32))
+        .will_execute(injectorpp::fake!(
+            func_type: fn() -> i32,
+            returns: 1
+        ));
+    injector
+        .when_called(injectorpp::func!(fn (stacked_global_value)() -> i32))
+        .will_execute(injectorpp::fake!(
+            func_type: fn() -> i32,
+            returns: 3
+        ));
+    assert_eq!(stacked_global_value(), 3);
+    drop(injector);
+    assert_eq!(stacked_global_value(), 52);
+}
+
+#[test]
+fn test_global_fake_below_paused_fake_dropped_should_hand_over_restore_bytes() {
+    let mut bottom = InjectorPP::new_global();
+    bottom
+        .when_called(injectorpp::func!(fn (stacked_paused_value)() -> i32))
+        .will_execute(injectorpp::fake!(
+            func_type: fn() -> i32,
+            returns: 1
+        ));
+    let mut top = InjectorPP::new_global();
+    let handle = top
+        .when_called(injectorpp::func!(fn (stacked_paused_value)() -> i32))
+        .will_execute(injectorpp::fake!(
+            func_type: fn() -> i32,
+            returns: 2
+        ));
+
+    handle.pause();
+    assert_eq!(stacked_paused_value(), 1);
+    drop(bottom);
+    assert_eq!(stacked_paused_value(), 72);
+
+    handle.resume();
+    assert_eq!(stacked_paused_value(), 2);
+    drop(top);
+    assert_eq!(stacked_paused_value(), 72);
+}
+
+#[test]
+#[should_panic(expected = "Thread-local and global fakes of the same function cannot be stacked")]
+fn test_global_fake_over_thread_local_fake_on_same_thread_should_panic() {
+    let mut thread_local = InjectorPP::new();
+    thread_local
+        .when_called(injectorpp::func!(fn (stacked_mixed_value)() -> i32))
+        .will_execute(injectorpp::fake!(
+            func_type: fn() -> i32,
+            returns: 1
+        ));
+
+    let mut global = InjectorPP::new_global();
+    global
+        .when_called(injectorpp::func!(fn (stacked_mixed_value)() -> i32))
+        .will_execute(injectorpp::fake!(
+            func_type: fn() -> i32,
+            returns: 2
+        ));
+}
+
+#[inline(never)]
+fn teardown_open_value() -> i32 {
+    core::hint::black_box(core::hint::black_box(80) + core::hint::black_box(2))
+}
+
+#[inline(never)]
+fn teardown_read_value() -> i32 {
+    core::hint::black_box(core::hint::black_box(90) + core::hint::black_box(2))
+}
+
+#[inline(never)]
+fn teardown_close_value() -> i32 {
+    core::hint::black_box(core::hint::black_box(100) + core::hint::black_box(2))
+}
+
+#[test]
+fn test_global_injector_with_several_fakes_dropped_should_restore_every_function() {
+    let mut injector = InjectorPP::new_global();
+    injector
+        .when_called(injectorpp::func!(fn (teardown_open_value)() -> i32))
+        .will_execute(injectorpp::fake!(
+            func_type: fn() -> i32,
+            returns: 1
+        ));
+    injector
+        .when_called(injectorpp::func!(fn (teardown_read_value)() -> i32))
+        .will_execute(injectorpp::fake!(
+            func_type: fn() -> i32,
+            returns: 2
+        ));
+    injector
+        .when_called(injectorpp::func!(fn (teardown_close_value)() -> i32))
+        .will_execute(injectorpp::fake!(
+            func_type: fn() -> i32,
+            returns: 3
+        ));
+    injector
+        .when_called(injectorpp::func!(fn (teardown_close_value)() -> i32))
+        .will_execute(injectorpp::fake!(
+            func_type: fn() -> i32,
+            returns: 4
+        ));
+    let mut above = InjectorPP::new_global();
+    above
+        .when_called(injectorpp::func!(fn (teardown_open_value)() -> i32))
+        .will_execute(injectorpp::fake!(
+            func_type: fn() -> i32,
+            returns: 5
+        ));
+    assert_eq!(teardown_open_value(), 5);
+    assert_eq!(teardown_read_value(), 2);
+    assert_eq!(teardown_close_value(), 4);
+
+    drop(injector);
+    assert_eq!(teardown_open_value(), 5);
+    assert_eq!(teardown_read_value(), 92);
+    assert_eq!(teardown_close_value(), 102);
+
+    drop(above);
+    assert_eq!(teardown_open_value(), 82);
+    assert_eq!(teardown_read_value(), 92);
+    assert_eq!(teardown_close_value(), 102);
+}
@@ -0,0 +1,73 @@
+use injectorpp::interface::injector::*;
+
+#[test]
+fn test_supported_strategies_should_match_platform() {
+    let supported = PatchStrategy::supported();
+    assert_eq!(supported.first(), Some(&PatchStrategy::InlineBranch));
+    assert_eq!(PatchStrategy::default(), PatchStrategy::InlineBranch);
+
+    for strategy in [PatchStrategy::Got, PatchStrategy::CallSites] {
+        assert_eq!(strategy.is_supported(), supported.contains(&strategy));
+    }
+    assert_eq!(PatchStrategy::Got.is_supported(), cfg!(target_os = "linux"));
+    assert_eq!(
+        PatchStrategy::CallSites.is_supported(),
+        cfg!(all(
+            target_os = "linux",
+            any(target_arch = "x86_64", target_arch = "aarch64")
+        ))
+    );
+}
+
+#[cfg(target_os = "linux")]
+#[inline(never)]
+fn retry_budget() -> u32 {
+    std::hint::black_box(3)
+}
+
+#[cfg(target_os = "linux")]
+extern "C" fn fake_id() -> u32 {
+    4242
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_default_strategy_should_apply_to_when_called() {
+    let real = unsafe { libc::getgid() };
+    {
+        let mut injector = InjectorPP::new_global();
+        injector.set_default_strategy(PatchStrategy::Got);
+        unsafe {
+            injector
+                .when_called_unchecked(injectorpp::func_unchecked!(libc::getgid))
+                .will_execute_raw_unchecked(injectorpp::func_unchecked!(fake_id));
+        }
+        // A function of the program is not called through the GOT, so it overrides the default.
+        injector
+            .when_called(injectorpp::func!(fn (retry_budget)() -> u32))
+            .strategy(PatchStrategy::InlineBranch)
+            .will_execute(injectorpp::fake!(func_type: fn() -> u32, returns: 0));
+
+        assert_eq!(unsafe { libc::getgid() }, 4242);
+        assert_eq!(retry_budget(), 0);
+    }
+
+    assert_eq!(unsafe { libc::getgid() }, real);
+    assert_eq!(retry_budget(), 3);
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+#[should_panic(expected = "requires an injector created with InjectorPP::new_global()")]
+fn test_default_strategy_redirecting_calls_should_panic_for_thread_local_injector() {
+    let mut injector = InjectorPP::new();
+    injector.set_default_strategy(PatchStrategy::Got);
+}
+
+#[test]
+#[cfg(not(target_os = "linux"))]
+#[should_panic(expected = "PatchStrategy::Got is not supported on this platform")]
+fn test_unsupported_strategy_should_panic() {
+    let mut injector = InjectorPP::new_global();
+    injector.set_default_strategy(PatchStrategy::Got);
+}
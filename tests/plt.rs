@@ -0,0 +1,100 @@
+#![cfg(all(target_os = "linux", target_arch = "x86_64"))]
+
+use injectorpp::interface::injector::*;
+
+// Functions whose implementation is selected at load time, as glibc does for `memcpy` and
+// `getenv`: `injectorpp_test_ifunc_*` are IFUNC symbols, whose addresses are the resolvers
+// `injectorpp_test_select_*`.
+std::arch::global_asm!(
+    ".text",
+    ".p2align 4",
+    ".type injectorpp_test_selected_one, @function",
+    "injectorpp_test_selected_one:",
+    "mov eax, 1",
+    "add eax, 0",
+    "ret",
+    ".size injectorpp_test_selected_one, . - injectorpp_test_selected_one",
+    ".p2align 4",
+    ".type injectorpp_test_selected_two, @function",
+    "injectorpp_test_selected_two:",
+    "mov eax, 2",
+    "add eax, 0",
+    "ret",
+    ".size injectorpp_test_selected_two, . - injectorpp_test_selected_two",
+    ".p2align 4",
+    ".globl injectorpp_test_select_one",
+    ".type injectorpp_test_select_one, @function",
+    "injectorpp_test_select_one:",
+    "lea rax, [rip + injectorpp_test_selected_one]",
+    "ret",
+    ".size injectorpp_test_select_one, . - injectorpp_test_select_one",
+    ".p2align 4",
+    ".globl injectorpp_test_select_two",
+    ".type injectorpp_test_select_two, @function",
+    "injectorpp_test_select_two:",
+    "lea rax, [rip + injectorpp_test_selected_two]",
+    "ret",
+    ".size injectorpp_test_select_two, . - injectorpp_test_select_two",
+    ".globl injectorpp_test_ifunc_one",
+    ".type injectorpp_test_ifunc_one, @gnu_indirect_function",
+    ".set injectorpp_test_ifunc_one, injectorpp_test_select_one",
+    ".globl injectorpp_test_ifunc_two",
+    ".type injectorpp_test_ifunc_two, @gnu_indirect_function",
+    ".set injectorpp_test_ifunc_two, injectorpp_test_select_two",
+);
+
+extern "C" {
+    fn injectorpp_test_select_one() -> usize;
+    fn injectorpp_test_ifunc_one() -> u32;
+    fn injectorpp_test_ifunc_two() -> u32;
+}
+
+extern "C" fn fake_seven() -> u32 {
+    7
+}
+
+#[test]
+fn test_fake_of_ifunc_resolver_should_fake_selected_implementation() {
+    let mut injector = InjectorPP::new();
+    unsafe {
+        injector
+            .when_called_unchecked(injectorpp::func_unchecked!(injectorpp_test_select_one))
+            .will_execute_raw_unchecked(injectorpp::func_unchecked!(fake_seven));
+
+        assert_eq!(injectorpp_test_ifunc_one(), 7);
+    }
+}
+
+#[test]
+fn test_fake_of_ifunc_should_fake_selected_implementation() {
+    let mut injector = InjectorPP::new();
+    unsafe {
+        injector
+            .when_called_unchecked(injectorpp::func_unchecked!(injectorpp_test_ifunc_two))
+            .will_execute_raw_unchecked(injectorpp::func_unchecked!(fake_seven));
+
+        assert_eq!(injectorpp_test_ifunc_two(), 7);
+    }
+
+    let original = std::thread::spawn(|| unsafe { injectorpp_test_ifunc_two() })
+        .join()
+        .unwrap();
+    assert_eq!(original, 2);
+}
+
+#[test]
+fn test_fake_of_libc_ifunc_should_fake_calls() {
+    let mut injector = InjectorPP::new();
+    unsafe {
+        injector
+            .when_called_unchecked(injectorpp::func_unchecked!(libc::strlen))
+            .will_execute_raw_unchecked(injectorpp::func_unchecked!(fake_strlen));
+
+        let text = std::hint::black_box(c"hello".as_ptr());
+        assert_eq!(libc::strlen(text), 42);
+    }
+}
+
+extern "C" fn fake_strlen(_s: *const libc::c_char) -> libc::size_t {
+    42
+}
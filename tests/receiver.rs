@@ -0,0 +1,103 @@
+use injectorpp::interface::injector::*;
+use std::pin::Pin;
+use std::sync::Arc;
+
+struct RequestBuilder {
+    url: String,
+    retries: u32,
+}
+
+impl RequestBuilder {
+    #[inline(never)]
+    fn build(self) -> String {
+        std::hint::black_box(format!("GET {} ({} retries)", self.url, self.retries))
+    }
+
+    #[inline(never)]
+    #[allow(clippy::boxed_local)]
+    fn into_url(self: Box<Self>) -> String {
+        std::hint::black_box(self.url)
+    }
+
+    #[inline(never)]
+    fn retries(self: Arc<Self>, extra: u32) -> u32 {
+        std::hint::black_box(self.retries + extra)
+    }
+
+    #[inline(never)]
+    fn reset(self: Pin<&mut Self>) {
+        self.get_mut().retries = std::hint::black_box(0);
+    }
+}
+
+fn builder(url: &str) -> RequestBuilder {
+    RequestBuilder {
+        url: url.to_string(),
+        retries: 3,
+    }
+}
+
+#[test]
+fn test_fake_with_self_by_value_should_match_on_receiver() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(RequestBuilder::build))
+        .will_execute(injectorpp::fake!(
+            func_type: fn(self: RequestBuilder) -> String,
+            when: self.url == "https://example.com",
+            returns: format!("fake {}", self.retries),
+            times: 1
+        ));
+
+    assert_eq!(builder("https://example.com").build(), "fake 3");
+}
+
+#[test]
+fn test_fake_with_smart_pointer_receivers_should_be_called() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(
+            injectorpp::func!(fn (RequestBuilder::into_url)(Box<RequestBuilder>) -> String),
+        )
+        .will_execute(injectorpp::fake!(
+            func_type: fn(self: Box<RequestBuilder>) -> String,
+            returns: self.url.to_uppercase()
+        ));
+    injector
+        .when_called(
+            injectorpp::func!(fn (RequestBuilder::retries)(Arc<RequestBuilder>, u32) -> u32),
+        )
+        .will_execute(injectorpp::fake!(
+            func_type: fn(self: Arc<RequestBuilder>, extra: u32) -> u32,
+            when: extra == 1,
+            returns: self.retries * 100 + extra
+        ));
+    injector
+        .when_called(injectorpp::func!(RequestBuilder::reset))
+        .will_execute(injectorpp::fake!(
+            func_type: fn(self: Pin<&mut RequestBuilder>) -> (),
+            assign: { self.get_mut().retries = 42 }
+        ));
+
+    assert_eq!(Box::new(builder("a.com")).into_url(), "A.COM");
+    assert_eq!(Arc::new(builder("a.com")).retries(1), 301);
+
+    let mut request = builder("a.com");
+    Pin::new(&mut request).reset();
+    assert_eq!(request.retries, 42);
+}
+
+#[test]
+#[should_panic(expected = "self.url: expected \"https://example.com\", got \"https://other.com\"")]
+fn test_fake_with_self_receiver_should_report_unexpected_receiver() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(RequestBuilder::build))
+        .will_execute(injectorpp::fake!(
+            func_type: fn(self: RequestBuilder) -> String,
+            when: self.url == "https://example.com",
+            returns: String::new()
+        ));
+
+    builder("https://other.com").build();
+}
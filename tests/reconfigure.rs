@@ -0,0 +1,104 @@
+#![cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec",
+    target_arch = "arm"
+))]
+
+use injectorpp::interface::injector::*;
+
+#[inline(never)]
+fn retries() -> i32 {
+    core::hint::black_box(core::hint::black_box(1) + core::hint::black_box(2))
+}
+
+#[inline(never)]
+fn global_retries() -> i32 {
+    core::hint::black_box(core::hint::black_box(4) + core::hint::black_box(2))
+}
+
+#[inline(never)]
+fn is_leader() -> bool {
+    core::hint::black_box(false)
+}
+
+#[test]
+fn test_reconfigured_fake_should_replace_previous_fake() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(fn (retries)() -> i32))
+        .will_execute(injectorpp::fake!(
+            func_type: fn() -> i32,
+            returns: 10,
+            times: 1
+        ));
+    assert_eq!(retries(), 10);
+
+    injector
+        .when_called(injectorpp::func!(fn (retries)() -> i32))
+        .will_execute(injectorpp::fake!(
+            func_type: fn() -> i32,
+            returns: 20
+        ));
+    assert_eq!(retries(), 20);
+    assert_eq!(retries(), 20);
+    assert_eq!(injector.active_patches().len(), 1);
+
+    injector
+        .when_called(injectorpp::func!(fn (is_leader)() -> bool))
+        .will_return_boolean(true);
+    injector
+        .when_called(injectorpp::func!(fn (is_leader)() -> bool))
+        .will_return_boolean(false);
+    assert!(!is_leader());
+
+    drop(injector);
+    assert_eq!(retries(), 3);
+    assert!(!is_leader());
+}
+
+#[test]
+fn test_reconfigured_global_fake_should_replace_previous_fake() {
+    let mut injector = InjectorPP::new_global();
+    injector
+        .when_called(injectorpp::func!(fn (global_retries)() -> i32))
+        .will_execute(injectorpp::fake!(
+            func_type: fn() -> i32,
+            returns: 10
+        ));
+    assert_eq!(global_retries(), 10);
+
+    injector
+        .when_called(injectorpp::func!(fn (global_retries)() -> i32))
+        .will_execute(injectorpp::fake!(
+            func_type: fn() -> i32,
+            returns: 20
+        ));
+    assert_eq!(global_retries(), 20);
+    assert_eq!(injector.active_patches().len(), 1);
+
+    drop(injector);
+    assert_eq!(global_retries(), 6);
+}
+
+#[test]
+#[should_panic(expected = "expected to be called 2 time(s), but it is actually called 1 time(s)")]
+fn test_replaced_fake_should_still_verify_call_count() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(fn (retries)() -> i32))
+        .will_execute(injectorpp::fake!(
+            func_type: fn() -> i32,
+            returns: 10,
+            times: 2
+        ));
+    assert_eq!(retries(), 10);
+
+    injector
+        .when_called(injectorpp::func!(fn (retries)() -> i32))
+        .will_execute(injectorpp::fake!(
+            func_type: fn() -> i32,
+            returns: 20
+        ));
+    assert_eq!(retries(), 20);
+}
@@ -0,0 +1,177 @@
+#![cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec",
+    target_arch = "arm"
+))]
+
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use injectorpp::interface::injector::*;
+
+static LOOKUPS: AtomicUsize = AtomicUsize::new(0);
+
+#[inline(never)]
+fn lookup_host(name: &str, port: u16) -> io::Result<Vec<String>> {
+    LOOKUPS.fetch_add(1, Ordering::SeqCst);
+    match name {
+        "localhost" => Ok(vec![format!("127.0.0.1:{}", port)]),
+        _ => Err(io::Error::new(io::ErrorKind::NotFound, "no such host")),
+    }
+}
+
+static RANDOMS: AtomicUsize = AtomicUsize::new(0);
+
+#[inline(never)]
+fn random_id() -> u64 {
+    RANDOMS.fetch_add(1, Ordering::SeqCst) as u64 + std::hint::black_box(1000)
+}
+
+/// Returns a cassette path unique to `test`, removing any cassette left by an earlier run.
+fn cassette_path(test: &str) -> PathBuf {
+    let path = std::env::temp_dir()
+        .join(format!("injectorpp-{}", std::process::id()))
+        .join(format!("{}.cassette", test));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+#[test]
+fn test_record_should_replay_recorded_calls_without_running_function() {
+    let path = cassette_path("replay");
+    {
+        let cassette = Cassette::open(&path);
+        assert_eq!(cassette.mode(), CassetteMode::Record);
+        let mut injector = InjectorPP::new();
+        injectorpp::record!(
+            injector,
+            &cassette,
+            fn (lookup_host)(name: &str, port: u16) -> io::Result<Vec<String>>
+        );
+
+        assert_eq!(
+            lookup_host("localhost", 80).unwrap(),
+            vec!["127.0.0.1:80".to_string()]
+        );
+        assert!(lookup_host("example.invalid", 443).is_err());
+        assert_eq!(cassette.len(), 2);
+    }
+    let recorded = LOOKUPS.load(Ordering::SeqCst);
+
+    let cassette = Cassette::open(&path);
+    assert_eq!(cassette.mode(), CassetteMode::Replay);
+    let mut injector = InjectorPP::new();
+    injectorpp::record!(
+        injector,
+        &cassette,
+        fn (lookup_host)(name: &str, port: u16) -> io::Result<Vec<String>>
+    );
+
+    assert_eq!(
+        lookup_host("localhost", 80).unwrap(),
+        vec!["127.0.0.1:80".to_string()]
+    );
+    let error = lookup_host("example.invalid", 443).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::NotFound);
+    assert_eq!(error.to_string(), "no such host");
+    assert_eq!(LOOKUPS.load(Ordering::SeqCst), recorded);
+}
+
+#[test]
+fn test_record_should_replay_values_in_recorded_order() {
+    let path = cassette_path("order");
+    let recorded: Vec<u64> = {
+        let cassette = Cassette::record(&path);
+        let mut injector = InjectorPP::new();
+        injectorpp::record!(injector, &cassette, fn (random_id)() -> u64);
+        (0..3).map(|_| random_id()).collect()
+    };
+
+    let cassette = Cassette::replay(&path);
+    let mut injector = InjectorPP::new();
+    injectorpp::record!(injector, &cassette, fn (random_id)() -> u64);
+
+    let replayed: Vec<u64> = (0..3).map(|_| random_id()).collect();
+    assert_eq!(replayed, recorded);
+}
+
+#[test]
+#[should_panic(
+    expected = "was recorded with arguments (\"localhost\", 80) but made with (\"localhost\", 8080)"
+)]
+fn test_replay_with_other_arguments_should_panic() {
+    let path = cassette_path("arguments");
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(
+        &path,
+        "{\"function\":\"lookup_host\",\"args\":\"(\\\"localhost\\\", 80)\",\"returns\":{\"Ok\":[]}}\n",
+    )
+    .unwrap();
+
+    let cassette = Cassette::replay(&path);
+    let mut injector = InjectorPP::new();
+    injectorpp::record!(
+        injector,
+        &cassette,
+        fn (lookup_host)(name: &str, port: u16) -> io::Result<Vec<String>>
+    );
+
+    let _ = lookup_host("localhost", 8080);
+}
+
+#[test]
+#[should_panic(expected = "random_id was called 2 times but only 1 calls were recorded")]
+fn test_replay_with_more_calls_than_recorded_should_panic() {
+    let path = cassette_path("more_calls");
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(
+        &path,
+        "{\"function\":\"random_id\",\"args\":\"()\",\"returns\":7}\n",
+    )
+    .unwrap();
+
+    let cassette = Cassette::replay(&path);
+    let mut injector = InjectorPP::new();
+    injectorpp::record!(injector, &cassette, fn (random_id)() -> u64);
+
+    assert_eq!(random_id(), 7);
+    random_id();
+}
+
+#[test]
+fn test_recordable_should_round_trip_through_fixture_text() {
+    type Value = (Option<Vec<(char, f64)>>, Result<String, i128>, bool, ());
+    let value: Value = (
+        Some(vec![('"', 0.1), ('\n', f64::INFINITY)]),
+        Err(-170141183460469231731687303715884105728),
+        true,
+        (),
+    );
+
+    let text = value.record().to_string();
+    let replayed = Recordable::replay(&Fixture::parse(&text).unwrap());
+
+    assert_eq!(replayed, Some(value));
+}
+
+#[test]
+fn test_fixture_parse_should_combine_surrogate_pairs() {
+    assert_eq!(
+        Fixture::parse(r#""smile \uD83D\uDE00 \u00e9""#).unwrap(),
+        Fixture::String("smile \u{1F600} \u{e9}".to_string())
+    );
+}
+
+#[test]
+fn test_fixture_parse_with_unpaired_surrogate_should_fail() {
+    for text in [
+        r#""\uD83D""#,
+        r#""\uD83Dx""#,
+        r#""\uD83D\u0041""#,
+        r#""\uDE00""#,
+    ] {
+        assert!(Fixture::parse(text).is_err(), "{}", text);
+    }
+}
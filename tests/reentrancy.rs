@@ -0,0 +1,108 @@
+#![cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec",
+    target_arch = "arm"
+))]
+
+use std::os::raw::{c_int, c_void};
+
+use injectorpp::interface::injector::*;
+
+extern "C" {
+    fn memset(s: *mut c_void, c: c_int, n: usize) -> *mut c_void;
+}
+
+#[inline(never)]
+fn describe(code: u32) -> String {
+    std::hint::black_box(format!("code {}", code))
+}
+
+#[inline(never)]
+fn report(code: u32) -> String {
+    describe(code)
+}
+
+#[test]
+fn test_reentrant_calls_original_should_send_nested_calls_to_original() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(fn (describe)(u32) -> String))
+        .reentrant_calls_original()
+        .will_execute(injectorpp::fake!(
+            func_type: fn(code: u32) -> String,
+            returns: format!("[fake] {}", report(code + 1))
+        ));
+
+    assert_eq!(describe(1), "[fake] code 2");
+    assert_eq!(report(3), "[fake] code 4");
+}
+
+#[test]
+fn test_reentrant_calls_original_should_let_fake_of_memset_call_memset() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(
+            func_info: unsafe extern "C" fn(memset)(*mut c_void, c_int, usize) -> *mut c_void
+        ))
+        .reentrant_calls_original()
+        .will_execute(injectorpp::fake!(
+            func_type: unsafe extern "C" fn(s: *mut c_void, _c: c_int, n: usize) -> *mut c_void,
+            returns: memset(s, 0x5a, n)
+        ));
+
+    let mut buf = [0u8; 16];
+    unsafe { memset(buf.as_mut_ptr() as *mut c_void, 0, buf.len()) };
+    assert_eq!(buf, [0x5a; 16]);
+}
+
+#[test]
+fn test_reentrant_calls_original_should_send_nested_calls_of_closure_to_original() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(fn (describe)(u32) -> String))
+        .reentrant_calls_original()
+        .will_execute_closure(injectorpp::boxed_closure!(
+            |code| report(code).to_uppercase(),
+            fn(u32) -> String
+        ));
+
+    assert_eq!(describe(7), "CODE 7");
+}
+
+#[test]
+fn test_reentrant_calls_original_should_not_affect_other_threads() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(fn (describe)(u32) -> String))
+        .reentrant_calls_original()
+        .will_execute(injectorpp::fake!(
+            func_type: fn(code: u32) -> String,
+            returns: std::thread::spawn(move || report(code)).join().unwrap()
+        ));
+
+    assert_eq!(describe(5), "code 5");
+}
+
+#[test]
+#[should_panic(expected = "requires a fake created with fake! or boxed_closure!")]
+fn test_reentrant_calls_original_with_plain_function_should_panic() {
+    fn fake_describe(_code: u32) -> String {
+        String::new()
+    }
+
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(fn (describe)(u32) -> String))
+        .reentrant_calls_original()
+        .will_execute_raw(injectorpp::func!(fn (fake_describe)(u32) -> String));
+}
+
+#[test]
+#[should_panic(expected = "not with InjectorPP::new_global()")]
+fn test_reentrant_calls_original_with_global_injector_should_panic() {
+    let mut injector = InjectorPP::new_global();
+    injector
+        .when_called(injectorpp::func!(fn (describe)(u32) -> String))
+        .reentrant_calls_original();
+}
@@ -0,0 +1,91 @@
+#![cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec",
+    target_arch = "arm"
+))]
+
+use injectorpp::interface::injector::*;
+
+#[inline(never)]
+fn config_path() -> String {
+    std::hint::black_box("/etc/app.toml").to_string()
+}
+
+#[inline(never)]
+fn is_cached() -> bool {
+    std::hint::black_box(false)
+}
+
+#[inline(never)]
+fn global_port() -> i32 {
+    core::hint::black_box(core::hint::black_box(8000) + core::hint::black_box(80))
+}
+
+#[test]
+fn test_restore_should_only_remove_given_fake() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(fn (config_path)() -> String))
+        .will_execute(injectorpp::fake!(
+            func_type: fn() -> String,
+            returns: "/tmp/test.toml".to_string()
+        ));
+    injector
+        .when_called(injectorpp::func!(fn (is_cached)() -> bool))
+        .will_return_boolean(true);
+
+    assert_eq!(config_path(), "/tmp/test.toml");
+
+    injector.restore(injectorpp::func!(fn (config_path)() -> String));
+
+    assert_eq!(config_path(), "/etc/app.toml");
+    assert!(is_cached());
+    assert_eq!(injector.active_patches().len(), 1);
+
+    injector
+        .when_called(injectorpp::func!(fn (config_path)() -> String))
+        .will_execute(injectorpp::fake!(
+            func_type: fn() -> String,
+            returns: "/tmp/again.toml".to_string()
+        ));
+    assert_eq!(config_path(), "/tmp/again.toml");
+}
+
+#[test]
+fn test_restore_global_fake_should_release_function() {
+    let mut injector = InjectorPP::new_global();
+    injector
+        .when_called(injectorpp::func!(fn (global_port)() -> i32))
+        .will_execute(injectorpp::fake!(
+            func_type: fn() -> i32,
+            returns: 1
+        ));
+    assert_eq!(global_port(), 1);
+
+    injector.restore(injectorpp::func!(fn (global_port)() -> i32));
+    assert_eq!(global_port(), 8080);
+
+    // Another thread can fake the function globally while this injector is alive.
+    std::thread::spawn(|| {
+        let mut other = InjectorPP::new_global();
+        other
+            .when_called(injectorpp::func!(fn (global_port)() -> i32))
+            .will_execute(injectorpp::fake!(
+                func_type: fn() -> i32,
+                returns: 2
+            ));
+        assert_eq!(global_port(), 2);
+    })
+    .join()
+    .unwrap();
+
+    assert_eq!(global_port(), 8080);
+}
+
+#[test]
+#[should_panic(expected = "it is not faked by this injector")]
+fn test_restore_unfaked_function_should_panic() {
+    let mut injector = InjectorPP::new();
+    injector.restore(injectorpp::func!(fn (is_cached)() -> bool));
+}
@@ -0,0 +1,144 @@
+#![cfg(target_os = "linux")]
+
+use injectorpp::interface::injector::*;
+
+mod net {
+    pub mod tcp {
+        #[inline(never)]
+        fn connect(port: u16) -> i32 {
+            core::hint::black_box(port as i32)
+        }
+
+        pub fn open(port: u16) -> i32 {
+            connect(port)
+        }
+    }
+
+    pub mod udp {
+        #[inline(never)]
+        fn connect(port: u16) -> i32 {
+            core::hint::black_box(port as i32 + 1)
+        }
+
+        pub fn open(port: u16) -> i32 {
+            connect(port)
+        }
+    }
+
+    #[inline(never)]
+    fn resolve(host: &str) -> usize {
+        core::hint::black_box(host.len())
+    }
+
+    pub fn lookup(host: &str) -> usize {
+        resolve(host)
+    }
+}
+
+#[inline(never)]
+fn type_tag<T>(value: &T) -> usize {
+    core::hint::black_box(std::mem::size_of_val(value))
+}
+
+#[test]
+fn test_when_called_symbol_should_fake_private_function_by_path() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called_symbol::<fn(u16) -> i32>("rust_symbol::net::tcp::connect")
+        .will_execute(injectorpp::fake!(
+            func_type: fn(_port: u16) -> i32,
+            returns: -1
+        ));
+
+    assert_eq!(net::tcp::open(80), -1);
+    assert_eq!(net::udp::open(80), 81);
+}
+
+#[test]
+fn test_when_called_symbol_should_accept_fake_borrowing_arguments() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called_symbol::<fn(&'static str) -> usize>("**::net::resolve")
+        .will_execute(injectorpp::fake!(
+            func_type: fn(host: &str) -> usize,
+            when: host == "localhost",
+            returns: 0
+        ));
+
+    assert_eq!(net::lookup("localhost"), 0);
+}
+
+#[test]
+#[should_panic(expected = "2 functions match it")]
+fn test_when_called_symbol_with_ambiguous_pattern_should_panic() {
+    assert_eq!(net::tcp::open(1) + net::udp::open(1), 3);
+
+    let mut injector = InjectorPP::new();
+    injector.when_called_symbol::<fn(u16) -> i32>("rust_symbol::net::*::connect");
+}
+
+#[test]
+#[should_panic(expected = "no function in the symbol tables of the loaded modules matches it")]
+fn test_when_called_symbol_without_match_should_panic() {
+    let mut injector = InjectorPP::new();
+    injector.when_called_symbol::<fn(u16) -> i32>("rust_symbol::net::*::disconnect");
+}
+
+#[test]
+fn test_when_called_symbols_should_fake_every_match() {
+    let mut injector = InjectorPP::new();
+    let mut paths = Vec::new();
+    let faked = injector.when_called_symbols::<fn(u16) -> i32>(
+        "rust_symbol::net::*::connect",
+        |when, path| {
+            paths.push(path.to_string());
+            when.will_execute(injectorpp::fake!(
+                func_type: fn(_port: u16) -> i32,
+                returns: 0
+            ));
+        },
+    );
+
+    assert_eq!(faked, 2);
+    paths.sort();
+    assert_eq!(
+        paths,
+        [
+            "rust_symbol::net::tcp::connect",
+            "rust_symbol::net::udp::connect"
+        ]
+    );
+    assert_eq!(net::tcp::open(80), 0);
+    assert_eq!(net::udp::open(80), 0);
+}
+
+#[test]
+fn test_from_symbol_pattern_should_return_no_match_for_unknown_path() {
+    assert!(FuncPtr::from_symbol_pattern::<fn()>("rust_symbol::no_such_function").is_empty());
+}
+
+#[test]
+fn test_when_called_instances_should_fake_every_instance_until_dropped() {
+    assert_eq!(type_tag(&1u16), 2);
+    assert_eq!(type_tag(&1u64), 8);
+
+    {
+        let mut injector = InjectorPP::new();
+        let faked = injector.when_called_instances(
+            injectorpp::func!(fn (type_tag::<u16>)(&u16) -> usize),
+            |when| {
+                when.will_execute(injectorpp::fake!(
+                    func_type: fn(_value: &u16) -> usize,
+                    returns: 0
+                ));
+            },
+        );
+
+        assert_eq!(faked, 2);
+        assert_eq!(type_tag(&1u16), 0);
+        assert_eq!(type_tag(&1u64), 0);
+    }
+
+    assert_eq!(type_tag(&1u16), 2);
+    assert_eq!(type_tag(&1u64), 8);
+}
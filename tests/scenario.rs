@@ -0,0 +1,142 @@
+#![cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec",
+    target_arch = "arm"
+))]
+
+use std::io;
+use std::sync::{Arc, Mutex};
+
+use injectorpp::interface::injector::*;
+
+#[inline(never)]
+fn connect(addr: &str) -> io::Result<()> {
+    std::hint::black_box(Err(io::Error::other(format!("cannot reach {}", addr))))
+}
+
+#[inline(never)]
+fn send(buf: &[u8]) -> io::Result<usize> {
+    std::hint::black_box(Err(io::Error::other(format!(
+        "cannot send {} bytes",
+        buf.len()
+    ))))
+}
+
+#[inline(never)]
+fn recv(buf: &mut [u8]) -> io::Result<usize> {
+    std::hint::black_box(Err(io::Error::other(format!(
+        "cannot fill {} bytes",
+        buf.len()
+    ))))
+}
+
+#[inline(never)]
+fn close() {
+    std::hint::black_box(());
+}
+
+/// Sends `payload`, retrying while the socket would block, and returns the reply.
+fn request(payload: &[u8]) -> io::Result<Vec<u8>> {
+    connect("example.com:80")?;
+    loop {
+        match send(payload) {
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => continue,
+            result => result?,
+        };
+        break;
+    }
+    let mut reply = [0u8; 16];
+    let len = recv(&mut reply)?;
+    close();
+    Ok(reply[..len].to_vec())
+}
+
+#[test]
+fn test_scenario_should_script_calls_across_functions() {
+    let mut injector = InjectorPP::new();
+    let scenario = injectorpp::scenario!(injector, [
+        fn (connect)(addr: &str) -> io::Result<()> => Ok(()),
+        fn (send)(buf: &[u8]) -> io::Result<usize> => Err(io::ErrorKind::WouldBlock.into()),
+        fn (send)(buf: &[u8]) -> io::Result<usize> => Ok(buf.len()),
+        fn (recv)(buf: &mut [u8]) -> io::Result<usize> => {
+            buf[..5].copy_from_slice(b"hello");
+            Ok(5)
+        },
+        fn (close)() => (),
+    ]);
+
+    assert_eq!(request(b"ping").unwrap(), b"hello");
+    assert_eq!(scenario.len(), 5);
+    assert!(scenario.is_done());
+}
+
+#[test]
+fn test_scenario_steps_should_see_arguments_and_captures() {
+    let sent = Arc::new(Mutex::new(Vec::new()));
+    let recorder = sent.clone();
+
+    let mut injector = InjectorPP::new();
+    let scenario = injectorpp::scenario!(injector, [
+        fn (connect)(addr: &str) -> io::Result<()> => {
+            assert_eq!(addr, "example.com:80");
+            Ok(())
+        },
+        fn (send)(buf: &[u8]) -> io::Result<usize> => {
+            recorder.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        },
+        fn (recv)(buf: &mut [u8]) -> io::Result<usize> => Ok(0),
+        fn (close)() => (),
+    ]);
+
+    assert!(request(b"ping").unwrap().is_empty());
+    assert_eq!(*sent.lock().unwrap(), b"ping");
+    scenario.verify();
+}
+
+#[test]
+#[should_panic(expected = "Scenario: step 2 of 3 expects a call of send, but recv was called")]
+fn test_scenario_call_out_of_order_should_panic() {
+    let mut injector = InjectorPP::new();
+    let _scenario = injectorpp::scenario!(injector, [
+        fn (connect)(addr: &str) -> io::Result<()> => Ok(()),
+        fn (send)(buf: &[u8]) -> io::Result<usize> => Ok(buf.len()),
+        fn (recv)(buf: &mut [u8]) -> io::Result<usize> => Ok(0),
+    ]);
+
+    connect("example.com:80").unwrap();
+    let _ = recv(&mut [0u8; 4]);
+}
+
+#[test]
+#[should_panic(expected = "Scenario: send was called after the last of its 2 steps")]
+fn test_scenario_call_after_last_step_should_panic() {
+    let mut injector = InjectorPP::new();
+    let _scenario = injectorpp::scenario!(injector, [
+        fn (connect)(addr: &str) -> io::Result<()> => Ok(()),
+        fn (send)(buf: &[u8]) -> io::Result<usize> => Ok(buf.len()),
+    ]);
+
+    connect("example.com:80").unwrap();
+    send(b"ping").unwrap();
+    let _ = send(b"ping");
+}
+
+#[test]
+#[should_panic(expected = "Scenario stopped at step 3 of 4: recv was never called")]
+fn test_scenario_with_steps_not_reached_should_panic_on_drop() {
+    let mut injector = InjectorPP::new();
+    let scenario = injectorpp::scenario!(injector, [
+        fn (connect)(addr: &str) -> io::Result<()> => Ok(()),
+        fn (send)(buf: &[u8]) -> io::Result<usize> => Ok(buf.len()),
+        fn (recv)(buf: &mut [u8]) -> io::Result<usize> => Ok(0),
+        fn (close)() => (),
+    ]);
+
+    connect("example.com:80").unwrap();
+    send(b"ping").unwrap();
+    assert_eq!(scenario.completed(), 2);
+    drop(scenario);
+    drop(injector);
+}
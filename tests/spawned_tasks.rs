@@ -0,0 +1,115 @@
+use injectorpp::utilities::task::SpawnedTasks;
+use std::sync::{Arc, Mutex};
+
+async fn audit(log: Arc<Mutex<Vec<String>>>, entry: String) -> usize {
+    tokio::task::yield_now().await;
+    let mut log = log.lock().unwrap();
+    log.push(entry);
+    log.len()
+}
+
+async fn refresh_cache(log: Arc<Mutex<Vec<String>>>) {
+    log.lock().unwrap().push("refresh".to_string());
+}
+
+fn handle_request(log: &Arc<Mutex<Vec<String>>>, user: &str) {
+    tokio::spawn(audit(log.clone(), format!("login {user}")));
+}
+
+#[tokio::test]
+async fn test_spawned_tasks_should_hold_tasks_until_run() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let tasks = SpawnedTasks::capture(injectorpp::async_fn!(
+        fn (audit)(Arc<Mutex<Vec<String>>>, String) -> usize
+    ));
+
+    handle_request(&log, "alice");
+    handle_request(&log, "bob");
+    tokio::task::yield_now().await;
+
+    assert_eq!(tasks.pending(), 2);
+    assert!(log.lock().unwrap().is_empty());
+
+    tasks.run_all().await;
+
+    assert_eq!(tasks.pending(), 0);
+    assert_eq!(*log.lock().unwrap(), vec!["login alice", "login bob"]);
+}
+
+#[tokio::test]
+async fn test_spawned_tasks_run_next_should_run_one_task_in_spawn_order() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let tasks = SpawnedTasks::capture(injectorpp::async_fn!(
+        fn (audit)(Arc<Mutex<Vec<String>>>, String) -> usize
+    ));
+
+    let first = tokio::spawn(audit(log.clone(), "first".to_string()));
+    let second = tokio::spawn(audit(log.clone(), "second".to_string()));
+    tokio::task::yield_now().await;
+
+    assert!(tasks.run_next().await);
+    assert_eq!(*log.lock().unwrap(), vec!["first"]);
+    assert_eq!(first.await.unwrap(), 1);
+
+    assert!(tasks.run_next().await);
+    assert_eq!(second.await.unwrap(), 2);
+
+    assert!(!tasks.run_next().await);
+}
+
+#[tokio::test]
+async fn test_spawned_tasks_should_not_hold_other_tasks() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let tasks = SpawnedTasks::capture(injectorpp::async_fn!(
+        fn (audit)(Arc<Mutex<Vec<String>>>, String) -> usize
+    ));
+
+    handle_request(&log, "alice");
+    tokio::spawn(refresh_cache(log.clone())).await.unwrap();
+
+    assert_eq!(*log.lock().unwrap(), vec!["refresh"]);
+
+    tasks.run_all().await;
+    assert_eq!(*log.lock().unwrap(), vec!["refresh", "login alice"]);
+}
+
+#[tokio::test]
+async fn test_spawned_tasks_when_dropped_should_release_tasks() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let handle = {
+        let tasks = SpawnedTasks::capture(injectorpp::async_fn!(
+            fn (audit)(Arc<Mutex<Vec<String>>>, String) -> usize
+        ));
+
+        let handle = tokio::spawn(audit(log.clone(), "late".to_string()));
+        tokio::task::yield_now().await;
+        assert_eq!(tasks.pending(), 1);
+
+        handle
+    };
+
+    assert_eq!(handle.await.unwrap(), 1);
+    assert_eq!(*log.lock().unwrap(), vec!["late"]);
+}
+
+#[tokio::test]
+async fn test_spawned_tasks_when_async_block_awaits_function_should_hold_it_from_there() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let tasks = SpawnedTasks::capture(injectorpp::async_fn!(
+        fn (audit)(Arc<Mutex<Vec<String>>>, String) -> usize
+    ));
+
+    let block_log = log.clone();
+    let handle = tokio::spawn(async move {
+        block_log.lock().unwrap().push("start".to_string());
+        audit(block_log, "block".to_string()).await
+    });
+    tokio::task::yield_now().await;
+
+    assert_eq!(tasks.pending(), 1);
+    assert_eq!(*log.lock().unwrap(), vec!["start"]);
+
+    tasks.run_all().await;
+    assert_eq!(handle.await.unwrap(), 2);
+    assert_eq!(*log.lock().unwrap(), vec!["start", "block"]);
+}
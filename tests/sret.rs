@@ -0,0 +1,132 @@
+#![cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec"
+))]
+
+use injectorpp::interface::injector::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Snapshot {
+    id: u64,
+    offsets: [u64; 4],
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Extent {
+    start: u64,
+    length: u64,
+    flags: u32,
+}
+
+#[inline(never)]
+fn take_snapshot(id: u64) -> Snapshot {
+    std::hint::black_box(Snapshot {
+        id,
+        offsets: [0; 4],
+    })
+}
+
+#[inline(never)]
+extern "C" fn first_extent(fd: i32) -> Extent {
+    std::hint::black_box(Extent {
+        start: fd as u64,
+        length: 0,
+        flags: 0,
+    })
+}
+
+#[inline(never)]
+fn snapshot_id(id: u64) -> u64 {
+    std::hint::black_box(id)
+}
+
+#[test]
+fn test_fake_function_returning_large_struct() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(fn (take_snapshot)(u64) -> Snapshot))
+        .will_execute(injectorpp::fake!(
+            func_type: fn(id: u64) -> Snapshot,
+            returns: Snapshot { id: id + 1, offsets: [1, 2, 3, 4] },
+            times: 1
+        ));
+
+    let snapshot = take_snapshot(7);
+    assert_eq!(
+        snapshot,
+        Snapshot {
+            id: 8,
+            offsets: [1, 2, 3, 4]
+        }
+    );
+}
+
+#[test]
+fn test_fake_extern_c_function_returning_large_repr_c_struct() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(
+            unsafe{} extern "C" fn (first_extent)(i32) -> Extent
+        ))
+        .will_execute(injectorpp::fake!(
+            func_type: unsafe extern "C" fn(_fd: i32) -> Extent,
+            returns: Extent { start: 4096, length: 512, flags: 1 }
+        ));
+
+    assert_eq!(
+        first_extent(3),
+        Extent {
+            start: 4096,
+            length: 512,
+            flags: 1
+        }
+    );
+}
+
+#[test]
+fn test_method_returning_large_struct_with_closure() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::method!(take_snapshot))
+        .will_execute_closure(injectorpp::boxed_closure!(
+            |id| Snapshot {
+                id,
+                offsets: [id; 4]
+            },
+            fn(u64) -> Snapshot
+        ));
+
+    assert_eq!(take_snapshot(5).offsets, [5; 4]);
+}
+
+#[test]
+#[should_panic(
+    expected = "Return ABI mismatch: the function returns 40 bytes through a hidden pointer but the fake returns 8 bytes in registers"
+)]
+fn test_fake_returning_in_registers_for_large_struct_should_panic() {
+    let mut injector = InjectorPP::new();
+    unsafe {
+        injector
+            .when_called(injectorpp::func!(fn (take_snapshot)(u64) -> Snapshot))
+            .will_execute_raw_unchecked(injectorpp::func!(fn (snapshot_id)(u64) -> u64));
+    }
+}
+
+#[test]
+#[should_panic(expected = "Return ABI mismatch")]
+fn test_unchecked_target_with_large_struct_fake_should_panic() {
+    let mut injector = InjectorPP::new();
+    unsafe {
+        injector
+            .when_called_unchecked(injectorpp::method!(snapshot_id))
+            .will_execute_raw_unchecked(
+                injectorpp::fake!(
+                    func_type: fn(id: u64) -> Snapshot,
+                    returns: Snapshot { id, offsets: [0; 4] }
+                )
+                .0,
+            );
+    }
+}
@@ -6,7 +6,12 @@
 // during parallel test execution.
 //
 // These tests verify that after patching functions, deep stack usage still works.
-#![cfg(any(target_arch = "x86_64", target_arch = "aarch64", target_arch = "arm"))]
+#![cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec",
+    target_arch = "arm"
+))]
 
 use std::sync::{Arc, Barrier};
 use std::thread;
@@ -0,0 +1,128 @@
+#![cfg(feature = "stream")]
+
+use futures::StreamExt;
+use futures_core::Stream;
+use injectorpp::interface::injector::*;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// A paginated listing that would fetch its pages from a remote service.
+struct BlobPages {
+    pub container: String,
+}
+
+impl Stream for BlobPages {
+    type Item = Result<Vec<String>, String>;
+
+    fn poll_next(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Poll::Ready(Some(Err(format!("cannot list {}", self.container))))
+    }
+}
+
+/// A socket that never receives anything.
+struct Frames {
+    pub port: u16,
+}
+
+impl Stream for Frames {
+    type Item = Vec<u8>;
+
+    fn poll_next(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Vec<u8>>> {
+        std::hint::black_box(self.port);
+        Poll::Pending
+    }
+}
+
+fn list_blobs(container: &str) -> BlobPages {
+    BlobPages {
+        container: container.to_string(),
+    }
+}
+
+#[tokio::test]
+async fn test_stream_return_should_yield_items_then_end() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called_stream(injectorpp::stream_func!(
+            list_blobs("logs"),
+            Result<Vec<String>, String>
+        ))
+        .will_return_stream(injectorpp::stream_return!(
+            [
+                Ok(vec!["a.log".to_string(), "b.log".to_string()]),
+                Ok(vec!["c.log".to_string()]),
+            ],
+            Result<Vec<String>, String>
+        ));
+
+    let mut pages = list_blobs("logs");
+    let mut blobs = Vec::new();
+    while let Some(page) = pages.next().await {
+        blobs.extend(page.unwrap());
+    }
+
+    assert_eq!(blobs, vec!["a.log", "b.log", "c.log"]);
+}
+
+#[tokio::test]
+async fn test_stream_return_when_multiple_streams_should_track_each_stream() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called_stream(injectorpp::stream_func!(Frames { port: 0 }, Vec<u8>))
+        .will_return_stream(injectorpp::stream_return!(
+            [b"hello".to_vec(), b"world".to_vec()],
+            Vec<u8>
+        ));
+
+    let mut first = Frames { port: 1 };
+    let mut second = Frames { port: 2 };
+
+    assert_eq!(first.next().await, Some(b"hello".to_vec()));
+    assert_eq!(second.next().await, Some(b"hello".to_vec()));
+    assert_eq!(first.next().await, Some(b"world".to_vec()));
+    assert_eq!(first.next().await, None);
+    assert_eq!(second.next().await, Some(b"world".to_vec()));
+    assert_eq!(second.next().await, None);
+}
+
+#[tokio::test]
+async fn test_stream_return_when_empty_should_end_immediately() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called_stream(injectorpp::stream_func!(Frames { port: 0 }, Vec<u8>))
+        .will_return_stream(injectorpp::stream_return!([], Vec<u8>));
+
+    assert_eq!(
+        Frames { port: 3 }.collect::<Vec<_>>().await,
+        Vec::<Vec<u8>>::new()
+    );
+}
+
+#[tokio::test]
+async fn test_stream_return_when_injector_dropped_should_restore() {
+    {
+        let mut injector = InjectorPP::new();
+        injector
+            .when_called_stream(injectorpp::stream_func!(
+                list_blobs("logs"),
+                Result<Vec<String>, String>
+            ))
+            .will_return_stream(injectorpp::stream_return!([], Result<Vec<String>, String>));
+
+        assert_eq!(list_blobs("logs").next().await, None);
+    }
+
+    assert_eq!(
+        list_blobs("logs").next().await,
+        Some(Err("cannot list logs".to_string()))
+    );
+}
+
+#[test]
+#[should_panic(expected = "Signature mismatch")]
+fn test_stream_return_when_item_type_mismatch_should_panic() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called_stream(injectorpp::stream_func!(Frames { port: 0 }, Vec<u8>))
+        .will_return_stream(injectorpp::stream_return!([1, 2], u32));
+}
@@ -0,0 +1,59 @@
+#![cfg(all(feature = "strict_wx", target_os = "linux"))]
+
+use injectorpp::interface::injector::*;
+
+#[inline(never)]
+fn guarded_func() -> i32 {
+    std::hint::black_box(1)
+}
+
+#[inline(never)]
+fn guarded_global_func() -> i32 {
+    std::hint::black_box(2)
+}
+
+/// Returns the lines of `/proc/self/maps` for mappings both writable and executable.
+fn writable_executable_mappings() -> Vec<String> {
+    std::fs::read_to_string("/proc/self/maps")
+        .unwrap()
+        .lines()
+        .filter(|line| {
+            let perms = line.split_whitespace().nth(1).unwrap_or("");
+            perms.contains('w') && perms.contains('x')
+        })
+        .map(str::to_string)
+        .collect()
+}
+
+#[test]
+fn test_strict_wx_fake_should_map_nothing_writable_and_executable() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(fn (guarded_func)() -> i32))
+        .will_execute(injectorpp::fake!(
+            func_type: fn() -> i32,
+            returns: 3
+        ));
+
+    assert_eq!(guarded_func(), 3);
+    assert_eq!(writable_executable_mappings(), Vec::<String>::new());
+}
+
+#[test]
+fn test_strict_wx_global_fake_should_restore_original() {
+    {
+        let mut injector = InjectorPP::new_global();
+        injector
+            .when_called(injectorpp::func!(fn (guarded_global_func)() -> i32))
+            .will_execute(injectorpp::fake!(
+                func_type: fn() -> i32,
+                returns: 4
+            ));
+
+        assert_eq!(guarded_global_func(), 4);
+        assert_eq!(writable_executable_mappings(), Vec::<String>::new());
+    }
+
+    assert_eq!(guarded_global_func(), 2);
+    assert_eq!(writable_executable_mappings(), Vec::<String>::new());
+}
@@ -0,0 +1,90 @@
+#![cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec",
+    target_arch = "arm"
+))]
+
+use injectorpp::interface::injector::*;
+
+#[inline(never)]
+fn region() -> String {
+    std::hint::black_box("westus").to_string()
+}
+
+#[inline(never)]
+fn is_primary() -> bool {
+    std::hint::black_box(false)
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_task_local_fake_should_only_apply_inside_scope() {
+    let mut injector = InjectorPP::new_task_local();
+    injector
+        .when_called(injectorpp::func!(fn (region)() -> String))
+        .will_execute(injectorpp::fake!(
+            func_type: fn() -> String,
+            returns: "eastus".to_string()
+        ));
+
+    // Not visible on the installing thread.
+    assert_eq!(region(), "westus");
+
+    let faked = tokio::spawn(injector.scope(async {
+        let mut seen = Vec::new();
+        for _ in 0..8 {
+            seen.push(region());
+            tokio::task::yield_now().await;
+        }
+        seen
+    }));
+    let original = tokio::spawn(async {
+        let mut seen = Vec::new();
+        for _ in 0..8 {
+            seen.push(region());
+            tokio::task::yield_now().await;
+        }
+        seen
+    });
+
+    assert!(faked.await.unwrap().iter().all(|r| r == "eastus"));
+    assert!(original.await.unwrap().iter().all(|r| r == "westus"));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_task_local_should_restore_original_after_drop() {
+    let scoped = {
+        let mut injector = InjectorPP::new_task_local();
+        injector
+            .when_called(injectorpp::func!(fn (is_primary)() -> bool))
+            .will_return_boolean(true);
+
+        assert!(tokio::spawn(injector.scope(async { is_primary() }))
+            .await
+            .unwrap());
+
+        injector.scope(async { is_primary() })
+    };
+
+    assert!(!scoped.await);
+    assert!(!is_primary());
+}
+
+#[tokio::test]
+async fn test_task_local_fake_installed_after_scope_should_apply() {
+    let mut injector = InjectorPP::new_task_local();
+    let scoped = injector.scope(async { is_primary() });
+
+    injector
+        .when_called(injectorpp::func!(fn (is_primary)() -> bool))
+        .will_return_boolean(true);
+
+    assert!(scoped.await);
+}
+
+#[test]
+#[should_panic(expected = "requires an injector created by InjectorPP::new_task_local()")]
+fn test_scope_without_task_local_injector_should_panic() {
+    let injector = InjectorPP::new();
+    drop(injector.scope(async {}));
+}
@@ -1,7 +1,12 @@
 // Thread-local dispatch is available on x86_64 and aarch64. On other architectures,
 // InjectorPP uses a global mutex which deadlocks with the barrier-based
 // synchronization these tests rely on.
-#![cfg(any(target_arch = "x86_64", target_arch = "aarch64", target_arch = "arm"))]
+#![cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec",
+    target_arch = "arm"
+))]
 
 use std::sync::atomic::{AtomicBool, AtomicI32, AtomicUsize, Ordering};
 use std::sync::{Arc, Barrier};
@@ -1147,3 +1152,51 @@ fn test_struct_method_scope_restore() {
     // After scope exit, original should be restored
     assert_eq!(w.get(), 10);
 }
+
+// ============================================================================
+// Explicit thread-local constructor
+// ============================================================================
+
+/// `new_thread_local()` fakes are only visible on the installing thread, so two threads
+/// faking different functions run at the same time.
+#[test]
+fn test_new_thread_local_fakes_different_functions_in_parallel() {
+    let barrier = Arc::new(Barrier::new(2));
+
+    let b1 = barrier.clone();
+    let h1 = thread::spawn(move || {
+        let mut injector = InjectorPP::new_thread_local();
+        injector
+            .when_called(injectorpp::func!(fn(get_value)() -> i32))
+            .will_execute(injectorpp::fake!(
+                func_type: fn() -> i32,
+                returns: 10
+            ));
+
+        b1.wait();
+        let seen = (get_value(), get_other_value());
+        b1.wait();
+        seen
+    });
+
+    let b2 = barrier.clone();
+    let h2 = thread::spawn(move || {
+        let mut injector = InjectorPP::new_thread_local();
+        injector
+            .when_called(injectorpp::func!(fn(get_other_value)() -> i32))
+            .will_execute(injectorpp::fake!(
+                func_type: fn() -> i32,
+                returns: 20
+            ));
+
+        b2.wait();
+        let seen = (get_value(), get_other_value());
+        b2.wait();
+        seen
+    });
+
+    assert_eq!(h1.join().unwrap(), (10, -2));
+    assert_eq!(h2.join().unwrap(), (-1, 20));
+    assert_eq!(get_value(), -1);
+    assert_eq!(get_other_value(), -2);
+}
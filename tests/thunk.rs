@@ -0,0 +1,123 @@
+#![cfg(all(target_os = "linux", target_arch = "x86_64"))]
+
+use injectorpp::interface::injector::*;
+
+// Import thunks, as the PLT and Windows import tables make them: an indirect jump through a
+// slot holding the address of the function.
+std::arch::global_asm!(
+    ".text",
+    ".p2align 4",
+    ".globl injectorpp_test_thunked_one",
+    ".type injectorpp_test_thunked_one, @function",
+    "injectorpp_test_thunked_one:",
+    "mov eax, 1",
+    "add eax, 0",
+    "ret",
+    ".size injectorpp_test_thunked_one, . - injectorpp_test_thunked_one",
+    ".p2align 4",
+    ".globl injectorpp_test_thunked_two",
+    ".type injectorpp_test_thunked_two, @function",
+    "injectorpp_test_thunked_two:",
+    "mov eax, 2",
+    "add eax, 0",
+    "ret",
+    ".size injectorpp_test_thunked_two, . - injectorpp_test_thunked_two",
+    ".p2align 4",
+    ".globl injectorpp_test_thunked_three",
+    ".type injectorpp_test_thunked_three, @function",
+    "injectorpp_test_thunked_three:",
+    "mov eax, 3",
+    "add eax, 0",
+    "ret",
+    ".size injectorpp_test_thunked_three, . - injectorpp_test_thunked_three",
+    ".p2align 4",
+    ".globl injectorpp_test_thunk_one",
+    "injectorpp_test_thunk_one:",
+    "jmp qword ptr [rip + injectorpp_test_slot_one]",
+    ".p2align 4",
+    ".globl injectorpp_test_thunk_two",
+    "injectorpp_test_thunk_two:",
+    "jmp qword ptr [rip + injectorpp_test_slot_two]",
+    ".p2align 4",
+    ".globl injectorpp_test_thunk_three",
+    "injectorpp_test_thunk_three:",
+    "jmp qword ptr [rip + injectorpp_test_slot_three]",
+    ".data",
+    ".p2align 3",
+    "injectorpp_test_slot_one:",
+    ".quad injectorpp_test_thunked_one",
+    "injectorpp_test_slot_two:",
+    ".quad injectorpp_test_thunked_two",
+    "injectorpp_test_slot_three:",
+    ".quad injectorpp_test_thunked_three",
+    ".text",
+);
+
+extern "C" {
+    fn injectorpp_test_thunked_one() -> u32;
+    fn injectorpp_test_thunked_two() -> u32;
+    fn injectorpp_test_thunk_one() -> u32;
+    fn injectorpp_test_thunk_two() -> u32;
+    fn injectorpp_test_thunked_three() -> u32;
+    fn injectorpp_test_thunk_three() -> u32;
+}
+
+extern "C" fn fake_seven() -> u32 {
+    7
+}
+
+#[test]
+fn test_fake_of_thunk_should_fake_direct_calls_of_its_target() {
+    let mut injector = InjectorPP::new();
+    unsafe {
+        injector
+            .when_called_unchecked(injectorpp::func_unchecked!(injectorpp_test_thunk_one))
+            .will_execute_raw_unchecked(injectorpp::func_unchecked!(fake_seven));
+
+        assert_eq!(injectorpp_test_thunked_one(), 7);
+        assert_eq!(injectorpp_test_thunk_one(), 7);
+    }
+}
+
+#[test]
+fn test_global_fake_of_thunk_should_patch_its_target() {
+    {
+        let mut injector = InjectorPP::new_global();
+        unsafe {
+            injector
+                .when_called_unchecked(injectorpp::func_unchecked!(injectorpp_test_thunk_two))
+                .will_execute_raw_unchecked(injectorpp::func_unchecked!(fake_seven));
+        }
+
+        let original = std::thread::spawn(|| unsafe { injectorpp_test_thunked_two() })
+            .join()
+            .unwrap();
+        assert_eq!(original, 7);
+    }
+
+    unsafe {
+        assert_eq!(injectorpp_test_thunk_two(), 2);
+    }
+}
+
+#[test]
+fn test_is_patched_when_thunk_target_faked_globally_should_be_true_through_thunk() {
+    let thunk = || unsafe { injectorpp::func_unchecked!(injectorpp_test_thunk_three) };
+    assert!(!injectorpp::is_patched(thunk()));
+
+    let mut injector = InjectorPP::new_global();
+    unsafe {
+        injector
+            .when_called_unchecked(injectorpp::func_unchecked!(injectorpp_test_thunked_three))
+            .will_execute_raw_unchecked(injectorpp::func_unchecked!(fake_seven));
+    }
+
+    assert!(injectorpp::is_patched(thunk()));
+    let seen_by_other_thread = std::thread::spawn(move || injectorpp::is_patched(thunk()))
+        .join()
+        .unwrap();
+    assert!(seen_by_other_thread);
+
+    drop(injector);
+    assert!(!injectorpp::is_patched(thunk()));
+}
@@ -0,0 +1,143 @@
+#![cfg(feature = "tokio")]
+
+use injectorpp::interface::injector::*;
+use injectorpp::utilities::time::TokioTimeMocker;
+use std::time::Duration;
+
+async fn connect_with_backoff(attempts: u32) -> Result<(), String> {
+    let mut delay = Duration::from_millis(100);
+    for _ in 0..attempts {
+        tokio::time::sleep(delay).await;
+        delay *= 2;
+    }
+
+    Err("unreachable".to_string())
+}
+
+async fn wait_for_response() -> u32 {
+    std::future::pending().await
+}
+
+#[tokio::test]
+async fn test_tokio_time_mocker_should_fast_forward_sleeps() {
+    let time = TokioTimeMocker::new();
+    let started = std::time::Instant::now();
+
+    assert!(connect_with_backoff(4).await.is_err());
+
+    assert!(started.elapsed() < Duration::from_millis(100));
+    assert_eq!(
+        time.sleeps(),
+        vec![
+            Duration::from_millis(100),
+            Duration::from_millis(200),
+            Duration::from_millis(400),
+            Duration::from_millis(800),
+        ]
+    );
+    assert_eq!(time.elapsed(), Duration::from_millis(1500));
+}
+
+#[tokio::test]
+async fn test_tokio_time_mocker_should_fast_forward_sleep_until() {
+    let time = TokioTimeMocker::new();
+
+    tokio::time::sleep_until(tokio::time::Instant::now() + Duration::from_secs(3600)).await;
+
+    assert_eq!(time.elapsed(), Duration::from_secs(3600));
+}
+
+#[tokio::test]
+async fn test_tokio_time_mocker_should_elapse_timeouts() {
+    let time = TokioTimeMocker::new();
+
+    let result = tokio::time::timeout(Duration::from_secs(30), wait_for_response()).await;
+
+    assert!(result.is_err());
+    assert_eq!(time.elapsed(), Duration::from_secs(30));
+}
+
+#[tokio::test]
+async fn test_tokio_time_mocker_when_dropped_should_restore_sleep() {
+    {
+        let _time = TokioTimeMocker::new();
+        tokio::time::sleep(Duration::from_secs(60)).await;
+    }
+
+    let result = tokio::time::timeout(Duration::from_millis(20), async {
+        tokio::time::sleep(Duration::from_secs(60)).await;
+    })
+    .await;
+
+    assert!(result.is_err());
+}
+
+#[inline(never)]
+fn read_config(path: &str) -> String {
+    std::hint::black_box(path.to_string())
+}
+
+async fn fetch_quote() -> u32 {
+    0
+}
+
+#[tokio::test]
+async fn test_tokio_time_mocker_should_fast_forward_fake_delay() {
+    let time = TokioTimeMocker::new();
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(fn (read_config)(&str) -> String))
+        .will_execute(injectorpp::fake!(
+            func_type: fn(_path: &str) -> String,
+            returns: "{}".to_string(),
+            delay: Duration::from_secs(30)
+        ));
+    let started = std::time::Instant::now();
+
+    assert_eq!(read_config("app.json"), "{}");
+
+    assert!(started.elapsed() < Duration::from_secs(1));
+    assert_eq!(time.sleeps(), vec![Duration::from_secs(30)]);
+}
+
+#[tokio::test]
+async fn test_tokio_time_mocker_should_elapse_timeout_shorter_than_fake_delay() {
+    let time = TokioTimeMocker::new();
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called_async(injectorpp::async_func!(fetch_quote(), u32))
+        .will_return_async(injectorpp::async_return!(
+            42,
+            u32,
+            delay: Duration::from_secs(5)
+        ));
+
+    let result = tokio::time::timeout(Duration::from_secs(2), fetch_quote()).await;
+
+    assert!(result.is_err());
+    assert_eq!(time.elapsed(), Duration::from_secs(2));
+
+    // Awaited again, the fake waits for its whole delay.
+    assert_eq!(fetch_quote().await, 42);
+    assert_eq!(time.elapsed(), Duration::from_secs(7));
+}
+
+#[tokio::test]
+async fn test_tokio_time_mocker_should_return_fake_delayed_less_than_timeout() {
+    let time = TokioTimeMocker::new();
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called_async(injectorpp::async_func!(fetch_quote(), u32))
+        .will_return_async(injectorpp::async_return!(
+            42,
+            u32,
+            delay: Duration::from_millis(50)
+        ));
+    let started = std::time::Instant::now();
+
+    let result = tokio::time::timeout(Duration::from_secs(30), fetch_quote()).await;
+
+    assert_eq!(result, Ok(42));
+    assert!(started.elapsed() < Duration::from_secs(1));
+    assert_eq!(time.elapsed(), Duration::from_millis(50));
+}
@@ -0,0 +1,26 @@
+#![cfg(feature = "tokio")]
+
+// A global mocker fast-forwards the sleeps of every thread, so it is kept away from the other
+// tokio time tests in a binary of its own.
+
+use injectorpp::utilities::time::TokioTimeMocker;
+use std::time::Duration;
+
+#[test]
+fn test_tokio_time_mocker_global_should_fast_forward_multi_thread_runtime() {
+    let time = TokioTimeMocker::new_global();
+
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(2)
+        .enable_time()
+        .build()
+        .unwrap();
+
+    runtime.block_on(async {
+        tokio::spawn(tokio::time::sleep(Duration::from_secs(5)))
+            .await
+            .unwrap();
+    });
+
+    assert_eq!(time.sleeps(), vec![Duration::from_secs(5)]);
+}
@@ -0,0 +1,81 @@
+#![cfg(feature = "tracing")]
+
+use injectorpp::interface::injector::*;
+use std::sync::{Arc, Mutex};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Metadata, Subscriber};
+
+#[derive(Clone, Default)]
+struct CollectingSubscriber {
+    events: Arc<Mutex<Vec<String>>>,
+}
+
+struct EventVisitor(String);
+
+impl Visit for EventVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if !self.0.is_empty() {
+            self.0.push(' ');
+        }
+        self.0.push_str(&format!("{}={:?}", field.name(), value));
+    }
+}
+
+impl Subscriber for CollectingSubscriber {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.target() == "injectorpp"
+    }
+
+    fn new_span(&self, _span: &Attributes<'_>) -> Id {
+        Id::from_u64(1)
+    }
+
+    fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut visitor = EventVisitor(String::new());
+        event.record(&mut visitor);
+        self.events.lock().unwrap().push(visitor.0);
+    }
+
+    fn enter(&self, _span: &Id) {}
+
+    fn exit(&self, _span: &Id) {}
+}
+
+#[inline(never)]
+fn traced_target(x: i32) -> i32 {
+    x + 1
+}
+
+#[test]
+fn test_tracing_should_report_patch_lifecycle_and_fake_calls() {
+    let subscriber = CollectingSubscriber::default();
+    let events = subscriber.events.clone();
+
+    tracing::subscriber::with_default(subscriber, || {
+        let mut injector = InjectorPP::new();
+        injector
+            .when_called(injectorpp::func!(fn (traced_target)(i32) -> i32))
+            .will_execute(injectorpp::fake!(
+                func_type: fn(_x: i32) -> i32,
+                returns: 42
+            ));
+
+        assert_eq!(traced_target(1), 42);
+        assert_eq!(traced_target(2), 42);
+    });
+
+    let events = events.lock().unwrap();
+    assert!(events.iter().any(|e| e.contains("patch installed")));
+    assert!(events
+        .iter()
+        .any(|e| e.contains("fake invoked") && e.contains("call_index=0")));
+    assert!(events
+        .iter()
+        .any(|e| e.contains("fake invoked") && e.contains("call_index=1")));
+    assert!(events.iter().any(|e| e.contains("patch restored")));
+}
@@ -572,3 +572,233 @@ fn test_will_execute_fake_unsafe_unit_assign_and_times_over_called_should_panic(
     });
     assert!(result.is_err());
 }
+
+struct NoDebug;
+
+fn when_report_target(path: &str, size: usize, _extra: &NoDebug) -> bool {
+    !path.is_empty() && size > 0
+}
+
+#[test]
+fn test_will_execute_when_condition_mismatch_should_report_arguments() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(fn (when_report_target)(&str, usize, &NoDebug) -> bool))
+        .will_execute(injectorpp::fake!(
+            func_type: fn(path: &str, size: usize, extra: &NoDebug) -> bool,
+            when: path == "/tmp/expected" && size > 10,
+            returns: true
+        ));
+
+    let result = std::panic::catch_unwind(|| when_report_target("/tmp/actual", 42, &NoDebug));
+
+    let message = result.unwrap_err();
+    let message_str = message
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| message.downcast_ref::<String>().map(|s| s.as_str()))
+        .unwrap();
+
+    assert!(message_str.contains("called with unexpected arguments"));
+    assert!(message_str.contains("when: path == \"/tmp/expected\" && size > 10"));
+    assert!(
+        message_str.contains("path: expected \"/tmp/expected\", got \"/tmp/actual\" (mismatch)")
+    );
+    assert!(message_str.contains("size > 10: not evaluated\n"));
+    assert!(message_str.contains("extra: got <"));
+    assert!(message_str.contains("does not implement Debug>"));
+}
+
+#[inline(never)]
+fn when_moves_target(name: String, tag: String) -> usize {
+    std::hint::black_box(name.len() + tag.len())
+}
+
+#[test]
+fn test_will_execute_when_condition_moves_argument_should_evaluate_it_once() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static EXPECTED_EVALUATED: AtomicUsize = AtomicUsize::new(0);
+
+    fn expected_bytes() -> Vec<u8> {
+        EXPECTED_EVALUATED.fetch_add(1, Ordering::SeqCst);
+        b"x".to_vec()
+    }
+
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(fn (when_moves_target)(String, String) -> usize))
+        .will_execute(injectorpp::fake!(
+            func_type: fn(name: String, tag: String) -> usize,
+            when: name.into_bytes() == expected_bytes() && tag.into_bytes().is_empty(),
+            returns: 7
+        ));
+
+    assert_eq!(when_moves_target("x".to_string(), String::new()), 7);
+    assert_eq!(EXPECTED_EVALUATED.load(Ordering::SeqCst), 1);
+
+    let result = std::panic::catch_unwind(|| when_moves_target("y".to_string(), String::new()));
+
+    let message = result.unwrap_err();
+    let message_str = message.downcast_ref::<String>().unwrap();
+    assert!(message_str.contains("name.into_bytes(): expected [120], got [121] (mismatch)"));
+    assert!(message_str.contains("tag: got \"\""));
+    assert_eq!(EXPECTED_EVALUATED.load(Ordering::SeqCst), 2);
+}
+
+#[inline(never)]
+fn when_guard_target(v: &[u8]) -> usize {
+    std::hint::black_box(v.len())
+}
+
+#[test]
+fn test_will_execute_when_or_guard_holds_should_not_evaluate_right_side() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(fn (when_guard_target)(&[u8]) -> usize))
+        .will_execute(injectorpp::fake!(
+            func_type: fn(v: &[u8]) -> usize,
+            when: v.is_empty() || v[0] == 1,
+            returns: 7,
+            times: 2
+        ));
+
+    assert_eq!(when_guard_target(&[]), 7);
+    assert_eq!(when_guard_target(&[1]), 7);
+}
+
+#[test]
+fn test_will_execute_when_and_guard_fails_should_not_evaluate_right_side() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(fn (when_guard_target)(&[u8]) -> usize))
+        .will_execute(injectorpp::fake!(
+            func_type: fn(v: &[u8]) -> usize,
+            when: !v.is_empty() && v[0] == 1,
+            returns: 7
+        ));
+
+    let result = std::panic::catch_unwind(|| when_guard_target(&[]));
+
+    let message = result.unwrap_err();
+    let message_str = message.downcast_ref::<String>().unwrap();
+    assert!(message_str.contains("called with unexpected arguments"));
+    assert!(message_str.contains("v[0] == 1: not evaluated"));
+    assert!(!message_str.contains("index out of bounds"));
+}
+
+#[inline(never)]
+fn is_reserved_name(name: &'static str) -> bool {
+    std::hint::black_box(name == "con")
+}
+
+#[test]
+fn test_will_execute_when_fake_has_same_argument_lifetimes_should_success() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(fn (is_reserved_name)(&'static str) -> bool))
+        .will_execute(injectorpp::fake!(
+            func_type: fn(name: &'static str) -> bool,
+            returns: name.starts_with('c')
+        ));
+
+    assert!(is_reserved_name("config"));
+}
+
+#[test]
+#[should_panic(expected = "Signature mismatch")]
+fn test_will_execute_when_fake_differs_only_in_argument_lifetimes_should_panic() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(fn (is_reserved_name)(&'static str) -> bool))
+        .will_execute(injectorpp::fake!(
+            func_type: fn(name: &str) -> bool,
+            returns: name.starts_with('c')
+        ));
+}
+
+#[inline(never)]
+fn intern(name: &str) -> &'static str {
+    std::hint::black_box(if name == "con" { "con" } else { "" })
+}
+
+#[test]
+#[should_panic(expected = "Signature mismatch")]
+fn test_will_execute_when_fake_returns_borrowed_argument_for_static_return_should_panic() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(fn (intern)(&str) -> &'static str))
+        .will_execute(injectorpp::fake!(
+            func_type: fn(name: &str) -> &str,
+            returns: name
+        ));
+}
+
+#[test]
+#[should_panic(expected = "got a different type with the same name")]
+fn test_will_execute_when_fake_returns_other_type_with_same_name_should_panic() {
+    // Both `Token` types are named after the test function, as they are declared in blocks.
+    let target = {
+        #[allow(dead_code)]
+        struct Token(u64);
+
+        #[inline(never)]
+        fn next_token(_input: &str) -> Token {
+            Token(0)
+        }
+
+        injectorpp::func!(fn (next_token)(&str) -> Token)
+    };
+
+    #[allow(dead_code)]
+    struct Token(u8);
+
+    let mut injector = InjectorPP::new();
+    injector.when_called(target).will_execute(injectorpp::fake!(
+        func_type: fn(_input: &str) -> Token,
+        returns: Token(1)
+    ));
+}
+
+#[test]
+fn test_will_execute_fake_with_delay_should_wait_before_returning() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(fn (single_reference_param_func)(&mut i32) -> bool))
+        .will_execute(injectorpp::fake!(
+            func_type: fn(a: &mut i32) -> bool,
+            assign: { *a = 6 },
+            returns: true,
+            delay: std::time::Duration::from_millis(30),
+            times: 1
+        ));
+
+    let mut value = 0;
+    let started = std::time::Instant::now();
+
+    assert!(single_reference_param_func(&mut value));
+    assert!(started.elapsed() >= std::time::Duration::from_millis(30));
+    assert_eq!(value, 6);
+}
+
+#[test]
+fn test_will_execute_fake_no_return_function_with_delay_should_wait() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(
+            fn (single_reference_param_no_return_func)(&mut i32) -> ()
+        ))
+        .will_execute(injectorpp::fake!(
+            func_type: fn(a: &mut i32) -> (),
+            assign: { *a = 6 },
+            delay: std::time::Duration::from_millis(30)
+        ));
+
+    let mut value = 0;
+    let started = std::time::Instant::now();
+
+    single_reference_param_no_return_func(&mut value);
+
+    assert!(started.elapsed() >= std::time::Duration::from_millis(30));
+    assert_eq!(value, 6);
+}
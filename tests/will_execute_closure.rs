@@ -0,0 +1,228 @@
+use injectorpp::interface::injector::*;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+#[inline(never)]
+fn get_response(path: &str) -> Option<String> {
+    std::hint::black_box(path);
+    None
+}
+
+#[inline(never)]
+fn record_event(id: u32, name: &str) {
+    std::hint::black_box((id, name));
+}
+
+#[inline(never)]
+fn add(a: i32, b: i32) -> i32 {
+    std::hint::black_box(a) + std::hint::black_box(b)
+}
+
+#[inline(never)]
+fn read_config() -> u32 {
+    std::hint::black_box(0)
+}
+
+#[inline(never)]
+fn read_limit() -> u32 {
+    std::hint::black_box(10)
+}
+
+fn install_value(injector: &mut InjectorPP, func: FuncPtr, value: u32) {
+    injector
+        .when_called(func)
+        .will_execute_closure(injectorpp::boxed_closure!(move || value, fn() -> u32));
+}
+
+fn install_counter(injector: &mut InjectorPP, counter: Arc<AtomicUsize>) {
+    injector
+        .when_called(injectorpp::func!(fn (read_config)() -> u32))
+        .will_execute_closure(injectorpp::boxed_closure!(
+            move || counter.fetch_add(1, Ordering::SeqCst) as u32,
+            fn() -> u32
+        ));
+}
+
+#[test]
+fn test_will_execute_closure_when_capturing_state_should_use_state() {
+    let mut responses = HashMap::new();
+    responses.insert("/a".to_string(), "alpha".to_string());
+    responses.insert("/b".to_string(), "beta".to_string());
+
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(fn (get_response)(&str) -> Option<String>))
+        .will_execute_closure(injectorpp::boxed_closure!(
+            move |path| responses.remove(path),
+            fn(&str) -> Option<String>
+        ));
+
+    assert_eq!(get_response("/a"), Some("alpha".to_string()));
+    assert_eq!(get_response("/a"), None);
+    assert_eq!(get_response("/b"), Some("beta".to_string()));
+}
+
+#[test]
+fn test_will_execute_closure_when_no_return_should_record_arguments() {
+    let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let recorder = events.clone();
+
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(fn (record_event)(u32, &str)))
+        .will_execute_closure(injectorpp::boxed_closure!(
+            move |id, name| recorder.lock().unwrap().push((id, name.to_string())),
+            fn(u32, &str)
+        ));
+
+    record_event(1, "start");
+    record_event(2, "stop");
+
+    assert_eq!(
+        *events.lock().unwrap(),
+        vec![(1, "start".to_string()), (2, "stop".to_string())]
+    );
+}
+
+#[test]
+fn test_will_execute_closure_when_injector_dropped_should_drop_closure() {
+    let offset = Arc::new(10);
+    let captured = offset.clone();
+
+    {
+        let mut injector = InjectorPP::new();
+        injector
+            .when_called(injectorpp::func!(fn (add)(i32, i32) -> i32))
+            .will_execute_closure(injectorpp::boxed_closure!(
+                move |a, b| a * b + *captured,
+                fn(i32, i32) -> i32
+            ));
+
+        assert_eq!(add(3, 4), 22);
+        assert_eq!(Arc::strong_count(&offset), 2);
+    }
+
+    assert_eq!(Arc::strong_count(&offset), 1);
+    assert_eq!(add(3, 4), 7);
+}
+
+#[test]
+fn test_will_execute_closure_when_global_should_be_visible_to_other_threads() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+
+    let mut injector = InjectorPP::new_global();
+    injector
+        .when_called(injectorpp::func!(fn (Path::exists)(&Path) -> bool))
+        .will_execute_closure(injectorpp::boxed_closure!(
+            move |_| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                true
+            },
+            fn(&Path) -> bool
+        ));
+
+    let result = std::thread::spawn(|| Path::new("/nonexistent").exists())
+        .join()
+        .unwrap();
+
+    assert!(result);
+    assert!(Path::new("/nonexistent").exists());
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn test_will_execute_closure_when_same_call_site_on_other_thread_should_be_isolated() {
+    let mut injector = InjectorPP::new();
+    install_counter(&mut injector, Arc::new(AtomicUsize::new(100)));
+
+    let other = std::thread::spawn(|| {
+        let mut injector = InjectorPP::new();
+        install_counter(&mut injector, Arc::new(AtomicUsize::new(200)));
+        (read_config(), read_config())
+    })
+    .join()
+    .unwrap();
+
+    assert_eq!(other, (200, 201));
+    assert_eq!(read_config(), 100);
+    assert_eq!(read_config(), 101);
+}
+
+#[test]
+fn test_will_execute_closure_when_helper_fakes_several_functions_should_call_each_closure() {
+    let mut injector = InjectorPP::new();
+    install_value(
+        &mut injector,
+        injectorpp::func!(fn (read_config)() -> u32),
+        1,
+    );
+    install_value(
+        &mut injector,
+        injectorpp::func!(fn (read_limit)() -> u32),
+        2,
+    );
+
+    assert_eq!(read_config(), 1);
+    assert_eq!(read_limit(), 2);
+
+    drop(injector);
+    assert_eq!(read_config(), 0);
+    assert_eq!(read_limit(), 10);
+}
+
+#[test]
+fn test_will_execute_closure_when_same_call_site_installed_twice_should_call_latest() {
+    let first = Arc::new(AtomicUsize::new(0));
+    let second = Arc::new(AtomicUsize::new(100));
+
+    let mut injector = InjectorPP::new();
+    install_counter(&mut injector, first.clone());
+    let mut stacked = InjectorPP::new();
+    install_counter(&mut stacked, second.clone());
+    assert_eq!(read_config(), 100);
+
+    drop(stacked);
+    assert_eq!(read_config(), 0);
+    assert_eq!(first.load(Ordering::SeqCst), 1);
+    assert_eq!(second.load(Ordering::SeqCst), 101);
+}
+
+#[test]
+#[should_panic(
+    expected = "8 closures created by the same boxed_closure! call site are already installed"
+)]
+fn test_will_execute_closure_when_call_site_slots_exhausted_should_panic() {
+    let _injectors: Vec<InjectorPP> = (0..9)
+        .map(|_| {
+            let mut injector = InjectorPP::new();
+            install_counter(&mut injector, Arc::new(AtomicUsize::new(0)));
+            injector
+        })
+        .collect();
+}
+
+#[test]
+fn test_will_execute_closure_when_reconfigured_should_reuse_call_site() {
+    let first = Arc::new(AtomicUsize::new(0));
+    let second = Arc::new(AtomicUsize::new(10));
+
+    let mut injector = InjectorPP::new();
+    install_counter(&mut injector, first.clone());
+    assert_eq!(read_config(), 0);
+
+    install_counter(&mut injector, second.clone());
+    assert_eq!(read_config(), 10);
+    assert_eq!(first.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+#[should_panic(expected = "Signature mismatch")]
+fn test_will_execute_closure_when_signature_mismatch_should_panic() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(fn (add)(i32, i32) -> i32))
+        .will_execute_closure(injectorpp::boxed_closure!(move |a| a, fn(i32) -> i32));
+}
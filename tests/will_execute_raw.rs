@@ -191,3 +191,21 @@ fn test_will_execute_raw_when_fake_generic_function_multiple_types_with_differen
     assert_eq!(CALL_COUNT_CONDITION_TWO_CLOSURE.load(Ordering::SeqCst), 1);
     assert_eq!(CALL_COUNT_CONDITION_THREE_CLOSURE.load(Ordering::SeqCst), 2);
 }
+
+#[inline(never)]
+fn is_known_host(host: &'static str) -> bool {
+    std::hint::black_box(host == "localhost")
+}
+
+fn accept_any_host(_host: &str) -> bool {
+    true
+}
+
+#[test]
+#[should_panic(expected = "Signature mismatch")]
+fn test_will_execute_raw_when_fake_differs_only_in_lifetimes_should_panic() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(fn (is_known_host)(&'static str) -> bool))
+        .will_execute_raw(injectorpp::func!(fn (accept_any_host)(&str) -> bool));
+}
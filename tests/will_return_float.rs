@@ -0,0 +1,115 @@
+use injectorpp::interface::injector::*;
+
+#[cfg(unix)]
+extern "C" {
+    fn cos(x: f64) -> f64;
+    fn cosf(x: f32) -> f32;
+}
+
+#[inline(never)]
+fn read_temperature(sensor: u8) -> f64 {
+    std::hint::black_box(sensor as f64 * 0.25)
+}
+
+#[inline(never)]
+fn read_humidity(sensor: u8) -> f32 {
+    std::hint::black_box(sensor as f32 * 0.5)
+}
+
+#[inline(never)]
+fn read_raw(sensor: u8) -> u64 {
+    std::hint::black_box(sensor as u64)
+}
+
+#[test]
+fn test_will_return_f64_should_return_value() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(fn (read_temperature)(u8) -> f64))
+        .will_return_f64(-40.125);
+
+    assert_eq!(read_temperature(1), -40.125);
+}
+
+#[test]
+fn test_will_return_f32_should_return_value() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(fn (read_humidity)(u8) -> f32))
+        .will_return_f32(87.5);
+
+    assert_eq!(read_humidity(1), 87.5);
+}
+
+#[test]
+fn test_will_return_f64_with_global_injector_should_return_value_on_other_threads() {
+    let mut injector = InjectorPP::new_global();
+    injector
+        .when_called(injectorpp::func!(fn (read_temperature)(u8) -> f64))
+        .will_return_f64(f64::MAX);
+
+    let value = std::thread::spawn(|| read_temperature(2)).join().unwrap();
+    assert_eq!(value, f64::MAX);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_will_return_float_for_c_math_functions() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(unsafe{} extern "C" fn (cos)(f64) -> f64))
+        .will_return_f64(0.5);
+    injector
+        .when_called(injectorpp::func!(unsafe{} extern "C" fn (cosf)(f32) -> f32))
+        .will_return_f32(-0.5);
+
+    unsafe {
+        assert_eq!(cos(std::hint::black_box(0.0)), 0.5);
+        assert_eq!(cosf(std::hint::black_box(0.0)), -0.5);
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_fake_for_c_math_function_should_receive_and_return_floats() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(unsafe{} extern "C" fn (cos)(f64) -> f64))
+        .will_execute(injectorpp::fake!(
+            func_type: unsafe extern "C" fn(x: f64) -> f64,
+            when: x > 1.0,
+            returns: x * 2.0,
+            times: 1
+        ));
+
+    unsafe {
+        assert_eq!(cos(std::hint::black_box(1.5)), 3.0);
+    }
+}
+
+#[test]
+#[should_panic(expected = "will_return_f64 requires a function returning f64")]
+fn test_will_return_f64_for_f32_function_should_panic() {
+    let mut injector = InjectorPP::new();
+    injector
+        .when_called(injectorpp::func!(fn (read_humidity)(u8) -> f32))
+        .will_return_f64(1.0);
+}
+
+#[cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec"
+))]
+#[test]
+#[should_panic(
+    expected = "Return ABI mismatch: the function returns 8 bytes in a floating-point register but the fake returns 8 bytes in registers"
+)]
+fn test_fake_returning_integer_for_float_function_should_panic() {
+    let mut injector = InjectorPP::new();
+    unsafe {
+        injector
+            .when_called(injectorpp::func!(fn (read_temperature)(u8) -> f64))
+            .will_execute_raw_unchecked(injectorpp::func!(fn (read_raw)(u8) -> u64));
+    }
+}